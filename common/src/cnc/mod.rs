@@ -716,6 +716,10 @@ mod tests {
                 r#"{"type":"action","payload":{"type":"install_os"}}"#,
                 PollAction::InstallOs,
             ),
+            (
+                r#"{"type":"action","payload":{"type":"chain_script"}}"#,
+                PollAction::ChainScript,
+            ),
         ];
 
         let mut server = mockito::Server::new_async().await;
@@ -14,6 +14,8 @@ pub enum PollAction {
     RebootDevice,
     InstallOs,
     Console,
+    /// A boot stage served as a chained iPXE script; the agent has nothing to do.
+    ChainScript,
 }
 
 /// Envelope wrapping a [`PollAction`] sent from rack-director to the agent daemon.
@@ -49,6 +49,10 @@ impl Ipv4Subnet {
         self.addr & self.netmask()
     }
 
+    pub fn broadcast(&self) -> Ipv4Addr {
+        self.network() | !self.netmask()
+    }
+
     pub fn ip_in_range(&self, ip: Ipv4Addr) -> bool {
         let netmask_bits = self.netmask().to_bits();
         (ip.to_bits() & netmask_bits) == self.network().to_bits()
@@ -112,6 +116,15 @@ mod tests {
         assert_eq!(subnet.network(), Ipv4Addr::new(192, 168, 0, 0));
     }
 
+    #[test]
+    fn ipv4subnet_broadcast() {
+        let subnet = Ipv4Subnet::new(Ipv4Addr::new(192, 168, 0, 15), 24);
+        assert_eq!(subnet.broadcast(), Ipv4Addr::new(192, 168, 0, 255));
+
+        let subnet = Ipv4Subnet::new(Ipv4Addr::new(10, 0, 0, 9), 29);
+        assert_eq!(subnet.broadcast(), Ipv4Addr::new(10, 0, 0, 15));
+    }
+
     #[test]
     fn ip_in_range() {
         let subnet = Ipv4Subnet::new(Ipv4Addr::new(192, 168, 0, 15), 24);
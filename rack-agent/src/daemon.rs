@@ -96,7 +96,7 @@ async fn dispatch_action(
                 LoopControl::SleepThenPoll
             }
         },
-        PollAction::RebootDevice | PollAction::InstallOs => {
+        PollAction::RebootDevice | PollAction::InstallOs | PollAction::ChainScript => {
             info!("Action {:?} requires reboot — exiting daemon", action);
             // EXIT WITHOUT calling action_success. This is intentional — do not
             // "fix" this by adding an action_success call here.
@@ -116,6 +116,9 @@ async fn dispatch_action(
             //   Calling action_success here would advance the plan before the OS is
             //   installed, causing the installer to never run.
             //
+            // ChainScript: same as RebootDevice, except on_boot() advances the plan on
+            //   the boot *after* the one that served the chained script.
+            //
             // Known limitation: if the machine crashes after the daemon exits but
             // before rebooting, the plan remains in 'running' state indefinitely with
             // no timeout. This requires manual intervention to reset the plan.
@@ -169,6 +172,16 @@ mod tests {
         assert!(matches!(control, LoopControl::Exit));
     }
 
+    /// Test that `dispatch_action` returns `Exit` for `ChainScript`.
+    #[tokio::test]
+    async fn test_dispatch_chain_script_exits() {
+        let server = mockito::Server::new_async().await;
+        let client = CncClient::new(&server.url());
+        let control = dispatch_action(&client, "", &PollAction::ChainScript, None).await;
+
+        assert!(matches!(control, LoopControl::Exit));
+    }
+
     /// Test that `dispatch_action` returns `SleepThenPoll` when `DiscoverHardware`
     /// fails (no SMBIOS tables available in the test environment).
     ///
@@ -125,6 +125,7 @@ export type DhcpNetwork = {
   lease_duration: number;
   relay_agent_address?: string;
   enable_autodiscovery: boolean;
+  enabled: boolean;
   created_at: string;
   updated_at: string;
 }
@@ -167,6 +168,7 @@ export type UpdateDhcpNetworkRequest = {
   lease_duration?: number;
   relay_agent_address?: string;
   enable_autodiscovery?: boolean;
+  enabled?: boolean;
 }
 
 export type CreateDhcpPoolRequest = {
@@ -0,0 +1,142 @@
+//! Fingerprints of the boot artifacts the director serves.
+//!
+//! A secure boot chain verifies what it fetched against a SHA-256 published by the
+//! director: `/cnc/agent-images/{file}.sha256` and `GET /api/images/{name}`. Images
+//! run to hundreds of megabytes, so each digest is computed once and reused until
+//! the file's size or modification time changes.
+
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use anyhow::Result;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+/// Bytes read at a time while hashing.
+const READ_CHUNK: usize = 64 * 1024;
+
+/// Size and SHA-256 of an artifact.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ArtifactDigest {
+    pub size: u64,
+    /// Lowercase hex.
+    pub sha256: String,
+}
+
+#[derive(Debug, Clone)]
+struct CachedDigest {
+    modified: SystemTime,
+    digest: ArtifactDigest,
+}
+
+/// Digests of artifacts already hashed, by path.
+#[derive(Debug, Default)]
+pub struct ArtifactHashes {
+    cache: Mutex<HashMap<PathBuf, CachedDigest>>,
+}
+
+impl ArtifactHashes {
+    /// The digest of the file at `path`, hashing it only if it is new or has
+    /// changed since it was last hashed.
+    pub async fn digest(&self, path: &Path) -> Result<ArtifactDigest> {
+        let metadata = tokio::fs::metadata(path).await?;
+        let modified = metadata.modified()?;
+        if let Some(cached) = self.cache.lock().unwrap().get(path)
+            && cached.modified == modified
+            && cached.digest.size == metadata.len()
+        {
+            return Ok(cached.digest.clone());
+        }
+
+        let owned = path.to_path_buf();
+        let digest = tokio::task::spawn_blocking(move || hash_file(&owned)).await??;
+        self.cache.lock().unwrap().insert(
+            path.to_path_buf(),
+            CachedDigest {
+                modified,
+                digest: digest.clone(),
+            },
+        );
+        Ok(digest)
+    }
+}
+
+fn hash_file(path: &Path) -> Result<ArtifactDigest> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0; READ_CHUNK];
+    let mut size = 0u64;
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+        size += n as u64;
+    }
+    Ok(ArtifactDigest {
+        size,
+        sha256: format!("{:x}", hasher.finalize()),
+    })
+}
+
+/// The `sha256sum`-style line published as a `.sha256` sidecar for `filename`.
+pub fn sha256sum_line(digest: &ArtifactDigest, filename: &str) -> String {
+    format!("{}  {}\n", digest.sha256, filename)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_digest_is_cached_until_the_file_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("vmlinuz");
+        std::fs::write(&path, b"abc").unwrap();
+
+        let hashes = ArtifactHashes::default();
+        let digest = hashes.digest(&path).await.unwrap();
+        assert_eq!(digest.size, 3);
+        assert_eq!(
+            digest.sha256,
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(hashes.digest(&path).await.unwrap(), digest);
+        assert_eq!(hashes.cache.lock().unwrap().len(), 1);
+
+        // A different size invalidates the entry even if the mtime didn't tick
+        std::fs::write(&path, b"").unwrap();
+        let digest = hashes.digest(&path).await.unwrap();
+        assert_eq!(digest.size, 0);
+        assert_eq!(
+            digest.sha256,
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(
+            sha256sum_line(&digest, "vmlinuz"),
+            format!("{}  vmlinuz\n", digest.sha256)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_digest_spans_read_chunks() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("initramfs.img");
+        let data: Vec<u8> = (0..READ_CHUNK * 2 + 1000)
+            .map(|i| (i % 251) as u8)
+            .collect();
+        std::fs::write(&path, &data).unwrap();
+
+        let digest = ArtifactHashes::default().digest(&path).await.unwrap();
+        assert_eq!(digest.size, data.len() as u64);
+        assert_eq!(
+            digest.sha256,
+            // sha256 of the same bytes, from Python's hashlib
+            "7c0266839dabf6180f9dedee790c97ad810cc4fc81963640eb046eedb53ba4f0"
+        );
+    }
+}
@@ -0,0 +1,8 @@
+//! Append-only audit log of state-changing API calls.
+//!
+//! Each entry records who made a change, what kind of change it was, what it was
+//! applied to and, where available, a JSON summary of the target before and after.
+
+mod store;
+
+pub use store::{AuditEntry, record};
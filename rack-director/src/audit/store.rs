@@ -0,0 +1,77 @@
+//! Database access for the audit log.
+
+use anyhow::Result;
+use chrono::Utc;
+
+use crate::database::{Connection, to_db_time};
+
+/// One audit log entry, prior to insertion.
+#[derive(Debug, Clone)]
+pub struct AuditEntry {
+    /// Who made the change (e.g. the client address).
+    pub actor: String,
+    /// Dotted action name, e.g. `"network.update"`.
+    pub action: String,
+    /// What the action applied to, e.g. `"network/3"` or `"device/{uuid}"`.
+    pub target: String,
+    /// JSON summary of the target before the change.
+    pub before: Option<serde_json::Value>,
+    /// JSON summary of the target after the change.
+    pub after: Option<serde_json::Value>,
+}
+
+/// Append `entry` to the audit log, timestamped now.
+pub async fn record(conn: &Connection, entry: &AuditEntry) -> Result<()> {
+    conn.execute(
+        "INSERT INTO audit_log (created_at, actor, action, target, before_summary, after_summary)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        (
+            to_db_time(Utc::now()),
+            entry.actor.clone(),
+            entry.action.clone(),
+            entry.target.clone(),
+            entry.before.as_ref().map(|v| v.to_string()),
+            entry.after.as_ref().map(|v| v.to_string()),
+        ),
+    )
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{database, test_connection_factory};
+
+    #[tokio::test]
+    async fn test_record_appends_row() {
+        let factory = test_connection_factory!();
+        let conn = database::run_migrations(&factory).await.unwrap();
+
+        record(
+            &conn,
+            &AuditEntry {
+                actor: "10.0.0.9".to_string(),
+                action: "device.rediscover".to_string(),
+                target: "device/abc".to_string(),
+                before: None,
+                after: Some(serde_json::json!({ "rediscover_pending": true })),
+            },
+        )
+        .await
+        .unwrap();
+
+        let (actor, action, before, after): (String, String, Option<String>, Option<String>) = conn
+            .query_one(
+                "SELECT actor, action, before_summary, after_summary FROM audit_log",
+                (),
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+            )
+            .await
+            .unwrap();
+        assert_eq!(actor, "10.0.0.9");
+        assert_eq!(action, "device.rediscover");
+        assert_eq!(before, None);
+        assert_eq!(after.as_deref(), Some(r#"{"rediscover_pending":true}"#));
+    }
+}
@@ -0,0 +1,376 @@
+//! TFTP handler that serves boot files from disk plus generated pxelinux configs.
+//!
+//! pxelinux (and iPXE builds of it) look for their config by trying, in order,
+//! `pxelinux.cfg/<uuid>`, `pxelinux.cfg/01-<mac>`, the client's IP in upper-case hex
+//! (`C0A80164`) with one digit dropped per attempt, and finally `pxelinux.cfg/default`.
+//! Every miss answers "file not found" and the client tries the next name.
+//!
+//! [`DirectorTftpHandler`] answers two of those names itself:
+//!
+//! - `pxelinux.cfg/<hex-ip>` when the full 8-digit IP is the requesting client's own
+//!   address and an active lease ties it to a known device: a config that hands off
+//!   to iPXE and chains straight to that device's `/cnc/ipxe` script.
+//! - `pxelinux.cfg/default`: the same hand-off, letting iPXE report its own UUID.
+//!
+//! Both configs boot `ipxe.lkrn`, which must sit in a boot file directory next to
+//! `pxelinux.0`. Files on disk always win, so an operator can still drop in a
+//! hand-written `pxelinux.cfg/default`; every other filename is served from disk
+//! as before.
+//!
+//! With a non-empty [`FilenameAllowlist`], names it doesn't admit are refused
+//! before either the disk or the database is consulted.
+
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+
+use anyhow::{Result, bail};
+use uuid::Uuid;
+
+use super::{FilenameAllowlist, FilesystemBootFileProvider};
+use crate::database::ConnectionFactory;
+use crate::dhcp::store;
+use crate::http::cnc::ipxe_scripts::{device_chain_url, uuid_chain_url};
+use crate::tftp::{Handler, Reader, TftpReader};
+
+const PXELINUX_CFG: &str = "pxelinux.cfg/";
+
+/// TFTP handler combining on-disk boot files with per-client pxelinux configs.
+pub struct DirectorTftpHandler {
+    files: Arc<FilesystemBootFileProvider>,
+    db: Arc<dyn ConnectionFactory>,
+    root_url: String,
+    allowlist: FilenameAllowlist,
+}
+
+impl DirectorTftpHandler {
+    /// Serve `files`, generating pxelinux configs that chain to `root_url`.
+    pub fn new(
+        files: Arc<FilesystemBootFileProvider>,
+        db: Arc<dyn ConnectionFactory>,
+        root_url: String,
+    ) -> Self {
+        Self {
+            files,
+            db,
+            root_url,
+            allowlist: FilenameAllowlist::default(),
+        }
+    }
+
+    /// Only resolve filenames admitted by `allowlist`.
+    pub fn with_allowlist(mut self, allowlist: FilenameAllowlist) -> Self {
+        self.allowlist = allowlist;
+        self
+    }
+
+    /// Fail unless `filename` is on the allowlist.
+    fn check_allowed(&self, client: SocketAddr, filename: &str) -> Result<()> {
+        if !self.allowlist.allows(filename) {
+            log::info!(
+                "TFTP: {} requested {:?}, not on the allowlist",
+                client,
+                filename
+            );
+            bail!("{} is not on the TFTP allowlist", filename);
+        }
+        Ok(())
+    }
+
+    /// The generated config for `pxelinux.cfg/<name>` as requested by `client`.
+    async fn pxelinux_config(&self, client: SocketAddr, name: &str) -> Result<String> {
+        if name == "default" {
+            return Ok(render_config(&uuid_chain_url(&self.root_url)));
+        }
+
+        let Some(ip) = parse_hex_ip(name) else {
+            bail!("no generated pxelinux config for {}", name);
+        };
+        if client.ip() != ip {
+            bail!(
+                "pxelinux.cfg/{} requested by {}, not its owner",
+                name,
+                client
+            );
+        }
+        let conn = self.db.open().await?;
+        let Some(lease) = store::get_active_lease_by_ip(&conn, &ip).await? else {
+            bail!("no active lease for {}", ip);
+        };
+        let Some(uuid) = lease.device_uuid else {
+            bail!("lease on {} is not tied to a device", ip);
+        };
+        Ok(device_config(&self.root_url, &uuid, &lease.mac_address))
+    }
+}
+
+/// Parse a pxelinux hex-IP config name. Only the full 8-digit form names one client;
+/// shorter prefixes are subnet fallbacks and are left to the filesystem.
+fn parse_hex_ip(name: &str) -> Option<Ipv4Addr> {
+    if name.len() != 8 || !name.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    u32::from_str_radix(name, 16).ok().map(Ipv4Addr::from)
+}
+
+/// pxelinux config for a known device, chaining to its iPXE script.
+fn device_config(root_url: &str, uuid: &Uuid, mac: &str) -> String {
+    render_config(&device_chain_url(root_url, uuid, mac))
+}
+
+/// pxelinux config that boots iPXE and has it chain to `chain_url`.
+fn render_config(chain_url: &str) -> String {
+    format!(
+        "DEFAULT rack-director\n\
+         LABEL rack-director\n  \
+         KERNEL ipxe.lkrn\n  \
+         APPEND dhcp && chain {chain_url}\n"
+    )
+}
+
+/// Reader for either an on-disk file or a generated config.
+pub enum DirectorTftpReader {
+    File(TftpReader),
+    Generated { data: Vec<u8>, block_size: usize },
+}
+
+impl Reader for DirectorTftpReader {
+    async fn read(&mut self) -> Result<Vec<u8>> {
+        match self {
+            DirectorTftpReader::File(reader) => reader.read().await,
+            DirectorTftpReader::Generated { data, block_size } => {
+                let n = (*block_size).min(data.len());
+                Ok(data.drain(..n).collect())
+            }
+        }
+    }
+}
+
+impl Handler for DirectorTftpHandler {
+    type Reader = DirectorTftpReader;
+
+    async fn create_reader(
+        &self,
+        client: SocketAddr,
+        filename: &str,
+        block_size: u64,
+    ) -> Result<Self::Reader> {
+        self.check_allowed(client, filename)?;
+        let err = match self.files.create_reader(client, filename, block_size).await {
+            Ok(reader) => return Ok(DirectorTftpReader::File(reader)),
+            Err(e) => e,
+        };
+        if let Some(name) = filename.strip_prefix(PXELINUX_CFG)
+            && let Ok(config) = self.pxelinux_config(client, name).await
+        {
+            return Ok(DirectorTftpReader::Generated {
+                data: config.into_bytes(),
+                block_size: block_size as usize,
+            });
+        }
+        Err(err)
+    }
+
+    /// Generated configs report no size, so their OACK carries no `tsize`.
+    async fn filesize(&self, client: SocketAddr, filename: &str) -> Result<Option<u64>> {
+        self.check_allowed(client, filename)?;
+        let err = match Handler::filesize(self.files.as_ref(), client, filename).await {
+            Ok(size) => return Ok(size),
+            Err(e) => e,
+        };
+        if let Some(name) = filename.strip_prefix(PXELINUX_CFG)
+            && self.pxelinux_config(client, name).await.is_ok()
+        {
+            return Ok(None);
+        }
+        Err(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::{self, DatabaseConnectionFactory};
+    use crate::director::{Architecture, Director};
+    use crate::test_connection_factory;
+
+    const ROOT_URL: &str = "http://10.0.0.1:3000";
+    const DEVICE: &str = "d4000000-0000-0000-0000-000000000001";
+
+    /// Handler with an empty boot directory and a device holding an active lease on
+    /// 10.0.0.100.
+    async fn setup(
+        factory: DatabaseConnectionFactory,
+    ) -> (DirectorTftpHandler, database::Connection, tempfile::TempDir) {
+        let conn = database::run_migrations(&factory).await.unwrap();
+        let uuid = Uuid::parse_str(DEVICE).unwrap();
+        Director::new(&conn)
+            .register_device(&uuid, Architecture::X86_64)
+            .await
+            .unwrap();
+        let network = store::create_network(
+            &conn,
+            "Test Network",
+            "10.0.0.0/24",
+            "10.0.0.1",
+            &[],
+            86400,
+            None,
+            false,
+        )
+        .await
+        .unwrap();
+        store::create_or_update_lease_with_network(
+            &conn,
+            "aa:bb:cc:dd:ee:ff",
+            &"10.0.0.100".parse().unwrap(),
+            Some(&uuid),
+            store::LeaseState::Active,
+            3600,
+            network.id,
+        )
+        .await
+        .unwrap();
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let files =
+            Arc::new(FilesystemBootFileProvider::new(temp_dir.path().to_path_buf()).unwrap());
+        let handler = DirectorTftpHandler::new(files, Arc::new(factory), ROOT_URL.to_string());
+        (handler, conn, temp_dir)
+    }
+
+    async fn read_all(handler: &DirectorTftpHandler, client: &str, filename: &str) -> String {
+        let mut reader = handler
+            .create_reader(client.parse().unwrap(), filename, 512)
+            .await
+            .unwrap();
+        let mut out = Vec::new();
+        loop {
+            let block = reader.read().await.unwrap();
+            out.extend_from_slice(&block);
+            if block.len() < 512 {
+                break;
+            }
+        }
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_parse_hex_ip() {
+        assert_eq!(parse_hex_ip("0A000064"), Some(Ipv4Addr::new(10, 0, 0, 100)));
+        assert_eq!(parse_hex_ip("0A00006"), None);
+        assert_eq!(parse_hex_ip("default"), None);
+    }
+
+    #[tokio::test]
+    async fn test_hex_ip_config_chains_to_leased_device() {
+        let (handler, _conn, _temp_dir) = setup(test_connection_factory!()).await;
+
+        let config = read_all(&handler, "10.0.0.100:2000", "pxelinux.cfg/0A000064").await;
+        assert!(config.contains("KERNEL ipxe.lkrn"));
+        assert!(config.contains(&format!(
+            "chain {}/cnc/ipxe?uuid={}&mac=aa:bb:cc:dd:ee:ff",
+            ROOT_URL, DEVICE
+        )));
+        // Generated, so no size is promised up front
+        assert_eq!(
+            handler
+                .filesize("10.0.0.100:2000".parse().unwrap(), "pxelinux.cfg/0A000064")
+                .await
+                .unwrap(),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_hex_ip_config_only_served_to_its_owner() {
+        let (handler, _conn, _temp_dir) = setup(test_connection_factory!()).await;
+
+        // Another client asking for 10.0.0.100's config falls through to the
+        // filesystem, which has no such file
+        let result = handler
+            .create_reader(
+                "10.0.0.101:2000".parse().unwrap(),
+                "pxelinux.cfg/0A000064",
+                512,
+            )
+            .await;
+        assert!(result.is_err());
+
+        // So does a client with no leased device
+        let result = handler
+            .create_reader(
+                "10.0.0.101:2000".parse().unwrap(),
+                "pxelinux.cfg/0A000065",
+                512,
+            )
+            .await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_default_config_lets_ipxe_report_uuid() {
+        let (handler, _conn, _temp_dir) = setup(test_connection_factory!()).await;
+
+        let config = read_all(&handler, "10.0.0.150:2000", "pxelinux.cfg/default").await;
+        assert!(config.contains(&format!(
+            "chain {}/cnc/ipxe?uuid=${{uuid}}&mac=${{netX/mac}}",
+            ROOT_URL
+        )));
+    }
+
+    #[tokio::test]
+    async fn test_files_on_disk_win() {
+        let (handler, _conn, temp_dir) = setup(test_connection_factory!()).await;
+        std::fs::write(temp_dir.path().join("pxelinux.0"), b"PXELINUX").unwrap();
+        std::fs::create_dir(temp_dir.path().join("pxelinux.cfg")).unwrap();
+        std::fs::write(temp_dir.path().join("pxelinux.cfg/default"), b"CUSTOM").unwrap();
+
+        assert_eq!(
+            read_all(&handler, "10.0.0.100:2000", "pxelinux.0").await,
+            "PXELINUX"
+        );
+        assert_eq!(
+            read_all(&handler, "10.0.0.100:2000", "pxelinux.cfg/default").await,
+            "CUSTOM"
+        );
+        // Disk files keep reporting their real size for tsize
+        assert_eq!(
+            handler
+                .filesize("10.0.0.100:2000".parse().unwrap(), "pxelinux.cfg/default")
+                .await
+                .unwrap(),
+            Some(6)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_allowlist_refuses_other_names() {
+        let (handler, _conn, temp_dir) = setup(test_connection_factory!()).await;
+        let handler = handler.with_allowlist(FilenameAllowlist::new(vec![
+            "*.efi".parse().unwrap(),
+            "pxelinux.cfg/*".parse().unwrap(),
+        ]));
+        std::fs::write(temp_dir.path().join("snponly.efi"), b"EFI").unwrap();
+        std::fs::write(temp_dir.path().join("notes.txt"), b"secret").unwrap();
+        let client: SocketAddr = "10.0.0.100:2000".parse().unwrap();
+
+        assert_eq!(
+            read_all(&handler, "10.0.0.100:2000", "snponly.efi").await,
+            "EFI"
+        );
+        assert!(
+            read_all(&handler, "10.0.0.100:2000", "pxelinux.cfg/0A000064")
+                .await
+                .contains("KERNEL ipxe.lkrn")
+        );
+
+        // Present on disk, but not allowlisted
+        assert!(
+            handler
+                .create_reader(client, "notes.txt", 512)
+                .await
+                .is_err()
+        );
+        assert!(handler.filesize(client, "notes.txt").await.is_err());
+    }
+}
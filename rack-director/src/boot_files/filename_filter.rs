@@ -0,0 +1,139 @@
+//! Allowlisting of TFTP filenames.
+//!
+//! Path validation already keeps requests inside the boot file directories, but
+//! every request still costs a filesystem lookup and, for `pxelinux.cfg/` names,
+//! possibly a database query. With `--tftp-allow`, only names matching one of the
+//! given globs are looked up at all; anything else is answered "file not found"
+//! straight away.
+//!
+//! Patterns are matched against the whole filename as the client sent it. `*`
+//! matches any run of characters and `?` any single character, neither crossing a
+//! `/`, so `pxelinux.cfg/01-*` admits per-MAC configs but not deeper paths.
+
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::{Result, bail};
+
+/// A filename glob, e.g. `*.efi` or `pxelinux.cfg/*`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilenamePattern(String);
+
+impl FilenamePattern {
+    /// Whether `filename` matches this pattern in full.
+    pub fn matches(&self, filename: &str) -> bool {
+        glob_match(self.0.as_bytes(), filename.as_bytes())
+    }
+}
+
+impl fmt::Display for FilenamePattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl FromStr for FilenamePattern {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if s.is_empty() {
+            bail!("Invalid TFTP filename pattern: empty");
+        }
+        if s.split('/').any(|segment| segment == "..") {
+            bail!("Invalid TFTP filename pattern '{}': contains '..'", s);
+        }
+        Ok(Self(s.to_string()))
+    }
+}
+
+/// Match `name` against `pattern`, where `*` and `?` never match `/`.
+fn glob_match(pattern: &[u8], name: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some((b'*', rest)) => {
+            // Try every split point up to the next `/`
+            let segment_len = name.iter().position(|&c| c == b'/').unwrap_or(name.len());
+            (0..=segment_len).any(|n| glob_match(rest, &name[n..]))
+        }
+        Some((b'?', rest)) => {
+            matches!(name.first(), Some(&c) if c != b'/') && glob_match(rest, &name[1..])
+        }
+        Some((&p, rest)) => name.first() == Some(&p) && glob_match(rest, &name[1..]),
+    }
+}
+
+/// Which filenames the TFTP handler will try to resolve.
+///
+/// An empty allowlist admits every name. A non-empty one admits only names
+/// matching one of its patterns, and never a name with a `..` component.
+#[derive(Debug, Clone, Default)]
+pub struct FilenameAllowlist {
+    patterns: Vec<FilenamePattern>,
+}
+
+impl FilenameAllowlist {
+    pub fn new(patterns: Vec<FilenamePattern>) -> Self {
+        Self { patterns }
+    }
+
+    /// Whether a request for `filename` may be resolved.
+    pub fn allows(&self, filename: &str) -> bool {
+        if self.patterns.is_empty() {
+            return true;
+        }
+        !filename.split('/').any(|segment| segment == "..")
+            && self.patterns.iter().any(|p| p.matches(filename))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn allowlist(patterns: &[&str]) -> FilenameAllowlist {
+        FilenameAllowlist::new(patterns.iter().map(|p| p.parse().unwrap()).collect())
+    }
+
+    #[test]
+    fn test_pattern_wildcards_stay_within_a_segment() {
+        let pattern: FilenamePattern = "pxelinux.cfg/01-*".parse().unwrap();
+        assert!(pattern.matches("pxelinux.cfg/01-aa-bb-cc-dd-ee-ff"));
+        assert!(pattern.matches("pxelinux.cfg/01-"));
+        assert!(!pattern.matches("pxelinux.cfg/01-aa/extra"));
+        assert!(!pattern.matches("pxelinux.cfg/default"));
+
+        let pattern: FilenamePattern = "ipxe-?.efi".parse().unwrap();
+        assert!(pattern.matches("ipxe-x.efi"));
+        assert!(!pattern.matches("ipxe-.efi"));
+        assert!(!pattern.matches("ipxe-/.efi"));
+
+        let pattern: FilenamePattern = "*.efi".parse().unwrap();
+        assert!(pattern.matches("snponly.efi"));
+        assert!(!pattern.matches("efi/snponly.efi"));
+    }
+
+    #[test]
+    fn test_pattern_rejects_invalid() {
+        assert!("".parse::<FilenamePattern>().is_err());
+        assert!("../*".parse::<FilenamePattern>().is_err());
+        assert!("pxelinux.cfg/../*".parse::<FilenamePattern>().is_err());
+    }
+
+    #[test]
+    fn test_default_allowlist_allows_everything() {
+        let allowlist = FilenameAllowlist::default();
+        assert!(allowlist.allows("anything/at/all.bin"));
+    }
+
+    #[test]
+    fn test_allowlist_admits_only_matching_names() {
+        let allowlist = allowlist(&["*.efi", "pxelinux.0", "pxelinux.cfg/*"]);
+        assert!(allowlist.allows("snponly.efi"));
+        assert!(allowlist.allows("pxelinux.0"));
+        assert!(allowlist.allows("pxelinux.cfg/default"));
+        assert!(allowlist.allows("pxelinux.cfg/0A000064"));
+        assert!(!allowlist.allows("etc/passwd"));
+        assert!(!allowlist.allows("pxelinux.cfg/.."));
+        assert!(!allowlist.allows("pxelinux.cfg/../secret.efi"));
+    }
+}
@@ -1,45 +1,41 @@
 use super::BootFileProvider;
 use anyhow::{Context, Result};
 use async_trait::async_trait;
+use std::net::SocketAddr;
 use std::path::PathBuf;
 use tokio::fs;
 use tokio::io::BufReader;
 
-use crate::tftp::{Handler, TftpReader};
+use crate::tftp::{DEFAULT_READ_CHUNK, Handler, TftpReader};
 
 /// Filesystem-based boot file provider with path canonicalization security.
 ///
-/// This provider serves boot files (such as iPXE binaries) from a local filesystem
-/// directory, enforcing path validation to prevent unauthorized file access.
+/// This provider serves boot files (such as iPXE binaries) from one or more local
+/// filesystem directories, enforcing path validation to prevent unauthorized file
+/// access. Roots are searched in order and the first one containing the requested
+/// file wins, so loaders, configs and images can live in separate directories.
 ///
 /// # Security
 ///
 /// Path validation is performed using canonicalization to prevent directory traversal
-/// attacks. The canonicalized requested path must be within the canonicalized base path.
-/// Any attempt to access files outside the base directory will be rejected.
+/// attacks. The canonicalized requested path must be within the canonicalized root it
+/// was resolved against. Any attempt to access files outside that root will be rejected.
 #[derive(Debug)]
 pub struct FilesystemBootFileProvider {
+    roots: Vec<BootRoot>,
+    /// Bytes read from disk at a time for TFTP transfers.
+    read_chunk: u64,
+}
+
+/// A single search root and its canonical form.
+#[derive(Debug)]
+struct BootRoot {
     base_path: PathBuf,
     canonical_base_path: PathBuf,
 }
 
-impl FilesystemBootFileProvider {
-    /// Create a new filesystem boot file provider.
-    ///
-    /// # Arguments
-    ///
-    /// * `base_path` - The root directory containing boot files
-    ///
-    /// # Returns
-    ///
-    /// Returns a new provider instance if the base path exists and can be canonicalized.
-    ///
-    /// # Errors
-    ///
-    /// Returns an error if:
-    /// - The base path does not exist or is not a directory
-    /// - The base path cannot be canonicalized
-    pub fn new(base_path: PathBuf) -> Result<Self> {
+impl BootRoot {
+    fn new(base_path: PathBuf) -> Result<Self> {
         // Verify base path exists and is a directory
         if !base_path.exists() {
             anyhow::bail!(
@@ -65,18 +61,71 @@ impl FilesystemBootFileProvider {
             canonical_base_path,
         })
     }
+}
+
+impl FilesystemBootFileProvider {
+    /// Create a new filesystem boot file provider serving a single directory.
+    ///
+    /// # Arguments
+    ///
+    /// * `base_path` - The root directory containing boot files
+    ///
+    /// # Returns
+    ///
+    /// Returns a new provider instance if the base path exists and can be canonicalized.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if:
+    /// - The base path does not exist or is not a directory
+    /// - The base path cannot be canonicalized
+    pub fn new(base_path: PathBuf) -> Result<Self> {
+        Self::with_roots(vec![base_path])
+    }
+
+    /// Create a provider that searches several root directories in order.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `roots` is empty, or if any root fails the same checks
+    /// as [`FilesystemBootFileProvider::new`].
+    pub fn with_roots(roots: Vec<PathBuf>) -> Result<Self> {
+        if roots.is_empty() {
+            anyhow::bail!("At least one boot files directory is required");
+        }
+
+        let roots = roots
+            .into_iter()
+            .map(BootRoot::new)
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            roots,
+            read_chunk: DEFAULT_READ_CHUNK,
+        })
+    }
+
+    /// Read about `bytes` from disk at a time for TFTP transfers instead of
+    /// [`DEFAULT_READ_CHUNK`], rounded down to whole blocks of the transfer's size.
+    pub fn with_read_chunk(mut self, bytes: u64) -> Self {
+        self.read_chunk = bytes;
+        self
+    }
 
     /// Validate and resolve a filename to a full filesystem path.
     ///
     /// This is a security-critical function that prevents directory traversal attacks
-    /// by ensuring the resolved path is within the base directory.
+    /// by ensuring the resolved path is within the root it was found in.
     ///
     /// # Security
     ///
-    /// The validation process:
-    /// 1. Join the filename to the base path
-    /// 2. Canonicalize the result
-    /// 3. Check if the canonicalized path starts with the canonicalized base path
+    /// For each root, in order:
+    /// 1. Join the filename to the root
+    /// 2. Canonicalize the result; if the file does not exist, try the next root
+    /// 3. Check if the canonicalized path starts with the canonicalized root
+    ///
+    /// A path that exists but escapes its root is rejected outright rather than
+    /// falling through to later roots.
     ///
     /// # Arguments
     ///
@@ -84,31 +133,39 @@ impl FilesystemBootFileProvider {
     ///
     /// # Returns
     ///
-    /// Returns the validated canonical path if the file is within the base directory.
+    /// Returns the validated canonical path from the first root containing the file.
     ///
     /// # Errors
     ///
     /// Returns an error if:
-    /// - The path cannot be canonicalized (file doesn't exist)
-    /// - The resolved path is outside the base directory (directory traversal attempt)
+    /// - The file does not exist in any root
+    /// - The resolved path is outside its root directory (directory traversal attempt)
     fn validate_and_resolve_path(&self, filename: &str) -> Result<PathBuf> {
-        let requested_path = self.base_path.join(filename);
-
-        // Canonicalize the requested path
-        let canonical_path = requested_path.canonicalize().context(format!(
-            "Failed to access boot file: {}",
-            requested_path.display()
-        ))?;
-
-        // Security check: ensure the canonical path is within the base directory
-        if !canonical_path.starts_with(&self.canonical_base_path) {
-            anyhow::bail!(
-                "Access denied: path '{}' is outside boot files directory",
-                filename
-            );
+        for root in &self.roots {
+            let requested_path = root.base_path.join(filename);
+
+            // Canonicalize the requested path; missing here means try the next root
+            let Ok(canonical_path) = requested_path.canonicalize() else {
+                continue;
+            };
+
+            // Security check: ensure the canonical path is within this root
+            if !canonical_path.starts_with(&root.canonical_base_path) {
+                anyhow::bail!(
+                    "Access denied: path '{}' is outside boot files directory",
+                    filename
+                );
+            }
+
+            return Ok(canonical_path);
         }
 
-        Ok(canonical_path)
+        anyhow::bail!(
+            "Failed to access boot file: {} (searched {} director{})",
+            filename,
+            self.roots.len(),
+            if self.roots.len() == 1 { "y" } else { "ies" }
+        )
     }
 }
 
@@ -142,17 +199,23 @@ impl BootFileProvider for FilesystemBootFileProvider {
 impl Handler for FilesystemBootFileProvider {
     type Reader = TftpReader;
 
-    async fn create_reader(&self, filename: &str, block_size: u64) -> Result<Self::Reader> {
+    async fn create_reader(
+        &self,
+        _client: SocketAddr,
+        filename: &str,
+        block_size: u64,
+    ) -> Result<Self::Reader> {
         // Security: Validate path and resolve to canonical path
         let file_path = self.validate_and_resolve_path(filename)?;
 
-        let reader = TftpReader::open(&file_path, block_size).await?;
+        let reader =
+            TftpReader::open_with_read_chunk(&file_path, block_size, self.read_chunk).await?;
         Ok(reader)
     }
 
-    async fn filesize(&self, filename: &str) -> Result<u64> {
+    async fn filesize(&self, _client: SocketAddr, filename: &str) -> Result<Option<u64>> {
         // Delegate to BootFileProvider implementation
-        BootFileProvider::filesize(self, filename).await
+        BootFileProvider::filesize(self, filename).await.map(Some)
     }
 }
 
@@ -385,4 +448,95 @@ mod tests {
         std::fs::remove_file(&outside_file).ok();
         std::fs::remove_file(&symlink_path).ok();
     }
+
+    #[test]
+    fn test_with_roots_empty_is_error() {
+        let result = FilesystemBootFileProvider::with_roots(vec![]);
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_with_roots_nonexistent_root_is_error() {
+        let temp_dir = TempDir::new().unwrap();
+
+        let result = FilesystemBootFileProvider::with_roots(vec![
+            temp_dir.path().to_path_buf(),
+            PathBuf::from("/nonexistent/path/12345"),
+        ]);
+
+        assert!(result.is_err());
+        assert!(result.unwrap_err().to_string().contains("does not exist"));
+    }
+
+    /// Two roots: `loaders/` holds `snponly.efi`, `configs/` holds `boot.cfg`
+    /// plus its own `snponly.efi` that should be shadowed by the first root.
+    fn create_multi_root_provider() -> (FilesystemBootFileProvider, TempDir) {
+        let temp_dir = TempDir::new().unwrap();
+        let loaders = temp_dir.path().join("loaders");
+        let configs = temp_dir.path().join("configs");
+        std::fs::create_dir(&loaders).unwrap();
+        std::fs::create_dir(&configs).unwrap();
+        std::fs::write(loaders.join("snponly.efi"), b"FIRST_ROOT").unwrap();
+        std::fs::write(configs.join("snponly.efi"), b"SECOND_ROOT").unwrap();
+        std::fs::write(configs.join("boot.cfg"), b"CONFIG").unwrap();
+
+        let provider = FilesystemBootFileProvider::with_roots(vec![loaders, configs]).unwrap();
+        (provider, temp_dir)
+    }
+
+    #[tokio::test]
+    async fn test_multi_root_file_in_second_root() {
+        let (provider, _temp_dir) = create_multi_root_provider();
+
+        let mut reader = provider.get_file("boot.cfg").await.unwrap();
+        let mut contents = Vec::new();
+        reader.read_to_end(&mut contents).await.unwrap();
+        assert_eq!(contents, b"CONFIG");
+        assert_eq!(
+            BootFileProvider::filesize(&provider, "boot.cfg")
+                .await
+                .unwrap(),
+            6
+        );
+    }
+
+    #[tokio::test]
+    async fn test_multi_root_first_root_wins() {
+        let (provider, _temp_dir) = create_multi_root_provider();
+
+        let mut reader = provider.get_file("snponly.efi").await.unwrap();
+        let mut contents = Vec::new();
+        reader.read_to_end(&mut contents).await.unwrap();
+        assert_eq!(contents, b"FIRST_ROOT");
+    }
+
+    #[tokio::test]
+    async fn test_multi_root_file_in_no_root() {
+        let (provider, _temp_dir) = create_multi_root_provider();
+
+        let result = provider.get_file("missing.efi").await;
+
+        assert!(result.is_err());
+        let error_msg = result.unwrap_err().to_string();
+        assert!(error_msg.contains("Failed to access boot file"));
+        assert!(error_msg.contains("searched 2 directories"));
+    }
+
+    #[tokio::test]
+    async fn test_multi_root_traversal_blocked_per_root() {
+        let (provider, _temp_dir) = create_multi_root_provider();
+
+        // Resolved against loaders/, this names a real file in the sibling root.
+        // It must be rejected, not served because configs/ is also a root.
+        let result = provider.get_file("../configs/boot.cfg").await;
+
+        assert!(result.is_err());
+        assert!(
+            result
+                .unwrap_err()
+                .to_string()
+                .contains("outside boot files directory")
+        );
+    }
 }
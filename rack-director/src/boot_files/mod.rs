@@ -1,5 +1,9 @@
+mod director_tftp;
+mod filename_filter;
 mod filesystem;
 
+pub use director_tftp::DirectorTftpHandler;
+pub use filename_filter::{FilenameAllowlist, FilenamePattern};
 pub use filesystem::FilesystemBootFileProvider;
 
 use anyhow::Result;
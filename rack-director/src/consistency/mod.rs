@@ -0,0 +1,620 @@
+//! Checks for drift between DHCP leases, pools and device interfaces.
+//!
+//! Nothing enforces these relationships in the schema, so pools edited under live
+//! leases, devices deleted while leased and interrupted updates leave records that
+//! disagree with each other. [`check`] reports them for `rack-director check` and
+//! `GET /api/admin/consistency`, and repairs them when asked to.
+
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::net::Ipv4Addr;
+use std::ops::RangeInclusive;
+
+use anyhow::Result;
+use rusqlite::OptionalExtension;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::audit::{self, AuditEntry};
+use crate::database::Connection;
+use crate::dhcp::{Lease, LeaseState, store as dhcp_store};
+use crate::director::Director;
+
+/// The audit log actor for repairs made by `rack-director check --fix`.
+const REPAIR_ACTOR: &str = "check --fix";
+
+/// One inconsistency found by [`check`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Anomaly {
+    /// A live lease on an address that is in none of its network's pools and not
+    /// statically reserved there. Fixed by releasing the lease.
+    LeaseOutsidePool {
+        mac: String,
+        ip: String,
+        network_id: i64,
+    },
+    /// A device interface records an address that no live lease for its MAC
+    /// backs. Fixed by clearing the interface's address.
+    InterfaceWithoutLease {
+        device_uuid: Uuid,
+        mac: String,
+        ip: String,
+    },
+    /// A live lease for a device that was deleted or no longer has an interface
+    /// with the lease's MAC. Fixed by releasing the lease.
+    LeaseForDeletedInterface {
+        device_uuid: Uuid,
+        mac: String,
+        ip: String,
+    },
+}
+
+impl fmt::Display for Anomaly {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Anomaly::LeaseOutsidePool {
+                mac,
+                ip,
+                network_id,
+            } => write!(
+                f,
+                "lease {} for {} is outside every pool of network {}",
+                ip, mac, network_id
+            ),
+            Anomaly::InterfaceWithoutLease {
+                device_uuid,
+                mac,
+                ip,
+            } => write!(
+                f,
+                "interface {} of device {} has address {} but no lease",
+                mac, device_uuid, ip
+            ),
+            Anomaly::LeaseForDeletedInterface {
+                device_uuid,
+                mac,
+                ip,
+            } => write!(
+                f,
+                "lease {} for {} belongs to device {}, which has no such interface",
+                ip, mac, device_uuid
+            ),
+        }
+    }
+}
+
+/// The outcome of a [`check`].
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ConsistencyReport {
+    pub anomalies: Vec<Anomaly>,
+    /// The anomalies were repaired after being found.
+    pub fixed: bool,
+}
+
+impl fmt::Display for ConsistencyReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.anomalies.is_empty() {
+            return writeln!(f, "No inconsistencies found");
+        }
+        for anomaly in &self.anomalies {
+            writeln!(f, "- {}", anomaly)?;
+        }
+        let verb = if self.fixed { "Fixed" } else { "Found" };
+        writeln!(f, "{} {} inconsistencies", verb, self.anomalies.len())
+    }
+}
+
+/// Scan for every kind of [`Anomaly`], repairing each one found if `fix` is set.
+///
+/// The scan and the repairs are not one transaction, so each repair re-checks its
+/// anomaly in its own transaction first and skips it if the server resolved it in
+/// the meantime. A fixed report lists only the anomalies actually repaired, and
+/// each repair is recorded in the audit log.
+pub async fn check(conn: &mut Connection, fix: bool) -> Result<ConsistencyReport> {
+    let anomalies = scan(conn).await?;
+    if !fix {
+        return Ok(ConsistencyReport {
+            anomalies,
+            fixed: false,
+        });
+    }
+
+    let mut repaired = Vec::new();
+    for anomaly in anomalies {
+        if repair(conn, &anomaly).await? {
+            repaired.push(anomaly);
+        }
+    }
+    Ok(ConsistencyReport {
+        anomalies: repaired,
+        fixed: true,
+    })
+}
+
+async fn scan(conn: &Connection) -> Result<Vec<Anomaly>> {
+    let leases: Vec<Lease> = dhcp_store::get_all_leases(conn)
+        .await?
+        .into_iter()
+        .filter(is_live)
+        .collect();
+
+    let mut anomalies = leases_outside_pools(conn, &leases).await?;
+    anomalies.extend(interfaces_without_leases(conn, &leases).await?);
+    anomalies.extend(leases_for_deleted_interfaces(conn, &leases).await?);
+    Ok(anomalies)
+}
+
+/// Whether `lease` still holds its address.
+fn is_live(lease: &Lease) -> bool {
+    matches!(lease.state, LeaseState::Active | LeaseState::Offered) && !lease.is_expired()
+}
+
+async fn leases_outside_pools(conn: &Connection, leases: &[Lease]) -> Result<Vec<Anomaly>> {
+    let mut allowed_by_network: HashMap<i64, AllowedAddresses> = HashMap::new();
+    let mut anomalies = Vec::new();
+
+    for lease in leases {
+        let Some(network_id) = lease.network_id else {
+            continue;
+        };
+        let allowed = match allowed_by_network.entry(network_id) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(AllowedAddresses::load(conn, network_id).await?),
+        };
+        if !allowed.contains(&lease.ip_address) {
+            anomalies.push(Anomaly::LeaseOutsidePool {
+                mac: lease.mac_address.clone(),
+                ip: lease.ip_address.clone(),
+                network_id,
+            });
+        }
+    }
+    Ok(anomalies)
+}
+
+/// The addresses a network may lease: its pool ranges and static reservations.
+struct AllowedAddresses {
+    ranges: Vec<RangeInclusive<u32>>,
+    reserved: HashSet<String>,
+}
+
+impl AllowedAddresses {
+    async fn load(conn: &Connection, network_id: i64) -> Result<Self> {
+        let ranges = dhcp_store::list_pools_for_network(conn, network_id)
+            .await?
+            .into_iter()
+            .filter_map(|pool| {
+                let start: Ipv4Addr = pool.range_start.parse().ok()?;
+                let end: Ipv4Addr = pool.range_end.parse().ok()?;
+                Some(u32::from(start)..=u32::from(end))
+            })
+            .collect();
+        let reserved = dhcp_store::list_static_reservations(conn, network_id)
+            .await?
+            .into_iter()
+            .map(|r| r.ip_address)
+            .collect();
+        Ok(Self { ranges, reserved })
+    }
+
+    fn contains(&self, ip: &str) -> bool {
+        self.reserved.contains(ip)
+            || ip
+                .parse::<Ipv4Addr>()
+                .is_ok_and(|ip| self.ranges.iter().any(|r| r.contains(&u32::from(ip))))
+    }
+}
+
+async fn interfaces_without_leases(conn: &Connection, leases: &[Lease]) -> Result<Vec<Anomaly>> {
+    let leased: HashSet<(&str, &str)> = leases
+        .iter()
+        .map(|l| (l.mac_address.as_str(), l.ip_address.as_str()))
+        .collect();
+    let mut anomalies = Vec::new();
+
+    for device_uuid in list_device_uuids(conn).await? {
+        for iface in Director::new(conn)
+            .get_network_interfaces(&device_uuid)
+            .await?
+        {
+            let Some(ip) = iface.ip_address else {
+                continue;
+            };
+            if !leased.contains(&(iface.mac_address.as_str(), ip.as_str())) {
+                anomalies.push(Anomaly::InterfaceWithoutLease {
+                    device_uuid,
+                    mac: iface.mac_address,
+                    ip,
+                });
+            }
+        }
+    }
+    Ok(anomalies)
+}
+
+async fn leases_for_deleted_interfaces(
+    conn: &Connection,
+    leases: &[Lease],
+) -> Result<Vec<Anomaly>> {
+    let mut anomalies = Vec::new();
+    for lease in leases {
+        let Some(device_uuid) = lease.device_uuid else {
+            continue;
+        };
+        let known = device_macs(conn, &device_uuid)
+            .await?
+            .is_some_and(|macs| macs.contains(&lease.mac_address));
+        if !known {
+            anomalies.push(Anomaly::LeaseForDeletedInterface {
+                device_uuid,
+                mac: lease.mac_address.clone(),
+                ip: lease.ip_address.clone(),
+            });
+        }
+    }
+    Ok(anomalies)
+}
+
+async fn list_device_uuids(conn: &Connection) -> Result<Vec<Uuid>> {
+    let uuids = conn
+        .query("SELECT uuid FROM devices ORDER BY uuid", (), |row| {
+            row.get(0)
+        })
+        .await?;
+    Ok(uuids)
+}
+
+/// Every MAC the device is known by, its BMC's included, or `None` if it doesn't
+/// exist.
+async fn device_macs(conn: &Connection, uuid: &Uuid) -> Result<Option<HashSet<String>>> {
+    let bmc_mac = conn
+        .query_row(
+            "SELECT json_extract(attributes, '$.bmc.mac_address') FROM devices WHERE uuid = ?1",
+            (*uuid,),
+            |row| row.get::<_, Option<String>>(0),
+        )
+        .await
+        .optional()?;
+    let Some(bmc_mac) = bmc_mac else {
+        return Ok(None);
+    };
+
+    let mut macs = Director::new(conn).get_interface_macs(uuid).await?;
+    macs.extend(bmc_mac);
+    Ok(Some(macs))
+}
+
+/// Repair `anomaly` if it is still present, returning whether it was.
+async fn repair(conn: &mut Connection, anomaly: &Anomaly) -> Result<bool> {
+    let tx = conn.transaction().await?;
+    if !still_present(&tx, anomaly).await? {
+        tx.rollback().await?;
+        return Ok(false);
+    }
+    apply_repair(&tx, anomaly).await?;
+    audit::record(
+        &tx,
+        &AuditEntry {
+            actor: REPAIR_ACTOR.to_string(),
+            action: "consistency.repair".to_string(),
+            target: repair_target(anomaly),
+            before: serde_json::to_value(anomaly).ok(),
+            after: None,
+        },
+    )
+    .await?;
+    tx.commit().await?;
+    Ok(true)
+}
+
+/// Re-check `anomaly` against the current state of the database.
+async fn still_present(conn: &Connection, anomaly: &Anomaly) -> Result<bool> {
+    match anomaly {
+        Anomaly::LeaseOutsidePool {
+            mac,
+            ip,
+            network_id,
+        } => {
+            let Some(lease) = live_lease(conn, mac, ip).await? else {
+                return Ok(false);
+            };
+            let allowed = AllowedAddresses::load(conn, *network_id).await?;
+            Ok(lease.network_id == Some(*network_id) && !allowed.contains(ip))
+        }
+        Anomaly::InterfaceWithoutLease {
+            device_uuid,
+            mac,
+            ip,
+        } => {
+            if live_lease(conn, mac, ip).await?.is_some() {
+                return Ok(false);
+            }
+            let interfaces = Director::new(conn)
+                .get_network_interfaces(device_uuid)
+                .await?;
+            Ok(interfaces
+                .iter()
+                .any(|i| &i.mac_address == mac && i.ip_address.as_ref() == Some(ip)))
+        }
+        Anomaly::LeaseForDeletedInterface {
+            device_uuid,
+            mac,
+            ip,
+        } => {
+            let Some(lease) = live_lease(conn, mac, ip).await? else {
+                return Ok(false);
+            };
+            let known = device_macs(conn, device_uuid)
+                .await?
+                .is_some_and(|macs| macs.contains(mac));
+            Ok(lease.device_uuid == Some(*device_uuid) && !known)
+        }
+    }
+}
+
+/// The live lease `mac` holds on `ip`, if it still has one.
+async fn live_lease(conn: &Connection, mac: &str, ip: &str) -> Result<Option<Lease>> {
+    let lease = dhcp_store::get_lease_by_mac(conn, mac).await?;
+    Ok(lease.filter(|l| l.ip_address == ip && is_live(l)))
+}
+
+async fn apply_repair(conn: &Connection, anomaly: &Anomaly) -> Result<()> {
+    match anomaly {
+        Anomaly::LeaseOutsidePool { mac, .. } | Anomaly::LeaseForDeletedInterface { mac, .. } => {
+            dhcp_store::release_lease(conn, mac).await
+        }
+        Anomaly::InterfaceWithoutLease {
+            device_uuid, mac, ..
+        } => {
+            let mut interfaces = Director::new(conn)
+                .get_network_interfaces(device_uuid)
+                .await?;
+            for iface in interfaces.iter_mut().filter(|i| &i.mac_address == mac) {
+                iface.ip_address = None;
+            }
+            Director::new(conn)
+                .set_network_interfaces(device_uuid, &interfaces)
+                .await
+        }
+    }
+}
+
+/// The audit log target of a repair: the lease or the device it changes.
+fn repair_target(anomaly: &Anomaly) -> String {
+    match anomaly {
+        Anomaly::LeaseOutsidePool { mac, .. } | Anomaly::LeaseForDeletedInterface { mac, .. } => {
+            format!("lease/{}", mac)
+        }
+        Anomaly::InterfaceWithoutLease { device_uuid, .. } => format!("device/{}", device_uuid),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database;
+    use crate::director::NetworkInterface;
+    use crate::test_connection_factory;
+
+    const DEVICE: &str = "c1000000-0000-0000-0000-000000000001";
+
+    /// A network with one pool, .100 - .200, and a device with interface
+    /// `aa:bb:cc:dd:ee:01` leased .100 in it.
+    async fn setup() -> (Connection, i64, Uuid) {
+        let factory = test_connection_factory!();
+        let conn = database::run_migrations(&factory).await.unwrap();
+        let network = dhcp_store::create_network(
+            &conn,
+            "Test Network",
+            "10.0.0.0/24",
+            "10.0.0.1",
+            &[],
+            86400,
+            None,
+            false,
+        )
+        .await
+        .unwrap();
+        dhcp_store::create_pool(&conn, network.id, "Pool", "10.0.0.100", "10.0.0.200")
+            .await
+            .unwrap();
+
+        let uuid = Uuid::parse_str(DEVICE).unwrap();
+        conn.execute(
+            "INSERT INTO devices (uuid, lifecycle, architecture) VALUES (?1, 'new', 'x86-64')",
+            (uuid,),
+        )
+        .await
+        .unwrap();
+        lease(
+            &conn,
+            network.id,
+            "aa:bb:cc:dd:ee:01",
+            "10.0.0.100",
+            Some(uuid),
+        )
+        .await;
+        Director::new(&conn)
+            .set_network_interfaces(
+                &uuid,
+                &[NetworkInterface {
+                    interface_name: "eth0".to_string(),
+                    mac_address: "aa:bb:cc:dd:ee:01".to_string(),
+                    ip_address: Some("10.0.0.100".to_string()),
+                    network_id: None,
+                    speed_mbps: None,
+                    disabled: false,
+                    warning_label: None,
+                }],
+            )
+            .await
+            .unwrap();
+        (conn, network.id, uuid)
+    }
+
+    async fn lease(conn: &Connection, network_id: i64, mac: &str, ip: &str, uuid: Option<Uuid>) {
+        dhcp_store::create_or_update_lease_with_network(
+            conn,
+            mac,
+            &ip.parse().unwrap(),
+            uuid.as_ref(),
+            LeaseState::Active,
+            3600,
+            network_id,
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_consistent_database_reports_nothing() {
+        let (mut conn, _network_id, _uuid) = setup().await;
+        let report = check(&mut conn, false).await.unwrap();
+        assert!(report.anomalies.is_empty(), "{:?}", report.anomalies);
+    }
+
+    #[tokio::test]
+    async fn test_detects_and_fixes_lease_outside_pool() {
+        let (mut conn, network_id, _uuid) = setup().await;
+        lease(&conn, network_id, "aa:bb:cc:dd:ee:02", "10.0.0.50", None).await;
+
+        let report = check(&mut conn, false).await.unwrap();
+        assert_eq!(
+            report.anomalies,
+            vec![Anomaly::LeaseOutsidePool {
+                mac: "aa:bb:cc:dd:ee:02".to_string(),
+                ip: "10.0.0.50".to_string(),
+                network_id,
+            }]
+        );
+
+        // A static reservation makes the same address legitimate
+        dhcp_store::create_static_reservation(
+            &conn,
+            network_id,
+            "aa:bb:cc:dd:ee:02",
+            "10.0.0.50",
+            None,
+        )
+        .await
+        .unwrap();
+        assert!(check(&mut conn, false).await.unwrap().anomalies.is_empty());
+        conn.execute("DELETE FROM dhcp_static_reservations", ())
+            .await
+            .unwrap();
+
+        assert!(check(&mut conn, true).await.unwrap().fixed);
+        assert!(check(&mut conn, false).await.unwrap().anomalies.is_empty());
+        let released = dhcp_store::get_lease_by_mac(&conn, "aa:bb:cc:dd:ee:02")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(released.state, LeaseState::Released);
+    }
+
+    #[tokio::test]
+    async fn test_detects_and_fixes_interface_without_lease() {
+        let (mut conn, _network_id, uuid) = setup().await;
+        dhcp_store::release_lease(&conn, "aa:bb:cc:dd:ee:01")
+            .await
+            .unwrap();
+
+        let report = check(&mut conn, false).await.unwrap();
+        assert_eq!(
+            report.anomalies,
+            vec![Anomaly::InterfaceWithoutLease {
+                device_uuid: uuid,
+                mac: "aa:bb:cc:dd:ee:01".to_string(),
+                ip: "10.0.0.100".to_string(),
+            }]
+        );
+
+        check(&mut conn, true).await.unwrap();
+        assert!(check(&mut conn, false).await.unwrap().anomalies.is_empty());
+        let interfaces = Director::new(&conn)
+            .get_network_interfaces(&uuid)
+            .await
+            .unwrap();
+        assert_eq!(interfaces[0].ip_address, None);
+    }
+
+    #[tokio::test]
+    async fn test_detects_and_fixes_lease_for_deleted_interface() {
+        let (mut conn, _network_id, uuid) = setup().await;
+        Director::new(&conn)
+            .set_network_interfaces(&uuid, &[])
+            .await
+            .unwrap();
+
+        let report = check(&mut conn, false).await.unwrap();
+        assert_eq!(
+            report.anomalies,
+            vec![Anomaly::LeaseForDeletedInterface {
+                device_uuid: uuid,
+                mac: "aa:bb:cc:dd:ee:01".to_string(),
+                ip: "10.0.0.100".to_string(),
+            }]
+        );
+        assert!(report.to_string().contains("Found 1 inconsistencies"));
+
+        check(&mut conn, true).await.unwrap();
+        assert!(check(&mut conn, false).await.unwrap().anomalies.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_fix_records_repairs_in_audit_log() {
+        let (mut conn, network_id, _uuid) = setup().await;
+        lease(&conn, network_id, "aa:bb:cc:dd:ee:02", "10.0.0.50", None).await;
+
+        check(&mut conn, true).await.unwrap();
+
+        let entries: Vec<(String, String, String)> = conn
+            .query("SELECT actor, action, target FROM audit_log", (), |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+            })
+            .await
+            .unwrap();
+        assert_eq!(
+            entries,
+            vec![(
+                "check --fix".to_string(),
+                "consistency.repair".to_string(),
+                "lease/aa:bb:cc:dd:ee:02".to_string(),
+            )]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_repair_skips_anomaly_resolved_since_scan() {
+        let (mut conn, network_id, uuid) = setup().await;
+        dhcp_store::release_lease(&conn, "aa:bb:cc:dd:ee:01")
+            .await
+            .unwrap();
+        let anomalies = check(&mut conn, false).await.unwrap().anomalies;
+        assert_eq!(anomalies.len(), 1);
+
+        // The client renews between the scan and the repair
+        lease(
+            &conn,
+            network_id,
+            "aa:bb:cc:dd:ee:01",
+            "10.0.0.100",
+            Some(uuid),
+        )
+        .await;
+
+        assert!(!repair(&mut conn, &anomalies[0]).await.unwrap());
+        let interfaces = Director::new(&conn)
+            .get_network_interfaces(&uuid)
+            .await
+            .unwrap();
+        assert_eq!(interfaces[0].ip_address.as_deref(), Some("10.0.0.100"));
+        let audited: i64 = conn
+            .query_one("SELECT COUNT(*) FROM audit_log", (), |row| row.get(0))
+            .await
+            .unwrap();
+        assert_eq!(audited, 0);
+    }
+}
@@ -2,11 +2,32 @@ use std::{
     ops::Deref,
     path::Path,
     thread::{self, JoinHandle},
+    time::Duration,
 };
 
 use rusqlite::Params;
 use tokio::sync::{mpsc, oneshot};
 
+/// How long a statement waits on another connection's lock before failing with
+/// `SQLITE_BUSY`. Kept short so that a stuck writer surfaces to HTTP clients as a
+/// 503 (see [`is_lock_contention`]) instead of stalling every request behind it.
+pub const BUSY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Whether `err` was caused by another connection holding the database lock
+/// (`SQLITE_BUSY`, or `SQLITE_LOCKED` under a shared cache).
+pub fn is_lock_contention(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| {
+        matches!(
+            cause.downcast_ref::<rusqlite::Error>(),
+            Some(rusqlite::Error::SqliteFailure(failure, _))
+                if matches!(
+                    failure.code,
+                    rusqlite::ErrorCode::DatabaseBusy | rusqlite::ErrorCode::DatabaseLocked
+                )
+        )
+    })
+}
+
 /// SqlRequest is the function to be called with the rusqlite Connection on the
 /// remote thread.
 trait SqlRequest: Send {
@@ -41,7 +62,9 @@ fn start_with_file<P: AsRef<Path>>(
     path: P,
     started: oneshot::Sender<rusqlite::Result<mpsc::Sender<ControlMessage>>>,
 ) {
-    let conn = match rusqlite::Connection::open(path) {
+    let conn = match rusqlite::Connection::open(path)
+        .and_then(|conn| conn.busy_timeout(BUSY_TIMEOUT).map(|_| conn))
+    {
         Ok(conn) => conn,
         Err(e) => {
             started
@@ -396,4 +419,29 @@ mod tests {
             .expect("connection must be reusable after implicit rollback");
         tx2.commit().await.unwrap();
     }
+
+    #[tokio::test]
+    async fn held_write_lock_is_reported_as_contention() {
+        let writer = Connection::open(test_database_path!()).await.unwrap();
+        writer
+            .execute("CREATE TABLE foo (x INTEGER)", ())
+            .await
+            .unwrap();
+        writer.execute("BEGIN IMMEDIATE", ()).await.unwrap();
+        writer
+            .execute("INSERT INTO foo(x) VALUES (1)", ())
+            .await
+            .unwrap();
+
+        let reader = Connection::open(test_database_path!()).await.unwrap();
+        let err: anyhow::Error = reader
+            .query("SELECT x FROM foo", (), |row| row.get::<_, i64>(0))
+            .await
+            .unwrap_err()
+            .into();
+        assert!(super::is_lock_contention(&err));
+        assert!(!super::is_lock_contention(&anyhow::anyhow!(
+            "other failure"
+        )));
+    }
 }
@@ -0,0 +1,101 @@
+//! Reclaiming space and refreshing query planner statistics.
+//!
+//! Lease churn leaves free pages behind and skews the statistics the planner
+//! relies on. `VACUUM` rewrites the file without them and `ANALYZE` refreshes
+//! the statistics. `VACUUM` needs every other connection to be idle for the
+//! whole rewrite, so it runs on its own connection with a longer busy timeout
+//! than request handlers get; requests that arrive meanwhile wait on the lock
+//! (and surface as 503s if it outlasts their own timeout) rather than deadlock.
+
+use std::time::Duration;
+
+use anyhow::Result;
+use serde::Serialize;
+
+use super::{Connection, ConnectionFactory};
+
+/// How long maintenance waits for other connections to go idle before giving up.
+pub const MAINTENANCE_BUSY_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Only one maintenance run at a time; a second would just queue behind the first.
+static MAINTENANCE_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+/// Database size before and after a maintenance run.
+#[derive(Debug, Serialize)]
+pub struct MaintenanceReport {
+    pub before_bytes: u64,
+    pub after_bytes: u64,
+}
+
+/// Run `VACUUM` and then `ANALYZE` on a dedicated connection from `factory`.
+///
+/// Fails with a lock contention error (see [`super::is_lock_contention`]) if other
+/// connections stay busy for longer than [`MAINTENANCE_BUSY_TIMEOUT`].
+pub async fn vacuum_and_analyze(factory: &dyn ConnectionFactory) -> Result<MaintenanceReport> {
+    let _guard = MAINTENANCE_LOCK.lock().await;
+
+    // A fresh connection can't be inside a handler's transaction, which VACUUM refuses.
+    let conn = factory.open().await?;
+    let timeout_ms = MAINTENANCE_BUSY_TIMEOUT.as_millis() as i64;
+    conn.query_row(format!("PRAGMA busy_timeout = {timeout_ms}"), (), |row| {
+        row.get::<_, i64>(0)
+    })
+    .await?;
+
+    let before_bytes = database_size(&conn).await?;
+    log::info!("Database maintenance: vacuuming {before_bytes} bytes");
+    conn.execute_batch("VACUUM").await?;
+    conn.execute_batch("ANALYZE").await?;
+    let after_bytes = database_size(&conn).await?;
+    log::info!("Database maintenance: done, {before_bytes} -> {after_bytes} bytes");
+
+    Ok(MaintenanceReport {
+        before_bytes,
+        after_bytes,
+    })
+}
+
+/// Size of the main database in bytes, as pages times page size.
+async fn database_size(conn: &Connection) -> Result<u64> {
+    let pages: i64 = conn
+        .query_one("PRAGMA page_count", (), |row| row.get(0))
+        .await?;
+    let page_size: i64 = conn
+        .query_one("PRAGMA page_size", (), |row| row.get(0))
+        .await?;
+    Ok((pages * page_size) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{database, test_connection_factory};
+
+    #[tokio::test]
+    async fn test_vacuum_reclaims_deleted_rows() {
+        let factory = test_connection_factory!();
+        let conn = database::run_migrations(&factory).await.unwrap();
+        conn.execute_batch(
+            "CREATE TABLE churn (payload TEXT);
+             WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 2000)
+             INSERT INTO churn SELECT hex(randomblob(250)) FROM n;
+             DELETE FROM churn;",
+        )
+        .await
+        .unwrap();
+
+        let report = vacuum_and_analyze(&factory).await.unwrap();
+        assert!(report.before_bytes > 0);
+        assert!(
+            report.after_bytes < report.before_bytes,
+            "{report:?} should shrink"
+        );
+
+        // Other connections keep working afterwards
+        let rows: i64 = conn
+            .query_one("SELECT COUNT(*) FROM churn", (), |row| row.get(0))
+            .await
+            .unwrap();
+        assert_eq!(rows, 0);
+    }
+}
@@ -0,0 +1,5 @@
+-- Migration 24: DHCP client identifier (option 61) on leases.
+-- Clients that send option 61 are keyed by it rather than by chaddr, so a
+-- bonded or virtualised NIC that changes MAC keeps its lease (RFC 2131 §4.2).
+ALTER TABLE dhcp_leases ADD COLUMN client_id TEXT;
+CREATE INDEX idx_dhcp_leases_client_id ON dhcp_leases(client_id);
@@ -0,0 +1,4 @@
+-- Migration 25: Per-network enabled flag.
+-- A disabled network still renews existing leases but hands out no new
+-- addresses, so operators can drain a subnet before renumbering it.
+ALTER TABLE dhcp_networks ADD COLUMN enabled BOOLEAN NOT NULL DEFAULT 1;
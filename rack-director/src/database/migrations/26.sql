@@ -0,0 +1,4 @@
+-- Migration 26: One-shot rediscovery request.
+-- Set by POST /api/devices/{uuid}/rediscover and cleared by the next boot-target
+-- lookup, which netboots the device into a hardware scan exactly once.
+ALTER TABLE devices ADD COLUMN rediscover_pending BOOLEAN NOT NULL DEFAULT 0;
@@ -0,0 +1,100 @@
+-- Migration 27: Standardize timestamps on RFC 3339 UTC text.
+-- Rows written by SQLite defaults ("YYYY-MM-DD HH:MM:SS") or by rusqlite's chrono
+-- encoding ("YYYY-MM-DD HH:MM:SS.fff+00:00") are rewritten to the RFC 3339 form
+-- the application now writes everywhere (database::to_db_time), so timestamps
+-- from different tables compare correctly as text.
+--
+-- Step 1 replaces the date/time separator; step 2 appends a UTC offset to values
+-- that had none. The legacy first_seen_at/last_seen_at `0` sentinel is left alone.
+
+-- devices
+UPDATE devices SET created_at = substr(created_at, 1, 10) || 'T' || substr(created_at, 12) WHERE created_at GLOB '????-??-?? ??:??:??*';
+UPDATE devices SET created_at = created_at || '+00:00' WHERE created_at GLOB '????-??-??T??:??:??*' AND substr(created_at, 20) NOT GLOB '*[^0-9.]*';
+UPDATE devices SET first_seen_at = substr(first_seen_at, 1, 10) || 'T' || substr(first_seen_at, 12) WHERE first_seen_at GLOB '????-??-?? ??:??:??*';
+UPDATE devices SET first_seen_at = first_seen_at || '+00:00' WHERE first_seen_at GLOB '????-??-??T??:??:??*' AND substr(first_seen_at, 20) NOT GLOB '*[^0-9.]*';
+UPDATE devices SET last_seen_at = substr(last_seen_at, 1, 10) || 'T' || substr(last_seen_at, 12) WHERE last_seen_at GLOB '????-??-?? ??:??:??*';
+UPDATE devices SET last_seen_at = last_seen_at || '+00:00' WHERE last_seen_at GLOB '????-??-??T??:??:??*' AND substr(last_seen_at, 20) NOT GLOB '*[^0-9.]*';
+UPDATE devices SET last_polled_at = substr(last_polled_at, 1, 10) || 'T' || substr(last_polled_at, 12) WHERE last_polled_at GLOB '????-??-?? ??:??:??*';
+UPDATE devices SET last_polled_at = last_polled_at || '+00:00' WHERE last_polled_at GLOB '????-??-??T??:??:??*' AND substr(last_polled_at, 20) NOT GLOB '*[^0-9.]*';
+
+-- plans
+UPDATE plans SET created_at = substr(created_at, 1, 10) || 'T' || substr(created_at, 12) WHERE created_at GLOB '????-??-?? ??:??:??*';
+UPDATE plans SET created_at = created_at || '+00:00' WHERE created_at GLOB '????-??-??T??:??:??*' AND substr(created_at, 20) NOT GLOB '*[^0-9.]*';
+UPDATE plans SET started_at = substr(started_at, 1, 10) || 'T' || substr(started_at, 12) WHERE started_at GLOB '????-??-?? ??:??:??*';
+UPDATE plans SET started_at = started_at || '+00:00' WHERE started_at GLOB '????-??-??T??:??:??*' AND substr(started_at, 20) NOT GLOB '*[^0-9.]*';
+UPDATE plans SET completed_at = substr(completed_at, 1, 10) || 'T' || substr(completed_at, 12) WHERE completed_at GLOB '????-??-?? ??:??:??*';
+UPDATE plans SET completed_at = completed_at || '+00:00' WHERE completed_at GLOB '????-??-??T??:??:??*' AND substr(completed_at, 20) NOT GLOB '*[^0-9.]*';
+
+-- lifecycle_transitions
+UPDATE lifecycle_transitions SET created_at = substr(created_at, 1, 10) || 'T' || substr(created_at, 12) WHERE created_at GLOB '????-??-?? ??:??:??*';
+UPDATE lifecycle_transitions SET created_at = created_at || '+00:00' WHERE created_at GLOB '????-??-??T??:??:??*' AND substr(created_at, 20) NOT GLOB '*[^0-9.]*';
+UPDATE lifecycle_transitions SET completed_at = substr(completed_at, 1, 10) || 'T' || substr(completed_at, 12) WHERE completed_at GLOB '????-??-?? ??:??:??*';
+UPDATE lifecycle_transitions SET completed_at = completed_at || '+00:00' WHERE completed_at GLOB '????-??-??T??:??:??*' AND substr(completed_at, 20) NOT GLOB '*[^0-9.]*';
+
+-- pending_devices
+UPDATE pending_devices SET created_at = substr(created_at, 1, 10) || 'T' || substr(created_at, 12) WHERE created_at GLOB '????-??-?? ??:??:??*';
+UPDATE pending_devices SET created_at = created_at || '+00:00' WHERE created_at GLOB '????-??-??T??:??:??*' AND substr(created_at, 20) NOT GLOB '*[^0-9.]*';
+UPDATE pending_devices SET completed_at = substr(completed_at, 1, 10) || 'T' || substr(completed_at, 12) WHERE completed_at GLOB '????-??-?? ??:??:??*';
+UPDATE pending_devices SET completed_at = completed_at || '+00:00' WHERE completed_at GLOB '????-??-??T??:??:??*' AND substr(completed_at, 20) NOT GLOB '*[^0-9.]*';
+
+-- dhcp_leases
+UPDATE dhcp_leases SET lease_start = substr(lease_start, 1, 10) || 'T' || substr(lease_start, 12) WHERE lease_start GLOB '????-??-?? ??:??:??*';
+UPDATE dhcp_leases SET lease_start = lease_start || '+00:00' WHERE lease_start GLOB '????-??-??T??:??:??*' AND substr(lease_start, 20) NOT GLOB '*[^0-9.]*';
+UPDATE dhcp_leases SET lease_end = substr(lease_end, 1, 10) || 'T' || substr(lease_end, 12) WHERE lease_end GLOB '????-??-?? ??:??:??*';
+UPDATE dhcp_leases SET lease_end = lease_end || '+00:00' WHERE lease_end GLOB '????-??-??T??:??:??*' AND substr(lease_end, 20) NOT GLOB '*[^0-9.]*';
+UPDATE dhcp_leases SET created_at = substr(created_at, 1, 10) || 'T' || substr(created_at, 12) WHERE created_at GLOB '????-??-?? ??:??:??*';
+UPDATE dhcp_leases SET created_at = created_at || '+00:00' WHERE created_at GLOB '????-??-??T??:??:??*' AND substr(created_at, 20) NOT GLOB '*[^0-9.]*';
+UPDATE dhcp_leases SET updated_at = substr(updated_at, 1, 10) || 'T' || substr(updated_at, 12) WHERE updated_at GLOB '????-??-?? ??:??:??*';
+UPDATE dhcp_leases SET updated_at = updated_at || '+00:00' WHERE updated_at GLOB '????-??-??T??:??:??*' AND substr(updated_at, 20) NOT GLOB '*[^0-9.]*';
+
+-- dhcp_networks
+UPDATE dhcp_networks SET created_at = substr(created_at, 1, 10) || 'T' || substr(created_at, 12) WHERE created_at GLOB '????-??-?? ??:??:??*';
+UPDATE dhcp_networks SET created_at = created_at || '+00:00' WHERE created_at GLOB '????-??-??T??:??:??*' AND substr(created_at, 20) NOT GLOB '*[^0-9.]*';
+UPDATE dhcp_networks SET updated_at = substr(updated_at, 1, 10) || 'T' || substr(updated_at, 12) WHERE updated_at GLOB '????-??-?? ??:??:??*';
+UPDATE dhcp_networks SET updated_at = updated_at || '+00:00' WHERE updated_at GLOB '????-??-??T??:??:??*' AND substr(updated_at, 20) NOT GLOB '*[^0-9.]*';
+
+-- dhcp_pools
+UPDATE dhcp_pools SET created_at = substr(created_at, 1, 10) || 'T' || substr(created_at, 12) WHERE created_at GLOB '????-??-?? ??:??:??*';
+UPDATE dhcp_pools SET created_at = created_at || '+00:00' WHERE created_at GLOB '????-??-??T??:??:??*' AND substr(created_at, 20) NOT GLOB '*[^0-9.]*';
+UPDATE dhcp_pools SET updated_at = substr(updated_at, 1, 10) || 'T' || substr(updated_at, 12) WHERE updated_at GLOB '????-??-?? ??:??:??*';
+UPDATE dhcp_pools SET updated_at = updated_at || '+00:00' WHERE updated_at GLOB '????-??-??T??:??:??*' AND substr(updated_at, 20) NOT GLOB '*[^0-9.]*';
+
+-- dhcp_static_reservations
+UPDATE dhcp_static_reservations SET created_at = substr(created_at, 1, 10) || 'T' || substr(created_at, 12) WHERE created_at GLOB '????-??-?? ??:??:??*';
+UPDATE dhcp_static_reservations SET created_at = created_at || '+00:00' WHERE created_at GLOB '????-??-??T??:??:??*' AND substr(created_at, 20) NOT GLOB '*[^0-9.]*';
+UPDATE dhcp_static_reservations SET updated_at = substr(updated_at, 1, 10) || 'T' || substr(updated_at, 12) WHERE updated_at GLOB '????-??-?? ??:??:??*';
+UPDATE dhcp_static_reservations SET updated_at = updated_at || '+00:00' WHERE updated_at GLOB '????-??-??T??:??:??*' AND substr(updated_at, 20) NOT GLOB '*[^0-9.]*';
+
+-- platforms
+UPDATE platforms SET created_at = substr(created_at, 1, 10) || 'T' || substr(created_at, 12) WHERE created_at GLOB '????-??-?? ??:??:??*';
+UPDATE platforms SET created_at = created_at || '+00:00' WHERE created_at GLOB '????-??-??T??:??:??*' AND substr(created_at, 20) NOT GLOB '*[^0-9.]*';
+UPDATE platforms SET updated_at = substr(updated_at, 1, 10) || 'T' || substr(updated_at, 12) WHERE updated_at GLOB '????-??-?? ??:??:??*';
+UPDATE platforms SET updated_at = updated_at || '+00:00' WHERE updated_at GLOB '????-??-??T??:??:??*' AND substr(updated_at, 20) NOT GLOB '*[^0-9.]*';
+
+-- roles
+UPDATE roles SET created_at = substr(created_at, 1, 10) || 'T' || substr(created_at, 12) WHERE created_at GLOB '????-??-?? ??:??:??*';
+UPDATE roles SET created_at = created_at || '+00:00' WHERE created_at GLOB '????-??-??T??:??:??*' AND substr(created_at, 20) NOT GLOB '*[^0-9.]*';
+UPDATE roles SET updated_at = substr(updated_at, 1, 10) || 'T' || substr(updated_at, 12) WHERE updated_at GLOB '????-??-?? ??:??:??*';
+UPDATE roles SET updated_at = updated_at || '+00:00' WHERE updated_at GLOB '????-??-??T??:??:??*' AND substr(updated_at, 20) NOT GLOB '*[^0-9.]*';
+
+-- osm_modules
+UPDATE osm_modules SET created_at = substr(created_at, 1, 10) || 'T' || substr(created_at, 12) WHERE created_at GLOB '????-??-?? ??:??:??*';
+UPDATE osm_modules SET created_at = created_at || '+00:00' WHERE created_at GLOB '????-??-??T??:??:??*' AND substr(created_at, 20) NOT GLOB '*[^0-9.]*';
+UPDATE osm_modules SET updated_at = substr(updated_at, 1, 10) || 'T' || substr(updated_at, 12) WHERE updated_at GLOB '????-??-?? ??:??:??*';
+UPDATE osm_modules SET updated_at = updated_at || '+00:00' WHERE updated_at GLOB '????-??-??T??:??:??*' AND substr(updated_at, 20) NOT GLOB '*[^0-9.]*';
+
+-- osm_operating_systems
+UPDATE osm_operating_systems SET created_at = substr(created_at, 1, 10) || 'T' || substr(created_at, 12) WHERE created_at GLOB '????-??-?? ??:??:??*';
+UPDATE osm_operating_systems SET created_at = created_at || '+00:00' WHERE created_at GLOB '????-??-??T??:??:??*' AND substr(created_at, 20) NOT GLOB '*[^0-9.]*';
+UPDATE osm_operating_systems SET updated_at = substr(updated_at, 1, 10) || 'T' || substr(updated_at, 12) WHERE updated_at GLOB '????-??-?? ??:??:??*';
+UPDATE osm_operating_systems SET updated_at = updated_at || '+00:00' WHERE updated_at GLOB '????-??-??T??:??:??*' AND substr(updated_at, 20) NOT GLOB '*[^0-9.]*';
+
+-- osm_uploads
+UPDATE osm_uploads SET created_at = substr(created_at, 1, 10) || 'T' || substr(created_at, 12) WHERE created_at GLOB '????-??-?? ??:??:??*';
+UPDATE osm_uploads SET created_at = created_at || '+00:00' WHERE created_at GLOB '????-??-??T??:??:??*' AND substr(created_at, 20) NOT GLOB '*[^0-9.]*';
+UPDATE osm_uploads SET updated_at = substr(updated_at, 1, 10) || 'T' || substr(updated_at, 12) WHERE updated_at GLOB '????-??-?? ??:??:??*';
+UPDATE osm_uploads SET updated_at = updated_at || '+00:00' WHERE updated_at GLOB '????-??-??T??:??:??*' AND substr(updated_at, 20) NOT GLOB '*[^0-9.]*';
+
+-- device_warnings
+UPDATE device_warnings SET created_at = substr(created_at, 1, 10) || 'T' || substr(created_at, 12) WHERE created_at GLOB '????-??-?? ??:??:??*';
+UPDATE device_warnings SET created_at = created_at || '+00:00' WHERE created_at GLOB '????-??-??T??:??:??*' AND substr(created_at, 20) NOT GLOB '*[^0-9.]*';
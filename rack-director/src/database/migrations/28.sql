@@ -0,0 +1,18 @@
+-- Migration 28: Named agent image sets and per-device pinning.
+-- An image set replaces the bundled agent kernel/initramfs for the devices that
+-- use it. A device's pinned set wins; otherwise the set flagged is_default
+-- applies; with neither, the bundled agent images are used.
+CREATE TABLE image_sets (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL UNIQUE,
+    kernel TEXT NOT NULL,
+    ramdisk TEXT NOT NULL,
+    cmdline TEXT NOT NULL DEFAULT '',
+    is_default BOOLEAN NOT NULL DEFAULT 0,
+    created_at TEXT NOT NULL
+);
+
+-- At most one fleet-wide default.
+CREATE UNIQUE INDEX idx_image_sets_default ON image_sets(is_default) WHERE is_default = 1;
+
+ALTER TABLE devices ADD COLUMN image_set_id INTEGER REFERENCES image_sets(id) ON DELETE SET NULL;
@@ -0,0 +1,14 @@
+-- Migration 29: Audit log of state-changing API calls.
+-- Append-only; one row per successful mutation, with JSON summaries of the
+-- target before and after the change where the handler has them.
+CREATE TABLE audit_log (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    created_at TEXT NOT NULL,
+    actor TEXT NOT NULL,
+    action TEXT NOT NULL,
+    target TEXT NOT NULL,
+    before_summary TEXT,
+    after_summary TEXT
+);
+
+CREATE INDEX idx_audit_log_target ON audit_log(target, created_at);
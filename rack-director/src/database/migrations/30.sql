@@ -0,0 +1,19 @@
+-- Migration 30: Reject malformed dns_servers on dhcp_networks.
+-- The column holds a JSON array of address strings. Anything else used to be
+-- stored silently and then read back as a default, so clients got the wrong DNS
+-- with no indication why. SQLite cannot add a CHECK constraint in place, so the
+-- rule is enforced with triggers. json_type() errors on invalid JSON, so the
+-- value is only inspected once json_valid() has passed.
+CREATE TRIGGER dhcp_networks_dns_servers_insert
+BEFORE INSERT ON dhcp_networks
+WHEN json_type(CASE WHEN json_valid(NEW.dns_servers) THEN NEW.dns_servers END) IS NOT 'array'
+BEGIN
+    SELECT RAISE(ABORT, 'dns_servers must be a JSON array');
+END;
+
+CREATE TRIGGER dhcp_networks_dns_servers_update
+BEFORE UPDATE OF dns_servers ON dhcp_networks
+WHEN json_type(CASE WHEN json_valid(NEW.dns_servers) THEN NEW.dns_servers END) IS NOT 'array'
+BEGIN
+    SELECT RAISE(ABORT, 'dns_servers must be a JSON array');
+END;
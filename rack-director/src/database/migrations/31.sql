@@ -0,0 +1,11 @@
+-- Migration 31: Operator-assigned key/value tags on devices.
+-- One value per key per device, e.g. role=compute. Used to group devices in the API.
+CREATE TABLE device_tags (
+    device_id INTEGER NOT NULL REFERENCES devices(id) ON DELETE CASCADE,
+    key TEXT NOT NULL,
+    value TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    PRIMARY KEY (device_id, key)
+);
+
+CREATE INDEX idx_device_tags_key_value ON device_tags(key, value);
@@ -0,0 +1,12 @@
+-- Migration 32: Track when each device last changed lifecycle state.
+-- Set whenever the lifecycle changes or a transition starts; the transition
+-- reaper uses it to find devices stuck mid-transition.
+ALTER TABLE devices ADD COLUMN state_changed_at TEXT;
+
+-- Backfill from the most recent transition activity, falling back to creation time.
+UPDATE devices SET state_changed_at = COALESCE(
+    (SELECT MAX(COALESCE(t.completed_at, t.created_at))
+     FROM lifecycle_transitions t
+     WHERE t.device_uuid = devices.uuid),
+    created_at
+);
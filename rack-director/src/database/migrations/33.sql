@@ -0,0 +1,10 @@
+-- Migration 33: Per-interface last-seen tracking.
+-- Kept out of the devices.attributes JSON because agent reports replace the
+-- network_interfaces array wholesale. Keyed by MAC so it also covers NICs the
+-- agent has not reported yet.
+CREATE TABLE interface_activity (
+    device_id INTEGER NOT NULL REFERENCES devices(id) ON DELETE CASCADE,
+    mac_address TEXT NOT NULL,
+    last_seen_at TEXT NOT NULL,
+    PRIMARY KEY (device_id, mac_address)
+);
@@ -0,0 +1,6 @@
+-- Migration 34: Quarantine network for devices that are not yet provisioned.
+-- When set, DISCOVER/REQUEST received on this network from an unknown or
+-- unprovisioned device is served from the quarantine network's pools and
+-- options instead. The quarantine network must be reachable on the same
+-- segment (a secondary subnet on the interface or relay).
+ALTER TABLE dhcp_networks ADD COLUMN quarantine_network_id INTEGER REFERENCES dhcp_networks(id) ON DELETE SET NULL;
@@ -0,0 +1,4 @@
+-- Migration 35: Free-text operator notes on devices (e.g. "flaky PSU, RMA pending").
+-- Kept in their own column rather than in attributes so hardware scans and
+-- reprovisioning, which rewrite attributes, never touch them.
+ALTER TABLE devices ADD COLUMN notes TEXT;
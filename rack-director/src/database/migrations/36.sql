@@ -0,0 +1,4 @@
+-- Migration 36: Boot-stage tracking for chain_script plan actions.
+-- Records which step's chain script has already been served, so the device's
+-- next boot completes that stage and moves on to the following one.
+ALTER TABLE plans ADD COLUMN chained_step INTEGER;
@@ -0,0 +1,12 @@
+-- Migration 37: Hardware inventory reported by the discovery image.
+-- One row per device holding the latest submitted document. serial and model are
+-- copied out of the JSON so devices can be looked up by them.
+CREATE TABLE IF NOT EXISTS device_inventory (
+    device_id INTEGER PRIMARY KEY REFERENCES devices(id) ON DELETE CASCADE,
+    json TEXT NOT NULL,
+    serial TEXT,
+    model TEXT,
+    collected_at TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_device_inventory_serial ON device_inventory(serial);
+CREATE INDEX IF NOT EXISTS idx_device_inventory_model ON device_inventory(model);
@@ -0,0 +1,4 @@
+-- Migration 38: Per-device boot token for discovery callbacks.
+-- Issued each time the device is netbooted into the agent image and passed on the
+-- kernel cmdline; callbacks from the image must present it.
+ALTER TABLE devices ADD COLUMN boot_token TEXT;
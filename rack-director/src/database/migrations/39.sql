@@ -0,0 +1,5 @@
+-- Migration 39: Per-network DHCP server identifier.
+-- The director's address as seen from the network's clients, sent as option 54 and
+-- siaddr. NULL uses the global server identifier (relayed) or the receiving
+-- interface's address (L2).
+ALTER TABLE dhcp_networks ADD COLUMN server_identifier TEXT;
@@ -0,0 +1,8 @@
+-- Migration 40: UUID blocklist.
+-- Hardware UUIDs that are never registered or updated; their iPXE requests get a
+-- local boot script. Keyed like devices.uuid.
+CREATE TABLE uuid_blocklist (
+    uuid BLOB PRIMARY KEY,
+    reason TEXT,
+    created_at TEXT NOT NULL
+);
@@ -1,8 +1,12 @@
 mod connection;
+mod maintenance;
 mod migrations;
+mod time;
 
 use anyhow::Result;
-pub use connection::Connection;
+pub use connection::{BUSY_TIMEOUT, Connection, is_lock_contention};
+pub use maintenance::{MaintenanceReport, vacuum_and_analyze};
+pub use time::{from_db_time, to_db_time};
 
 /// A factory for opening database connections.
 ///
@@ -64,7 +68,7 @@ pub trait FromRow: Sized {
     fn from_row(row: &rusqlite::Row) -> rusqlite::Result<Self>;
 }
 
-const LATEST_VERSION: usize = 23;
+const LATEST_VERSION: usize = 40;
 const MIGRATIONS: [&str; LATEST_VERSION] = [
     include_str!("migrations/1.sql"),
     include_str!("migrations/2.sql"),
@@ -89,6 +93,23 @@ const MIGRATIONS: [&str; LATEST_VERSION] = [
     include_str!("migrations/21.sql"),
     include_str!("migrations/22.sql"),
     include_str!("migrations/23.sql"),
+    include_str!("migrations/24.sql"),
+    include_str!("migrations/25.sql"),
+    include_str!("migrations/26.sql"),
+    include_str!("migrations/27.sql"),
+    include_str!("migrations/28.sql"),
+    include_str!("migrations/29.sql"),
+    include_str!("migrations/30.sql"),
+    include_str!("migrations/31.sql"),
+    include_str!("migrations/32.sql"),
+    include_str!("migrations/33.sql"),
+    include_str!("migrations/34.sql"),
+    include_str!("migrations/35.sql"),
+    include_str!("migrations/36.sql"),
+    include_str!("migrations/37.sql"),
+    include_str!("migrations/38.sql"),
+    include_str!("migrations/39.sql"),
+    include_str!("migrations/40.sql"),
 ];
 
 use futures::{FutureExt, future::BoxFuture};
@@ -120,6 +141,23 @@ const POST_MIGRATION_HOOKS: [Option<PostMigrationHook>; LATEST_VERSION] = [
     None,                                                                          // Migration 21
     None,                                                                          // Migration 22
     None,                                                                          // Migration 23
+    None,                                                                          // Migration 24
+    None,                                                                          // Migration 25
+    None,                                                                          // Migration 26
+    None,                                                                          // Migration 27
+    None,                                                                          // Migration 28
+    None,                                                                          // Migration 29
+    None,                                                                          // Migration 30
+    None,                                                                          // Migration 31
+    None,                                                                          // Migration 32
+    None,                                                                          // Migration 33
+    None,                                                                          // Migration 34
+    None,                                                                          // Migration 35
+    None,                                                                          // Migration 36
+    None,                                                                          // Migration 37
+    None,                                                                          // Migration 38
+    None,                                                                          // Migration 39
+    None,                                                                          // Migration 40
 ];
 
 /// Pre-migration hooks run Rust code BEFORE the SQL for each migration version.
@@ -149,6 +187,23 @@ const PRE_MIGRATION_HOOKS: [Option<PreMigrationHook>; LATEST_VERSION] = [
     None,                                                                     // Migration 21
     None,                                                                     // Migration 22
     None,                                                                     // Migration 23
+    None,                                                                     // Migration 24
+    None,                                                                     // Migration 25
+    None,                                                                     // Migration 26
+    None,                                                                     // Migration 27
+    None,                                                                     // Migration 28
+    None,                                                                     // Migration 29
+    None,                                                                     // Migration 30
+    None,                                                                     // Migration 31
+    None,                                                                     // Migration 32
+    None,                                                                     // Migration 33
+    None,                                                                     // Migration 34
+    None,                                                                     // Migration 35
+    None,                                                                     // Migration 36
+    None,                                                                     // Migration 37
+    None,                                                                     // Migration 38
+    None,                                                                     // Migration 39
+    None,                                                                     // Migration 40
 ];
 
 /// Run all pending database migrations against the database opened by `factory`.
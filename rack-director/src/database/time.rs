@@ -0,0 +1,103 @@
+//! Timestamp encoding for database columns.
+//!
+//! Every timestamp column is stored as RFC 3339 UTC text (e.g.
+//! `"2026-10-17T12:34:56.123456789+00:00"`), written by the application rather
+//! than by SQLite defaults, so values from different tables compare correctly as
+//! text and in joins. Rows written before migration 27 may still hold SQLite's
+//! `"YYYY-MM-DD HH:MM:SS"` form; [`from_db_time`] accepts both.
+
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, NaiveDateTime, Utc};
+
+/// Encode `time` for storage in a timestamp column.
+pub fn to_db_time(time: DateTime<Utc>) -> String {
+    time.to_rfc3339()
+}
+
+/// Decode a timestamp column value.
+///
+/// Accepts RFC 3339 with any offset, plus SQLite's `CURRENT_TIMESTAMP` and
+/// rusqlite's space-separated formats, which are treated as UTC.
+pub fn from_db_time(s: &str) -> Result<DateTime<Utc>> {
+    if let Ok(dt) = DateTime::parse_from_rfc3339(s) {
+        return Ok(dt.with_timezone(&Utc));
+    }
+    // rusqlite's chrono encoding: "YYYY-MM-DD HH:MM:SS.fff+00:00"
+    if let Ok(dt) = DateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S%.f%:z") {
+        return Ok(dt.with_timezone(&Utc));
+    }
+    // SQLite CURRENT_TIMESTAMP / datetime('now'): "YYYY-MM-DD HH:MM:SS"
+    if let Ok(dt) = NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S%.f") {
+        return Ok(dt.and_utc());
+    }
+    Err(anyhow!("Failed to parse datetime: {}", s))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{database, test_connection_factory};
+    use chrono::Duration;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_round_trip() {
+        let now = Utc::now();
+        assert_eq!(from_db_time(&to_db_time(now)).unwrap(), now);
+    }
+
+    #[test]
+    fn test_from_db_time_accepts_legacy_formats() {
+        let expected = DateTime::parse_from_rfc3339("2026-06-10T12:34:56Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        assert_eq!(from_db_time("2026-06-10 12:34:56").unwrap(), expected);
+        assert_eq!(
+            from_db_time("2026-06-10 12:34:56.000+00:00").unwrap(),
+            expected
+        );
+        assert_eq!(from_db_time("2026-06-10T14:34:56+02:00").unwrap(), expected);
+        assert!(from_db_time("0").is_err());
+        assert!(from_db_time("").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_cross_table_comparison() {
+        let factory = test_connection_factory!();
+        let conn = database::run_migrations(&factory).await.unwrap();
+
+        let uuid = Uuid::parse_str("d6000000-0000-0000-0000-000000000001").unwrap();
+        let seen = Utc::now();
+        conn.execute(
+            "INSERT INTO devices (uuid, lifecycle, architecture, created_at, last_seen_at)
+             VALUES (?1, 'new', 'x86-64', ?2, ?2)",
+            (uuid, to_db_time(seen)),
+        )
+        .await
+        .unwrap();
+
+        let lease_start = seen + Duration::seconds(5);
+        conn.execute(
+            "INSERT INTO dhcp_leases (mac_address, ip_address, device_uuid, lease_start, lease_end, state)
+             VALUES ('aa:bb:cc:dd:ee:01', '10.0.0.5', ?1, ?2, ?3, 'active')",
+            (
+                uuid,
+                to_db_time(lease_start),
+                to_db_time(lease_start + Duration::hours(1)),
+            ),
+        )
+        .await
+        .unwrap();
+
+        let seen_before_lease: bool = conn
+            .query_one(
+                "SELECT d.last_seen_at < l.lease_start
+                 FROM devices d JOIN dhcp_leases l ON l.device_uuid = d.uuid",
+                (),
+                |row| row.get(0),
+            )
+            .await
+            .unwrap();
+        assert!(seen_before_lease);
+    }
+}
@@ -0,0 +1,12 @@
+//! Hardware inventory reported by the discovery image.
+//!
+//! The discovery ramdisk posts what it finds (CPUs, memory, disks, NICs) to
+//! `/cnc/inventory`; the latest document per device is kept and served back to
+//! operators at `GET /api/devices/{uuid}/inventory`. The NICs it lists are checked
+//! against the interfaces DHCP has seen (see [`reconcile_nics`]).
+
+mod reconcile;
+mod store;
+
+pub use reconcile::reconcile_nics;
+pub use store::{DeviceInventory, Inventory, get_inventory, save_inventory};
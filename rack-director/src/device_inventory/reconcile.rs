@@ -0,0 +1,231 @@
+//! Checking the NICs an inventory reports against the interfaces DHCP has seen.
+//!
+//! A NIC the OS sees that never sent a DHCP packet usually means a cabling or
+//! switch problem; an interface DHCP saw that the OS doesn't list usually means a
+//! missing driver. Each is recorded as a [`DeviceWarning`](crate::device_warnings::DeviceWarning),
+//! replacing those from the previous submission, so it shows up wherever warnings do.
+
+use std::collections::BTreeSet;
+
+use anyhow::Result;
+use uuid::Uuid;
+
+use super::Inventory;
+use crate::{database::Connection, device_warnings, director};
+
+/// Warning code for a NIC the OS reports but DHCP has never seen.
+pub const NIC_NOT_SEEN_BY_DHCP: &str = "NIC_NOT_SEEN_BY_DHCP";
+/// Warning code for an interface DHCP has seen but the OS doesn't report.
+pub const NIC_MISSING_FROM_OS: &str = "NIC_MISSING_FROM_OS";
+
+/// A disagreement between the inventory and DHCP about one MAC address.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NicDiscrepancy {
+    /// Reported by the OS, never seen by DHCP.
+    NotSeenByDhcp(String),
+    /// Seen by DHCP, not reported by the OS.
+    MissingFromOs(String),
+}
+
+impl NicDiscrepancy {
+    fn code(&self) -> &'static str {
+        match self {
+            NicDiscrepancy::NotSeenByDhcp(_) => NIC_NOT_SEEN_BY_DHCP,
+            NicDiscrepancy::MissingFromOs(_) => NIC_MISSING_FROM_OS,
+        }
+    }
+
+    fn message(&self) -> String {
+        match self {
+            NicDiscrepancy::NotSeenByDhcp(mac) => {
+                format!("NIC {mac} is present in the OS but has never sent a DHCP request")
+            }
+            NicDiscrepancy::MissingFromOs(mac) => {
+                format!("Interface {mac} was seen by DHCP but is not present in the OS")
+            }
+        }
+    }
+}
+
+/// Lowercase `mac` and use `:` separators, the form DHCP records MACs in.
+fn normalize_mac(mac: &str) -> String {
+    mac.trim().to_ascii_lowercase().replace('-', ":")
+}
+
+/// Compare the MACs the OS reports with those DHCP has seen, in MAC order.
+fn diff_nics(os_macs: &BTreeSet<String>, dhcp_macs: &BTreeSet<String>) -> Vec<NicDiscrepancy> {
+    let not_seen = os_macs
+        .difference(dhcp_macs)
+        .map(|mac| NicDiscrepancy::NotSeenByDhcp(mac.clone()));
+    let missing = dhcp_macs
+        .difference(os_macs)
+        .map(|mac| NicDiscrepancy::MissingFromOs(mac.clone()));
+    not_seen.chain(missing).collect()
+}
+
+/// Check `inventory`'s NICs against the interfaces DHCP has seen for the device and
+/// replace its NIC warnings with one per discrepancy.
+///
+/// An inventory without NICs is not checked, and leaves earlier warnings in place.
+pub async fn reconcile_nics(
+    conn: &Connection,
+    device_id: i64,
+    uuid: &Uuid,
+    inventory: &Inventory,
+) -> Result<Vec<NicDiscrepancy>> {
+    if inventory.nics.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let os_macs: BTreeSet<String> = inventory
+        .nics
+        .iter()
+        .map(|nic| normalize_mac(&nic.mac))
+        .collect();
+    let dhcp_macs: BTreeSet<String> = director::store::get_interface_last_seen(conn, uuid)
+        .await?
+        .into_keys()
+        .map(|mac| normalize_mac(&mac))
+        .collect();
+    let discrepancies = diff_nics(&os_macs, &dhcp_macs);
+
+    for code in [NIC_NOT_SEEN_BY_DHCP, NIC_MISSING_FROM_OS] {
+        device_warnings::delete_warnings_with_code(conn, device_id, code).await?;
+    }
+    for discrepancy in &discrepancies {
+        let message = discrepancy.message();
+        device_warnings::create_warning(conn, device_id, discrepancy.code(), &message).await?;
+        log::warn!("device {}: {}", uuid, message);
+    }
+    Ok(discrepancies)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device_inventory::store::{InventoryNic, save_inventory};
+    use crate::{database, test_connection_factory};
+
+    const UUID: &str = "e6000000-0000-0000-0000-000000000001";
+    const MAC_A: &str = "aa:bb:cc:00:00:01";
+    const MAC_B: &str = "aa:bb:cc:00:00:02";
+
+    /// A device whose interfaces `dhcp_macs` have been seen by DHCP.
+    async fn setup(
+        factory: database::DatabaseConnectionFactory,
+        dhcp_macs: &[&str],
+    ) -> (database::Connection, i64, Uuid) {
+        let conn = database::run_migrations(&factory).await.unwrap();
+        let uuid = Uuid::parse_str(UUID).unwrap();
+        conn.execute(
+            "INSERT INTO devices (uuid, lifecycle, architecture) VALUES (?1, 'new', 'x86-64')",
+            (uuid,),
+        )
+        .await
+        .unwrap();
+        for mac in dhcp_macs {
+            director::store::touch_interface(&conn, &uuid, mac)
+                .await
+                .unwrap();
+        }
+        let device_id = device_warnings::get_device_id_by_uuid(&conn, &uuid)
+            .await
+            .unwrap()
+            .unwrap();
+        (conn, device_id, uuid)
+    }
+
+    fn inventory(macs: &[&str]) -> Inventory {
+        Inventory {
+            nics: macs
+                .iter()
+                .map(|mac| InventoryNic {
+                    mac: mac.to_string(),
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    async fn warning_codes(conn: &database::Connection, device_id: i64) -> Vec<String> {
+        device_warnings::list_warnings(conn, device_id)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|w| w.code)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_matching_nics_have_no_discrepancies() {
+        let (conn, device_id, uuid) = setup(test_connection_factory!(), &[MAC_A, MAC_B]).await;
+
+        // The OS may report MACs in another case and with dashes
+        let inventory = inventory(&["AA-BB-CC-00-00-01", MAC_B]);
+        let found = reconcile_nics(&conn, device_id, &uuid, &inventory)
+            .await
+            .unwrap();
+        assert!(found.is_empty(), "{found:?}");
+        assert!(warning_codes(&conn, device_id).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_extra_os_nic_is_flagged() {
+        let (conn, device_id, uuid) = setup(test_connection_factory!(), &[MAC_A]).await;
+
+        let found = reconcile_nics(&conn, device_id, &uuid, &inventory(&[MAC_A, MAC_B]))
+            .await
+            .unwrap();
+        assert_eq!(
+            found,
+            vec![NicDiscrepancy::NotSeenByDhcp(MAC_B.to_string())]
+        );
+        assert_eq!(
+            warning_codes(&conn, device_id).await,
+            [NIC_NOT_SEEN_BY_DHCP]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_interface_missing_from_os_is_flagged() {
+        let (conn, device_id, uuid) = setup(test_connection_factory!(), &[MAC_A, MAC_B]).await;
+
+        let found = reconcile_nics(&conn, device_id, &uuid, &inventory(&[MAC_A]))
+            .await
+            .unwrap();
+        assert_eq!(
+            found,
+            vec![NicDiscrepancy::MissingFromOs(MAC_B.to_string())]
+        );
+        assert_eq!(warning_codes(&conn, device_id).await, [NIC_MISSING_FROM_OS]);
+    }
+
+    #[tokio::test]
+    async fn test_resubmission_replaces_nic_warnings() {
+        let (conn, device_id, uuid) = setup(test_connection_factory!(), &[MAC_A]).await;
+        device_warnings::create_warning(&conn, device_id, "LABEL_OVERRIDE_DROPPED", "kept")
+            .await
+            .unwrap();
+
+        reconcile_nics(&conn, device_id, &uuid, &inventory(&[MAC_A, MAC_B]))
+            .await
+            .unwrap();
+        // The cable is fixed and MAC_B shows up in DHCP
+        director::store::touch_interface(&conn, &uuid, MAC_B)
+            .await
+            .unwrap();
+        let inventory = inventory(&[MAC_A, MAC_B]);
+        save_inventory(&conn, device_id, &inventory, chrono::Utc::now())
+            .await
+            .unwrap();
+        reconcile_nics(&conn, device_id, &uuid, &inventory)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            warning_codes(&conn, device_id).await,
+            ["LABEL_OVERRIDE_DROPPED"]
+        );
+    }
+}
@@ -0,0 +1,180 @@
+//! Database access for device inventory.
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use rusqlite::OptionalExtension;
+use serde::{Deserialize, Serialize};
+
+use crate::database::{Connection, FromRow, from_db_time, to_db_time};
+
+/// A hardware inventory document as submitted by the discovery image.
+///
+/// `serial` and `model` are stored in indexed columns and `nics` is checked against
+/// the interfaces DHCP has seen. Everything else (conventionally `cpus`,
+/// `memory_bytes` and `disks`) is kept exactly as submitted.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Inventory {
+    /// Chassis or system serial number.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub serial: Option<String>,
+    /// System model name.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// Network interfaces the OS sees.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub nics: Vec<InventoryNic>,
+    /// The rest of the document.
+    #[serde(flatten)]
+    pub details: serde_json::Map<String, serde_json::Value>,
+}
+
+/// A network interface in an [`Inventory`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct InventoryNic {
+    /// MAC address, in any case, with `:` or `-` separators.
+    pub mac: String,
+    /// The rest of the entry (name, driver, speed, ...).
+    #[serde(flatten)]
+    pub details: serde_json::Map<String, serde_json::Value>,
+}
+
+/// The latest inventory stored for a device.
+#[derive(Debug, Clone, Serialize)]
+pub struct DeviceInventory {
+    /// When the director received the document (UTC).
+    pub collected_at: DateTime<Utc>,
+    pub inventory: Inventory,
+}
+
+impl FromRow for DeviceInventory {
+    fn from_row(row: &rusqlite::Row) -> rusqlite::Result<Self> {
+        let json: String = row.get("json")?;
+        let inventory = serde_json::from_str(&json).map_err(|e| {
+            rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, Box::new(e))
+        })?;
+        let collected_at: String = row.get("collected_at")?;
+        Ok(DeviceInventory {
+            collected_at: from_db_time(&collected_at).unwrap_or_else(|_| Utc::now()),
+            inventory,
+        })
+    }
+}
+
+/// Store `inventory` for the device with row id `device_id`, replacing any earlier one.
+pub async fn save_inventory(
+    conn: &Connection,
+    device_id: i64,
+    inventory: &Inventory,
+    collected_at: DateTime<Utc>,
+) -> Result<()> {
+    conn.execute(
+        "INSERT INTO device_inventory (device_id, json, serial, model, collected_at) \
+         VALUES (?1, ?2, ?3, ?4, ?5) \
+         ON CONFLICT(device_id) DO UPDATE SET \
+             json = ?2, serial = ?3, model = ?4, collected_at = ?5",
+        (
+            device_id,
+            serde_json::to_string(inventory)?,
+            inventory.serial.clone(),
+            inventory.model.clone(),
+            to_db_time(collected_at),
+        ),
+    )
+    .await?;
+    Ok(())
+}
+
+/// The latest inventory for the device with row id `device_id`, if one was submitted.
+pub async fn get_inventory(conn: &Connection, device_id: i64) -> Result<Option<DeviceInventory>> {
+    let inventory = conn
+        .query_row(
+            "SELECT json, collected_at FROM device_inventory WHERE device_id = ?1",
+            (device_id,),
+            DeviceInventory::from_row,
+        )
+        .await
+        .optional()?;
+    Ok(inventory)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    use crate::{database, test_connection_factory};
+
+    async fn setup(factory: database::DatabaseConnectionFactory) -> (database::Connection, i64) {
+        let conn = database::run_migrations(&factory).await.unwrap();
+        let uuid = Uuid::parse_str("e5000000-0000-0000-0000-000000000001").unwrap();
+        conn.execute(
+            "INSERT INTO devices (uuid, lifecycle, architecture) VALUES (?1, 'new', 'x86-64')",
+            (uuid,),
+        )
+        .await
+        .unwrap();
+        let device_id: i64 = conn
+            .query_one("SELECT id FROM devices WHERE uuid = ?1", (uuid,), |r| {
+                r.get(0)
+            })
+            .await
+            .unwrap();
+        (conn, device_id)
+    }
+
+    fn inventory(json: serde_json::Value) -> Inventory {
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_save_and_get_inventory() {
+        let (conn, device_id) = setup(test_connection_factory!()).await;
+        assert!(get_inventory(&conn, device_id).await.unwrap().is_none());
+
+        let submitted = inventory(serde_json::json!({
+            "serial": "SN123",
+            "model": "PowerEdge R650",
+            "memory_bytes": 68719476736u64,
+            "cpus": [{"model": "Xeon Gold 6338", "cores": 32}],
+        }));
+        save_inventory(&conn, device_id, &submitted, Utc::now())
+            .await
+            .unwrap();
+
+        let stored = get_inventory(&conn, device_id).await.unwrap().unwrap();
+        assert_eq!(stored.inventory, submitted);
+        assert_eq!(stored.inventory.details["cpus"][0]["cores"], 32);
+        let serial: String = conn
+            .query_one(
+                "SELECT serial FROM device_inventory WHERE device_id = ?1",
+                (device_id,),
+                |r| r.get(0),
+            )
+            .await
+            .unwrap();
+        assert_eq!(serial, "SN123");
+    }
+
+    #[tokio::test]
+    async fn test_save_inventory_replaces_previous() {
+        let (conn, device_id) = setup(test_connection_factory!()).await;
+
+        let first = inventory(serde_json::json!({"serial": "SN1", "model": "A"}));
+        save_inventory(&conn, device_id, &first, Utc::now())
+            .await
+            .unwrap();
+        let second = inventory(serde_json::json!({"model": "B"}));
+        save_inventory(&conn, device_id, &second, Utc::now())
+            .await
+            .unwrap();
+
+        let stored = get_inventory(&conn, device_id).await.unwrap().unwrap();
+        assert_eq!(stored.inventory.model.as_deref(), Some("B"));
+        assert_eq!(stored.inventory.serial, None);
+        let rows: i64 = conn
+            .query_one("SELECT COUNT(*) FROM device_inventory", (), |r| r.get(0))
+            .await
+            .unwrap();
+        assert_eq!(rows, 1);
+    }
+}
@@ -0,0 +1,393 @@
+//! Finding devices by whatever identifier an operator has at hand.
+//!
+//! In the field a machine is known by a MAC on a sticker, the IP on a console, its
+//! hostname, the start of its UUID or the service tag on the chassis. [`search_devices`]
+//! tries one query string against all of them at once and returns each matching
+//! device once, best match first.
+
+use std::net::IpAddr;
+
+use anyhow::Result;
+use chrono::Utc;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{database::Connection, lifecycle::DeviceLifecycle};
+
+/// Most devices a search returns.
+pub const MAX_SEARCH_RESULTS: usize = 50;
+
+/// Which identifier a search matched.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchField {
+    Uuid,
+    Mac,
+    Ip,
+    Hostname,
+    Serial,
+}
+
+impl MatchField {
+    fn from_sql(field: &str) -> Option<Self> {
+        match field {
+            "uuid" => Some(Self::Uuid),
+            "mac" => Some(Self::Mac),
+            "ip" => Some(Self::Ip),
+            "hostname" => Some(Self::Hostname),
+            "serial" => Some(Self::Serial),
+            _ => None,
+        }
+    }
+}
+
+/// A device matching a search, with the identifier that matched it best.
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchHit {
+    pub uuid: Uuid,
+    pub hostname: Option<String>,
+    pub lifecycle: Option<DeviceLifecycle>,
+    pub matched: MatchField,
+    /// The matched identifier as stored, e.g. the MAC in its recorded form.
+    pub value: String,
+}
+
+/// The query in each form it can be compared in; `None` where it can't be one.
+#[derive(Debug, Default, PartialEq)]
+struct SearchTerms {
+    /// Lowercase hex digits of a UUID prefix.
+    uuid_prefix: Option<String>,
+    /// A full MAC, lowercase and `:`-separated.
+    mac: Option<String>,
+    /// An IP address in canonical form.
+    ip: Option<String>,
+    /// The query as typed, for exact hostname and serial matches.
+    text: String,
+    /// The query with `LIKE` wildcards escaped, for hostname prefix and substring matches.
+    like: String,
+}
+
+impl SearchTerms {
+    fn parse(query: &str) -> Self {
+        let query = query.trim();
+        let hex: String = query.chars().filter(|&c| c != '-').collect();
+        Self {
+            uuid_prefix: (!hex.is_empty()
+                && hex.len() <= 32
+                && hex.chars().all(|c| c.is_ascii_hexdigit()))
+            .then(|| hex.to_ascii_lowercase()),
+            mac: normalize_mac(query),
+            ip: query.parse::<IpAddr>().ok().map(|ip| ip.to_string()),
+            text: query.to_string(),
+            like: escape_like(query),
+        }
+    }
+}
+
+/// `mac` as lowercase `aa:bb:cc:dd:ee:ff`, accepting `:`, `-` or `.` separators or
+/// none at all, or `None` if it isn't a 48-bit MAC.
+fn normalize_mac(mac: &str) -> Option<String> {
+    let digits: String = mac
+        .chars()
+        .filter(|c| !matches!(c, ':' | '-' | '.'))
+        .collect();
+    if digits.len() != 12 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let digits = digits.to_ascii_lowercase();
+    let octets: Vec<&str> = (0..12).step_by(2).map(|i| &digits[i..i + 2]).collect();
+    Some(octets.join(":"))
+}
+
+/// Escape `LIKE` wildcards (and the escape character) in `s`.
+fn escape_like(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Every (device, rank, field, value) the terms match. Rank 0 is an exact match,
+/// 1 a UUID or hostname prefix and 2 a hostname substring.
+const SEARCH_SQL: &str = "
+    WITH hits(device_id, rank, field, value) AS (
+        SELECT id, CASE WHEN lower(hex(uuid)) = ?1 THEN 0 ELSE 1 END, 'uuid', NULL
+          FROM devices
+         WHERE ?1 IS NOT NULL AND lower(hex(uuid)) LIKE ?1 || '%'
+        UNION ALL
+        SELECT d.id, 0, 'mac', json_extract(iface.value, '$.mac_address')
+          FROM devices d, json_each(d.attributes, '$.network_interfaces') iface
+         WHERE ?2 IS NOT NULL
+           AND lower(replace(json_extract(iface.value, '$.mac_address'), '-', ':')) = ?2
+        UNION ALL
+        SELECT device_id, 0, 'mac', mac_address
+          FROM interface_activity
+         WHERE ?2 IS NOT NULL AND lower(mac_address) = ?2
+        UNION ALL
+        SELECT d.id, 0, 'ip', l.ip_address
+          FROM dhcp_leases l JOIN devices d ON d.uuid = l.device_uuid
+         WHERE ?3 IS NOT NULL AND l.ip_address = ?3 AND l.state = 'active' AND l.lease_end > ?6
+        UNION ALL
+        SELECT d.id, 0, 'ip', json_extract(iface.value, '$.ip_address')
+          FROM devices d, json_each(d.attributes, '$.network_interfaces') iface
+         WHERE ?3 IS NOT NULL AND json_extract(iface.value, '$.ip_address') = ?3
+        UNION ALL
+        SELECT id,
+               CASE WHEN lower(hostname) = lower(?4) THEN 0
+                    WHEN hostname LIKE ?5 || '%' ESCAPE '\\' THEN 1
+                    ELSE 2 END,
+               'hostname', hostname
+          FROM (SELECT id, json_extract(attributes, '$.hostname') AS hostname FROM devices)
+         WHERE hostname LIKE '%' || ?5 || '%' ESCAPE '\\'
+        UNION ALL
+        SELECT device_id, 0, 'serial', serial
+          FROM device_inventory
+         WHERE lower(serial) = lower(?4)
+    )
+    SELECT d.uuid, json_extract(d.attributes, '$.hostname') AS hostname, d.lifecycle,
+           h.field, h.value
+      FROM hits h JOIN devices d ON d.id = h.device_id
+     ORDER BY h.rank, d.id";
+
+/// Devices whose UUID starts with `query`, or with a MAC, IP address, hostname or
+/// serial number matching it.
+///
+/// MACs match in any common notation and IPs against unexpired leases and recorded
+/// interface addresses; hostnames match exactly, by prefix or as a substring, all
+/// case-insensitively. Each device is listed once, under its best match, exact
+/// matches first. At most [`MAX_SEARCH_RESULTS`] devices are returned; an empty
+/// query matches nothing.
+pub async fn search_devices(conn: &Connection, query: &str) -> Result<Vec<SearchHit>> {
+    let terms = SearchTerms::parse(query);
+    if terms.text.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let rows = conn
+        .query(
+            SEARCH_SQL,
+            (
+                terms.uuid_prefix,
+                terms.mac,
+                terms.ip,
+                terms.text,
+                terms.like,
+                Utc::now().to_rfc3339(),
+            ),
+            |row| {
+                let uuid: Uuid = row.get("uuid")?;
+                let lifecycle: Option<String> = row.get("lifecycle")?;
+                let field: String = row.get("field")?;
+                let value: Option<String> = row.get("value")?;
+                Ok((
+                    uuid,
+                    row.get::<_, Option<String>>("hostname")?,
+                    lifecycle.map(DeviceLifecycle::from),
+                    field,
+                    value,
+                ))
+            },
+        )
+        .await?;
+
+    let mut hits: Vec<SearchHit> = Vec::new();
+    for (uuid, hostname, lifecycle, field, value) in rows {
+        if hits.iter().any(|hit| hit.uuid == uuid) {
+            continue;
+        }
+        let Some(matched) = MatchField::from_sql(&field) else {
+            continue;
+        };
+        hits.push(SearchHit {
+            uuid,
+            hostname,
+            lifecycle,
+            matched,
+            value: value.unwrap_or_else(|| uuid.to_string()),
+        });
+        if hits.len() == MAX_SEARCH_RESULTS {
+            break;
+        }
+    }
+    Ok(hits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        database, device_inventory, device_warnings,
+        dhcp::store::{self as dhcp_store, LeaseState},
+        director::{self, Architecture, Director, NetworkInterface},
+        test_connection_factory,
+    };
+
+    const UUID_A: &str = "e7000000-0000-0000-0000-0000000000a1";
+    const UUID_B: &str = "e7100000-0000-0000-0000-0000000000b2";
+
+    /// Two registered devices (hostnames `node-0000000000a1` and `node-0000000000b2`);
+    /// A has an interface DHCP has seen and an active lease, B a serial number.
+    async fn setup(factory: database::DatabaseConnectionFactory) -> database::Connection {
+        let conn = database::run_migrations(&factory).await.unwrap();
+        let director = Director::new(&conn);
+        let (a, b) = (
+            Uuid::parse_str(UUID_A).unwrap(),
+            Uuid::parse_str(UUID_B).unwrap(),
+        );
+        for uuid in [&a, &b] {
+            director
+                .register_device(uuid, Architecture::X86_64)
+                .await
+                .unwrap();
+        }
+
+        let network = dhcp_store::create_network(
+            &conn,
+            "Rack",
+            "10.0.0.0/24",
+            "10.0.0.1",
+            &[],
+            3600,
+            None,
+            false,
+        )
+        .await
+        .unwrap();
+        director::store::set_network_interfaces(
+            &conn,
+            &a,
+            &[NetworkInterface {
+                interface_name: "eth0".to_string(),
+                mac_address: "aa:bb:cc:00:00:01".to_string(),
+                network_id: Some(network.id),
+                ..Default::default()
+            }],
+        )
+        .await
+        .unwrap();
+        director::store::touch_interface(&conn, &a, "aa:bb:cc:00:00:01")
+            .await
+            .unwrap();
+        dhcp_store::create_or_update_lease_with_network(
+            &conn,
+            "aa:bb:cc:00:00:01",
+            &"10.0.0.42".parse().unwrap(),
+            Some(&a),
+            LeaseState::Active,
+            3600,
+            network.id,
+        )
+        .await
+        .unwrap();
+
+        let b_id = device_warnings::get_device_id_by_uuid(&conn, &b)
+            .await
+            .unwrap()
+            .unwrap();
+        let inventory = device_inventory::Inventory {
+            serial: Some("SVC7TAG".to_string()),
+            ..Default::default()
+        };
+        device_inventory::save_inventory(&conn, b_id, &inventory, Utc::now())
+            .await
+            .unwrap();
+        conn
+    }
+
+    async fn search(conn: &database::Connection, query: &str) -> Vec<(String, MatchField)> {
+        search_devices(conn, query)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|hit| (hit.uuid.to_string(), hit.matched))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_search_by_uuid_prefix() {
+        let conn = setup(test_connection_factory!()).await;
+        assert_eq!(
+            search(&conn, "E71").await,
+            vec![(UUID_B.to_string(), MatchField::Uuid)]
+        );
+        // Hyphens are ignored
+        assert_eq!(
+            search(&conn, "e7000000-00").await,
+            vec![(UUID_A.to_string(), MatchField::Uuid)]
+        );
+        assert_eq!(search(&conn, "e7").await.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_search_by_mac_in_any_notation() {
+        let conn = setup(test_connection_factory!()).await;
+        // The MAC is both in A's interfaces and its DHCP activity; A is listed once
+        for query in ["aa:bb:cc:00:00:01", "AA-BB-CC-00-00-01", "aabb.cc00.0001"] {
+            let hits = search_devices(&conn, query).await.unwrap();
+            assert_eq!(hits.len(), 1, "{query}");
+            assert_eq!(hits[0].uuid.to_string(), UUID_A);
+            assert_eq!(hits[0].matched, MatchField::Mac);
+            assert_eq!(hits[0].value, "aa:bb:cc:00:00:01");
+        }
+        assert!(search(&conn, "aa:bb:cc:00:00:02").await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_search_by_lease_ip() {
+        let conn = setup(test_connection_factory!()).await;
+        assert_eq!(
+            search(&conn, "10.0.0.42").await,
+            vec![(UUID_A.to_string(), MatchField::Ip)]
+        );
+        assert!(search(&conn, "10.0.0.43").await.is_empty());
+
+        // An expired lease no longer places the device at its address
+        conn.execute(
+            "UPDATE dhcp_leases SET lease_end = ?1",
+            ((Utc::now() - chrono::Duration::hours(1)).to_rfc3339(),),
+        )
+        .await
+        .unwrap();
+        assert!(search(&conn, "10.0.0.42").await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_search_by_hostname_ranks_exact_first() {
+        let conn = setup(test_connection_factory!()).await;
+        let hits = search_devices(&conn, "NODE-0000000000B2").await.unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].uuid.to_string(), UUID_B);
+        assert_eq!(hits[0].matched, MatchField::Hostname);
+        assert_eq!(hits[0].hostname.as_deref(), Some("node-0000000000b2"));
+
+        // A substring matches both; `_` is not a wildcard
+        assert_eq!(search(&conn, "node").await.len(), 2);
+        assert!(search(&conn, "node_").await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_search_by_serial() {
+        let conn = setup(test_connection_factory!()).await;
+        assert_eq!(
+            search(&conn, "svc7tag").await,
+            vec![(UUID_B.to_string(), MatchField::Serial)]
+        );
+        assert!(search(&conn, "   ").await.is_empty());
+    }
+
+    #[test]
+    fn test_search_terms() {
+        let terms = SearchTerms::parse(" AABB-CCDD-EEFF ");
+        assert_eq!(terms.mac.as_deref(), Some("aa:bb:cc:dd:ee:ff"));
+        assert_eq!(terms.uuid_prefix.as_deref(), Some("aabbccddeeff"));
+        assert_eq!(terms.ip, None);
+
+        let terms = SearchTerms::parse("node-1_%");
+        assert_eq!(terms.uuid_prefix, None);
+        assert_eq!(terms.like, "node-1\\_\\%");
+    }
+}
@@ -0,0 +1,8 @@
+//! Device tag CRUD operations.
+//!
+//! Tags are operator-assigned `key=value` labels (e.g. `role=compute`) used to group
+//! devices. A device has at most one value per key.
+
+mod store;
+
+pub use store::{DeviceTag, device_uuids_with_tag, list_tags, remove_tag, set_tag};
@@ -0,0 +1,156 @@
+//! Database access for device tags.
+
+use anyhow::Result;
+use chrono::Utc;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::database::{Connection, FromRow, to_db_time};
+
+/// A single `key=value` tag on a device.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DeviceTag {
+    pub key: String,
+    pub value: String,
+}
+
+impl FromRow for DeviceTag {
+    fn from_row(row: &rusqlite::Row) -> rusqlite::Result<Self> {
+        Ok(DeviceTag {
+            key: row.get("key")?,
+            value: row.get("value")?,
+        })
+    }
+}
+
+/// Set `key` to `value` on the device with integer row `device_id`, replacing any
+/// existing value for that key.
+pub async fn set_tag(conn: &Connection, device_id: i64, key: &str, value: &str) -> Result<()> {
+    conn.execute(
+        "INSERT INTO device_tags (device_id, key, value, created_at) VALUES (?1, ?2, ?3, ?4)
+         ON CONFLICT(device_id, key) DO UPDATE SET value = ?3",
+        (
+            device_id,
+            key.to_string(),
+            value.to_string(),
+            to_db_time(Utc::now()),
+        ),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Remove `key` from the device. Returns `false` if the device had no such tag.
+pub async fn remove_tag(conn: &Connection, device_id: i64, key: &str) -> Result<bool> {
+    let rows_affected = conn
+        .execute(
+            "DELETE FROM device_tags WHERE device_id = ?1 AND key = ?2",
+            (device_id, key.to_string()),
+        )
+        .await?;
+    Ok(rows_affected > 0)
+}
+
+/// List the device's tags ordered by key.
+pub async fn list_tags(conn: &Connection, device_id: i64) -> Result<Vec<DeviceTag>> {
+    let tags = conn
+        .query(
+            "SELECT key, value FROM device_tags WHERE device_id = ?1 ORDER BY key",
+            (device_id,),
+            DeviceTag::from_row,
+        )
+        .await?;
+    Ok(tags)
+}
+
+/// UUIDs of every device tagged `key=value`.
+pub async fn device_uuids_with_tag(conn: &Connection, key: &str, value: &str) -> Result<Vec<Uuid>> {
+    let uuids = conn
+        .query(
+            "SELECT d.uuid FROM devices d
+             JOIN device_tags t ON t.device_id = d.id
+             WHERE t.key = ?1 AND t.value = ?2
+             ORDER BY d.id",
+            (key.to_string(), value.to_string()),
+            |row| row.get(0),
+        )
+        .await?;
+    Ok(uuids)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{database, test_connection_factory};
+
+    async fn insert_device(conn: &Connection, uuid: &str) -> i64 {
+        let uuid = Uuid::parse_str(uuid).unwrap();
+        conn.execute(
+            "INSERT INTO devices (uuid, lifecycle, architecture) VALUES (?1, 'new', 'x86-64')",
+            (uuid,),
+        )
+        .await
+        .unwrap();
+        conn.query_one("SELECT id FROM devices WHERE uuid = ?1", (uuid,), |r| {
+            r.get(0)
+        })
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_set_list_and_remove_tags() {
+        let factory = test_connection_factory!();
+        let conn = database::run_migrations(&factory).await.unwrap();
+        let device_id = insert_device(&conn, "a7000000-0000-0000-0000-000000000001").await;
+
+        set_tag(&conn, device_id, "role", "storage").await.unwrap();
+        set_tag(&conn, device_id, "rack", "r12").await.unwrap();
+        set_tag(&conn, device_id, "role", "compute").await.unwrap();
+
+        let tags = list_tags(&conn, device_id).await.unwrap();
+        assert_eq!(
+            tags,
+            vec![
+                DeviceTag {
+                    key: "rack".to_string(),
+                    value: "r12".to_string()
+                },
+                DeviceTag {
+                    key: "role".to_string(),
+                    value: "compute".to_string()
+                },
+            ]
+        );
+
+        assert!(remove_tag(&conn, device_id, "rack").await.unwrap());
+        assert!(!remove_tag(&conn, device_id, "rack").await.unwrap());
+        assert_eq!(list_tags(&conn, device_id).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_device_uuids_with_tag() {
+        let factory = test_connection_factory!();
+        let conn = database::run_migrations(&factory).await.unwrap();
+        let compute = insert_device(&conn, "a7000000-0000-0000-0000-000000000001").await;
+        let storage = insert_device(&conn, "a7000000-0000-0000-0000-000000000002").await;
+        insert_device(&conn, "a7000000-0000-0000-0000-000000000003").await;
+
+        set_tag(&conn, compute, "role", "compute").await.unwrap();
+        set_tag(&conn, storage, "role", "storage").await.unwrap();
+
+        let uuids = device_uuids_with_tag(&conn, "role", "compute")
+            .await
+            .unwrap();
+        assert_eq!(
+            uuids,
+            vec![Uuid::parse_str("a7000000-0000-0000-0000-000000000001").unwrap()]
+        );
+        assert!(
+            device_uuids_with_tag(&conn, "role", "gpu")
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
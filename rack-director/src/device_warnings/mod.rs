@@ -7,5 +7,6 @@
 mod store;
 
 pub use store::{
-    DeviceWarning, create_warning, delete_warning, get_device_id_by_uuid, list_warnings,
+    DeviceWarning, create_warning, delete_warning, delete_warnings_with_code,
+    get_device_id_by_uuid, list_warnings,
 };
@@ -5,7 +5,7 @@ use chrono::{DateTime, Utc};
 use rusqlite::OptionalExtension;
 use serde::Serialize;
 
-use crate::database::{Connection, FromRow};
+use crate::database::{Connection, FromRow, from_db_time, to_db_time};
 
 /// A non-fatal warning attached to a specific device.
 ///
@@ -28,9 +28,7 @@ pub struct DeviceWarning {
 impl FromRow for DeviceWarning {
     fn from_row(row: &rusqlite::Row) -> rusqlite::Result<Self> {
         let created_at_str: String = row.get("created_at")?;
-        let created_at = created_at_str
-            .parse::<DateTime<Utc>>()
-            .unwrap_or_else(|_| Utc::now());
+        let created_at = from_db_time(&created_at_str).unwrap_or_else(|_| Utc::now());
         Ok(DeviceWarning {
             id: row.get("id")?,
             device_id: row.get("device_id")?,
@@ -51,8 +49,13 @@ pub async fn create_warning(
     message: &str,
 ) -> Result<DeviceWarning> {
     conn.execute(
-        "INSERT INTO device_warnings (device_id, code, message) VALUES (?1, ?2, ?3)",
-        (device_id, code.to_string(), message.to_string()),
+        "INSERT INTO device_warnings (device_id, code, message, created_at) VALUES (?1, ?2, ?3, ?4)",
+        (
+            device_id,
+            code.to_string(),
+            message.to_string(),
+            to_db_time(Utc::now()),
+        ),
     )
     .await?;
 
@@ -101,6 +104,24 @@ pub async fn delete_warning(conn: &Connection, warning_id: i64, device_id: i64)
     Ok(rows_affected > 0)
 }
 
+/// Delete every warning with `code` from the device, e.g. before re-checking a condition.
+///
+/// Returns the number of warnings deleted.
+pub async fn delete_warnings_with_code(
+    conn: &Connection,
+    device_id: i64,
+    code: &str,
+) -> Result<usize> {
+    let rows_affected = conn
+        .execute(
+            "DELETE FROM device_warnings WHERE device_id = ?1 AND code = ?2",
+            (device_id, code.to_string()),
+        )
+        .await?;
+
+    Ok(rows_affected)
+}
+
 /// Look up the integer `id` of a device by its UUID string representation.
 ///
 /// Returns `None` when no device with that UUID exists.
@@ -242,6 +263,30 @@ mod tests {
         assert_eq!(list.len(), 1);
     }
 
+    #[tokio::test]
+    async fn test_delete_warnings_with_code() {
+        let (conn, device_id) = setup(test_connection_factory!()).await;
+
+        create_warning(&conn, device_id, "CODE_A", "first")
+            .await
+            .unwrap();
+        create_warning(&conn, device_id, "CODE_A", "second")
+            .await
+            .unwrap();
+        create_warning(&conn, device_id, "CODE_B", "other")
+            .await
+            .unwrap();
+
+        let deleted = delete_warnings_with_code(&conn, device_id, "CODE_A")
+            .await
+            .unwrap();
+        assert_eq!(deleted, 2);
+
+        let list = list_warnings(&conn, device_id).await.unwrap();
+        assert_eq!(list.len(), 1);
+        assert_eq!(list[0].code, "CODE_B");
+    }
+
     #[tokio::test]
     async fn test_get_device_id_by_uuid() {
         let (conn, device_id) = setup(test_connection_factory!()).await;
@@ -1,18 +1,50 @@
-use anyhow::Result;
-use std::collections::HashSet;
+use anyhow::{Result, bail};
+use common::Ipv4Subnet;
+use rand::seq::IteratorRandom;
+use serde::Serialize;
+use std::collections::{BTreeSet, HashSet};
 use std::net::Ipv4Addr;
+use std::str::FromStr;
 use uuid::Uuid;
 
 use crate::database::Connection;
 
 use super::store;
 
+/// How a new address is picked from a pool.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AllocationStrategy {
+    /// The lowest free address, so small subnets fill predictably.
+    #[default]
+    Sequential,
+    /// Any free address, chosen uniformly, so addresses are spread out and hard
+    /// to predict.
+    Random,
+}
+
+impl FromStr for AllocationStrategy {
+    type Err = anyhow::Error;
+
+    /// Accepts `sequential` or `random`.
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "sequential" => Ok(Self::Sequential),
+            "random" => Ok(Self::Random),
+            _ => bail!(
+                "Invalid allocation strategy '{}': expected sequential or random",
+                s
+            ),
+        }
+    }
+}
+
 /// Allocate IP for a known device (MAC -> UUID mapping exists) within a specific network
 pub async fn allocate_for_device_in_network(
     conn: &Connection,
     mac: &str,
     uuid: &Uuid,
     network_id: i64,
+    strategy: AllocationStrategy,
 ) -> Result<Ipv4Addr> {
     // 1. Check static reservation in this network
     if let Some(reservation) = store::get_static_reservation(conn, network_id, mac).await? {
@@ -40,7 +72,7 @@ pub async fn allocate_for_device_in_network(
     }
 
     // 3. Allocate from pools in this network
-    allocate_from_pools(conn, network_id, mac).await
+    allocate_from_pools(conn, network_id, mac, strategy).await
 }
 
 /// Allocate IP for unknown device (no UUID mapping) within a specific network
@@ -48,6 +80,7 @@ pub async fn allocate_for_mac_in_network(
     conn: &Connection,
     mac: &str,
     network_id: i64,
+    strategy: AllocationStrategy,
 ) -> Result<Ipv4Addr> {
     // 1. Check static reservation in this network
     if let Some(reservation) = store::get_static_reservation(conn, network_id, mac).await? {
@@ -75,11 +108,42 @@ pub async fn allocate_for_mac_in_network(
     }
 
     // 3. Allocate from pools in this network
-    allocate_from_pools(conn, network_id, mac).await
+    allocate_from_pools(conn, network_id, mac, strategy).await
+}
+
+/// Allocate from pools within a network (try each pool until success), picking
+/// within a pool by `strategy`
+async fn allocate_from_pools(
+    conn: &Connection,
+    network_id: i64,
+    mac: &str,
+    strategy: AllocationStrategy,
+) -> Result<Ipv4Addr> {
+    allocate_from_pools_excluding(conn, network_id, mac, strategy, &HashSet::new()).await
 }
 
-/// Allocate from pools within a network (try each pool until success)
-async fn allocate_from_pools(conn: &Connection, network_id: i64, mac: &str) -> Result<Ipv4Addr> {
+/// Pick a free pool address in `network_id`, never one in `exclude`.
+///
+/// Unlike [`allocate_for_mac_in_network`], the MAC's own reservation and lease are
+/// not consulted: the DHCP handler uses this to retry after the address they gave
+/// turned out to be held by another MAC.
+pub async fn allocate_from_pools_excluding(
+    conn: &Connection,
+    network_id: i64,
+    mac: &str,
+    strategy: AllocationStrategy,
+    exclude: &HashSet<Ipv4Addr>,
+) -> Result<Ipv4Addr> {
+    // Disabled networks keep serving reservations and existing leases (handled by
+    // the callers above) but hand out nothing new.
+    let network = store::get_network(conn, network_id).await?;
+    if !network.enabled {
+        return Err(anyhow::anyhow!(
+            "Network {} is disabled for new allocations",
+            network_id
+        ));
+    }
+
     let pools = store::list_pools_for_network(conn, network_id).await?;
 
     if pools.is_empty() {
@@ -104,21 +168,30 @@ async fn allocate_from_pools(conn: &Connection, network_id: i64, mac: &str) -> R
         .filter_map(|r| r.ip_address.parse().ok())
         .collect();
 
+    let infrastructure = infrastructure_addresses(&network)?;
+
     // Try each pool until allocation succeeds
     for pool in pools {
-        let range = parse_ip_range(&pool.range_start, &pool.range_end)?;
-
-        for ip in range {
-            if !active_ips.contains(&ip) && !reserved_ips.contains(&ip) {
-                log::info!(
-                    "Allocated {} from pool '{}' (network {}) for MAC {}",
-                    ip,
-                    pool.name,
-                    network_id,
-                    mac
-                );
-                return Ok(ip);
-            }
+        let mut free = parse_ip_range(&pool.range_start, &pool.range_end)?.filter(|ip| {
+            !infrastructure.contains(ip)
+                && !active_ips.contains(ip)
+                && !reserved_ips.contains(ip)
+                && !exclude.contains(ip)
+        });
+        let ip = match strategy {
+            AllocationStrategy::Sequential => free.next(),
+            AllocationStrategy::Random => free.choose(&mut rand::thread_rng()),
+        };
+
+        if let Some(ip) = ip {
+            log::info!(
+                "Allocated {} from pool '{}' (network {}) for MAC {}",
+                ip,
+                pool.name,
+                network_id,
+                mac
+            );
+            return Ok(ip);
         }
     }
 
@@ -128,6 +201,81 @@ async fn allocate_from_pools(conn: &Connection, network_id: i64, mac: &str) -> R
     ))
 }
 
+/// Address counts across a network's pools, for capacity monitoring.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct PoolUtilization {
+    /// Addresses the pools can hand out: their ranges, deduplicated, minus the
+    /// network, broadcast and gateway addresses.
+    pub total: usize,
+    /// Addresses held by an unexpired lease.
+    pub allocated: usize,
+    /// Unleased addresses held back by a static reservation.
+    pub reserved: usize,
+    /// Addresses the next allocation could still hand out.
+    pub free: usize,
+}
+
+/// Count how much of a network's pool space is in use.
+///
+/// Matches what [`allocate_for_mac_in_network`] would consider: a pool range may
+/// cover the whole subnet and the infrastructure addresses are still skipped.
+pub async fn network_utilization(conn: &Connection, network_id: i64) -> Result<PoolUtilization> {
+    let network = store::get_network(conn, network_id).await?;
+    let infrastructure = infrastructure_addresses(&network)?;
+
+    let mut addresses = BTreeSet::new();
+    for pool in store::list_pools_for_network(conn, network_id).await? {
+        addresses.extend(
+            parse_ip_range(&pool.range_start, &pool.range_end)?
+                .filter(|ip| !infrastructure.contains(ip)),
+        );
+    }
+
+    let leased: HashSet<Ipv4Addr> = store::get_leases_by_network(conn, network_id)
+        .await?
+        .into_iter()
+        .filter(|l| !l.is_expired())
+        .filter_map(|l| l.ip_address.parse().ok())
+        .collect();
+    let reserved: HashSet<Ipv4Addr> = store::list_static_reservations(conn, network_id)
+        .await?
+        .into_iter()
+        .filter_map(|r| r.ip_address.parse().ok())
+        .collect();
+
+    let allocated = addresses.iter().filter(|ip| leased.contains(ip)).count();
+    let reserved = addresses
+        .iter()
+        .filter(|ip| !leased.contains(ip) && reserved.contains(ip))
+        .count();
+    Ok(PoolUtilization {
+        total: addresses.len(),
+        allocated,
+        reserved,
+        free: addresses.len() - allocated - reserved,
+    })
+}
+
+/// Addresses in a network that are never handed out from a pool: the gateway and,
+/// for subnets with room for them, the network and broadcast addresses.
+fn infrastructure_addresses(network: &store::DhcpNetwork) -> Result<HashSet<Ipv4Addr>> {
+    let subnet: Ipv4Subnet = network
+        .subnet
+        .parse()
+        .map_err(|e: common::Ipv4SubnetError| anyhow::anyhow!("{}", e))?;
+
+    let mut addresses = HashSet::new();
+    if let Ok(gateway) = network.gateway.parse() {
+        addresses.insert(gateway);
+    }
+    // /31 point-to-point links and /32 hosts have no network or broadcast address
+    if subnet.subnet() <= 30 {
+        addresses.insert(subnet.network());
+        addresses.insert(subnet.broadcast());
+    }
+    Ok(addresses)
+}
+
 /// Parse IP range from start and end addresses
 fn parse_ip_range(start: &str, end: &str) -> Result<impl Iterator<Item = Ipv4Addr>> {
     let start_ip: Ipv4Addr = start.parse()?;
@@ -180,7 +328,7 @@ mod tests {
         let mac = "aa:bb:cc:dd:ee:ff";
 
         // Allocate IP in test network
-        let ip = allocate_for_mac_in_network(&db, mac, network_id)
+        let ip = allocate_for_mac_in_network(&db, mac, network_id, AllocationStrategy::Sequential)
             .await
             .unwrap();
         assert_eq!(ip.to_string(), "10.0.0.100"); // First IP in test range
@@ -192,7 +340,7 @@ mod tests {
         let mac = "aa:bb:cc:dd:ee:ff";
 
         // First allocation
-        let ip1 = allocate_for_mac_in_network(&db, mac, network_id)
+        let ip1 = allocate_for_mac_in_network(&db, mac, network_id, AllocationStrategy::Sequential)
             .await
             .unwrap();
 
@@ -210,7 +358,7 @@ mod tests {
         .unwrap();
 
         // Second allocation should return same IP
-        let ip2 = allocate_for_mac_in_network(&db, mac, network_id)
+        let ip2 = allocate_for_mac_in_network(&db, mac, network_id, AllocationStrategy::Sequential)
             .await
             .unwrap();
         assert_eq!(ip1, ip2);
@@ -222,9 +370,10 @@ mod tests {
         let mac1 = "aa:bb:cc:dd:ee:ff";
         let mac2 = "11:22:33:44:55:66";
 
-        let ip1 = allocate_for_mac_in_network(&db, mac1, network_id)
-            .await
-            .unwrap();
+        let ip1 =
+            allocate_for_mac_in_network(&db, mac1, network_id, AllocationStrategy::Sequential)
+                .await
+                .unwrap();
         store::create_or_update_lease_with_network(
             &db,
             mac1,
@@ -237,15 +386,156 @@ mod tests {
         .await
         .unwrap();
 
-        let ip2 = allocate_for_mac_in_network(&db, mac2, network_id)
-            .await
-            .unwrap();
+        let ip2 =
+            allocate_for_mac_in_network(&db, mac2, network_id, AllocationStrategy::Sequential)
+                .await
+                .unwrap();
 
         assert_ne!(ip1, ip2);
         assert_eq!(ip1.to_string(), "10.0.0.100");
         assert_eq!(ip2.to_string(), "10.0.0.101");
     }
 
+    #[tokio::test]
+    async fn test_sequential_strategy_returns_lowest_free_address() {
+        let (db, network_id) = create_test_db(test_connection_factory!()).await;
+        for (mac, ip) in [
+            ("aa:bb:cc:dd:ee:01", "10.0.0.100"),
+            ("aa:bb:cc:dd:ee:02", "10.0.0.102"),
+        ] {
+            store::create_or_update_lease_with_network(
+                &db,
+                mac,
+                &ip.parse().unwrap(),
+                None,
+                LeaseState::Active,
+                3600,
+                network_id,
+            )
+            .await
+            .unwrap();
+        }
+
+        let ip = allocate_for_mac_in_network(
+            &db,
+            "aa:bb:cc:dd:ee:ff",
+            network_id,
+            AllocationStrategy::Sequential,
+        )
+        .await
+        .unwrap();
+        assert_eq!(ip.to_string(), "10.0.0.101");
+    }
+
+    #[tokio::test]
+    async fn test_allocate_from_pools_skips_excluded_addresses() {
+        let (db, network_id) = create_test_db(test_connection_factory!()).await;
+        let exclude: HashSet<Ipv4Addr> =
+            ["10.0.0.100".parse().unwrap(), "10.0.0.101".parse().unwrap()]
+                .into_iter()
+                .collect();
+
+        let ip = allocate_from_pools_excluding(
+            &db,
+            network_id,
+            "aa:bb:cc:dd:ee:ff",
+            AllocationStrategy::Sequential,
+            &exclude,
+        )
+        .await
+        .unwrap();
+        assert_eq!(ip.to_string(), "10.0.0.102");
+    }
+
+    #[tokio::test]
+    async fn test_random_strategy_varies() {
+        let (db, network_id) = create_test_db(test_connection_factory!()).await;
+        let pool = parse_ip_range("10.0.0.100", "10.0.0.200")
+            .unwrap()
+            .collect::<HashSet<_>>();
+
+        let mut picked = HashSet::new();
+        for _ in 0..20 {
+            let ip = allocate_for_mac_in_network(
+                &db,
+                "aa:bb:cc:dd:ee:ff",
+                network_id,
+                AllocationStrategy::Random,
+            )
+            .await
+            .unwrap();
+            assert!(pool.contains(&ip), "{} is outside the pool", ip);
+            picked.insert(ip);
+        }
+        // 20 draws from 101 addresses all landing on one is vanishingly unlikely
+        assert!(picked.len() > 1);
+    }
+
+    #[test]
+    fn test_parse_allocation_strategy() {
+        assert_eq!(
+            "random".parse::<AllocationStrategy>().unwrap(),
+            AllocationStrategy::Random
+        );
+        assert_eq!(
+            "sequential".parse::<AllocationStrategy>().unwrap(),
+            AllocationStrategy::Sequential
+        );
+        assert!("lowest".parse::<AllocationStrategy>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_disabled_network_renews_but_does_not_allocate() {
+        let (db, network_id) = create_test_db(test_connection_factory!()).await;
+        let existing_mac = "aa:bb:cc:dd:ee:ff";
+        let new_mac = "11:22:33:44:55:66";
+
+        let ip = allocate_for_mac_in_network(
+            &db,
+            existing_mac,
+            network_id,
+            AllocationStrategy::Sequential,
+        )
+        .await
+        .unwrap();
+        store::create_or_update_lease_with_network(
+            &db,
+            existing_mac,
+            &ip,
+            None,
+            LeaseState::Active,
+            3600,
+            network_id,
+        )
+        .await
+        .unwrap();
+
+        db.execute(
+            "UPDATE dhcp_networks SET enabled = 0 WHERE id = ?1",
+            (network_id,),
+        )
+        .await
+        .unwrap();
+
+        // Existing lease holder keeps its address
+        let renewed = allocate_for_mac_in_network(
+            &db,
+            existing_mac,
+            network_id,
+            AllocationStrategy::Sequential,
+        )
+        .await
+        .unwrap();
+        assert_eq!(renewed, ip);
+
+        // New clients get nothing from the disabled network
+        let err =
+            allocate_for_mac_in_network(&db, new_mac, network_id, AllocationStrategy::Sequential)
+                .await
+                .unwrap_err();
+        assert!(err.to_string().contains("disabled"));
+    }
+
     #[tokio::test]
     async fn test_static_reservation_takes_priority() {
         let (db, network_id) = create_test_db(test_connection_factory!()).await;
@@ -258,7 +548,7 @@ mod tests {
             .unwrap();
 
         // Allocation should return the static IP
-        let ip = allocate_for_mac_in_network(&db, mac, network_id)
+        let ip = allocate_for_mac_in_network(&db, mac, network_id, AllocationStrategy::Sequential)
             .await
             .unwrap();
         assert_eq!(ip.to_string(), static_ip);
@@ -270,7 +560,7 @@ mod tests {
         let mac = "aa:bb:cc:dd:ee:ff";
 
         // First, allocate IP from pool
-        let ip1 = allocate_for_mac_in_network(&db, mac, network_id)
+        let ip1 = allocate_for_mac_in_network(&db, mac, network_id, AllocationStrategy::Sequential)
             .await
             .unwrap();
         assert_eq!(ip1.to_string(), "10.0.0.100"); // First IP in pool
@@ -295,7 +585,7 @@ mod tests {
             .unwrap();
 
         // Next allocation should return the static IP, not the existing lease IP
-        let ip2 = allocate_for_mac_in_network(&db, mac, network_id)
+        let ip2 = allocate_for_mac_in_network(&db, mac, network_id, AllocationStrategy::Sequential)
             .await
             .unwrap();
         assert_eq!(
@@ -305,6 +595,102 @@ mod tests {
         );
     }
 
+    /// A /29 (10.0.1.0 - 10.0.1.7) with its gateway on 10.0.1.1 and no pools.
+    async fn create_small_network(
+        factory: DatabaseConnectionFactory,
+    ) -> (Arc<crate::database::Connection>, i64) {
+        let db = Arc::new(database::run_migrations(&factory).await.unwrap());
+        let network = store::create_network(
+            &db,
+            "Small Network",
+            "10.0.1.0/29",
+            "10.0.1.1",
+            &[],
+            86400,
+            None,
+            false,
+        )
+        .await
+        .unwrap();
+        (db, network.id)
+    }
+
+    #[tokio::test]
+    async fn test_pool_spanning_subnet_skips_infrastructure() {
+        let (db, network_id) = create_small_network(test_connection_factory!()).await;
+        store::create_pool(&db, network_id, "Whole", "10.0.1.0", "10.0.1.7")
+            .await
+            .unwrap();
+
+        // .0 is the network address and .1 the gateway
+        let ip = allocate_for_mac_in_network(
+            &db,
+            "aa:bb:cc:dd:ee:ff",
+            network_id,
+            AllocationStrategy::Sequential,
+        )
+        .await
+        .unwrap();
+        assert_eq!(ip.to_string(), "10.0.1.2");
+    }
+
+    #[tokio::test]
+    async fn test_network_utilization_counts_29_with_one_exclusion() {
+        let (db, network_id) = create_small_network(test_connection_factory!()).await;
+        // Overlapping pools across the whole /29 are counted once
+        store::create_pool(&db, network_id, "Low", "10.0.1.0", "10.0.1.5")
+            .await
+            .unwrap();
+        store::create_pool(&db, network_id, "High", "10.0.1.4", "10.0.1.7")
+            .await
+            .unwrap();
+        // One exclusion: a static reservation for a MAC that holds no lease
+        store::create_static_reservation(&db, network_id, "11:22:33:44:55:66", "10.0.1.6", None)
+            .await
+            .unwrap();
+
+        // .2 through .6 remain once network, broadcast and gateway are excluded
+        let empty = network_utilization(&db, network_id).await.unwrap();
+        assert_eq!(
+            empty,
+            PoolUtilization {
+                total: 5,
+                allocated: 0,
+                reserved: 1,
+                free: 4,
+            }
+        );
+
+        for mac in ["aa:bb:cc:dd:ee:01", "aa:bb:cc:dd:ee:02"] {
+            let ip =
+                allocate_for_mac_in_network(&db, mac, network_id, AllocationStrategy::Sequential)
+                    .await
+                    .unwrap();
+            store::create_or_update_lease_with_network(
+                &db,
+                mac,
+                &ip,
+                None,
+                LeaseState::Active,
+                3600,
+                network_id,
+            )
+            .await
+            .unwrap();
+        }
+
+        let used = network_utilization(&db, network_id).await.unwrap();
+        assert_eq!(
+            used,
+            PoolUtilization {
+                total: 5,
+                allocated: 2,
+                reserved: 1,
+                free: 2,
+            }
+        );
+    }
+
     #[tokio::test]
     async fn test_parse_ip_range() {
         let range: Vec<Ipv4Addr> = parse_ip_range("10.0.0.1", "10.0.0.5").unwrap().collect();
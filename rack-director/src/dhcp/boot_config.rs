@@ -1,10 +1,13 @@
-use anyhow::Result;
+use anyhow::{Result, bail};
 use dhcproto::v4::{self, Architecture, Message};
 use std::net::Ipv4Addr;
+use std::str::FromStr;
 use std::sync::Arc;
 
 use crate::boot_files::BootFileProvider;
 
+use super::message_builder::set_boot_file;
+use super::options::TftpServerAddress;
 use super::request::RequestContext;
 
 #[derive(Debug, Clone)]
@@ -14,11 +17,41 @@ struct BootOptions {
     file_size_blocks: Option<u16>,
 }
 
+/// A boot file served over TFTP to clients that send a given user class (option 77).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UserClassBootFile {
+    pub user_class: String,
+    pub filename: String,
+}
+
+impl FromStr for UserClassBootFile {
+    type Err = anyhow::Error;
+
+    /// Accepts `CLASS=FILENAME`, e.g. `gpu-nodes=gpu/snponly.efi`.
+    fn from_str(s: &str) -> Result<Self> {
+        match s.split_once('=') {
+            Some((class, file)) if !class.is_empty() && !file.is_empty() => Ok(Self {
+                user_class: class.to_string(),
+                filename: file.to_string(),
+            }),
+            _ => bail!(
+                "Invalid user class boot file '{}': expected CLASS=FILENAME",
+                s
+            ),
+        }
+    }
+}
+
+/// Boot loader for BIOS PXE ROMs, fetched over TFTP.
+const BIOS_BOOT_FILE: &str = "undionly.kpxe";
+
 #[derive(Clone)]
 pub struct BootConfigProvider {
     tftp_server: String,
     http_server: String,
     boot_file_provider: Arc<dyn BootFileProvider>,
+    always_send_tftp_server_address: bool,
+    user_class_boot_files: Vec<UserClassBootFile>,
 }
 
 impl BootConfigProvider {
@@ -49,17 +82,45 @@ impl BootConfigProvider {
             tftp_server,
             http_server,
             boot_file_provider,
+            always_send_tftp_server_address: false,
+            user_class_boot_files: Vec::new(),
         }
     }
 
+    /// Send option 150 (Cisco TFTP server address) on TFTP boot replies even when
+    /// the client did not request it.
+    pub fn with_always_send_tftp_server_address(mut self, always: bool) -> Self {
+        self.always_send_tftp_server_address = always;
+        self
+    }
+
+    /// Serve a specific boot file to clients by user class (option 77).
+    ///
+    /// The first entry whose class the client sends wins. iPXE clients still get
+    /// the boot script.
+    pub fn with_user_class_boot_files(mut self, boot_files: Vec<UserClassBootFile>) -> Self {
+        self.user_class_boot_files = boot_files;
+        self
+    }
+
+    /// The configured boot file for the first matching user class of the client.
+    fn user_class_boot_file(&self, req_ctx: &RequestContext) -> Option<&str> {
+        let user_class = req_ctx.user_class.as_ref()?;
+        self.user_class_boot_files
+            .iter()
+            .find(|entry| user_class.contains(&entry.user_class))
+            .map(|entry| entry.filename.as_str())
+    }
+
     /// Resolves and applies boot options to a DHCP message.
     ///
     /// Decision logic (in order):
     /// 1. Check if client requested any boot options — skip if not requested
     /// 2. iPXE client → filename = HTTP boot script URL (no file size)
-    /// 3. HTTP boot arch (15/16/17) → filename = HTTP URL for iPXE firmware
-    /// 4. UEFI arch (7, 11) → next_server = TFTP server, filename = snponly.efi
-    /// 5. BIOS arch (0, 9, default) → next_server = TFTP server, filename = undionly.kpxe
+    /// 3. Configured user class → next_server = TFTP server, filename = its boot file
+    /// 4. HTTP boot arch (15/16/17) → filename = HTTP URL for iPXE firmware
+    /// 5. UEFI arch (7, 11) → next_server = TFTP server, filename = snponly.efi
+    /// 6. BIOS arch (0, 9, default) → next_server = TFTP server, filename = undionly.kpxe
     ///
     /// For actual boot files (not scripts), looks up file size and includes Option 13 if requested.
     pub async fn populate_boot_options(
@@ -68,7 +129,10 @@ impl BootConfigProvider {
         req_ctx: &RequestContext,
     ) -> Result<()> {
         // 1. Check if client requested any boot options
-        if !req_ctx.requested_tftp_server && !req_ctx.requested_bootfile {
+        if !req_ctx.requested_tftp_server
+            && !req_ctx.requested_bootfile
+            && !req_ctx.requested_tftp_server_address
+        {
             log::debug!("Client did not request any boot options");
             return Ok(());
         }
@@ -84,7 +148,18 @@ impl BootConfigProvider {
             return Ok(());
         }
 
-        // 3. Determine boot file and lookup size
+        // 3. Boot file configured for the client's user class
+        if let Some(filename) = self.user_class_boot_file(req_ctx) {
+            let boot_opts = BootOptions {
+                next_server: Some(self.tftp_server.clone()),
+                filename: filename.to_string(),
+                file_size_blocks: self.lookup_file_size_blocks(filename).await,
+            };
+            self.apply_boot_options_to_message(msg, &boot_opts, req_ctx)?;
+            return Ok(());
+        }
+
+        // 4. Determine boot file by architecture and lookup size
         log::debug!(
             "DHCP: Matching boot args to client arch {:?}",
             req_ctx.client_arch
@@ -115,7 +190,7 @@ impl BootConfigProvider {
             }
             // BIOS architectures (0, 9) and default → TFTP undionly.kpxe
             _ => {
-                let filename = "undionly.kpxe";
+                let filename = BIOS_BOOT_FILE;
                 let file_size_blocks = self.lookup_file_size_blocks(filename).await;
                 BootOptions {
                     next_server: Some(self.tftp_server.clone()),
@@ -170,7 +245,10 @@ impl BootConfigProvider {
     /// - Option 66: TFTP Server Name - only if requested AND next_server is Some
     /// - Option 67: Bootfile Name - only if requested
     /// - Option 13: Boot File Size - only if requested AND file_size_blocks is Some
+    /// - Option 150: TFTP Server Address - if requested (or always-send is configured)
+    ///   AND next_server is an IPv4 address
     /// - siaddr field: Next server IP address - only if option 66 requested
+    /// - file field: Boot filename, always; skipped with a warning if it does not fit
     ///
     /// # Arguments
     /// * `msg` - The DHCP message to modify
@@ -209,21 +287,51 @@ impl BootConfigProvider {
             msg.opts_mut().insert(v4::DhcpOption::BootFileSize(blocks));
         }
 
+        // Option 150 (TFTP Server Address) - only for TFTP boots with an IP next server
+        if (req_ctx.requested_tftp_server_address || self.always_send_tftp_server_address)
+            && let Some(ip) = boot_opts.next_server.as_deref().and_then(parse_server_ip)
+        {
+            msg.opts_mut()
+                .insert(TftpServerAddress(vec![ip]).to_option());
+        }
+
+        // file field - legacy PXE ROMs read the loader path from the BOOTP header
+        if let Err(e) = set_boot_file(msg, &boot_opts.filename) {
+            log::warn!("Leaving BOOTP file field empty: {:#}", e);
+        }
+
         // siaddr field (next server IP) - only if option 66 requested
         if req_ctx.requested_tftp_server
             && let Some(next_server) = &boot_opts.next_server
-            && let Ok(next_ip) = next_server.parse::<Ipv4Addr>()
+            && let Some(next_ip) = parse_server_ip(next_server)
         {
             msg.set_siaddr(next_ip);
         }
 
         Ok(())
     }
+
+    /// Next server and boot file for a legacy BOOTP client, which can only be a BIOS
+    /// PXE ROM and can only be told an address, not a hostname.
+    pub fn bootp_boot_target(&self) -> (Option<Ipv4Addr>, &'static str) {
+        (parse_server_ip(&self.tftp_server), BIOS_BOOT_FILE)
+    }
+}
+
+/// Parse the IPv4 address out of a configured server, which may be `ip` or `ip:port`.
+fn parse_server_ip(server: &str) -> Option<Ipv4Addr> {
+    server.parse::<Ipv4Addr>().ok().or_else(|| {
+        server
+            .parse::<std::net::SocketAddrV4>()
+            .ok()
+            .map(|addr| *addr.ip())
+    })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::dhcp::options::UserClass;
     use async_trait::async_trait;
     use dhcproto::v4::{Message, MessageType, Opcode};
     use std::collections::HashMap;
@@ -287,13 +395,20 @@ mod tests {
             mac: "aa:bb:cc:dd:ee:ff".to_string(),
             message_type: MessageType::Discover,
             requested_ip: None,
+            requested_lease_time: None,
             client_arch,
+            user_class: None,
             is_ipxe,
+            is_pxe_client: false,
             requested_tftp_server,
             requested_bootfile,
             requested_bootfile_size,
+            requested_tftp_server_address: false,
+            requested_domain_search: false,
             ciaddr: Ipv4Addr::UNSPECIFIED,
             guid: None,
+            client_id: None,
+            rapid_commit: false,
         }
     }
 
@@ -606,6 +721,11 @@ mod tests {
             Some("undionly.kpxe".to_string()),
             "BIOS arch 0 should get undionly.kpxe"
         );
+        assert_eq!(
+            msg.fname(),
+            Some(b"undionly.kpxe".as_slice()),
+            "BIOS arch 0 should carry the loader path in the BOOTP file field"
+        );
         assert_eq!(
             get_tftp_server_name(&msg),
             Some("10.0.0.1".to_string()),
@@ -996,4 +1116,162 @@ mod tests {
         assert_eq!(get_bootfile_name(&msg), Some("undionly.kpxe".to_string()));
         assert_eq!(get_bootfile_size(&msg), Some(200));
     }
+
+    fn get_tftp_server_address(msg: &Message) -> Option<TftpServerAddress> {
+        msg.opts()
+            .iter()
+            .find_map(|(_, opt)| TftpServerAddress::from_option(opt))
+    }
+
+    #[tokio::test]
+    async fn test_option_150_sent_when_requested() {
+        let provider = make_provider();
+        let mut req_ctx = make_req_ctx(Some(Architecture::Intelx86PC), false, false, true, false);
+        req_ctx.requested_tftp_server_address = true;
+        let mut msg = Message::default();
+        msg.set_opcode(Opcode::BootReply);
+
+        provider
+            .populate_boot_options(&mut msg, &req_ctx)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            get_tftp_server_address(&msg),
+            Some(TftpServerAddress(vec![Ipv4Addr::new(10, 0, 0, 1)]))
+        );
+        assert_eq!(get_tftp_server_name(&msg), None, "66 was not requested");
+    }
+
+    #[tokio::test]
+    async fn test_option_150_omitted_when_not_requested() {
+        let provider = make_provider();
+        let req_ctx = make_req_ctx(Some(Architecture::Intelx86PC), false, true, true, false);
+        let mut msg = Message::default();
+        msg.set_opcode(Opcode::BootReply);
+
+        provider
+            .populate_boot_options(&mut msg, &req_ctx)
+            .await
+            .unwrap();
+
+        assert_eq!(get_tftp_server_address(&msg), None);
+    }
+
+    #[tokio::test]
+    async fn test_option_150_always_send_uses_ip_of_host_port() {
+        let mock = MockBootFileProvider::new().with_file("undionly.kpxe", 102400);
+        let provider = BootConfigProvider::new(
+            "10.0.0.1:69".to_string(),
+            "http://10.0.0.1".to_string(),
+            Arc::new(mock),
+        )
+        .with_always_send_tftp_server_address(true);
+        let req_ctx = make_req_ctx(Some(Architecture::Intelx86PC), false, true, true, false);
+        let mut msg = Message::default();
+        msg.set_opcode(Opcode::BootReply);
+
+        provider
+            .populate_boot_options(&mut msg, &req_ctx)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            get_tftp_server_address(&msg),
+            Some(TftpServerAddress(vec![Ipv4Addr::new(10, 0, 0, 1)]))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_option_150_not_sent_for_ipxe_script() {
+        let provider = make_provider().with_always_send_tftp_server_address(true);
+        let req_ctx = make_req_ctx(None, true, true, true, false);
+        let mut msg = Message::default();
+        msg.set_opcode(Opcode::BootReply);
+
+        provider
+            .populate_boot_options(&mut msg, &req_ctx)
+            .await
+            .unwrap();
+
+        assert_eq!(get_tftp_server_address(&msg), None);
+    }
+
+    fn gpu_boot_files() -> Vec<UserClassBootFile> {
+        vec!["gpu-nodes=gpu/snponly.efi".parse().unwrap()]
+    }
+
+    #[tokio::test]
+    async fn test_user_class_selects_boot_file() {
+        let mock = MockBootFileProvider::new().with_file("gpu/snponly.efi", 1024);
+        let provider = BootConfigProvider::new(
+            "10.0.0.1".to_string(),
+            "http://10.0.0.1".to_string(),
+            Arc::new(mock),
+        )
+        .with_user_class_boot_files(gpu_boot_files());
+        let mut req_ctx = make_req_ctx(Some(Architecture::Intelx86PC), false, true, true, true);
+        req_ctx.user_class = Some(UserClass(vec![
+            "rack1".to_string(),
+            "gpu-nodes".to_string(),
+        ]));
+        let mut msg = Message::default();
+        msg.set_opcode(Opcode::BootReply);
+
+        provider
+            .populate_boot_options(&mut msg, &req_ctx)
+            .await
+            .unwrap();
+
+        assert_eq!(get_bootfile_name(&msg), Some("gpu/snponly.efi".to_string()));
+        assert_eq!(get_tftp_server_name(&msg), Some("10.0.0.1".to_string()));
+        assert_eq!(get_bootfile_size(&msg), Some(2));
+    }
+
+    #[tokio::test]
+    async fn test_unmatched_user_class_uses_arch_default() {
+        let provider = make_provider().with_user_class_boot_files(gpu_boot_files());
+        let mut req_ctx = make_req_ctx(Some(Architecture::Intelx86PC), false, true, true, false);
+        req_ctx.user_class = Some(UserClass(vec!["storage".to_string()]));
+        let mut msg = Message::default();
+        msg.set_opcode(Opcode::BootReply);
+
+        provider
+            .populate_boot_options(&mut msg, &req_ctx)
+            .await
+            .unwrap();
+
+        assert_eq!(get_bootfile_name(&msg), Some("undionly.kpxe".to_string()));
+    }
+
+    #[test]
+    fn test_user_class_boot_file_from_str() {
+        assert_eq!(
+            "gpu-nodes=gpu/snponly.efi"
+                .parse::<UserClassBootFile>()
+                .unwrap(),
+            UserClassBootFile {
+                user_class: "gpu-nodes".to_string(),
+                filename: "gpu/snponly.efi".to_string(),
+            }
+        );
+        assert!("gpu-nodes".parse::<UserClassBootFile>().is_err());
+        assert!("=file.efi".parse::<UserClassBootFile>().is_err());
+        assert!("gpu-nodes=".parse::<UserClassBootFile>().is_err());
+    }
+
+    #[test]
+    fn test_bootp_boot_target_is_bios_loader() {
+        assert_eq!(
+            make_provider().bootp_boot_target(),
+            (Some(Ipv4Addr::new(10, 0, 0, 1)), "undionly.kpxe")
+        );
+
+        let provider = BootConfigProvider::new(
+            "tftp.example.com".to_string(),
+            "http://10.0.0.1".to_string(),
+            Arc::new(MockBootFileProvider::new()),
+        );
+        assert_eq!(provider.bootp_boot_target().0, None);
+    }
 }
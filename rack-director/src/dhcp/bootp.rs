@@ -0,0 +1,175 @@
+//! Legacy BOOTP (RFC 951) replies for PXE ROMs that predate DHCP.
+//!
+//! A few very old ROMs send a plain BOOTREQUEST without the DHCP magic cookie and
+//! only understand a plain BOOTREPLY. With `--dhcp-legacy-bootp`, such a request from
+//! a MAC with a static reservation is answered with the reserved address in `yiaddr`,
+//! the TFTP server in `siaddr` and the BIOS boot loader in `file`, and an all-zero
+//! vendor area. Clients without a reservation are ignored: BOOTP has no lease, so
+//! there is nothing to hand out from a pool safely.
+
+use std::net::{Ipv4Addr, SocketAddr};
+
+use anyhow::{Result, bail};
+
+/// Length of the fixed header, up to and including `file`.
+const HEADER_LEN: usize = 236;
+/// Length of the RFC 951 vendor area, which a BOOTREPLY always carries.
+const VEND_LEN: usize = 64;
+const MAGIC_COOKIE: [u8; 4] = [99, 130, 83, 99];
+
+const OP_BOOTREQUEST: u8 = 1;
+const OP_BOOTREPLY: u8 = 2;
+const HTYPE_ETHERNET: u8 = 1;
+const ETHERNET_ADDR_LEN: u8 = 6;
+
+const FILE_RANGE: std::ops::Range<usize> = 108..236;
+
+/// The fields of a legacy BOOTREQUEST that its reply depends on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BootpRequest {
+    pub xid: u32,
+    pub flags: u16,
+    pub ciaddr: Ipv4Addr,
+    pub giaddr: Ipv4Addr,
+    pub chaddr: [u8; 6],
+}
+
+fn ipv4_at(data: &[u8], offset: usize) -> Ipv4Addr {
+    Ipv4Addr::new(
+        data[offset],
+        data[offset + 1],
+        data[offset + 2],
+        data[offset + 3],
+    )
+}
+
+impl BootpRequest {
+    /// Parse `data` as a BOOTREQUEST from an Ethernet client whose vendor area does
+    /// not start with the DHCP magic cookie. DHCP packets and anything malformed give
+    /// `None`.
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        if data.len() < HEADER_LEN
+            || data[0] != OP_BOOTREQUEST
+            || data[1] != HTYPE_ETHERNET
+            || data[2] != ETHERNET_ADDR_LEN
+            || data.get(HEADER_LEN..HEADER_LEN + MAGIC_COOKIE.len()) == Some(&MAGIC_COOKIE[..])
+        {
+            return None;
+        }
+        Some(Self {
+            xid: u32::from_be_bytes(data[4..8].try_into().ok()?),
+            flags: u16::from_be_bytes(data[10..12].try_into().ok()?),
+            ciaddr: ipv4_at(data, 12),
+            giaddr: ipv4_at(data, 24),
+            chaddr: data[28..34].try_into().ok()?,
+        })
+    }
+
+    /// Where the reply goes (RFC 951 §4): the relay if there is one, else the
+    /// client's own address if it knows it, else broadcast.
+    pub fn reply_destination(&self) -> SocketAddr {
+        if self.giaddr != Ipv4Addr::UNSPECIFIED {
+            SocketAddr::new(self.giaddr.into(), 67)
+        } else if self.ciaddr != Ipv4Addr::UNSPECIFIED {
+            SocketAddr::new(self.ciaddr.into(), 68)
+        } else {
+            SocketAddr::new(Ipv4Addr::BROADCAST.into(), 68)
+        }
+    }
+}
+
+/// Encode a BOOTREPLY to `request` assigning `yiaddr`, naming `siaddr` as the next
+/// server and `file` as the boot file. Carries no options.
+///
+/// Fails if `file` does not fit the 128-byte field with its terminating NUL.
+pub fn build_reply(
+    request: &BootpRequest,
+    yiaddr: Ipv4Addr,
+    siaddr: Ipv4Addr,
+    file: &str,
+) -> Result<Vec<u8>> {
+    if file.len() >= FILE_RANGE.len() {
+        bail!(
+            "Boot file name '{}' does not fit the {}-byte BOOTP file field",
+            file,
+            FILE_RANGE.len()
+        );
+    }
+
+    let mut reply = vec![0u8; HEADER_LEN + VEND_LEN];
+    reply[0] = OP_BOOTREPLY;
+    reply[1] = HTYPE_ETHERNET;
+    reply[2] = ETHERNET_ADDR_LEN;
+    reply[4..8].copy_from_slice(&request.xid.to_be_bytes());
+    reply[10..12].copy_from_slice(&request.flags.to_be_bytes());
+    reply[12..16].copy_from_slice(&request.ciaddr.octets());
+    reply[16..20].copy_from_slice(&yiaddr.octets());
+    reply[20..24].copy_from_slice(&siaddr.octets());
+    reply[24..28].copy_from_slice(&request.giaddr.octets());
+    reply[28..34].copy_from_slice(&request.chaddr);
+    reply[FILE_RANGE.start..FILE_RANGE.start + file.len()].copy_from_slice(file.as_bytes());
+    Ok(reply)
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+
+    /// A 300-byte legacy BOOTREQUEST from `mac`, with a zeroed vendor area.
+    pub(crate) fn bootp_request(mac: [u8; 6], xid: u32) -> Vec<u8> {
+        let mut data = vec![0u8; HEADER_LEN + VEND_LEN];
+        data[0] = OP_BOOTREQUEST;
+        data[1] = HTYPE_ETHERNET;
+        data[2] = ETHERNET_ADDR_LEN;
+        data[4..8].copy_from_slice(&xid.to_be_bytes());
+        data[28..34].copy_from_slice(&mac);
+        data
+    }
+
+    const MAC: [u8; 6] = [0x00, 0x0c, 0x29, 0x00, 0x00, 0x01];
+
+    #[test]
+    fn test_parse_accepts_only_cookieless_requests() {
+        let data = bootp_request(MAC, 0x1234);
+        let request = BootpRequest::parse(&data).unwrap();
+        assert_eq!(request.xid, 0x1234);
+        assert_eq!(request.chaddr, MAC);
+        assert_eq!(
+            request.reply_destination(),
+            "255.255.255.255:68".parse().unwrap()
+        );
+
+        // A DHCP packet has the cookie
+        let mut dhcp = data.clone();
+        dhcp[HEADER_LEN..HEADER_LEN + 4].copy_from_slice(&MAGIC_COOKIE);
+        assert!(BootpRequest::parse(&dhcp).is_none());
+
+        let mut reply = data.clone();
+        reply[0] = OP_BOOTREPLY;
+        assert!(BootpRequest::parse(&reply).is_none());
+        assert!(BootpRequest::parse(&data[..HEADER_LEN - 1]).is_none());
+    }
+
+    #[test]
+    fn test_build_reply_fills_header_only() {
+        let mut data = bootp_request(MAC, 0xdeadbeef);
+        data[24..28].copy_from_slice(&[10, 1, 0, 1]);
+        let request = BootpRequest::parse(&data).unwrap();
+        assert_eq!(request.reply_destination(), "10.1.0.1:67".parse().unwrap());
+
+        let yiaddr = Ipv4Addr::new(10, 1, 0, 50);
+        let siaddr = Ipv4Addr::new(10, 1, 0, 2);
+        let reply = build_reply(&request, yiaddr, siaddr, "undionly.kpxe").unwrap();
+        assert_eq!(reply.len(), 300);
+        assert_eq!(reply[0], OP_BOOTREPLY);
+        assert_eq!(&reply[4..8], &0xdeadbeefu32.to_be_bytes());
+        assert_eq!(ipv4_at(&reply, 16), yiaddr);
+        assert_eq!(ipv4_at(&reply, 20), siaddr);
+        assert_eq!(ipv4_at(&reply, 24), request.giaddr);
+        assert_eq!(&reply[28..34], &MAC);
+        assert!(reply[FILE_RANGE].starts_with(b"undionly.kpxe\0"));
+        assert!(reply[HEADER_LEN..].iter().all(|&b| b == 0), "no options");
+
+        assert!(build_reply(&request, yiaddr, siaddr, &"x".repeat(128)).is_err());
+    }
+}
@@ -0,0 +1,152 @@
+//! Client-side packet builders for exercising the server without a real DHCP client.
+//!
+//! Each constructor returns a [`Probe`] carrying the fields and options RFC 2131
+//! requires for that client state; chain [`Probe::option`], [`Probe::xid`] or
+//! [`Probe::relayed`] to adjust it, then [`Probe::build`] or [`Probe::to_bytes`].
+
+use std::net::Ipv4Addr;
+
+use dhcproto::v4::{DhcpOption, Message, MessageType, Opcode};
+
+use super::message_builder;
+
+/// A client request under construction.
+pub struct Probe {
+    msg: Message,
+}
+
+impl Probe {
+    /// A bare BOOTREQUEST of `message_type` from `mac` with no addresses or other
+    /// options set.
+    pub fn new(mac: [u8; 6], message_type: MessageType) -> Self {
+        let mut msg = Message::default();
+        msg.set_opcode(Opcode::BootRequest);
+        msg.set_xid(rand::random());
+        msg.set_chaddr(&mac);
+        msg.opts_mut().insert(DhcpOption::MessageType(message_type));
+        Self { msg }
+    }
+
+    /// DHCPDISCOVER.
+    pub fn discover(mac: [u8; 6]) -> Self {
+        Self::new(mac, MessageType::Discover)
+    }
+
+    /// DHCPREQUEST in SELECTING state: accepting `server_id`'s offer of `requested_ip`.
+    pub fn request(mac: [u8; 6], requested_ip: Ipv4Addr, server_id: Ipv4Addr) -> Self {
+        Self::new(mac, MessageType::Request)
+            .option(DhcpOption::RequestedIpAddress(requested_ip))
+            .option(DhcpOption::ServerIdentifier(server_id))
+    }
+
+    /// DHCPREQUEST in INIT-REBOOT state: confirming a remembered address, with no
+    /// server identifier and no `ciaddr`.
+    pub fn init_reboot(mac: [u8; 6], requested_ip: Ipv4Addr) -> Self {
+        Self::new(mac, MessageType::Request).option(DhcpOption::RequestedIpAddress(requested_ip))
+    }
+
+    /// DHCPREQUEST in RENEWING state: extending the lease on `ciaddr`.
+    pub fn renew(mac: [u8; 6], ciaddr: Ipv4Addr) -> Self {
+        let mut probe = Self::new(mac, MessageType::Request);
+        probe.msg.set_ciaddr(ciaddr);
+        probe
+    }
+
+    /// DHCPRELEASE of `ciaddr`, addressed to `server_id`.
+    pub fn release(mac: [u8; 6], ciaddr: Ipv4Addr, server_id: Ipv4Addr) -> Self {
+        let mut probe =
+            Self::new(mac, MessageType::Release).option(DhcpOption::ServerIdentifier(server_id));
+        probe.msg.set_ciaddr(ciaddr);
+        probe
+    }
+
+    /// DHCPDECLINE of `declined_ip`, addressed to `server_id`.
+    pub fn decline(mac: [u8; 6], declined_ip: Ipv4Addr, server_id: Ipv4Addr) -> Self {
+        Self::new(mac, MessageType::Decline)
+            .option(DhcpOption::RequestedIpAddress(declined_ip))
+            .option(DhcpOption::ServerIdentifier(server_id))
+    }
+
+    /// Add or replace an option.
+    pub fn option(mut self, option: DhcpOption) -> Self {
+        self.msg.opts_mut().insert(option);
+        self
+    }
+
+    /// Use a fixed transaction ID instead of a random one.
+    pub fn xid(mut self, xid: u32) -> Self {
+        self.msg.set_xid(xid);
+        self
+    }
+
+    /// Mark the packet as forwarded by the relay agent at `giaddr`.
+    pub fn relayed(mut self, giaddr: Ipv4Addr) -> Self {
+        self.msg.set_giaddr(giaddr);
+        self
+    }
+
+    /// The finished message.
+    pub fn build(self) -> Message {
+        self.msg
+    }
+
+    /// The finished message in wire format, options in a fixed order.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        message_builder::encode_message(&self.msg).expect("encoding to a Vec cannot fail")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dhcp::parse::parse_message;
+    use dhcproto::v4::OptionCode;
+
+    const MAC: [u8; 6] = [0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff];
+
+    #[test]
+    fn test_request_carries_selecting_options() {
+        let ip = Ipv4Addr::new(10, 0, 0, 50);
+        let server = Ipv4Addr::new(10, 0, 0, 1);
+        let msg = Probe::request(MAC, ip, server).xid(7).build();
+
+        assert_eq!(msg.opcode(), Opcode::BootRequest);
+        assert_eq!(msg.xid(), 7);
+        assert_eq!(msg.chaddr(), &MAC);
+        assert_eq!(msg.opts().msg_type(), Some(MessageType::Request));
+        assert_eq!(
+            msg.opts().get(OptionCode::RequestedIpAddress),
+            Some(&DhcpOption::RequestedIpAddress(ip))
+        );
+        assert_eq!(
+            msg.opts().get(OptionCode::ServerIdentifier),
+            Some(&DhcpOption::ServerIdentifier(server))
+        );
+        assert_eq!(msg.ciaddr(), Ipv4Addr::UNSPECIFIED);
+    }
+
+    #[test]
+    fn test_release_round_trips_through_server_parser() {
+        let ciaddr = Ipv4Addr::new(10, 0, 0, 50);
+        let probe = Probe::release(MAC, ciaddr, Ipv4Addr::new(10, 0, 0, 1))
+            .relayed(Ipv4Addr::new(10, 0, 0, 254));
+
+        let msg = parse_message(&probe.to_bytes()).unwrap();
+        assert_eq!(msg.opts().msg_type(), Some(MessageType::Release));
+        assert_eq!(msg.ciaddr(), ciaddr);
+        assert_eq!(msg.giaddr(), Ipv4Addr::new(10, 0, 0, 254));
+    }
+
+    #[test]
+    fn test_decline_names_the_declined_address() {
+        let ip = Ipv4Addr::new(10, 0, 0, 50);
+        let msg = Probe::decline(MAC, ip, Ipv4Addr::new(10, 0, 0, 1)).build();
+
+        assert_eq!(msg.opts().msg_type(), Some(MessageType::Decline));
+        assert_eq!(
+            msg.opts().get(OptionCode::RequestedIpAddress),
+            Some(&DhcpOption::RequestedIpAddress(ip))
+        );
+        assert_eq!(msg.ciaddr(), Ipv4Addr::UNSPECIFIED);
+    }
+}
@@ -0,0 +1,147 @@
+//! Offline decoding of captured DHCP packets.
+//!
+//! Backs the `rack-director decode-dhcp` subcommand: reads a hex dump of a packet
+//! (e.g. copied from Wireshark), runs it through the same `dhcproto` decoder the
+//! server uses, and renders every header field and option so support can see how
+//! the server would interpret it.
+
+use std::fmt::Write;
+use std::path::Path;
+
+use anyhow::{Context, Result, anyhow};
+use dhcproto::v4::Message;
+
+use super::display::OptionDisplay;
+use super::parse;
+use super::store::format_mac;
+
+/// Parse a hex dump into bytes.
+///
+/// Whitespace, `:` and `-` separators and `0x` prefixes are ignored, so output
+/// from most capture tools can be pasted as-is.
+pub fn parse_hex(text: &str) -> Result<Vec<u8>> {
+    let digits: String = text
+        .split_whitespace()
+        .map(|word| word.trim_start_matches("0x"))
+        .collect::<String>()
+        .chars()
+        .filter(|c| *c != ':' && *c != '-')
+        .collect();
+    if !digits.is_ascii() {
+        return Err(anyhow!("hex dump contains non-hex characters"));
+    }
+    if digits.len() % 2 != 0 {
+        return Err(anyhow!("hex dump has an odd number of digits"));
+    }
+    (0..digits.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&digits[i..i + 2], 16)
+                .with_context(|| format!("invalid hex byte {:?}", &digits[i..i + 2]))
+        })
+        .collect()
+}
+
+/// Read a hex dump from `path`, decode it and render it with [`describe`].
+pub fn decode_file(path: &Path) -> Result<String> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read {}", path.display()))?;
+    let bytes = parse_hex(&text)?;
+    let msg = parse::parse_message(&bytes).context("not a valid DHCP packet")?;
+    Ok(describe(&msg))
+}
+
+/// Render every header field and option of `msg`, one per line.
+///
+/// Options are listed in code order, including ones the decoder does not model, and
+/// rendered with [`OptionDisplay`].
+pub fn describe(msg: &Message) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "op:      {:?}", msg.opcode());
+    let _ = writeln!(out, "htype:   {:?}", msg.htype());
+    let _ = writeln!(out, "hops:    {}", msg.hops());
+    let _ = writeln!(out, "xid:     {:#010x}", msg.xid());
+    let _ = writeln!(out, "secs:    {}", msg.secs());
+    let _ = writeln!(out, "flags:   broadcast={}", msg.flags().broadcast());
+    let _ = writeln!(out, "ciaddr:  {}", msg.ciaddr());
+    let _ = writeln!(out, "yiaddr:  {}", msg.yiaddr());
+    let _ = writeln!(out, "siaddr:  {}", msg.siaddr());
+    let _ = writeln!(out, "giaddr:  {}", msg.giaddr());
+    let _ = writeln!(out, "chaddr:  {}", format_mac(msg.chaddr()));
+    let _ = writeln!(out, "sname:   {}", lossy_field(msg.sname()));
+    let _ = writeln!(out, "file:    {}", lossy_field(msg.fname()));
+    let _ = writeln!(out, "options:");
+
+    let mut options: Vec<_> = msg.opts().iter().collect();
+    options.sort_by_key(|(code, _)| u8::from(**code));
+    for (code, opt) in options {
+        let _ = writeln!(
+            out,
+            "  {:>3} {:?}: {}",
+            u8::from(*code),
+            code,
+            OptionDisplay(opt)
+        );
+    }
+    out
+}
+
+/// Header string fields are null-padded; show `-` when empty.
+fn lossy_field(field: Option<&[u8]>) -> String {
+    let bytes = field.unwrap_or_default();
+    let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    if end == 0 {
+        "-".to_string()
+    } else {
+        String::from_utf8_lossy(&bytes[..end]).into_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dhcproto::{Decodable, Decoder};
+
+    const PXE_DISCOVER: &str = include_str!("../../tests/fixtures/dhcp/pxe_discover.hex");
+
+    #[test]
+    fn test_parse_hex_accepts_common_separators() {
+        assert_eq!(
+            parse_hex("0x01 0x02\n0a:0b-ff").unwrap(),
+            vec![0x01, 0x02, 0x0a, 0x0b, 0xff]
+        );
+        assert!(parse_hex("abc").is_err());
+        assert!(parse_hex("zz").is_err());
+    }
+
+    #[test]
+    fn test_describe_captured_pxe_discover() {
+        let bytes = parse_hex(PXE_DISCOVER).unwrap();
+        let msg = Message::decode(&mut Decoder::new(&bytes)).unwrap();
+        let text = describe(&msg);
+
+        for line in [
+            "op:      BootRequest\n",
+            "xid:     0x3903f326\n",
+            "giaddr:  10.0.0.1\n",
+            "chaddr:  52:54:00:12:34:56\n",
+            "   53 MessageType: Discover\n",
+            "   55 ParameterRequestList: 1,3,6,15,66,67,150\n",
+            "   60 ClassIdentifier: \"PXEClient:Arch:00007:UNDI:003016\"\n",
+            "   61 ClientIdentifier: 01:52:54:00:12:34:56\n",
+            "   82 RelayAgentInformation: {circuit-id=65:74:68:30:2f:31 \"eth0/1\", remote-id=00:1b:21:aa:bb:cc \"..!...\"}\n",
+            "  224 Unknown(224): de:ad:be:ef\n",
+        ] {
+            assert!(text.contains(line), "missing {line:?} in:\n{text}");
+        }
+        // Options are listed in code order
+        let codes: Vec<u8> = text
+            .lines()
+            .skip_while(|l| *l != "options:")
+            .skip(1)
+            .map(|l| l.split_whitespace().next().unwrap().parse().unwrap())
+            .collect();
+        assert!(codes.windows(2).all(|w| w[0] < w[1]), "{codes:?}");
+        assert!(codes.contains(&82));
+    }
+}
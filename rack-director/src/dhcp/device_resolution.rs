@@ -4,12 +4,16 @@ use uuid::Uuid;
 
 use crate::database::Connection;
 use crate::director::Director;
+use crate::director::hardware_uuid::byte_swapped;
+use crate::lifecycle::DeviceLifecycle;
 
 /// Pre-resolved device context for DHCP handling.
 pub struct DeviceContext {
     pub device_uuid: Option<Uuid>,
     pub is_disabled: bool,
     pub disable_reason: Option<String>,
+    /// Lifecycle state of the resolved device, if any.
+    pub lifecycle: Option<DeviceLifecycle>,
 }
 
 /// Trait for resolving device information from a MAC address and optional GUID.
@@ -37,16 +41,55 @@ pub trait DeviceResolver: Send + Sync {
     ) -> Result<()>;
 }
 
+/// Interfaces a device may have before new MACs are refused, unless configured.
+pub const DEFAULT_MAX_INTERFACES_PER_DEVICE: usize = 16;
+
 /// Stateless DeviceResolver implementation backed by the Director service.
 ///
 /// Each call constructs a short-lived `Director` from the provided connection, so no
 /// connection is stored here. This keeps `DirectorDeviceResolver` cheaply cloneable
 /// and `Send + Sync`.
-pub struct DirectorDeviceResolver;
+#[derive(Debug, Clone)]
+pub struct DirectorDeviceResolver {
+    max_interfaces: usize,
+}
 
 impl DirectorDeviceResolver {
     pub fn new() -> Self {
-        Self
+        Self {
+            max_interfaces: DEFAULT_MAX_INTERFACES_PER_DEVICE,
+        }
+    }
+
+    /// Refuse to link more than `max` interfaces to one device by GUID.
+    ///
+    /// A client spoofing many MACs with one option-97 UUID would otherwise add an
+    /// interface to that device for every MAC it makes up.
+    pub fn with_max_interfaces(mut self, max: usize) -> Self {
+        self.max_interfaces = max;
+        self
+    }
+
+    /// Whether `mac`, which reported `uuid` as its GUID, may be linked to that device.
+    ///
+    /// Interfaces the device already has are always allowed.
+    async fn may_link_interface(
+        &self,
+        director: &Director<'_>,
+        uuid: &Uuid,
+        mac: &str,
+    ) -> Result<bool> {
+        let macs = director.get_interface_macs(uuid).await?;
+        if macs.contains(mac) || macs.len() < self.max_interfaces {
+            return Ok(true);
+        }
+        log::warn!(
+            "Device {} already has {} interfaces; refusing new interface {}",
+            uuid,
+            macs.len(),
+            mac
+        );
+        Ok(false)
     }
 }
 
@@ -67,17 +110,23 @@ impl DeviceResolver for DirectorDeviceResolver {
         let director = Director::new(conn);
 
         // Try GUID-based resolution first if GUID is provided
-        let mut device_uuid = if let Some(guid) = guid {
-            if director.device_exists(guid).await? {
-                log::debug!("Resolved device {} via GUID", guid);
-                Some(*guid)
-            } else {
-                None
-            }
-        } else {
-            None
+        let mut device_uuid = match guid {
+            Some(guid) => resolve_guid(&director, guid).await?,
+            None => None,
         };
 
+        // A GUID match links this MAC to the device, so it counts against the cap
+        if let Some(uuid) = &device_uuid
+            && !self.may_link_interface(&director, uuid, mac).await?
+        {
+            return Ok(DeviceContext {
+                device_uuid: Some(*uuid),
+                is_disabled: true,
+                disable_reason: Some("too many interfaces for device".to_string()),
+                lifecycle: None,
+            });
+        }
+
         // Fall back to MAC-based resolution if GUID didn't match
         if device_uuid.is_none() {
             device_uuid = director.find_device_by_mac(mac).await?;
@@ -89,6 +138,18 @@ impl DeviceResolver for DirectorDeviceResolver {
             }
         }
 
+        // Every packet from a known device is a sign of life for the NIC that sent it
+        if let Some(uuid) = &device_uuid
+            && let Err(e) = director.mark_interface_seen(uuid, mac).await
+        {
+            log::warn!(
+                "Failed to record interface {} of {} as seen: {}",
+                mac,
+                uuid,
+                e
+            );
+        }
+
         // Check if interface is disabled
         let (is_disabled, disable_reason) = if let Some(uuid) = &device_uuid {
             let interfaces = director.get_network_interfaces(uuid).await?;
@@ -105,10 +166,16 @@ impl DeviceResolver for DirectorDeviceResolver {
             (false, None)
         };
 
+        let lifecycle = match &device_uuid {
+            Some(uuid) => director.get_device_lifecycle(uuid).await?,
+            None => None,
+        };
+
         Ok(DeviceContext {
             device_uuid,
             is_disabled,
             disable_reason,
+            lifecycle,
         })
     }
 
@@ -124,6 +191,23 @@ impl DeviceResolver for DirectorDeviceResolver {
     }
 }
 
+/// The device whose UUID is `guid`, in either byte order.
+///
+/// Some firmware sends option 97 big-endian, so its bytes read as a different
+/// UUID from the one iPXE registered the device under.
+async fn resolve_guid(director: &Director<'_>, guid: &Uuid) -> Result<Option<Uuid>> {
+    if director.device_exists(guid).await? {
+        log::debug!("Resolved device {} via GUID", guid);
+        return Ok(Some(*guid));
+    }
+    let swapped = byte_swapped(guid);
+    if director.device_exists(&swapped).await? {
+        log::debug!("Resolved device {} via byte-swapped GUID {}", swapped, guid);
+        return Ok(Some(swapped));
+    }
+    Ok(None)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -134,6 +218,80 @@ mod tests {
         database::run_migrations(&factory).await.unwrap()
     }
 
+    #[tokio::test]
+    async fn test_repeat_resolve_advances_interface_last_seen() {
+        let conn = create_test_db(test_connection_factory!()).await;
+        let director = Director::new(&conn);
+        let uuid = Uuid::parse_str("550e8400-e29b-41d4-a716-4466554402a1").unwrap();
+        let mac = "aa:bb:cc:00:02:a1";
+        director
+            .register_device(&uuid, crate::director::Architecture::X86_64)
+            .await
+            .unwrap();
+        director
+            .set_device_ip_address(&uuid, "10.0.0.5", mac)
+            .await
+            .unwrap();
+        assert!(
+            director
+                .get_interface_last_seen(&uuid)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+
+        let resolver = DirectorDeviceResolver::new();
+        let ctx = resolver.resolve(&conn, mac, None).await.unwrap();
+        assert_eq!(ctx.device_uuid, Some(uuid));
+        let first = director.get_interface_last_seen(&uuid).await.unwrap()[mac];
+
+        // Backdate so the second DISCOVER is distinguishable at timestamp precision
+        conn.execute(
+            "UPDATE interface_activity SET last_seen_at = ?1",
+            (database::to_db_time(first - chrono::Duration::minutes(5)),),
+        )
+        .await
+        .unwrap();
+
+        resolver.resolve(&conn, mac, None).await.unwrap();
+        let second = director.get_interface_last_seen(&uuid).await.unwrap()[mac];
+        assert!(second >= first);
+    }
+
+    #[tokio::test]
+    async fn test_interface_cap_refuses_extra_macs_for_one_guid() {
+        let conn = create_test_db(test_connection_factory!()).await;
+        let director = Director::new(&conn);
+        let uuid = Uuid::parse_str("550e8400-e29b-41d4-a716-4466554402a2").unwrap();
+        director
+            .register_device(&uuid, crate::director::Architecture::X86_64)
+            .await
+            .unwrap();
+        let resolver = DirectorDeviceResolver::new().with_max_interfaces(3);
+        let mac = |n: u8| format!("aa:bb:cc:00:03:{n:02x}");
+
+        for n in 0..3 {
+            let ctx = resolver.resolve(&conn, &mac(n), Some(&uuid)).await.unwrap();
+            assert_eq!(ctx.device_uuid, Some(uuid));
+            assert!(!ctx.is_disabled);
+        }
+
+        // The N+1th MAC is refused and never recorded
+        let ctx = resolver.resolve(&conn, &mac(3), Some(&uuid)).await.unwrap();
+        assert!(ctx.is_disabled);
+        assert_eq!(
+            ctx.disable_reason.as_deref(),
+            Some("too many interfaces for device")
+        );
+        let macs = director.get_interface_macs(&uuid).await.unwrap();
+        assert_eq!(macs.len(), 3);
+        assert!(!macs.contains(&mac(3)));
+
+        // Interfaces the device already has keep resolving
+        let ctx = resolver.resolve(&conn, &mac(0), Some(&uuid)).await.unwrap();
+        assert!(!ctx.is_disabled);
+    }
+
     #[tokio::test]
     async fn test_resolve_unknown_mac() {
         let conn = create_test_db(test_connection_factory!()).await;
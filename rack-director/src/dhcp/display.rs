@@ -0,0 +1,208 @@
+//! Human-readable rendering of DHCP messages for logs.
+//!
+//! `dhcproto`'s `Debug` output dumps raw byte vectors, which makes MACs, client
+//! identifiers and relay sub-options unreadable. These wrappers render addresses as
+//! dotted quads, hardware addresses as colon-hex, message types by name, and opaque
+//! relay ids as both hex and ASCII.
+
+use std::fmt;
+
+use dhcproto::v4::relay::{RelayCode, RelayInfo};
+use dhcproto::v4::{DhcpOption, Message, OptionCode};
+
+use super::store::format_mac;
+
+/// Displays a whole message on one line, options in code order.
+pub struct PacketDisplay<'a>(pub &'a Message);
+
+/// Displays a single option's value.
+pub struct OptionDisplay<'a>(pub &'a DhcpOption);
+
+/// Log target of the per-option dump of received packets, so it can be enabled on
+/// its own with `LOG=rack_director::dhcp::options=trace`.
+pub const OPTION_DUMP_TARGET: &str = "rack_director::dhcp::options";
+
+/// Displays every option of a message, one per line as `code name: value`, with
+/// options the parser does not know shown as hex.
+pub struct OptionsDump<'a>(pub &'a Message);
+
+impl fmt::Display for PacketDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let msg = self.0;
+        let message_type = match msg.opts().get(OptionCode::MessageType) {
+            Some(DhcpOption::MessageType(mt)) => format!("{:?}", mt),
+            _ => "(no type)".to_string(),
+        };
+        write!(
+            f,
+            "{:?} {} xid={:#010x} chaddr={} ciaddr={} yiaddr={} siaddr={} giaddr={}",
+            msg.opcode(),
+            message_type,
+            msg.xid(),
+            format_mac(msg.chaddr()),
+            msg.ciaddr(),
+            msg.yiaddr(),
+            msg.siaddr(),
+            msg.giaddr()
+        )?;
+
+        let mut options: Vec<_> = msg.opts().iter().collect();
+        options.sort_by_key(|(code, _)| u8::from(**code));
+        for (i, (code, opt)) in options.into_iter().enumerate() {
+            let sep = if i == 0 { " options: " } else { ", " };
+            write!(f, "{}{}={}", sep, u8::from(*code), OptionDisplay(opt))?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for OptionDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            DhcpOption::MessageType(mt) => write!(f, "{:?}", mt),
+            DhcpOption::ServerIdentifier(ip) | DhcpOption::RequestedIpAddress(ip) => {
+                write!(f, "{}", ip)
+            }
+            DhcpOption::SubnetMask(mask) => write!(f, "{}", mask),
+            DhcpOption::Router(ips) | DhcpOption::DomainNameServer(ips) => {
+                let ips: Vec<String> = ips.iter().map(|ip| ip.to_string()).collect();
+                write!(f, "{}", ips.join(","))
+            }
+            DhcpOption::AddressLeaseTime(secs)
+            | DhcpOption::Renewal(secs)
+            | DhcpOption::Rebinding(secs) => write!(f, "{}s", secs),
+            DhcpOption::ParameterRequestList(codes) => {
+                let codes: Vec<String> = codes.iter().map(|c| u8::from(*c).to_string()).collect();
+                write!(f, "{}", codes.join(","))
+            }
+            DhcpOption::ClassIdentifier(data) | DhcpOption::UserClass(data) => {
+                write!(f, "{:?}", String::from_utf8_lossy(data))
+            }
+            DhcpOption::ClientIdentifier(data) => write!(f, "{}", format_mac(data)),
+            DhcpOption::RelayAgentInformation(info) => {
+                let circuit = match info.get(RelayCode::AgentCircuitId) {
+                    Some(RelayInfo::AgentCircuitId(id)) => Some(id),
+                    _ => None,
+                };
+                let remote = match info.get(RelayCode::AgentRemoteId) {
+                    Some(RelayInfo::AgentRemoteId(id)) => Some(id),
+                    _ => None,
+                };
+                write!(f, "{{circuit-id=")?;
+                write_opaque(f, circuit.map(Vec::as_slice))?;
+                write!(f, ", remote-id=")?;
+                write_opaque(f, remote.map(Vec::as_slice))?;
+                write!(f, "}}")
+            }
+            DhcpOption::Unknown(unknown) => write!(f, "{}", format_mac(unknown.data())),
+            other => write!(f, "{:?}", other),
+        }
+    }
+}
+
+impl fmt::Display for OptionsDump<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut options: Vec<_> = self.0.opts().iter().collect();
+        options.sort_by_key(|(code, _)| u8::from(**code));
+        for (i, (code, opt)) in options.into_iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{:>3} ", u8::from(*code))?;
+            match code {
+                OptionCode::Unknown(_) => write!(f, "unknown")?,
+                code => write!(f, "{:?}", code)?,
+            }
+            write!(f, ": {}", OptionDisplay(opt))?;
+        }
+        Ok(())
+    }
+}
+
+/// Write opaque bytes as colon-hex followed by a quoted ASCII rendering, with
+/// non-printable bytes shown as `.`; `-` when absent.
+fn write_opaque(f: &mut fmt::Formatter<'_>, bytes: Option<&[u8]>) -> fmt::Result {
+    let Some(bytes) = bytes else {
+        return write!(f, "-");
+    };
+    let ascii: String = bytes
+        .iter()
+        .map(|&b| {
+            if b.is_ascii_graphic() || b == b' ' {
+                b as char
+            } else {
+                '.'
+            }
+        })
+        .collect();
+    write!(f, "{} {:?}", format_mac(bytes), ascii)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dhcproto::v4::{MessageType, Opcode, UnknownOption, relay::RelayAgentInformation};
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn test_packet_display_snapshot() {
+        let mut msg = Message::default();
+        msg.set_opcode(Opcode::BootRequest);
+        msg.set_xid(0x3903f326);
+        msg.set_chaddr(&[0x52, 0x54, 0x00, 0x12, 0x34, 0x56]);
+        msg.set_giaddr(Ipv4Addr::new(10, 0, 0, 1));
+
+        let mut relay = RelayAgentInformation::default();
+        relay.insert(RelayInfo::AgentCircuitId(b"eth0/1".to_vec()));
+        relay.insert(RelayInfo::AgentRemoteId(vec![0x00, 0x1b, 0x21, 0xaa]));
+
+        let opts = msg.opts_mut();
+        opts.insert(DhcpOption::MessageType(MessageType::Discover));
+        opts.insert(DhcpOption::RequestedIpAddress(Ipv4Addr::new(10, 0, 0, 50)));
+        opts.insert(DhcpOption::ParameterRequestList(vec![
+            OptionCode::SubnetMask,
+            OptionCode::Router,
+            OptionCode::BootfileName,
+        ]));
+        opts.insert(DhcpOption::ClassIdentifier(b"PXEClient".to_vec()));
+        opts.insert(DhcpOption::ClientIdentifier(vec![
+            0x01, 0x52, 0x54, 0x00, 0x12, 0x34, 0x56,
+        ]));
+        opts.insert(DhcpOption::RelayAgentInformation(relay));
+
+        assert_eq!(
+            PacketDisplay(&msg).to_string(),
+            "BootRequest Discover xid=0x3903f326 chaddr=52:54:00:12:34:56 \
+             ciaddr=0.0.0.0 yiaddr=0.0.0.0 siaddr=0.0.0.0 giaddr=10.0.0.1 \
+             options: 50=10.0.0.50, 53=Discover, 55=1,3,67, 60=\"PXEClient\", \
+             61=01:52:54:00:12:34:56, \
+             82={circuit-id=65:74:68:30:2f:31 \"eth0/1\", remote-id=00:1b:21:aa \"..!.\"}"
+        );
+    }
+
+    #[test]
+    fn test_options_dump_snapshot() {
+        let mut msg = Message::default();
+        let opts = msg.opts_mut();
+        opts.insert(DhcpOption::MessageType(MessageType::Request));
+        opts.insert(DhcpOption::ClassIdentifier(b"PXEClient".to_vec()));
+        opts.insert(DhcpOption::Unknown(UnknownOption::new(
+            OptionCode::from(224),
+            vec![0xde, 0xad, 0x01],
+        )));
+
+        assert_eq!(
+            OptionsDump(&msg).to_string(),
+            " 53 MessageType: Request\n 60 ClassIdentifier: \"PXEClient\"\n224 unknown: de:ad:01"
+        );
+    }
+
+    #[test]
+    fn test_option_display_missing_relay_ids() {
+        let opt = DhcpOption::RelayAgentInformation(RelayAgentInformation::default());
+        assert_eq!(
+            OptionDisplay(&opt).to_string(),
+            "{circuit-id=-, remote-id=-}"
+        );
+    }
+}
@@ -1,38 +1,68 @@
 use anyhow::Result;
-use dhcproto::{
-    Decodable, Encodable,
-    decoder::Decoder,
-    encoder::Encoder,
-    v4::{self, Architecture, Message, MessageType},
-};
-use log::{debug, info, trace, warn};
+use dhcproto::v4::{self, Architecture, Message, MessageType, Opcode};
+use log::{Level, debug, info, log_enabled, trace, warn};
+use std::collections::HashSet;
 use std::net::{Ipv4Addr, SocketAddr};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio_recvmsg::PktInfo;
 
-use super::allocator;
-use super::boot_config::BootConfigProvider;
+use super::allocator::{self, AllocationStrategy};
+use super::boot_config::{BootConfigProvider, UserClassBootFile};
+use super::bootp::{self, BootpRequest};
 use super::device_resolution::{DeviceContext, DeviceResolver};
+use super::display::{OPTION_DUMP_TARGET, OptionsDump, PacketDisplay};
 use super::interface;
-use super::message_builder;
-use super::request::{RequestContext, extract_server_identifier};
+use super::message_builder::{self, LeaseTimers};
+use super::options::{DomainSearch, PxeVendorOptions};
+use super::oui::{Oui, OuiFilter};
+use super::parse;
+use super::rate_limit::AllocationRateLimiter;
+use super::recent::{Decision, PacketEvent, RecentPackets};
+use super::request::{Option82Data, RequestContext, extract_server_identifier, parse_option82};
 use super::store::{self, DhcpNetwork, LeaseState, format_mac};
 use crate::database::{Connection, ConnectionFactory};
+use crate::lifecycle::DeviceLifecycle;
 
 /// Reply to send after processing a DHCP packet.
 pub enum DhcpReply {
-    /// L2 client response. `local_ip` selects which per-network socket to send from.
-    /// `peer_addr` is the source address of the received packet — used to decide
-    /// whether to broadcast (peer IP is unspecified) or unicast (peer IP is set, e.g. test/renewal).
+    /// L2 client response. `local_ip` selects which per-network socket to send from;
+    /// `dest` is chosen by [`l2_reply_destination`] (or, for legacy BOOTP, by
+    /// [`BootpRequest::reply_destination`]).
     L2 {
         data: Vec<u8>,
         local_ip: Ipv4Addr,
-        peer_addr: SocketAddr,
+        dest: SocketAddr,
     },
     /// Relay agent response — unicast to the relay agent on port 67.
     Relay { data: Vec<u8>, dest: SocketAddr },
 }
 
+/// Where to send an L2 reply of type `reply_type` to `msg`, which arrived from
+/// `peer_addr` (RFC 2131 §4.1).
+///
+/// A NAK is always broadcast, since the client may no longer be able to use the
+/// address it is being refused. Otherwise a client in RENEWING or REBINDING puts its
+/// address in `ciaddr` and can take a unicast there, so it gets one on `ciaddr:68`
+/// whatever the packet's source was. A client without an address yet (source
+/// `0.0.0.0`) gets a broadcast; anything else is answered where it came from.
+pub(crate) fn l2_reply_destination(
+    msg: &Message,
+    reply_type: Option<MessageType>,
+    peer_addr: SocketAddr,
+) -> SocketAddr {
+    let broadcast = SocketAddr::new(Ipv4Addr::BROADCAST.into(), 68);
+    if reply_type == Some(MessageType::Nak) {
+        broadcast
+    } else if msg.ciaddr() != Ipv4Addr::UNSPECIFIED {
+        SocketAddr::new(msg.ciaddr().into(), 68)
+    } else if peer_addr.ip().is_unspecified() {
+        broadcast
+    } else {
+        peer_addr
+    }
+}
+
 /// Determines the appropriate vendor class identifier (Option 60) based on client architecture.
 ///
 /// # DHCP Option 60 - Vendor Class Identifier
@@ -68,12 +98,103 @@ fn determine_vendor_class_identifier(client_arch: Option<Architecture>) -> &'sta
     }
 }
 
+/// Addresses tried for one DISCOVER before giving up on conflicting leases.
+const MAX_OFFER_ATTEMPTS: usize = 3;
+
 #[derive(Clone)]
 pub struct DhcpHandler {
     db: Arc<dyn ConnectionFactory>,
     device_resolver: Arc<dyn DeviceResolver>,
     boot_config: BootConfigProvider,
     server_identifier: Ipv4Addr,
+    /// Serializes lease allocation and lease-state changes across packet tasks.
+    allocation_lock: Arc<tokio::sync::Mutex<()>>,
+    /// T1/T2 ratios advertised in OFFER and ACK.
+    lease_timers: LeaseTimers,
+    /// Shortest lease granted to a client that requests one (Option 51).
+    min_lease_secs: u32,
+    /// Client vendors we answer DISCOVER and REQUEST from.
+    oui_filter: OuiFilter,
+    /// Whether we own the configured subnets outright. When false, REQUESTs for
+    /// addresses we have no record of are ignored instead of NAKed.
+    authoritative: bool,
+    /// What we did with recently received packets, for `GET /api/dhcp/recent`.
+    recent: RecentPackets,
+    /// How long to hold each OFFER before sending it. ACKs and NAKs are never delayed.
+    offer_delay: Duration,
+    /// Seconds an offered address is held for the client's REQUEST.
+    offer_ttl: u32,
+    /// Caps how many new clients are offered addresses per window.
+    allocation_rate: Arc<AllocationRateLimiter>,
+    /// Search list sent as option 119 to clients that request it.
+    domain_search: Option<DomainSearch>,
+    /// PXE sub-options sent as option 43 to PXE ROMs.
+    pxe_vendor: Option<PxeVendorOptions>,
+    /// Answer cookie-less BOOTREQUESTs from reserved MACs with a plain BOOTREPLY.
+    legacy_bootp: bool,
+    /// Ceiling on reply size, IP and UDP headers included, whatever the client allows.
+    max_message_size: u16,
+    /// Answer DISCOVERs carrying Rapid Commit (option 80) with an ACK.
+    rapid_commit: bool,
+    /// How new addresses are picked from a pool.
+    allocation_strategy: AllocationStrategy,
+}
+
+/// Whether `ip` lies within `network`'s subnet.
+fn network_contains(network: &DhcpNetwork, ip: Ipv4Addr) -> Result<bool> {
+    let subnet: common::Ipv4Subnet = network
+        .subnet
+        .parse()
+        .map_err(|e: common::Ipv4SubnetError| anyhow::anyhow!("{}", e))?;
+    Ok(subnet.ip_in_range(ip))
+}
+
+/// Where a REQUESTed address lies relative to the subnets we serve.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AddressScope {
+    /// Inside the network the client is on.
+    OnNetwork,
+    /// Inside another network we serve.
+    WrongSubnet,
+    /// Inside none of our networks.
+    Unserved,
+}
+
+/// Classify `ip`, requested by a client on `network`.
+async fn requested_address_scope(
+    conn: &Connection,
+    network: &DhcpNetwork,
+    ip: Ipv4Addr,
+) -> Result<AddressScope> {
+    if network_contains(network, ip)? {
+        return Ok(AddressScope::OnNetwork);
+    }
+    let networks = store::list_networks(conn).await?;
+    Ok(match interface::find_network_containing(ip, &networks)? {
+        Some(_) => AddressScope::WrongSubnet,
+        None => AddressScope::Unserved,
+    })
+}
+
+/// The MAC of another client holding `ip` on `network`, through an active lease or a
+/// static reservation.
+///
+/// Only called for a client without a reservation of its own, so a reservation
+/// matching `mac` or `ip` can only be someone else's claim on `ip`.
+async fn address_holder(
+    conn: &Connection,
+    network: &DhcpNetwork,
+    ip: Ipv4Addr,
+    mac: &str,
+) -> Result<Option<String>> {
+    if let Some(lease) = store::get_active_lease_by_ip(conn, &ip).await?
+        && lease.mac_address != mac
+    {
+        return Ok(Some(lease.mac_address));
+    }
+    let reservation =
+        store::find_conflicting_static_reservation(conn, network.id, mac, &ip.to_string()).await?;
+    Ok(reservation.map(|r| r.mac_address))
 }
 
 impl DhcpHandler {
@@ -88,7 +209,226 @@ impl DhcpHandler {
             device_resolver,
             boot_config,
             server_identifier,
+            allocation_lock: Arc::new(tokio::sync::Mutex::new(())),
+            lease_timers: LeaseTimers::default(),
+            min_lease_secs: message_builder::DEFAULT_MIN_LEASE_SECS,
+            oui_filter: OuiFilter::default(),
+            authoritative: true,
+            recent: RecentPackets::default(),
+            offer_delay: Duration::ZERO,
+            offer_ttl: store::DEFAULT_OFFER_TTL,
+            allocation_rate: Arc::new(AllocationRateLimiter::default()),
+            domain_search: None,
+            pxe_vendor: None,
+            legacy_bootp: false,
+            max_message_size: message_builder::DEFAULT_MAX_MESSAGE_SIZE,
+            rapid_commit: false,
+            allocation_strategy: AllocationStrategy::default(),
+        }
+    }
+
+    /// Resolve devices with `resolver` instead of the one given to [`DhcpHandler::new`].
+    pub fn with_device_resolver(mut self, resolver: Arc<dyn DeviceResolver>) -> Self {
+        self.device_resolver = resolver;
+        self
+    }
+
+    /// Advertise renewal/rebinding times using `timers` instead of the defaults.
+    pub fn with_lease_timers(mut self, timers: LeaseTimers) -> Self {
+        self.lease_timers = timers;
+        self
+    }
+
+    /// Never grant a client-requested lease shorter than `secs`.
+    pub fn with_min_lease_time(mut self, secs: u32) -> Self {
+        self.min_lease_secs = secs;
+        self
+    }
+
+    /// Only answer clients whose MAC vendor prefix `filter` permits.
+    pub fn with_oui_filter(mut self, filter: OuiFilter) -> Self {
+        self.oui_filter = filter;
+        self
+    }
+
+    /// NAK (`true`, the default) or ignore (`false`) REQUESTs for addresses outside
+    /// our scope, such as a stale lease from another network.
+    pub fn with_authoritative(mut self, authoritative: bool) -> Self {
+        self.authoritative = authoritative;
+        self
+    }
+
+    /// Wait `delay` before sending each OFFER, so that another DHCP server on the
+    /// segment answers first and this one acts as a backup.
+    pub fn with_offer_delay(mut self, delay: Duration) -> Self {
+        self.offer_delay = delay;
+        self
+    }
+
+    /// Hold each offered address for `secs` (at most the lease time) waiting for the
+    /// client's REQUEST, after which it can be offered to someone else.
+    pub fn with_offer_ttl(mut self, secs: u32) -> Self {
+        self.offer_ttl = secs;
+        self
+    }
+
+    /// Drop DISCOVERs from clients we have never seen once `limiter` is exhausted.
+    /// Known clients and renewals are always answered.
+    pub fn with_allocation_rate_limit(mut self, limiter: AllocationRateLimiter) -> Self {
+        self.allocation_rate = Arc::new(limiter);
+        self
+    }
+
+    /// Send `search` as option 119 to clients that list it in option 55.
+    pub fn with_domain_search(mut self, search: Option<DomainSearch>) -> Self {
+        self.domain_search = search;
+        self
+    }
+
+    /// Send `options` as option 43 to PXE ROMs (vendor class `PXEClient`), to steer
+    /// boot server discovery and the boot menu prompt. iPXE ignores them and is
+    /// never sent them.
+    pub fn with_pxe_vendor_options(mut self, options: Option<PxeVendorOptions>) -> Self {
+        self.pxe_vendor = options.filter(|o| !o.is_empty());
+        self
+    }
+
+    /// Serve the given boot files to clients by user class (option 77).
+    pub fn with_user_class_boot_files(mut self, boot_files: Vec<UserClassBootFile>) -> Self {
+        self.boot_config = self.boot_config.with_user_class_boot_files(boot_files);
+        self
+    }
+
+    /// Answer legacy BOOTP clients (no DHCP magic cookie) that have a static
+    /// reservation. Off by default, so such packets are dropped as malformed.
+    pub fn with_legacy_bootp(mut self, enabled: bool) -> Self {
+        self.legacy_bootp = enabled;
+        self
+    }
+
+    /// Never send replies larger than `size` bytes (IP and UDP headers included), even
+    /// to clients announcing a larger maximum in option 57. Values below the RFC 2131
+    /// minimum of 576 act as 576.
+    pub fn with_max_message_size(mut self, size: u16) -> Self {
+        self.max_message_size = size;
+        self
+    }
+
+    /// Answer a DISCOVER carrying Rapid Commit (option 80, RFC 4039) with an ACK
+    /// that commits the lease, skipping the OFFER/REQUEST round trip. Off by
+    /// default, so such DISCOVERs get an ordinary OFFER.
+    pub fn with_rapid_commit(mut self, enabled: bool) -> Self {
+        self.rapid_commit = enabled;
+        self
+    }
+
+    /// Pick new addresses from a pool by `strategy` (sequential by default).
+    /// Reservations and existing leases are unaffected.
+    pub fn with_allocation_strategy(mut self, strategy: AllocationStrategy) -> Self {
+        self.allocation_strategy = strategy;
+        self
+    }
+
+    /// Seconds to lease to the client: its requested time clamped to
+    /// `[min_lease_secs, network.lease_duration]`, or the network's duration.
+    fn lease_time(&self, req_ctx: &RequestContext, network: &DhcpNetwork) -> u32 {
+        message_builder::grant_lease_time(
+            req_ctx.requested_lease_time,
+            self.min_lease_secs,
+            network.lease_duration,
+        )
+    }
+
+    /// The log of recently received packets this handler records into.
+    pub fn recent(&self) -> &RecentPackets {
+        &self.recent
+    }
+
+    /// Add a packet and the decision made about it to the recent-packet log.
+    fn note(&self, msg: Option<&Message>, decision: Decision) {
+        let addr = |ip: Ipv4Addr| (!ip.is_unspecified()).then_some(ip);
+        self.recent.record(PacketEvent {
+            at: chrono::Utc::now(),
+            client: msg.and_then(|m| addr(m.ciaddr())),
+            relay: msg.and_then(|m| addr(m.giaddr())),
+            mac: msg.map(|m| format_mac(m.chaddr())),
+            message_type: msg
+                .and_then(|m| m.opts().msg_type())
+                .map(|t| format!("{:?}", t)),
+            decision,
+        });
+    }
+
+    /// Record that `msg` was dropped without a reply because of `reason`.
+    fn note_ignored(&self, msg: Option<&Message>, reason: impl Into<String>) {
+        self.note(
+            msg,
+            Decision::Ignored {
+                reason: reason.into(),
+            },
+        );
+    }
+
+    /// Decode a received packet, returning `None` if it is malformed or is not a
+    /// BOOTREQUEST.
+    ///
+    /// Servers only act on client requests; a BOOTREPLY seen on our port is another
+    /// server's answer (misrouted or looped back) and must not allocate leases.
+    fn decode_request(&self, data: &[u8]) -> Option<Message> {
+        let msg = match parse::parse_message(data) {
+            Ok(msg) => msg,
+            Err(e) => {
+                log::warn!("Failed to decode DHCP message: {}", e);
+                self.note_ignored(None, format!("malformed packet: {}", e));
+                return None;
+            }
+        };
+        if msg.opcode() != Opcode::BootRequest {
+            debug!(
+                "Ignoring DHCP packet with op {:?} from chaddr {} (not a BOOTREQUEST)",
+                msg.opcode(),
+                format_mac(msg.chaddr())
+            );
+            self.note_ignored(Some(&msg), "not a BOOTREQUEST");
+            return None;
+        }
+        // Formatting every option is costly; skip it unless someone is listening
+        if log_enabled!(target: OPTION_DUMP_TARGET, Level::Trace) {
+            trace!(
+                target: OPTION_DUMP_TARGET,
+                "DHCP: Options from {}:\n{}",
+                format_mac(msg.chaddr()),
+                OptionsDump(&msg)
+            );
         }
+        Some(msg)
+    }
+
+    /// Whether to answer `msg`, logging the ones the OUI filter rejects.
+    fn oui_permitted(&self, msg: &Message, kind: &str) -> bool {
+        match self.oui_rejection(msg.chaddr(), kind) {
+            Some(reason) => {
+                self.note_ignored(Some(msg), reason);
+                false
+            }
+            None => true,
+        }
+    }
+
+    /// Why the OUI filter rejects a `kind` request from `mac`, logged, or `None` if
+    /// it permits it.
+    fn oui_rejection(&self, mac: &[u8], kind: &str) -> Option<String> {
+        if self.oui_filter.permits(mac) {
+            return None;
+        }
+        let oui = Oui::of(mac).map_or_else(|| "-".to_string(), |o| o.to_string());
+        info!(
+            "Ignoring DHCP {} from MAC {}: OUI {} is not permitted",
+            kind,
+            format_mac(mac),
+            oui
+        );
+        Some(format!("OUI {} is not permitted", oui))
     }
 
     /// Handle a DHCP packet received on the wildcard broadcast socket.
@@ -101,36 +441,40 @@ impl DhcpHandler {
         data: &[u8],
         pkt_info: &PktInfo,
     ) -> Result<Option<DhcpReply>> {
-        let msg = match Message::decode(&mut Decoder::new(data)) {
-            Ok(msg) => msg,
-            Err(e) => {
-                log::warn!("Failed to decode DHCP message: {}", e);
-                return Ok(None);
-            }
+        if let Some(request) = self.legacy_bootp_request(data) {
+            let conn = self.db.open().await?;
+            let l2_networks = store::get_l2_networks(&conn).await?;
+            let l2 = interface::find_matching_l2_network(pkt_info.if_index, &l2_networks)?;
+            return self.handle_bootp(&conn, &request, l2).await;
+        }
+        let Some(msg) = self.decode_request(data) else {
+            return Ok(None);
         };
 
-        trace!("DHCP: Received packet {:?}", msg);
-        let conn = self.db.open().await?;
+        trace!("DHCP: Received packet {}", PacketDisplay(&msg));
+        let mut conn = self.db.open().await?;
 
         // If relay agent (giaddr != 0), use relay-based network selection
         if msg.giaddr() != Ipv4Addr::UNSPECIFIED {
             let relay_agent = msg.giaddr();
-            let network = match store::get_network_by_relay(&conn, Some(relay_agent)).await? {
-                Some(n) => n,
-                None => {
-                    log::warn!("No network found for relay agent {}", relay_agent);
-                    return Ok(None);
-                }
+            let option82 = parse_option82(&msg).unwrap_or_default();
+            let Some(network) = self.relay_network(&conn, &msg, &option82).await? else {
+                return Ok(None);
             };
-            debug!(
-                "Using network '{}' (id={}) for relay {}",
-                network.name, network.id, relay_agent
-            );
+            // RFC 5107: the relay asked to stand in for us so renewals reach it
+            let server_identifier = option82
+                .server_id_override
+                .or(network.server_identifier)
+                .unwrap_or(self.server_identifier);
             let dest = SocketAddr::new(relay_agent.into(), 67);
             return self
-                .process_and_reply(&conn, &msg, &network, self.server_identifier, move |data| {
-                    DhcpReply::Relay { data, dest }
-                })
+                .process_and_reply(
+                    &mut conn,
+                    &msg,
+                    &network,
+                    server_identifier,
+                    move |data, _| DhcpReply::Relay { data, dest },
+                )
                 .await;
         }
 
@@ -143,21 +487,87 @@ impl DhcpHandler {
                 "No L2 network matches interface {}, dropping",
                 pkt_info.if_index
             );
+            self.note_ignored(
+                Some(&msg),
+                format!("no network for interface {}", pkt_info.if_index),
+            );
             return Ok(None);
         };
         debug!(
             "Using network '{}' (id={}) for interface index {} (local_ip={})",
             network.name, network.id, pkt_info.if_index, local_ip
         );
+        let request = &msg;
         let peer_addr = pkt_info.addr_src;
-        self.process_and_reply(&conn, &msg, network, local_ip, move |data| DhcpReply::L2 {
-            data,
-            local_ip,
-            peer_addr,
-        })
+        let server_identifier = network.server_identifier.unwrap_or(local_ip);
+        self.process_and_reply(
+            &mut conn,
+            &msg,
+            network,
+            server_identifier,
+            move |data, reply_type| DhcpReply::L2 {
+                data,
+                local_ip,
+                dest: l2_reply_destination(request, reply_type, peer_addr),
+            },
+        )
         .await
     }
 
+    /// Pick the network for a relayed packet.
+    ///
+    /// The Link Selection sub-option (RFC 3527) names the client's subnet directly and
+    /// wins over giaddr, which then only says where to send the reply. Returns `None`
+    /// (after noting why) when no network matches.
+    async fn relay_network(
+        &self,
+        conn: &Connection,
+        msg: &Message,
+        option82: &Option82Data,
+    ) -> Result<Option<DhcpNetwork>> {
+        let relay_agent = msg.giaddr();
+        let network = match option82.link_selection {
+            Some(link) => {
+                let networks = store::list_networks(conn).await?;
+                let network = interface::find_network_containing(link, &networks)?.cloned();
+                if network.is_none() {
+                    log::warn!(
+                        "No network contains link selection {} from relay {}",
+                        link,
+                        relay_agent
+                    );
+                    self.note_ignored(Some(msg), format!("no network for link selection {}", link));
+                }
+                network
+            }
+            None => {
+                let network = store::get_network_by_relay(conn, Some(relay_agent)).await?;
+                if network.is_none() {
+                    log::warn!("No network found for relay agent {}", relay_agent);
+                    self.note_ignored(
+                        Some(msg),
+                        format!("no network for relay agent {}", relay_agent),
+                    );
+                }
+                network
+            }
+        };
+
+        if let Some(network) = &network {
+            debug!(
+                "Using network '{}' (id={}) for relay {}{}",
+                network.name,
+                network.id,
+                relay_agent,
+                option82
+                    .link_selection
+                    .map(|link| format!(" (link selection {})", link))
+                    .unwrap_or_default()
+            );
+        }
+        Ok(network)
+    }
+
     /// Handle a DHCP packet received on a per-network socket (unicast renewals).
     ///
     /// The `local_ip` is the address the per-network socket is bound to, which identifies
@@ -168,74 +578,218 @@ impl DhcpHandler {
         peer_addr: SocketAddr,
         local_ip: Ipv4Addr,
     ) -> Result<Option<DhcpReply>> {
-        let msg = match Message::decode(&mut Decoder::new(data)) {
-            Ok(msg) => msg,
-            Err(e) => {
-                log::warn!("Failed to decode DHCP message: {}", e);
-                return Ok(None);
-            }
+        if let Some(request) = self.legacy_bootp_request(data) {
+            let conn = self.db.open().await?;
+            let l2_networks = store::get_l2_networks(&conn).await?;
+            let l2 = interface::find_l2_network_for_ip(local_ip, &l2_networks)?
+                .map(|network| (network, local_ip));
+            return self.handle_bootp(&conn, &request, l2).await;
+        }
+        let Some(msg) = self.decode_request(data) else {
+            return Ok(None);
         };
 
-        trace!("DHCP unicast: Received packet {:?}", msg);
-        let conn = self.db.open().await?;
+        trace!("DHCP unicast: Received packet {}", PacketDisplay(&msg));
+        let mut conn = self.db.open().await?;
 
         let l2_networks = store::get_l2_networks(&conn).await?;
         let Some(network) = interface::find_l2_network_for_ip(local_ip, &l2_networks)? else {
             debug!("No L2 network matches local IP {}, dropping", local_ip);
+            self.note_ignored(Some(&msg), format!("no network for local IP {}", local_ip));
             return Ok(None);
         };
         debug!(
             "Unicast: Using network '{}' (id={}) for local_ip={}",
             network.name, network.id, local_ip
         );
-        self.process_and_reply(&conn, &msg, network, local_ip, move |data| DhcpReply::L2 {
-            data,
-            local_ip,
-            peer_addr,
-        })
+        let request = &msg;
+        let server_identifier = network.server_identifier.unwrap_or(local_ip);
+        self.process_and_reply(
+            &mut conn,
+            &msg,
+            network,
+            server_identifier,
+            move |data, reply_type| DhcpReply::L2 {
+                data,
+                local_ip,
+                dest: l2_reply_destination(request, reply_type, peer_addr),
+            },
+        )
         .await
     }
 
+    /// `data` as a legacy BOOTREQUEST, if legacy BOOTP is enabled and it is one.
+    fn legacy_bootp_request(&self, data: &[u8]) -> Option<BootpRequest> {
+        if !self.legacy_bootp {
+            return None;
+        }
+        BootpRequest::parse(data)
+    }
+
+    /// Add a legacy BOOTP request and the decision made about it to the recent-packet log.
+    fn note_bootp(&self, request: &BootpRequest, decision: Decision) {
+        let addr = |ip: Ipv4Addr| (!ip.is_unspecified()).then_some(ip);
+        self.recent.record(PacketEvent {
+            at: chrono::Utc::now(),
+            client: addr(request.ciaddr),
+            relay: addr(request.giaddr),
+            mac: Some(format_mac(&request.chaddr)),
+            message_type: Some("Bootp".to_string()),
+            decision,
+        });
+    }
+
+    /// The network and local address to answer a legacy BOOTREQUEST from.
+    ///
+    /// Relayed requests pick their network by giaddr and answer from its server
+    /// identifier; others use `l2`, the network and local address the packet
+    /// arrived on. Returns `None` (after noting why) when neither matches.
+    async fn bootp_network(
+        &self,
+        conn: &Connection,
+        request: &BootpRequest,
+        l2: Option<(&DhcpNetwork, Ipv4Addr)>,
+    ) -> Result<Option<(DhcpNetwork, Ipv4Addr)>> {
+        let selected = if request.giaddr != Ipv4Addr::UNSPECIFIED {
+            store::get_network_by_relay(conn, Some(request.giaddr))
+                .await?
+                .map(|network| {
+                    let server_identifier =
+                        network.server_identifier.unwrap_or(self.server_identifier);
+                    (network, server_identifier)
+                })
+        } else {
+            l2.map(|(network, local_ip)| (network.clone(), local_ip))
+        };
+        if selected.is_none() {
+            let reason = match request.giaddr {
+                Ipv4Addr::UNSPECIFIED => "no network for legacy BOOTP request".to_string(),
+                relay => format!("no network for relay agent {}", relay),
+            };
+            self.note_bootp(request, Decision::Ignored { reason });
+        }
+        Ok(selected)
+    }
+
+    /// Answer a legacy BOOTREQUEST from the client's static reservation.
+    ///
+    /// Clients without a reservation get no reply, since BOOTP has no lease to
+    /// expire a pool address with.
+    async fn handle_bootp(
+        &self,
+        conn: &Connection,
+        request: &BootpRequest,
+        l2: Option<(&DhcpNetwork, Ipv4Addr)>,
+    ) -> Result<Option<DhcpReply>> {
+        if let Some(reason) = self.oui_rejection(&request.chaddr, "BOOTP") {
+            self.note_bootp(request, Decision::Ignored { reason });
+            return Ok(None);
+        }
+        let Some((network, local_ip)) = self.bootp_network(conn, request, l2).await? else {
+            return Ok(None);
+        };
+        let mac = format_mac(&request.chaddr);
+        let Some(reservation) = store::get_static_reservation(conn, network.id, &mac).await? else {
+            info!(
+                "Ignoring legacy BOOTP request from {}: no static reservation in network '{}'",
+                mac, network.name
+            );
+            let reason = "legacy BOOTP client has no static reservation".to_string();
+            self.note_bootp(request, Decision::Ignored { reason });
+            return Ok(None);
+        };
+        let yiaddr: Ipv4Addr = reservation.ip_address.parse()?;
+        let (next_server, file) = self.boot_config.bootp_boot_target();
+        let server_identifier = network.server_identifier.unwrap_or(local_ip);
+        let siaddr = next_server.unwrap_or(server_identifier);
+
+        let data = bootp::build_reply(request, yiaddr, siaddr, file)?;
+        info!(
+            "BOOTP: Assigned {} to {} from its reservation in network '{}'",
+            yiaddr, mac, network.name
+        );
+        self.note_bootp(request, Decision::Acked { ip: yiaddr });
+        let dest = request.reply_destination();
+        Ok(Some(if request.giaddr != Ipv4Addr::UNSPECIFIED {
+            DhcpReply::Relay { data, dest }
+        } else {
+            DhcpReply::L2 {
+                data,
+                local_ip,
+                dest,
+            }
+        }))
+    }
+
     /// Shared logic for processing a DHCP message against a selected network and producing a reply.
+    ///
+    /// Replies, releases and declines are noted in the recent-packet log here; the
+    /// handlers note their own reasons when they ignore a packet. `make_reply` gets the
+    /// encoded reply and its message type.
     async fn process_and_reply<F>(
         &self,
-        conn: &Connection,
+        conn: &mut Connection,
         msg: &Message,
         network: &DhcpNetwork,
         server_identifier: Ipv4Addr,
         make_reply: F,
     ) -> Result<Option<DhcpReply>>
     where
-        F: FnOnce(Vec<u8>) -> DhcpReply,
+        F: FnOnce(Vec<u8>, Option<MessageType>) -> DhcpReply,
     {
-        let response = match msg.opts().msg_type() {
+        let result = match msg.opts().msg_type() {
             Some(MessageType::Discover) => {
                 self.handle_discover(conn, msg, network, server_identifier)
-                    .await?
+                    .await
             }
             Some(MessageType::Request) => {
                 self.handle_request(conn, msg, network, server_identifier)
-                    .await?
+                    .await
             }
-            Some(MessageType::Release) => {
-                self.handle_release(conn, msg).await?;
+            Some(MessageType::Release) => self.handle_release(conn, msg, network).await.map(|()| {
+                self.note(Some(msg), Decision::Released);
                 None
-            }
-            Some(MessageType::Decline) => {
-                self.handle_decline(conn, msg).await?;
+            }),
+            Some(MessageType::Decline) => self.handle_decline(conn, msg, network).await.map(|()| {
+                self.note(Some(msg), Decision::Declined);
                 None
-            }
+            }),
             _ => {
                 log::debug!("Ignoring unsupported DHCP message type");
+                self.note_ignored(Some(msg), "unsupported message type");
                 return Ok(None);
             }
         };
+        let response = match result {
+            Ok(response) => response,
+            Err(e) => {
+                self.note(
+                    Some(msg),
+                    Decision::Error {
+                        error: format!("{:#}", e),
+                    },
+                );
+                return Err(e);
+            }
+        };
 
         if let Some(resp) = response {
-            trace!("DHCP: Sending response {:?}", resp);
-            let mut buf = Vec::new();
-            resp.encode(&mut Encoder::new(&mut buf))?;
-            Ok(Some(make_reply(buf)))
+            let decision = match resp.opts().msg_type() {
+                Some(MessageType::Offer) => Decision::Offered { ip: resp.yiaddr() },
+                Some(MessageType::Ack) => Decision::Acked { ip: resp.yiaddr() },
+                _ => Decision::Nak,
+            };
+            let is_offer = matches!(decision, Decision::Offered { .. });
+            self.note(Some(msg), decision);
+            // Each packet is handled on its own task, so this holds back only this OFFER.
+            if is_offer && !self.offer_delay.is_zero() {
+                debug!("Delaying OFFER by {:?}", self.offer_delay);
+                tokio::time::sleep(self.offer_delay).await;
+            }
+            trace!("DHCP: Sending response {}", PacketDisplay(&resp));
+            let limit = message_builder::reply_size_limit(msg, self.max_message_size);
+            let buf = message_builder::encode_reply(&resp, limit)?;
+            Ok(Some(make_reply(buf, resp.opts().msg_type())))
         } else {
             Ok(None)
         }
@@ -243,11 +797,14 @@ impl DhcpHandler {
 
     async fn handle_discover(
         &self,
-        conn: &Connection,
+        conn: &mut Connection,
         msg: &Message,
         network: &DhcpNetwork,
         server_identifier: Ipv4Addr,
     ) -> Result<Option<Message>> {
+        if !self.oui_permitted(msg, "DISCOVER") {
+            return Ok(None);
+        }
         let req_ctx = RequestContext::from_message(msg);
 
         info!(
@@ -276,32 +833,44 @@ impl DhcpHandler {
                     .unwrap_or_default(),
                 dev_ctx.disable_reason.as_deref().unwrap_or("unknown")
             );
+            self.note_ignored(Some(msg), "interface is disabled");
             return Ok(None);
         }
 
-        // Allocate or retrieve existing IP in this network
-        let ip = if let Some(uuid) = &dev_ctx.device_uuid {
-            debug!("Device UUID {} found for MAC {}", uuid, req_ctx.mac);
-            allocator::allocate_for_device_in_network(conn, &req_ctx.mac, uuid, network.id).await?
-        } else {
-            debug!(
-                "No device UUID found for MAC {}, allocating from pool",
-                req_ctx.mac
-            );
-            allocator::allocate_for_mac_in_network(conn, &req_ctx.mac, network.id).await?
-        };
+        let quarantine = self.quarantine_network(conn, network, &dev_ctx).await?;
+        let network = quarantine.as_ref().unwrap_or(network);
 
-        // Create lease in 'offered' state
-        store::create_or_update_lease_with_network(
-            conn,
-            &req_ctx.mac,
-            &ip,
-            dev_ctx.device_uuid.as_ref(),
-            LeaseState::Offered,
-            network.lease_duration,
-            network.id,
-        )
-        .await?;
+        if !self
+            .allocation_permitted(conn, &req_ctx, &dev_ctx, network)
+            .await?
+        {
+            self.note_ignored(Some(msg), "new-client allocation rate limit reached");
+            return Ok(None);
+        }
+
+        // Allocation runs under `allocation_lock` (see `reserve_offer`). A rapid commit
+        // keeps it through activation, as `handle_request` does, so nothing can touch
+        // the reserved address before its lease is active.
+        let guard = self.allocation_lock.lock().await;
+        let ip = self
+            .reserve_offer(conn, &req_ctx, &dev_ctx, network)
+            .await?;
+
+        if self.rapid_commit && req_ctx.rapid_commit {
+            self.commit_lease(conn, &req_ctx, &dev_ctx, network, ip)
+                .await?;
+            drop(guard);
+            let mut ack = self
+                .build_ack(msg, ip, network, &req_ctx, &dev_ctx, server_identifier)
+                .await?;
+            message_builder::add_rapid_commit(&mut ack);
+            info!(
+                "DHCP ACK {} (rapid commit) to MAC {} on network '{}'",
+                ip, req_ctx.mac, network.name
+            );
+            return Ok(Some(ack));
+        }
+        drop(guard);
 
         let offer = self
             .build_offer(msg, ip, network, &req_ctx, &dev_ctx, server_identifier)
@@ -314,44 +883,236 @@ impl DhcpHandler {
         Ok(Some(offer))
     }
 
-    async fn handle_request(
+    /// Whether a DISCOVER may be offered an address under the allocation rate limit.
+    ///
+    /// Only clients new to us take a slot: ones with no device, no lease by MAC or
+    /// client identifier, and no static reservation on `network`.
+    async fn allocation_permitted(
         &self,
         conn: &Connection,
-        msg: &Message,
+        req_ctx: &RequestContext,
+        dev_ctx: &DeviceContext,
         network: &DhcpNetwork,
-        server_identifier: Ipv4Addr,
-    ) -> Result<Option<Message>> {
-        let req_ctx = RequestContext::from_message(msg);
-
-        // Check Server Identifier option (Option 54) per RFC 2131 Section 4.3.2
-        // If present, only respond if it matches our server identifier
-        if let Some(server_id) = extract_server_identifier(msg)
-            && server_id != server_identifier
+    ) -> Result<bool> {
+        if !self.allocation_rate.is_enabled() || dev_ctx.device_uuid.is_some() {
+            return Ok(true);
+        }
+        if store::get_lease_by_mac(conn, &req_ctx.mac).await?.is_some() {
+            return Ok(true);
+        }
+        if let Some(client_id) = &req_ctx.client_id
+            && store::get_lease_by_client_id(conn, client_id)
+                .await?
+                .is_some()
         {
-            // This request is for a different DHCP server - ignore it
-            debug!(
-                "Ignoring DHCPREQUEST from {} - server identifier {} doesn't match ours {}",
-                req_ctx.mac, server_id, server_identifier
-            );
+            return Ok(true);
+        }
+        if store::get_static_reservation(conn, network.id, &req_ctx.mac)
+            .await?
+            .is_some()
+        {
+            return Ok(true);
+        }
+
+        match self.allocation_rate.try_acquire(network.id) {
+            Ok(()) => Ok(true),
+            Err(scope) => {
+                warn!(
+                    "Dropping DHCP DISCOVER from new MAC {} on network '{}': {} allocation rate limit reached",
+                    req_ctx.mac, network.name, scope
+                );
+                Ok(false)
+            }
+        }
+    }
+
+    /// The quarantine network to serve the client from instead of `network`, if any.
+    ///
+    /// Only devices that have been provisioned get addresses on a network with a
+    /// quarantine network configured; unknown devices and those still being discovered
+    /// or provisioned are kept on the quarantine network.
+    async fn quarantine_network(
+        &self,
+        conn: &Connection,
+        network: &DhcpNetwork,
+        dev_ctx: &DeviceContext,
+    ) -> Result<Option<DhcpNetwork>> {
+        let Some(quarantine_id) = network.quarantine_network_id else {
+            return Ok(None);
+        };
+        if dev_ctx.lifecycle == Some(DeviceLifecycle::Provisioned) {
             return Ok(None);
         }
-        // Note: If no Server Identifier is present, this is an INIT-REBOOT, RENEWING,
-        // or REBINDING request and should be processed normally per RFC 2131
 
-        info!(
-            "DHCP REQUEST from MAC {} on network '{}'{}",
-            req_ctx.mac,
-            network.name,
-            req_ctx
-                .guid
-                .map(|g| format!(" (GUID: {})", g))
-                .unwrap_or_default()
+        let quarantine = store::get_network(conn, quarantine_id).await?;
+        debug!(
+            "Serving device in state {:?} from quarantine network '{}' instead of '{}'",
+            dev_ctx.lifecycle, quarantine.name, network.name
         );
+        Ok(Some(quarantine))
+    }
 
-        let dev_ctx = self
-            .device_resolver
-            .resolve(conn, &req_ctx.mac, req_ctx.guid.as_ref())
-            .await?;
+    /// Pick an address for a DISCOVER and record it as an `offered` lease.
+    ///
+    /// Packets are handled on concurrent tasks, so the caller must hold
+    /// `allocation_lock` across allocation and the lease write; otherwise two clients
+    /// could both be offered the same free address before either lease is recorded.
+    async fn reserve_offer(
+        &self,
+        conn: &mut Connection,
+        req_ctx: &RequestContext,
+        dev_ctx: &DeviceContext,
+        network: &DhcpNetwork,
+    ) -> Result<Ipv4Addr> {
+        self.adopt_client_id_lease(conn, req_ctx, network).await?;
+
+        // Allocate or retrieve existing IP in this network
+        let ip = if let Some(uuid) = &dev_ctx.device_uuid {
+            debug!("Device UUID {} found for MAC {}", uuid, req_ctx.mac);
+            allocator::allocate_for_device_in_network(
+                conn,
+                &req_ctx.mac,
+                uuid,
+                network.id,
+                self.allocation_strategy,
+            )
+            .await?
+        } else {
+            debug!(
+                "No device UUID found for MAC {}, allocating from pool",
+                req_ctx.mac
+            );
+            allocator::allocate_for_mac_in_network(
+                conn,
+                &req_ctx.mac,
+                network.id,
+                self.allocation_strategy,
+            )
+            .await?
+        };
+
+        let ip = self
+            .record_offer(conn, req_ctx, dev_ctx, network, ip)
+            .await?;
+        self.record_client_id(conn, req_ctx).await?;
+
+        Ok(ip)
+    }
+
+    /// Record an `offered` lease on `ip`, returning the address actually offered.
+    ///
+    /// An address another MAC turns out to hold (a [`store::LeaseConflict`], e.g. a
+    /// reservation over a live lease) is skipped and allocation retried from the
+    /// pools, up to [`MAX_OFFER_ATTEMPTS`] addresses in all.
+    async fn record_offer(
+        &self,
+        conn: &Connection,
+        req_ctx: &RequestContext,
+        dev_ctx: &DeviceContext,
+        network: &DhcpNetwork,
+        mut ip: Ipv4Addr,
+    ) -> Result<Ipv4Addr> {
+        // Create lease in 'offered' state, held only until the REQUEST is due. The row
+        // outlives a restart, so the address stays reserved across one.
+        let mut conflicted = HashSet::new();
+        loop {
+            let offered = store::create_or_update_lease_with_network(
+                conn,
+                &req_ctx.mac,
+                &ip,
+                dev_ctx.device_uuid.as_ref(),
+                LeaseState::Offered,
+                self.lease_time(req_ctx, network).min(self.offer_ttl),
+                network.id,
+            )
+            .await;
+            let Err(e) = offered else {
+                return Ok(ip);
+            };
+            let Some(conflict) = e.downcast_ref::<store::LeaseConflict>() else {
+                return Err(e);
+            };
+            warn!("Not offering {} to MAC {}: {}", ip, req_ctx.mac, conflict);
+            conflicted.insert(ip);
+            if conflicted.len() >= MAX_OFFER_ATTEMPTS {
+                return Err(e);
+            }
+            ip = allocator::allocate_from_pools_excluding(
+                conn,
+                network.id,
+                &req_ctx.mac,
+                self.allocation_strategy,
+                &conflicted,
+            )
+            .await?;
+        }
+    }
+
+    /// Mark the client's lease on `ip` active for a full lease time and tell the
+    /// device resolver, as an ACK is about to be sent.
+    async fn commit_lease(
+        &self,
+        conn: &Connection,
+        req_ctx: &RequestContext,
+        dev_ctx: &DeviceContext,
+        network: &DhcpNetwork,
+        ip: Ipv4Addr,
+    ) -> Result<()> {
+        store::activate_lease(conn, &req_ctx.mac, self.lease_time(req_ctx, network)).await?;
+        self.record_client_id(conn, req_ctx).await?;
+        if let Some(uuid) = &dev_ctx.device_uuid {
+            self.device_resolver
+                .on_lease_activated(conn, uuid, &ip.to_string(), &req_ctx.mac)
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn handle_request(
+        &self,
+        conn: &mut Connection,
+        msg: &Message,
+        network: &DhcpNetwork,
+        server_identifier: Ipv4Addr,
+    ) -> Result<Option<Message>> {
+        if !self.oui_permitted(msg, "REQUEST") {
+            return Ok(None);
+        }
+        let req_ctx = RequestContext::from_message(msg);
+
+        // Check Server Identifier option (Option 54) per RFC 2131 Section 4.3.2
+        // If present, only respond if it matches our server identifier
+        if let Some(server_id) = extract_server_identifier(msg)
+            && server_id != server_identifier
+        {
+            // This request is for a different DHCP server - ignore it
+            debug!(
+                "Ignoring DHCPREQUEST from {} - server identifier {} doesn't match ours {}",
+                req_ctx.mac, server_id, server_identifier
+            );
+            self.note_ignored(
+                Some(msg),
+                format!("addressed to another server ({})", server_id),
+            );
+            return Ok(None);
+        }
+        // Note: If no Server Identifier is present, this is an INIT-REBOOT, RENEWING,
+        // or REBINDING request and should be processed normally per RFC 2131
+
+        info!(
+            "DHCP REQUEST from MAC {} on network '{}'{}",
+            req_ctx.mac,
+            network.name,
+            req_ctx
+                .guid
+                .map(|g| format!(" (GUID: {})", g))
+                .unwrap_or_default()
+        );
+
+        let dev_ctx = self
+            .device_resolver
+            .resolve(conn, &req_ctx.mac, req_ctx.guid.as_ref())
+            .await?;
 
         if dev_ctx.is_disabled {
             warn!(
@@ -364,9 +1125,13 @@ impl DhcpHandler {
                     .unwrap_or_default(),
                 dev_ctx.disable_reason.as_deref().unwrap_or("unknown")
             );
+            self.note_ignored(Some(msg), "interface is disabled");
             return Ok(None);
         }
 
+        let quarantine = self.quarantine_network(conn, network, &dev_ctx).await?;
+        let network = quarantine.as_ref().unwrap_or(network);
+
         // Extract requested IP address
         let requested_ip = if let Some(ip) = req_ctx.requested_ip {
             ip
@@ -379,6 +1144,30 @@ impl DhcpHandler {
 
         debug!("Requested IP: {}", requested_ip);
 
+        match requested_address_scope(conn, network, requested_ip).await? {
+            AddressScope::OnNetwork => {}
+            AddressScope::WrongSubnet => {
+                // We serve that subnet, so we know the client has moved off it
+                info!(
+                    "NAKing DHCPREQUEST from {} - requested {} belongs to another of our subnets, not '{}'",
+                    req_ctx.mac, requested_ip, network.name
+                );
+                return Ok(Some(self.build_nak(msg, server_identifier)?));
+            }
+            AddressScope::Unserved => {
+                return self.reject_out_of_scope(
+                    msg,
+                    server_identifier,
+                    format!("requested {} is in none of our subnets", requested_ip),
+                );
+            }
+        }
+
+        // Hold the allocation lock while validating and activating so a
+        // concurrent DISCOVER cannot hand out this address mid-request.
+        let _guard = self.allocation_lock.lock().await;
+        self.adopt_client_id_lease(conn, &req_ctx, network).await?;
+
         // Check for static reservation - takes priority over everything
         let static_reservation =
             store::get_static_reservation(conn, network.id, &req_ctx.mac).await?;
@@ -396,16 +1185,27 @@ impl DhcpHandler {
             }
 
             // Static reservation matches requested IP - update or create lease
-            store::create_or_update_lease_with_network(
+            let created = store::create_or_update_lease_with_network(
                 conn,
                 &req_ctx.mac,
                 &reserved_ip,
                 dev_ctx.device_uuid.as_ref(),
                 LeaseState::Active,
-                network.lease_duration,
+                self.lease_time(&req_ctx, network),
                 network.id,
             )
-            .await?;
+            .await;
+            if let Err(e) = created {
+                let Some(conflict) = e.downcast_ref::<store::LeaseConflict>() else {
+                    return Err(e);
+                };
+                warn!(
+                    "NAKing DHCPREQUEST from {} - reserved {}",
+                    req_ctx.mac, conflict
+                );
+                return Ok(Some(self.build_nak(msg, server_identifier)?));
+            }
+            self.record_client_id(conn, &req_ctx).await?;
 
             if let Some(uuid) = &dev_ctx.device_uuid {
                 self.device_resolver
@@ -443,14 +1243,33 @@ impl DhcpHandler {
                 return Ok(Some(self.build_nak(msg, server_identifier)?));
             }
 
-            // Update lease to 'active'
-            store::activate_lease(conn, &req_ctx.mac).await?;
-            if let Some(uuid) = &dev_ctx.device_uuid {
-                self.device_resolver
-                    .on_lease_activated(conn, uuid, &lease_ip.to_string(), &req_ctx.mac)
-                    .await?;
+            // The device moved in or out of quarantine since this lease was granted;
+            // NAK so it rediscovers on the network it now belongs to.
+            if lease.network_id.is_some_and(|id| id != network.id) {
+                info!(
+                    "NAKing DHCPREQUEST from {} - lease {} is not on network '{}'",
+                    req_ctx.mac, lease_ip, network.name
+                );
+                return Ok(Some(self.build_nak(msg, server_identifier)?));
+            }
+
+            // An offer that lapsed before this REQUEST may have gone to someone else
+            if lease.state == LeaseState::Offered
+                && lease.is_expired()
+                && let Some(holder) = address_holder(conn, network, lease_ip, &req_ctx.mac)
+                    .await?
+                    .or(store::find_lease_holder(conn, &lease_ip, &req_ctx.mac).await?)
+            {
+                warn!(
+                    "NAKing DHCPREQUEST from {} - offer of {} lapsed and {} now holds it",
+                    req_ctx.mac, lease_ip, holder
+                );
+                return Ok(Some(self.build_nak(msg, server_identifier)?));
             }
 
+            self.commit_lease(conn, &req_ctx, &dev_ctx, network, lease_ip)
+                .await?;
+
             let ack = self
                 .build_ack(
                     msg,
@@ -467,35 +1286,110 @@ impl DhcpHandler {
             );
 
             Ok(Some(ack))
+        } else if let Some(holder) =
+            address_holder(conn, network, requested_ip, &req_ctx.mac).await?
+        {
+            // Ours to give, and someone else has it: the client must not use it
+            warn!(
+                "NAKing DHCPREQUEST from {} - {} is held by {}",
+                req_ctx.mac, requested_ip, holder
+            );
+            Ok(Some(self.build_nak(msg, server_identifier)?))
         } else {
-            warn!("No lease found for MAC {}", req_ctx.mac);
+            self.reject_out_of_scope(
+                msg,
+                server_identifier,
+                format!("no lease for {}", req_ctx.mac),
+            )
+        }
+    }
+
+    /// Answer a REQUEST for an address we have no record of handing out.
+    ///
+    /// As the authoritative server we NAK so the client restarts discovery at once;
+    /// otherwise we stay silent, since another server on the segment may own it.
+    fn reject_out_of_scope(
+        &self,
+        msg: &Message,
+        server_identifier: Ipv4Addr,
+        reason: String,
+    ) -> Result<Option<Message>> {
+        if self.authoritative {
+            warn!("NAKing DHCPREQUEST: {}", reason);
             Ok(Some(self.build_nak(msg, server_identifier)?))
+        } else {
+            debug!("Ignoring DHCPREQUEST (not authoritative): {}", reason);
+            self.note_ignored(Some(msg), reason);
+            Ok(None)
         }
     }
 
-    async fn handle_release(&self, conn: &Connection, msg: &Message) -> Result<()> {
+    async fn handle_release(
+        &self,
+        conn: &mut Connection,
+        msg: &Message,
+        network: &DhcpNetwork,
+    ) -> Result<()> {
         let mac = msg.chaddr();
         let mac_str = format_mac(mac);
 
         info!("DHCP RELEASE from MAC {}", mac_str);
 
+        self.adopt_client_id_lease(conn, &RequestContext::from_message(msg), network)
+            .await?;
+
         store::release_lease(conn, &mac_str).await?;
 
         Ok(())
     }
 
-    async fn handle_decline(&self, conn: &Connection, msg: &Message) -> Result<()> {
+    async fn handle_decline(
+        &self,
+        conn: &mut Connection,
+        msg: &Message,
+        network: &DhcpNetwork,
+    ) -> Result<()> {
         let mac = msg.chaddr();
         let mac_str = format_mac(mac);
 
         warn!("DHCP DECLINE from MAC {}", mac_str);
 
+        self.adopt_client_id_lease(conn, &RequestContext::from_message(msg), network)
+            .await?;
+
         // Mark lease as released to prevent reuse
         store::release_lease(conn, &mac_str).await?;
 
         Ok(())
     }
 
+    /// Re-key a lease held on `network` under this client's identifier (Option 61) to
+    /// its current MAC, so the MAC-keyed lease logic below treats it as the same client.
+    async fn adopt_client_id_lease(
+        &self,
+        conn: &mut Connection,
+        req_ctx: &RequestContext,
+        network: &DhcpNetwork,
+    ) -> Result<()> {
+        if let Some(client_id) = &req_ctx.client_id
+            && store::adopt_lease_by_client_id(conn, client_id, &req_ctx.mac, network.id).await?
+        {
+            info!(
+                "Client identifier {} moved its lease to MAC {}",
+                client_id, req_ctx.mac
+            );
+        }
+        Ok(())
+    }
+
+    /// Store the client's identifier (Option 61), if any, on its lease.
+    async fn record_client_id(&self, conn: &Connection, req_ctx: &RequestContext) -> Result<()> {
+        if let Some(client_id) = &req_ctx.client_id {
+            store::set_lease_client_id(conn, &req_ctx.mac, client_id).await?;
+        }
+        Ok(())
+    }
+
     async fn build_offer(
         &self,
         req: &Message,
@@ -512,8 +1406,11 @@ impl DhcpHandler {
             .insert(v4::DhcpOption::MessageType(MessageType::Offer));
         msg.opts_mut()
             .insert(v4::DhcpOption::ServerIdentifier(server_identifier));
-        msg.opts_mut()
-            .insert(v4::DhcpOption::AddressLeaseTime(network.lease_duration));
+        message_builder::add_lease_time_options(
+            &mut msg,
+            self.lease_time(req_ctx, network),
+            &self.lease_timers,
+        );
 
         // Set vendor class identifier based on client architecture
         let vendor_class = determine_vendor_class_identifier(req_ctx.client_arch);
@@ -521,6 +1418,17 @@ impl DhcpHandler {
             .insert(v4::DhcpOption::ClassIdentifier(vendor_class.to_vec()));
 
         message_builder::add_network_options(&mut msg, network)?;
+        if let Some(search) = &self.domain_search
+            && req_ctx.requested_domain_search
+        {
+            msg.opts_mut().insert(search.to_option());
+        }
+        if let Some(pxe) = &self.pxe_vendor
+            && req_ctx.is_pxe_client
+            && !req_ctx.is_ipxe
+        {
+            msg.opts_mut().insert(pxe.to_option());
+        }
 
         self.boot_config
             .populate_boot_options(&mut msg, req_ctx)
@@ -555,24 +1463,21 @@ impl DhcpHandler {
 #[cfg(test)]
 mod tests {
     use chrono::DateTime;
-    use dhcproto::v4::Opcode;
+    use dhcproto::{Encodable, encoder::Encoder};
 
     use super::*;
+    use crate::dhcp::client::Probe;
     use crate::test_connection_factory;
 
+    const MAC: [u8; 6] = [0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff];
+
     #[tokio::test]
     async fn test_server_identifier_in_offer() {
         let (handler, conn, network_id, _temp_dir) =
             create_test_handler_with_store(test_connection_factory!()).await;
 
         // Create a minimal DISCOVER message
-        let mut discover = Message::default();
-        discover.set_opcode(Opcode::BootRequest);
-        discover.set_xid(0x12345678);
-        discover.set_chaddr(&[0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff]);
-        discover
-            .opts_mut()
-            .insert(v4::DhcpOption::MessageType(MessageType::Discover));
+        let discover = Probe::discover(MAC).build();
 
         // Get test network
         let network = store::get_network(&conn, network_id).await.unwrap();
@@ -583,6 +1488,7 @@ mod tests {
             device_uuid: None,
             is_disabled: false,
             disable_reason: None,
+            lifecycle: None,
         };
 
         // Build an OFFER response
@@ -622,13 +1528,7 @@ mod tests {
         let handler = create_test_handler(test_connection_factory!()).await;
 
         // Create a minimal REQUEST message
-        let mut request = Message::default();
-        request.set_opcode(Opcode::BootRequest);
-        request.set_xid(0x12345678);
-        request.set_chaddr(&[0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff]);
-        request
-            .opts_mut()
-            .insert(v4::DhcpOption::MessageType(MessageType::Request));
+        let request = Probe::new(MAC, MessageType::Request).build();
 
         // Build a NAK response
         let nak = handler
@@ -717,13 +1617,7 @@ mod tests {
         let network = store::get_network(&conn, network.id).await.unwrap();
 
         // Build an OFFER and verify it uses the custom identifier
-        let mut discover = Message::default();
-        discover.set_opcode(Opcode::BootRequest);
-        discover.set_xid(0x12345678);
-        discover.set_chaddr(&[0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff]);
-        discover
-            .opts_mut()
-            .insert(v4::DhcpOption::MessageType(MessageType::Discover));
+        let discover = Probe::discover(MAC).build();
 
         // Create contexts
         let req_ctx = RequestContext::from_message(&discover);
@@ -731,6 +1625,7 @@ mod tests {
             device_uuid: None,
             is_disabled: false,
             disable_reason: None,
+            lifecycle: None,
         };
 
         let offer = handler
@@ -832,13 +1727,7 @@ mod tests {
         let handler = create_test_handler(test_connection_factory!()).await;
 
         // Create a minimal REQUEST message without architecture
-        let mut request = Message::default();
-        request.set_opcode(Opcode::BootRequest);
-        request.set_xid(0x12345678);
-        request.set_chaddr(&[0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff]);
-        request
-            .opts_mut()
-            .insert(v4::DhcpOption::MessageType(MessageType::Request));
+        let request = Probe::new(MAC, MessageType::Request).build();
 
         // Build an Offer response
         let network = DhcpNetwork {
@@ -850,6 +1739,9 @@ mod tests {
             lease_duration: 1,
             relay_agent_address: None,
             enable_autodiscovery: true,
+            enabled: true,
+            quarantine_network_id: None,
+            server_identifier: None,
             created_at: DateTime::default(),
             updated_at: DateTime::default(),
         };
@@ -858,6 +1750,7 @@ mod tests {
             device_uuid: None,
             is_disabled: false,
             disable_reason: None,
+            lifecycle: None,
         };
         let offer = handler
             .build_offer(
@@ -967,18 +1860,11 @@ mod tests {
             create_test_handler_with_store(test_connection_factory!()).await;
 
         // Create a DISCOVER message with HTTP boot architecture (14)
-        let mut discover = Message::default();
-        discover.set_opcode(Opcode::BootRequest);
-        discover.set_xid(0x12345678);
-        discover.set_chaddr(&[0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff]);
-        discover
-            .opts_mut()
-            .insert(v4::DhcpOption::MessageType(MessageType::Discover));
-        discover
-            .opts_mut()
-            .insert(v4::DhcpOption::ClientSystemArchitecture(
-                dhcproto::v4::Architecture::Unknown(14),
-            ));
+        let discover = Probe::discover(MAC)
+            .option(v4::DhcpOption::ClientSystemArchitecture(
+                Architecture::Unknown(14),
+            ))
+            .build();
 
         // Get test network
         let network = store::get_network(&conn, network_id).await.unwrap();
@@ -989,6 +1875,7 @@ mod tests {
             device_uuid: None,
             is_disabled: false,
             disable_reason: None,
+            lifecycle: None,
         };
 
         // Build an OFFER response
@@ -1029,18 +1916,9 @@ mod tests {
             create_test_handler_with_store(test_connection_factory!()).await;
 
         // Create a DISCOVER message with traditional UEFI architecture (7)
-        let mut discover = Message::default();
-        discover.set_opcode(Opcode::BootRequest);
-        discover.set_xid(0x12345678);
-        discover.set_chaddr(&[0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff]);
-        discover
-            .opts_mut()
-            .insert(v4::DhcpOption::MessageType(MessageType::Discover));
-        discover
-            .opts_mut()
-            .insert(v4::DhcpOption::ClientSystemArchitecture(
-                dhcproto::v4::Architecture::BC,
-            ));
+        let discover = Probe::discover(MAC)
+            .option(v4::DhcpOption::ClientSystemArchitecture(Architecture::BC))
+            .build();
 
         // Get test network
         let network = store::get_network(&conn, network_id).await.unwrap();
@@ -1051,6 +1929,7 @@ mod tests {
             device_uuid: None,
             is_disabled: false,
             disable_reason: None,
+            lifecycle: None,
         };
 
         // Build an OFFER response
@@ -1091,18 +1970,11 @@ mod tests {
             create_test_handler_with_store(test_connection_factory!()).await;
 
         // Create a REQUEST message with HTTP boot architecture (15)
-        let mut request = Message::default();
-        request.set_opcode(Opcode::BootRequest);
-        request.set_xid(0x12345678);
-        request.set_chaddr(&[0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff]);
-        request
-            .opts_mut()
-            .insert(v4::DhcpOption::MessageType(MessageType::Request));
-        request
-            .opts_mut()
-            .insert(v4::DhcpOption::ClientSystemArchitecture(
-                dhcproto::v4::Architecture::Unknown(15),
-            ));
+        let request = Probe::new(MAC, MessageType::Request)
+            .option(v4::DhcpOption::ClientSystemArchitecture(
+                Architecture::Unknown(15),
+            ))
+            .build();
 
         // Get test network
         let network = store::get_network(&conn, network_id).await.unwrap();
@@ -1113,6 +1985,7 @@ mod tests {
             device_uuid: None,
             is_disabled: false,
             disable_reason: None,
+            lifecycle: None,
         };
 
         // Build an ACK response
@@ -1151,7 +2024,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_handle_request_matching_server_id() {
-        let (handler, conn, network_id, _temp_dir) =
+        let (handler, mut conn, network_id, _temp_dir) =
             create_test_handler_with_store(test_connection_factory!()).await;
 
         // Create a lease first
@@ -1170,26 +2043,14 @@ mod tests {
         .unwrap();
 
         // Create a REQUEST message with matching server ID
-        let mut request = Message::default();
-        request.set_opcode(Opcode::BootRequest);
-        request.set_xid(0x12345678);
-        request.set_chaddr(&[0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff]);
-        request
-            .opts_mut()
-            .insert(v4::DhcpOption::MessageType(MessageType::Request));
-        request
-            .opts_mut()
-            .insert(v4::DhcpOption::RequestedIpAddress(ip));
-        request
-            .opts_mut()
-            .insert(v4::DhcpOption::ServerIdentifier(handler.server_identifier));
+        let request = Probe::request(MAC, ip, handler.server_identifier).build();
 
         // Get test network
         let network = store::get_network(&conn, network_id).await.unwrap();
 
         // Handle the request
         let response = handler
-            .handle_request(&conn, &request, &network, handler.server_identifier)
+            .handle_request(&mut conn, &request, &network, handler.server_identifier)
             .await
             .unwrap();
 
@@ -1217,7 +2078,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_handle_request_non_matching_server_id() {
-        let (handler, conn, network_id, _temp_dir) =
+        let (handler, mut conn, network_id, _temp_dir) =
             create_test_handler_with_store(test_connection_factory!()).await;
 
         // Create a lease first
@@ -1242,26 +2103,14 @@ mod tests {
             "Test setup: wrong server ID should differ from handler's server ID"
         );
 
-        let mut request = Message::default();
-        request.set_opcode(Opcode::BootRequest);
-        request.set_xid(0x12345678);
-        request.set_chaddr(&[0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff]);
-        request
-            .opts_mut()
-            .insert(v4::DhcpOption::MessageType(MessageType::Request));
-        request
-            .opts_mut()
-            .insert(v4::DhcpOption::RequestedIpAddress(ip));
-        request
-            .opts_mut()
-            .insert(v4::DhcpOption::ServerIdentifier(wrong_server_id));
+        let request = Probe::request(MAC, ip, wrong_server_id).build();
 
         // Get test network
         let network = store::get_network(&conn, network_id).await.unwrap();
 
         // Handle the request
         let response = handler
-            .handle_request(&conn, &request, &network, handler.server_identifier)
+            .handle_request(&mut conn, &request, &network, handler.server_identifier)
             .await
             .unwrap();
 
@@ -1274,7 +2123,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_handle_request_without_server_id() {
-        let (handler, conn, network_id, _temp_dir) =
+        let (handler, mut conn, network_id, _temp_dir) =
             create_test_handler_with_store(test_connection_factory!()).await;
 
         // Create a lease first
@@ -1293,16 +2142,7 @@ mod tests {
         .unwrap();
 
         // Create a REQUEST message WITHOUT server ID (RENEWING or INIT-REBOOT)
-        let mut request = Message::default();
-        request.set_opcode(Opcode::BootRequest);
-        request.set_xid(0x12345678);
-        request.set_chaddr(&[0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff]);
-        request
-            .opts_mut()
-            .insert(v4::DhcpOption::MessageType(MessageType::Request));
-        request
-            .opts_mut()
-            .insert(v4::DhcpOption::RequestedIpAddress(ip));
+        let request = Probe::init_reboot(MAC, ip).build();
         // Note: No ServerIdentifier option added
 
         // Get test network
@@ -1310,7 +2150,7 @@ mod tests {
 
         // Handle the request
         let response = handler
-            .handle_request(&conn, &request, &network, handler.server_identifier)
+            .handle_request(&mut conn, &request, &network, handler.server_identifier)
             .await
             .unwrap();
 
@@ -1338,7 +2178,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_handle_request_init_reboot_without_server_id() {
-        let (handler, conn, network_id, _temp_dir) =
+        let (handler, mut conn, network_id, _temp_dir) =
             create_test_handler_with_store(test_connection_factory!()).await;
 
         // Create a lease first
@@ -1357,17 +2197,7 @@ mod tests {
         .unwrap();
 
         // Create an INIT-REBOOT REQUEST (has requested IP, no server ID, no ciaddr)
-        let mut request = Message::default();
-        request.set_opcode(Opcode::BootRequest);
-        request.set_xid(0x12345678);
-        request.set_chaddr(&[0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff]);
-        request.set_ciaddr(Ipv4Addr::UNSPECIFIED); // No ciaddr in INIT-REBOOT
-        request
-            .opts_mut()
-            .insert(v4::DhcpOption::MessageType(MessageType::Request));
-        request
-            .opts_mut()
-            .insert(v4::DhcpOption::RequestedIpAddress(ip));
+        let request = Probe::init_reboot(MAC, ip).build();
         // No ServerIdentifier - characteristic of INIT-REBOOT
 
         // Get test network
@@ -1375,7 +2205,7 @@ mod tests {
 
         // Handle the request
         let response = handler
-            .handle_request(&conn, &request, &network, handler.server_identifier)
+            .handle_request(&mut conn, &request, &network, handler.server_identifier)
             .await
             .unwrap();
 
@@ -1409,7 +2239,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_static_reservation_nak_on_wrong_requested_ip() {
-        let (handler, conn, network_id, _temp_dir) =
+        let (handler, mut conn, network_id, _temp_dir) =
             create_test_handler_with_store(test_connection_factory!()).await;
 
         let mac = "aa:bb:cc:dd:ee:ff";
@@ -1422,20 +2252,11 @@ mod tests {
             .unwrap();
 
         // Create a REQUEST with different IP than reservation
-        let mut request = Message::default();
-        request.set_opcode(Opcode::BootRequest);
-        request.set_xid(0x12345678);
-        request.set_chaddr(&[0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff]);
-        request
-            .opts_mut()
-            .insert(v4::DhcpOption::MessageType(MessageType::Request));
-        request
-            .opts_mut()
-            .insert(v4::DhcpOption::RequestedIpAddress(requested_ip));
+        let request = Probe::init_reboot(MAC, requested_ip).build();
 
         let network = store::get_network(&conn, network_id).await.unwrap();
         let response = handler
-            .handle_request(&conn, &request, &network, handler.server_identifier)
+            .handle_request(&mut conn, &request, &network, handler.server_identifier)
             .await
             .unwrap();
 
@@ -1462,9 +2283,57 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_discover_skips_reserved_address_leased_to_another_mac() {
+        let (handler, mut conn, network_id, _temp_dir) =
+            create_test_handler_with_store(test_connection_factory!()).await;
+        let reserved_ip: Ipv4Addr = "10.0.0.150".parse().unwrap();
+
+        // Another MAC is still actively leasing the address reserved for ours
+        store::create_or_update_lease_with_network(
+            &conn,
+            "aa:bb:cc:dd:ee:01",
+            &reserved_ip,
+            None,
+            LeaseState::Active,
+            3600,
+            network_id,
+        )
+        .await
+        .unwrap();
+        store::create_static_reservation(
+            &conn,
+            network_id,
+            "aa:bb:cc:dd:ee:ff",
+            &reserved_ip.to_string(),
+            None,
+        )
+        .await
+        .unwrap();
+
+        let network = store::get_network(&conn, network_id).await.unwrap();
+        let offer = handler
+            .handle_discover(
+                &mut conn,
+                &Probe::discover(MAC).build(),
+                &network,
+                handler.server_identifier,
+            )
+            .await
+            .unwrap()
+            .expect("DISCOVER should still be offered an address");
+        assert_ne!(offer.yiaddr(), reserved_ip);
+
+        let holder = store::get_lease_by_mac(&conn, "aa:bb:cc:dd:ee:01")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(holder.ip_address, reserved_ip.to_string());
+    }
+
     #[tokio::test]
     async fn test_static_reservation_nak_on_wrong_ciaddr() {
-        let (handler, conn, network_id, _temp_dir) =
+        let (handler, mut conn, network_id, _temp_dir) =
             create_test_handler_with_store(test_connection_factory!()).await;
 
         let mac = "aa:bb:cc:dd:ee:ff";
@@ -1477,18 +2346,11 @@ mod tests {
             .unwrap();
 
         // Create a renewal REQUEST (with ciaddr) for different IP
-        let mut request = Message::default();
-        request.set_opcode(Opcode::BootRequest);
-        request.set_xid(0x12345678);
-        request.set_chaddr(&[0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff]);
-        request.set_ciaddr(ciaddr); // Client renewing with wrong IP
-        request
-            .opts_mut()
-            .insert(v4::DhcpOption::MessageType(MessageType::Request));
+        let request = Probe::renew(MAC, ciaddr).build();
 
         let network = store::get_network(&conn, network_id).await.unwrap();
         let response = handler
-            .handle_request(&conn, &request, &network, handler.server_identifier)
+            .handle_request(&mut conn, &request, &network, handler.server_identifier)
             .await
             .unwrap();
 
@@ -1517,7 +2379,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_static_reservation_ack_on_correct_ip() {
-        let (handler, conn, network_id, _temp_dir) =
+        let (handler, mut conn, network_id, _temp_dir) =
             create_test_handler_with_store(test_connection_factory!()).await;
 
         let mac = "aa:bb:cc:dd:ee:ff";
@@ -1529,20 +2391,11 @@ mod tests {
             .unwrap();
 
         // Create a REQUEST with matching IP
-        let mut request = Message::default();
-        request.set_opcode(Opcode::BootRequest);
-        request.set_xid(0x12345678);
-        request.set_chaddr(&[0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff]);
-        request
-            .opts_mut()
-            .insert(v4::DhcpOption::MessageType(MessageType::Request));
-        request
-            .opts_mut()
-            .insert(v4::DhcpOption::RequestedIpAddress(reserved_ip));
+        let request = Probe::init_reboot(MAC, reserved_ip).build();
 
         let network = store::get_network(&conn, network_id).await.unwrap();
         let response = handler
-            .handle_request(&conn, &request, &network, handler.server_identifier)
+            .handle_request(&mut conn, &request, &network, handler.server_identifier)
             .await
             .unwrap();
 
@@ -1578,7 +2431,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_static_reservation_overrides_existing_lease() {
-        let (handler, conn, network_id, _temp_dir) =
+        let (handler, mut conn, network_id, _temp_dir) =
             create_test_handler_with_store(test_connection_factory!()).await;
 
         let mac = "aa:bb:cc:dd:ee:ff";
@@ -1604,18 +2457,11 @@ mod tests {
             .unwrap();
 
         // Client tries to renew the old IP
-        let mut request = Message::default();
-        request.set_opcode(Opcode::BootRequest);
-        request.set_xid(0x12345678);
-        request.set_chaddr(&[0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff]);
-        request.set_ciaddr(old_ip); // Renewing old IP
-        request
-            .opts_mut()
-            .insert(v4::DhcpOption::MessageType(MessageType::Request));
+        let request = Probe::renew(MAC, old_ip).build();
 
         let network = store::get_network(&conn, network_id).await.unwrap();
         let response = handler
-            .handle_request(&conn, &request, &network, handler.server_identifier)
+            .handle_request(&mut conn, &request, &network, handler.server_identifier)
             .await
             .unwrap();
 
@@ -1642,9 +2488,62 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_request_naks_reservation_held_by_another_mac() {
+        let (handler, mut conn, network_id, _temp_dir) =
+            create_test_handler_with_store(test_connection_factory!()).await;
+
+        let reserved_ip: Ipv4Addr = "10.0.0.50".parse().unwrap();
+        let holder = "11:22:33:44:55:66";
+
+        // Another client already holds the address the reservation points at
+        store::create_or_update_lease_with_network(
+            &conn,
+            holder,
+            &reserved_ip,
+            None,
+            LeaseState::Active,
+            3600,
+            network_id,
+        )
+        .await
+        .unwrap();
+        store::create_static_reservation(
+            &conn,
+            network_id,
+            "aa:bb:cc:dd:ee:ff",
+            &reserved_ip.to_string(),
+            None,
+        )
+        .await
+        .unwrap();
+
+        let request = Probe::init_reboot(MAC, reserved_ip).build();
+        let network = store::get_network(&conn, network_id).await.unwrap();
+        let response = handler
+            .handle_request(&mut conn, &request, &network, handler.server_identifier)
+            .await
+            .unwrap()
+            .expect("Should respond with NAK");
+        assert_eq!(response.opts().msg_type(), Some(MessageType::Nak));
+
+        assert!(
+            store::get_lease_by_mac(&conn, "aa:bb:cc:dd:ee:ff")
+                .await
+                .unwrap()
+                .is_none(),
+            "no second lease should be recorded"
+        );
+        let held = store::get_lease_by_mac(&conn, holder)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(held.ip_address, reserved_ip.to_string());
+    }
+
     #[tokio::test]
     async fn test_static_reservation_full_workflow() {
-        let (handler, conn, network_id, _temp_dir) =
+        let (handler, mut conn, network_id, _temp_dir) =
             create_test_handler_with_store(test_connection_factory!()).await;
 
         let mac = "aa:bb:cc:dd:ee:ff";
@@ -1670,18 +2569,16 @@ mod tests {
             .unwrap();
 
         // Step 3: Client tries to renew old IP and gets NAKed
-        let mut renew_request = Message::default();
-        renew_request.set_opcode(Opcode::BootRequest);
-        renew_request.set_xid(0x12345678);
-        renew_request.set_chaddr(&[0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff]);
-        renew_request.set_ciaddr(old_ip);
-        renew_request
-            .opts_mut()
-            .insert(v4::DhcpOption::MessageType(MessageType::Request));
+        let renew_request = Probe::renew(MAC, old_ip).build();
 
         let network = store::get_network(&conn, network_id).await.unwrap();
         let response = handler
-            .handle_request(&conn, &renew_request, &network, handler.server_identifier)
+            .handle_request(
+                &mut conn,
+                &renew_request,
+                &network,
+                handler.server_identifier,
+            )
             .await
             .unwrap();
 
@@ -1694,19 +2591,10 @@ mod tests {
         assert_eq!(msg_type, MessageType::Nak, "Should NAK renewal of old IP");
 
         // Step 4: Client rediscovers and requests the reserved IP
-        let mut new_request = Message::default();
-        new_request.set_opcode(Opcode::BootRequest);
-        new_request.set_xid(0x87654321);
-        new_request.set_chaddr(&[0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff]);
-        new_request
-            .opts_mut()
-            .insert(v4::DhcpOption::MessageType(MessageType::Request));
-        new_request
-            .opts_mut()
-            .insert(v4::DhcpOption::RequestedIpAddress(reserved_ip));
+        let new_request = Probe::init_reboot(MAC, reserved_ip).xid(0x87654321).build();
 
         let response = handler
-            .handle_request(&conn, &new_request, &network, handler.server_identifier)
+            .handle_request(&mut conn, &new_request, &network, handler.server_identifier)
             .await
             .unwrap();
 
@@ -1739,4 +2627,1300 @@ mod tests {
         );
         assert_eq!(lease.state, LeaseState::Active, "Lease should be active");
     }
+
+    // Client Identifier (Option 61) Tests
+
+    fn make_discover(chaddr: [u8; 6], client_id: Option<&[u8]>) -> Message {
+        let probe = Probe::discover(chaddr);
+        match client_id {
+            Some(id) => probe.option(v4::DhcpOption::ClientIdentifier(id.to_vec())),
+            None => probe,
+        }
+        .build()
+    }
+
+    #[tokio::test]
+    async fn test_client_id_keyed_lease_follows_mac_change() {
+        let (handler, mut conn, network_id, _temp_dir) =
+            create_test_handler_with_store(test_connection_factory!()).await;
+        let network = store::get_network(&conn, network_id).await.unwrap();
+        let client_id: &[u8] = &[0xff, 0x00, 0x00, 0x00, 0x01];
+
+        let first = handler
+            .handle_discover(
+                &mut conn,
+                &make_discover([0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0x01], Some(client_id)),
+                &network,
+                handler.server_identifier,
+            )
+            .await
+            .unwrap()
+            .expect("first DISCOVER should be offered");
+
+        // Same client identifier behind a different MAC (e.g. bond failover)
+        let second = handler
+            .handle_discover(
+                &mut conn,
+                &make_discover([0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0x02], Some(client_id)),
+                &network,
+                handler.server_identifier,
+            )
+            .await
+            .unwrap()
+            .expect("second DISCOVER should be offered");
+
+        assert_eq!(first.yiaddr(), second.yiaddr());
+
+        let lease = store::get_lease_by_client_id(&conn, "ff:00:00:00:01")
+            .await
+            .unwrap()
+            .expect("lease should be keyed by client identifier");
+        assert_eq!(lease.mac_address, "aa:bb:cc:dd:ee:02");
+        assert!(
+            store::get_lease_by_mac(&conn, "aa:bb:cc:dd:ee:01")
+                .await
+                .unwrap()
+                .is_none(),
+            "old MAC should no longer hold a lease"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_mac_keyed_leases_without_client_id() {
+        let (handler, mut conn, network_id, _temp_dir) =
+            create_test_handler_with_store(test_connection_factory!()).await;
+        let network = store::get_network(&conn, network_id).await.unwrap();
+
+        let first = handler
+            .handle_discover(
+                &mut conn,
+                &make_discover([0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0x01], None),
+                &network,
+                handler.server_identifier,
+            )
+            .await
+            .unwrap()
+            .expect("first DISCOVER should be offered");
+        let second = handler
+            .handle_discover(
+                &mut conn,
+                &make_discover([0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0x02], None),
+                &network,
+                handler.server_identifier,
+            )
+            .await
+            .unwrap()
+            .expect("second DISCOVER should be offered");
+
+        assert_ne!(first.yiaddr(), second.yiaddr());
+        let lease = store::get_lease_by_mac(&conn, "aa:bb:cc:dd:ee:01")
+            .await
+            .unwrap()
+            .expect("first MAC keeps its own lease");
+        assert!(lease.client_id.is_none());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_discovers_get_unique_offers() {
+        let (handler, conn, network_id, _temp_dir) =
+            create_test_handler_with_store(test_connection_factory!()).await;
+        let network = store::get_network(&conn, network_id).await.unwrap();
+
+        let tasks: Vec<_> = (0..20u8)
+            .map(|i| {
+                let handler = handler.clone();
+                let network = network.clone();
+                tokio::spawn(async move {
+                    let mut conn = handler.db.open().await.unwrap();
+                    let discover = make_discover([0xaa, 0xbb, 0xcc, 0xdd, 0x00, i], None);
+                    handler
+                        .handle_discover(&mut conn, &discover, &network, handler.server_identifier)
+                        .await
+                        .unwrap()
+                        .expect("every DISCOVER should be offered")
+                        .yiaddr()
+                })
+            })
+            .collect();
+
+        let mut offered = std::collections::HashSet::new();
+        for task in tasks {
+            let ip = task.await.unwrap();
+            assert!(offered.insert(ip), "{} was offered twice", ip);
+        }
+        assert_eq!(offered.len(), 20);
+    }
+
+    #[tokio::test]
+    async fn test_bootreply_packet_is_ignored() {
+        let (handler, conn, _network_id, _temp_dir) =
+            create_test_handler_with_store(test_connection_factory!()).await;
+
+        let mut msg = Probe::discover([0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0x42]).build();
+        msg.set_opcode(Opcode::BootReply);
+        let encode = |msg: &Message| {
+            let mut buf = Vec::new();
+            msg.encode(&mut Encoder::new(&mut buf)).unwrap();
+            buf
+        };
+        let local_ip: Ipv4Addr = "10.0.0.1".parse().unwrap();
+        let peer: SocketAddr = "10.0.0.150:68".parse().unwrap();
+
+        let reply = handler
+            .handle_l2_unicast_packet(&encode(&msg), peer, local_ip)
+            .await
+            .unwrap();
+        assert!(reply.is_none(), "BOOTREPLY must not be answered");
+        assert!(
+            store::get_lease_by_mac(&conn, "aa:bb:cc:dd:ee:42")
+                .await
+                .unwrap()
+                .is_none(),
+            "BOOTREPLY must not allocate a lease"
+        );
+
+        // The same packet as a BOOTREQUEST is answered
+        msg.set_opcode(Opcode::BootRequest);
+        let reply = handler
+            .handle_l2_unicast_packet(&encode(&msg), peer, local_ip)
+            .await
+            .unwrap();
+        assert!(reply.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_oui_allowlist_gates_discover() {
+        let (handler, mut conn, network_id, _temp_dir) =
+            create_test_handler_with_store(test_connection_factory!()).await;
+        let handler =
+            handler.with_oui_filter(OuiFilter::new(vec!["00:25:90".parse().unwrap()], vec![]));
+        let network = store::get_network(&conn, network_id).await.unwrap();
+
+        let allowed = [0x00, 0x25, 0x90, 0x00, 0x00, 0x01];
+        let offer = handler
+            .handle_discover(
+                &mut conn,
+                &Probe::discover(allowed).build(),
+                &network,
+                handler.server_identifier,
+            )
+            .await
+            .unwrap();
+        assert!(offer.is_some(), "allowlisted vendor should be offered");
+
+        let filtered = [0xf0, 0x18, 0x98, 0x00, 0x00, 0x01];
+        let offer = handler
+            .handle_discover(
+                &mut conn,
+                &Probe::discover(filtered).build(),
+                &network,
+                handler.server_identifier,
+            )
+            .await
+            .unwrap();
+        assert!(offer.is_none(), "other vendors should be ignored");
+        assert!(
+            store::get_lease_by_mac(&conn, "f0:18:98:00:00:01")
+                .await
+                .unwrap()
+                .is_none(),
+            "an ignored client must not get a lease row"
+        );
+
+        // INIT-REBOOT cannot bypass the filter either
+        let reply = handler
+            .handle_request(
+                &mut conn,
+                &Probe::init_reboot(filtered, "10.0.0.100".parse().unwrap()).build(),
+                &network,
+                handler.server_identifier,
+            )
+            .await
+            .unwrap();
+        assert!(reply.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_discover_is_noted_in_recent_packets() {
+        let (handler, conn, _network_id, _temp_dir) =
+            create_test_handler_with_store(test_connection_factory!()).await;
+        let reply = handler
+            .handle_l2_unicast_packet(
+                &Probe::discover(MAC).to_bytes(),
+                "0.0.0.0:68".parse().unwrap(),
+                "10.0.0.1".parse().unwrap(),
+            )
+            .await
+            .unwrap();
+        assert!(reply.is_some());
+
+        let events = handler.recent().snapshot();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].mac.as_deref(), Some("aa:bb:cc:dd:ee:ff"));
+        assert_eq!(events[0].message_type.as_deref(), Some("Discover"));
+        let offered = store::get_lease_by_mac(&conn, "aa:bb:cc:dd:ee:ff")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            events[0].decision,
+            Decision::Offered {
+                ip: offered.ip_address.parse().unwrap()
+            }
+        );
+
+        // A REQUEST for another server is ignored, with the reason recorded
+        handler
+            .handle_l2_unicast_packet(
+                &Probe::request(
+                    MAC,
+                    "10.0.0.100".parse().unwrap(),
+                    "10.9.9.9".parse().unwrap(),
+                )
+                .to_bytes(),
+                "0.0.0.0:68".parse().unwrap(),
+                "10.0.0.1".parse().unwrap(),
+            )
+            .await
+            .unwrap();
+        assert!(matches!(
+            &handler.recent().snapshot()[0].decision,
+            Decision::Ignored { reason } if reason.contains("another server")
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_handler_error_is_noted_in_recent_packets() {
+        let (handler, conn, _network_id, _temp_dir) =
+            create_test_handler_with_store(test_connection_factory!()).await;
+        conn.execute("DROP TABLE dhcp_leases", ()).await.unwrap();
+
+        let result = handler
+            .handle_l2_unicast_packet(
+                &Probe::discover(MAC).to_bytes(),
+                "0.0.0.0:68".parse().unwrap(),
+                "10.0.0.1".parse().unwrap(),
+            )
+            .await;
+        assert!(result.is_err());
+
+        let events = handler.recent().snapshot();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].message_type.as_deref(), Some("Discover"));
+        assert!(matches!(
+            &events[0].decision,
+            Decision::Error { error } if error.contains("dhcp_leases")
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_renewal_ack_is_unicast_to_ciaddr() {
+        let (handler, _conn, _network_id, _temp_dir) =
+            create_test_handler_with_store(test_connection_factory!()).await;
+        let local_ip: Ipv4Addr = "10.0.0.1".parse().unwrap();
+        let no_address: SocketAddr = "0.0.0.0:68".parse().unwrap();
+        let broadcast: SocketAddr = "255.255.255.255:68".parse().unwrap();
+
+        let offer = handler
+            .handle_l2_unicast_packet(&Probe::discover(MAC).to_bytes(), no_address, local_ip)
+            .await
+            .unwrap()
+            .unwrap();
+        let DhcpReply::L2 { dest, .. } = &offer else {
+            panic!("expected an L2 reply");
+        };
+        assert_eq!(*dest, broadcast, "INIT-path replies are broadcast");
+        let ip = decode_reply(&offer).yiaddr();
+
+        let ack = handler
+            .handle_l2_unicast_packet(
+                &Probe::request(MAC, ip, local_ip).to_bytes(),
+                no_address,
+                local_ip,
+            )
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(decode_reply(&ack).yiaddr(), ip);
+
+        // RENEWING: unicast from the leased address, answered on ciaddr:68
+        let ack = handler
+            .handle_l2_unicast_packet(
+                &Probe::renew(MAC, ip).to_bytes(),
+                SocketAddr::new(ip.into(), 68),
+                local_ip,
+            )
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(decode_reply(&ack).opts().msg_type(), Some(MessageType::Ack));
+        let DhcpReply::L2 { dest, .. } = &ack else {
+            panic!("expected an L2 reply");
+        };
+        assert_eq!(*dest, SocketAddr::new(ip.into(), 68));
+    }
+
+    #[tokio::test]
+    async fn test_renewal_nak_is_broadcast() {
+        let (handler, conn, network_id, _temp_dir) =
+            create_test_handler_with_store(test_connection_factory!()).await;
+        let local_ip: Ipv4Addr = "10.0.0.1".parse().unwrap();
+        let old_ip: Ipv4Addr = "10.0.0.100".parse().unwrap();
+        let mac = format_mac(&MAC);
+        store::create_or_update_lease_with_network(
+            &conn,
+            &mac,
+            &old_ip,
+            None,
+            LeaseState::Active,
+            3600,
+            network_id,
+        )
+        .await
+        .unwrap();
+        // The lease no longer matches the client's reservation, so the renewal is refused
+        store::create_static_reservation(&conn, network_id, &mac, "10.0.0.50", None)
+            .await
+            .unwrap();
+
+        let nak = handler
+            .handle_l2_unicast_packet(
+                &Probe::renew(MAC, old_ip).to_bytes(),
+                SocketAddr::new(old_ip.into(), 68),
+                local_ip,
+            )
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(decode_reply(&nak).opts().msg_type(), Some(MessageType::Nak));
+        let DhcpReply::L2 { dest, .. } = &nak else {
+            panic!("expected an L2 reply");
+        };
+        assert_eq!(*dest, "255.255.255.255:68".parse().unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_legacy_bootp_request_answered_from_reservation() {
+        use crate::dhcp::bootp::tests::bootp_request;
+
+        let (handler, conn, network_id, _temp_dir) =
+            create_test_handler_with_store(test_connection_factory!()).await;
+        let local_ip: Ipv4Addr = "10.0.0.1".parse().unwrap();
+        let peer: SocketAddr = "0.0.0.0:68".parse().unwrap();
+        let request = bootp_request(MAC, 0x0bad_cafe);
+        store::create_static_reservation(&conn, network_id, &format_mac(&MAC), "10.0.0.42", None)
+            .await
+            .unwrap();
+
+        // Off by default: a cookie-less packet is malformed DHCP
+        assert!(
+            handler
+                .handle_l2_unicast_packet(&request, peer, local_ip)
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        let handler = handler.with_legacy_bootp(true);
+        let reply = handler
+            .handle_l2_unicast_packet(&request, peer, local_ip)
+            .await
+            .unwrap()
+            .unwrap();
+        let DhcpReply::L2 { data, dest, .. } = reply else {
+            panic!("expected an L2 reply");
+        };
+        assert_eq!(dest, "255.255.255.255:68".parse().unwrap());
+        assert_eq!(data.len(), 300);
+        assert_eq!(data[0], 2, "BOOTREPLY");
+        assert_eq!(&data[4..8], &0x0bad_cafeu32.to_be_bytes());
+        assert_eq!(
+            &data[16..20],
+            &[10, 0, 0, 42],
+            "yiaddr from the reservation"
+        );
+        assert_eq!(&data[20..24], &[10, 0, 0, 1], "siaddr is the TFTP server");
+        assert!(data[108..236].starts_with(b"undionly.kpxe\0"));
+        assert!(
+            data[236..].iter().all(|&b| b == 0),
+            "no magic cookie or options"
+        );
+
+        // No reservation, no reply
+        let stranger = bootp_request([0x00, 0x11, 0x22, 0x33, 0x44, 0x55], 1);
+        assert!(
+            handler
+                .handle_l2_unicast_packet(&stranger, peer, local_ip)
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_legacy_bootp_request_respects_oui_filter() {
+        use crate::dhcp::bootp::tests::bootp_request;
+
+        let (handler, conn, network_id, _temp_dir) =
+            create_test_handler_with_store(test_connection_factory!()).await;
+        let handler = handler
+            .with_legacy_bootp(true)
+            .with_oui_filter(OuiFilter::new(vec![], vec![Oui::of(&MAC).unwrap()]));
+        store::create_static_reservation(&conn, network_id, &format_mac(&MAC), "10.0.0.42", None)
+            .await
+            .unwrap();
+
+        let reply = handler
+            .handle_l2_unicast_packet(
+                &bootp_request(MAC, 1),
+                "0.0.0.0:68".parse().unwrap(),
+                "10.0.0.1".parse().unwrap(),
+            )
+            .await
+            .unwrap();
+        assert!(reply.is_none(), "a denied OUI gets no BOOTREPLY");
+        let events = handler.recent().snapshot();
+        assert!(matches!(
+            &events.last().unwrap().decision,
+            Decision::Ignored { reason } if reason.contains("is not permitted")
+        ));
+    }
+
+    #[test]
+    fn test_l2_reply_destination_prefers_ciaddr() {
+        let ciaddr: Ipv4Addr = "10.0.0.150".parse().unwrap();
+        let renew = Probe::renew(MAC, ciaddr).build();
+        let discover = Probe::discover(MAC).build();
+
+        // Even when the source address says otherwise (e.g. a client's ephemeral port)
+        let from: SocketAddr = "10.0.0.150:49152".parse().unwrap();
+        let ack = Some(MessageType::Ack);
+        assert_eq!(
+            l2_reply_destination(&renew, ack, from),
+            SocketAddr::new(ciaddr.into(), 68)
+        );
+        // ...but a NAK is broadcast, ciaddr or not
+        assert_eq!(
+            l2_reply_destination(&renew, Some(MessageType::Nak), from),
+            "255.255.255.255:68".parse::<SocketAddr>().unwrap()
+        );
+        let offer = Some(MessageType::Offer);
+        assert_eq!(
+            l2_reply_destination(&discover, offer, "0.0.0.0:68".parse().unwrap()),
+            "255.255.255.255:68".parse::<SocketAddr>().unwrap()
+        );
+        assert_eq!(
+            l2_reply_destination(&discover, offer, "127.0.0.1:6868".parse().unwrap()),
+            "127.0.0.1:6868".parse::<SocketAddr>().unwrap()
+        );
+    }
+
+    /// Decode the message carried by a reply.
+    fn decode_reply(reply: &DhcpReply) -> Message {
+        use dhcproto::Decodable;
+        let (DhcpReply::L2 { data, .. } | DhcpReply::Relay { data, .. }) = reply;
+        Message::decode(&mut dhcproto::Decoder::new(data)).unwrap()
+    }
+
+    /// Lease time option (51) of a reply.
+    fn granted_lease_time(reply: &DhcpReply) -> u32 {
+        let msg = decode_reply(reply);
+        match msg.opts().get(v4::OptionCode::AddressLeaseTime) {
+            Some(v4::DhcpOption::AddressLeaseTime(secs)) => *secs,
+            other => panic!("expected a lease time option, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_authoritative_naks_out_of_scope_requests() {
+        let (handler, mut conn, network_id, _temp_dir) =
+            create_test_handler_with_store(test_connection_factory!()).await;
+        let network = store::get_network(&conn, network_id).await.unwrap();
+
+        // Stale address from a previous network, and an in-subnet address we never leased
+        for ip in ["192.168.1.50", "10.0.0.150"] {
+            let request = Probe::init_reboot(MAC, ip.parse().unwrap()).build();
+            let reply = handler
+                .handle_request(&mut conn, &request, &network, handler.server_identifier)
+                .await
+                .unwrap()
+                .expect("authoritative server should answer");
+            assert_eq!(reply.opts().msg_type(), Some(MessageType::Nak), "{ip}");
+        }
+    }
+
+    /// Send an INIT-REBOOT REQUEST for `ip` from `mac` and return the reply type.
+    async fn request_reply_type(
+        handler: &DhcpHandler,
+        conn: &mut Connection,
+        network: &DhcpNetwork,
+        mac: [u8; 6],
+        ip: &str,
+    ) -> Option<MessageType> {
+        let request = Probe::init_reboot(mac, ip.parse().unwrap()).build();
+        handler
+            .handle_request(conn, &request, network, handler.server_identifier)
+            .await
+            .unwrap()
+            .map(|reply| reply.opts().msg_type().unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_request_for_another_served_subnet_is_naked() {
+        let (handler, mut conn, network_id, _temp_dir) =
+            create_test_handler_with_store(test_connection_factory!()).await;
+        store::create_network(
+            &conn,
+            "Remote Network",
+            "10.0.1.0/24",
+            "10.0.1.1",
+            &[],
+            86400,
+            Some("10.0.1.254"),
+            false,
+        )
+        .await
+        .unwrap();
+        let network = store::get_network(&conn, network_id).await.unwrap();
+
+        // We know the client moved, so even a non-authoritative server NAKs
+        for handler in [handler.clone(), handler.with_authoritative(false)] {
+            assert_eq!(
+                request_reply_type(&handler, &mut conn, &network, MAC, "10.0.1.50").await,
+                Some(MessageType::Nak)
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_request_for_address_held_by_another_client_is_naked() {
+        let (handler, mut conn, network_id, _temp_dir) =
+            create_test_handler_with_store(test_connection_factory!()).await;
+        let handler = handler.with_authoritative(false);
+        let network = store::get_network(&conn, network_id).await.unwrap();
+
+        // Leased to another client
+        store::create_or_update_lease_with_network(
+            &conn,
+            "aa:bb:cc:dd:ee:01",
+            &"10.0.0.150".parse().unwrap(),
+            None,
+            LeaseState::Active,
+            3600,
+            network_id,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            request_reply_type(&handler, &mut conn, &network, MAC, "10.0.0.150").await,
+            Some(MessageType::Nak)
+        );
+
+        // Reserved for another client
+        store::create_static_reservation(&conn, network_id, "aa:bb:cc:dd:ee:02", "10.0.0.20", None)
+            .await
+            .unwrap();
+        assert_eq!(
+            request_reply_type(&handler, &mut conn, &network, MAC, "10.0.0.20").await,
+            Some(MessageType::Nak)
+        );
+
+        // Free and in our subnet, but never offered to this client: not ours to refuse
+        assert_eq!(
+            request_reply_type(&handler, &mut conn, &network, MAC, "10.0.0.151").await,
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_request_for_unserved_subnet_depends_on_authority() {
+        let (handler, mut conn, network_id, _temp_dir) =
+            create_test_handler_with_store(test_connection_factory!()).await;
+        let network = store::get_network(&conn, network_id).await.unwrap();
+
+        assert_eq!(
+            request_reply_type(&handler, &mut conn, &network, MAC, "172.16.0.5").await,
+            Some(MessageType::Nak)
+        );
+
+        let handler = handler.with_authoritative(false);
+        assert_eq!(
+            request_reply_type(&handler, &mut conn, &network, MAC, "172.16.0.5").await,
+            None
+        );
+        assert!(matches!(
+            &handler.recent().snapshot()[0].decision,
+            Decision::Ignored { reason } if reason == "requested 172.16.0.5 is in none of our subnets"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_non_authoritative_ignores_out_of_scope_requests() {
+        let (handler, mut conn, network_id, _temp_dir) =
+            create_test_handler_with_store(test_connection_factory!()).await;
+        let handler = handler.with_authoritative(false);
+        let network = store::get_network(&conn, network_id).await.unwrap();
+
+        for ip in ["192.168.1.50", "10.0.0.150"] {
+            let request = Probe::init_reboot(MAC, ip.parse().unwrap()).build();
+            let reply = handler
+                .handle_request(&mut conn, &request, &network, handler.server_identifier)
+                .await
+                .unwrap();
+            assert!(reply.is_none(), "{ip} should be ignored");
+        }
+        assert!(matches!(
+            &handler.recent().snapshot()[0].decision,
+            Decision::Ignored { reason } if reason == "no lease for aa:bb:cc:dd:ee:ff"
+        ));
+
+        // Clients we did lease to are still answered
+        let offer = handler
+            .handle_discover(
+                &mut conn,
+                &Probe::discover(MAC).build(),
+                &network,
+                handler.server_identifier,
+            )
+            .await
+            .unwrap()
+            .unwrap();
+        let request = Probe::request(MAC, offer.yiaddr(), handler.server_identifier).build();
+        let ack = handler
+            .handle_request(&mut conn, &request, &network, handler.server_identifier)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(ack.opts().msg_type(), Some(MessageType::Ack));
+    }
+
+    #[tokio::test]
+    async fn test_relayed_replies_use_network_server_identifier() {
+        let (handler, conn, _network_id, _temp_dir) =
+            create_test_handler_with_store(test_connection_factory!()).await;
+        let mut relays = Vec::new();
+        for (n, mac_last) in [(1u8, 0x01u8), (2, 0x02)] {
+            let relay = Ipv4Addr::new(10, n, 0, 1);
+            let network = store::create_network(
+                &conn,
+                &format!("Rack {}", n),
+                &format!("10.{}.0.0/24", n),
+                &relay.to_string(),
+                &[],
+                3600,
+                Some(&relay.to_string()),
+                false,
+            )
+            .await
+            .unwrap();
+            store::create_pool(
+                &conn,
+                network.id,
+                "Pool",
+                &format!("10.{}.0.100", n),
+                &format!("10.{}.0.200", n),
+            )
+            .await
+            .unwrap();
+            let server_ip = Ipv4Addr::new(10, n, 0, 2);
+            store::set_server_identifier(&conn, network.id, Some(server_ip))
+                .await
+                .unwrap();
+            relays.push((relay, server_ip, [0x02, 0, 0, 0, 0, mac_last]));
+        }
+
+        for (relay, server_ip, mac) in relays {
+            let pkt_info = PktInfo {
+                if_index: 0,
+                addr_src: SocketAddr::new(relay.into(), 67),
+                addr_dst: handler.server_identifier,
+            };
+            let offer = handler
+                .handle_packet(&Probe::discover(mac).relayed(relay).to_bytes(), &pkt_info)
+                .await
+                .unwrap()
+                .unwrap();
+            let offer = decode_reply(&offer);
+            assert_eq!(extract_server_identifier(&offer), Some(server_ip));
+            assert_eq!(offer.siaddr(), server_ip);
+
+            // The client echoes the subnet's identifier back and is ACKed
+            let request = Probe::request(mac, offer.yiaddr(), server_ip).relayed(relay);
+            let ack = handler
+                .handle_packet(&request.to_bytes(), &pkt_info)
+                .await
+                .unwrap()
+                .unwrap();
+            let ack = decode_reply(&ack);
+            assert_eq!(ack.opts().msg_type(), Some(MessageType::Ack));
+            assert_eq!(extract_server_identifier(&ack), Some(server_ip));
+        }
+
+        // Clearing goes back to the server-wide default; non-unicast addresses are refused
+        let network = store::get_network_by_relay(&conn, Some(Ipv4Addr::new(10, 1, 0, 1)))
+            .await
+            .unwrap()
+            .unwrap();
+        let network = store::set_server_identifier(&conn, network.id, None)
+            .await
+            .unwrap();
+        assert_eq!(network.server_identifier, None);
+        assert!(
+            store::set_server_identifier(&conn, network.id, Some(Ipv4Addr::BROADCAST))
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_link_selection_overrides_giaddr_for_network() {
+        use dhcproto::v4::relay::{RelayAgentInformation, RelayInfo};
+
+        let (handler, conn, network_id, _temp_dir) =
+            create_test_handler_with_store(test_connection_factory!()).await;
+        // The relay's own address is on a transit subnet we have no network for
+        let relay = Ipv4Addr::new(192, 168, 77, 1);
+        let plain = Probe::discover(MAC).relayed(relay).build();
+        let option82 = parse_option82(&plain).unwrap_or_default();
+        assert!(
+            handler
+                .relay_network(&conn, &plain, &option82)
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        let mut info = RelayAgentInformation::default();
+        info.insert(RelayInfo::LinkSelection(Ipv4Addr::new(10, 0, 0, 0)));
+        let linked = Probe::discover(MAC)
+            .relayed(relay)
+            .option(v4::DhcpOption::RelayAgentInformation(info))
+            .build();
+        let option82 = parse_option82(&linked).unwrap();
+        let network = handler
+            .relay_network(&conn, &linked, &option82)
+            .await
+            .unwrap()
+            .expect("link selection should pick the 10.0.0.0/24 network");
+        assert_eq!(network.id, network_id);
+    }
+
+    #[tokio::test]
+    async fn test_quarantine_network_serves_unprovisioned_devices() {
+        let (handler, conn, network_id, _temp_dir) =
+            create_test_handler_with_store(test_connection_factory!()).await;
+        let quarantine = store::create_network(
+            &conn,
+            "Quarantine",
+            "10.99.0.0/24",
+            "10.99.0.1",
+            &[],
+            3600,
+            Some("10.99.0.1"),
+            false,
+        )
+        .await
+        .unwrap();
+        store::create_pool(
+            &conn,
+            quarantine.id,
+            "Quarantine Pool",
+            "10.99.0.100",
+            "10.99.0.200",
+        )
+        .await
+        .unwrap();
+        store::set_quarantine_network(&conn, network_id, Some(quarantine.id))
+            .await
+            .unwrap();
+
+        let discover = |mac| {
+            let handler = &handler;
+            async move {
+                let reply = handler
+                    .handle_l2_unicast_packet(
+                        &Probe::discover(mac).to_bytes(),
+                        "0.0.0.0:68".parse().unwrap(),
+                        "10.0.0.1".parse().unwrap(),
+                    )
+                    .await
+                    .unwrap()
+                    .expect("DISCOVER should be offered");
+                decode_reply(&reply)
+            }
+        };
+        let in_subnet = |ip, subnet: &str| {
+            subnet
+                .parse::<common::subnet::Ipv4Subnet>()
+                .unwrap()
+                .ip_in_range(ip)
+        };
+
+        // Unknown device: quarantine address and options
+        let offer = discover(MAC).await;
+        assert!(in_subnet(offer.yiaddr(), "10.99.0.0/24"));
+
+        // Provisioned device: production address
+        let provisioned_mac = [0xaa, 0xbb, 0xcc, 0x00, 0x00, 0x01];
+        let uuid = uuid::Uuid::parse_str("550e8400-e29b-41d4-a716-4466554402b1").unwrap();
+        let director = crate::director::Director::new(&conn);
+        director
+            .register_device(&uuid, crate::director::Architecture::X86_64)
+            .await
+            .unwrap();
+        director
+            .set_device_ip_address(&uuid, "10.0.0.50", "aa:bb:cc:00:00:01")
+            .await
+            .unwrap();
+        crate::lifecycle::store::update_device_lifecycle(
+            &conn,
+            &uuid,
+            DeviceLifecycle::Provisioned,
+        )
+        .await
+        .unwrap();
+        let offer = discover(provisioned_mac).await;
+        assert!(in_subnet(offer.yiaddr(), "10.0.0.0/24"));
+    }
+
+    #[tokio::test]
+    async fn test_requested_lease_time_is_clamped_and_recorded() {
+        let (handler, conn, _network_id, _temp_dir) =
+            create_test_handler_with_store(test_connection_factory!()).await;
+        let handler = handler.with_min_lease_time(300);
+        let recorded = |lease: store::Lease| (lease.lease_end - lease.lease_start).num_seconds();
+
+        // Within [300, 86400]: granted as asked in the OFFER, while the offered lease
+        // row is only held until the REQUEST is due
+        let offer = handler
+            .handle_l2_unicast_packet(
+                &Probe::discover(MAC)
+                    .option(v4::DhcpOption::AddressLeaseTime(3600))
+                    .to_bytes(),
+                "0.0.0.0:68".parse().unwrap(),
+                "10.0.0.1".parse().unwrap(),
+            )
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(granted_lease_time(&offer), 3600);
+        let lease = store::get_lease_by_mac(&conn, "aa:bb:cc:dd:ee:ff")
+            .await
+            .unwrap()
+            .unwrap();
+        let ip: Ipv4Addr = lease.ip_address.parse().unwrap();
+        assert_eq!(recorded(lease), store::DEFAULT_OFFER_TTL as i64);
+
+        // Above the network's lease duration: capped at it
+        let ack = handler
+            .handle_l2_unicast_packet(
+                &Probe::request(MAC, ip, "10.0.0.1".parse().unwrap())
+                    .option(v4::DhcpOption::AddressLeaseTime(7 * 86400))
+                    .to_bytes(),
+                "0.0.0.0:68".parse().unwrap(),
+                "10.0.0.1".parse().unwrap(),
+            )
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(granted_lease_time(&ack), 86400);
+        let lease = store::get_lease_by_mac(&conn, "aa:bb:cc:dd:ee:ff")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(lease.state, LeaseState::Active);
+        assert_eq!(recorded(lease), 86400);
+
+        // Below the floor: raised to it on renewal
+        let ack = handler
+            .handle_l2_unicast_packet(
+                &Probe::request(MAC, ip, "10.0.0.1".parse().unwrap())
+                    .option(v4::DhcpOption::AddressLeaseTime(30))
+                    .to_bytes(),
+                "0.0.0.0:68".parse().unwrap(),
+                "10.0.0.1".parse().unwrap(),
+            )
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(granted_lease_time(&ack), 300);
+        let lease = store::get_lease_by_mac(&conn, "aa:bb:cc:dd:ee:ff")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(recorded(lease), 300);
+    }
+
+    #[tokio::test]
+    async fn test_offer_delay_applies_to_offers_not_acks() {
+        let (handler, _conn, _network_id, _temp_dir) =
+            create_test_handler_with_store(test_connection_factory!()).await;
+        let delay = Duration::from_millis(300);
+        let handler = handler.with_offer_delay(delay);
+        let local_ip: Ipv4Addr = "10.0.0.1".parse().unwrap();
+        let peer: SocketAddr = "0.0.0.0:68".parse().unwrap();
+
+        let started = tokio::time::Instant::now();
+        let offer = handler
+            .handle_l2_unicast_packet(&Probe::discover(MAC).to_bytes(), peer, local_ip)
+            .await
+            .unwrap()
+            .expect("DISCOVER should be offered");
+        assert!(
+            started.elapsed() >= delay,
+            "OFFER sent after {:?}",
+            started.elapsed()
+        );
+        let offered_ip = decode_reply(&offer).yiaddr();
+
+        let started = tokio::time::Instant::now();
+        let ack = handler
+            .handle_l2_unicast_packet(
+                &Probe::request(MAC, offered_ip, local_ip).to_bytes(),
+                peer,
+                local_ip,
+            )
+            .await
+            .unwrap()
+            .expect("REQUEST should be acked");
+        assert!(
+            started.elapsed() < delay,
+            "ACK held for {:?}",
+            started.elapsed()
+        );
+        assert_eq!(decode_reply(&ack).opts().msg_type(), Some(MessageType::Ack));
+    }
+
+    /// Offers are `offered` lease rows, so one made before a restart still holds its
+    /// address and is honoured when the REQUEST reaches the restarted server.
+    #[tokio::test]
+    async fn test_pending_offer_survives_restart() {
+        use super::super::device_resolution::DirectorDeviceResolver;
+        use crate::boot_files::FilesystemBootFileProvider;
+
+        let (handler, _conn, _network_id, temp_dir) =
+            create_test_handler_with_store(test_connection_factory!()).await;
+        let local_ip: Ipv4Addr = "10.0.0.1".parse().unwrap();
+        let peer: SocketAddr = "0.0.0.0:68".parse().unwrap();
+
+        let offer = handler
+            .handle_l2_unicast_packet(&Probe::discover(MAC).to_bytes(), peer, local_ip)
+            .await
+            .unwrap()
+            .expect("DISCOVER should be offered");
+        let offered_ip = decode_reply(&offer).yiaddr();
+        drop(handler);
+
+        // A fresh handler over the same database, as after a restart
+        let boot_file_provider =
+            Arc::new(FilesystemBootFileProvider::new(temp_dir.path().join("boot_files")).unwrap());
+        let handler = DhcpHandler::new(
+            Arc::new(test_connection_factory!()),
+            Arc::new(DirectorDeviceResolver::new()),
+            BootConfigProvider::new(
+                "10.0.0.1".to_string(),
+                "http://10.0.0.1".to_string(),
+                boot_file_provider,
+            ),
+            local_ip,
+        );
+
+        let other = handler
+            .handle_l2_unicast_packet(
+                &Probe::discover([0x02, 0x00, 0x00, 0x00, 0x00, 0x01]).to_bytes(),
+                peer,
+                local_ip,
+            )
+            .await
+            .unwrap()
+            .expect("DISCOVER should be offered");
+        assert_ne!(decode_reply(&other).yiaddr(), offered_ip);
+
+        let ack = handler
+            .handle_l2_unicast_packet(
+                &Probe::request(MAC, offered_ip, local_ip).to_bytes(),
+                peer,
+                local_ip,
+            )
+            .await
+            .unwrap()
+            .expect("REQUEST should be answered");
+        let ack = decode_reply(&ack);
+        assert_eq!(ack.opts().msg_type(), Some(MessageType::Ack));
+        assert_eq!(ack.yiaddr(), offered_ip);
+    }
+
+    #[tokio::test]
+    async fn test_lapsed_offer_is_confirmed_only_if_still_free() {
+        let (handler, _conn, _network_id, _temp_dir) =
+            create_test_handler_with_store(test_connection_factory!()).await;
+        let lapsing = handler.clone().with_offer_ttl(0);
+        let local_ip: Ipv4Addr = "10.0.0.1".parse().unwrap();
+        let peer: SocketAddr = "0.0.0.0:68".parse().unwrap();
+        let exchange = |handler: &DhcpHandler, probe: Probe| {
+            let handler = handler.clone();
+            async move {
+                let reply = handler
+                    .handle_l2_unicast_packet(&probe.to_bytes(), peer, local_ip)
+                    .await
+                    .unwrap()
+                    .expect("packet should be answered");
+                decode_reply(&reply)
+            }
+        };
+
+        // Nobody took the address meanwhile: the late REQUEST still gets it
+        let first = exchange(&lapsing, Probe::discover(MAC)).await.yiaddr();
+        let ack = exchange(&handler, Probe::request(MAC, first, local_ip)).await;
+        assert_eq!(ack.opts().msg_type(), Some(MessageType::Ack));
+
+        // Offered to a second client after it lapsed: the late REQUEST is NAKed
+        let late_mac = [0x02, 0x00, 0x00, 0x00, 0x00, 0x02];
+        let lapsed = exchange(&lapsing, Probe::discover(late_mac)).await.yiaddr();
+        let other = exchange(
+            &handler,
+            Probe::discover([0x02, 0x00, 0x00, 0x00, 0x00, 0x03]),
+        )
+        .await;
+        assert_eq!(other.yiaddr(), lapsed, "a lapsed offer frees its address");
+        let nak = exchange(&handler, Probe::request(late_mac, lapsed, local_ip)).await;
+        assert_eq!(nak.opts().msg_type(), Some(MessageType::Nak));
+    }
+
+    #[tokio::test]
+    async fn test_allocation_rate_limit_throttles_new_macs_not_renewals() {
+        use crate::dhcp::rate_limit::{AllocationRateLimiter, DEFAULT_WINDOW};
+
+        let (handler, _conn, _network_id, _temp_dir) =
+            create_test_handler_with_store(test_connection_factory!()).await;
+        let handler = handler.with_allocation_rate_limit(AllocationRateLimiter::new(
+            Some(3),
+            None,
+            DEFAULT_WINDOW,
+        ));
+        let local_ip: Ipv4Addr = "10.0.0.1".parse().unwrap();
+        let peer: SocketAddr = "0.0.0.0:68".parse().unwrap();
+
+        // A client leases an address, using one slot
+        let offer = handler
+            .handle_l2_unicast_packet(&Probe::discover(MAC).to_bytes(), peer, local_ip)
+            .await
+            .unwrap()
+            .expect("DISCOVER should be offered");
+        let leased_ip = decode_reply(&offer).yiaddr();
+        handler
+            .handle_l2_unicast_packet(
+                &Probe::request(MAC, leased_ip, local_ip).to_bytes(),
+                peer,
+                local_ip,
+            )
+            .await
+            .unwrap()
+            .expect("REQUEST should be acked");
+
+        // A burst of spoofed MACs gets the two remaining slots, then is dropped
+        let mut offered = 0;
+        for i in 0..10u8 {
+            let reply = handler
+                .handle_l2_unicast_packet(
+                    &Probe::discover([0x02, 0x00, 0x00, 0x00, 0x00, i]).to_bytes(),
+                    peer,
+                    local_ip,
+                )
+                .await
+                .unwrap();
+            offered += usize::from(reply.is_some());
+        }
+        assert_eq!(offered, 2);
+        assert!(matches!(
+            &handler.recent().snapshot()[0].decision,
+            Decision::Ignored { reason } if reason.contains("rate limit")
+        ));
+
+        // The existing client still renews and rediscovers
+        let ack = handler
+            .handle_l2_unicast_packet(&Probe::renew(MAC, leased_ip).to_bytes(), peer, local_ip)
+            .await
+            .unwrap()
+            .expect("renewal should be answered");
+        assert_eq!(decode_reply(&ack).opts().msg_type(), Some(MessageType::Ack));
+        assert!(
+            handler
+                .handle_l2_unicast_packet(&Probe::discover(MAC).to_bytes(), peer, local_ip)
+                .await
+                .unwrap()
+                .is_some(),
+            "a known MAC is not counted against the limit"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_domain_search_sent_only_when_requested() {
+        let (handler, _conn, _network_id, _temp_dir) =
+            create_test_handler_with_store(test_connection_factory!()).await;
+        let search = DomainSearch::new(&["eng.example.com", "corp.example.com"]).unwrap();
+        let handler = handler.with_domain_search(Some(search.clone()));
+        let local_ip: Ipv4Addr = "10.0.0.1".parse().unwrap();
+        let peer: SocketAddr = "0.0.0.0:68".parse().unwrap();
+
+        // Option 119 as it appears in the reply: code, length, compressed payload
+        let mut encoded = vec![DomainSearch::CODE, search.encode().len() as u8];
+        encoded.extend(search.encode());
+        let carries_search = |reply: &DhcpReply| {
+            let (DhcpReply::L2 { data, .. } | DhcpReply::Relay { data, .. }) = reply;
+            data.windows(encoded.len()).any(|w| w == encoded.as_slice())
+        };
+
+        let requested = Probe::discover(MAC).option(v4::DhcpOption::ParameterRequestList(vec![
+            v4::OptionCode::SubnetMask,
+            v4::OptionCode::from(DomainSearch::CODE),
+        ]));
+        let offer = handler
+            .handle_l2_unicast_packet(&requested.to_bytes(), peer, local_ip)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(carries_search(&offer));
+
+        let offer = handler
+            .handle_l2_unicast_packet(&Probe::discover(MAC).to_bytes(), peer, local_ip)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(!carries_search(&offer));
+    }
+
+    #[tokio::test]
+    async fn test_pxe_vendor_options_sent_only_to_pxe_roms() {
+        let (handler, _conn, _network_id, _temp_dir) =
+            create_test_handler_with_store(test_connection_factory!()).await;
+        let pxe = PxeVendorOptions::default()
+            .with_discovery_control(PxeVendorOptions::USE_BOOTFILE)
+            .with_menu_prompt(3, "Booting rack-director")
+            .unwrap();
+        let handler = handler.with_pxe_vendor_options(Some(pxe.clone()));
+        let local_ip: Ipv4Addr = "10.0.0.1".parse().unwrap();
+        let peer: SocketAddr = "0.0.0.0:68".parse().unwrap();
+
+        // Option 43 as it appears in the reply: code, length, sub-options
+        let mut encoded = vec![PxeVendorOptions::CODE, pxe.encode().len() as u8];
+        encoded.extend(pxe.encode());
+        let offer_for = |probe: Probe| {
+            let handler = handler.clone();
+            async move {
+                let reply = handler
+                    .handle_l2_unicast_packet(&probe.to_bytes(), peer, local_ip)
+                    .await
+                    .unwrap()
+                    .unwrap();
+                let (DhcpReply::L2 { data, .. } | DhcpReply::Relay { data, .. }) = reply;
+                data
+            }
+        };
+        let carries_pxe =
+            |data: &[u8]| data.windows(encoded.len()).any(|w| w == encoded.as_slice());
+
+        let pxe_rom = Probe::discover(MAC).option(v4::DhcpOption::ClassIdentifier(
+            b"PXEClient:Arch:00000:UNDI:002001".to_vec(),
+        ));
+        assert!(carries_pxe(&offer_for(pxe_rom).await));
+
+        let ipxe = Probe::discover(MAC)
+            .option(v4::DhcpOption::ClassIdentifier(
+                b"PXEClient:Arch:00000:UNDI:002001".to_vec(),
+            ))
+            .option(v4::DhcpOption::UserClass(b"iPXE".to_vec()));
+        assert!(!carries_pxe(&offer_for(ipxe).await));
+
+        assert!(!carries_pxe(&offer_for(Probe::discover(MAC)).await));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_offer_delay_does_not_block_other_packets() {
+        let (handler, _conn, _network_id, _temp_dir) =
+            create_test_handler_with_store(test_connection_factory!()).await;
+        let handler = handler.with_offer_delay(Duration::from_secs(2));
+        let local_ip: Ipv4Addr = "10.0.0.1".parse().unwrap();
+        let peer: SocketAddr = "0.0.0.0:68".parse().unwrap();
+
+        // An OFFER held back on its own task...
+        let delayed = {
+            let handler = handler.clone();
+            tokio::spawn(async move {
+                handler
+                    .handle_l2_unicast_packet(&Probe::discover(MAC).to_bytes(), peer, local_ip)
+                    .await
+            })
+        };
+
+        // ...does not hold up a NAK for another client meanwhile
+        let started = tokio::time::Instant::now();
+        let nak = handler
+            .handle_l2_unicast_packet(
+                &Probe::init_reboot(
+                    [0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0x01],
+                    "192.168.1.50".parse().unwrap(),
+                )
+                .to_bytes(),
+                peer,
+                local_ip,
+            )
+            .await
+            .unwrap()
+            .expect("REQUEST for a foreign address should be NAKed");
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert_eq!(decode_reply(&nak).opts().msg_type(), Some(MessageType::Nak));
+        assert!(!delayed.is_finished(), "OFFER should still be held back");
+
+        assert!(delayed.await.unwrap().unwrap().is_some());
+    }
+
+    async fn rapid_commit_discover(
+        handler: &DhcpHandler,
+        conn: &mut Connection,
+        network_id: i64,
+    ) -> Message {
+        let mut discover = Probe::discover(MAC).build();
+        message_builder::add_rapid_commit(&mut discover);
+        let network = store::get_network(conn, network_id).await.unwrap();
+        handler
+            .handle_discover(conn, &discover, &network, handler.server_identifier)
+            .await
+            .unwrap()
+            .expect("DISCOVER should be answered")
+    }
+
+    #[tokio::test]
+    async fn test_rapid_commit_discover_is_acked_when_enabled() {
+        let (handler, mut conn, network_id, _temp_dir) =
+            create_test_handler_with_store(test_connection_factory!()).await;
+        let handler = handler.with_rapid_commit(true);
+
+        let reply = rapid_commit_discover(&handler, &mut conn, network_id).await;
+        assert_eq!(reply.opts().msg_type(), Some(MessageType::Ack));
+        assert!(message_builder::has_rapid_commit(&reply));
+
+        let lease = store::get_lease_by_mac(&conn, "aa:bb:cc:dd:ee:ff")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(lease.state, LeaseState::Active);
+        assert_eq!(lease.ip_address, reply.yiaddr().to_string());
+    }
+
+    #[tokio::test]
+    async fn test_rapid_commit_discover_is_offered_when_disabled() {
+        let (handler, mut conn, network_id, _temp_dir) =
+            create_test_handler_with_store(test_connection_factory!()).await;
+
+        let reply = rapid_commit_discover(&handler, &mut conn, network_id).await;
+        assert_eq!(reply.opts().msg_type(), Some(MessageType::Offer));
+        assert!(!message_builder::has_rapid_commit(&reply));
+
+        let lease = store::get_lease_by_mac(&conn, "aa:bb:cc:dd:ee:ff")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(lease.state, LeaseState::Offered);
+    }
 }
@@ -53,13 +53,21 @@ pub fn find_matching_l2_network(
 pub fn find_l2_network_for_ip(
     local_ip: Ipv4Addr,
     networks: &[DhcpNetwork],
+) -> anyhow::Result<Option<&DhcpNetwork>> {
+    find_network_containing(local_ip, networks)
+}
+
+/// Find the network whose subnet contains `ip`.
+pub fn find_network_containing(
+    ip: Ipv4Addr,
+    networks: &[DhcpNetwork],
 ) -> anyhow::Result<Option<&DhcpNetwork>> {
     for network in networks {
         let subnet: Ipv4Subnet = network
             .subnet
             .parse()
             .map_err(|e: common::Ipv4SubnetError| anyhow::anyhow!("{}", e))?;
-        if subnet.ip_in_range(local_ip) {
+        if subnet.ip_in_range(ip) {
             return Ok(Some(network));
         }
     }
@@ -118,6 +126,9 @@ mod tests {
             lease_duration: 3600,
             relay_agent_address: None,
             enable_autodiscovery: false,
+            enabled: true,
+            quarantine_network_id: None,
+            server_identifier: None,
             created_at: Utc::now(),
             updated_at: Utc::now(),
         }
@@ -1,9 +1,13 @@
-use anyhow::Result;
+use anyhow::{Result, bail};
 use common::Ipv4Subnet;
-use dhcproto::v4::{self, Message, MessageType, Opcode};
+use dhcproto::{
+    Encodable,
+    encoder::Encoder,
+    v4::{self, Message, MessageType, Opcode, OptionCode},
+};
 use std::net::Ipv4Addr;
 
-use super::store::DhcpNetwork;
+use super::store::{DhcpNetwork, format_mac};
 
 /// Creates a base DHCP reply message with common fields copied from the request.
 ///
@@ -49,11 +53,7 @@ pub fn add_network_options(msg: &mut Message, network: &DhcpNetwork) -> Result<(
     msg.opts_mut()
         .insert(v4::DhcpOption::Router(vec![network.gateway.parse()?]));
 
-    let dns_servers: Vec<Ipv4Addr> = network
-        .dns_servers
-        .iter()
-        .filter_map(|s| s.parse().ok())
-        .collect();
+    let dns_servers = network.dns_servers_v4();
     if !dns_servers.is_empty() {
         msg.opts_mut()
             .insert(v4::DhcpOption::DomainNameServer(dns_servers));
@@ -62,6 +62,107 @@ pub fn add_network_options(msg: &mut Message, network: &DhcpNetwork) -> Result<(
     Ok(())
 }
 
+/// Ratios of the lease time at which clients should renew (T1, option 58) and
+/// rebind (T2, option 59).
+///
+/// Defaults to RFC 2131's 50% and 87.5%, which clients assume anyway when the
+/// options are absent; sending them explicitly helps clients that do not.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LeaseTimers {
+    renewal_ratio: f64,
+    rebinding_ratio: f64,
+}
+
+impl Default for LeaseTimers {
+    fn default() -> Self {
+        Self {
+            renewal_ratio: 0.5,
+            rebinding_ratio: 0.875,
+        }
+    }
+}
+
+impl LeaseTimers {
+    /// Create timers from explicit ratios; requires `0 < renewal < rebinding < 1`.
+    pub fn new(renewal_ratio: f64, rebinding_ratio: f64) -> Result<Self> {
+        if !(0.0 < renewal_ratio && renewal_ratio < rebinding_ratio && rebinding_ratio < 1.0) {
+            bail!(
+                "lease timer ratios must satisfy 0 < renewal < rebinding < 1, got {} and {}",
+                renewal_ratio,
+                rebinding_ratio
+            );
+        }
+        Ok(Self {
+            renewal_ratio,
+            rebinding_ratio,
+        })
+    }
+
+    /// T1 and T2 in seconds for a lease of `lease_secs`, or `None` if the lease is
+    /// too short to give distinct values.
+    pub fn compute(&self, lease_secs: u32) -> Option<(u32, u32)> {
+        let t1 = (f64::from(lease_secs) * self.renewal_ratio) as u32;
+        let t2 = (f64::from(lease_secs) * self.rebinding_ratio) as u32;
+        (0 < t1 && t1 < t2 && t2 < lease_secs).then_some((t1, t2))
+    }
+}
+
+/// Default shortest lease granted to a client that asks for one (option 51).
+pub const DEFAULT_MIN_LEASE_SECS: u32 = 60;
+
+/// The lease time to grant a client that requested `requested` seconds (option 51).
+///
+/// RFC 2131 lets the server grant up to, but not beyond, its configured maximum,
+/// here the network's lease duration `max`. Requests are clamped to
+/// `[min, max]`; without a request the client gets `max`. A floor above `max`
+/// yields `max`.
+pub fn grant_lease_time(requested: Option<u32>, min: u32, max: u32) -> u32 {
+    requested.map_or(max, |secs| secs.clamp(min.min(max), max))
+}
+
+/// Adds the lease time options to a DHCP message.
+///
+/// This function adds the following DHCP options:
+/// - Option 51: IP Address Lease Time
+/// - Option 58: Renewal (T1) Time
+/// - Option 59: Rebinding (T2) Time
+///
+/// Options 58 and 59 are omitted for leases too short to yield `T1 < T2 < lease`.
+pub fn add_lease_time_options(msg: &mut Message, lease_secs: u32, timers: &LeaseTimers) {
+    msg.opts_mut()
+        .insert(v4::DhcpOption::AddressLeaseTime(lease_secs));
+    if let Some((t1, t2)) = timers.compute(lease_secs) {
+        msg.opts_mut().insert(v4::DhcpOption::Renewal(t1));
+        msg.opts_mut().insert(v4::DhcpOption::Rebinding(t2));
+    }
+}
+
+/// Size of the BOOTP `file` header field in bytes.
+pub const BOOTP_FILE_LEN: usize = 128;
+
+/// Writes `filename` into the BOOTP `file` header field.
+///
+/// The field is a fixed 128-byte, null-terminated string, so `filename` may be at
+/// most 127 bytes; the rest of the field is zero-padded on encode. Longer names are
+/// rejected rather than truncated, since a truncated path would point the client at
+/// the wrong file.
+///
+/// # Returns
+/// * `Ok(())` - The field was set
+/// * `Err(_)` - `filename` does not fit in the field; the message is unchanged
+pub fn set_boot_file(msg: &mut Message, filename: &str) -> Result<()> {
+    if filename.len() >= BOOTP_FILE_LEN {
+        bail!(
+            "boot filename is {} bytes, BOOTP file field holds at most {}: {}",
+            filename.len(),
+            BOOTP_FILE_LEN - 1,
+            filename
+        );
+    }
+    msg.set_fname(filename.as_bytes());
+    Ok(())
+}
+
 /// Builds a DHCP NAK message.
 ///
 /// A NAK (Negative Acknowledgement) is sent when the server cannot fulfill a
@@ -90,6 +191,138 @@ pub fn build_nak(req: &Message, server_identifier: Ipv4Addr) -> Message {
     msg
 }
 
+/// Rapid Commit option (RFC 4039): in a DISCOVER it asks for an ACK straight away,
+/// in the ACK it confirms the lease was committed. It has no payload.
+pub const RAPID_COMMIT: u8 = 80;
+
+/// Whether `req` carries the Rapid Commit option.
+pub fn has_rapid_commit(req: &Message) -> bool {
+    req.opts().get(OptionCode::from(RAPID_COMMIT)).is_some()
+}
+
+/// Add an empty Rapid Commit option to `msg`.
+pub fn add_rapid_commit(msg: &mut Message) {
+    msg.opts_mut()
+        .insert(v4::DhcpOption::Unknown(v4::UnknownOption::new(
+            OptionCode::from(RAPID_COMMIT),
+            Vec::new(),
+        )));
+}
+
+/// Largest DHCP message every client accepts, IP and UDP headers included
+/// (RFC 2131 §2); clients announce more with option 57.
+pub const MIN_MESSAGE_SIZE: u16 = 576;
+
+/// Default ceiling on reply size, IP and UDP headers included: one Ethernet frame.
+pub const DEFAULT_MAX_MESSAGE_SIZE: u16 = 1500;
+
+const IP_UDP_HEADER_LEN: usize = 28;
+/// The fixed BOOTP header plus the magic cookie; options start here.
+const OPTIONS_OFFSET: usize = 240;
+const END_OPTION: u8 = 255;
+
+/// Options kept however large the reply gets. Relay agent information (82) must be
+/// echoed for the relay to forward the reply at all, and a rapid-commit ACK without
+/// option 80 would be discarded by the client.
+const ESSENTIAL_OPTIONS: [u8; 9] = [53, 54, 51, 58, 59, 1, 3, 82, 80];
+
+/// Options kept next, in this order, while they fit: boot options first, then name
+/// service. Any others are kept by option code.
+const PREFERRED_OPTIONS: [u8; 11] = [66, 67, 43, 60, 97, 6, 15, 12, 13, 150, 119];
+
+/// Largest reply to `req` in bytes, counting from the BOOTP header: the client's
+/// option 57 (at least [`MIN_MESSAGE_SIZE`]) capped at `ceiling`, less the IP and
+/// UDP headers.
+pub fn reply_size_limit(req: &Message, ceiling: u16) -> usize {
+    let client_max = match req.opts().get(OptionCode::MaxMessageSize) {
+        Some(v4::DhcpOption::MaxMessageSize(size)) => *size,
+        _ => MIN_MESSAGE_SIZE,
+    };
+    let size = client_max
+        .max(MIN_MESSAGE_SIZE)
+        .min(ceiling.max(MIN_MESSAGE_SIZE));
+    size as usize - IP_UDP_HEADER_LEN
+}
+
+/// Rank of option `code` when deciding what fits: lower ranks are kept first.
+fn keep_priority(code: u8) -> (usize, u8) {
+    ESSENTIAL_OPTIONS
+        .iter()
+        .chain(PREFERRED_OPTIONS.iter())
+        .position(|&c| c == code)
+        .map_or((usize::MAX, code), |i| (i, 0))
+}
+
+/// Position of option `code` on the wire: message type first, then by code.
+fn wire_order(code: u8) -> (bool, u8) {
+    (code != u8::from(OptionCode::MessageType), code)
+}
+
+/// Encode `msg` in at most `limit` bytes.
+///
+/// Options are kept in priority order: [`ESSENTIAL_OPTIONS`] always, then
+/// [`PREFERRED_OPTIONS`] and the rest by code while they fit. Those that would take
+/// the reply over `limit` are left out with a warning, so an oversized reply loses
+/// its least important options rather than being fragmented or dropped. The kept
+/// options are written message type (53) first, then by ascending code, then End,
+/// so the same message always encodes to the same bytes.
+pub fn encode_reply(msg: &Message, limit: usize) -> Result<Vec<u8>> {
+    let mut header = msg.clone();
+    *header.opts_mut() = v4::DhcpOptions::default();
+    let mut buf = Vec::new();
+    header.encode(&mut Encoder::new(&mut buf))?;
+    buf.truncate(OPTIONS_OFFSET);
+
+    let mut options = Vec::new();
+    for (code, opt) in msg.opts().iter() {
+        let code = u8::from(*code);
+        if !matches!(code, 0 | END_OPTION) {
+            let mut encoded = Vec::new();
+            opt.encode(&mut Encoder::new(&mut encoded))?;
+            options.push((code, encoded));
+        }
+    }
+    options.sort_by_key(|(code, _)| keep_priority(*code));
+
+    // Leave room for the end option
+    let mut size = buf.len() + 1;
+    let mut kept = Vec::with_capacity(options.len());
+    for (code, encoded) in options {
+        if size + encoded.len() > limit && !ESSENTIAL_OPTIONS.contains(&code) {
+            log::warn!(
+                "Dropping option {} ({} bytes) from reply to {}: it would exceed {} bytes",
+                code,
+                encoded.len(),
+                format_mac(msg.chaddr()),
+                limit
+            );
+            continue;
+        }
+        size += encoded.len();
+        kept.push((code, encoded));
+    }
+    kept.sort_by_key(|(code, _)| wire_order(*code));
+    for (_, encoded) in kept {
+        buf.extend(encoded);
+    }
+    buf.push(END_OPTION);
+
+    if buf.len() > limit {
+        log::warn!(
+            "Reply to {} is {} bytes, over the {}-byte limit, with essential options alone",
+            format_mac(msg.chaddr()),
+            buf.len(),
+            limit
+        );
+    }
+    Ok(buf)
+}
+
+/// Encode `msg` with no size limit, options in the same order as [`encode_reply`].
+pub fn encode_message(msg: &Message) -> Result<Vec<u8>> {
+    encode_reply(msg, usize::MAX)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -125,6 +358,9 @@ mod tests {
             lease_duration: 3600,
             relay_agent_address: None,
             enable_autodiscovery: false,
+            enabled: true,
+            quarantine_network_id: None,
+            server_identifier: None,
             created_at: Utc::now(),
             updated_at: Utc::now(),
         };
@@ -191,6 +427,9 @@ mod tests {
             lease_duration: 3600,
             relay_agent_address: None,
             enable_autodiscovery: false,
+            enabled: true,
+            quarantine_network_id: None,
+            server_identifier: None,
             created_at: Utc::now(),
             updated_at: Utc::now(),
         };
@@ -206,6 +445,49 @@ mod tests {
         assert!(dns.is_none());
     }
 
+    #[test]
+    fn test_add_network_options_mixed_dns_sends_only_v4() {
+        use chrono::Utc;
+
+        let mut network = DhcpNetwork {
+            id: 1,
+            name: "dual-stack".to_string(),
+            subnet: "10.0.0.0/24".to_string(),
+            gateway: "10.0.0.1".to_string(),
+            dns_servers: vec![
+                "2001:db8::53".to_string(),
+                "10.0.0.53".to_string(),
+                "fd00::53".to_string(),
+            ],
+            lease_duration: 3600,
+            relay_agent_address: None,
+            enable_autodiscovery: false,
+            enabled: true,
+            quarantine_network_id: None,
+            server_identifier: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        };
+
+        let mut msg = Message::default();
+        add_network_options(&mut msg, &network).unwrap();
+        let dns = msg.opts().iter().find_map(|(_, opt)| match opt {
+            v4::DhcpOption::DomainNameServer(servers) => Some(servers.clone()),
+            _ => None,
+        });
+        assert_eq!(dns, Some(vec![Ipv4Addr::new(10, 0, 0, 53)]));
+
+        // With only IPv6 servers there is nothing to put in option 6.
+        network.dns_servers.retain(|s| s.contains(':'));
+        let mut msg = Message::default();
+        add_network_options(&mut msg, &network).unwrap();
+        let dns = msg
+            .opts()
+            .iter()
+            .find(|(_, opt)| matches!(opt, v4::DhcpOption::DomainNameServer(_)));
+        assert!(dns.is_none());
+    }
+
     #[test]
     fn test_build_nak() {
         use dhcproto::v4::Flags;
@@ -252,4 +534,186 @@ mod tests {
             .expect("ServerIdentifier should be present");
         assert_eq!(server_identifier, server_id);
     }
+
+    #[test]
+    fn test_set_boot_file() {
+        let mut msg = Message::default();
+        set_boot_file(&mut msg, "snponly.efi").unwrap();
+        assert_eq!(msg.fname(), Some(b"snponly.efi".as_slice()));
+
+        // Encoded header carries the name null-padded to 128 bytes
+        use dhcproto::{Encodable, Encoder};
+        let mut buf = Vec::new();
+        msg.encode(&mut Encoder::new(&mut buf)).unwrap();
+        // op..chaddr (44) + sname (64) precede the file field
+        let file = &buf[108..108 + BOOTP_FILE_LEN];
+        assert_eq!(&file[..11], b"snponly.efi");
+        assert!(file[11..].iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn test_lease_time_options_order() {
+        use dhcproto::{Decodable, Decoder, Encodable, Encoder};
+
+        let mut msg = Message::default();
+        add_lease_time_options(&mut msg, 3600, &LeaseTimers::default());
+
+        let mut buf = Vec::new();
+        msg.encode(&mut Encoder::new(&mut buf)).unwrap();
+        let decoded = Message::decode(&mut Decoder::new(&buf)).unwrap();
+
+        let Some(v4::DhcpOption::AddressLeaseTime(lease)) =
+            decoded.opts().get(v4::OptionCode::AddressLeaseTime)
+        else {
+            panic!("missing option 51");
+        };
+        let Some(v4::DhcpOption::Renewal(t1)) = decoded.opts().get(v4::OptionCode::Renewal) else {
+            panic!("missing option 58");
+        };
+        let Some(v4::DhcpOption::Rebinding(t2)) = decoded.opts().get(v4::OptionCode::Rebinding)
+        else {
+            panic!("missing option 59");
+        };
+        assert!(t1 < t2 && t2 < lease, "T1={t1} T2={t2} lease={lease}");
+        assert_eq!((*t1, *t2), (1800, 3150));
+    }
+
+    #[test]
+    fn test_grant_lease_time_clamps_request() {
+        // Below the floor
+        assert_eq!(grant_lease_time(Some(10), 60, 3600), 60);
+        // Within the range
+        assert_eq!(grant_lease_time(Some(600), 60, 3600), 600);
+        // Above the network maximum
+        assert_eq!(grant_lease_time(Some(86400), 60, 3600), 3600);
+        // No request, or a floor above the maximum
+        assert_eq!(grant_lease_time(None, 60, 3600), 3600);
+        assert_eq!(grant_lease_time(Some(10), 600, 300), 300);
+    }
+
+    #[test]
+    fn test_lease_timers_custom_ratios() {
+        let timers = LeaseTimers::new(0.25, 0.5).unwrap();
+        assert_eq!(timers.compute(86400), Some((21600, 43200)));
+        // Too short to produce distinct values
+        assert_eq!(timers.compute(2), None);
+
+        assert!(LeaseTimers::new(0.9, 0.5).is_err());
+        assert!(LeaseTimers::new(0.5, 1.0).is_err());
+        assert!(LeaseTimers::new(0.0, 0.5).is_err());
+    }
+
+    #[test]
+    fn test_set_boot_file_rejects_oversized_name() {
+        let mut msg = Message::default();
+        let long = format!("http://10.0.0.1:3000/cnc/boot/{}", "a".repeat(120));
+        assert!(long.len() > BOOTP_FILE_LEN);
+
+        assert!(set_boot_file(&mut msg, &long).is_err());
+        assert!(msg.fname().is_none_or(|f| f.is_empty()));
+    }
+
+    #[test]
+    fn test_reply_size_limit_follows_option_57() {
+        let mut req = Message::default();
+        assert_eq!(reply_size_limit(&req, DEFAULT_MAX_MESSAGE_SIZE), 548);
+
+        req.opts_mut().insert(v4::DhcpOption::MaxMessageSize(1024));
+        assert_eq!(reply_size_limit(&req, DEFAULT_MAX_MESSAGE_SIZE), 996);
+        // Capped by the ceiling, and never below the RFC minimum
+        assert_eq!(reply_size_limit(&req, 800), 772);
+        req.opts_mut().insert(v4::DhcpOption::MaxMessageSize(300));
+        assert_eq!(reply_size_limit(&req, DEFAULT_MAX_MESSAGE_SIZE), 548);
+    }
+
+    #[test]
+    fn test_encode_reply_drops_low_priority_options_over_budget() {
+        use dhcproto::{Decodable, Decoder, v4::UnknownOption};
+
+        let mut reply = create_base_reply(&Message::default(), &Ipv4Addr::new(10, 0, 0, 1));
+        let opts = reply.opts_mut();
+        opts.insert(v4::DhcpOption::MessageType(MessageType::Offer));
+        opts.insert(v4::DhcpOption::ServerIdentifier(Ipv4Addr::new(10, 0, 0, 1)));
+        opts.insert(v4::DhcpOption::AddressLeaseTime(3600));
+        opts.insert(v4::DhcpOption::SubnetMask(Ipv4Addr::new(255, 255, 255, 0)));
+        opts.insert(v4::DhcpOption::Router(vec![Ipv4Addr::new(10, 0, 0, 1)]));
+        opts.insert(v4::DhcpOption::DomainNameServer(vec![
+            Ipv4Addr::new(
+                10, 0, 0, 53
+            );
+            10
+        ]));
+        for code in [224, 225] {
+            opts.insert(v4::DhcpOption::Unknown(UnknownOption::new(
+                OptionCode::from(code),
+                vec![0xab; 200],
+            )));
+        }
+
+        let limit = 548;
+        let encoded = encode_reply(&reply, limit).unwrap();
+        assert!(encoded.len() <= limit, "{} bytes", encoded.len());
+        // Message type leads
+        assert_eq!(&encoded[OPTIONS_OFFSET..OPTIONS_OFFSET + 3], &[53, 1, 2]);
+
+        let decoded = Message::decode(&mut Decoder::new(&encoded)).unwrap();
+        for code in [53, 54, 51, 1, 3, 6, 224] {
+            assert!(
+                decoded.opts().get(OptionCode::from(code)).is_some(),
+                "option {code} should be kept"
+            );
+        }
+        assert!(decoded.opts().get(OptionCode::from(225)).is_none());
+
+        // With room for everything, nothing is dropped
+        let encoded = encode_reply(&reply, 1472).unwrap();
+        let decoded = Message::decode(&mut Decoder::new(&encoded)).unwrap();
+        assert!(decoded.opts().get(OptionCode::from(225)).is_some());
+    }
+
+    #[test]
+    fn test_encode_message_is_deterministic() {
+        let mut msg = create_base_reply(&Message::default(), &Ipv4Addr::new(10, 0, 0, 1));
+        let opts = msg.opts_mut();
+        opts.insert(v4::DhcpOption::Hostname("node-1".to_string()));
+        opts.insert(v4::DhcpOption::Router(vec![Ipv4Addr::new(10, 0, 0, 1)]));
+        opts.insert(v4::DhcpOption::ServerIdentifier(Ipv4Addr::new(10, 0, 0, 1)));
+        opts.insert(v4::DhcpOption::AddressLeaseTime(3600));
+        opts.insert(v4::DhcpOption::SubnetMask(Ipv4Addr::new(255, 255, 255, 0)));
+        opts.insert(v4::DhcpOption::MessageType(MessageType::Ack));
+
+        let first = encode_message(&msg).unwrap();
+        assert_eq!(first, encode_message(&msg.clone()).unwrap());
+
+        // Walk the options: 53 first, then ascending, then End
+        let mut codes = Vec::new();
+        let mut i = OPTIONS_OFFSET;
+        while first[i] != END_OPTION {
+            codes.push(first[i]);
+            i += 2 + first[i + 1] as usize;
+        }
+        assert_eq!(i, first.len() - 1);
+        assert_eq!(codes, vec![53, 1, 3, 12, 51, 54]);
+    }
+
+    #[test]
+    fn test_rapid_commit_round_trip() {
+        use dhcproto::{Decodable, Decoder};
+
+        let mut msg = create_base_reply(&Message::default(), &Ipv4Addr::new(10, 0, 0, 1));
+        msg.opts_mut()
+            .insert(v4::DhcpOption::MessageType(MessageType::Ack));
+        assert!(!has_rapid_commit(&msg));
+
+        add_rapid_commit(&mut msg);
+        let encoded = encode_message(&msg).unwrap();
+        // Zero-length option right before End
+        assert_eq!(
+            &encoded[encoded.len() - 3..],
+            &[RAPID_COMMIT, 0, END_OPTION]
+        );
+
+        let decoded = Message::decode(&mut Decoder::new(&encoded)).unwrap();
+        assert!(has_rapid_commit(&decoded));
+    }
 }
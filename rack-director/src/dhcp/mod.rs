@@ -1,13 +1,25 @@
 mod allocator;
 mod boot_config;
-mod device_resolution;
+mod bootp;
+#[cfg(test)]
+pub(crate) mod client;
+pub mod decode;
+pub(crate) mod device_resolution;
+pub mod display;
 mod handler;
 mod interface;
 mod ip_discovery;
 pub mod message_builder;
+mod options;
+pub mod oui;
+pub mod parse;
+pub mod rate_limit;
+pub mod recent;
 mod request;
+pub mod seed;
 pub mod socket_manager;
 pub mod store;
+pub mod subnet_config;
 
 use anyhow::Result;
 use std::collections::HashMap;
@@ -18,7 +30,10 @@ use tokio_recvmsg::UdpSocketRecvMsg;
 
 use crate::database::ConnectionFactory;
 
+pub use allocator::{AllocationStrategy, PoolUtilization, network_utilization};
+pub use boot_config::UserClassBootFile;
 pub use ip_discovery::discover_server_identifier;
+pub use options::{DomainSearch, PxeVendorOptions};
 pub use socket_manager::SocketCmd;
 #[allow(unused_imports)] // re-exported for `crate::dhcp::LeaseState` usage in other modules
 pub use store::{DhcpNetwork, DhcpPool, Lease, LeaseState, StaticReservation};
@@ -30,6 +45,9 @@ use socket_manager::{DhcpSocketManager, SocketTable};
 
 pub struct DhcpServer {
     handler: DhcpHandler,
+    /// The resolver `handler` uses, kept so later options adjust it rather than
+    /// replacing it.
+    device_resolver: DirectorDeviceResolver,
     address: SocketAddr,
     conn: Arc<dyn ConnectionFactory>,
     server_identifier: Ipv4Addr,
@@ -42,10 +60,12 @@ pub struct StartResult {
 }
 
 /// A cheaply-clonable handle for notifying the DHCP socket manager of
-/// network lifecycle events. Obtained from `StartResult::control`.
+/// network lifecycle events and reading the recent-packet log. Obtained from
+/// `StartResult::control`.
 #[derive(Clone)]
 pub struct DhcpControl {
     cmd_tx: mpsc::Sender<SocketCmd>,
+    recent: recent::RecentPackets,
 }
 
 impl DhcpControl {
@@ -58,7 +78,15 @@ impl DhcpControl {
         // Capacity 1; the receiver is immediately dropped so the manager never
         // runs. Commands sent via DhcpControl are silently discarded.
         let (cmd_tx, _cmd_rx) = mpsc::channel(1);
-        Self { cmd_tx }
+        Self {
+            cmd_tx,
+            recent: recent::RecentPackets::default(),
+        }
+    }
+
+    /// Packets the server received recently and what it decided for each.
+    pub fn recent(&self) -> &recent::RecentPackets {
+        &self.recent
     }
 
     /// Notify the socket manager that a new DHCP network was created.
@@ -106,6 +134,7 @@ impl DhcpServer {
         boot_file_provider: Arc<dyn crate::boot_files::BootFileProvider>,
         server_identifier: Ipv4Addr,
         address: Option<SocketAddr>,
+        always_send_option_150: bool,
     ) -> Result<Self> {
         log::info!("DHCP server configuration:");
         log::info!("  TFTP Server: {}", tftp_server);
@@ -125,23 +154,125 @@ impl DhcpServer {
             );
         }
 
-        let boot_config = BootConfigProvider::new(tftp_server, http_server, boot_file_provider);
-        let device_resolver = Arc::new(DirectorDeviceResolver::new());
+        let boot_config = BootConfigProvider::new(tftp_server, http_server, boot_file_provider)
+            .with_always_send_tftp_server_address(always_send_option_150);
+        let device_resolver = DirectorDeviceResolver::new();
         let handler = DhcpHandler::new(
             conn.clone(),
-            device_resolver,
+            Arc::new(device_resolver.clone()),
             boot_config,
             server_identifier,
         );
 
         Ok(Self {
             handler,
+            device_resolver,
             address: address.unwrap_or_else(|| SocketAddr::new(server_identifier.into(), 67)),
             conn,
             server_identifier,
         })
     }
 
+    /// Advertise renewal (T1) and rebinding (T2) times using `timers`.
+    pub fn with_lease_timers(mut self, timers: message_builder::LeaseTimers) -> Self {
+        self.handler = self.handler.with_lease_timers(timers);
+        self
+    }
+
+    /// Never grant a client-requested lease shorter than `secs`.
+    pub fn with_min_lease_time(mut self, secs: u32) -> Self {
+        self.handler = self.handler.with_min_lease_time(secs);
+        self
+    }
+
+    /// Ignore clients that would add more than `max` interfaces to one device by
+    /// reporting its GUID.
+    pub fn with_max_interfaces_per_device(mut self, max: usize) -> Self {
+        self.device_resolver = self.device_resolver.with_max_interfaces(max);
+        self.handler = self
+            .handler
+            .with_device_resolver(Arc::new(self.device_resolver.clone()));
+        self
+    }
+
+    /// Only answer clients whose MAC vendor prefix `filter` permits.
+    pub fn with_oui_filter(mut self, filter: oui::OuiFilter) -> Self {
+        self.handler = self.handler.with_oui_filter(filter);
+        self
+    }
+
+    /// NAK (`true`, the default) or ignore (`false`) REQUESTs for addresses outside
+    /// our scope. Turn off when sharing a segment with another DHCP server.
+    pub fn with_authoritative(mut self, authoritative: bool) -> Self {
+        self.handler = self.handler.with_authoritative(authoritative);
+        self
+    }
+
+    /// Wait `delay` before sending each OFFER, letting another DHCP server on the
+    /// segment answer first. ACKs are sent immediately.
+    pub fn with_offer_delay(mut self, delay: std::time::Duration) -> Self {
+        self.handler = self.handler.with_offer_delay(delay);
+        self
+    }
+
+    /// Hold each offered address for `secs` waiting for the client's REQUEST.
+    pub fn with_offer_ttl(mut self, secs: u32) -> Self {
+        self.handler = self.handler.with_offer_ttl(secs);
+        self
+    }
+
+    /// Cap how many clients the server has never seen are offered addresses per
+    /// window. Renewals and known clients are not limited.
+    pub fn with_allocation_rate_limit(
+        mut self,
+        limiter: rate_limit::AllocationRateLimiter,
+    ) -> Self {
+        self.handler = self.handler.with_allocation_rate_limit(limiter);
+        self
+    }
+
+    /// Send `search` as the domain search list (option 119) to clients requesting it.
+    pub fn with_domain_search(mut self, search: Option<DomainSearch>) -> Self {
+        self.handler = self.handler.with_domain_search(search);
+        self
+    }
+
+    /// Send PXE vendor sub-options (option 43) to PXE ROMs.
+    pub fn with_pxe_vendor_options(mut self, options: Option<PxeVendorOptions>) -> Self {
+        self.handler = self.handler.with_pxe_vendor_options(options);
+        self
+    }
+
+    /// Serve a specific boot file to clients that send a given user class (option 77).
+    pub fn with_user_class_boot_files(mut self, boot_files: Vec<UserClassBootFile>) -> Self {
+        self.handler = self.handler.with_user_class_boot_files(boot_files);
+        self
+    }
+
+    /// Answer legacy BOOTP clients that have a static reservation.
+    pub fn with_legacy_bootp(mut self, enabled: bool) -> Self {
+        self.handler = self.handler.with_legacy_bootp(enabled);
+        self
+    }
+
+    /// Never send replies larger than `size` bytes, IP and UDP headers included.
+    pub fn with_max_message_size(mut self, size: u16) -> Self {
+        self.handler = self.handler.with_max_message_size(size);
+        self
+    }
+
+    /// Answer DISCOVERs carrying Rapid Commit with a committing ACK.
+    pub fn with_rapid_commit(mut self, enabled: bool) -> Self {
+        self.handler = self.handler.with_rapid_commit(enabled);
+        self
+    }
+
+    /// Pick new addresses from a pool by `strategy`.
+    pub fn with_allocation_strategy(mut self, strategy: AllocationStrategy) -> Self {
+        self.handler = self.handler.with_allocation_strategy(strategy);
+        self
+    }
+
     /// Start the DHCP server.
     ///
     /// When `no_broadcast` is `true` (used in tests), no wildcard socket is
@@ -185,7 +316,10 @@ impl DhcpServer {
         Ok(StartResult {
             join_handle,
             port,
-            control: DhcpControl { cmd_tx },
+            control: DhcpControl {
+                cmd_tx,
+                recent: self.handler.recent().clone(),
+            },
         })
     }
 }
@@ -384,17 +518,37 @@ pub(crate) async fn make_server_id_socket(
     Ok(udp)
 }
 
-/// Spawn a background task that periodically deletes expired DHCP leases.
-pub fn spawn_lease_cleanup_task(connection_factory: Arc<dyn ConnectionFactory>) -> JoinHandle<()> {
+/// Spawn a background task that periodically deletes DHCP leases that ended more
+/// than `retention` ago, and offers whose REQUEST never came.
+///
+/// Offers made before a restart are still `offered` rows, so they keep holding
+/// their addresses until they lapse; the first pass runs at startup.
+pub fn spawn_lease_cleanup_task(
+    connection_factory: Arc<dyn ConnectionFactory>,
+    retention: chrono::Duration,
+) -> JoinHandle<()> {
     tokio::spawn(async move {
         let conn = connection_factory
             .open()
             .await
             .expect("Failed to open database");
+        match store::list_pending_offers(&conn).await {
+            Ok(offers) if !offers.is_empty() => log::info!(
+                "Holding {} DHCP offer(s) made before restart until they are requested or lapse",
+                offers.len()
+            ),
+            Ok(_) => {}
+            Err(e) => log::error!("Failed to load pending DHCP offers: {}", e),
+        }
         let mut interval = tokio::time::interval(std::time::Duration::from_secs(60));
         loop {
             interval.tick().await;
-            match store::delete_expired_leases(&conn).await {
+            match store::delete_expired_offers(&conn).await {
+                Ok(count) if count > 0 => log::debug!("Dropped {} lapsed DHCP offer(s)", count),
+                Ok(_) => {}
+                Err(e) => log::error!("Failed to drop lapsed DHCP offers: {}", e),
+            }
+            match store::delete_expired_leases(&conn, retention).await {
                 Ok(count) if count > 0 => log::info!("Cleaned up {} expired DHCP lease(s)", count),
                 Ok(_) => {}
                 Err(e) => log::error!("Failed to clean up expired DHCP leases: {}", e),
@@ -433,6 +587,7 @@ mod tests {
             boot_file_provider,
             server_identifier,
             Some(SocketAddr::new(Ipv4Addr::new(0, 0, 0, 0).into(), 67)),
+            false,
         )
         .await
         .unwrap();
@@ -0,0 +1,676 @@
+//! Typed encodings for DHCP options that `dhcproto` does not model.
+//!
+//! `dhcproto` carries these as `DhcpOption::Unknown` (or, for option 77, an opaque
+//! byte string) with raw bytes; the types here convert to and from that
+//! representation so the rest of the server never touches the wire format directly.
+
+use anyhow::{Result, bail};
+use dhcproto::v4::{DhcpOption, OptionCode, UnknownOption};
+use std::collections::HashMap;
+use std::net::Ipv4Addr;
+
+/// Option 150: TFTP server address list (Cisco).
+///
+/// Used by some network gear and PXE stacks instead of, or alongside, option 66.
+/// The payload is a list of IPv4 addresses, four bytes each.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TftpServerAddress(pub Vec<Ipv4Addr>);
+
+impl TftpServerAddress {
+    pub const CODE: u8 = 150;
+
+    /// Whether `code` is option 150.
+    pub fn matches(code: &OptionCode) -> bool {
+        u8::from(*code) == Self::CODE
+    }
+
+    /// Encode as a raw DHCP option.
+    pub fn to_option(&self) -> DhcpOption {
+        let data = self.0.iter().flat_map(|ip| ip.octets()).collect();
+        DhcpOption::Unknown(UnknownOption::new(OptionCode::from(Self::CODE), data))
+    }
+
+    /// Decode from a raw DHCP option, returning `None` for other options or a
+    /// payload that is not a whole number of addresses.
+    pub fn from_option(opt: &DhcpOption) -> Option<Self> {
+        let DhcpOption::Unknown(unknown) = opt else {
+            return None;
+        };
+        let data = unknown.data();
+        if !Self::matches(&unknown.code()) || data.is_empty() || data.len() % 4 != 0 {
+            return None;
+        }
+        Some(Self(
+            data.chunks_exact(4)
+                .map(|b| Ipv4Addr::new(b[0], b[1], b[2], b[3]))
+                .collect(),
+        ))
+    }
+}
+
+/// Option 77: User Class Information (RFC 3004).
+///
+/// The payload is one or more class names, each prefixed with its length. iPXE
+/// predates the RFC and sends a single bare string instead, so a payload that is not
+/// a valid length-prefixed list is read as one class.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UserClass(pub Vec<String>);
+
+impl UserClass {
+    pub const CODE: u8 = 77;
+
+    /// Whether `class` is one of the client's user classes.
+    pub fn contains(&self, class: &str) -> bool {
+        self.0.iter().any(|c| c == class)
+    }
+
+    /// Encode as a DHCP option in the RFC 3004 length-prefixed form.
+    ///
+    /// Class names longer than 255 bytes cannot be encoded and are truncated.
+    pub fn to_option(&self) -> DhcpOption {
+        let mut data = Vec::new();
+        for class in &self.0 {
+            let bytes = &class.as_bytes()[..class.len().min(u8::MAX as usize)];
+            data.push(bytes.len() as u8);
+            data.extend_from_slice(bytes);
+        }
+        DhcpOption::UserClass(data)
+    }
+
+    /// Decode from a DHCP option, returning `None` for other options or an empty
+    /// payload.
+    pub fn from_option(opt: &DhcpOption) -> Option<Self> {
+        let DhcpOption::UserClass(data) = opt else {
+            return None;
+        };
+        Self::parse(data)
+    }
+
+    /// Parse an option 77 payload.
+    fn parse(data: &[u8]) -> Option<Self> {
+        if data.is_empty() {
+            return None;
+        }
+        let classes = Self::parse_length_prefixed(data)
+            .unwrap_or_else(|| vec![String::from_utf8_lossy(data).into_owned()]);
+        Some(Self(classes))
+    }
+
+    /// Split an RFC 3004 payload into its class names, or `None` if it is not one
+    /// (an entry is empty or runs past the end of the payload).
+    fn parse_length_prefixed(data: &[u8]) -> Option<Vec<String>> {
+        let mut classes = Vec::new();
+        let mut rest = data;
+        while let Some((&len, tail)) = rest.split_first() {
+            let (class, next) = tail.split_at_checked(len as usize)?;
+            if class.is_empty() {
+                return None;
+            }
+            classes.push(String::from_utf8_lossy(class).into_owned());
+            rest = next;
+        }
+        Some(classes)
+    }
+}
+
+/// Option 119: Domain Search List (RFC 3397).
+///
+/// The payload is the search domains in DNS wire format, concatenated, with each
+/// suffix already written replaced by a pointer to it (RFC 1035 message
+/// compression, offsets counted from the start of the payload). Clients that
+/// implement the RFC misparse a list whose shared suffixes are repeated instead.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DomainSearch(Vec<String>);
+
+/// Longest DNS label, in bytes.
+const MAX_LABEL_LEN: usize = 63;
+/// Longest DNS name in wire format, in bytes.
+const MAX_NAME_LEN: usize = 255;
+/// Largest offset a compression pointer can hold.
+const MAX_POINTER_OFFSET: usize = 0x3fff;
+
+impl DomainSearch {
+    pub const CODE: u8 = 119;
+
+    /// Whether `code` is option 119.
+    pub fn matches(code: &OptionCode) -> bool {
+        u8::from(*code) == Self::CODE
+    }
+
+    /// A search list of `domains`, in order; a trailing dot on a domain is ignored.
+    ///
+    /// Fails for an empty list, an empty or non-ASCII label, a label over 63 bytes, or
+    /// a list that does not fit one option once encoded.
+    pub fn new<S: AsRef<str>>(domains: &[S]) -> Result<Self> {
+        if domains.is_empty() {
+            bail!("domain search list is empty");
+        }
+        let mut names = Vec::with_capacity(domains.len());
+        for domain in domains {
+            let name = domain.as_ref().trim_end_matches('.');
+            Self::validate_name(name)?;
+            names.push(name.to_string());
+        }
+        let search = Self(names);
+        let len = search.encode().len();
+        if len > u8::MAX as usize {
+            bail!(
+                "domain search list encodes to {} bytes, more than fits in one option",
+                len
+            );
+        }
+        Ok(search)
+    }
+
+    fn validate_name(name: &str) -> Result<()> {
+        let mut wire_len = 1;
+        for label in name.split('.') {
+            if label.is_empty() || label.len() > MAX_LABEL_LEN || !label.is_ascii() {
+                bail!(
+                    "invalid domain '{}': labels must be 1 to {} ASCII characters",
+                    name,
+                    MAX_LABEL_LEN
+                );
+            }
+            wire_len += 1 + label.len();
+        }
+        if wire_len > MAX_NAME_LEN {
+            bail!(
+                "invalid domain '{}': longer than {} bytes",
+                name,
+                MAX_NAME_LEN
+            );
+        }
+        Ok(())
+    }
+
+    /// The search domains, in order.
+    pub fn domains(&self) -> &[String] {
+        &self.0
+    }
+
+    /// Encode as a raw DHCP option.
+    pub fn to_option(&self) -> DhcpOption {
+        DhcpOption::Unknown(UnknownOption::new(
+            OptionCode::from(Self::CODE),
+            self.encode(),
+        ))
+    }
+
+    /// The option payload: each domain's labels, ending at a pointer to the longest
+    /// suffix already written, or at the root label if none was.
+    ///
+    /// Suffixes are matched case-insensitively, as DNS names compare.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+        let mut suffix_offsets: HashMap<String, usize> = HashMap::new();
+        for name in &self.0 {
+            let labels: Vec<&str> = name.split('.').collect();
+            let mut pointer = None;
+            for i in 0..labels.len() {
+                let suffix = labels[i..].join(".").to_ascii_lowercase();
+                if let Some(&offset) = suffix_offsets.get(&suffix) {
+                    pointer = Some(offset);
+                    break;
+                }
+                if data.len() <= MAX_POINTER_OFFSET {
+                    suffix_offsets.insert(suffix, data.len());
+                }
+                data.push(labels[i].len() as u8);
+                data.extend_from_slice(labels[i].as_bytes());
+            }
+            match pointer {
+                Some(offset) => data.extend_from_slice(&(0xc000 | offset as u16).to_be_bytes()),
+                None => data.push(0),
+            }
+        }
+        data
+    }
+
+    /// Decode an option 119 payload, following compression pointers. Returns
+    /// `None` if it is truncated, holds a pointer that does not point back into the
+    /// payload, or a name over 255 bytes.
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        let mut names = Vec::new();
+        let mut pos = 0;
+        while pos < data.len() {
+            let (name, next) = Self::decode_name(data, pos)?;
+            names.push(name);
+            pos = next;
+        }
+        if names.is_empty() {
+            return None;
+        }
+        Some(Self(names))
+    }
+
+    /// Read the name starting at `start`, returning it and the offset just past it.
+    fn decode_name(data: &[u8], start: usize) -> Option<(String, usize)> {
+        let mut labels: Vec<String> = Vec::new();
+        let mut wire_len = 1;
+        let mut pos = start;
+        let mut end = None;
+        // Lowest offset read so far. A pointer must land below it, so jumps strictly
+        // descend and a pointer loop is impossible.
+        let mut floor = start;
+        loop {
+            let len = *data.get(pos)? as usize;
+            match len & 0xc0 {
+                0xc0 => {
+                    let offset = ((len & 0x3f) << 8) | *data.get(pos + 1)? as usize;
+                    if offset >= floor {
+                        return None;
+                    }
+                    end.get_or_insert(pos + 2);
+                    floor = offset;
+                    pos = offset;
+                }
+                0x00 if len == 0 => {
+                    let end = end.unwrap_or(pos + 1);
+                    return (!labels.is_empty()).then(|| (labels.join("."), end));
+                }
+                0x00 => {
+                    let label = data.get(pos + 1..pos + 1 + len)?;
+                    wire_len += 1 + len;
+                    if wire_len > MAX_NAME_LEN {
+                        return None;
+                    }
+                    labels.push(String::from_utf8_lossy(label).into_owned());
+                    pos += 1 + len;
+                }
+                _ => return None,
+            }
+        }
+    }
+}
+
+/// Option 43 as PXE clients read it: vendor-encapsulated sub-options (PXE 2.1,
+/// section 2.4).
+///
+/// A PXE ROM looks here for how to run boot server discovery and whether to show
+/// its boot menu prompt. Each sub-option is a code, a length and that many bytes,
+/// and the list ends with sub-option 255.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PxeVendorOptions {
+    discovery_control: Option<u8>,
+    menu_prompt: Option<(u8, String)>,
+}
+
+/// Longest menu prompt that still fits in one option with every sub-option set.
+const MAX_PXE_PROMPT_LEN: usize = 248;
+
+impl PxeVendorOptions {
+    pub const CODE: u8 = 43;
+
+    /// Sub-option 6, PXE_DISCOVERY_CONTROL.
+    const DISCOVERY_CONTROL: u8 = 6;
+    /// Sub-option 10, PXE_MENU_PROMPT.
+    const MENU_PROMPT: u8 = 10;
+    const END: u8 = 255;
+
+    /// Discovery control bit: do not broadcast to find boot servers.
+    pub const DISABLE_BROADCAST: u8 = 0x01;
+    /// Discovery control bit: do not multicast to find boot servers.
+    pub const DISABLE_MULTICAST: u8 = 0x02;
+    /// Discovery control bit: only use servers from the boot server list.
+    pub const SERVER_LIST_ONLY: u8 = 0x04;
+    /// Discovery control bit: skip discovery and download the offered boot file.
+    pub const USE_BOOTFILE: u8 = 0x08;
+
+    /// Whether `code` is option 43.
+    pub fn matches(code: &OptionCode) -> bool {
+        u8::from(*code) == Self::CODE
+    }
+
+    /// Set the PXE_DISCOVERY_CONTROL bits, a combination of the constants above.
+    pub fn with_discovery_control(mut self, bits: u8) -> Self {
+        self.discovery_control = Some(bits);
+        self
+    }
+
+    /// Set the boot menu prompt, shown for `timeout` seconds before the first menu
+    /// item is booted. A timeout of 0 boots it at once and 255 waits for a key.
+    ///
+    /// Fails for a non-ASCII prompt or one over 248 bytes.
+    pub fn with_menu_prompt(mut self, timeout: u8, prompt: &str) -> Result<Self> {
+        if !prompt.is_ascii() || prompt.len() > MAX_PXE_PROMPT_LEN {
+            bail!(
+                "PXE menu prompt must be at most {} ASCII characters",
+                MAX_PXE_PROMPT_LEN
+            );
+        }
+        self.menu_prompt = Some((timeout, prompt.to_string()));
+        Ok(self)
+    }
+
+    /// The PXE_DISCOVERY_CONTROL bits, if set.
+    pub fn discovery_control(&self) -> Option<u8> {
+        self.discovery_control
+    }
+
+    /// The menu prompt timeout and text, if set.
+    pub fn menu_prompt(&self) -> Option<(u8, &str)> {
+        self.menu_prompt
+            .as_ref()
+            .map(|(timeout, prompt)| (*timeout, prompt.as_str()))
+    }
+
+    /// Whether no sub-option is set, so there is nothing worth sending.
+    pub fn is_empty(&self) -> bool {
+        self.discovery_control.is_none() && self.menu_prompt.is_none()
+    }
+
+    /// Encode as a raw DHCP option.
+    pub fn to_option(&self) -> DhcpOption {
+        DhcpOption::Unknown(UnknownOption::new(
+            OptionCode::from(Self::CODE),
+            self.encode(),
+        ))
+    }
+
+    /// The option payload: the set sub-options in code order, then the end marker.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+        if let Some(bits) = self.discovery_control {
+            data.extend_from_slice(&[Self::DISCOVERY_CONTROL, 1, bits]);
+        }
+        if let Some((timeout, prompt)) = &self.menu_prompt {
+            data.extend_from_slice(&[Self::MENU_PROMPT, 1 + prompt.len() as u8, *timeout]);
+            data.extend_from_slice(prompt.as_bytes());
+        }
+        data.push(Self::END);
+        data
+    }
+
+    /// Decode an option 43 payload, skipping sub-options this type does not model.
+    /// Returns `None` if a sub-option runs past the payload or a known one has the
+    /// wrong length.
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        let mut options = Self::default();
+        let mut pos = 0;
+        while let Some(&code) = data.get(pos) {
+            match code {
+                0 => {
+                    pos += 1;
+                    continue;
+                }
+                Self::END => break,
+                _ => {}
+            }
+            let len = *data.get(pos + 1)? as usize;
+            let value = data.get(pos + 2..pos + 2 + len)?;
+            match code {
+                Self::DISCOVERY_CONTROL => match value {
+                    [bits] => options.discovery_control = Some(*bits),
+                    _ => return None,
+                },
+                Self::MENU_PROMPT => {
+                    let (timeout, prompt) = value.split_first()?;
+                    options.menu_prompt =
+                        Some((*timeout, String::from_utf8_lossy(prompt).into_owned()));
+                }
+                _ => {}
+            }
+            pos += 2 + len;
+        }
+        Some(options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dhcproto::v4::{Message, Opcode};
+    use dhcproto::{Decodable, Decoder, Encodable, Encoder};
+
+    #[test]
+    fn test_tftp_server_address_round_trip() {
+        let option =
+            TftpServerAddress(vec![Ipv4Addr::new(10, 0, 0, 1), Ipv4Addr::new(10, 0, 0, 2)]);
+
+        let mut msg = Message::default();
+        msg.set_opcode(Opcode::BootReply);
+        msg.opts_mut().insert(option.to_option());
+
+        let mut buf = Vec::new();
+        msg.encode(&mut Encoder::new(&mut buf)).unwrap();
+        let decoded = Message::decode(&mut Decoder::new(&buf)).unwrap();
+
+        let parsed = decoded
+            .opts()
+            .iter()
+            .find_map(|(_, opt)| TftpServerAddress::from_option(opt));
+        assert_eq!(parsed, Some(option));
+    }
+
+    #[test]
+    fn test_tftp_server_address_wire_format() {
+        let option = TftpServerAddress(vec![Ipv4Addr::new(192, 168, 1, 10)]);
+        let DhcpOption::Unknown(unknown) = option.to_option() else {
+            panic!("expected raw option");
+        };
+        assert_eq!(u8::from(unknown.code()), 150);
+        assert_eq!(unknown.data(), &[192, 168, 1, 10]);
+    }
+
+    #[test]
+    fn test_tftp_server_address_rejects_truncated_payload() {
+        let opt = DhcpOption::Unknown(UnknownOption::new(OptionCode::from(150), vec![10, 0, 0]));
+        assert_eq!(TftpServerAddress::from_option(&opt), None);
+    }
+
+    #[test]
+    fn test_user_class_single_value() {
+        let opt = DhcpOption::UserClass(b"\x04iPXE".to_vec());
+        let parsed = UserClass::from_option(&opt).unwrap();
+        assert_eq!(parsed, UserClass(vec!["iPXE".to_string()]));
+        assert!(parsed.contains("iPXE"));
+    }
+
+    #[test]
+    fn test_user_class_multiple_values() {
+        let opt = DhcpOption::UserClass(b"\x05rack1\x07storage".to_vec());
+        let parsed = UserClass::from_option(&opt).unwrap();
+        assert_eq!(
+            parsed,
+            UserClass(vec!["rack1".to_string(), "storage".to_string()])
+        );
+        assert!(!parsed.contains("rack"));
+    }
+
+    #[test]
+    fn test_user_class_bare_string() {
+        // iPXE sends its class without a length prefix
+        let opt = DhcpOption::UserClass(b"iPXE".to_vec());
+        assert_eq!(
+            UserClass::from_option(&opt),
+            Some(UserClass(vec!["iPXE".to_string()]))
+        );
+
+        // A truncated length-prefixed entry falls back to the raw string too
+        let opt = DhcpOption::UserClass(b"\x09iPXE".to_vec());
+        assert_eq!(
+            UserClass::from_option(&opt),
+            Some(UserClass(vec!["\tiPXE".to_string()]))
+        );
+    }
+
+    #[test]
+    fn test_user_class_rejects_empty_and_other_options() {
+        assert_eq!(
+            UserClass::from_option(&DhcpOption::UserClass(Vec::new())),
+            None
+        );
+        assert_eq!(
+            UserClass::from_option(&DhcpOption::ClassIdentifier(b"PXEClient".to_vec())),
+            None
+        );
+    }
+
+    #[test]
+    fn test_user_class_round_trip() {
+        let option = UserClass(vec!["iPXE".to_string(), "gpu-nodes".to_string()]);
+
+        let mut msg = Message::default();
+        msg.set_opcode(Opcode::BootRequest);
+        msg.opts_mut().insert(option.to_option());
+
+        let mut buf = Vec::new();
+        msg.encode(&mut Encoder::new(&mut buf)).unwrap();
+        let decoded = Message::decode(&mut Decoder::new(&buf)).unwrap();
+
+        let parsed = decoded
+            .opts()
+            .iter()
+            .find_map(|(_, opt)| UserClass::from_option(opt));
+        assert_eq!(parsed, Some(option));
+    }
+
+    #[test]
+    fn test_domain_search_compresses_shared_suffixes() {
+        let search = DomainSearch::new(&["eng.example.com", "corp.example.com"]).unwrap();
+        let mut expected = Vec::new();
+        expected.extend_from_slice(b"\x03eng\x07example\x03com\x00");
+        // "corp", then a pointer to "example.com" at offset 4
+        expected.extend_from_slice(b"\x04corp\xc0\x04");
+        assert_eq!(search.encode(), expected);
+
+        let DhcpOption::Unknown(unknown) = search.to_option() else {
+            panic!("expected a raw option");
+        };
+        assert_eq!(u8::from(unknown.code()), DomainSearch::CODE);
+        assert_eq!(unknown.data(), expected.as_slice());
+    }
+
+    #[test]
+    fn test_domain_search_points_at_whole_earlier_names() {
+        let search = DomainSearch::new(&[
+            "example.com",
+            "eng.example.com",
+            "example.com.",
+            "EXAMPLE.org",
+        ])
+        .unwrap();
+        let mut expected = Vec::new();
+        expected.extend_from_slice(b"\x07example\x03com\x00");
+        expected.extend_from_slice(b"\x03eng\xc0\x00");
+        expected.extend_from_slice(b"\xc0\x00");
+        // Case-insensitive match on "example", but "org" is new
+        expected.extend_from_slice(b"\x07EXAMPLE\x03org\x00");
+        assert_eq!(search.encode(), expected);
+    }
+
+    #[test]
+    fn test_domain_search_round_trip() {
+        let domains = [
+            "eng.example.com",
+            "corp.example.com",
+            "example.com",
+            "lab.eng.example.com",
+            "example.net",
+        ];
+        let search = DomainSearch::new(&domains).unwrap();
+        let decoded = DomainSearch::decode(&search.encode()).unwrap();
+        assert_eq!(decoded, search);
+        assert_eq!(decoded.domains(), domains);
+    }
+
+    #[test]
+    fn test_domain_search_rejects_invalid_lists() {
+        let empty: [&str; 0] = [];
+        assert!(DomainSearch::new(&empty).is_err());
+        assert!(DomainSearch::new(&["example..com"]).is_err());
+        assert!(DomainSearch::new(&[format!("{}.com", "a".repeat(64))]).is_err());
+        assert!(DomainSearch::new(&["exämple.com"]).is_err());
+
+        // Too many unrelated domains to fit in one option
+        let many: Vec<String> = (0..20)
+            .map(|i| format!("site{:02}.example{:02}.net", i, i))
+            .collect();
+        assert!(DomainSearch::new(&many).is_err());
+    }
+
+    #[test]
+    fn test_domain_search_decode_rejects_bad_pointers() {
+        // Forward pointer
+        assert_eq!(DomainSearch::decode(b"\xc0\x05\x03com\x00"), None);
+        // Pointer back to the label that leads to it
+        assert_eq!(DomainSearch::decode(b"\x03com\xc0\x00"), None);
+        // Truncated label and truncated pointer
+        assert_eq!(DomainSearch::decode(b"\x05co"), None);
+        assert_eq!(DomainSearch::decode(b"\x03com\x00\xc0"), None);
+        assert_eq!(DomainSearch::decode(b""), None);
+    }
+
+    #[test]
+    fn test_pxe_vendor_options_sub_option_layout() {
+        let options = PxeVendorOptions::default()
+            .with_discovery_control(
+                PxeVendorOptions::DISABLE_BROADCAST | PxeVendorOptions::DISABLE_MULTICAST,
+            )
+            .with_menu_prompt(5, "Press F8")
+            .unwrap();
+        let mut expected = vec![6, 1, 0x03];
+        expected.extend_from_slice(b"\x0a\x09\x05Press F8");
+        expected.push(255);
+        assert_eq!(options.encode(), expected);
+
+        let DhcpOption::Unknown(unknown) = options.to_option() else {
+            panic!("expected a raw option");
+        };
+        assert_eq!(u8::from(unknown.code()), PxeVendorOptions::CODE);
+        assert_eq!(unknown.data(), expected.as_slice());
+    }
+
+    #[test]
+    fn test_pxe_vendor_options_only_set_sub_options() {
+        let control = PxeVendorOptions::default().with_discovery_control(0x08);
+        assert_eq!(control.encode(), vec![6, 1, 0x08, 255]);
+
+        let prompt = PxeVendorOptions::default().with_menu_prompt(0, "").unwrap();
+        assert_eq!(prompt.encode(), vec![10, 1, 0, 255]);
+
+        assert!(PxeVendorOptions::default().is_empty());
+        assert_eq!(PxeVendorOptions::default().encode(), vec![255]);
+    }
+
+    #[test]
+    fn test_pxe_vendor_options_round_trip() {
+        let options = PxeVendorOptions::default()
+            .with_discovery_control(PxeVendorOptions::USE_BOOTFILE)
+            .with_menu_prompt(255, &"x".repeat(248))
+            .unwrap();
+        let data = options.encode();
+        assert!(data.len() <= u8::MAX as usize);
+        assert_eq!(PxeVendorOptions::decode(&data), Some(options));
+    }
+
+    #[test]
+    fn test_pxe_vendor_options_decode() {
+        // Pad, an unmodelled boot server list, then discovery control
+        let data = b"\x00\x08\x07\x80\x00\x01\x0a\x00\x00\x01\x06\x01\x07\xff";
+        let options = PxeVendorOptions::decode(data).unwrap();
+        assert_eq!(options.discovery_control(), Some(0x07));
+        assert_eq!(options.menu_prompt(), None);
+
+        assert_eq!(PxeVendorOptions::decode(b"\x06\x02\x01\x02\xff"), None);
+        assert_eq!(PxeVendorOptions::decode(b"\x0a\x05\x01ab"), None);
+        assert_eq!(PxeVendorOptions::decode(b"\x0a\x00\xff"), None);
+    }
+
+    #[test]
+    fn test_pxe_vendor_options_rejects_invalid_prompts() {
+        let options = PxeVendorOptions::default();
+        assert!(options.clone().with_menu_prompt(5, "Drück F8").is_err());
+        assert!(options.with_menu_prompt(5, &"x".repeat(249)).is_err());
+    }
+
+    #[test]
+    fn test_user_class_wire_format() {
+        let option = UserClass(vec!["ab".to_string(), "c".to_string()]);
+        let DhcpOption::UserClass(data) = option.to_option() else {
+            panic!("expected user class option");
+        };
+        assert_eq!(data, vec![2, b'a', b'b', 1, b'c']);
+    }
+}
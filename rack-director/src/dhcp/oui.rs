@@ -0,0 +1,117 @@
+//! Vendor-prefix (OUI) filtering of DHCP clients.
+//!
+//! On a shared provisioning VLAN, phones and laptops broadcast DHCP too. Every
+//! lease we offer them shows up as a phantom interface, so the server can be told
+//! to answer only MACs whose first three octets belong to expected server and BMC
+//! vendors (`--dhcp-oui-allow`), or to ignore known-bad vendors (`--dhcp-oui-deny`).
+
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::{Result, bail};
+
+/// The first three octets of a MAC address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Oui([u8; 3]);
+
+impl Oui {
+    /// The OUI of `mac`, or `None` if it is shorter than three octets.
+    pub fn of(mac: &[u8]) -> Option<Self> {
+        Some(Self(mac.get(..3)?.try_into().ok()?))
+    }
+}
+
+impl fmt::Display for Oui {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:02x}:{:02x}:{:02x}", self.0[0], self.0[1], self.0[2])
+    }
+}
+
+impl FromStr for Oui {
+    type Err = anyhow::Error;
+
+    /// Accepts `aa:bb:cc`, `aa-bb-cc` or `aabbcc`, in either case.
+    fn from_str(s: &str) -> Result<Self> {
+        let hex: String = s.chars().filter(|c| !matches!(c, ':' | '-')).collect();
+        if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            bail!(
+                "Invalid OUI '{}': expected three hex octets, e.g. 00:25:90",
+                s
+            );
+        }
+        let mut octets = [0u8; 3];
+        for (i, octet) in octets.iter_mut().enumerate() {
+            *octet = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16)?;
+        }
+        Ok(Self(octets))
+    }
+}
+
+/// Which client vendors the DHCP server answers.
+///
+/// A denylisted OUI is always refused. Otherwise an empty allowlist admits every
+/// client, and a non-empty one admits only its own OUIs.
+#[derive(Debug, Clone, Default)]
+pub struct OuiFilter {
+    allow: Vec<Oui>,
+    deny: Vec<Oui>,
+}
+
+impl OuiFilter {
+    pub fn new(allow: Vec<Oui>, deny: Vec<Oui>) -> Self {
+        Self { allow, deny }
+    }
+
+    /// Whether the server should answer the client with hardware address `mac`.
+    pub fn permits(&self, mac: &[u8]) -> bool {
+        let Some(oui) = Oui::of(mac) else {
+            return self.allow.is_empty();
+        };
+        !self.deny.contains(&oui) && (self.allow.is_empty() || self.allow.contains(&oui))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SUPERMICRO: [u8; 6] = [0x00, 0x25, 0x90, 0x01, 0x02, 0x03];
+    const PHONE: [u8; 6] = [0xf0, 0x18, 0x98, 0x01, 0x02, 0x03];
+
+    #[test]
+    fn test_parse_accepts_common_separators() {
+        let expected = Oui([0x00, 0x25, 0x90]);
+        assert_eq!("00:25:90".parse::<Oui>().unwrap(), expected);
+        assert_eq!("00-25-90".parse::<Oui>().unwrap(), expected);
+        assert_eq!("002590".parse::<Oui>().unwrap(), expected);
+        assert_eq!("AC:1F:6B".parse::<Oui>().unwrap().to_string(), "ac:1f:6b");
+        assert!("00:25".parse::<Oui>().is_err());
+        assert!("00:25:9g".parse::<Oui>().is_err());
+    }
+
+    #[test]
+    fn test_default_filter_permits_everything() {
+        let filter = OuiFilter::default();
+        assert!(filter.permits(&SUPERMICRO));
+        assert!(filter.permits(&PHONE));
+    }
+
+    #[test]
+    fn test_allowlist_admits_only_listed_vendors() {
+        let filter = OuiFilter::new(vec!["00:25:90".parse().unwrap()], vec![]);
+        assert!(filter.permits(&SUPERMICRO));
+        assert!(!filter.permits(&PHONE));
+        assert!(!filter.permits(&[0x00, 0x25]));
+    }
+
+    #[test]
+    fn test_denylist_wins_over_allowlist() {
+        let oui: Oui = "f0:18:98".parse().unwrap();
+        let filter = OuiFilter::new(vec![oui], vec![oui]);
+        assert!(!filter.permits(&PHONE));
+
+        let filter = OuiFilter::new(vec![], vec![oui]);
+        assert!(!filter.permits(&PHONE));
+        assert!(filter.permits(&SUPERMICRO));
+    }
+}
@@ -0,0 +1,183 @@
+//! Bounds-checked decoding of received DHCP packets.
+//!
+//! `dhcproto` stops reading options at the first one it cannot decode and keeps the
+//! packet, so a single bad option silently drops every option after it. Before
+//! handing bytes to `dhcproto`, [`parse_message`] walks the options region itself and
+//! applies one policy consistently:
+//!
+//! - **Reject the packet** when the framing is broken: shorter than the fixed header,
+//!   a wrong magic cookie, an option code with no length byte, or a length that runs
+//!   past the end of the packet. The option boundaries after that point are unknown,
+//!   so nothing in the packet can be trusted.
+//! - **Skip the option** when it is framed correctly but its payload does not decode
+//!   (e.g. a zero-length message type or a 3-byte address). The options around it are
+//!   kept.
+//! - **Tolerate a missing END**: the end of the packet ends the options. Bytes after
+//!   END are ignored.
+
+use anyhow::{Result, anyhow, bail};
+use dhcproto::v4::{DhcpOption, Message};
+use dhcproto::{Decodable, Decoder};
+
+/// Fixed BOOTP header length, up to and including the `file` field.
+const HEADER_LEN: usize = 236;
+
+/// DHCP magic cookie that precedes the options.
+const MAGIC_COOKIE: [u8; 4] = [99, 130, 83, 99];
+
+const PAD: u8 = 0;
+const END: u8 = 255;
+
+/// Decode a DHCP packet received from the network.
+///
+/// See the module docs for which malformations reject the packet and which only
+/// drop an option.
+pub fn parse_message(data: &[u8]) -> Result<Message> {
+    let sanitized = sanitize_options(data)?;
+    Message::decode(&mut Decoder::new(&sanitized)).map_err(|e| anyhow!("{}", e))
+}
+
+/// Copy `data` with unusable options removed and a terminating END guaranteed.
+fn sanitize_options(data: &[u8]) -> Result<Vec<u8>> {
+    let options_start = HEADER_LEN + MAGIC_COOKIE.len();
+    if data.len() < options_start {
+        bail!(
+            "packet is {} bytes, shorter than the {}-byte header",
+            data.len(),
+            options_start
+        );
+    }
+    if data[HEADER_LEN..options_start] != MAGIC_COOKIE {
+        bail!("missing DHCP magic cookie");
+    }
+
+    let mut out = data[..options_start].to_vec();
+    let mut i = options_start;
+    while let Some(&code) = data.get(i) {
+        match code {
+            PAD => i += 1,
+            END => break,
+            _ => {
+                let Some(&len) = data.get(i + 1) else {
+                    bail!("option {} at offset {} has no length byte", code, i);
+                };
+                let end = i + 2 + len as usize;
+                let Some(raw) = data.get(i..end) else {
+                    bail!(
+                        "option {} at offset {} claims {} bytes but only {} remain",
+                        code,
+                        i,
+                        len,
+                        data.len() - i - 2
+                    );
+                };
+                match DhcpOption::decode(&mut Decoder::new(raw)) {
+                    Ok(_) => out.extend_from_slice(raw),
+                    Err(e) => log::debug!("Skipping undecodable DHCP option {}: {}", code, e),
+                }
+                i = end;
+            }
+        }
+    }
+    out.push(END);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dhcp::decode::parse_hex;
+    use dhcproto::v4::{MessageType, OptionCode};
+
+    const PXE_DISCOVER: &str = include_str!("../../tests/fixtures/dhcp/pxe_discover.hex");
+
+    /// Header and magic cookie followed by `options`, with no END appended.
+    fn packet(options: &[u8]) -> Vec<u8> {
+        let mut data = vec![0u8; HEADER_LEN];
+        data[0] = 1; // BOOTREQUEST
+        data[1] = 1; // Ethernet
+        data[2] = 6;
+        data.extend_from_slice(&MAGIC_COOKIE);
+        data.extend_from_slice(options);
+        data
+    }
+
+    const DISCOVER: [u8; 3] = [53, 1, 1];
+    const HOSTNAME: [u8; 6] = [12, 4, b'n', b'o', b'd', b'e'];
+
+    #[test]
+    fn test_well_formed_packet() {
+        let data = packet(&[&DISCOVER[..], &HOSTNAME, &[END]].concat());
+        let msg = parse_message(&data).unwrap();
+        assert_eq!(msg.opts().msg_type(), Some(MessageType::Discover));
+        assert!(msg.opts().get(OptionCode::Hostname).is_some());
+    }
+
+    #[test]
+    fn test_missing_end_is_tolerated() {
+        let data = packet(&[&DISCOVER[..], &HOSTNAME].concat());
+        let msg = parse_message(&data).unwrap();
+        assert_eq!(msg.opts().msg_type(), Some(MessageType::Discover));
+        assert!(msg.opts().get(OptionCode::Hostname).is_some());
+    }
+
+    #[test]
+    fn test_bytes_after_end_are_ignored() {
+        let data = packet(&[&DISCOVER[..], &[END], &[12, 200, b'x']].concat());
+        let msg = parse_message(&data).unwrap();
+        assert_eq!(msg.opts().msg_type(), Some(MessageType::Discover));
+        assert!(msg.opts().get(OptionCode::Hostname).is_none());
+    }
+
+    #[test]
+    fn test_length_overflow_rejects_packet() {
+        let data = packet(&[&DISCOVER[..], &[12, 10, b'a', b'b']].concat());
+        let err = parse_message(&data).unwrap_err().to_string();
+        assert!(err.contains("option 12"), "{}", err);
+    }
+
+    #[test]
+    fn test_missing_length_byte_rejects_packet() {
+        let data = packet(&[&DISCOVER[..], &[12]].concat());
+        assert!(parse_message(&data).is_err());
+    }
+
+    #[test]
+    fn test_undecodable_option_is_skipped_not_fatal() {
+        // Zero-length requested IP sits between two valid options
+        let data = packet(&[&DISCOVER[..], &[50, 0], &HOSTNAME, &[END]].concat());
+        let msg = parse_message(&data).unwrap();
+        assert_eq!(msg.opts().msg_type(), Some(MessageType::Discover));
+        assert!(msg.opts().get(OptionCode::RequestedIpAddress).is_none());
+        assert!(msg.opts().get(OptionCode::Hostname).is_some());
+    }
+
+    #[test]
+    fn test_zero_length_opaque_option_is_kept() {
+        let data = packet(&[&[224, 0], &DISCOVER[..], &[END]].concat());
+        let msg = parse_message(&data).unwrap();
+        assert_eq!(msg.opts().msg_type(), Some(MessageType::Discover));
+        assert!(msg.opts().get(OptionCode::from(224)).is_some());
+    }
+
+    #[test]
+    fn test_short_packet_and_bad_cookie_rejected() {
+        assert!(parse_message(&[]).is_err());
+        assert!(parse_message(&packet(&[])[..HEADER_LEN + 2]).is_err());
+
+        let mut data = packet(&DISCOVER);
+        data[HEADER_LEN] = 0;
+        assert!(parse_message(&data).is_err());
+    }
+
+    #[test]
+    fn test_every_truncation_of_captured_packet_is_handled() {
+        let bytes = parse_hex(PXE_DISCOVER).unwrap();
+        assert!(parse_message(&bytes).is_ok());
+        for len in 0..bytes.len() {
+            // Must return, never panic; which prefixes succeed depends on where
+            // option boundaries fall.
+            let _ = parse_message(&bytes[..len]);
+        }
+    }
+}
@@ -0,0 +1,186 @@
+//! Limits on how fast addresses are handed to new clients.
+//!
+//! A client spoofing thousands of MACs can drain a pool with DISCOVERs alone. The
+//! handler takes a slot here before allocating for a client it has never seen, so
+//! new allocations are capped per time window, in total and per network, while
+//! renewals and known clients are never counted.
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Window new allocations are counted over unless configured otherwise.
+pub const DEFAULT_WINDOW: Duration = Duration::from_secs(60);
+
+/// Which limit refused an allocation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateLimitScope {
+    /// The limit across all networks.
+    Global,
+    /// The limit of the network with this id.
+    Network(i64),
+}
+
+impl fmt::Display for RateLimitScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Global => write!(f, "global"),
+            Self::Network(id) => write!(f, "network {}", id),
+        }
+    }
+}
+
+/// Sliding-window limit on new-address allocations.
+#[derive(Debug)]
+pub struct AllocationRateLimiter {
+    /// Most allocations per window across all networks; `None` for no limit.
+    global_max: Option<u32>,
+    /// Most allocations per window on any one network; `None` for no limit.
+    network_max: Option<u32>,
+    window: Duration,
+    state: Mutex<WindowState>,
+}
+
+/// When each allocation still inside the window was granted.
+#[derive(Debug, Default)]
+struct WindowState {
+    global: VecDeque<Instant>,
+    networks: HashMap<i64, VecDeque<Instant>>,
+}
+
+impl Default for AllocationRateLimiter {
+    fn default() -> Self {
+        Self::new(None, None, DEFAULT_WINDOW)
+    }
+}
+
+impl AllocationRateLimiter {
+    pub fn new(global_max: Option<u32>, network_max: Option<u32>, window: Duration) -> Self {
+        Self {
+            global_max,
+            network_max,
+            window,
+            state: Mutex::new(WindowState::default()),
+        }
+    }
+
+    /// Whether any limit is configured.
+    pub fn is_enabled(&self) -> bool {
+        self.global_max.is_some() || self.network_max.is_some()
+    }
+
+    /// Take a slot for a new allocation on `network_id`, or report the limit that is
+    /// exhausted. Refused attempts do not use up a slot.
+    pub fn try_acquire(&self, network_id: i64) -> Result<(), RateLimitScope> {
+        self.try_acquire_at(Instant::now(), network_id)
+    }
+
+    fn try_acquire_at(&self, now: Instant, network_id: i64) -> Result<(), RateLimitScope> {
+        if !self.is_enabled() {
+            return Ok(());
+        }
+        let mut guard = self.state.lock().unwrap();
+        let state = &mut *guard;
+
+        expire(&mut state.global, now, self.window);
+        if let Some(max) = self.global_max
+            && state.global.len() >= max as usize
+        {
+            return Err(RateLimitScope::Global);
+        }
+
+        let network = state.networks.entry(network_id).or_default();
+        expire(network, now, self.window);
+        if let Some(max) = self.network_max
+            && network.len() >= max as usize
+        {
+            return Err(RateLimitScope::Network(network_id));
+        }
+
+        network.push_back(now);
+        state.global.push_back(now);
+        Ok(())
+    }
+}
+
+/// Drop grants that are a full window old.
+fn expire(grants: &mut VecDeque<Instant>, now: Instant, window: Duration) {
+    while let Some(&granted) = grants.front()
+        && now.duration_since(granted) >= window
+    {
+        grants.pop_front();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unlimited_by_default() {
+        let limiter = AllocationRateLimiter::default();
+        assert!(!limiter.is_enabled());
+        for _ in 0..1000 {
+            assert_eq!(limiter.try_acquire(1), Ok(()));
+        }
+    }
+
+    #[test]
+    fn test_global_limit_spans_networks() {
+        let limiter = AllocationRateLimiter::new(Some(2), None, DEFAULT_WINDOW);
+        let now = Instant::now();
+        assert_eq!(limiter.try_acquire_at(now, 1), Ok(()));
+        assert_eq!(limiter.try_acquire_at(now, 2), Ok(()));
+        assert_eq!(limiter.try_acquire_at(now, 3), Err(RateLimitScope::Global));
+    }
+
+    #[test]
+    fn test_network_limit_is_per_network() {
+        let limiter = AllocationRateLimiter::new(None, Some(1), DEFAULT_WINDOW);
+        let now = Instant::now();
+        assert_eq!(limiter.try_acquire_at(now, 1), Ok(()));
+        assert_eq!(
+            limiter.try_acquire_at(now, 1),
+            Err(RateLimitScope::Network(1))
+        );
+        assert_eq!(limiter.try_acquire_at(now, 2), Ok(()));
+    }
+
+    #[test]
+    fn test_refusals_do_not_use_slots() {
+        let limiter = AllocationRateLimiter::new(Some(2), Some(1), DEFAULT_WINDOW);
+        let now = Instant::now();
+        assert_eq!(limiter.try_acquire_at(now, 1), Ok(()));
+        for _ in 0..5 {
+            assert_eq!(
+                limiter.try_acquire_at(now, 1),
+                Err(RateLimitScope::Network(1))
+            );
+        }
+        assert_eq!(
+            limiter.try_acquire_at(now, 2),
+            Ok(()),
+            "refused attempts on network 1 did not count globally"
+        );
+    }
+
+    #[test]
+    fn test_slots_free_up_after_window() {
+        let window = Duration::from_secs(10);
+        let limiter = AllocationRateLimiter::new(Some(1), None, window);
+        let start = Instant::now();
+        assert_eq!(limiter.try_acquire_at(start, 1), Ok(()));
+        assert_eq!(
+            limiter.try_acquire_at(start + Duration::from_secs(9), 1),
+            Err(RateLimitScope::Global)
+        );
+        assert_eq!(limiter.try_acquire_at(start + window, 1), Ok(()));
+    }
+
+    #[test]
+    fn test_scope_display() {
+        assert_eq!(RateLimitScope::Global.to_string(), "global");
+        assert_eq!(RateLimitScope::Network(7).to_string(), "network 7");
+    }
+}
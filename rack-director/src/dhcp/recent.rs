@@ -0,0 +1,138 @@
+//! In-memory log of recently received DHCP packets and what the server did with them.
+//!
+//! When a device gets no DHCP response, the reason is usually a decision the server
+//! made on purpose (wrong server identifier, disabled interface, filtered vendor, no
+//! matching network). The handler records one [`PacketEvent`] per packet into a
+//! fixed-capacity ring so operators can see those decisions via `GET /api/dhcp/recent`
+//! without turning on debug logging.
+
+use std::collections::VecDeque;
+use std::net::Ipv4Addr;
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+/// Number of events kept by [`RecentPackets::default`].
+pub const DEFAULT_CAPACITY: usize = 500;
+
+/// What the server did with a packet.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "decision", rename_all = "snake_case")]
+pub enum Decision {
+    Offered {
+        ip: Ipv4Addr,
+    },
+    Acked {
+        ip: Ipv4Addr,
+    },
+    Nak,
+    Released,
+    Declined,
+    Ignored {
+        reason: String,
+    },
+    /// Handling the packet failed, e.g. on a database error; no reply was sent.
+    Error {
+        error: String,
+    },
+}
+
+/// One received packet.
+#[derive(Debug, Clone, Serialize)]
+pub struct PacketEvent {
+    pub at: DateTime<Utc>,
+    /// `ciaddr`, when the client already has an address (renewals and releases).
+    pub client: Option<Ipv4Addr>,
+    /// `giaddr`, when a relay agent forwarded the packet.
+    pub relay: Option<Ipv4Addr>,
+    /// `chaddr`, if the packet decoded.
+    pub mac: Option<String>,
+    /// Option 53, e.g. `Discover`, if present.
+    pub message_type: Option<String>,
+    #[serde(flatten)]
+    pub decision: Decision,
+}
+
+/// Shared, cloneable handle to the ring of recent packet events.
+#[derive(Clone)]
+pub struct RecentPackets {
+    capacity: usize,
+    events: Arc<Mutex<VecDeque<PacketEvent>>>,
+}
+
+impl RecentPackets {
+    /// A ring keeping the last `capacity` events.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            events: Arc::new(Mutex::new(VecDeque::with_capacity(capacity))),
+        }
+    }
+
+    /// Append `event`, evicting the oldest one when full.
+    pub(crate) fn record(&self, event: PacketEvent) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut events = self.events.lock().unwrap();
+        if events.len() == self.capacity {
+            events.pop_front();
+        }
+        events.push_back(event);
+    }
+
+    /// All retained events, newest first.
+    pub fn snapshot(&self) -> Vec<PacketEvent> {
+        self.events.lock().unwrap().iter().rev().cloned().collect()
+    }
+}
+
+impl Default for RecentPackets {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(n: u8) -> PacketEvent {
+        PacketEvent {
+            at: Utc::now(),
+            client: None,
+            relay: None,
+            mac: Some(format!("aa:bb:cc:dd:ee:{:02x}", n)),
+            message_type: Some("Discover".to_string()),
+            decision: Decision::Nak,
+        }
+    }
+
+    #[test]
+    fn test_ring_evicts_oldest_and_lists_newest_first() {
+        let recent = RecentPackets::new(2);
+        recent.record(event(1));
+        recent.record(event(2));
+        recent.record(event(3));
+
+        let macs: Vec<_> = recent
+            .snapshot()
+            .into_iter()
+            .map(|e| e.mac.unwrap())
+            .collect();
+        assert_eq!(macs, ["aa:bb:cc:dd:ee:03", "aa:bb:cc:dd:ee:02"]);
+    }
+
+    #[test]
+    fn test_event_serializes_decision_inline() {
+        let mut e = event(1);
+        e.decision = Decision::Offered {
+            ip: Ipv4Addr::new(10, 0, 0, 100),
+        };
+        let json = serde_json::to_value(&e).unwrap();
+        assert_eq!(json["decision"], "offered");
+        assert_eq!(json["ip"], "10.0.0.100");
+        assert_eq!(json["mac"], "aa:bb:cc:dd:ee:01");
+    }
+}
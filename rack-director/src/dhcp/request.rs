@@ -1,8 +1,12 @@
+use dhcproto::v4::relay::{RelayCode, RelayInfo};
 use dhcproto::v4::{Architecture, DhcpOption, Message, MessageType, OptionCode};
 use std::net::Ipv4Addr;
 use uuid::Uuid;
 
+use super::message_builder;
+use super::options::{DomainSearch, TftpServerAddress, UserClass};
 use super::store::format_mac;
+use crate::director::{RawUuid, normalize_uuid};
 
 /// Extract Server Identifier (Option 54) from a DHCP message.
 ///
@@ -33,19 +37,81 @@ pub fn extract_server_identifier(msg: &Message) -> Option<Ipv4Addr> {
         })
 }
 
+/// Relay Agent Information (Option 82) sub-options the server understands.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Option82Data {
+    /// Sub-option 1: the relay port the client is attached to.
+    pub circuit_id: Option<Vec<u8>>,
+    /// Sub-option 2: identifies the relay itself.
+    pub remote_id: Option<Vec<u8>>,
+    /// Sub-option 5 (RFC 3527): an address on the subnet to serve the client from,
+    /// used instead of giaddr when the relay cannot put a client-subnet address there.
+    pub link_selection: Option<Ipv4Addr>,
+    /// Sub-option 11 (RFC 5107): the address to advertise as our Server Identifier,
+    /// so that the client's renewals go through the relay.
+    pub server_id_override: Option<Ipv4Addr>,
+}
+
+/// Parse Relay Agent Information (Option 82) from a DHCP message.
+///
+/// Returns `None` when the option is absent. Sub-options other than those in
+/// [`Option82Data`] are ignored.
+pub fn parse_option82(msg: &Message) -> Option<Option82Data> {
+    let Some(DhcpOption::RelayAgentInformation(info)) =
+        msg.opts().get(OptionCode::RelayAgentInformation)
+    else {
+        return None;
+    };
+
+    Some(Option82Data {
+        circuit_id: match info.get(RelayCode::AgentCircuitId) {
+            Some(RelayInfo::AgentCircuitId(id)) => Some(id.clone()),
+            _ => None,
+        },
+        remote_id: match info.get(RelayCode::AgentRemoteId) {
+            Some(RelayInfo::AgentRemoteId(id)) => Some(id.clone()),
+            _ => None,
+        },
+        link_selection: match info.get(RelayCode::LinkSelection) {
+            Some(RelayInfo::LinkSelection(ip)) => Some(*ip),
+            _ => None,
+        },
+        server_id_override: match info.get(RelayCode::ServerIdentifierOverride) {
+            Some(RelayInfo::ServerIdentifierOverride(ip)) => Some(*ip),
+            _ => None,
+        },
+    })
+}
+
 /// Pre-parsed DHCP request options extracted in a single pass.
 pub struct RequestContext {
     pub mac: String,
     #[allow(dead_code)]
     pub message_type: MessageType,
     pub requested_ip: Option<Ipv4Addr>,
+    /// Lease time the client asked for (Option 51), in seconds.
+    pub requested_lease_time: Option<u32>,
     pub client_arch: Option<Architecture>,
+    /// User Class Information (Option 77), if the client sent one.
+    pub user_class: Option<UserClass>,
+    /// The client's user class includes `iPXE`.
     pub is_ipxe: bool,
+    /// The client's vendor class (Option 60) starts with `PXEClient`.
+    pub is_pxe_client: bool,
     pub requested_tftp_server: bool,
     pub requested_bootfile: bool,
     pub requested_bootfile_size: bool,
+    /// Option 150 (Cisco TFTP server address list) appears in option 55.
+    pub requested_tftp_server_address: bool,
+    /// Option 119 (domain search list) appears in option 55.
+    pub requested_domain_search: bool,
     pub ciaddr: Ipv4Addr,
     pub guid: Option<Uuid>,
+    /// Client Identifier (Option 61) as colon-separated hex. When present it
+    /// identifies the client instead of `mac` (RFC 2131 Section 4.2).
+    pub client_id: Option<String>,
+    /// The client sent Rapid Commit (Option 80), asking to skip the OFFER.
+    pub rapid_commit: bool,
 }
 
 impl RequestContext {
@@ -54,40 +120,62 @@ impl RequestContext {
         let mac = format_mac(msg.chaddr());
         let mut message_type = MessageType::Discover; // safe default
         let mut requested_ip = None;
+        let mut requested_lease_time = None;
         let mut client_arch = None;
-        let mut is_ipxe = false;
+        let mut user_class = None;
         let mut has_tftp_server_name = false;
         let mut has_bootfile_name = false;
         let mut has_bootfile_size = false;
+        let mut has_tftp_server_address = false;
+        let mut has_domain_search = false;
+        let mut client_id = None;
+        let mut is_pxe_client = false;
 
         for (_code, opt) in msg.opts().iter() {
             match opt {
                 DhcpOption::MessageType(mt) => message_type = *mt,
                 DhcpOption::RequestedIpAddress(ip) => requested_ip = Some(*ip),
+                DhcpOption::AddressLeaseTime(secs) => requested_lease_time = Some(*secs),
                 DhcpOption::ClientSystemArchitecture(arch) => client_arch = Some(*arch),
-                DhcpOption::UserClass(data) if data == b"iPXE" => is_ipxe = true,
+                DhcpOption::UserClass(_) => user_class = UserClass::from_option(opt),
+                DhcpOption::ClassIdentifier(class) => {
+                    is_pxe_client = class.starts_with(b"PXEClient")
+                }
+                DhcpOption::ClientIdentifier(id) if !id.is_empty() => {
+                    client_id = Some(format_mac(id))
+                }
                 DhcpOption::ParameterRequestList(list) => {
                     has_tftp_server_name = list.contains(&OptionCode::TFTPServerName);
                     has_bootfile_name = list.contains(&OptionCode::BootfileName);
                     has_bootfile_size = list.contains(&OptionCode::BootFileSize);
+                    has_tftp_server_address = list.iter().any(TftpServerAddress::matches);
+                    has_domain_search = list.iter().any(DomainSearch::matches);
                 }
                 _ => {}
             }
         }
 
         let guid = extract_guid(msg);
+        let is_ipxe = user_class.as_ref().is_some_and(|uc| uc.contains("iPXE"));
 
         Self {
             mac,
             message_type,
             requested_ip,
+            requested_lease_time,
             client_arch,
+            user_class,
             is_ipxe,
+            is_pxe_client,
             requested_tftp_server: has_tftp_server_name,
             requested_bootfile: has_bootfile_name,
             requested_bootfile_size: has_bootfile_size,
+            requested_tftp_server_address: has_tftp_server_address,
+            requested_domain_search: has_domain_search,
             ciaddr: msg.ciaddr(),
             guid,
+            client_id,
+            rapid_commit: message_builder::has_rapid_commit(msg),
         }
     }
 }
@@ -102,7 +190,9 @@ impl RequestContext {
 /// - First 3 groups (time_low, time_mid, time_hi_and_version): little-endian
 /// - Last 2 groups (clock_seq, node): big-endian
 ///
-/// We use `Uuid::from_bytes_le()` which handles this correctly.
+/// The bytes go through the same normalization as the UUID iPXE reports over
+/// HTTP, so both name the same device. Firmware that sends the bytes big-endian
+/// is handled at resolution time (see `hardware_uuid::byte_swapped`).
 fn extract_guid(msg: &Message) -> Option<Uuid> {
     // DHCP Option 97 - Client Machine Identifier
     // dhcproto may expose this as Unknown(97) with raw bytes
@@ -113,11 +203,8 @@ fn extract_guid(msg: &Message) -> Option<Uuid> {
             // UnknownOption has a data() method that returns &[u8]
             let data = unknown_opt.data();
             // First byte is type (0 = GUID), remaining 16 bytes are UUID
-            if data.len() == 17
-                && data[0] == 0
-                && let Ok(uuid_bytes) = data[1..17].try_into()
-            {
-                return Some(Uuid::from_bytes_le(uuid_bytes));
+            if data.len() == 17 && data[0] == 0 {
+                return normalize_uuid(RawUuid::Smbios(&data[1..]));
             }
         }
     }
@@ -129,6 +216,32 @@ mod tests {
     use super::*;
     use dhcproto::v4::Opcode;
 
+    #[test]
+    fn test_parse_option82_link_selection_and_server_override() {
+        use dhcproto::v4::relay::RelayAgentInformation;
+
+        let mut relay = RelayAgentInformation::default();
+        relay.insert(RelayInfo::AgentCircuitId(b"eth0/1".to_vec()));
+        relay.insert(RelayInfo::LinkSelection(Ipv4Addr::new(10, 0, 5, 0)));
+        relay.insert(RelayInfo::ServerIdentifierOverride(Ipv4Addr::new(
+            10, 0, 5, 1,
+        )));
+        let mut msg = Message::default();
+        msg.opts_mut()
+            .insert(DhcpOption::RelayAgentInformation(relay));
+
+        assert_eq!(
+            parse_option82(&msg),
+            Some(Option82Data {
+                circuit_id: Some(b"eth0/1".to_vec()),
+                remote_id: None,
+                link_selection: Some(Ipv4Addr::new(10, 0, 5, 0)),
+                server_id_override: Some(Ipv4Addr::new(10, 0, 5, 1)),
+            })
+        );
+        assert_eq!(parse_option82(&Message::default()), None);
+    }
+
     #[test]
     fn test_extract_guid_with_valid_option() {
         use dhcproto::v4::UnknownOption;
@@ -262,6 +375,86 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_request_context_detects_ipxe_user_class() {
+        let ctx_with = |user_class: Option<&[u8]>| {
+            let mut msg = Message::default();
+            msg.set_opcode(Opcode::BootRequest);
+            msg.set_chaddr(&[0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff]);
+            msg.opts_mut()
+                .insert(DhcpOption::MessageType(MessageType::Discover));
+            if let Some(data) = user_class {
+                msg.opts_mut().insert(DhcpOption::UserClass(data.to_vec()));
+            }
+            RequestContext::from_message(&msg)
+        };
+
+        assert!(ctx_with(Some(b"iPXE")).is_ipxe);
+        assert!(
+            ctx_with(Some(b"\x05other\x04iPXE")).is_ipxe,
+            "RFC 3004 list"
+        );
+        assert_eq!(
+            ctx_with(Some(b"\x05other\x04iPXE")).user_class,
+            Some(UserClass(vec!["other".to_string(), "iPXE".to_string()]))
+        );
+        assert_eq!(ctx_with(None).user_class, None);
+        assert!(!ctx_with(None).is_ipxe, "plain PXE ROM");
+        assert!(!ctx_with(Some(b"PXEClient")).is_ipxe);
+        assert!(
+            !ctx_with(Some(b"\x09iPXE")).is_ipxe,
+            "truncated RFC 3004 entry"
+        );
+    }
+
+    #[test]
+    fn test_request_context_detects_pxe_client() {
+        let ctx_with = |vendor_class: Option<&[u8]>| {
+            let mut msg = Message::default();
+            msg.opts_mut()
+                .insert(DhcpOption::MessageType(MessageType::Discover));
+            if let Some(class) = vendor_class {
+                msg.opts_mut()
+                    .insert(DhcpOption::ClassIdentifier(class.to_vec()));
+            }
+            RequestContext::from_message(&msg)
+        };
+
+        assert!(ctx_with(Some(b"PXEClient:Arch:00007:UNDI:003016")).is_pxe_client);
+        assert!(!ctx_with(Some(b"HTTPClient:Arch:00016")).is_pxe_client);
+        assert!(!ctx_with(None).is_pxe_client);
+    }
+
+    #[test]
+    fn test_request_context_includes_client_id() {
+        let mut msg = Message::default();
+        msg.set_opcode(Opcode::BootRequest);
+        msg.set_chaddr(&[0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff]);
+        msg.opts_mut()
+            .insert(DhcpOption::MessageType(MessageType::Discover));
+        msg.opts_mut()
+            .insert(DhcpOption::ClientIdentifier(vec![0x01, 0x02, 0x03, 0xff]));
+
+        let ctx = RequestContext::from_message(&msg);
+        assert_eq!(ctx.client_id.as_deref(), Some("01:02:03:ff"));
+    }
+
+    #[test]
+    fn test_request_context_without_client_id() {
+        let mut msg = Message::default();
+        msg.set_opcode(Opcode::BootRequest);
+        msg.set_chaddr(&[0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff]);
+        msg.opts_mut()
+            .insert(DhcpOption::MessageType(MessageType::Discover));
+        msg.opts_mut().insert(DhcpOption::ClientIdentifier(vec![]));
+
+        let ctx = RequestContext::from_message(&msg);
+        assert!(
+            ctx.client_id.is_none(),
+            "Missing or empty option 61 should fall back to MAC keying"
+        );
+    }
+
     #[test]
     fn test_extract_server_identifier_present() {
         let mut msg = Message::default();
@@ -323,4 +516,21 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn test_request_context_detects_option_150_request() {
+        let mut msg = Message::default();
+        msg.set_opcode(Opcode::BootRequest);
+        msg.set_chaddr(&[0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff]);
+        msg.opts_mut().insert(DhcpOption::ParameterRequestList(vec![
+            OptionCode::SubnetMask,
+            OptionCode::from(150),
+            OptionCode::BootfileName,
+        ]));
+
+        let ctx = RequestContext::from_message(&msg);
+        assert!(ctx.requested_tftp_server_address);
+        assert!(ctx.requested_bootfile);
+        assert!(!ctx.requested_tftp_server);
+    }
 }
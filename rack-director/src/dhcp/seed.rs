@@ -0,0 +1,435 @@
+//! Seeding DHCP subnets and reservations from a config file at startup.
+//!
+//! `--config` points at a TOML file declaring `[[subnets]]` (each with its pools) and
+//! `[[reservations]]`. At startup every declared entry is reconciled into the database
+//! by name: entries that don't exist are created, and entries that do are left as the
+//! operator last edited them unless the entry sets `managed = true`, in which case the
+//! database is brought back in line with the file. Running it twice changes nothing.
+//!
+//! ```toml
+//! [[subnets]]
+//! name = "provisioning"
+//! subnet = "10.0.0.0/24"
+//! gateway = "10.0.0.1"
+//! dns_servers = ["10.0.0.2"]
+//! managed = true
+//!
+//! [[subnets.pools]]
+//! name = "dynamic"
+//! start = "10.0.0.100"
+//! end = "10.0.0.200"
+//!
+//! [[reservations]]
+//! subnet = "provisioning"
+//! mac = "aa:bb:cc:dd:ee:ff"
+//! ip = "10.0.0.10"
+//! hostname = "bastion"
+//! ```
+
+use std::net::Ipv4Addr;
+use std::path::Path;
+
+use anyhow::{Context, Result, anyhow};
+use serde::Deserialize;
+
+use super::store::{self, DhcpNetwork};
+use super::subnet_config::SubnetConfig;
+use crate::database::Connection;
+
+/// The declarative DHCP section of the startup config file.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SeedConfig {
+    #[serde(default)]
+    pub subnets: Vec<SubnetSeed>,
+    #[serde(default)]
+    pub reservations: Vec<ReservationSeed>,
+}
+
+/// A declared DHCP network, matched to `dhcp_networks` by `name`.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SubnetSeed {
+    pub name: String,
+    pub subnet: String,
+    pub gateway: String,
+    #[serde(default)]
+    pub dns_servers: Vec<String>,
+    /// Falls back to `--default-lease-duration` when omitted.
+    pub lease_duration: Option<u32>,
+    pub relay_agent_address: Option<String>,
+    #[serde(default)]
+    pub enable_autodiscovery: bool,
+    #[serde(default)]
+    pub pools: Vec<PoolSeed>,
+    /// Overwrite the existing network and its declared pools with this entry.
+    #[serde(default)]
+    pub managed: bool,
+}
+
+/// A declared address pool, matched within its subnet by `name`.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PoolSeed {
+    pub name: String,
+    pub start: String,
+    pub end: String,
+}
+
+/// A declared static reservation, matched by subnet name and MAC address.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ReservationSeed {
+    /// Name of the subnet the reservation belongs to.
+    pub subnet: String,
+    pub mac: String,
+    pub ip: String,
+    pub hostname: Option<String>,
+    /// Overwrite the existing reservation's address and hostname with this entry.
+    #[serde(default)]
+    pub managed: bool,
+}
+
+impl SeedConfig {
+    /// Read and validate a seed config from a TOML file.
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let config: SeedConfig = toml::from_str(&contents)
+            .with_context(|| format!("Failed to parse {}", path.display()))?;
+        config
+            .validate()
+            .with_context(|| format!("Invalid config in {}", path.display()))?;
+        Ok(config)
+    }
+
+    /// Reject addresses that would fail later when serving, before anything is written.
+    fn validate(&self) -> Result<()> {
+        for subnet in &self.subnets {
+            SubnetConfig {
+                subnet: &subnet.subnet,
+                gateway: &subnet.gateway,
+                dns_servers: &subnet.dns_servers,
+                lease_duration: subnet.lease_duration,
+                relay_agent_address: subnet.relay_agent_address.as_deref(),
+            }
+            .validate()
+            .map_err(|e| anyhow!("subnet {}: {}", subnet.name, e))?;
+            for pool in &subnet.pools {
+                parse_ipv4(&subnet.name, &pool.start)?;
+                parse_ipv4(&subnet.name, &pool.end)?;
+            }
+        }
+        for reservation in &self.reservations {
+            parse_ipv4(&reservation.subnet, &reservation.ip)?;
+        }
+        Ok(())
+    }
+}
+
+fn parse_ipv4(subnet: &str, address: &str) -> Result<Ipv4Addr> {
+    address
+        .parse()
+        .map_err(|_| anyhow!("subnet {}: invalid IPv4 address {}", subnet, address))
+}
+
+/// Reconcile the declared subnets, pools and reservations into the database.
+///
+/// Missing entries are created; existing ones are only updated when `managed` is set.
+/// Nothing is ever deleted. `default_lease_duration` applies to subnets that don't
+/// declare their own.
+pub async fn reconcile(
+    conn: &mut Connection,
+    config: &SeedConfig,
+    default_lease_duration: u32,
+) -> Result<()> {
+    for subnet in &config.subnets {
+        let network = reconcile_subnet(conn, subnet, default_lease_duration).await?;
+        reconcile_pools(conn, &network, subnet).await?;
+    }
+    for reservation in &config.reservations {
+        reconcile_reservation(conn, reservation).await?;
+    }
+    Ok(())
+}
+
+async fn reconcile_subnet(
+    conn: &mut Connection,
+    seed: &SubnetSeed,
+    default_lease_duration: u32,
+) -> Result<DhcpNetwork> {
+    let lease_duration = seed.lease_duration.unwrap_or(default_lease_duration);
+    let relay = seed.relay_agent_address.as_deref();
+
+    match store::get_network_by_name(conn, &seed.name).await? {
+        None => {
+            log::info!("Seeding DHCP network {} ({})", seed.name, seed.subnet);
+            store::create_network(
+                conn,
+                &seed.name,
+                &seed.subnet,
+                &seed.gateway,
+                &seed.dns_servers,
+                lease_duration,
+                relay,
+                seed.enable_autodiscovery,
+            )
+            .await
+        }
+        Some(network) if seed.managed => {
+            store::update_network(
+                conn,
+                network.id,
+                None,
+                Some(&seed.subnet),
+                Some(&seed.gateway),
+                Some(&seed.dns_servers),
+                Some(lease_duration),
+                Some(relay),
+                Some(seed.enable_autodiscovery),
+                None,
+            )
+            .await
+        }
+        Some(network) => Ok(network),
+    }
+}
+
+async fn reconcile_pools(
+    conn: &mut Connection,
+    network: &DhcpNetwork,
+    seed: &SubnetSeed,
+) -> Result<()> {
+    let existing = store::list_pools_for_network(conn, network.id).await?;
+
+    for pool in &seed.pools {
+        match existing.iter().find(|p| p.name == pool.name) {
+            None => {
+                store::create_pool(conn, network.id, &pool.name, &pool.start, &pool.end).await?;
+            }
+            Some(current)
+                if seed.managed
+                    && (current.range_start != pool.start || current.range_end != pool.end) =>
+            {
+                store::update_pool(conn, current.id, None, Some(&pool.start), Some(&pool.end))
+                    .await?;
+            }
+            Some(_) => {}
+        }
+    }
+    Ok(())
+}
+
+async fn reconcile_reservation(conn: &Connection, seed: &ReservationSeed) -> Result<()> {
+    let network = store::get_network_by_name(conn, &seed.subnet)
+        .await?
+        .ok_or_else(|| {
+            anyhow!(
+                "reservation for {} names unknown subnet {}",
+                seed.mac,
+                seed.subnet
+            )
+        })?;
+
+    let existing = store::get_static_reservation(conn, network.id, &seed.mac).await?;
+    if existing.is_none() || seed.managed {
+        store::create_or_update_static_reservation(
+            conn,
+            network.id,
+            &seed.mac,
+            &seed.ip,
+            seed.hostname.as_deref(),
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database;
+    use crate::test_connection_factory;
+
+    const CONFIG: &str = r#"
+        [[subnets]]
+        name = "provisioning"
+        subnet = "10.0.0.0/24"
+        gateway = "10.0.0.1"
+        dns_servers = ["10.0.0.2"]
+
+        [[subnets.pools]]
+        name = "dynamic"
+        start = "10.0.0.100"
+        end = "10.0.0.200"
+
+        [[subnets]]
+        name = "oob"
+        subnet = "10.1.0.0/24"
+        gateway = "10.1.0.1"
+        lease_duration = 600
+        managed = true
+
+        [[subnets.pools]]
+        name = "bmcs"
+        start = "10.1.0.100"
+        end = "10.1.0.150"
+
+        [[reservations]]
+        subnet = "provisioning"
+        mac = "aa:bb:cc:dd:ee:ff"
+        ip = "10.0.0.10"
+        hostname = "bastion"
+    "#;
+
+    fn config() -> SeedConfig {
+        let config: SeedConfig = toml::from_str(CONFIG).unwrap();
+        config.validate().unwrap();
+        config
+    }
+
+    #[tokio::test]
+    async fn test_reconcile_creates_declared_subnets() {
+        let factory = test_connection_factory!();
+        let mut conn = database::run_migrations(&factory).await.unwrap();
+
+        reconcile(&mut conn, &config(), 86400).await.unwrap();
+
+        let networks = store::list_networks(&conn).await.unwrap();
+        assert_eq!(networks.len(), 2);
+
+        let provisioning = store::get_network_by_name(&conn, "provisioning")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(provisioning.subnet, "10.0.0.0/24");
+        assert_eq!(provisioning.lease_duration, 86400);
+        let pools = store::list_pools_for_network(&conn, provisioning.id)
+            .await
+            .unwrap();
+        assert_eq!(pools.len(), 1);
+        assert_eq!(pools[0].range_start, "10.0.0.100");
+
+        let oob = store::get_network_by_name(&conn, "oob")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(oob.lease_duration, 600);
+
+        let reservation =
+            store::get_static_reservation(&conn, provisioning.id, "aa:bb:cc:dd:ee:ff")
+                .await
+                .unwrap()
+                .unwrap();
+        assert_eq!(reservation.ip_address, "10.0.0.10");
+        assert_eq!(reservation.hostname.as_deref(), Some("bastion"));
+    }
+
+    #[tokio::test]
+    async fn test_reconcile_is_idempotent() {
+        let factory = test_connection_factory!();
+        let mut conn = database::run_migrations(&factory).await.unwrap();
+
+        reconcile(&mut conn, &config(), 86400).await.unwrap();
+        reconcile(&mut conn, &config(), 86400).await.unwrap();
+
+        assert_eq!(store::list_networks(&conn).await.unwrap().len(), 2);
+        for network in store::list_networks(&conn).await.unwrap() {
+            let pools = store::list_pools_for_network(&conn, network.id)
+                .await
+                .unwrap();
+            assert_eq!(pools.len(), 1);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reconcile_only_overwrites_managed_entries() {
+        let factory = test_connection_factory!();
+        let mut conn = database::run_migrations(&factory).await.unwrap();
+        reconcile(&mut conn, &config(), 86400).await.unwrap();
+
+        // Operator edits both networks after the first start.
+        for (name, gateway) in [("provisioning", "10.0.0.254"), ("oob", "10.1.0.254")] {
+            let network = store::get_network_by_name(&conn, name)
+                .await
+                .unwrap()
+                .unwrap();
+            store::update_network(
+                &mut conn,
+                network.id,
+                None,
+                None,
+                Some(gateway),
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        }
+
+        reconcile(&mut conn, &config(), 86400).await.unwrap();
+
+        let provisioning = store::get_network_by_name(&conn, "provisioning")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(provisioning.gateway, "10.0.0.254", "unmanaged edit kept");
+        let oob = store::get_network_by_name(&conn, "oob")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(oob.gateway, "10.1.0.1", "managed entry restored");
+    }
+
+    #[test]
+    fn test_validate_rejects_bad_addresses() {
+        let config: SeedConfig = toml::from_str(
+            r#"
+            [[subnets]]
+            name = "broken"
+            subnet = "10.0.0.0/24"
+            gateway = "not-an-ip"
+            "#,
+        )
+        .unwrap();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_validate_rejects_gateway_outside_subnet() {
+        let config: SeedConfig = toml::from_str(
+            r#"
+            [[subnets]]
+            name = "broken"
+            subnet = "10.0.0.0/24"
+            gateway = "10.0.1.1"
+            "#,
+        )
+        .unwrap();
+        let err = config.validate().unwrap_err();
+        assert!(
+            err.to_string().contains("outside subnet"),
+            "unexpected error: {err}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reservation_for_unknown_subnet_fails() {
+        let factory = test_connection_factory!();
+        let mut conn = database::run_migrations(&factory).await.unwrap();
+        let config: SeedConfig = toml::from_str(
+            r#"
+            [[reservations]]
+            subnet = "missing"
+            mac = "aa:bb:cc:dd:ee:ff"
+            ip = "10.0.0.10"
+            "#,
+        )
+        .unwrap();
+
+        assert!(reconcile(&mut conn, &config, 86400).await.is_err());
+    }
+}
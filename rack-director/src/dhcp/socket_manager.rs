@@ -1,6 +1,7 @@
 use std::collections::HashMap;
-use std::net::Ipv4Addr;
+use std::net::{Ipv4Addr, SocketAddr};
 use std::sync::Arc;
+use std::time::Duration;
 
 use tokio::net::UdpSocket;
 use tokio::sync::{mpsc, oneshot, watch};
@@ -278,9 +279,20 @@ async fn pktinfo_recv_loop(
     table_rx: watch::Receiver<Arc<SocketTable>>,
 ) {
     let mut buf = vec![0u8; 1500];
+    let mut backoff = RecvBackoff::default();
     loop {
         match socket.recv_msg(&mut buf).await {
-            Ok((len, pkt_info)) => {
+            Ok((len, mut pkt_info)) => {
+                let Some(peer_addr) = ipv4_peer(pkt_info.addr_src) else {
+                    log::warn!(
+                        "DHCP {} dropping packet from non-IPv4 source {}",
+                        label,
+                        pkt_info.addr_src
+                    );
+                    backoff.reset();
+                    continue;
+                };
+                pkt_info.addr_src = peer_addr;
                 let data = buf[..len].to_vec();
                 let h = handler.clone();
                 let rx = table_rx.clone();
@@ -292,8 +304,14 @@ async fn pktinfo_recv_loop(
                     }
                 });
             }
-            Err(e) => log::error!("DHCP {} recv error: {}", label, e),
+            Err(e) => {
+                let delay = backoff.on_error();
+                log::error!("DHCP {} recv error (retrying in {:?}): {}", label, delay, e);
+                tokio::time::sleep(delay).await;
+                continue;
+            }
         }
+        backoff.reset();
     }
 }
 
@@ -301,6 +319,50 @@ async fn pktinfo_recv_loop(
 // Private helpers
 // ---------------------------------------------------------------------------
 
+/// Delay before retrying after the first consecutive recv error.
+const RECV_BACKOFF_INITIAL: Duration = Duration::from_millis(10);
+/// Upper bound on the recv-error retry delay.
+const RECV_BACKOFF_MAX: Duration = Duration::from_secs(5);
+
+/// Exponential backoff for persistent socket recv errors.
+///
+/// Without it a socket stuck in an error state (e.g. interface down) would
+/// spin a receive loop at full CPU, logging on every iteration.
+#[derive(Default)]
+struct RecvBackoff {
+    next: Option<Duration>,
+}
+
+impl RecvBackoff {
+    /// Record a recv error and return how long to wait before retrying.
+    fn on_error(&mut self) -> Duration {
+        let delay = self.next.unwrap_or(RECV_BACKOFF_INITIAL);
+        self.next = Some((delay * 2).min(RECV_BACKOFF_MAX));
+        delay
+    }
+
+    /// Reset after a successful receive.
+    fn reset(&mut self) {
+        self.next = None;
+    }
+}
+
+/// The IPv4 source address of a received packet, or `None` if it has none.
+///
+/// The DHCP sockets are IPv4-only, so a v6 source should never appear; if one
+/// does, the packet must not be handed to the DHCPv4 parser and reply path, which
+/// assume an IPv4 peer. A v4-mapped v6 address (`::ffff:a.b.c.d`) is still an IPv4
+/// peer and is unmapped rather than dropped.
+fn ipv4_peer(addr: SocketAddr) -> Option<SocketAddr> {
+    match addr {
+        SocketAddr::V4(_) => Some(addr),
+        SocketAddr::V6(v6) => v6
+            .ip()
+            .to_ipv4_mapped()
+            .map(|ip| SocketAddr::new(ip.into(), v6.port())),
+    }
+}
+
 /// Receive loop for a socket bound to a specific local interface IP. Handles
 /// unicast DHCP renewals from clients that already have a lease.
 ///
@@ -313,9 +375,19 @@ async fn per_network_recv_loop(
     table_rx: watch::Receiver<Arc<SocketTable>>,
 ) {
     let mut buf = vec![0u8; 1500];
+    let mut backoff = RecvBackoff::default();
     loop {
         match socket.recv_from(&mut buf).await {
-            Ok((len, peer_addr)) => {
+            Ok((len, src)) => {
+                let Some(peer_addr) = ipv4_peer(src) else {
+                    log::warn!(
+                        "DHCP per-network socket {} dropping packet from non-IPv4 source {}",
+                        local_ip,
+                        src
+                    );
+                    backoff.reset();
+                    continue;
+                };
                 let data = buf[..len].to_vec();
                 let h = handler.clone();
                 let rx = table_rx.clone();
@@ -327,8 +399,18 @@ async fn per_network_recv_loop(
                     }
                 });
             }
-            Err(e) => log::error!("DHCP per-network socket recv error: {}", e),
+            Err(e) => {
+                let delay = backoff.on_error();
+                log::error!(
+                    "DHCP per-network socket recv error (retrying in {:?}): {}",
+                    delay,
+                    e
+                );
+                tokio::time::sleep(delay).await;
+                continue;
+            }
         }
+        backoff.reset();
     }
 }
 
@@ -336,26 +418,18 @@ async fn per_network_recv_loop(
 ///
 /// For `L2` replies the socket bound to `local_ip` is used so replies egress
 /// on the correct interface and carry the server's source port (required by
-/// `dhclient`'s BPF filter: `udp src port 67 and dst port 68`).
+/// `dhclient`'s BPF filter: `udp src port 67 and dst port 68`). Every socket has
+/// `SO_BROADCAST` set, so the same one sends INIT-path broadcasts and unicast
+/// renewal replies alike.
 ///
 /// For `Relay` replies the server-id socket is used.
 async fn dispatch_reply(reply: DhcpReply, table_rx: &watch::Receiver<Arc<SocketTable>>) {
-    use std::net::{IpAddr, SocketAddr};
-
     match reply {
         DhcpReply::L2 {
             data,
             local_ip,
-            peer_addr,
+            dest,
         } => {
-            // RFC 2131: broadcast when the client has no IP yet (INIT/SELECTING),
-            // unicast when the client already has an IP (RENEWING/REBINDING).
-            let dest: SocketAddr = if peer_addr.ip() == IpAddr::V4(Ipv4Addr::UNSPECIFIED) {
-                SocketAddr::new(Ipv4Addr::BROADCAST.into(), 68)
-            } else {
-                peer_addr
-            };
-
             let socket = {
                 let table = table_rx.borrow();
                 table.network_sockets.get(&local_ip).cloned()
@@ -411,3 +485,45 @@ fn bind_l2_socket(local_ip: Ipv4Addr, port: u16) -> anyhow::Result<UdpSocket> {
     sock.set_nonblocking(true)?;
     Ok(UdpSocket::from_std(sock.into())?)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recv_backoff_doubles_up_to_cap() {
+        let mut backoff = RecvBackoff::default();
+
+        let delays: Vec<Duration> = (0..16).map(|_| backoff.on_error()).collect();
+
+        assert_eq!(delays[0], RECV_BACKOFF_INITIAL);
+        assert_eq!(delays[1], RECV_BACKOFF_INITIAL * 2);
+        assert_eq!(delays[2], RECV_BACKOFF_INITIAL * 4);
+        assert!(delays.windows(2).all(|w| w[1] >= w[0]));
+        assert_eq!(*delays.last().unwrap(), RECV_BACKOFF_MAX);
+    }
+
+    #[test]
+    fn test_ipv4_peer_classifies_source_family() {
+        let v4: SocketAddr = "10.0.0.5:68".parse().unwrap();
+        assert_eq!(ipv4_peer(v4), Some(v4));
+
+        let mapped: SocketAddr = "[::ffff:10.0.0.5]:68".parse().unwrap();
+        assert_eq!(ipv4_peer(mapped), Some(v4));
+
+        assert_eq!(ipv4_peer("[fe80::1]:546".parse().unwrap()), None);
+        assert_eq!(ipv4_peer("[::]:68".parse().unwrap()), None);
+    }
+
+    #[test]
+    fn test_recv_backoff_reset_after_success() {
+        let mut backoff = RecvBackoff::default();
+        for _ in 0..5 {
+            backoff.on_error();
+        }
+
+        backoff.reset();
+
+        assert_eq!(backoff.on_error(), RECV_BACKOFF_INITIAL);
+    }
+}
@@ -1,11 +1,22 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use chrono::{DateTime, Duration, Utc};
 use rusqlite::OptionalExtension;
 use serde::{Deserialize, Serialize};
-use std::net::Ipv4Addr;
+use std::net::{Ipv4Addr, Ipv6Addr};
 use uuid::Uuid;
 
-use crate::database::{Connection, FromRow};
+use super::subnet_config::SubnetConfig;
+use crate::database::{Connection, FromRow, from_db_time};
+
+/// Lease duration in seconds used for new networks when none is specified.
+///
+/// Matches the `dhcp_networks.lease_duration` column default. The HTTP layer
+/// can override it at startup via `--default-lease-duration`.
+pub const DEFAULT_LEASE_DURATION: u32 = 86400;
+
+/// Seconds an `offered` lease holds its address while waiting for the client's
+/// REQUEST, unless configured otherwise.
+pub const DEFAULT_OFFER_TTL: u32 = 60;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Lease {
@@ -18,6 +29,8 @@ pub struct Lease {
     pub state: LeaseState,
     pub hostname: Option<String>,
     pub network_id: Option<i64>,
+    /// DHCP client identifier (option 61) as colon-separated hex, if the client sent one.
+    pub client_id: Option<String>,
 }
 
 impl FromRow for Lease {
@@ -36,6 +49,7 @@ impl FromRow for Lease {
             state: state_str.parse().unwrap(),
             hostname: row.get("hostname")?,
             network_id: row.get("network_id")?,
+            client_id: row.get("client_id")?,
         })
     }
 }
@@ -90,30 +104,83 @@ pub struct DhcpNetwork {
     pub lease_duration: u32,
     pub relay_agent_address: Option<String>,
     pub enable_autodiscovery: bool,
+    /// When false, existing leases are still renewed but no new addresses are allocated.
+    pub enabled: bool,
+    /// Network whose addresses unknown and not-yet-provisioned devices receive when
+    /// they DHCP on this one.
+    pub quarantine_network_id: Option<i64>,
+    /// The director's address as this network's clients should see it, sent as
+    /// option 54 and siaddr. `None` uses the server-wide default.
+    pub server_identifier: Option<Ipv4Addr>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
+impl DhcpNetwork {
+    /// IPv4 DNS servers, advertised in DHCPv4 option 6.
+    pub fn dns_servers_v4(&self) -> Vec<Ipv4Addr> {
+        self.dns_servers
+            .iter()
+            .filter_map(|s| s.parse().ok())
+            .collect()
+    }
+
+    /// IPv6 DNS servers, which belong in the DHCPv6 recursive-DNS option (23) rather
+    /// than option 6.
+    pub fn dns_servers_v6(&self) -> Vec<Ipv6Addr> {
+        self.dns_servers
+            .iter()
+            .filter_map(|s| s.parse().ok())
+            .collect()
+    }
+}
+
 impl FromRow for DhcpNetwork {
     fn from_row(row: &rusqlite::Row) -> rusqlite::Result<Self> {
+        let name: String = row.get("name")?;
         let dns_servers_json: String = row.get("dns_servers")?;
-        let dns_servers: Vec<String> =
-            serde_json::from_str(&dns_servers_json).unwrap_or_else(|_| vec!["8.8.8.8".to_string()]);
-
+        let dns_servers: Vec<String> = match serde_json::from_str(&dns_servers_json) {
+            Ok(servers) => servers,
+            Err(e) => {
+                log::warn!(
+                    "Network {} has malformed dns_servers {:?} ({}); falling back to 8.8.8.8",
+                    name,
+                    dns_servers_json,
+                    e
+                );
+                vec!["8.8.8.8".to_string()]
+            }
+        };
+
+        let server_identifier_str: Option<String> = row.get("server_identifier")?;
+        let server_identifier = server_identifier_str.and_then(|s| match s.parse() {
+            Ok(ip) => Some(ip),
+            Err(_) => {
+                log::warn!(
+                    "Network {} has malformed server_identifier {:?}; using the default",
+                    name,
+                    s
+                );
+                None
+            }
+        });
         let created_at_str: String = row.get("created_at")?;
         let updated_at_str: String = row.get("updated_at")?;
 
         Ok(DhcpNetwork {
             id: row.get("id")?,
-            name: row.get("name")?,
+            name,
             subnet: row.get("subnet")?,
             gateway: row.get("gateway")?,
             dns_servers,
             lease_duration: row.get("lease_duration")?,
             relay_agent_address: row.get("relay_agent_address")?,
             enable_autodiscovery: row.get("enable_autodiscovery")?,
-            created_at: parse_datetime(&created_at_str).unwrap(),
-            updated_at: parse_datetime(&updated_at_str).unwrap(),
+            enabled: row.get("enabled")?,
+            quarantine_network_id: row.get("quarantine_network_id")?,
+            server_identifier,
+            created_at: from_db_time(&created_at_str).unwrap(),
+            updated_at: from_db_time(&updated_at_str).unwrap(),
         })
     }
 }
@@ -140,8 +207,8 @@ impl FromRow for DhcpPool {
             name: row.get("name")?,
             range_start: row.get("range_start")?,
             range_end: row.get("range_end")?,
-            created_at: parse_datetime(&created_at_str).unwrap(),
-            updated_at: parse_datetime(&updated_at_str).unwrap(),
+            created_at: from_db_time(&created_at_str).unwrap(),
+            updated_at: from_db_time(&updated_at_str).unwrap(),
         })
     }
 }
@@ -168,17 +235,39 @@ impl FromRow for StaticReservation {
             mac_address: row.get("mac_address")?,
             ip_address: row.get("ip_address")?,
             hostname: row.get("hostname")?,
-            created_at: parse_datetime(&created_at_str).unwrap(),
-            updated_at: parse_datetime(&updated_at_str).unwrap(),
+            created_at: from_db_time(&created_at_str).unwrap(),
+            updated_at: from_db_time(&updated_at_str).unwrap(),
         })
     }
 }
 
+/// Error returned by [`create_or_update_lease_with_network`] when a different MAC
+/// already holds an unexpired active lease on the address.
+///
+/// Carried inside `anyhow::Error`; callers that need to answer differently (the
+/// request handler NAKs) use `downcast_ref`.
+#[derive(Debug)]
+pub struct LeaseConflict {
+    pub ip: Ipv4Addr,
+    pub holder_mac: String,
+}
+
+impl std::fmt::Display for LeaseConflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} is already leased to {}", self.ip, self.holder_mac)
+    }
+}
+
+impl std::error::Error for LeaseConflict {}
+
 // ============================================================
 // Standalone functions accepting &Connection
 // ============================================================
 
 /// Create or update a DHCP lease with network context.
+///
+/// Fails with [`LeaseConflict`] rather than recording a second lease when another
+/// MAC holds an unexpired active lease on `ip`.
 pub async fn create_or_update_lease_with_network(
     conn: &Connection,
     mac: &str,
@@ -197,10 +286,18 @@ pub async fn create_or_update_lease_with_network(
     let device_uuid_copy = device_uuid.copied();
     let mac = mac.to_string();
 
+    if let Some(holder_mac) = find_active_lease_holder(conn, &ip_str, &mac, &now_str).await? {
+        return Err(LeaseConflict {
+            ip: *ip,
+            holder_mac,
+        }
+        .into());
+    }
+
     conn.execute(
         "INSERT INTO dhcp_leases
-            (mac_address, ip_address, device_uuid, lease_start, lease_end, state, network_id, updated_at)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+            (mac_address, ip_address, device_uuid, lease_start, lease_end, state, network_id, created_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?8)
          ON CONFLICT(mac_address) DO UPDATE SET
             ip_address = ?2,
             device_uuid = ?3,
@@ -216,11 +313,36 @@ pub async fn create_or_update_lease_with_network(
     Ok(())
 }
 
+/// MAC of an unexpired active lease on `ip` held by anyone other than `mac`.
+async fn find_active_lease_holder(
+    conn: &Connection,
+    ip: &str,
+    mac: &str,
+    now: &str,
+) -> Result<Option<String>> {
+    let holder = conn
+        .query_row(
+            "SELECT mac_address FROM dhcp_leases
+             WHERE ip_address = ?1 AND mac_address != ?2 AND state = ?3 AND lease_end > ?4
+             LIMIT 1",
+            (
+                ip.to_string(),
+                mac.to_string(),
+                LeaseState::Active.to_string(),
+                now.to_string(),
+            ),
+            |row| row.get(0),
+        )
+        .await
+        .optional()?;
+    Ok(holder)
+}
+
 /// Get lease by MAC address.
 pub async fn get_lease_by_mac(conn: &Connection, mac: &str) -> Result<Option<Lease>> {
     let lease = conn
         .query_row(
-            "SELECT id, mac_address, ip_address, device_uuid, lease_start, lease_end, state, hostname, network_id
+            "SELECT id, mac_address, ip_address, device_uuid, lease_start, lease_end, state, hostname, network_id, client_id
              FROM dhcp_leases WHERE mac_address = ?1",
             (mac.to_string(),),
             Lease::from_row,
@@ -231,11 +353,130 @@ pub async fn get_lease_by_mac(conn: &Connection, mac: &str) -> Result<Option<Lea
     Ok(lease)
 }
 
+/// Get the unexpired active lease on `ip`, if any.
+pub async fn get_active_lease_by_ip(conn: &Connection, ip: &Ipv4Addr) -> Result<Option<Lease>> {
+    let lease = conn
+        .query_row(
+            "SELECT id, mac_address, ip_address, device_uuid, lease_start, lease_end, state, hostname, network_id, client_id
+             FROM dhcp_leases WHERE ip_address = ?1 AND state = ?2 AND lease_end > ?3
+             ORDER BY lease_end DESC LIMIT 1",
+            (
+                ip.to_string(),
+                LeaseState::Active.to_string(),
+                Utc::now().to_rfc3339(),
+            ),
+            Lease::from_row,
+        )
+        .await
+        .optional()?;
+
+    Ok(lease)
+}
+
+/// MAC of the unexpired offered or active lease on `ip` held by anyone other than
+/// `mac`.
+///
+/// Used before confirming an offer that has lapsed, since its address may have
+/// been offered to someone else since.
+pub async fn find_lease_holder(
+    conn: &Connection,
+    ip: &Ipv4Addr,
+    mac: &str,
+) -> Result<Option<String>> {
+    let holder = conn
+        .query_row(
+            "SELECT mac_address FROM dhcp_leases
+             WHERE ip_address = ?1 AND mac_address != ?2 AND state IN (?3, ?4) AND lease_end > ?5
+             LIMIT 1",
+            (
+                ip.to_string(),
+                mac.to_string(),
+                LeaseState::Offered.to_string(),
+                LeaseState::Active.to_string(),
+                Utc::now().to_rfc3339(),
+            ),
+            |row| row.get(0),
+        )
+        .await
+        .optional()?;
+    Ok(holder)
+}
+
+/// Get lease by DHCP client identifier (option 61).
+pub async fn get_lease_by_client_id(conn: &Connection, client_id: &str) -> Result<Option<Lease>> {
+    let lease = conn
+        .query_row(
+            "SELECT id, mac_address, ip_address, device_uuid, lease_start, lease_end, state, hostname, network_id, client_id
+             FROM dhcp_leases WHERE client_id = ?1",
+            (client_id.to_string(),),
+            Lease::from_row,
+        )
+        .await
+        .optional()?;
+
+    Ok(lease)
+}
+
+/// Move the lease held by `client_id` on `network_id` onto `mac` so MAC-keyed
+/// lookups find it.
+///
+/// Per RFC 2131 a client identifier, when present, identifies the client
+/// instead of chaddr. If a lease on the network already carries `client_id` under
+/// a different MAC, any lease recorded for `mac` itself is dropped and the
+/// client-id lease is re-keyed to `mac`, both in one transaction. Leases on other
+/// networks are left alone. Returns `true` if a lease was moved.
+pub async fn adopt_lease_by_client_id(
+    conn: &mut Connection,
+    client_id: &str,
+    mac: &str,
+    network_id: i64,
+) -> Result<bool> {
+    let tx = conn.transaction().await?;
+    let lease = tx
+        .query_row(
+            "SELECT id, mac_address, ip_address, device_uuid, lease_start, lease_end, state, hostname, network_id, client_id
+             FROM dhcp_leases WHERE client_id = ?1 AND network_id = ?2",
+            (client_id.to_string(), network_id),
+            Lease::from_row,
+        )
+        .await
+        .optional()?;
+    let Some(lease) = lease.filter(|lease| lease.mac_address != mac) else {
+        tx.rollback().await?;
+        return Ok(false);
+    };
+
+    tx.execute(
+        "DELETE FROM dhcp_leases WHERE mac_address = ?1",
+        (mac.to_string(),),
+    )
+    .await?;
+    tx.execute(
+        "UPDATE dhcp_leases SET mac_address = ?1, updated_at = ?2 WHERE id = ?3",
+        (mac.to_string(), Utc::now().to_rfc3339(), lease.id),
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(true)
+}
+
+/// Record the client identifier (option 61) on the lease held by `mac`.
+pub async fn set_lease_client_id(conn: &Connection, mac: &str, client_id: &str) -> Result<()> {
+    conn.execute(
+        "UPDATE dhcp_leases SET client_id = ?1 WHERE mac_address = ?2",
+        (client_id.to_string(), mac.to_string()),
+    )
+    .await?;
+
+    Ok(())
+}
+
 /// Get lease by ID.
 pub async fn get_lease_by_id(conn: &Connection, id: i64) -> Result<Option<Lease>> {
     let lease = conn
         .query_row(
-            "SELECT id, mac_address, ip_address, device_uuid, lease_start, lease_end, state, hostname, network_id
+            "SELECT id, mac_address, ip_address, device_uuid, lease_start, lease_end, state, hostname, network_id, client_id
              FROM dhcp_leases WHERE id = ?1",
             (id,),
             Lease::from_row,
@@ -246,13 +487,18 @@ pub async fn get_lease_by_id(conn: &Connection, id: i64) -> Result<Option<Lease>
     Ok(lease)
 }
 
-/// Activate a lease (transition from Offered to Active).
-pub async fn activate_lease(conn: &Connection, mac: &str) -> Result<()> {
+/// Activate a lease (transition from Offered to Active), running it for
+/// `lease_duration` seconds from now. Renewals use this too, which extends the lease.
+pub async fn activate_lease(conn: &Connection, mac: &str, lease_duration: u32) -> Result<()> {
+    let now = Utc::now();
+    let lease_end = now + Duration::seconds(lease_duration as i64);
     conn.execute(
-        "UPDATE dhcp_leases SET state = ?1, updated_at = ?2 WHERE mac_address = ?3",
+        "UPDATE dhcp_leases SET state = ?1, lease_start = ?2, lease_end = ?3, updated_at = ?2
+         WHERE mac_address = ?4",
         (
             LeaseState::Active.to_string(),
-            Utc::now().to_rfc3339(),
+            now.to_rfc3339(),
+            lease_end.to_rfc3339(),
             mac.to_string(),
         ),
     )
@@ -280,7 +526,7 @@ pub async fn release_lease(conn: &Connection, mac: &str) -> Result<()> {
 pub async fn get_all_leases(conn: &Connection) -> Result<Vec<Lease>> {
     let leases = conn
         .query(
-            "SELECT id, mac_address, ip_address, device_uuid, lease_start, lease_end, state, hostname, network_id
+            "SELECT id, mac_address, ip_address, device_uuid, lease_start, lease_end, state, hostname, network_id, client_id
              FROM dhcp_leases ORDER BY updated_at DESC",
             (),
             Lease::from_row,
@@ -297,7 +543,7 @@ pub async fn find_lease_by_device_uuid(
 ) -> Result<Option<Lease>> {
     let lease = conn
         .query_row(
-            "SELECT id, mac_address, ip_address, device_uuid, lease_start, lease_end, state, hostname, network_id
+            "SELECT id, mac_address, ip_address, device_uuid, lease_start, lease_end, state, hostname, network_id, client_id
              FROM dhcp_leases WHERE device_uuid = ?1 AND state = 'active' ORDER BY lease_end DESC LIMIT 1",
             (*device_uuid,),
             Lease::from_row,
@@ -308,13 +554,65 @@ pub async fn find_lease_by_device_uuid(
     Ok(lease)
 }
 
+/// One row of the neighbor table built from active leases.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ArpEntry {
+    pub mac_address: String,
+    pub ip_address: String,
+    /// Device owning the MAC: the lease's device, else the device listing the MAC
+    /// among its network interfaces.
+    pub device_uuid: Option<Uuid>,
+    /// Name of the interface with this MAC, if a device reports one.
+    pub interface_name: Option<String>,
+}
+
+impl FromRow for ArpEntry {
+    fn from_row(row: &rusqlite::Row) -> rusqlite::Result<Self> {
+        Ok(ArpEntry {
+            mac_address: row.get("mac_address")?,
+            ip_address: row.get("ip_address")?,
+            device_uuid: row.get("device_uuid")?,
+            interface_name: row.get("interface_name")?,
+        })
+    }
+}
+
+/// MAC-to-IP mapping of every unexpired active lease, ordered by IP address.
+///
+/// Each lease is joined to the device interface carrying its MAC, so entries for
+/// leases handed out before the device was identified still name their device.
+pub async fn list_arp_entries(conn: &Connection) -> Result<Vec<ArpEntry>> {
+    let mut entries = conn
+        .query(
+            "WITH interfaces AS (
+                SELECT d.uuid AS device_uuid,
+                       json_extract(i.value, '$.mac_address') AS mac_address,
+                       json_extract(i.value, '$.interface_name') AS interface_name
+                FROM devices d, json_each(d.attributes, '$.network_interfaces') i
+             )
+             SELECT l.mac_address, l.ip_address,
+                    COALESCE(l.device_uuid, i.device_uuid) AS device_uuid,
+                    i.interface_name
+             FROM dhcp_leases l
+             LEFT JOIN interfaces i ON i.mac_address = l.mac_address
+             WHERE l.state = ?1 AND l.lease_end > ?2
+             GROUP BY l.mac_address",
+            (LeaseState::Active.to_string(), Utc::now().to_rfc3339()),
+            ArpEntry::from_row,
+        )
+        .await?;
+
+    entries.sort_by_key(|entry| entry.ip_address.parse::<Ipv4Addr>().ok());
+    Ok(entries)
+}
+
 // ========== Network CRUD Operations ==========
 
 /// Get a network by ID.
 pub async fn get_network(conn: &Connection, id: i64) -> Result<DhcpNetwork> {
     let network = conn
         .query_one(
-            "SELECT id, name, subnet, gateway, dns_servers, lease_duration, relay_agent_address, enable_autodiscovery, created_at, updated_at
+            "SELECT id, name, subnet, gateway, dns_servers, lease_duration, relay_agent_address, enable_autodiscovery, enabled, quarantine_network_id, server_identifier, created_at, updated_at
              FROM dhcp_networks WHERE id = ?1",
             (id,),
             DhcpNetwork::from_row,
@@ -333,7 +631,7 @@ pub async fn get_network_by_relay(
 
     let network = conn
         .query_row(
-            "SELECT id, name, subnet, gateway, dns_servers, lease_duration, relay_agent_address, enable_autodiscovery, created_at, updated_at
+            "SELECT id, name, subnet, gateway, dns_servers, lease_duration, relay_agent_address, enable_autodiscovery, enabled, quarantine_network_id, server_identifier, created_at, updated_at
              FROM dhcp_networks WHERE relay_agent_address IS ?1 OR (relay_agent_address IS NULL AND ?1 IS NULL)",
             (relay_str,),
             DhcpNetwork::from_row,
@@ -348,7 +646,7 @@ pub async fn get_network_by_relay(
 pub async fn get_network_by_name(conn: &Connection, name: &str) -> Result<Option<DhcpNetwork>> {
     let network = conn
         .query_row(
-            "SELECT id, name, subnet, gateway, dns_servers, lease_duration, relay_agent_address, enable_autodiscovery, created_at, updated_at
+            "SELECT id, name, subnet, gateway, dns_servers, lease_duration, relay_agent_address, enable_autodiscovery, enabled, quarantine_network_id, server_identifier, created_at, updated_at
              FROM dhcp_networks WHERE name = ?1",
             (name.to_string(),),
             DhcpNetwork::from_row,
@@ -367,7 +665,7 @@ pub async fn get_network_by_relay_string(
     let network = match relay_agent_address {
         None | Some("") => conn
             .query_row(
-                "SELECT id, name, subnet, gateway, dns_servers, lease_duration, relay_agent_address, enable_autodiscovery, created_at, updated_at
+                "SELECT id, name, subnet, gateway, dns_servers, lease_duration, relay_agent_address, enable_autodiscovery, enabled, quarantine_network_id, server_identifier, created_at, updated_at
                  FROM dhcp_networks WHERE relay_agent_address IS NULL OR relay_agent_address = ''",
                 (),
                 DhcpNetwork::from_row,
@@ -376,7 +674,7 @@ pub async fn get_network_by_relay_string(
             .optional()?,
         Some(addr) => conn
             .query_row(
-                "SELECT id, name, subnet, gateway, dns_servers, lease_duration, relay_agent_address, enable_autodiscovery, created_at, updated_at
+                "SELECT id, name, subnet, gateway, dns_servers, lease_duration, relay_agent_address, enable_autodiscovery, enabled, quarantine_network_id, server_identifier, created_at, updated_at
                  FROM dhcp_networks WHERE relay_agent_address = ?1",
                 (addr.to_string(),),
                 DhcpNetwork::from_row,
@@ -392,7 +690,7 @@ pub async fn get_network_by_relay_string(
 pub async fn list_networks(conn: &Connection) -> Result<Vec<DhcpNetwork>> {
     let networks = conn
         .query(
-            "SELECT id, name, subnet, gateway, dns_servers, lease_duration, relay_agent_address, enable_autodiscovery, created_at, updated_at
+            "SELECT id, name, subnet, gateway, dns_servers, lease_duration, relay_agent_address, enable_autodiscovery, enabled, quarantine_network_id, server_identifier, created_at, updated_at
              FROM dhcp_networks ORDER BY name",
             (),
             DhcpNetwork::from_row,
@@ -407,7 +705,7 @@ pub async fn get_l2_networks(conn: &Connection) -> Result<Vec<DhcpNetwork>> {
     let networks = conn
         .query(
             "SELECT id, name, subnet, gateway, dns_servers, lease_duration, \
-             relay_agent_address, enable_autodiscovery, created_at, updated_at \
+             relay_agent_address, enable_autodiscovery, enabled, quarantine_network_id, server_identifier, created_at, updated_at \
              FROM dhcp_networks WHERE relay_agent_address IS NULL",
             (),
             DhcpNetwork::from_row,
@@ -428,6 +726,14 @@ pub async fn create_network(
     relay_agent_address: Option<&str>,
     enable_autodiscovery: bool,
 ) -> Result<DhcpNetwork> {
+    SubnetConfig {
+        subnet,
+        gateway,
+        dns_servers,
+        lease_duration: Some(lease_duration),
+        relay_agent_address,
+    }
+    .validate()?;
     let dns_servers_json = serde_json::to_string(dns_servers)?;
     let now = Utc::now().to_rfc3339();
     let relay = relay_agent_address.map(|s| s.to_string());
@@ -440,13 +746,31 @@ pub async fn create_network(
     .await?;
 
     let id = conn.last_insert_rowid().await;
-    get_network(conn, id).await
+    let network = get_network(conn, id).await?;
+    warn_on_unserved_v6_dns(&network);
+    Ok(network)
+}
+
+/// Warn when a network lists IPv6 DNS servers that none of its clients will receive.
+///
+/// Networks are only served over DHCPv4, whose option 6 cannot carry IPv6 addresses.
+fn warn_on_unserved_v6_dns(network: &DhcpNetwork) {
+    let v6 = network.dns_servers_v6();
+    if !v6.is_empty() {
+        log::warn!(
+            "Network {} lists IPv6 DNS servers {:?}, but it only serves DHCPv4; they are not sent in option 6",
+            network.name,
+            v6
+        );
+    }
 }
 
 /// Update a network.
 ///
-/// All field updates are wrapped in a single transaction so that a partial
-/// failure cannot leave the network in an inconsistent state.
+/// The updated addressing is validated as a whole (e.g. a new subnet against the
+/// existing gateway) before anything is written. All field updates are wrapped in a
+/// single transaction so that a partial failure cannot leave the network in an
+/// inconsistent state.
 #[allow(clippy::too_many_arguments)]
 pub async fn update_network(
     conn: &mut Connection,
@@ -458,7 +782,17 @@ pub async fn update_network(
     lease_duration: Option<u32>,
     relay_agent_address: Option<Option<&str>>,
     enable_autodiscovery: Option<bool>,
+    enabled: Option<bool>,
 ) -> Result<DhcpNetwork> {
+    let current = get_network(conn, id).await?;
+    SubnetConfig {
+        subnet: subnet.unwrap_or(&current.subnet),
+        gateway: gateway.unwrap_or(&current.gateway),
+        dns_servers: dns_servers.unwrap_or(&current.dns_servers),
+        lease_duration: Some(lease_duration.unwrap_or(current.lease_duration)),
+        relay_agent_address: relay_agent_address.unwrap_or(current.relay_agent_address.as_deref()),
+    }
+    .validate()?;
     let now = Utc::now().to_rfc3339();
 
     let tx = conn.transaction().await?;
@@ -510,12 +844,85 @@ pub async fn update_network(
     if let Some(enable_autodiscovery) = enable_autodiscovery {
         tx.execute(
             "UPDATE dhcp_networks SET enable_autodiscovery = ?1, updated_at = ?2 WHERE id = ?3",
-            (enable_autodiscovery, now, id),
+            (enable_autodiscovery, now.clone(), id),
+        )
+        .await?;
+    }
+    if let Some(enabled) = enabled {
+        tx.execute(
+            "UPDATE dhcp_networks SET enabled = ?1, updated_at = ?2 WHERE id = ?3",
+            (enabled, now, id),
         )
         .await?;
     }
 
     tx.commit().await?;
+    let network = get_network(conn, id).await?;
+    if dns_servers.is_some() {
+        warn_on_unserved_v6_dns(&network);
+    }
+    Ok(network)
+}
+
+/// Point the network's unknown and unprovisioned devices at `quarantine_network_id`,
+/// or stop quarantining with `None`.
+///
+/// The quarantine network must exist and differ from the network itself.
+pub async fn set_quarantine_network(
+    conn: &Connection,
+    id: i64,
+    quarantine_network_id: Option<i64>,
+) -> Result<DhcpNetwork> {
+    if let Some(quarantine_id) = quarantine_network_id {
+        if quarantine_id == id {
+            return Err(anyhow::anyhow!("A network cannot quarantine to itself"));
+        }
+        get_network(conn, quarantine_id)
+            .await
+            .with_context(|| format!("Quarantine network {} not found", quarantine_id))?;
+    }
+
+    conn.execute(
+        "UPDATE dhcp_networks SET quarantine_network_id = ?1, updated_at = ?2 WHERE id = ?3",
+        (quarantine_network_id, Utc::now().to_rfc3339(), id),
+    )
+    .await?;
+    get_network(conn, id).await
+}
+
+/// Check that `ip` can be a server identifier, i.e. a unicast destination.
+pub fn validate_server_identifier(ip: Ipv4Addr) -> Result<()> {
+    if ip.is_unspecified() || ip.is_broadcast() || ip.is_multicast() {
+        return Err(anyhow::anyhow!(
+            "Server identifier {} is not a unicast address",
+            ip
+        ));
+    }
+    Ok(())
+}
+
+/// Set the address sent as the server identifier (option 54) and siaddr to the
+/// network's clients, or go back to the server-wide default with `None`.
+///
+/// The address must pass [`validate_server_identifier`].
+pub async fn set_server_identifier(
+    conn: &Connection,
+    id: i64,
+    server_identifier: Option<Ipv4Addr>,
+) -> Result<DhcpNetwork> {
+    if let Some(ip) = server_identifier {
+        validate_server_identifier(ip)?;
+    }
+
+    conn.execute(
+        "UPDATE dhcp_networks SET server_identifier = ?1, updated_at = ?2 WHERE id = ?3",
+        (
+            server_identifier.map(|ip| ip.to_string()),
+            Utc::now().to_rfc3339(),
+            id,
+        ),
+    )
+    .await?;
     get_network(conn, id).await
 }
 
@@ -670,6 +1077,28 @@ pub async fn list_static_reservations(
     Ok(reservations)
 }
 
+/// Find a static reservation in a network that already claims `mac` or `ip`.
+pub async fn find_conflicting_static_reservation(
+    conn: &Connection,
+    network_id: i64,
+    mac: &str,
+    ip: &str,
+) -> Result<Option<StaticReservation>> {
+    let reservation = conn
+        .query_row(
+            "SELECT id, network_id, mac_address, ip_address, hostname, created_at, updated_at
+             FROM dhcp_static_reservations
+             WHERE network_id = ?1 AND (mac_address = ?2 OR ip_address = ?3)
+             LIMIT 1",
+            (network_id, mac.to_string(), ip.to_string()),
+            StaticReservation::from_row,
+        )
+        .await
+        .optional()?;
+
+    Ok(reservation)
+}
+
 /// Create a static reservation.
 pub async fn create_static_reservation(
     conn: &Connection,
@@ -749,7 +1178,7 @@ pub async fn delete_static_reservations_by_mac(conn: &Connection, mac: &str) ->
 pub async fn get_leases_by_network(conn: &Connection, network_id: i64) -> Result<Vec<Lease>> {
     let leases = conn
         .query(
-            "SELECT id, mac_address, ip_address, device_uuid, lease_start, lease_end, state, hostname, network_id
+            "SELECT id, mac_address, ip_address, device_uuid, lease_start, lease_end, state, hostname, network_id, client_id
              FROM dhcp_leases WHERE network_id = ?1 ORDER BY updated_at DESC",
             (network_id,),
             Lease::from_row,
@@ -759,11 +1188,43 @@ pub async fn get_leases_by_network(conn: &Connection, network_id: i64) -> Result
     Ok(leases)
 }
 
-/// Delete all expired DHCP leases from the database.
-pub async fn delete_expired_leases(conn: &Connection) -> Result<u64> {
-    let now = Utc::now().to_rfc3339();
+/// Unexpired `offered` leases: addresses offered in a DISCOVER reply whose REQUEST
+/// has not arrived yet.
+pub async fn list_pending_offers(conn: &Connection) -> Result<Vec<Lease>> {
+    let leases = conn
+        .query(
+            "SELECT id, mac_address, ip_address, device_uuid, lease_start, lease_end, state, hostname, network_id, client_id
+             FROM dhcp_leases WHERE state = ?1 AND lease_end > ?2 ORDER BY lease_end",
+            (LeaseState::Offered.to_string(), Utc::now().to_rfc3339()),
+            Lease::from_row,
+        )
+        .await?;
+
+    Ok(leases)
+}
+
+/// Delete `offered` leases whose REQUEST never came before they expired.
+///
+/// Unlike ended leases these are not kept as history: the client never held the
+/// address.
+pub async fn delete_expired_offers(conn: &Connection) -> Result<u64> {
+    let deleted = conn
+        .execute(
+            "DELETE FROM dhcp_leases WHERE state = ?1 AND lease_end <= ?2",
+            (LeaseState::Offered.to_string(), Utc::now().to_rfc3339()),
+        )
+        .await?;
+    Ok(deleted as u64)
+}
+
+/// Delete DHCP leases that ended more than `retention` ago.
+///
+/// Leases stay in the table as history for `retention` after they expire or are
+/// released; pass `Duration::zero()` to delete them as soon as they end.
+pub async fn delete_expired_leases(conn: &Connection, retention: Duration) -> Result<u64> {
+    let cutoff = (Utc::now() - retention).to_rfc3339();
     let deleted = conn
-        .execute("DELETE FROM dhcp_leases WHERE lease_end < ?1", (now,))
+        .execute("DELETE FROM dhcp_leases WHERE lease_end < ?1", (cutoff,))
         .await?;
     Ok(deleted as u64)
 }
@@ -775,21 +1236,6 @@ pub fn format_mac(mac: &[u8]) -> String {
         .join(":")
 }
 
-/// Parse datetime from SQLite's CURRENT_TIMESTAMP format or RFC3339.
-fn parse_datetime(s: &str) -> Result<DateTime<Utc>> {
-    // Try RFC3339 first
-    if let Ok(dt) = DateTime::parse_from_rfc3339(s) {
-        return Ok(dt.with_timezone(&Utc));
-    }
-
-    // Try SQLite's CURRENT_TIMESTAMP format: "YYYY-MM-DD HH:MM:SS"
-    if let Ok(dt) = chrono::NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S") {
-        return Ok(DateTime::from_naive_utc_and_offset(dt, Utc));
-    }
-
-    Err(anyhow::anyhow!("Failed to parse datetime: {}", s))
-}
-
 #[cfg(test)]
 mod tests {
     use crate::{database::DatabaseConnectionFactory, test_database_path};
@@ -820,6 +1266,80 @@ mod tests {
         (db, network.id)
     }
 
+    #[tokio::test]
+    async fn test_adopt_lease_by_client_id_stays_on_network() {
+        let (mut db, network_id) = setup_db_with_network(test_database_path!()).await;
+        let other = create_network(
+            &db,
+            "Other Network",
+            "10.1.0.0/24",
+            "10.1.0.1",
+            &[],
+            86400,
+            None,
+            false,
+        )
+        .await
+        .unwrap();
+        create_or_update_lease_with_network(
+            &db,
+            "aa:bb:cc:dd:ee:01",
+            &"10.0.0.100".parse().unwrap(),
+            None,
+            LeaseState::Active,
+            3600,
+            network_id,
+        )
+        .await
+        .unwrap();
+        set_lease_client_id(&db, "aa:bb:cc:dd:ee:01", "01aabbccddee01")
+            .await
+            .unwrap();
+        create_or_update_lease_with_network(
+            &db,
+            "aa:bb:cc:dd:ee:02",
+            &"10.0.0.101".parse().unwrap(),
+            None,
+            LeaseState::Offered,
+            3600,
+            network_id,
+        )
+        .await
+        .unwrap();
+
+        // Another network's lookup leaves the lease where it is
+        assert!(
+            !adopt_lease_by_client_id(&mut db, "01aabbccddee01", "aa:bb:cc:dd:ee:02", other.id)
+                .await
+                .unwrap()
+        );
+        assert!(
+            get_lease_by_mac(&db, "aa:bb:cc:dd:ee:01")
+                .await
+                .unwrap()
+                .is_some()
+        );
+
+        // On its own network the lease moves and the new MAC's old lease is dropped
+        assert!(
+            adopt_lease_by_client_id(&mut db, "01aabbccddee01", "aa:bb:cc:dd:ee:02", network_id)
+                .await
+                .unwrap()
+        );
+        assert!(
+            get_lease_by_mac(&db, "aa:bb:cc:dd:ee:01")
+                .await
+                .unwrap()
+                .is_none()
+        );
+        let moved = get_lease_by_mac(&db, "aa:bb:cc:dd:ee:02")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(moved.ip_address, "10.0.0.100");
+        assert_eq!(moved.client_id.as_deref(), Some("01aabbccddee01"));
+    }
+
     #[tokio::test]
     async fn test_get_network() {
         let (db, network_id) = setup_db_with_network(test_database_path!()).await;
@@ -829,6 +1349,321 @@ mod tests {
         assert_eq!(network.gateway, "10.0.0.1");
     }
 
+    #[tokio::test]
+    async fn test_lease_refused_when_ip_actively_held_by_other_mac() {
+        let (db, network_id) = setup_db_with_network(test_database_path!()).await;
+        let ip: Ipv4Addr = "10.0.0.100".parse().unwrap();
+
+        create_or_update_lease_with_network(
+            &db,
+            "aa:bb:cc:dd:ee:01",
+            &ip,
+            None,
+            LeaseState::Active,
+            3600,
+            network_id,
+        )
+        .await
+        .unwrap();
+
+        let err = create_or_update_lease_with_network(
+            &db,
+            "aa:bb:cc:dd:ee:02",
+            &ip,
+            None,
+            LeaseState::Offered,
+            3600,
+            network_id,
+        )
+        .await
+        .unwrap_err();
+        let conflict = err.downcast_ref::<LeaseConflict>().expect("LeaseConflict");
+        assert_eq!(conflict.holder_mac, "aa:bb:cc:dd:ee:01");
+        assert!(
+            get_lease_by_mac(&db, "aa:bb:cc:dd:ee:02")
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        // The holder renewing its own lease is not a conflict
+        create_or_update_lease_with_network(
+            &db,
+            "aa:bb:cc:dd:ee:01",
+            &ip,
+            None,
+            LeaseState::Active,
+            3600,
+            network_id,
+        )
+        .await
+        .unwrap();
+
+        // Neither is an address whose active lease has lapsed
+        db.execute(
+            "UPDATE dhcp_leases SET lease_end = ?1",
+            ((Utc::now() - Duration::seconds(1)).to_rfc3339(),),
+        )
+        .await
+        .unwrap();
+        create_or_update_lease_with_network(
+            &db,
+            "aa:bb:cc:dd:ee:02",
+            &ip,
+            None,
+            LeaseState::Offered,
+            3600,
+            network_id,
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_new_lease_created_at_is_rfc3339() {
+        let (db, network_id) = setup_db_with_network(test_database_path!()).await;
+        create_or_update_lease_with_network(
+            &db,
+            "aa:bb:cc:dd:ee:01",
+            &"10.0.0.100".parse().unwrap(),
+            None,
+            LeaseState::Offered,
+            3600,
+            network_id,
+        )
+        .await
+        .unwrap();
+
+        // Not the column's legacy CURRENT_TIMESTAMP default
+        let created_at: String = db
+            .query_one("SELECT created_at FROM dhcp_leases", (), |row| row.get(0))
+            .await
+            .unwrap();
+        assert!(
+            chrono::DateTime::parse_from_rfc3339(&created_at).is_ok(),
+            "{created_at}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_active_lease_by_ip() {
+        let (db, network_id) = setup_db_with_network(test_database_path!()).await;
+        let ip: Ipv4Addr = "10.0.0.100".parse().unwrap();
+
+        create_or_update_lease_with_network(
+            &db,
+            "aa:bb:cc:dd:ee:01",
+            &ip,
+            None,
+            LeaseState::Offered,
+            3600,
+            network_id,
+        )
+        .await
+        .unwrap();
+        assert!(get_active_lease_by_ip(&db, &ip).await.unwrap().is_none());
+
+        activate_lease(&db, "aa:bb:cc:dd:ee:01", 3600)
+            .await
+            .unwrap();
+        let lease = get_active_lease_by_ip(&db, &ip).await.unwrap().unwrap();
+        assert_eq!(lease.mac_address, "aa:bb:cc:dd:ee:01");
+
+        db.execute(
+            "UPDATE dhcp_leases SET lease_end = ?1",
+            ((Utc::now() - Duration::seconds(1)).to_rfc3339(),),
+        )
+        .await
+        .unwrap();
+        assert!(get_active_lease_by_ip(&db, &ip).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_list_arp_entries() {
+        use crate::director::{Architecture, store as director_store};
+
+        let (db, network_id) = setup_db_with_network(test_database_path!()).await;
+        let device = Uuid::new_v4();
+        director_store::register_device(&db, &device, Architecture::X86_64)
+            .await
+            .unwrap();
+        director_store::set_network_interfaces(
+            &db,
+            &device,
+            &[common::device_attributes::NetworkInterface {
+                interface_name: "eth1".to_string(),
+                mac_address: "aa:bb:cc:dd:ee:02".to_string(),
+                ip_address: None,
+                network_id: None,
+                speed_mbps: None,
+                disabled: false,
+                warning_label: None,
+            }],
+        )
+        .await
+        .unwrap();
+
+        for (mac, ip, state) in [
+            ("aa:bb:cc:dd:ee:01", "10.0.0.120", LeaseState::Active),
+            ("aa:bb:cc:dd:ee:02", "10.0.0.100", LeaseState::Active),
+            ("aa:bb:cc:dd:ee:03", "10.0.0.110", LeaseState::Offered),
+        ] {
+            create_or_update_lease_with_network(
+                &db,
+                mac,
+                &ip.parse().unwrap(),
+                None,
+                state,
+                3600,
+                network_id,
+            )
+            .await
+            .unwrap();
+        }
+
+        let entries = list_arp_entries(&db).await.unwrap();
+        assert_eq!(
+            entries,
+            vec![
+                ArpEntry {
+                    mac_address: "aa:bb:cc:dd:ee:02".to_string(),
+                    ip_address: "10.0.0.100".to_string(),
+                    device_uuid: Some(device),
+                    interface_name: Some("eth1".to_string()),
+                },
+                ArpEntry {
+                    mac_address: "aa:bb:cc:dd:ee:01".to_string(),
+                    ip_address: "10.0.0.120".to_string(),
+                    device_uuid: None,
+                    interface_name: None,
+                },
+            ],
+            "offered leases are left out and entries are ordered by IP"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_malformed_dns_servers_rejected_on_insert() {
+        let (db, network_id) = setup_db_with_network(test_database_path!()).await;
+
+        for bad in ["8.8.8.8", "not json", r#"{"primary":"8.8.8.8"}"#] {
+            let result = db
+                .execute(
+                    "INSERT INTO dhcp_networks (name, subnet, gateway, dns_servers, created_at, updated_at)
+                     VALUES ('Bad', '10.9.0.0/24', '10.9.0.1', ?1, '', '')",
+                    (bad.to_string(),),
+                )
+                .await;
+            assert!(result.is_err(), "{bad:?} should be rejected");
+        }
+
+        let result = db
+            .execute(
+                "UPDATE dhcp_networks SET dns_servers = '[' WHERE id = ?1",
+                (network_id,),
+            )
+            .await;
+        assert!(result.is_err());
+
+        let result = create_network(
+            &db,
+            "Bad Entry",
+            "10.9.0.0/24",
+            "10.9.0.1",
+            &["dns.example.com".to_string()],
+            86400,
+            None,
+            false,
+        )
+        .await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_create_and_update_reject_gateway_outside_subnet() {
+        let (mut db, network_id) = setup_db_with_network(test_database_path!()).await;
+
+        let result = create_network(
+            &db,
+            "Stray Gateway",
+            "10.9.0.0/24",
+            "10.8.0.1",
+            &[],
+            86400,
+            Some("10.9.0.2"),
+            false,
+        )
+        .await;
+        let err = result.unwrap_err();
+        assert!(err.to_string().contains("outside subnet"), "{err}");
+
+        // Moving the subnet away from the existing gateway is caught before writing
+        let result = update_network(
+            &mut db,
+            network_id,
+            None,
+            Some("10.5.0.0/24"),
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+        .await;
+        assert!(result.is_err());
+        let network = get_network(&db, network_id).await.unwrap();
+        assert_eq!(network.subnet, "10.0.0.0/24");
+
+        // ...but succeeds when the gateway moves with it
+        let network = update_network(
+            &mut db,
+            network_id,
+            None,
+            Some("10.5.0.0/24"),
+            Some("10.5.0.1"),
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+        assert_eq!(network.gateway, "10.5.0.1");
+    }
+
+    #[tokio::test]
+    async fn test_mixed_dns_servers_split_by_family() {
+        let (db, _) = setup_db_with_network(test_database_path!()).await;
+
+        let network = create_network(
+            &db,
+            "Dual Stack",
+            "10.9.0.0/24",
+            "10.9.0.1",
+            &[
+                "10.9.0.53".to_string(),
+                "2001:db8::53".to_string(),
+                "1.1.1.1".to_string(),
+            ],
+            86400,
+            None,
+            false,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            network.dns_servers_v4(),
+            vec![Ipv4Addr::new(10, 9, 0, 53), Ipv4Addr::new(1, 1, 1, 1)]
+        );
+        assert_eq!(
+            network.dns_servers_v6(),
+            vec!["2001:db8::53".parse::<Ipv6Addr>().unwrap()]
+        );
+    }
+
     #[tokio::test]
     async fn test_get_l2_networks() {
         let (db, _) = setup_db_with_network(test_database_path!()).await;
@@ -923,7 +1758,7 @@ mod tests {
         .await
         .unwrap();
 
-        let deleted = delete_expired_leases(&db).await.unwrap();
+        let deleted = delete_expired_leases(&db, Duration::zero()).await.unwrap();
         assert_eq!(deleted, 1);
 
         let leases = get_leases_by_network(&db, network_id).await.unwrap();
@@ -943,7 +1778,7 @@ mod tests {
         .await
         .unwrap();
 
-        let deleted = delete_expired_leases(&db).await.unwrap();
+        let deleted = delete_expired_leases(&db, Duration::zero()).await.unwrap();
         assert_eq!(deleted, 0);
 
         let leases = get_leases_by_network(&db, network_id).await.unwrap();
@@ -978,7 +1813,7 @@ mod tests {
         .await
         .unwrap();
 
-        let deleted = delete_expired_leases(&db).await.unwrap();
+        let deleted = delete_expired_leases(&db, Duration::zero()).await.unwrap();
         assert_eq!(deleted, 2);
 
         let leases = get_leases_by_network(&db, network_id).await.unwrap();
@@ -990,7 +1825,7 @@ mod tests {
     async fn test_delete_expired_leases_no_leases() {
         let (db, _network_id) = setup_db_with_network(test_database_path!()).await;
 
-        let deleted = delete_expired_leases(&db).await.unwrap();
+        let deleted = delete_expired_leases(&db, Duration::zero()).await.unwrap();
         assert_eq!(deleted, 0);
     }
 
@@ -1006,13 +1841,103 @@ mod tests {
         .await
         .unwrap();
 
-        let deleted = delete_expired_leases(&db).await.unwrap();
+        let deleted = delete_expired_leases(&db, Duration::zero()).await.unwrap();
         assert_eq!(deleted, 1);
 
         let leases = get_leases_by_network(&db, network_id).await.unwrap();
         assert_eq!(leases.len(), 0);
     }
 
+    #[tokio::test]
+    async fn test_delete_expired_leases_keeps_leases_within_retention() {
+        let (db, network_id) = setup_db_with_network(test_database_path!()).await;
+
+        let old = (Utc::now() - Duration::days(10)).to_rfc3339();
+        let recent = (Utc::now() - Duration::days(2)).to_rfc3339();
+        for (mac, ip, ended) in [
+            ("aa:bb:cc:dd:ee:07", "10.0.0.107", old),
+            ("aa:bb:cc:dd:ee:08", "10.0.0.108", recent),
+        ] {
+            db.execute(
+                "INSERT INTO dhcp_leases (mac_address, ip_address, lease_start, lease_end, state, network_id) VALUES (?1, ?2, ?3, ?3, 'released', ?4)",
+                (mac.to_string(), ip.to_string(), ended, network_id),
+            )
+            .await
+            .unwrap();
+        }
+
+        let deleted = delete_expired_leases(&db, Duration::days(7)).await.unwrap();
+        assert_eq!(deleted, 1);
+
+        let leases = get_leases_by_network(&db, network_id).await.unwrap();
+        assert_eq!(leases.len(), 1);
+        assert_eq!(leases[0].mac_address, "aa:bb:cc:dd:ee:08");
+    }
+
+    #[tokio::test]
+    async fn test_pending_offers_listed_until_expired() {
+        let (db, network_id) = setup_db_with_network(test_database_path!()).await;
+
+        let now = Utc::now();
+        for (mac, ip, state, ends) in [
+            (
+                "aa:bb:cc:dd:ee:11",
+                "10.0.0.111",
+                "offered",
+                now + Duration::seconds(60),
+            ),
+            (
+                "aa:bb:cc:dd:ee:12",
+                "10.0.0.112",
+                "offered",
+                now - Duration::seconds(1),
+            ),
+            (
+                "aa:bb:cc:dd:ee:13",
+                "10.0.0.113",
+                "active",
+                now - Duration::hours(1),
+            ),
+        ] {
+            db.execute(
+                "INSERT INTO dhcp_leases (mac_address, ip_address, lease_start, lease_end, state, network_id) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                (mac.to_string(), ip.to_string(), now.to_rfc3339(), ends.to_rfc3339(), state.to_string(), network_id),
+            )
+            .await
+            .unwrap();
+        }
+
+        let pending = list_pending_offers(&db).await.unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].mac_address, "aa:bb:cc:dd:ee:11");
+        let ip: Ipv4Addr = "10.0.0.111".parse().unwrap();
+        assert_eq!(
+            find_lease_holder(&db, &ip, "aa:bb:cc:dd:ee:99")
+                .await
+                .unwrap()
+                .as_deref(),
+            Some("aa:bb:cc:dd:ee:11"),
+            "a pending offer holds its address"
+        );
+        assert_eq!(
+            find_lease_holder(&db, &ip, "aa:bb:cc:dd:ee:11")
+                .await
+                .unwrap(),
+            None
+        );
+
+        // Only the lapsed offer goes; the ended active lease is history
+        assert_eq!(delete_expired_offers(&db).await.unwrap(), 1);
+        let macs: Vec<String> = get_leases_by_network(&db, network_id)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|l| l.mac_address)
+            .collect();
+        assert_eq!(macs.len(), 2);
+        assert!(!macs.contains(&"aa:bb:cc:dd:ee:12".to_string()));
+    }
+
     #[tokio::test]
     async fn test_create_or_update_static_reservation_insert() {
         let (db, network_id) = setup_db_with_network(test_database_path!()).await;
@@ -1185,4 +2110,30 @@ mod tests {
             .unwrap();
         assert_eq!(deleted, 0);
     }
+
+    #[tokio::test]
+    async fn test_find_conflicting_static_reservation() {
+        let (db, network_id) = setup_db_with_network(test_database_path!()).await;
+        create_static_reservation(&db, network_id, "aa:bb:cc:dd:ee:07", "10.0.0.50", None)
+            .await
+            .unwrap();
+
+        let by_mac =
+            find_conflicting_static_reservation(&db, network_id, "aa:bb:cc:dd:ee:07", "10.0.0.51")
+                .await
+                .unwrap();
+        assert_eq!(by_mac.unwrap().ip_address, "10.0.0.50");
+
+        let by_ip =
+            find_conflicting_static_reservation(&db, network_id, "aa:bb:cc:dd:ee:08", "10.0.0.50")
+                .await
+                .unwrap();
+        assert_eq!(by_ip.unwrap().mac_address, "aa:bb:cc:dd:ee:07");
+
+        let none =
+            find_conflicting_static_reservation(&db, network_id, "aa:bb:cc:dd:ee:08", "10.0.0.51")
+                .await
+                .unwrap();
+        assert!(none.is_none());
+    }
 }
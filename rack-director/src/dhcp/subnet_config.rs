@@ -0,0 +1,329 @@
+//! Validation of a DHCP network's addressing.
+//!
+//! Every path that creates or changes a network (the UI handlers, config seeding and
+//! the store itself) checks the same rules here, so a network accepted by one is
+//! accepted by all of them.
+
+use common::Ipv4Subnet;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr};
+
+/// Longest lease a network may hand out: one year, in seconds.
+pub const MAX_LEASE_DURATION: u32 = 31_536_000;
+
+/// The addressing of a DHCP network, as given by a caller before it is stored.
+#[derive(Debug, Clone, Copy)]
+pub struct SubnetConfig<'a> {
+    /// CIDR, e.g. `10.0.0.0/24`.
+    pub subnet: &'a str,
+    pub gateway: &'a str,
+    /// IPv4 or IPv6 resolver addresses.
+    pub dns_servers: &'a [String],
+    /// `None` when the server default applies.
+    pub lease_duration: Option<u32>,
+    /// `None` or an empty string for a network served directly on L2.
+    pub relay_agent_address: Option<&'a str>,
+}
+
+/// One reason a [`SubnetConfig`] was rejected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SubnetConfigError {
+    InvalidSubnet { subnet: String, reason: String },
+    InvalidGateway(String),
+    GatewayOutsideSubnet { gateway: String, subnet: String },
+    InvalidDnsServer(String),
+    LeaseDurationOutOfRange(u32),
+    InvalidRelayAgent(String),
+}
+
+impl SubnetConfigError {
+    /// The request field the error belongs to.
+    pub fn field(&self) -> &'static str {
+        match self {
+            Self::InvalidSubnet { .. } => "subnet",
+            Self::InvalidGateway(_) | Self::GatewayOutsideSubnet { .. } => "gateway",
+            Self::InvalidDnsServer(_) => "dns_servers",
+            Self::LeaseDurationOutOfRange(_) => "lease_duration",
+            Self::InvalidRelayAgent(_) => "relay_agent_address",
+        }
+    }
+
+    /// A message describing the error, suitable for showing next to its field.
+    pub fn message(&self) -> String {
+        match self {
+            Self::InvalidSubnet { reason, .. } => format!("Invalid CIDR notation: {}", reason),
+            Self::InvalidGateway(_) | Self::InvalidRelayAgent(_) => {
+                "Must be a valid IPv4 address".to_string()
+            }
+            Self::GatewayOutsideSubnet { .. } => {
+                "IP address must be within the subnet range".to_string()
+            }
+            Self::InvalidDnsServer(server) => format!("'{}' is not a valid IP address", server),
+            Self::LeaseDurationOutOfRange(_) => format!(
+                "Lease duration must be between 1 and {} seconds",
+                MAX_LEASE_DURATION
+            ),
+        }
+    }
+}
+
+impl fmt::Display for SubnetConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidSubnet { subnet, reason } => {
+                write!(f, "invalid subnet {}: {}", subnet, reason)
+            }
+            Self::InvalidGateway(gateway) => write!(f, "invalid gateway {}", gateway),
+            Self::GatewayOutsideSubnet { gateway, subnet } => {
+                write!(f, "gateway {} is outside subnet {}", gateway, subnet)
+            }
+            Self::InvalidDnsServer(server) => write!(f, "invalid DNS server address {}", server),
+            Self::LeaseDurationOutOfRange(secs) => write!(
+                f,
+                "lease duration {} is not between 1 and {} seconds",
+                secs, MAX_LEASE_DURATION
+            ),
+            Self::InvalidRelayAgent(relay) => write!(f, "invalid relay agent address {}", relay),
+        }
+    }
+}
+
+/// Every reason a [`SubnetConfig`] was rejected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubnetConfigErrors(pub Vec<SubnetConfigError>);
+
+impl fmt::Display for SubnetConfigErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, error) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, "; ")?;
+            }
+            write!(f, "{}", error)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for SubnetConfigErrors {}
+
+impl SubnetConfig<'_> {
+    /// Check every field, returning all problems found rather than just the first.
+    ///
+    /// The gateway is only checked against the subnet when the subnet itself parses.
+    pub fn validate(&self) -> Result<(), SubnetConfigErrors> {
+        let mut errors = Vec::new();
+
+        let subnet = match self.subnet.parse::<Ipv4Subnet>() {
+            Ok(subnet) => Some(subnet),
+            Err(e) => {
+                errors.push(SubnetConfigError::InvalidSubnet {
+                    subnet: self.subnet.to_string(),
+                    reason: e.to_string(),
+                });
+                None
+            }
+        };
+
+        match self.gateway.parse::<Ipv4Addr>() {
+            Err(_) => errors.push(SubnetConfigError::InvalidGateway(self.gateway.to_string())),
+            Ok(gateway) => {
+                if let Some(subnet) = &subnet
+                    && !subnet.ip_in_range(gateway)
+                {
+                    errors.push(SubnetConfigError::GatewayOutsideSubnet {
+                        gateway: self.gateway.to_string(),
+                        subnet: self.subnet.to_string(),
+                    });
+                }
+            }
+        }
+
+        for server in self.dns_servers {
+            if server.parse::<IpAddr>().is_err() {
+                errors.push(SubnetConfigError::InvalidDnsServer(server.clone()));
+            }
+        }
+
+        if let Some(secs) = self.lease_duration
+            && !(1..=MAX_LEASE_DURATION).contains(&secs)
+        {
+            errors.push(SubnetConfigError::LeaseDurationOutOfRange(secs));
+        }
+
+        if let Some(relay) = self.relay_agent_address
+            && !relay.is_empty()
+            && relay.parse::<Ipv4Addr>().is_err()
+        {
+            errors.push(SubnetConfigError::InvalidRelayAgent(relay.to_string()));
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(SubnetConfigErrors(errors))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dns(servers: &[&str]) -> Vec<String> {
+        servers.iter().map(|s| s.to_string()).collect()
+    }
+
+    fn valid(dns_servers: &[String]) -> SubnetConfig<'_> {
+        SubnetConfig {
+            subnet: "10.0.0.0/24",
+            gateway: "10.0.0.1",
+            dns_servers,
+            lease_duration: Some(86400),
+            relay_agent_address: None,
+        }
+    }
+
+    fn errors_of(config: SubnetConfig) -> Vec<SubnetConfigError> {
+        config.validate().unwrap_err().0
+    }
+
+    #[test]
+    fn test_valid_config() {
+        let dns = dns(&["8.8.8.8", "2001:4860:4860::8888"]);
+        assert!(valid(&dns).validate().is_ok());
+        assert!(
+            SubnetConfig {
+                lease_duration: None,
+                relay_agent_address: Some("10.1.0.1"),
+                ..valid(&dns)
+            }
+            .validate()
+            .is_ok()
+        );
+        assert!(
+            SubnetConfig {
+                relay_agent_address: Some(""),
+                ..valid(&dns)
+            }
+            .validate()
+            .is_ok(),
+            "an empty relay means no relay"
+        );
+        assert!(valid(&[]).validate().is_ok());
+    }
+
+    #[test]
+    fn test_invalid_subnet() {
+        let errors = errors_of(SubnetConfig {
+            subnet: "10.0.0.0/33",
+            ..valid(&[])
+        });
+        assert_eq!(
+            errors.len(),
+            1,
+            "gateway is not checked against a bad subnet"
+        );
+        assert!(
+            matches!(&errors[0], SubnetConfigError::InvalidSubnet { subnet, .. } if subnet == "10.0.0.0/33")
+        );
+        assert_eq!(errors[0].field(), "subnet");
+    }
+
+    #[test]
+    fn test_invalid_gateway() {
+        let errors = errors_of(SubnetConfig {
+            gateway: "gateway",
+            ..valid(&[])
+        });
+        assert_eq!(
+            errors,
+            vec![SubnetConfigError::InvalidGateway("gateway".to_string())]
+        );
+        assert_eq!(errors[0].field(), "gateway");
+    }
+
+    #[test]
+    fn test_gateway_outside_subnet() {
+        let errors = errors_of(SubnetConfig {
+            gateway: "10.0.1.1",
+            ..valid(&[])
+        });
+        assert_eq!(
+            errors,
+            vec![SubnetConfigError::GatewayOutsideSubnet {
+                gateway: "10.0.1.1".to_string(),
+                subnet: "10.0.0.0/24".to_string(),
+            }]
+        );
+        assert_eq!(errors[0].field(), "gateway");
+    }
+
+    #[test]
+    fn test_invalid_dns_server() {
+        let dns = dns(&["8.8.8.8", "dns.example.com"]);
+        let errors = errors_of(valid(&dns));
+        assert_eq!(
+            errors,
+            vec![SubnetConfigError::InvalidDnsServer(
+                "dns.example.com".to_string()
+            )]
+        );
+        assert_eq!(errors[0].field(), "dns_servers");
+    }
+
+    #[test]
+    fn test_lease_duration_out_of_range() {
+        for secs in [0, MAX_LEASE_DURATION + 1] {
+            let errors = errors_of(SubnetConfig {
+                lease_duration: Some(secs),
+                ..valid(&[])
+            });
+            assert_eq!(
+                errors,
+                vec![SubnetConfigError::LeaseDurationOutOfRange(secs)]
+            );
+            assert_eq!(errors[0].field(), "lease_duration");
+        }
+        assert!(
+            SubnetConfig {
+                lease_duration: Some(MAX_LEASE_DURATION),
+                ..valid(&[])
+            }
+            .validate()
+            .is_ok()
+        );
+    }
+
+    #[test]
+    fn test_invalid_relay_agent() {
+        let errors = errors_of(SubnetConfig {
+            relay_agent_address: Some("relay"),
+            ..valid(&[])
+        });
+        assert_eq!(
+            errors,
+            vec![SubnetConfigError::InvalidRelayAgent("relay".to_string())]
+        );
+        assert_eq!(errors[0].field(), "relay_agent_address");
+    }
+
+    #[test]
+    fn test_reports_every_error() {
+        let dns = dns(&["nope"]);
+        let errors = SubnetConfig {
+            subnet: "10.0.0.0/24",
+            gateway: "192.168.0.1",
+            dns_servers: &dns,
+            lease_duration: Some(0),
+            relay_agent_address: Some("x"),
+        }
+        .validate()
+        .unwrap_err();
+        assert_eq!(errors.0.len(), 4);
+        assert_eq!(
+            errors.to_string(),
+            "gateway 192.168.0.1 is outside subnet 10.0.0.0/24; invalid DNS server address nope; \
+             lease duration 0 is not between 1 and 31536000 seconds; invalid relay agent address x"
+        );
+    }
+}
@@ -0,0 +1,437 @@
+//! Handling iPXE boot requests.
+//!
+//! A boot request may adopt an unknown device, advance its plan, record the address it
+//! booted from and then resolve what it should boot. Firmware retries and multi-NIC
+//! devices routinely send several requests for the same new UUID at once; handled
+//! independently, two of them could both try to register the device, or one could read
+//! the boot target between another's registration and its discovery transition and
+//! send the device to sleep. `handle_boot_request` runs the whole sequence under a
+//! per-UUID lock so every request sees the completed result of the one before it,
+//! while requests from different devices proceed in parallel.
+
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock, Mutex};
+
+use log::{info, warn};
+use uuid::Uuid;
+
+use crate::dhcp;
+use crate::lifecycle::DeviceLifecycle;
+use crate::plans::actions::BootTarget;
+
+/// One lock per UUID with a boot request in flight.
+static BOOT_LOCKS: LazyLock<Mutex<HashMap<Uuid, Arc<tokio::sync::Mutex<()>>>>> =
+    LazyLock::new(Default::default);
+
+/// Wait for exclusive boot handling of `uuid`.
+async fn lock_boot(uuid: &Uuid) -> tokio::sync::OwnedMutexGuard<()> {
+    let lock = {
+        let mut locks = BOOT_LOCKS.lock().unwrap();
+        // Forget locks that nobody holds or waits on, so the map only grows with
+        // the number of devices booting at once.
+        locks.retain(|_, lock| Arc::strong_count(lock) > 1);
+        locks.entry(*uuid).or_default().clone()
+    };
+    lock.lock_owned().await
+}
+
+impl<'a> super::Director<'a> {
+    /// Handle a boot request from `uuid` and return what it should boot.
+    ///
+    /// An unknown device booting from `mac_address` is adopted (registered and put
+    /// into discovery) when it is a pending device or its lease's network has
+    /// autodiscovery enabled. The plan is then advanced for the boot event and the
+    /// lease address recorded before the boot target is resolved. Serving a chained
+    /// boot stage is recorded so that the device's next boot moves on to the
+    /// following stage. Everything runs under a lock on `uuid`, so concurrent
+    /// requests for the same device are handled one after another. No power
+    /// operation runs under it: the device is booting, so adoption skips the kick.
+    pub async fn handle_boot_request(
+        &self,
+        uuid: &Uuid,
+        mac_address: Option<&str>,
+        sleep_secs: u64,
+    ) -> anyhow::Result<BootTarget> {
+        let _guard = lock_boot(uuid).await;
+
+        match mac_address {
+            Some(mac) => {
+                if !self.device_exists(uuid).await? && self.should_adopt(uuid, mac).await? {
+                    self.adopt_device(uuid, Some(mac)).await;
+                }
+            }
+            None => warn!("Missing MAC address"),
+        }
+
+        // on_boot() may advance the plan, so it must run before the boot target is read.
+        if let Err(e) = self.on_boot(uuid).await {
+            warn!("Failed to handle boot event for {}: {:?}", uuid, e);
+        }
+
+        if let Some(mac) = mac_address {
+            self.record_lease_address(uuid, mac).await;
+        }
+
+        let target = self.next_boot_target(uuid, sleep_secs).await?;
+        // Serving a chain script starts its stage; the device's next boot ends it.
+        if matches!(target, BootTarget::Chain { .. }) {
+            self.mark_chain_served(uuid).await?;
+        }
+        Ok(target)
+    }
+
+    /// Whether an unknown device booting from `mac` should be adopted.
+    async fn should_adopt(&self, uuid: &Uuid, mac: &str) -> anyhow::Result<bool> {
+        if self.find_pending_device_by_mac(mac).await?.is_some() {
+            info!("Found pending device {}. Starting discovery.", uuid);
+            return Ok(true);
+        }
+
+        let Some(lease) = dhcp::store::get_lease_by_mac(self.conn, mac).await? else {
+            warn!("MAC {:?} does not have a DHCP lease", mac);
+            return Ok(false);
+        };
+        let Some(network_id) = lease.network_id else {
+            warn!("DHCP Lease does not have a network ID");
+            return Ok(false);
+        };
+
+        let network = dhcp::store::get_network(self.conn, network_id).await?;
+        if network.enable_autodiscovery {
+            info!(
+                "Found new device {} on network with autodiscovery enabled. Adopting and starting discovery.",
+                uuid
+            );
+        }
+        Ok(network.enable_autodiscovery)
+    }
+
+    /// Registers a new device and starts the discovery lifecycle.
+    ///
+    /// Registers the device, links it to any pending device for `mac_address`,
+    /// creates a static DHCP reservation from its active lease and starts the
+    /// Unprovisioned transition. The device is adopted while it boots, so no power
+    /// kick is issued. All errors are logged but not propagated, making adoption
+    /// best-effort.
+    pub async fn adopt_device(&self, device_uuid: &Uuid, mac_address: Option<&str>) {
+        if let Err(e) = self
+            .register_device(device_uuid, super::Architecture::X86_64)
+            .await
+        {
+            warn!("Couldn't register device {}: {}", device_uuid, e);
+            return;
+        }
+
+        if let Some(mac) = mac_address {
+            if let Err(e) = self.complete_pending_device(mac, device_uuid).await {
+                warn!("Couldn't complete pending device: {}", e);
+            }
+            self.reserve_lease_address(device_uuid, mac).await;
+        }
+
+        if let Err(e) = self
+            .start_transition(device_uuid, DeviceLifecycle::Unprovisioned, false)
+            .await
+        {
+            warn!(
+                "Couldn't start discovery transition for {}: {}",
+                device_uuid, e
+            );
+        }
+    }
+
+    /// Create a static DHCP reservation for the address `mac` currently leases.
+    async fn reserve_lease_address(&self, device_uuid: &Uuid, mac: &str) {
+        let Ok(Some(lease)) = dhcp::store::get_lease_by_mac(self.conn, mac).await else {
+            return;
+        };
+        let Some(network_id) = lease.network_id else {
+            return;
+        };
+
+        let hostname = self
+            .get_device(device_uuid)
+            .await
+            .ok()
+            .and_then(|d| d.attributes.hostname);
+
+        if let Err(e) = dhcp::store::create_or_update_static_reservation(
+            self.conn,
+            network_id,
+            mac,
+            &lease.ip_address,
+            hostname.as_deref(),
+        )
+        .await
+        {
+            warn!(
+                "Couldn't create static DHCP reservation for device {}: {}",
+                device_uuid, e
+            );
+        }
+    }
+
+    /// Store the address leased to `mac` on the device's matching interface.
+    async fn record_lease_address(&self, uuid: &Uuid, mac: &str) {
+        let Ok(Some(lease)) = dhcp::store::get_lease_by_mac(self.conn, mac).await else {
+            return;
+        };
+        info!(
+            "Found DHCP lease for device {}: MAC {} IP {}",
+            uuid, lease.mac_address, lease.ip_address
+        );
+
+        // This will also create the interface if it doesn't exist yet
+        if let Err(e) = self
+            .set_device_ip_address(uuid, &lease.ip_address, &lease.mac_address)
+            .await
+        {
+            warn!("Couldn't store IP address for device {uuid}: {e}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+    use std::sync::Arc;
+
+    use super::super::Director;
+    use super::*;
+    use crate::database::{self, Connection, ConnectionFactory};
+    use crate::test_connection_factory;
+
+    fn test_uuid() -> Uuid {
+        Uuid::parse_str("550e8400-e29b-41d4-a716-446655440001").unwrap()
+    }
+
+    /// Create a test network and return its id.
+    async fn create_test_network(conn: &Connection, enable_autodiscovery: bool) -> i64 {
+        let network = dhcp::store::create_network(
+            conn,
+            "Test Network",
+            "10.0.0.0/24",
+            "10.0.0.1",
+            &["8.8.8.8".to_string()],
+            86400,
+            None,
+            enable_autodiscovery,
+        )
+        .await
+        .unwrap();
+
+        dhcp::store::create_pool(conn, network.id, "Test Pool", "10.0.0.100", "10.0.0.200")
+            .await
+            .unwrap();
+
+        network.id
+    }
+
+    async fn create_test_lease(conn: &Connection, mac: &str, ip: &str, network_id: i64) {
+        let ip: Ipv4Addr = ip.parse().unwrap();
+        dhcp::store::create_or_update_lease_with_network(
+            conn,
+            mac,
+            &ip,
+            None,
+            crate::dhcp::LeaseState::Active,
+            3600,
+            network_id,
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_adopt_device() {
+        let factory = test_connection_factory!();
+        let conn = database::run_migrations(&factory).await.unwrap();
+        let director = Director::new(&conn);
+        let uuid = test_uuid();
+
+        assert!(!director.device_exists(&uuid).await.unwrap());
+
+        director.adopt_device(&uuid, None).await;
+
+        assert!(director.device_exists(&uuid).await.unwrap());
+        let lifecycle = director.get_device_lifecycle(&uuid).await.unwrap();
+        assert_eq!(lifecycle, Some(DeviceLifecycle::New));
+        let plan = director.get_active_plan_for_device(&uuid).await.unwrap();
+        assert!(plan.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_adopt_device_completes_pending_device() {
+        let factory = test_connection_factory!();
+        let conn = database::run_migrations(&factory).await.unwrap();
+        let network_id = create_test_network(&conn, false).await;
+        let director = Director::new(&conn);
+        let uuid = test_uuid();
+        let mac = "aa:bb:cc:dd:ee:ff";
+
+        director
+            .create_pending_device(mac, network_id)
+            .await
+            .unwrap();
+
+        director.adopt_device(&uuid, Some(mac)).await;
+
+        assert!(director.device_exists(&uuid).await.unwrap());
+        let pending = director.find_pending_device_by_mac(mac).await.unwrap();
+        assert!(pending.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_adopt_device_creates_static_reservation() {
+        let factory = test_connection_factory!();
+        let conn = database::run_migrations(&factory).await.unwrap();
+        let network_id = create_test_network(&conn, false).await;
+        let director = Director::new(&conn);
+        let uuid = test_uuid();
+        let mac = "aa:bb:cc:dd:ee:11";
+        create_test_lease(&conn, mac, "10.0.0.150", network_id).await;
+
+        director.adopt_device(&uuid, Some(mac)).await;
+
+        let reservation = dhcp::store::get_static_reservation(&conn, network_id, mac)
+            .await
+            .unwrap()
+            .expect("reservation should be created");
+        assert_eq!(reservation.mac_address, mac);
+        assert_eq!(reservation.ip_address, "10.0.0.150");
+        assert_eq!(reservation.network_id, network_id);
+
+        let device = director.get_device(&uuid).await.unwrap();
+        assert_eq!(reservation.hostname, device.attributes.hostname);
+    }
+
+    #[tokio::test]
+    async fn test_handle_boot_request_ignores_unknown_network() {
+        let factory = test_connection_factory!();
+        let conn = database::run_migrations(&factory).await.unwrap();
+        let network_id = create_test_network(&conn, false).await;
+        let director = Director::new(&conn);
+        let uuid = test_uuid();
+        let mac = "aa:bb:cc:dd:ee:ff";
+        create_test_lease(&conn, mac, "10.0.0.150", network_id).await;
+
+        let target = director
+            .handle_boot_request(&uuid, Some(mac), 600)
+            .await
+            .unwrap();
+
+        assert!(matches!(target, BootTarget::SleepReboot { seconds: 600 }));
+        assert!(!director.device_exists(&uuid).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_consecutive_boots_advance_through_chained_stages() {
+        let factory = test_connection_factory!();
+        let conn = database::run_migrations(&factory).await.unwrap();
+        let director = Director::new(&conn);
+        let uuid = test_uuid();
+        director
+            .register_device(&uuid, super::super::Architecture::X86_64)
+            .await
+            .unwrap();
+        crate::lifecycle::store::update_device_lifecycle(
+            &conn,
+            &uuid,
+            DeviceLifecycle::Provisioned,
+        )
+        .await
+        .unwrap();
+        let stages = [
+            "/stages/discover.ipxe",
+            "http://stages.example/install.ipxe",
+        ];
+        let plan = crate::plans::Plan::new(
+            uuid,
+            stages
+                .iter()
+                .map(|url| crate::plans::Action::ChainScript {
+                    url: url.to_string(),
+                })
+                .collect(),
+        );
+        director.create_plan(&plan).await.unwrap();
+
+        for stage in stages {
+            let target = director
+                .handle_boot_request(&uuid, None, 600)
+                .await
+                .unwrap();
+            let BootTarget::Chain { url } = &target else {
+                panic!("Expected Chain for stage {stage}, got {target:?}");
+            };
+            assert_eq!(url, stage);
+        }
+
+        // The boot after the last stage completes the plan
+        let target = director
+            .handle_boot_request(&uuid, None, 600)
+            .await
+            .unwrap();
+        assert!(matches!(target, BootTarget::LocalDisk), "got {target:?}");
+        let plan = director.get_active_plan_for_device(&uuid).await.unwrap();
+        assert!(plan.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_boot_requests_adopt_device_once() {
+        let factory = Arc::new(test_connection_factory!());
+        let conn = database::run_migrations(factory.as_ref()).await.unwrap();
+        let network_id = create_test_network(&conn, true).await;
+        let uuid = test_uuid();
+        let mac = "aa:bb:cc:dd:ee:ff";
+        create_test_lease(&conn, mac, "10.0.0.150", network_id).await;
+
+        let mut tasks = tokio::task::JoinSet::new();
+        for _ in 0..16 {
+            let factory = factory.clone();
+            tasks.spawn(async move {
+                let conn = factory.open().await.unwrap();
+                Director::new(&conn)
+                    .handle_boot_request(&uuid, Some(mac), 600)
+                    .await
+            });
+        }
+
+        while let Some(result) = tasks.join_next().await {
+            let target = result.unwrap().expect("boot request should succeed");
+            assert!(
+                matches!(target, BootTarget::AgentImage { .. }),
+                "every request should see the discovery plan, got {target:?}"
+            );
+        }
+
+        let devices: i64 = conn
+            .query_one("SELECT COUNT(*) FROM devices", (), |row| row.get(0))
+            .await
+            .unwrap();
+        assert_eq!(devices, 1);
+        let transitions = crate::lifecycle::store::get_transitions_for_device(&conn, &uuid, true)
+            .await
+            .unwrap();
+        assert_eq!(transitions.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_boot_lock_is_per_device() {
+        let first = Uuid::parse_str("550e8400-e29b-41d4-a716-4466554401b1").unwrap();
+        let second = Uuid::parse_str("550e8400-e29b-41d4-a716-4466554401b2").unwrap();
+
+        let _held = lock_boot(&first).await;
+        let other = tokio::time::timeout(std::time::Duration::from_secs(1), lock_boot(&second))
+            .await
+            .expect("another device's boot should not wait");
+        drop(other);
+
+        assert!(
+            tokio::time::timeout(std::time::Duration::from_millis(50), lock_boot(&first))
+                .await
+                .is_err(),
+            "the same device's boot should wait"
+        );
+    }
+}
@@ -0,0 +1,130 @@
+//! Pluggable boot-target decisions.
+//!
+//! Deciding what a device boots is split in two. The [`super::Director`] gathers
+//! everything known about the device into a [`DeviceContext`]: whether it is in the
+//! inventory, its lifecycle state, tags, one-shot overrides and the target its active
+//! plan calls for (resolving plan actions needs the database, so that part is done up
+//! front). A [`BootPolicy`] then turns the context into a [`BootTarget`] without any
+//! I/O. Deployments with their own rules supply a policy through
+//! [`super::Director::with_boot_policy`]; [`DefaultBootPolicy`] implements the
+//! built-in ones.
+
+use std::collections::BTreeMap;
+
+use uuid::Uuid;
+
+use crate::lifecycle::DeviceLifecycle;
+use crate::plans::actions::{self, BootTarget};
+
+/// What a [`BootPolicy`] knows about a booting device.
+#[derive(Debug, Clone)]
+pub struct DeviceContext {
+    pub uuid: Uuid,
+    /// Whether the device is registered. Unknown devices have no lifecycle, tags
+    /// or plan.
+    pub in_inventory: bool,
+    pub lifecycle: Option<DeviceLifecycle>,
+    /// Operator-assigned tags, by key.
+    pub tags: BTreeMap<String, String>,
+    /// A one-shot rediscovery was requested for this boot.
+    pub rediscover: bool,
+    /// What the current action of the device's active plan boots, if it has one.
+    /// Not resolved when `rediscover` is set.
+    pub plan_target: Option<BootTarget>,
+    /// How long devices with nothing to do should sleep before retrying PXE.
+    pub sleep_secs: u64,
+}
+
+/// Decides what a device boots.
+pub trait BootPolicy: Send + Sync {
+    /// The boot target for `device`. Agent image targets get the device's image
+    /// set filled in afterwards.
+    fn decide(&self, device: &DeviceContext) -> BootTarget;
+}
+
+/// The built-in rules.
+///
+/// In order: a pending rediscovery boots the hardware scan; unknown devices sleep
+/// and retry; the active plan's target wins next; otherwise provisioned devices
+/// boot local disk, broken and removed devices are held for an operator, and the
+/// rest sleep and retry until a plan is available.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DefaultBootPolicy;
+
+impl BootPolicy for DefaultBootPolicy {
+    fn decide(&self, device: &DeviceContext) -> BootTarget {
+        if device.rediscover {
+            return actions::rediscovery_boot_target();
+        }
+        if !device.in_inventory {
+            return BootTarget::SleepReboot {
+                seconds: device.sleep_secs,
+            };
+        }
+        if let Some(target) = &device.plan_target {
+            return target.clone();
+        }
+        match device.lifecycle {
+            Some(DeviceLifecycle::Provisioned) => BootTarget::LocalDisk,
+            // Retrying on a timer won't help these; wait for an operator instead.
+            Some(DeviceLifecycle::Broken) => BootTarget::Hold {
+                reason: "device is marked broken; start a lifecycle transition to recover it"
+                    .to_string(),
+            },
+            Some(DeviceLifecycle::Removed) => BootTarget::Hold {
+                reason: "device has been removed from the rack".to_string(),
+            },
+            _ => BootTarget::SleepReboot {
+                seconds: device.sleep_secs,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context(lifecycle: Option<DeviceLifecycle>) -> DeviceContext {
+        DeviceContext {
+            uuid: Uuid::nil(),
+            in_inventory: true,
+            lifecycle,
+            tags: BTreeMap::new(),
+            rediscover: false,
+            plan_target: None,
+            sleep_secs: 600,
+        }
+    }
+
+    #[test]
+    fn test_default_policy_precedence() {
+        let policy = DefaultBootPolicy;
+
+        let mut ctx = context(Some(DeviceLifecycle::Provisioned));
+        assert!(matches!(policy.decide(&ctx), BootTarget::LocalDisk));
+
+        ctx.plan_target = Some(BootTarget::Chain {
+            url: "http://example.com/boot.ipxe".to_string(),
+        });
+        assert!(matches!(policy.decide(&ctx), BootTarget::Chain { .. }));
+
+        ctx.rediscover = true;
+        assert!(matches!(
+            policy.decide(&ctx),
+            BootTarget::AgentImage { action, .. } if action == "device-scan"
+        ));
+
+        let mut unknown = context(None);
+        unknown.in_inventory = false;
+        assert!(matches!(
+            policy.decide(&unknown),
+            BootTarget::SleepReboot { seconds: 600 }
+        ));
+
+        assert!(matches!(
+            policy.decide(&context(Some(DeviceLifecycle::Removed))),
+            BootTarget::Hold { .. }
+        ));
+    }
+}
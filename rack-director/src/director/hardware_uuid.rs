@@ -0,0 +1,100 @@
+//! Canonical form of the hardware (SMBIOS system) UUID that identifies a device.
+//!
+//! A machine reports the same UUID twice while booting: as raw bytes in DHCP
+//! option 97 from its PXE ROM, and as text in iPXE's `${uuid}` when it chains
+//! `/cnc/ipxe`. Both go through [`normalize_uuid`] so they key the same
+//! `devices` row.
+//!
+//! SMBIOS 2.6+ stores the first three fields little-endian, which is what option
+//! 97 carries. Firmware predating that, and some that ignore it, sends the bytes
+//! in RFC 4122 (big-endian) order instead. The two can't be told apart from the
+//! bytes alone, so lookups by a DHCP-reported UUID also try [`byte_swapped`].
+
+use uuid::Uuid;
+
+/// A hardware UUID as the client reported it.
+#[derive(Debug, Clone, Copy)]
+pub enum RawUuid<'a> {
+    /// The 16 SMBIOS bytes, e.g. option 97 without its type byte.
+    Smbios(&'a [u8]),
+    /// Text in any form iPXE or an operator might use: hyphenated, braced,
+    /// `urn:uuid:` or bare hex, in either case.
+    Text(&'a str),
+}
+
+/// Parse a reported UUID into its canonical form, whose `Display` is the
+/// lowercase hyphenated string stored in the database. Returns `None` if the
+/// input is not a UUID.
+pub fn normalize_uuid(raw: RawUuid<'_>) -> Option<Uuid> {
+    match raw {
+        RawUuid::Smbios(bytes) => bytes.try_into().ok().map(Uuid::from_bytes_le),
+        RawUuid::Text(text) => Uuid::parse_str(text.trim()).ok(),
+    }
+}
+
+/// `uuid` as it would read had its bytes been sent in the other byte order.
+///
+/// Swaps the three little-endian fields of the SMBIOS layout; applying it twice
+/// returns the original.
+pub fn byte_swapped(uuid: &Uuid) -> Uuid {
+    Uuid::from_bytes_le(*uuid.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CANONICAL: &str = "550e8400-e29b-41d4-a716-446655440000";
+
+    /// `CANONICAL` as SMBIOS 2.6+ stores it.
+    const SMBIOS_BYTES: [u8; 16] = [
+        0x00, 0x84, 0x0e, 0x55, 0x9b, 0xe2, 0xd4, 0x41, 0xa7, 0x16, 0x44, 0x66, 0x55, 0x44, 0x00,
+        0x00,
+    ];
+
+    #[test]
+    fn test_text_forms_normalize_to_canonical() {
+        for text in [
+            CANONICAL,
+            "550E8400-E29B-41D4-A716-446655440000",
+            "{550e8400-e29b-41d4-a716-446655440000}",
+            "urn:uuid:550e8400-e29b-41d4-a716-446655440000",
+            "550e8400e29b41d4a716446655440000",
+            " 550e8400-e29b-41d4-a716-446655440000\n",
+        ] {
+            let uuid = normalize_uuid(RawUuid::Text(text));
+            assert_eq!(
+                uuid.map(|u| u.to_string()).as_deref(),
+                Some(CANONICAL),
+                "{:?}",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn test_smbios_bytes_match_text() {
+        assert_eq!(
+            normalize_uuid(RawUuid::Smbios(&SMBIOS_BYTES)),
+            normalize_uuid(RawUuid::Text(CANONICAL))
+        );
+    }
+
+    #[test]
+    fn test_rejects_malformed_input() {
+        assert_eq!(normalize_uuid(RawUuid::Text("")), None);
+        assert_eq!(normalize_uuid(RawUuid::Text("not-a-uuid")), None);
+        assert_eq!(normalize_uuid(RawUuid::Smbios(&SMBIOS_BYTES[..15])), None);
+        assert_eq!(normalize_uuid(RawUuid::Smbios(&[0u8; 17])), None);
+    }
+
+    #[test]
+    fn test_byte_swapped_reads_big_endian_firmware() {
+        let canonical = Uuid::parse_str(CANONICAL).unwrap();
+        // Firmware that sends RFC 4122 order puts the canonical bytes on the wire
+        let misread = normalize_uuid(RawUuid::Smbios(canonical.as_bytes())).unwrap();
+        assert_ne!(misread, canonical);
+        assert_eq!(byte_swapped(&misread), canonical);
+        assert_eq!(byte_swapped(&byte_swapped(&canonical)), canonical);
+    }
+}
@@ -1,3 +1,7 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
 use uuid::Uuid;
 
 use crate::database::Connection;
@@ -7,11 +11,18 @@ use crate::plans::actions::BootTarget;
 use crate::plans::{Plan, PlanStatus};
 use crate::{platforms, roles};
 
+mod boot;
+pub mod boot_policy;
+pub mod hardware_uuid;
 pub(crate) mod power;
 mod power_ops;
+mod reaper;
 pub(crate) mod store;
 
+pub use boot_policy::{BootPolicy, DefaultBootPolicy, DeviceContext};
+pub use hardware_uuid::{RawUuid, normalize_uuid};
 pub use power::PowerAction;
+pub use reaper::spawn_transition_reaper_task;
 
 pub use common::device_attributes::NetworkInterface;
 pub use store::Device;
@@ -54,6 +65,7 @@ impl std::fmt::Display for Architecture {
 pub struct Director<'a> {
     conn: &'a Connection,
     power_config: power::PowerConfig,
+    boot_policy: Arc<dyn BootPolicy>,
 }
 
 impl<'a> Director<'a> {
@@ -74,6 +86,7 @@ impl<'a> Director<'a> {
         Director {
             conn,
             power_config: power::PowerConfig::default(),
+            boot_policy: Arc::new(DefaultBootPolicy),
         }
     }
 
@@ -85,9 +98,16 @@ impl<'a> Director<'a> {
         Director {
             conn,
             power_config: cfg,
+            boot_policy: Arc::new(DefaultBootPolicy),
         }
     }
 
+    /// Decide boot targets with `policy` instead of [`DefaultBootPolicy`].
+    pub fn with_boot_policy(mut self, policy: Arc<dyn BootPolicy>) -> Self {
+        self.boot_policy = policy;
+        self
+    }
+
     pub async fn register_device(
         &self,
         uuid: &Uuid,
@@ -122,53 +142,149 @@ impl<'a> Director<'a> {
         store::device_exists(self.conn, uuid).await
     }
 
-    /// Get the boot target for this device.
+    /// Get the boot target for this device, consuming a pending rediscovery.
     ///
     /// `sleep_secs` controls how long an unprovisioned or unknown device sleeps
     /// before rebooting to retry PXE boot.  Production callers pass 600; e2e
     /// tests may pass 0 to avoid waiting.
+    ///
+    /// Does not touch `last_seen_at`; callers that actually heard from the device
+    /// record that with [`Director::mark_device_seen`].
     pub async fn next_boot_target(
         &self,
         uuid: &Uuid,
         sleep_secs: u64,
     ) -> anyhow::Result<BootTarget> {
-        if self.device_exists(uuid).await? {
-            store::update_device_last_seen(self.conn, uuid)
-                .await
-                .expect("update device last seen should not fail");
+        // A pending rediscovery request wins over everything else, exactly once.
+        let rediscover = store::take_rediscover_pending(self.conn, uuid).await?;
+        if rediscover {
+            log::info!("Device {uuid} booting into one-shot rediscovery");
+        }
+        self.resolve_boot_target(uuid, sleep_secs, rediscover).await
+    }
 
-            // Check if there's an active plan for this device
-            if let Some(plan) =
-                crate::plans::store::get_active_plan_for_device(self.conn, uuid).await?
-                && let Some(current_action) = plan.get_current_action()
-            {
-                // Get device for ActionContext
-                let device = store::get_device(self.conn, uuid).await?;
-
-                // Create ActionContext for the action
-                let ctx = crate::plans::actions::ActionContext {
-                    device: &device,
-                    conn: self.conn,
-                    director: None, // Director not needed for boot target resolution
-                };
+    /// Record that the device was just seen on the network.
+    pub async fn mark_device_seen(&self, uuid: &Uuid) -> anyhow::Result<()> {
+        store::update_device_last_seen(self.conn, uuid).await
+    }
 
-                // Return appropriate boot target based on the current action
-                return current_action.to_boot_target(&ctx).await;
-            }
+    /// Issue a fresh boot token for the device, replacing any earlier one.
+    ///
+    /// The token goes on the agent image's kernel cmdline; callbacks made from that
+    /// boot present it to prove which device they come from.
+    pub async fn issue_boot_token(&self, uuid: &Uuid) -> anyhow::Result<String> {
+        let token = generate_boot_token();
+        if !store::set_boot_token(self.conn, uuid, &token).await? {
+            anyhow::bail!("Device {} not found", uuid);
+        }
+        Ok(token)
+    }
 
-            // No active plan — only boot local disk if the device is fully provisioned.
-            // Any other lifecycle state means the device has no OS yet, so sleep and retry
-            // so it will pick up a plan when one becomes available.
-            let lifecycle = crate::lifecycle::store::get_device_lifecycle(self.conn, uuid).await?;
-            if matches!(lifecycle, Some(DeviceLifecycle::Provisioned)) {
-                return Ok(BootTarget::LocalDisk);
-            }
+    /// Whether `presented` is the device's current boot token. A device that has
+    /// never been issued one accepts nothing.
+    pub async fn check_boot_token(&self, uuid: &Uuid, presented: &str) -> anyhow::Result<bool> {
+        Ok(store::get_boot_token(self.conn, uuid)
+            .await?
+            .is_some_and(|token| constant_time_eq(token.as_bytes(), presented.as_bytes())))
+    }
+
+    /// The boot target [`Director::next_boot_target`] would return if the device
+    /// booted now, leaving a pending rediscovery pending.
+    pub async fn peek_boot_target(
+        &self,
+        uuid: &Uuid,
+        sleep_secs: u64,
+    ) -> anyhow::Result<BootTarget> {
+        let rediscover = store::is_rediscover_pending(self.conn, uuid).await?;
+        self.resolve_boot_target(uuid, sleep_secs, rediscover).await
+    }
+
+    /// Work out the boot target with the boot policy, with no side effects.
+    /// `rediscover` boots the one-shot hardware scan instead.
+    async fn resolve_boot_target(
+        &self,
+        uuid: &Uuid,
+        sleep_secs: u64,
+        rediscover: bool,
+    ) -> anyhow::Result<BootTarget> {
+        let ctx = self.boot_context(uuid, sleep_secs, rediscover).await?;
+        let target = self.boot_policy.decide(&ctx);
+        self.with_image_set(uuid, target).await
+    }
+
+    /// Gather what the boot policy needs to know about the device.
+    async fn boot_context(
+        &self,
+        uuid: &Uuid,
+        sleep_secs: u64,
+        rediscover: bool,
+    ) -> anyhow::Result<DeviceContext> {
+        let mut ctx = DeviceContext {
+            uuid: *uuid,
+            in_inventory: false,
+            lifecycle: None,
+            tags: Default::default(),
+            rediscover,
+            plan_target: None,
+            sleep_secs,
+        };
+        if !self.device_exists(uuid).await? {
+            return Ok(ctx);
         }
 
-        // Unknown device or non-provisioned device with no active plan — sleep and retry.
-        Ok(BootTarget::SleepReboot {
-            seconds: sleep_secs,
-        })
+        let device = store::get_device(self.conn, uuid).await?;
+        ctx.in_inventory = true;
+        ctx.lifecycle = device.lifecycle.clone();
+        ctx.tags = crate::device_tags::list_tags(self.conn, device.id)
+            .await?
+            .into_iter()
+            .map(|tag| (tag.key, tag.value))
+            .collect();
+
+        if !rediscover
+            && let Some(plan) =
+                crate::plans::store::get_active_plan_for_device(self.conn, uuid).await?
+            && let Some(current_action) = plan.get_current_action()
+        {
+            let action_ctx = crate::plans::actions::ActionContext {
+                device: &device,
+                conn: self.conn,
+                director: None, // Director not needed for boot target resolution
+            };
+            ctx.plan_target = Some(current_action.to_boot_target(&action_ctx).await?);
+        }
+        Ok(ctx)
+    }
+
+    /// Fill in the device's image set for agent boots.
+    ///
+    /// Uses the device's pinned image set, falling back to the fleet default; other
+    /// boot targets are returned unchanged.
+    async fn with_image_set(&self, uuid: &Uuid, target: BootTarget) -> anyhow::Result<BootTarget> {
+        match target {
+            BootTarget::AgentImage {
+                action, cmdline, ..
+            } => Ok(BootTarget::AgentImage {
+                action,
+                cmdline,
+                image_set: crate::image_sets::resolve_image_set(self.conn, uuid).await?,
+            }),
+            other => Ok(other),
+        }
+    }
+
+    /// Request that the device netboot into a hardware scan on its next boot only.
+    ///
+    /// The flag is consumed by [`Director::next_boot_target`]; later boots follow the
+    /// normal plan and lifecycle logic. Returns `false` if the device does not exist.
+    pub async fn request_rediscovery(&self, uuid: &Uuid) -> anyhow::Result<bool> {
+        store::set_rediscover_pending(self.conn, uuid).await
+    }
+
+    /// Set the device's free-text notes, or clear them with `None`. Returns `false`
+    /// if the device does not exist.
+    pub async fn set_notes(&self, uuid: &Uuid, notes: Option<&str>) -> anyhow::Result<bool> {
+        store::set_notes(self.conn, uuid, notes).await
     }
 
     pub async fn update_attributes(
@@ -238,6 +354,8 @@ impl<'a> Director<'a> {
     /// Called when a device boots via iPXE. If the current action's advance_on_boot()
     /// returns true, the action is automatically marked as successful and the plan advances.
     /// This is used for actions like RebootDevice that complete when the device boots.
+    /// Actions whose completes_on_next_boot() returns true (chained boot stages) advance
+    /// the same way once their target has been served on an earlier boot.
     pub async fn on_boot(&self, device_uuid: &Uuid) -> anyhow::Result<()> {
         // Get the current active plan
         let mut plan =
@@ -258,7 +376,7 @@ impl<'a> Director<'a> {
 
         // Check if current action should advance on boot
         let should_advance = if let Some(action) = plan.get_current_action() {
-            if action.advance_on_boot() {
+            if boot_completes_current_action(&plan) {
                 log::info!(
                     "Device {} booted - advancing action {:?}",
                     device_uuid,
@@ -301,6 +419,18 @@ impl<'a> Director<'a> {
         Ok(())
     }
 
+    /// Record that the device was just served the chain script of its current plan step.
+    ///
+    /// Its next boot then completes that stage in [`Director::on_boot`].
+    pub async fn mark_chain_served(&self, device_uuid: &Uuid) -> anyhow::Result<()> {
+        let Some(plan) =
+            crate::plans::store::get_active_plan_for_device(self.conn, device_uuid).await?
+        else {
+            return Ok(());
+        };
+        crate::plans::store::set_chained_step(self.conn, plan.id.unwrap(), plan.current_step).await
+    }
+
     /// Mark the current action for a device as successful, advancing the plan.
     ///
     /// If `reported_plan_id` is `Some`, it is verified against the active plan's
@@ -495,6 +625,17 @@ impl<'a> Director<'a> {
         &self,
         device_uuid: &Uuid,
         to_state: DeviceLifecycle,
+    ) -> anyhow::Result<i64> {
+        self.start_transition(device_uuid, to_state, true).await
+    }
+
+    /// Start a transition to `to_state`, issuing an OOB power kick before the first
+    /// action only when `power_kick` is set.
+    async fn start_transition(
+        &self,
+        device_uuid: &Uuid,
+        to_state: DeviceLifecycle,
+        power_kick: bool,
     ) -> anyhow::Result<i64> {
         // Get current device lifecycle
         let current_lifecycle =
@@ -567,7 +708,7 @@ impl<'a> Director<'a> {
             // Issue an OOB power kick before starting the action so the device
             // is actually booted and running the agent.  This is best-effort:
             // failures are logged and never block the transition.
-            if let Err(e) = self.ensure_powered_for_plan(&device, action).await {
+            if power_kick && let Err(e) = self.ensure_powered_for_plan(&device, action).await {
                 log::warn!(
                     "Power kick failed for device {}: {} (continuing)",
                     device_uuid,
@@ -720,6 +861,25 @@ impl<'a> Director<'a> {
         store::get_network_interfaces(self.conn, uuid).await
     }
 
+    /// Record that the device's interface `mac` was just seen on the network.
+    pub async fn mark_interface_seen(&self, uuid: &Uuid, mac: &str) -> anyhow::Result<()> {
+        store::touch_interface(self.conn, uuid, mac).await
+    }
+
+    /// When each of the device's interfaces was last seen, keyed by MAC address.
+    pub async fn get_interface_last_seen(
+        &self,
+        uuid: &Uuid,
+    ) -> anyhow::Result<HashMap<String, DateTime<Utc>>> {
+        store::get_interface_last_seen(self.conn, uuid).await
+    }
+
+    /// MAC addresses of all the device's interfaces, whether recorded in its
+    /// attributes or only seen on the network.
+    pub async fn get_interface_macs(&self, uuid: &Uuid) -> anyhow::Result<HashSet<String>> {
+        store::get_interface_macs(self.conn, uuid).await
+    }
+
     pub async fn set_network_interfaces(
         &self,
         uuid: &Uuid,
@@ -935,6 +1095,31 @@ impl<'a> Director<'a> {
     }
 }
 
+/// Whether the device booting completes the plan's current action.
+///
+/// True for actions that advance on boot, and for chained boot stages whose script
+/// was already served for this step on an earlier boot.
+fn boot_completes_current_action(plan: &Plan) -> bool {
+    match plan.get_current_action() {
+        Some(action) if action.advance_on_boot() => true,
+        Some(action) if action.completes_on_next_boot() => {
+            plan.chained_step == Some(plan.current_step)
+        }
+        _ => false,
+    }
+}
+
+/// 128 random bits, hex encoded, safe to put on a kernel cmdline.
+fn generate_boot_token() -> String {
+    let bytes: [u8; 16] = rand::random();
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Compare secrets without leaking, through timing, how long a prefix matched.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1107,7 +1292,7 @@ mod tests {
         // Verify the device gets the right boot target for first action (discover_hardware)
         let boot_target = director.next_boot_target(&test_uuid, 600).await.unwrap();
         match boot_target {
-            BootTarget::AgentImage { action, cmdline: _ } => {
+            BootTarget::AgentImage { action, .. } => {
                 assert_eq!(action, "daemon");
             }
             _ => panic!("Expected NetBoot, got LocalDisk"),
@@ -1130,9 +1315,7 @@ mod tests {
 
         // Verify the device gets BMC config boot target for second action
         let boot_target = director.next_boot_target(&test_uuid, 600).await.unwrap();
-        assert!(
-            matches!(boot_target, BootTarget::AgentImage { action, cmdline: _ } if action == "daemon")
-        );
+        assert!(matches!(boot_target, BootTarget::AgentImage { action, .. } if action == "daemon"));
 
         // Simulate BMC configuration completion
         director
@@ -2393,6 +2576,207 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_rediscovery_request_is_consumed_by_one_boot() {
+        let conn = setup_test_db(test_connection_factory!()).await;
+        let director = Director::new(&conn);
+        let test_uuid = Uuid::parse_str("550e8400-e29b-41d4-a716-446655440388").unwrap();
+
+        director
+            .register_device(&test_uuid, Architecture::X86_64)
+            .await
+            .unwrap();
+        crate::lifecycle::store::update_device_lifecycle(
+            &conn,
+            &test_uuid,
+            DeviceLifecycle::Provisioned,
+        )
+        .await
+        .unwrap();
+
+        assert!(director.request_rediscovery(&test_uuid).await.unwrap());
+
+        // First boot after the request scans hardware
+        let boot_target = director.next_boot_target(&test_uuid, 600).await.unwrap();
+        assert!(
+            matches!(&boot_target, BootTarget::AgentImage { action, .. } if action == "device-scan"),
+            "Expected device-scan AgentImage, got {boot_target:?}"
+        );
+
+        // Subsequent boots revert to the normal lifecycle logic
+        for _ in 0..2 {
+            let boot_target = director.next_boot_target(&test_uuid, 600).await.unwrap();
+            assert!(
+                matches!(boot_target, BootTarget::LocalDisk),
+                "Expected LocalDisk after rediscovery, got {boot_target:?}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_agent_boot_resolves_pinned_then_default_image_set() {
+        let mut conn = setup_test_db(test_connection_factory!()).await;
+        let v1 = crate::image_sets::create_image_set(&conn, "v1", "k1", "r1", "")
+            .await
+            .unwrap();
+        let v2 = crate::image_sets::create_image_set(&conn, "v2", "k2", "r2", "")
+            .await
+            .unwrap();
+        crate::image_sets::set_default_image_set(&mut conn, Some(v1.id))
+            .await
+            .unwrap();
+
+        let director = Director::new(&conn);
+        let pinned = Uuid::parse_str("550e8400-e29b-41d4-a716-446655440390").unwrap();
+        let fleet = Uuid::parse_str("550e8400-e29b-41d4-a716-446655440391").unwrap();
+        for uuid in [&pinned, &fleet] {
+            director
+                .register_device(uuid, Architecture::X86_64)
+                .await
+                .unwrap();
+        }
+        crate::image_sets::set_device_image_set(&conn, &pinned, Some(v2.id))
+            .await
+            .unwrap();
+
+        for (uuid, expected) in [(&pinned, "v2"), (&fleet, "v1")] {
+            director.request_rediscovery(uuid).await.unwrap();
+            let boot_target = director.next_boot_target(uuid, 600).await.unwrap();
+            match boot_target {
+                BootTarget::AgentImage {
+                    image_set: Some(set),
+                    ..
+                } => assert_eq!(set.name, expected),
+                other => panic!("Expected AgentImage with an image set, got {other:?}"),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rediscovery_request_unknown_device() {
+        let conn = setup_test_db(test_connection_factory!()).await;
+        let director = Director::new(&conn);
+        let unknown_uuid = Uuid::parse_str("550e8400-e29b-41d4-a716-446655440389").unwrap();
+
+        assert!(!director.request_rediscovery(&unknown_uuid).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_next_boot_target_holds_broken_device() {
+        let conn = setup_test_db(test_connection_factory!()).await;
+        let director = Director::new(&conn);
+        let test_uuid = Uuid::parse_str("550e8400-e29b-41d4-a716-446655440393").unwrap();
+
+        director
+            .register_device(&test_uuid, Architecture::X86_64)
+            .await
+            .unwrap();
+        crate::lifecycle::store::update_device_lifecycle(
+            &conn,
+            &test_uuid,
+            DeviceLifecycle::Broken,
+        )
+        .await
+        .unwrap();
+
+        let boot_target = director.next_boot_target(&test_uuid, 600).await.unwrap();
+        let BootTarget::Hold { reason } = &boot_target else {
+            panic!("Expected Hold for Broken device, got {boot_target:?}");
+        };
+        assert!(reason.contains("broken"));
+
+        let script = boot_target
+            .to_ipxe_script(
+                crate::plans::actions::BootUrls::single("http://10.0.0.1:3000"),
+                Some(&test_uuid),
+            )
+            .await
+            .unwrap();
+        assert!(script.contains(&format!("echo Device held by rack-director: {reason}")));
+        assert!(script.contains("prompt "));
+    }
+
+    /// Holds devices tagged `maintenance=true`, otherwise defers to the default.
+    struct MaintenanceTagPolicy;
+
+    impl BootPolicy for MaintenanceTagPolicy {
+        fn decide(&self, device: &DeviceContext) -> BootTarget {
+            if device.tags.get("maintenance").is_some_and(|v| v == "true") {
+                return BootTarget::Hold {
+                    reason: "in maintenance".to_string(),
+                };
+            }
+            DefaultBootPolicy.decide(device)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_next_boot_target_uses_custom_boot_policy() {
+        let conn = setup_test_db(test_connection_factory!()).await;
+        let director = Director::new(&conn).with_boot_policy(Arc::new(MaintenanceTagPolicy));
+        let test_uuid = Uuid::parse_str("550e8400-e29b-41d4-a716-446655440394").unwrap();
+
+        director
+            .register_device(&test_uuid, Architecture::X86_64)
+            .await
+            .unwrap();
+        crate::lifecycle::store::update_device_lifecycle(
+            &conn,
+            &test_uuid,
+            DeviceLifecycle::Provisioned,
+        )
+        .await
+        .unwrap();
+        let boot_target = director.next_boot_target(&test_uuid, 600).await.unwrap();
+        assert!(matches!(boot_target, BootTarget::LocalDisk));
+
+        let device = director.get_device(&test_uuid).await.unwrap();
+        crate::device_tags::set_tag(&conn, device.id, "maintenance", "true")
+            .await
+            .unwrap();
+        let boot_target = director.next_boot_target(&test_uuid, 600).await.unwrap();
+        assert!(
+            matches!(&boot_target, BootTarget::Hold { reason } if reason == "in maintenance"),
+            "{boot_target:?}"
+        );
+    }
+
+    /// Make every `last_seen_at` update fail, as a database error would.
+    async fn reject_last_seen_updates(conn: &database::Connection) {
+        conn.execute(
+            "CREATE TRIGGER reject_last_seen BEFORE UPDATE OF last_seen_at ON devices
+             BEGIN SELECT RAISE(ABORT, 'last_seen_at is read-only'); END",
+            (),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_next_boot_target_does_not_need_last_seen_update() {
+        let conn = setup_test_db(test_connection_factory!()).await;
+        let director = Director::new(&conn);
+        let test_uuid = Uuid::parse_str("550e8400-e29b-41d4-a716-446655440394").unwrap();
+        director
+            .register_device(&test_uuid, Architecture::X86_64)
+            .await
+            .unwrap();
+        crate::lifecycle::store::update_device_lifecycle(
+            &conn,
+            &test_uuid,
+            DeviceLifecycle::Provisioned,
+        )
+        .await
+        .unwrap();
+        reject_last_seen_updates(&conn).await;
+
+        let boot_target = director.next_boot_target(&test_uuid, 600).await.unwrap();
+        assert!(matches!(boot_target, BootTarget::LocalDisk));
+        assert!(director.mark_device_seen(&test_uuid).await.is_err());
+        let device = director.get_device(&test_uuid).await.unwrap();
+        assert_eq!(device.last_seen_at, None);
+    }
+
     #[tokio::test]
     async fn test_cancel_active_transition_success() {
         let conn = setup_test_db(test_connection_factory!()).await;
@@ -2501,4 +2885,42 @@ mod tests {
             err_msg
         );
     }
+
+    #[tokio::test]
+    async fn test_boot_token_is_checked_and_rotated() {
+        let conn = setup_test_db(test_connection_factory!()).await;
+        let director = Director::new(&conn);
+        let test_uuid = Uuid::parse_str("550e8400-e29b-41d4-a716-4466554400b7").unwrap();
+        director
+            .register_device(&test_uuid, Architecture::X86_64)
+            .await
+            .unwrap();
+
+        // Nothing is accepted before a token is issued
+        assert!(!director.check_boot_token(&test_uuid, "").await.unwrap());
+
+        let first = director.issue_boot_token(&test_uuid).await.unwrap();
+        assert_eq!(first.len(), 32);
+        assert!(director.check_boot_token(&test_uuid, &first).await.unwrap());
+        assert!(
+            !director
+                .check_boot_token(&test_uuid, "wrong")
+                .await
+                .unwrap()
+        );
+
+        // The next boot's token replaces the previous one
+        let second = director.issue_boot_token(&test_uuid).await.unwrap();
+        assert_ne!(first, second);
+        assert!(!director.check_boot_token(&test_uuid, &first).await.unwrap());
+        assert!(
+            director
+                .check_boot_token(&test_uuid, &second)
+                .await
+                .unwrap()
+        );
+
+        let unknown = Uuid::parse_str("550e8400-e29b-41d4-a716-4466554400b8").unwrap();
+        assert!(director.issue_boot_token(&unknown).await.is_err());
+    }
 }
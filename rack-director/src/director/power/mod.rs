@@ -18,7 +18,9 @@ pub use ops::PowerAction;
 
 use std::time::Duration;
 
-use chrono::{DateTime, NaiveDateTime, Utc};
+use chrono::Utc;
+
+use crate::database::from_db_time;
 
 /// Window within which a `last_polled_at` timestamp is considered "in daemon
 /// mode".  Set to ~3× the agent's 5-second poll interval so a brief network
@@ -113,7 +115,7 @@ pub fn is_in_daemon_mode(last_polled_at: Option<&str>, window: Duration) -> bool
         Some(s) => s,
     };
 
-    let dt = parse_timestamp(ts);
+    let dt = from_db_time(ts).ok();
     match dt {
         None => false,
         Some(t) => {
@@ -131,24 +133,6 @@ pub fn is_in_daemon_mode(last_polled_at: Option<&str>, window: Duration) -> bool
     }
 }
 
-/// Try to parse `s` as either RFC 3339 or the SQLite `"%Y-%m-%d %H:%M:%S"` format
-/// (treated as UTC).  Returns `None` on failure.
-fn parse_timestamp(s: &str) -> Option<DateTime<Utc>> {
-    // Try RFC 3339 first (e.g. "2026-06-10T12:34:56Z" or with offset)
-    if let Ok(dt) = s.parse::<DateTime<Utc>>() {
-        return Some(dt);
-    }
-    // chrono's DateTime<FixedOffset> covers RFC 3339 with non-UTC offsets
-    if let Ok(dt) = DateTime::parse_from_rfc3339(s) {
-        return Some(dt.with_timezone(&Utc));
-    }
-    // SQLite CURRENT_TIMESTAMP: "2026-06-10 12:34:56" — treat as UTC
-    if let Ok(naive) = NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S") {
-        return Some(naive.and_utc());
-    }
-    None
-}
-
 /// Probe the BMC and return the best available `PowerDriver`.
 ///
 /// Attempts Redfish discovery via [`redfish::RedfishDriver::discover`], which
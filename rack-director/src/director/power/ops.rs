@@ -9,7 +9,7 @@ use super::{PowerDriver, PowerState};
 use crate::plans::actions::Action;
 
 /// User-requested power action for the UI power controls.
-#[derive(Debug, Clone, Copy, serde::Deserialize)]
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum PowerAction {
     /// Power the device on.
@@ -39,6 +39,7 @@ pub(crate) fn action_requires_boot(action: &Action) -> bool {
             | Action::PartitionDisks
             | Action::InstallOs
             | Action::Console
+            | Action::ChainScript { .. }
     )
 }
 
@@ -254,6 +255,13 @@ mod tests {
         assert!(action_requires_boot(&Action::Console));
     }
 
+    #[test]
+    fn test_action_requires_boot_chain_script() {
+        assert!(action_requires_boot(&Action::ChainScript {
+            url: "/stages/install.ipxe".to_string()
+        }));
+    }
+
     #[test]
     fn test_action_requires_boot_reboot_device_is_false() {
         assert!(!action_requires_boot(&Action::RebootDevice));
@@ -0,0 +1,285 @@
+//! Failing provisioning transitions that have stopped making progress.
+//!
+//! A transition stays open until the agent reports its plan finished. If the agent
+//! never boots, crashes mid-install, or the device loses power, nothing ever closes
+//! it and the device sits mid-provision indefinitely, blocking further transitions.
+//! The reaper fails any open transition to Provisioned whose device has shown no
+//! progress (state change, check-in or poll) for longer than
+//! `--transition-timeout-secs`, moving the device to Broken so it shows up for
+//! operator attention. Discovery and unprovisioning transitions are left alone.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+
+use crate::database::ConnectionFactory;
+use crate::lifecycle::DeviceLifecycle;
+
+/// How often the reaper looks for stale transitions.
+const REAP_INTERVAL: Duration = Duration::from_secs(60);
+
+impl<'a> super::Director<'a> {
+    /// Fail every open provisioning transition with no device progress within `timeout`.
+    ///
+    /// Each transition is closed through the same conditional updates as
+    /// `cancel_active_transition`, so one that completes concurrently is left alone.
+    /// Returns the UUIDs of the devices moved to Broken.
+    pub async fn fail_stale_transitions(&self, timeout: Duration) -> anyhow::Result<Vec<Uuid>> {
+        let cutoff = chrono::Utc::now() - chrono::Duration::from_std(timeout)?;
+        let error_message = format!("Timed out after {}s without progress", timeout.as_secs());
+        let mut failed = Vec::new();
+
+        for transition in crate::lifecycle::store::get_stale_transitions(self.conn, cutoff).await? {
+            let Some(transition_id) = transition.id else {
+                continue;
+            };
+            let device_uuid = transition.device_uuid;
+
+            if transition.plan_id.is_some()
+                && !crate::plans::store::fail_active_plan_for_device(
+                    self.conn,
+                    &device_uuid,
+                    &error_message,
+                )
+                .await?
+            {
+                // The plan finished concurrently; its completion handler closes the transition.
+                continue;
+            }
+
+            let rows = crate::lifecycle::store::complete_transition(
+                self.conn,
+                transition_id,
+                false,
+                Some(&error_message),
+            )
+            .await?;
+            if rows == 0 {
+                continue;
+            }
+
+            crate::lifecycle::store::update_device_lifecycle(
+                self.conn,
+                &device_uuid,
+                DeviceLifecycle::Broken,
+            )
+            .await?;
+
+            log::warn!(
+                "Device {} stuck transitioning {:?} -> {:?} for over {}s; marked broken",
+                device_uuid,
+                transition.from_state,
+                transition.to_state,
+                timeout.as_secs()
+            );
+            failed.push(device_uuid);
+        }
+
+        Ok(failed)
+    }
+}
+
+/// Spawn a background task that periodically fails provisioning transitions stuck for
+/// longer than `timeout`.
+///
+/// A zero `timeout` disables the reaper and spawns nothing.
+pub fn spawn_transition_reaper_task(
+    connection_factory: Arc<dyn ConnectionFactory>,
+    timeout: Duration,
+) -> Option<JoinHandle<()>> {
+    if timeout.is_zero() {
+        log::info!("Transition reaper disabled");
+        return None;
+    }
+    Some(tokio::spawn(async move {
+        let conn = connection_factory
+            .open()
+            .await
+            .expect("Failed to open database");
+        let mut interval = tokio::time::interval(REAP_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(e) = super::Director::new(&conn)
+                .fail_stale_transitions(timeout)
+                .await
+            {
+                log::error!("Failed to reap stale lifecycle transitions: {}", e);
+            }
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Architecture, Director};
+    use super::*;
+    use crate::database::{self, Connection};
+    use crate::lifecycle::{LifecycleManager, LifecycleTransition, TransitionType};
+    use crate::plans::Plan;
+    use crate::test_connection_factory;
+
+    /// Register `uuid` as Unprovisioned and open a provisioning transition with its plan.
+    ///
+    /// Going through the store skips the Role that `start_lifecycle_transition` requires.
+    async fn start_provisioning(conn: &Connection, uuid: &Uuid) {
+        Director::new(conn)
+            .register_device(uuid, Architecture::X86_64)
+            .await
+            .unwrap();
+        crate::lifecycle::store::update_device_lifecycle(
+            conn,
+            uuid,
+            DeviceLifecycle::Unprovisioned,
+        )
+        .await
+        .unwrap();
+        let actions = LifecycleManager::get_plan_stub_for_transition(&TransitionType::Provision);
+        let plan_id = crate::plans::store::create_plan(conn, &Plan::new(*uuid, actions))
+            .await
+            .unwrap();
+        let transition = LifecycleTransition::new(
+            *uuid,
+            DeviceLifecycle::Unprovisioned,
+            DeviceLifecycle::Provisioned,
+            Some(plan_id),
+        );
+        crate::lifecycle::store::create_transition(conn, &transition)
+            .await
+            .unwrap();
+    }
+
+    /// Backdate the device's state change and its transitions by two hours.
+    async fn backdate(conn: &Connection, uuid: &Uuid) {
+        let two_hours_ago = database::to_db_time(chrono::Utc::now() - chrono::Duration::hours(2));
+        conn.execute(
+            "UPDATE devices SET state_changed_at = ?1 WHERE uuid = ?2",
+            (two_hours_ago.clone(), *uuid),
+        )
+        .await
+        .unwrap();
+        conn.execute(
+            "UPDATE lifecycle_transitions SET created_at = ?1 WHERE device_uuid = ?2",
+            (two_hours_ago, *uuid),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_stale_transition_fails_while_recent_one_survives() {
+        let factory = test_connection_factory!();
+        let conn = database::run_migrations(&factory).await.unwrap();
+        let director = Director::new(&conn);
+        let stale = Uuid::parse_str("550e8400-e29b-41d4-a716-4466554401a1").unwrap();
+        let recent = Uuid::parse_str("550e8400-e29b-41d4-a716-4466554401a2").unwrap();
+
+        for uuid in [&stale, &recent] {
+            start_provisioning(&conn, uuid).await;
+        }
+        backdate(&conn, &stale).await;
+
+        let failed = director
+            .fail_stale_transitions(Duration::from_secs(3600))
+            .await
+            .unwrap();
+        assert_eq!(failed, vec![stale]);
+
+        assert_eq!(
+            director.get_device_lifecycle(&stale).await.unwrap(),
+            Some(DeviceLifecycle::Broken)
+        );
+        assert!(
+            director
+                .get_active_transition_for_device(&stale)
+                .await
+                .unwrap()
+                .is_none()
+        );
+        assert!(
+            director
+                .get_active_plan_for_device(&stale)
+                .await
+                .unwrap()
+                .is_none()
+        );
+        let transitions = director.get_device_transitions(&stale, true).await.unwrap();
+        assert_eq!(transitions[0].success, Some(false));
+        assert_eq!(
+            transitions[0].error_message.as_deref(),
+            Some("Timed out after 3600s without progress")
+        );
+
+        // The recent device is untouched
+        assert_eq!(
+            director.get_device_lifecycle(&recent).await.unwrap(),
+            Some(DeviceLifecycle::Unprovisioned)
+        );
+        assert!(
+            director
+                .get_active_transition_for_device(&recent)
+                .await
+                .unwrap()
+                .is_some()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_recent_check_in_keeps_transition_alive() {
+        let factory = test_connection_factory!();
+        let conn = database::run_migrations(&factory).await.unwrap();
+        let director = Director::new(&conn);
+        let uuid = Uuid::parse_str("550e8400-e29b-41d4-a716-4466554401a3").unwrap();
+
+        start_provisioning(&conn, &uuid).await;
+        backdate(&conn, &uuid).await;
+        conn.execute(
+            "UPDATE devices SET last_polled_at = ?1 WHERE uuid = ?2",
+            (database::to_db_time(chrono::Utc::now()), uuid),
+        )
+        .await
+        .unwrap();
+
+        let failed = director
+            .fail_stale_transitions(Duration::from_secs(3600))
+            .await
+            .unwrap();
+        assert!(failed.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_stale_discovery_transition_is_not_reaped() {
+        let factory = test_connection_factory!();
+        let conn = database::run_migrations(&factory).await.unwrap();
+        let director = Director::new(&conn);
+        let uuid = Uuid::parse_str("550e8400-e29b-41d4-a716-4466554401a4").unwrap();
+
+        director
+            .register_device(&uuid, Architecture::X86_64)
+            .await
+            .unwrap();
+        director
+            .start_lifecycle_transition(&uuid, DeviceLifecycle::Unprovisioned)
+            .await
+            .unwrap();
+        backdate(&conn, &uuid).await;
+
+        let failed = director
+            .fail_stale_transitions(Duration::from_secs(3600))
+            .await
+            .unwrap();
+        assert!(failed.is_empty());
+        assert_eq!(
+            director.get_device_lifecycle(&uuid).await.unwrap(),
+            Some(DeviceLifecycle::New)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_zero_timeout_disables_reaper() {
+        let factory = test_connection_factory!();
+        database::run_migrations(&factory).await.unwrap();
+        assert!(spawn_transition_reaper_task(Arc::new(factory), Duration::ZERO).is_none());
+    }
+}
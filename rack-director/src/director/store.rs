@@ -1,9 +1,12 @@
+use std::collections::{HashMap, HashSet};
+
 use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
 use rusqlite::{OptionalExtension, Row};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use crate::database::{Connection, FromRow};
+use crate::database::{Connection, FromRow, from_db_time, to_db_time};
 use crate::device_warnings;
 use crate::director::Architecture;
 use crate::lifecycle::DeviceLifecycle;
@@ -27,10 +30,12 @@ pub struct Device {
     pub last_seen_at: Option<String>,
     /// Timestamp of the last daemon-mode poll from the agent.
     ///
-    /// Set to `CURRENT_TIMESTAMP` each time the agent calls `GET /cnc/poll`.
+    /// Set to the current time each time the agent calls `GET /cnc/poll`.
     /// Used by the power management layer to detect whether the agent is
     /// already running in daemon mode and skip issuing an OOB power kick.
     pub last_polled_at: Option<String>,
+    /// Free-text operator notes, kept across reprovisioning.
+    pub notes: Option<String>,
 }
 
 impl FromRow for Device {
@@ -49,6 +54,7 @@ impl FromRow for Device {
         let first_seen_at: Option<String> = row.get("first_seen_at").ok();
         let last_seen_at: Option<String> = row.get("last_seen_at").ok();
         let last_polled_at: Option<String> = row.get("last_polled_at").ok();
+        let notes: Option<String> = row.get("notes")?;
 
         let attributes = match attributes_json {
             Some(json_str) => {
@@ -73,6 +79,7 @@ impl FromRow for Device {
             first_seen_at,
             last_seen_at,
             last_polled_at,
+            notes,
         })
     }
 }
@@ -114,8 +121,12 @@ pub async fn register_device(
     architecture: Architecture,
 ) -> Result<()> {
     conn.execute(
-        "INSERT INTO devices (uuid, lifecycle, architecture) VALUES (?1, 'new', ?2)",
-        (*uuid, architecture.as_str().to_string()),
+        "INSERT INTO devices (uuid, lifecycle, architecture, created_at, state_changed_at) VALUES (?1, 'new', ?2, ?3, ?3)",
+        (
+            *uuid,
+            architecture.as_str().to_string(),
+            to_db_time(chrono::Utc::now()),
+        ),
     )
     .await
     .map(|_| ())?;
@@ -135,8 +146,8 @@ pub async fn device_exists(conn: &Connection, uuid: &Uuid) -> Result<bool> {
 
 pub async fn update_device_last_seen(conn: &Connection, uuid: &Uuid) -> Result<()> {
     conn.execute(
-        "UPDATE devices SET last_seen_at = CURRENT_TIMESTAMP WHERE uuid = ?1",
-        (*uuid,),
+        "UPDATE devices SET last_seen_at = ?1 WHERE uuid = ?2",
+        (to_db_time(chrono::Utc::now()), *uuid),
     )
     .await?;
     Ok(())
@@ -151,13 +162,92 @@ pub async fn update_device_last_seen(conn: &Connection, uuid: &Uuid) -> Result<(
 /// and continue.
 pub async fn update_device_last_polled(conn: &Connection, uuid: &Uuid) -> Result<()> {
     conn.execute(
-        "UPDATE devices SET last_polled_at = CURRENT_TIMESTAMP WHERE uuid = ?1",
-        (*uuid,),
+        "UPDATE devices SET last_polled_at = ?1 WHERE uuid = ?2",
+        (to_db_time(chrono::Utc::now()), *uuid),
     )
     .await?;
     Ok(())
 }
 
+/// Replace the device's boot token.
+///
+/// Returns `false` if no device with `uuid` exists.
+pub async fn set_boot_token(conn: &Connection, uuid: &Uuid, token: &str) -> Result<bool> {
+    let updated = conn
+        .execute(
+            "UPDATE devices SET boot_token = ?1 WHERE uuid = ?2",
+            (token.to_string(), *uuid),
+        )
+        .await?;
+    Ok(updated > 0)
+}
+
+/// The device's current boot token, if one has been issued.
+pub async fn get_boot_token(conn: &Connection, uuid: &Uuid) -> Result<Option<String>> {
+    let token = conn
+        .query_row(
+            "SELECT boot_token FROM devices WHERE uuid = ?1",
+            (*uuid,),
+            |row| row.get::<_, Option<String>>(0),
+        )
+        .await
+        .optional()?;
+    Ok(token.flatten())
+}
+
+/// Flag a device to netboot into a hardware scan on its next boot.
+///
+/// Returns `false` if no device with `uuid` exists.
+pub async fn set_rediscover_pending(conn: &Connection, uuid: &Uuid) -> Result<bool> {
+    let updated = conn
+        .execute(
+            "UPDATE devices SET rediscover_pending = 1 WHERE uuid = ?1",
+            (*uuid,),
+        )
+        .await?;
+    Ok(updated > 0)
+}
+
+/// Set the device's notes, or clear them with `None`.
+///
+/// Returns `false` if no device with `uuid` exists.
+pub async fn set_notes(conn: &Connection, uuid: &Uuid, notes: Option<&str>) -> Result<bool> {
+    let updated = conn
+        .execute(
+            "UPDATE devices SET notes = ?1 WHERE uuid = ?2",
+            (notes.map(str::to_string), *uuid),
+        )
+        .await?;
+    Ok(updated > 0)
+}
+
+/// Whether the one-shot rediscovery flag is set, without clearing it.
+pub async fn is_rediscover_pending(conn: &Connection, uuid: &Uuid) -> Result<bool> {
+    let pending = conn
+        .query_row(
+            "SELECT rediscover_pending FROM devices WHERE uuid = ?1",
+            (*uuid,),
+            |row| row.get(0),
+        )
+        .await
+        .optional()?;
+    Ok(pending.unwrap_or(false))
+}
+
+/// Clear the one-shot rediscovery flag, returning whether it was set.
+///
+/// The check and the clear happen in a single statement so concurrent boot
+/// lookups cannot both observe the flag.
+pub async fn take_rediscover_pending(conn: &Connection, uuid: &Uuid) -> Result<bool> {
+    let updated = conn
+        .execute(
+            "UPDATE devices SET rediscover_pending = 0 WHERE uuid = ?1 AND rediscover_pending = 1",
+            (*uuid,),
+        )
+        .await?;
+    Ok(updated > 0)
+}
+
 pub async fn update_attributes(
     conn: &Connection,
     uuid: &Uuid,
@@ -238,7 +328,7 @@ async fn drop_stale_overrides(
 pub async fn get_device(conn: &Connection, uuid: &Uuid) -> Result<Device> {
     let device = conn
         .query_one(
-            "SELECT id, uuid, architecture, lifecycle, role_id, platform_id, attributes, created_at, first_seen_at, last_seen_at, last_polled_at FROM devices WHERE uuid = ?1",
+            "SELECT id, uuid, architecture, lifecycle, role_id, platform_id, attributes, created_at, first_seen_at, last_seen_at, last_polled_at, notes FROM devices WHERE uuid = ?1",
             (*uuid,),
             Device::from_row,
         )
@@ -250,7 +340,7 @@ pub async fn get_device(conn: &Connection, uuid: &Uuid) -> Result<Device> {
 pub async fn get_all_devices(conn: &Connection) -> Result<Vec<Device>> {
     let devices = conn
         .query(
-            "SELECT id, uuid, architecture, lifecycle, role_id, platform_id, attributes, created_at, first_seen_at, last_seen_at, last_polled_at FROM devices",
+            "SELECT id, uuid, architecture, lifecycle, role_id, platform_id, attributes, created_at, first_seen_at, last_seen_at, last_polled_at, notes FROM devices",
             (),
             Device::from_row,
         )
@@ -377,6 +467,52 @@ pub async fn set_network_interfaces(
     Ok(())
 }
 
+/// Record that the device's interface `mac` was seen on the network just now.
+///
+/// Does nothing if the device does not exist.
+pub async fn touch_interface(conn: &Connection, uuid: &Uuid, mac: &str) -> Result<()> {
+    conn.execute(
+        "INSERT INTO interface_activity (device_id, mac_address, last_seen_at)
+         SELECT id, ?2, ?3 FROM devices WHERE uuid = ?1
+         ON CONFLICT(device_id, mac_address) DO UPDATE SET last_seen_at = excluded.last_seen_at",
+        (*uuid, mac.to_string(), to_db_time(chrono::Utc::now())),
+    )
+    .await?;
+    Ok(())
+}
+
+/// When each of the device's interfaces was last seen, keyed by MAC address.
+pub async fn get_interface_last_seen(
+    conn: &Connection,
+    uuid: &Uuid,
+) -> Result<HashMap<String, DateTime<Utc>>> {
+    let rows = conn
+        .query(
+            "SELECT a.mac_address, a.last_seen_at FROM interface_activity a
+             JOIN devices d ON d.id = a.device_id
+             WHERE d.uuid = ?1",
+            (*uuid,),
+            |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)),
+        )
+        .await?;
+
+    rows.into_iter()
+        .map(|(mac, seen)| Ok((mac, from_db_time(&seen)?)))
+        .collect()
+}
+
+/// MAC addresses of all the device's interfaces, whether recorded in its attributes
+/// or only seen on the network.
+pub async fn get_interface_macs(conn: &Connection, uuid: &Uuid) -> Result<HashSet<String>> {
+    let mut macs: HashSet<String> = get_network_interfaces(conn, uuid)
+        .await?
+        .into_iter()
+        .map(|iface| iface.mac_address)
+        .collect();
+    macs.extend(get_interface_last_seen(conn, uuid).await?.into_keys());
+    Ok(macs)
+}
+
 /// Create a pending device entry for a MAC address.
 ///
 /// Returns the ID of the created pending device. If a pending device already exists
@@ -387,9 +523,13 @@ pub async fn create_pending_device(
     network_id: i64,
 ) -> Result<i64> {
     conn.execute(
-        "INSERT INTO pending_devices (mac_address, network_id) VALUES (?1, ?2)
+        "INSERT INTO pending_devices (mac_address, network_id, created_at) VALUES (?1, ?2, ?3)
          ON CONFLICT(mac_address) DO NOTHING",
-        (mac_address.to_string(), network_id),
+        (
+            mac_address.to_string(),
+            network_id,
+            to_db_time(chrono::Utc::now()),
+        ),
     )
     .await?;
 
@@ -438,9 +578,13 @@ pub async fn complete_pending_device(
 ) -> Result<()> {
     conn.execute(
         "UPDATE pending_devices
-         SET device_uuid = ?1, completed_at = CURRENT_TIMESTAMP
-         WHERE mac_address = ?2 AND completed_at IS NULL",
-        (*device_uuid, mac_address.to_string()),
+         SET device_uuid = ?1, completed_at = ?2
+         WHERE mac_address = ?3 AND completed_at IS NULL",
+        (
+            *device_uuid,
+            to_db_time(chrono::Utc::now()),
+            mac_address.to_string(),
+        ),
     )
     .await?;
 
@@ -1118,6 +1262,34 @@ mod tests {
         assert_eq!(interfaces[0].ip_address, Some("10.0.0.100".to_string()));
     }
 
+    #[tokio::test]
+    async fn test_get_interface_macs_merges_attributes_and_activity() {
+        let db = setup_db(test_database_path!()).await;
+        let uuid = test_uuid(0x59);
+
+        register_device(&db, &uuid, Architecture::X86_64)
+            .await
+            .unwrap();
+        set_ip_address(&db, &uuid, "10.0.0.100", "aa:bb:cc:dd:ee:01")
+            .await
+            .unwrap();
+        touch_interface(&db, &uuid, "aa:bb:cc:dd:ee:01")
+            .await
+            .unwrap();
+        touch_interface(&db, &uuid, "aa:bb:cc:dd:ee:02")
+            .await
+            .unwrap();
+
+        let macs = get_interface_macs(&db, &uuid).await.unwrap();
+        assert_eq!(
+            macs,
+            HashSet::from([
+                "aa:bb:cc:dd:ee:01".to_string(),
+                "aa:bb:cc:dd:ee:02".to_string()
+            ])
+        );
+    }
+
     #[tokio::test]
     async fn test_set_ip_address_updates_by_mac() {
         let db = setup_db(test_database_path!()).await;
@@ -0,0 +1,177 @@
+//! `/api/admin` HTTP handlers for operating the director itself.
+
+use std::sync::Arc;
+
+use axum::{
+    Json, Router,
+    extract::State,
+    routing::{get, post},
+};
+
+use crate::{
+    consistency::{self, ConsistencyReport},
+    database::{self, MaintenanceReport},
+    http::{
+        AppState,
+        audit::{self, Actor},
+        error::Error,
+    },
+};
+
+// ---------------------------------------------------------------------------
+// Route registration
+// ---------------------------------------------------------------------------
+
+pub fn routes(state: Arc<AppState>) -> Router {
+    Router::new()
+        .route("/api/admin/maintenance", post(post_maintenance))
+        .route("/api/admin/consistency", get(get_consistency))
+        .with_state(state)
+}
+
+// ---------------------------------------------------------------------------
+// Handlers
+// ---------------------------------------------------------------------------
+
+/// `POST /api/admin/maintenance`
+///
+/// Run `VACUUM` and `ANALYZE` on the database and report its size before and
+/// after. Waits for other connections to go idle; answers 503 if they don't in time.
+/// A completed run is recorded in the audit log with its report.
+async fn post_maintenance(
+    State(state): State<Arc<AppState>>,
+    actor: Actor,
+) -> Result<Json<MaintenanceReport>, Error> {
+    let report = database::vacuum_and_analyze(state.connection_factory.as_ref()).await?;
+    let conn = state.connection_factory.open().await?;
+    audit::record(
+        &conn,
+        &actor,
+        "database.maintenance",
+        "database",
+        None,
+        audit::summary(&report),
+    )
+    .await;
+    Ok(Json(report))
+}
+
+/// `GET /api/admin/consistency`
+///
+/// Report leases outside their network's pools, interface addresses no lease
+/// backs and leases for deleted interfaces. Nothing is repaired; that takes
+/// `rack-director check --fix`.
+async fn get_consistency(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<ConsistencyReport>, Error> {
+    let mut conn = state.connection_factory.open().await?;
+    Ok(Json(consistency::check(&mut conn, false).await?))
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Body,
+        http::{Request, StatusCode},
+    };
+    use tower::ServiceExt;
+
+    use crate::{
+        dhcp::{LeaseState, store},
+        http::test_helpers::build_test_app,
+        test_connection_factory,
+    };
+
+    #[tokio::test]
+    async fn test_post_maintenance_on_populated_db() {
+        let app = build_test_app(test_connection_factory!()).await;
+        for n in 1..=20u8 {
+            app.conn
+                .execute(
+                    "INSERT INTO devices (uuid, lifecycle, architecture) VALUES (?1, 'new', 'x86-64')",
+                    (format!("d4000000-0000-0000-0000-0000000000{n:02x}"),),
+                )
+                .await
+                .unwrap();
+        }
+
+        let req = Request::builder()
+            .method("POST")
+            .uri("/api/admin/maintenance")
+            .body(Body::empty())
+            .unwrap();
+        let resp = app.router.oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert!(json["before_bytes"].as_u64().unwrap() > 0);
+        assert!(json["after_bytes"].as_u64().unwrap() > 0);
+
+        let devices: i64 = app
+            .conn
+            .query_one("SELECT COUNT(*) FROM devices", (), |row| row.get(0))
+            .await
+            .unwrap();
+        assert_eq!(devices, 20);
+
+        let (action, after): (String, String) = app
+            .conn
+            .query_one("SELECT action, after_summary FROM audit_log", (), |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .await
+            .unwrap();
+        assert_eq!(action, "database.maintenance");
+        let after: serde_json::Value = serde_json::from_str(&after).unwrap();
+        assert_eq!(after["before_bytes"], json["before_bytes"]);
+    }
+
+    #[tokio::test]
+    async fn test_get_consistency_reports_anomalies() {
+        let app = build_test_app(test_connection_factory!()).await;
+        let network = store::create_network(
+            &app.conn,
+            "Test Network",
+            "10.0.0.0/24",
+            "10.0.0.1",
+            &[],
+            86400,
+            None,
+            false,
+        )
+        .await
+        .unwrap();
+        // No pools, so any lease is outside them
+        store::create_or_update_lease_with_network(
+            &app.conn,
+            "aa:bb:cc:dd:ee:01",
+            &"10.0.0.5".parse().unwrap(),
+            None,
+            LeaseState::Active,
+            3600,
+            network.id,
+        )
+        .await
+        .unwrap();
+
+        let req = Request::builder()
+            .uri("/api/admin/consistency")
+            .body(Body::empty())
+            .unwrap();
+        let resp = app.router.oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(json["fixed"], false);
+        assert_eq!(json["anomalies"][0]["kind"], "lease_outside_pool");
+        assert_eq!(json["anomalies"][0]["ip"], "10.0.0.5");
+    }
+}
@@ -0,0 +1,233 @@
+//! `/api/arp` HTTP handler serving a neighbor table built from DHCP leases.
+//!
+//! Switches, monitoring and hosts that cannot ARP across a relay can load the
+//! MAC-to-IP mapping of every active lease instead, either as JSON or in the
+//! `/etc/ethers` format understood by `arp -f` and dnsmasq.
+
+use std::sync::Arc;
+
+use axum::{
+    Json, Router,
+    extract::{Query, State},
+    http::{HeaderMap, header},
+    response::{IntoResponse, Response},
+    routing::get,
+};
+use serde::Deserialize;
+
+use crate::{
+    dhcp::store::{self, ArpEntry},
+    http::{AppState, error::Error as HttpError},
+};
+
+/// Query string for `GET /api/arp`.
+#[derive(Deserialize)]
+pub struct ArpQuery {
+    /// `json` or `ethers`; overrides the `Accept` header.
+    pub format: Option<String>,
+}
+
+/// Output formats of `GET /api/arp`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ArpFormat {
+    Json,
+    Ethers,
+}
+
+// ---------------------------------------------------------------------------
+// Route registration
+// ---------------------------------------------------------------------------
+
+pub fn routes(state: Arc<AppState>) -> Router {
+    Router::new()
+        .route("/api/arp", get(get_arp))
+        .with_state(state)
+}
+
+// ---------------------------------------------------------------------------
+// Handlers
+// ---------------------------------------------------------------------------
+
+/// `GET /api/arp`
+///
+/// Every unexpired active lease as `{mac_address, ip_address, device_uuid,
+/// interface_name}`, ordered by IP. With `?format=ethers`, or an `Accept` header
+/// preferring `text/plain`, returns one `MAC IP` line per lease instead. Returns
+/// `400` for any other `format`.
+async fn get_arp(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<ArpQuery>,
+    headers: HeaderMap,
+) -> Result<Response, HttpError> {
+    let format = arp_format(query.format.as_deref(), &headers)?;
+    let conn = state.connection_factory.open().await?;
+    let entries = store::list_arp_entries(&conn).await?;
+
+    Ok(match format {
+        ArpFormat::Json => Json(entries).into_response(),
+        ArpFormat::Ethers => (
+            [(header::CONTENT_TYPE, "text/plain; charset=utf-8")],
+            format_ethers(&entries),
+        )
+            .into_response(),
+    })
+}
+
+// ---------------------------------------------------------------------------
+// Helpers
+// ---------------------------------------------------------------------------
+
+/// The format asked for by `?format=`, falling back to the `Accept` header.
+fn arp_format(format: Option<&str>, headers: &HeaderMap) -> Result<ArpFormat, HttpError> {
+    match format {
+        Some("json") => return Ok(ArpFormat::Json),
+        Some("ethers") => return Ok(ArpFormat::Ethers),
+        Some(other) => {
+            return Err(HttpError::BadRequest(format!(
+                "Unknown format '{}', expected 'json' or 'ethers'",
+                other
+            )));
+        }
+        None => {}
+    }
+
+    let accept = headers
+        .get(header::ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    if accept.starts_with("text/plain") {
+        Ok(ArpFormat::Ethers)
+    } else {
+        Ok(ArpFormat::Json)
+    }
+}
+
+/// Render entries as `/etc/ethers` lines: `aa:bb:cc:dd:ee:ff 10.0.0.5`.
+fn format_ethers(entries: &[ArpEntry]) -> String {
+    entries
+        .iter()
+        .map(|entry| format!("{} {}\n", entry.mac_address, entry.ip_address))
+        .collect()
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        body::Body,
+        http::{Request, StatusCode},
+    };
+    use tower::ServiceExt;
+
+    use crate::{
+        dhcp::LeaseState,
+        http::test_helpers::{TestApp, build_test_app},
+        test_connection_factory,
+    };
+
+    /// Two active leases and one offer on a fresh network.
+    async fn app_with_leases() -> TestApp {
+        let app = build_test_app(test_connection_factory!()).await;
+        let network = store::create_network(
+            &app.conn,
+            "Rack",
+            "10.0.0.0/24",
+            "10.0.0.1",
+            &[],
+            86400,
+            None,
+            false,
+        )
+        .await
+        .unwrap();
+        for (mac, ip, state) in [
+            ("aa:bb:cc:dd:ee:02", "10.0.0.20", LeaseState::Active),
+            ("aa:bb:cc:dd:ee:01", "10.0.0.10", LeaseState::Active),
+            ("aa:bb:cc:dd:ee:03", "10.0.0.30", LeaseState::Offered),
+        ] {
+            store::create_or_update_lease_with_network(
+                &app.conn,
+                mac,
+                &ip.parse().unwrap(),
+                None,
+                state,
+                3600,
+                network.id,
+            )
+            .await
+            .unwrap();
+        }
+        app
+    }
+
+    async fn get(app: &TestApp, uri: &str, accept: Option<&str>) -> (StatusCode, String, String) {
+        let mut req = Request::builder().uri(uri);
+        if let Some(accept) = accept {
+            req = req.header(header::ACCEPT, accept);
+        }
+        let resp = app
+            .router
+            .clone()
+            .oneshot(req.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = resp.status();
+        let content_type = resp
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .map(|v| v.to_str().unwrap().to_string())
+            .unwrap_or_default();
+        let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (
+            status,
+            content_type,
+            String::from_utf8(bytes.to_vec()).unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_get_arp_json() {
+        let app = app_with_leases().await;
+
+        let (status, content_type, body) = get(&app, "/api/arp", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(content_type, "application/json");
+        let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+        let entries = json.as_array().unwrap();
+        assert_eq!(entries.len(), 2, "offered leases are not listed");
+        assert_eq!(entries[0]["mac_address"], "aa:bb:cc:dd:ee:01");
+        assert_eq!(entries[0]["ip_address"], "10.0.0.10");
+        assert!(entries[0]["device_uuid"].is_null());
+        assert_eq!(entries[1]["ip_address"], "10.0.0.20");
+    }
+
+    #[tokio::test]
+    async fn test_get_arp_ethers() {
+        let app = app_with_leases().await;
+        let expected = "aa:bb:cc:dd:ee:01 10.0.0.10\naa:bb:cc:dd:ee:02 10.0.0.20\n";
+
+        let (status, content_type, body) = get(&app, "/api/arp?format=ethers", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(content_type.starts_with("text/plain"));
+        assert_eq!(body, expected);
+
+        let (_, _, body) = get(&app, "/api/arp", Some("text/plain")).await;
+        assert_eq!(body, expected, "Accept: text/plain selects ethers");
+
+        let (_, content_type, _) = get(&app, "/api/arp?format=json", Some("text/plain")).await;
+        assert_eq!(content_type, "application/json", "?format wins over Accept");
+    }
+
+    #[tokio::test]
+    async fn test_get_arp_unknown_format() {
+        let app = build_test_app(test_connection_factory!()).await;
+        let (status, _, _) = get(&app, "/api/arp?format=xml", None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+}
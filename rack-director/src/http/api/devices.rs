@@ -1,30 +1,122 @@
-//! `/api/devices` HTTP handlers for device-level disk label overrides and warnings.
+//! `/api/devices` HTTP handlers for listing devices, interfaces, tags, device-level
+//! disk label overrides, warnings, inventory, one-shot rediscovery and bulk operations.
 //!
-//! These endpoints allow operators to pin platform labels to specific disk paths on a
-//! per-device basis, and to view or dismiss warnings that the system generates
-//! automatically (e.g. when a stale label override is removed).
+//! These endpoints allow operators to group devices with `key=value` tags and filter
+//! by them, to pin platform labels to specific disk paths on a per-device basis, to
+//! view or dismiss warnings that the system generates automatically (e.g. when a
+//! stale label override is removed), to read the hardware inventory reported by the
+//! discovery image, and to force a single hardware rescan on the device's next boot.
+//! Operators can also keep free-text notes on a device, and automation can poll what
+//! a device would boot next.
 
 use std::sync::Arc;
 
 use axum::{
     Json, Router,
-    extract::{Path, State},
+    extract::{Path, Query, State},
     http::StatusCode,
-    routing::{delete, get, put},
+    routing::{delete, get, post, put},
 };
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
 use crate::{
+    device_inventory,
+    device_tags::{self, DeviceTag},
     device_warnings,
-    director::Director,
-    http::{AppState, error::Error as HttpError},
+    director::{Director, NetworkInterface, power::PowerAction},
+    http::{
+        AppState,
+        audit::{self, Actor},
+        error::Error as HttpError,
+    },
+    lifecycle::DeviceLifecycle,
+    plans::actions::BootTarget,
 };
 
 // ---------------------------------------------------------------------------
 // Request / response types
 // ---------------------------------------------------------------------------
 
+/// Query string for `GET /api/devices`.
+#[derive(Deserialize)]
+pub struct ListDevicesQuery {
+    /// `key:value`; when given, only devices carrying that tag are listed.
+    pub tag: Option<String>,
+}
+
+/// A device entry in `GET /api/devices` responses.
+#[derive(Serialize)]
+pub struct DeviceSummary {
+    pub uuid: Uuid,
+    pub hostname: Option<String>,
+    pub lifecycle: Option<DeviceLifecycle>,
+    pub role_id: Option<i64>,
+    pub platform_id: Option<i64>,
+    pub tags: Vec<DeviceTag>,
+    pub notes: Option<String>,
+}
+
+/// An entry in `GET /api/devices/{uuid}/interfaces` responses.
+#[derive(Serialize)]
+pub struct InterfaceSummary {
+    #[serde(flatten)]
+    pub interface: NetworkInterface,
+    /// When the director last received a DHCP packet from this NIC, if ever.
+    pub last_seen_at: Option<DateTime<Utc>>,
+}
+
+/// Body for `PUT /api/devices/{uuid}/notes`, also its response.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct DeviceNotes {
+    /// `null` or blank to clear.
+    pub notes: Option<String>,
+}
+
+/// Longest accepted device notes, in characters.
+const MAX_NOTES_LEN: usize = 4096;
+
+/// Body for `PUT /api/devices/{uuid}/tags/{key}`.
+#[derive(Deserialize)]
+pub struct PutTagRequest {
+    pub value: String,
+}
+
+/// Operation applied by `POST /api/devices/bulk`.
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum BulkAction {
+    /// Power-cycle the device through its BMC.
+    Reset,
+    /// Netboot into a hardware scan on the next boot only.
+    Rediscover,
+    /// Start a lifecycle transition to `state`.
+    SetState,
+}
+
+/// Body for `POST /api/devices/bulk`.
+///
+/// Exactly one of `uuids` or `tag` selects the devices. Racks are selected by tag
+/// (e.g. `rack:r12`).
+#[derive(Deserialize)]
+pub struct BulkRequest {
+    pub uuids: Option<Vec<Uuid>>,
+    /// `key:value`, as for `GET /api/devices?tag=`.
+    pub tag: Option<String>,
+    pub action: BulkAction,
+    /// Target lifecycle state; required for `set_state`.
+    pub state: Option<DeviceLifecycle>,
+}
+
+/// Outcome for one device in a `POST /api/devices/bulk` response.
+#[derive(Debug, Serialize)]
+pub struct BulkResult {
+    pub uuid: Uuid,
+    pub ok: bool,
+    pub error: Option<String>,
+}
+
 /// Body for `PUT /api/devices/{id}/label-overrides`.
 #[derive(Deserialize)]
 pub struct PutLabelOverrideRequest {
@@ -53,6 +145,14 @@ pub struct LabelOverridesResponse {
 
 pub fn routes(state: Arc<AppState>) -> Router {
     Router::new()
+        .route("/api/devices", get(list_devices))
+        .route("/api/devices/bulk", post(post_bulk))
+        .route("/api/devices/{uuid}/interfaces", get(get_interfaces))
+        .route("/api/devices/{uuid}/tags", get(get_tags))
+        .route(
+            "/api/devices/{uuid}/tags/{key}",
+            put(put_tag).delete(delete_tag),
+        )
         .route(
             "/api/devices/{uuid}/label-overrides",
             put(put_label_override),
@@ -61,11 +161,15 @@ pub fn routes(state: Arc<AppState>) -> Router {
             "/api/devices/{uuid}/label-overrides/{label}",
             delete(delete_label_override),
         )
+        .route("/api/devices/{uuid}/inventory", get(get_inventory))
         .route("/api/devices/{uuid}/warnings", get(get_warnings))
         .route(
             "/api/devices/{uuid}/warnings/{warning_id}",
             delete(delete_warning),
         )
+        .route("/api/devices/{uuid}/rediscover", post(post_rediscover))
+        .route("/api/devices/{uuid}/boot-target", get(get_boot_target))
+        .route("/api/devices/{uuid}/notes", put(put_notes))
         .with_state(state)
 }
 
@@ -73,6 +177,148 @@ pub fn routes(state: Arc<AppState>) -> Router {
 // Handlers
 // ---------------------------------------------------------------------------
 
+/// `GET /api/devices[?tag=key:value]`
+///
+/// List devices with their tags, optionally only those tagged `key=value`.
+///
+/// Returns `400` if `tag` is not of the form `key:value`.
+async fn list_devices(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<ListDevicesQuery>,
+) -> Result<Json<Vec<DeviceSummary>>, HttpError> {
+    let filter = query.tag.as_deref().map(parse_tag_filter).transpose()?;
+
+    let conn = state.connection_factory.open().await?;
+    let mut devices = Director::new(&conn).get_all_devices().await?;
+    if let Some((key, value)) = filter {
+        let tagged = device_tags::device_uuids_with_tag(&conn, key, value).await?;
+        devices.retain(|device| tagged.contains(&device.uuid));
+    }
+
+    let mut summaries = Vec::with_capacity(devices.len());
+    for device in devices {
+        summaries.push(DeviceSummary {
+            uuid: device.uuid,
+            hostname: device.attributes.hostname,
+            lifecycle: device.lifecycle,
+            role_id: device.role_id,
+            platform_id: device.platform_id,
+            tags: device_tags::list_tags(&conn, device.id).await?,
+            notes: device.notes,
+        });
+    }
+    Ok(Json(summaries))
+}
+
+/// `GET /api/devices/{uuid}/interfaces`
+///
+/// List the device's network interfaces with when each was last seen on the network.
+///
+/// Returns `404` if the device is not found.
+async fn get_interfaces(
+    State(state): State<Arc<AppState>>,
+    Path(uuid): Path<Uuid>,
+) -> Result<Json<Vec<InterfaceSummary>>, HttpError> {
+    let conn = state.connection_factory.open().await?;
+    let director = Director::new(&conn);
+    if !director.device_exists(&uuid).await? {
+        return Err(HttpError::NotFound(format!("Device {} not found", uuid)));
+    }
+
+    let mut last_seen = director.get_interface_last_seen(&uuid).await?;
+    let interfaces = director
+        .get_network_interfaces(&uuid)
+        .await?
+        .into_iter()
+        .map(|interface| InterfaceSummary {
+            last_seen_at: last_seen.remove(&interface.mac_address),
+            interface,
+        })
+        .collect();
+    Ok(Json(interfaces))
+}
+
+/// `GET /api/devices/{uuid}/tags`
+///
+/// List the device's tags ordered by key.
+async fn get_tags(
+    State(state): State<Arc<AppState>>,
+    Path(uuid): Path<Uuid>,
+) -> Result<Json<Vec<DeviceTag>>, HttpError> {
+    let conn = state.connection_factory.open().await?;
+    let device_id = device_warnings::get_device_id_by_uuid(&conn, &uuid)
+        .await?
+        .ok_or_else(|| HttpError::NotFound(format!("Device {} not found", uuid)))?;
+
+    Ok(Json(device_tags::list_tags(&conn, device_id).await?))
+}
+
+/// `PUT /api/devices/{uuid}/tags/{key}`
+///
+/// Set the tag `key` to the body's `value`, replacing any previous value.
+///
+/// Returns the device's tags, `400` for an empty or `:`-containing key or an empty
+/// value, and `404` if the device is not found.
+async fn put_tag(
+    State(state): State<Arc<AppState>>,
+    actor: Actor,
+    Path((uuid, key)): Path<(Uuid, String)>,
+    Json(req): Json<PutTagRequest>,
+) -> Result<Json<Vec<DeviceTag>>, HttpError> {
+    validate_tag(&key, &req.value)?;
+
+    let conn = state.connection_factory.open().await?;
+    let device_id = device_warnings::get_device_id_by_uuid(&conn, &uuid)
+        .await?
+        .ok_or_else(|| HttpError::NotFound(format!("Device {} not found", uuid)))?;
+
+    let before = device_tags::list_tags(&conn, device_id).await?;
+    device_tags::set_tag(&conn, device_id, &key, &req.value).await?;
+    let tags = device_tags::list_tags(&conn, device_id).await?;
+    audit::record(
+        &conn,
+        &actor,
+        "device.tag",
+        &format!("device/{}", uuid),
+        audit::summary(&before),
+        audit::summary(&tags),
+    )
+    .await;
+    Ok(Json(tags))
+}
+
+/// `DELETE /api/devices/{uuid}/tags/{key}`
+///
+/// Remove a tag. Returns `204 No Content`, or `404` if the device or tag is not found.
+async fn delete_tag(
+    State(state): State<Arc<AppState>>,
+    actor: Actor,
+    Path((uuid, key)): Path<(Uuid, String)>,
+) -> Result<StatusCode, HttpError> {
+    let conn = state.connection_factory.open().await?;
+    let device_id = device_warnings::get_device_id_by_uuid(&conn, &uuid)
+        .await?
+        .ok_or_else(|| HttpError::NotFound(format!("Device {} not found", uuid)))?;
+
+    let before = device_tags::list_tags(&conn, device_id).await?;
+    if !device_tags::remove_tag(&conn, device_id, &key).await? {
+        return Err(HttpError::NotFound(format!(
+            "Tag '{}' not found on device {}",
+            key, uuid
+        )));
+    }
+    audit::record(
+        &conn,
+        &actor,
+        "device.untag",
+        &format!("device/{}", uuid),
+        audit::summary(&before),
+        audit::summary(&device_tags::list_tags(&conn, device_id).await?),
+    )
+    .await;
+    Ok(StatusCode::NO_CONTENT)
+}
+
 /// `PUT /api/devices/{uuid}/label-overrides`
 ///
 /// Add or update a single disk label override for the device.  The body must
@@ -82,6 +328,7 @@ pub fn routes(state: Arc<AppState>) -> Router {
 /// Returns the full updated map of label overrides.
 async fn put_label_override(
     State(state): State<Arc<AppState>>,
+    actor: Actor,
     Path(uuid): Path<Uuid>,
     Json(req): Json<PutLabelOverrideRequest>,
 ) -> Result<(StatusCode, Json<LabelOverridesResponse>), HttpError> {
@@ -96,8 +343,19 @@ async fn put_label_override(
         .map_err(|_| HttpError::NotFound(format!("Device {} not found", uuid)))?;
 
     let mut attrs = device.attributes.clone();
-    attrs.disk_label_overrides.insert(req.label, req.path);
+    let before = attrs
+        .disk_label_overrides
+        .insert(req.label.clone(), req.path.clone());
     director.update_attributes_raw(&uuid, &attrs).await?;
+    audit::record(
+        &conn,
+        &actor,
+        "device.label_override",
+        &format!("device/{}", uuid),
+        before.map(|path| serde_json::json!({ "label": req.label, "path": path })),
+        Some(serde_json::json!({ "label": req.label, "path": req.path })),
+    )
+    .await;
 
     let overrides = build_overrides_response(&attrs.disk_label_overrides);
     Ok((StatusCode::OK, Json(LabelOverridesResponse { overrides })))
@@ -110,6 +368,7 @@ async fn put_label_override(
 /// Returns `204 No Content` on success, `404` if the device or label is not found.
 async fn delete_label_override(
     State(state): State<Arc<AppState>>,
+    actor: Actor,
     Path((uuid, label)): Path<(Uuid, String)>,
 ) -> Result<StatusCode, HttpError> {
     let conn = state.connection_factory.open().await?;
@@ -121,19 +380,48 @@ async fn delete_label_override(
         .map_err(|_| HttpError::NotFound(format!("Device {} not found", uuid)))?;
 
     let mut attrs = device.attributes.clone();
-    let removed = attrs.disk_label_overrides.remove(&label);
-    if removed.is_none() {
+    let Some(removed) = attrs.disk_label_overrides.remove(&label) else {
         return Err(HttpError::NotFound(format!(
             "Label override '{}' not found on device {}",
             label, uuid
         )));
-    }
+    };
 
     director.update_attributes_raw(&uuid, &attrs).await?;
+    audit::record(
+        &conn,
+        &actor,
+        "device.label_override",
+        &format!("device/{}", uuid),
+        Some(serde_json::json!({ "label": label, "path": removed })),
+        None,
+    )
+    .await;
 
     Ok(StatusCode::NO_CONTENT)
 }
 
+/// `GET /api/devices/{uuid}/inventory`
+///
+/// The latest hardware inventory submitted by the device's discovery image.
+///
+/// Returns `404` if the device is unknown or has not submitted an inventory yet.
+async fn get_inventory(
+    State(state): State<Arc<AppState>>,
+    Path(uuid): Path<Uuid>,
+) -> Result<Json<device_inventory::DeviceInventory>, HttpError> {
+    let conn = state.connection_factory.open().await?;
+
+    let device_id = device_warnings::get_device_id_by_uuid(&conn, &uuid)
+        .await?
+        .ok_or_else(|| HttpError::NotFound(format!("Device {} not found", uuid)))?;
+
+    let inventory = device_inventory::get_inventory(&conn, device_id)
+        .await?
+        .ok_or_else(|| HttpError::NotFound(format!("No inventory for device {}", uuid)))?;
+    Ok(Json(inventory))
+}
+
 /// `GET /api/devices/{uuid}/warnings`
 ///
 /// List all warnings for the device.
@@ -158,6 +446,7 @@ async fn get_warnings(
 /// Returns `204 No Content` on success, `404` if the device or warning is not found.
 async fn delete_warning(
     State(state): State<Arc<AppState>>,
+    actor: Actor,
     Path((uuid, warning_id)): Path<(Uuid, i64)>,
 ) -> Result<StatusCode, HttpError> {
     let conn = state.connection_factory.open().await?;
@@ -168,6 +457,15 @@ async fn delete_warning(
 
     let deleted = device_warnings::delete_warning(&conn, warning_id, device_id).await?;
     if deleted {
+        audit::record(
+            &conn,
+            &actor,
+            "device.dismiss_warning",
+            &format!("device/{}", uuid),
+            Some(serde_json::json!({ "warning_id": warning_id })),
+            None,
+        )
+        .await;
         Ok(StatusCode::NO_CONTENT)
     } else {
         Err(HttpError::NotFound(format!(
@@ -177,10 +475,263 @@ async fn delete_warning(
     }
 }
 
+/// `POST /api/devices/{uuid}/rediscover`
+///
+/// Netboot the device into a hardware scan on its next boot only. The flag is
+/// cleared when the boot target is served, so later boots follow the normal
+/// plan and lifecycle logic. The device's lifecycle state is not changed.
+///
+/// Returns `204 No Content` on success, `404` if the device is not found.
+async fn post_rediscover(
+    State(state): State<Arc<AppState>>,
+    actor: Actor,
+    Path(uuid): Path<Uuid>,
+) -> Result<StatusCode, HttpError> {
+    let conn = state.connection_factory.open().await?;
+    let director = Director::new(&conn);
+
+    if director.request_rediscovery(&uuid).await? {
+        audit::record(
+            &conn,
+            &actor,
+            "device.rediscover",
+            &format!("device/{}", uuid),
+            None,
+            None,
+        )
+        .await;
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(HttpError::NotFound(format!("Device {} not found", uuid)))
+    }
+}
+
+/// `GET /api/devices/{uuid}/boot-target`
+///
+/// What the device would boot if it booted now, tagged by `type`: `local_disk`,
+/// `net_boot` or `agent_image` with their kernel and cmdline, `sleep_reboot` or
+/// `hold`. Read-only: unlike a real boot it does not update `last_seen_at` or
+/// consume a pending rediscovery.
+///
+/// Returns `404` if the device is not found.
+async fn get_boot_target(
+    State(state): State<Arc<AppState>>,
+    Path(uuid): Path<Uuid>,
+) -> Result<Json<BootTarget>, HttpError> {
+    let conn = state.connection_factory.open().await?;
+    let director = Director::new(&conn);
+    if !director.device_exists(&uuid).await? {
+        return Err(HttpError::NotFound(format!("Device {} not found", uuid)));
+    }
+
+    let target = director
+        .peek_boot_target(&uuid, state.unprovisioned_sleep_secs)
+        .await?;
+    Ok(Json(target))
+}
+
+/// `PUT /api/devices/{uuid}/notes`
+///
+/// Replace the device's notes; a `null` or blank `notes` clears them. Notes are kept
+/// when the device is rediscovered or reprovisioned.
+///
+/// Returns the stored notes, `400` if they are longer than 4096 characters or
+/// contain control characters other than newlines and tabs, and `404` if the device
+/// is not found.
+async fn put_notes(
+    State(state): State<Arc<AppState>>,
+    actor: Actor,
+    Path(uuid): Path<Uuid>,
+    Json(req): Json<DeviceNotes>,
+) -> Result<Json<DeviceNotes>, HttpError> {
+    let notes = req.notes.filter(|notes| !notes.trim().is_empty());
+    if let Some(notes) = &notes {
+        validate_notes(notes)?;
+    }
+
+    let conn = state.connection_factory.open().await?;
+    let director = Director::new(&conn);
+    let before = director
+        .get_device(&uuid)
+        .await
+        .map_err(|_| HttpError::NotFound(format!("Device {} not found", uuid)))?
+        .notes;
+    director.set_notes(&uuid, notes.as_deref()).await?;
+    audit::record(
+        &conn,
+        &actor,
+        "device.notes",
+        &format!("device/{}", uuid),
+        audit::summary(&before),
+        audit::summary(&notes),
+    )
+    .await;
+    Ok(Json(DeviceNotes { notes }))
+}
+
+/// `POST /api/devices/bulk`
+///
+/// Apply `reset`, `rediscover` or `set_state` to every selected device, using the
+/// same logic as the single-device endpoints. Devices are handled one at a time and
+/// independently: a BMC power cycle cannot be rolled back, so one device failing
+/// does not undo or skip the others. Each success is audited individually.
+///
+/// Returns `200` with one [`BulkResult`] per device, in selection order. Returns
+/// `400` if the selector is missing or ambiguous, the tag filter is malformed, or
+/// `set_state` has no `state`.
+async fn post_bulk(
+    State(state): State<Arc<AppState>>,
+    actor: Actor,
+    Json(req): Json<BulkRequest>,
+) -> Result<Json<Vec<BulkResult>>, HttpError> {
+    if req.action == BulkAction::SetState && req.state.is_none() {
+        return Err(HttpError::BadRequest(
+            "set_state requires a state".to_string(),
+        ));
+    }
+
+    let conn = state.connection_factory.open().await?;
+    let uuids = match (req.uuids, req.tag.as_deref()) {
+        (Some(uuids), None) => uuids,
+        (None, Some(tag)) => {
+            let (key, value) = parse_tag_filter(tag)?;
+            device_tags::device_uuids_with_tag(&conn, key, value).await?
+        }
+        _ => {
+            return Err(HttpError::BadRequest(
+                "exactly one of uuids or tag must be given".to_string(),
+            ));
+        }
+    };
+
+    let director = Director::with_power_config(&conn, state.power_config);
+    let mut results = Vec::with_capacity(uuids.len());
+    for uuid in uuids {
+        let error = match apply_bulk_action(&director, &uuid, req.action, &req.state).await {
+            Ok(()) => {
+                let (action, after) = bulk_audit_entry(req.action, &req.state);
+                audit::record(
+                    &conn,
+                    &actor,
+                    action,
+                    &format!("device/{}", uuid),
+                    None,
+                    after,
+                )
+                .await;
+                None
+            }
+            Err(e) => Some(e),
+        };
+        results.push(BulkResult {
+            uuid,
+            ok: error.is_none(),
+            error,
+        });
+    }
+    Ok(Json(results))
+}
+
 // ---------------------------------------------------------------------------
 // Helpers
 // ---------------------------------------------------------------------------
 
+/// Apply one bulk action to one device, describing any failure for the response.
+async fn apply_bulk_action(
+    director: &Director<'_>,
+    uuid: &Uuid,
+    action: BulkAction,
+    state: &Option<DeviceLifecycle>,
+) -> Result<(), String> {
+    match director.device_exists(uuid).await {
+        Ok(true) => {}
+        Ok(false) => return Err(format!("Device {} not found", uuid)),
+        Err(e) => return Err(e.to_string()),
+    }
+
+    match action {
+        BulkAction::Reset => match director.power_action(uuid, PowerAction::Cycle).await {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(format!("No BMC configured for device {}", uuid)),
+            Err(e) => Err(format!("Power command failed: {}", e)),
+        },
+        BulkAction::Rediscover => match director.request_rediscovery(uuid).await {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(format!("Device {} not found", uuid)),
+            Err(e) => Err(e.to_string()),
+        },
+        BulkAction::SetState => {
+            let to_state = state.clone().expect("checked by post_bulk");
+            director
+                .start_lifecycle_transition(uuid, to_state)
+                .await
+                .map(|_| ())
+                .map_err(|e| e.to_string())
+        }
+    }
+}
+
+/// Audit action and `after` value matching what the single-device endpoint records.
+fn bulk_audit_entry(
+    action: BulkAction,
+    state: &Option<DeviceLifecycle>,
+) -> (&'static str, Option<serde_json::Value>) {
+    match action {
+        BulkAction::Reset => (
+            "device.power",
+            Some(serde_json::json!({ "action": PowerAction::Cycle })),
+        ),
+        BulkAction::Rediscover => ("device.rediscover", None),
+        BulkAction::SetState => ("device.lifecycle_transition", audit::summary(state)),
+    }
+}
+
+/// Split a `key:value` tag filter at the first `:`.
+fn parse_tag_filter(filter: &str) -> Result<(&str, &str), HttpError> {
+    match filter.split_once(':') {
+        Some((key, value)) if !key.is_empty() && !value.is_empty() => Ok((key, value)),
+        _ => Err(HttpError::BadRequest(format!(
+            "tag filter must be key:value, got '{}'",
+            filter
+        ))),
+    }
+}
+
+/// Validate a tag key and value. Keys cannot contain `:` so filters stay unambiguous.
+fn validate_tag(key: &str, value: &str) -> Result<(), HttpError> {
+    if key.trim().is_empty() || key.contains(':') {
+        return Err(HttpError::BadRequest(
+            "tag key must be non-empty and must not contain ':'".to_string(),
+        ));
+    }
+    if value.trim().is_empty() {
+        return Err(HttpError::BadRequest(
+            "tag value must not be empty".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Validate device notes: at most [`MAX_NOTES_LEN`] characters, and no control
+/// characters other than newlines and tabs.
+fn validate_notes(notes: &str) -> Result<(), HttpError> {
+    if notes.chars().count() > MAX_NOTES_LEN {
+        return Err(HttpError::BadRequest(format!(
+            "notes must be at most {} characters",
+            MAX_NOTES_LEN
+        )));
+    }
+    if notes
+        .chars()
+        .any(|c| c.is_control() && !matches!(c, '\n' | '\r' | '\t'))
+    {
+        return Err(HttpError::BadRequest(
+            "notes must not contain control characters".to_string(),
+        ));
+    }
+    Ok(())
+}
+
 /// Validate a `PUT` label-override request.
 fn validate_label_override_request(req: &PutLabelOverrideRequest) -> Result<(), HttpError> {
     if req.label.trim().is_empty() {
@@ -489,4 +1040,419 @@ mod tests {
         let resp = app.oneshot(req).await.unwrap();
         assert_eq!(resp.status(), StatusCode::NOT_FOUND);
     }
+
+    #[tokio::test]
+    async fn test_post_rediscover_sets_one_shot_flag() {
+        let (app, conn, uuid) = setup_app(test_connection_factory!()).await;
+
+        let req = Request::builder()
+            .method(Method::POST)
+            .uri(format!("/api/devices/{}/rediscover", uuid))
+            .body(Body::empty())
+            .unwrap();
+
+        let resp = app.oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+
+        let pending: bool = conn
+            .query_one(
+                "SELECT rediscover_pending FROM devices WHERE uuid = ?1",
+                (uuid,),
+                |r| r.get(0),
+            )
+            .await
+            .unwrap();
+        assert!(pending);
+    }
+
+    #[tokio::test]
+    async fn test_post_rediscover_device_not_found() {
+        let (app, _conn, _uuid) = setup_app(test_connection_factory!()).await;
+
+        let req = Request::builder()
+            .method(Method::POST)
+            .uri("/api/devices/d4000000-0000-0000-0000-00000000ffff/rediscover")
+            .body(Body::empty())
+            .unwrap();
+
+        let resp = app.oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_get_boot_target_does_not_record_a_boot() {
+        let (app, conn, uuid) = setup_app(test_connection_factory!()).await;
+        crate::lifecycle::store::update_device_lifecycle(
+            &conn,
+            &uuid,
+            DeviceLifecycle::Provisioned,
+        )
+        .await
+        .unwrap();
+        Director::new(&conn)
+            .request_rediscovery(&uuid)
+            .await
+            .unwrap();
+        let device_state = || async {
+            conn.query_one(
+                "SELECT last_seen_at, rediscover_pending FROM devices WHERE uuid = ?1",
+                (uuid,),
+                |r| Ok((r.get::<_, Option<String>>(0)?, r.get::<_, bool>(1)?)),
+            )
+            .await
+            .unwrap()
+        };
+        let before = device_state().await;
+
+        for _ in 0..2 {
+            let (status, body) = get_json(&app, &format!("/api/devices/{uuid}/boot-target")).await;
+            assert_eq!(status, StatusCode::OK);
+            assert_eq!(body["type"], "agent_image", "pending rediscovery: {}", body);
+        }
+        assert_eq!(device_state().await, before);
+        assert!(before.1, "rediscovery is still pending");
+
+        // Once the device really boots, the rescan is consumed and local disk is next
+        Director::new(&conn)
+            .next_boot_target(&uuid, 600)
+            .await
+            .unwrap();
+        let (_, body) = get_json(&app, &format!("/api/devices/{uuid}/boot-target")).await;
+        assert_eq!(body, json!({ "type": "local_disk" }));
+
+        let missing = Uuid::parse_str("d4000000-0000-0000-0000-00000000ffff").unwrap();
+        let (status, _) = get_json(&app, &format!("/api/devices/{missing}/boot-target")).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    async fn put_tag(app: &axum::Router, uuid: &Uuid, key: &str, value: &str) -> StatusCode {
+        let req = Request::builder()
+            .method(Method::PUT)
+            .uri(format!("/api/devices/{}/tags/{}", uuid, key))
+            .header("content-type", "application/json")
+            .body(Body::from(json!({ "value": value }).to_string()))
+            .unwrap();
+        app.clone().oneshot(req).await.unwrap().status()
+    }
+
+    async fn list_devices(app: &axum::Router, query: &str) -> (StatusCode, serde_json::Value) {
+        get_json(app, &format!("/api/devices{}", query)).await
+    }
+
+    async fn get_json(app: &axum::Router, uri: &str) -> (StatusCode, serde_json::Value) {
+        let req = Request::builder().uri(uri).body(Body::empty()).unwrap();
+        let resp = app.clone().oneshot(req).await.unwrap();
+        let status = resp.status();
+        let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap_or_default())
+    }
+
+    #[tokio::test]
+    async fn test_get_interfaces_reports_last_seen() {
+        let (app, conn, uuid) = setup_app(test_connection_factory!()).await;
+        let director = Director::new(&conn);
+        director
+            .set_device_ip_address(&uuid, "10.0.0.5", "aa:bb:cc:00:00:01")
+            .await
+            .unwrap();
+        director
+            .set_device_ip_address(&uuid, "10.0.0.6", "aa:bb:cc:00:00:02")
+            .await
+            .unwrap();
+        director
+            .mark_interface_seen(&uuid, "aa:bb:cc:00:00:01")
+            .await
+            .unwrap();
+
+        let (status, body) = get_json(&app, &format!("/api/devices/{uuid}/interfaces")).await;
+        assert_eq!(status, StatusCode::OK);
+        let interfaces = body.as_array().unwrap();
+        assert_eq!(interfaces.len(), 2);
+        assert_eq!(interfaces[0]["mac_address"], "aa:bb:cc:00:00:01");
+        assert!(interfaces[0]["last_seen_at"].is_string());
+        assert_eq!(interfaces[1]["mac_address"], "aa:bb:cc:00:00:02");
+        assert!(interfaces[1]["last_seen_at"].is_null());
+
+        let missing = Uuid::parse_str("d4000000-0000-0000-0000-00000000ffff").unwrap();
+        let (status, _) = get_json(&app, &format!("/api/devices/{missing}/interfaces")).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_held_database_lock_returns_timely_503() {
+        let (app, conn, uuid) = setup_app(test_connection_factory!()).await;
+        // Hold an uncommitted write so the handler's read cannot take the table lock
+        conn.execute("BEGIN IMMEDIATE", ()).await.unwrap();
+        conn.execute(
+            "UPDATE devices SET lifecycle = 'broken' WHERE uuid = ?1",
+            (uuid,),
+        )
+        .await
+        .unwrap();
+
+        let (status, _) = tokio::time::timeout(database::BUSY_TIMEOUT * 2, list_devices(&app, ""))
+            .await
+            .expect("handler should give up on the lock instead of hanging");
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+
+        conn.execute("ROLLBACK", ()).await.unwrap();
+        let (status, _) = list_devices(&app, "").await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_held_file_database_lock_returns_503_after_busy_timeout() {
+        // A shared-cache memory database fails with SQLITE_LOCKED at once; only a real
+        // file makes SQLite wait out the busy timeout before giving up.
+        let dir = tempfile::tempdir().unwrap();
+        let factory = DatabaseConnectionFactory::new(dir.path().join("rack-director.db"));
+        let (app, conn, _uuid) = setup_app(factory).await;
+        conn.execute("BEGIN EXCLUSIVE", ()).await.unwrap();
+
+        let started = std::time::Instant::now();
+        let (status, _) = tokio::time::timeout(database::BUSY_TIMEOUT * 2, list_devices(&app, ""))
+            .await
+            .expect("handler should give up on the lock instead of hanging");
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert!(
+            started.elapsed() >= database::BUSY_TIMEOUT / 2,
+            "gave up after {:?} without waiting on the lock",
+            started.elapsed()
+        );
+
+        conn.execute("ROLLBACK", ()).await.unwrap();
+        let (status, _) = list_devices(&app, "").await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_tag_device_and_filter_by_tag() {
+        let (app, conn, uuid) = setup_app(test_connection_factory!()).await;
+        let other = Uuid::parse_str("d4000000-0000-0000-0000-000000000002").unwrap();
+        conn.execute(
+            "INSERT INTO devices (uuid, lifecycle, architecture) VALUES (?1, 'new', 'x86-64')",
+            (other,),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            put_tag(&app, &uuid, "role", "compute").await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            put_tag(&app, &other, "role", "storage").await,
+            StatusCode::OK
+        );
+
+        let (status, all) = list_devices(&app, "").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(all.as_array().unwrap().len(), 2);
+
+        let (status, compute) = list_devices(&app, "?tag=role:compute").await;
+        assert_eq!(status, StatusCode::OK);
+        let compute = compute.as_array().unwrap();
+        assert_eq!(compute.len(), 1);
+        assert_eq!(compute[0]["uuid"], uuid.to_string());
+        assert_eq!(
+            compute[0]["tags"],
+            json!([{ "key": "role", "value": "compute" }])
+        );
+
+        let (status, _) = list_devices(&app, "?tag=role").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_delete_tag() {
+        let (app, _conn, uuid) = setup_app(test_connection_factory!()).await;
+        assert_eq!(put_tag(&app, &uuid, "role", "gpu").await, StatusCode::OK);
+
+        let delete = |key: &'static str| {
+            Request::builder()
+                .method(Method::DELETE)
+                .uri(format!("/api/devices/{}/tags/{}", uuid, key))
+                .body(Body::empty())
+                .unwrap()
+        };
+        let resp = app.clone().oneshot(delete("role")).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+        let resp = app.clone().oneshot(delete("role")).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        let (_, compute) = list_devices(&app, "?tag=role:gpu").await;
+        assert!(compute.as_array().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_put_tag_rejects_colon_in_key() {
+        let (app, _conn, uuid) = setup_app(test_connection_factory!()).await;
+        assert_eq!(
+            put_tag(&app, &uuid, "role:x", "compute").await,
+            StatusCode::BAD_REQUEST
+        );
+    }
+
+    async fn put_notes(
+        app: &axum::Router,
+        uuid: &Uuid,
+        notes: serde_json::Value,
+    ) -> (StatusCode, serde_json::Value) {
+        let req = Request::builder()
+            .method(Method::PUT)
+            .uri(format!("/api/devices/{}/notes", uuid))
+            .header("content-type", "application/json")
+            .body(Body::from(json!({ "notes": notes }).to_string()))
+            .unwrap();
+        let resp = app.clone().oneshot(req).await.unwrap();
+        let status = resp.status();
+        let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap_or_default())
+    }
+
+    #[tokio::test]
+    async fn test_put_notes_shown_in_device_list() {
+        let (app, _conn, uuid) = setup_app(test_connection_factory!()).await;
+
+        let (_, json) = list_devices(&app, "").await;
+        assert!(json[0]["notes"].is_null());
+
+        let notes = "flaky PSU, RMA pending\n\"slot 2\" <b>";
+        let (status, json) = put_notes(&app, &uuid, json!(notes)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(json["notes"], notes);
+        let (_, json) = list_devices(&app, "").await;
+        assert_eq!(json[0]["notes"], notes, "stored and returned verbatim");
+
+        // Blank clears, like null
+        let (status, json) = put_notes(&app, &uuid, json!("  ")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(json["notes"].is_null());
+        let (_, json) = list_devices(&app, "").await;
+        assert!(json[0]["notes"].is_null());
+    }
+
+    #[tokio::test]
+    async fn test_put_notes_rejects_invalid_notes() {
+        let (app, _conn, uuid) = setup_app(test_connection_factory!()).await;
+        put_notes(&app, &uuid, json!("keep me")).await;
+
+        let (status, _) = put_notes(&app, &uuid, json!("x".repeat(MAX_NOTES_LEN + 1))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, _) = put_notes(&app, &uuid, json!("bell\u{7}")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (_, json) = list_devices(&app, "").await;
+        assert_eq!(
+            json[0]["notes"], "keep me",
+            "rejected notes leave the old ones"
+        );
+
+        // Limit is in characters, not bytes
+        let (status, _) = put_notes(&app, &uuid, json!("é".repeat(MAX_NOTES_LEN))).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let missing = Uuid::parse_str("d4000000-0000-0000-0000-00000000ffff").unwrap();
+        let (status, _) = put_notes(&app, &missing, json!("hi")).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    async fn post_bulk(
+        app: &axum::Router,
+        body: serde_json::Value,
+    ) -> (StatusCode, serde_json::Value) {
+        let req = Request::builder()
+            .method(Method::POST)
+            .uri("/api/devices/bulk")
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let resp = app.clone().oneshot(req).await.unwrap();
+        let status = resp.status();
+        let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap_or_default())
+    }
+
+    #[tokio::test]
+    async fn test_bulk_reset_by_tag_reports_each_device() {
+        let (app, conn, uuid) = setup_app(test_connection_factory!()).await;
+        let other = Uuid::parse_str("d4000000-0000-0000-0000-000000000002").unwrap();
+        conn.execute(
+            "INSERT INTO devices (uuid, lifecycle, architecture) VALUES (?1, 'new', 'x86-64')",
+            (other,),
+        )
+        .await
+        .unwrap();
+        put_tag(&app, &uuid, "rack", "r1").await;
+        put_tag(&app, &other, "rack", "r1").await;
+
+        let (status, results) =
+            post_bulk(&app, json!({ "tag": "rack:r1", "action": "reset" })).await;
+        assert_eq!(status, StatusCode::OK);
+        let results = results.as_array().unwrap();
+        assert_eq!(results.len(), 2);
+        // Neither test device has a BMC, so each fails on its own without aborting the batch
+        for result in results {
+            assert_eq!(result["ok"], false);
+            assert!(
+                result["error"]
+                    .as_str()
+                    .unwrap()
+                    .starts_with("No BMC configured")
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_bulk_rediscover_partial_failure() {
+        let (app, conn, uuid) = setup_app(test_connection_factory!()).await;
+        let missing = Uuid::parse_str("d4000000-0000-0000-0000-00000000ffff").unwrap();
+
+        let (status, results) = post_bulk(
+            &app,
+            json!({ "uuids": [uuid, missing], "action": "rediscover" }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            results,
+            json!([
+                { "uuid": uuid, "ok": true, "error": null },
+                { "uuid": missing, "ok": false, "error": format!("Device {} not found", missing) },
+            ])
+        );
+
+        let pending: bool = conn
+            .query_one(
+                "SELECT rediscover_pending FROM devices WHERE uuid = ?1",
+                (uuid,),
+                |r| r.get(0),
+            )
+            .await
+            .unwrap();
+        assert!(pending);
+    }
+
+    #[tokio::test]
+    async fn test_bulk_rejects_bad_selector_and_missing_state() {
+        let (app, _conn, uuid) = setup_app(test_connection_factory!()).await;
+
+        let (status, _) = post_bulk(&app, json!({ "action": "rediscover" })).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (status, _) = post_bulk(
+            &app,
+            json!({ "uuids": [uuid], "tag": "rack:r1", "action": "rediscover" }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (status, _) = post_bulk(&app, json!({ "uuids": [uuid], "action": "set_state" })).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
 }
@@ -0,0 +1,439 @@
+//! `/api/dhcp` HTTP handlers exposing the state of the DHCP server.
+//!
+//! Lists the packets the server received recently and what it decided for each, so
+//! operators can see why a device got no answer without enabling debug logging, and
+//! reports how full each network's pools are before allocation starts failing. Also
+//! sets the quarantine network that unprovisioned devices are served from and the
+//! server identifier each network's clients are given.
+
+use std::net::Ipv4Addr;
+use std::sync::Arc;
+
+use axum::{
+    Json, Router,
+    extract::{Path, State},
+    routing::{get, put},
+};
+use serde::Deserialize;
+
+use crate::{
+    dhcp::{self, PoolUtilization, recent::PacketEvent, store::DhcpNetwork},
+    http::{
+        AppState,
+        audit::{self, Actor},
+        error::Error as HttpError,
+    },
+};
+
+/// Body for `PUT /api/dhcp/networks/{id}/quarantine`.
+#[derive(Deserialize)]
+pub struct PutQuarantineRequest {
+    /// Network to serve unknown and unprovisioned devices from; `null` to stop.
+    pub network_id: Option<i64>,
+}
+
+/// Body for `PUT /api/dhcp/networks/{id}/server-identifier`.
+#[derive(Deserialize)]
+pub struct PutServerIdentifierRequest {
+    /// The director's address on the network's subnet; `null` for the default.
+    pub server_identifier: Option<Ipv4Addr>,
+}
+
+// ---------------------------------------------------------------------------
+// Route registration
+// ---------------------------------------------------------------------------
+
+pub fn routes(state: Arc<AppState>) -> Router {
+    Router::new()
+        .route("/api/dhcp/recent", get(get_recent))
+        .route(
+            "/api/dhcp/networks/{id}/utilization",
+            get(get_network_utilization),
+        )
+        .route(
+            "/api/dhcp/networks/{id}/quarantine",
+            put(put_quarantine_network),
+        )
+        .route(
+            "/api/dhcp/networks/{id}/server-identifier",
+            put(put_server_identifier),
+        )
+        .with_state(state)
+}
+
+// ---------------------------------------------------------------------------
+// Handlers
+// ---------------------------------------------------------------------------
+
+/// `GET /api/dhcp/recent`
+///
+/// The last packets the DHCP server received (up to 500), newest first, each with
+/// its MAC, message type and decision: `offered`/`acked` with the address, `nak`,
+/// `released`, `declined`, `ignored` with a reason, or `error` with the error text.
+async fn get_recent(State(state): State<Arc<AppState>>) -> Json<Vec<PacketEvent>> {
+    Json(state.dhcp.recent().snapshot())
+}
+
+/// `GET /api/dhcp/networks/{id}/utilization`
+///
+/// Address counts across the network's pools: `total` allocatable addresses
+/// (excluding the network, broadcast and gateway addresses), `allocated` to active
+/// leases, `reserved` for static reservations, and `free`. Returns `404` if the
+/// network does not exist.
+async fn get_network_utilization(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+) -> Result<Json<PoolUtilization>, HttpError> {
+    let conn = state.connection_factory.open().await?;
+    dhcp::store::get_network(&conn, id)
+        .await
+        .map_err(|_| HttpError::NotFound(format!("Network {} not found", id)))?;
+    Ok(Json(dhcp::network_utilization(&conn, id).await?))
+}
+
+/// `PUT /api/dhcp/networks/{id}/quarantine`
+///
+/// Serve devices that DHCP on this network from `network_id` until they are
+/// provisioned, or stop doing so when `network_id` is `null`. Returns the updated
+/// network, `400` if `network_id` is this network or does not exist, and `404` if
+/// this network does not exist.
+async fn put_quarantine_network(
+    State(state): State<Arc<AppState>>,
+    actor: Actor,
+    Path(id): Path<i64>,
+    Json(req): Json<PutQuarantineRequest>,
+) -> Result<Json<DhcpNetwork>, HttpError> {
+    let conn = state.connection_factory.open().await?;
+    let before = dhcp::store::get_network(&conn, id)
+        .await
+        .map_err(|_| HttpError::NotFound(format!("Network {} not found", id)))?;
+    if let Some(quarantine_id) = req.network_id {
+        if quarantine_id == id {
+            return Err(HttpError::BadRequest(
+                "A network cannot quarantine to itself".to_string(),
+            ));
+        }
+        dhcp::store::get_network(&conn, quarantine_id)
+            .await
+            .map_err(|_| {
+                HttpError::BadRequest(format!("Quarantine network {} not found", quarantine_id))
+            })?;
+    }
+
+    let network = dhcp::store::set_quarantine_network(&conn, id, req.network_id).await?;
+    audit::record(
+        &conn,
+        &actor,
+        "network.quarantine",
+        &format!("network/{}", id),
+        audit::summary(&before),
+        audit::summary(&network),
+    )
+    .await;
+    Ok(Json(network))
+}
+
+/// `PUT /api/dhcp/networks/{id}/server-identifier`
+///
+/// Send `server_identifier` as option 54 and siaddr to this network's clients, or
+/// go back to the default when it is `null`: the server-wide identifier for relayed
+/// networks, the receiving interface's address for L2 ones. Returns the updated
+/// network, `400` if the address is not unicast, and `404` if the network does not
+/// exist.
+async fn put_server_identifier(
+    State(state): State<Arc<AppState>>,
+    actor: Actor,
+    Path(id): Path<i64>,
+    Json(req): Json<PutServerIdentifierRequest>,
+) -> Result<Json<DhcpNetwork>, HttpError> {
+    if let Some(ip) = req.server_identifier {
+        dhcp::store::validate_server_identifier(ip)
+            .map_err(|e| HttpError::BadRequest(e.to_string()))?;
+    }
+    let conn = state.connection_factory.open().await?;
+    let before = dhcp::store::get_network(&conn, id)
+        .await
+        .map_err(|_| HttpError::NotFound(format!("Network {} not found", id)))?;
+    let network = dhcp::store::set_server_identifier(&conn, id, req.server_identifier).await?;
+    audit::record(
+        &conn,
+        &actor,
+        "network.server_identifier",
+        &format!("network/{}", id),
+        audit::summary(&before),
+        audit::summary(&network),
+    )
+    .await;
+    Ok(Json(network))
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        body::Body,
+        http::{Request, StatusCode},
+    };
+    use tower::ServiceExt;
+
+    use crate::{
+        dhcp::recent::Decision, http::test_helpers::build_test_app, test_connection_factory,
+    };
+
+    #[tokio::test]
+    async fn test_get_recent_lists_newest_first() {
+        let app = build_test_app(test_connection_factory!()).await;
+        for (mac, decision) in [
+            (
+                "aa:bb:cc:dd:ee:01",
+                Decision::Offered {
+                    ip: "10.0.0.100".parse().unwrap(),
+                },
+            ),
+            (
+                "aa:bb:cc:dd:ee:02",
+                Decision::Ignored {
+                    reason: "interface is disabled".to_string(),
+                },
+            ),
+        ] {
+            app.state.dhcp.recent().record(PacketEvent {
+                at: chrono::Utc::now(),
+                client: None,
+                relay: None,
+                mac: Some(mac.to_string()),
+                message_type: Some("Discover".to_string()),
+                decision,
+            });
+        }
+
+        let req = Request::builder()
+            .uri("/api/dhcp/recent")
+            .body(Body::empty())
+            .unwrap();
+        let resp = app.router.oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+
+        assert_eq!(json[0]["mac"], "aa:bb:cc:dd:ee:02");
+        assert_eq!(json[0]["decision"], "ignored");
+        assert_eq!(json[0]["reason"], "interface is disabled");
+        assert_eq!(json[1]["decision"], "offered");
+        assert_eq!(json[1]["ip"], "10.0.0.100");
+    }
+
+    #[tokio::test]
+    async fn test_get_network_utilization() {
+        let app = build_test_app(test_connection_factory!()).await;
+        let network = dhcp::store::create_network(
+            &app.conn,
+            "Small Network",
+            "10.0.1.0/29",
+            "10.0.1.1",
+            &[],
+            86400,
+            None,
+            false,
+        )
+        .await
+        .unwrap();
+        dhcp::store::create_pool(&app.conn, network.id, "Whole", "10.0.1.0", "10.0.1.7")
+            .await
+            .unwrap();
+        dhcp::store::create_static_reservation(
+            &app.conn,
+            network.id,
+            "aa:bb:cc:dd:ee:ff",
+            "10.0.1.6",
+            None,
+        )
+        .await
+        .unwrap();
+
+        let req = Request::builder()
+            .uri(format!("/api/dhcp/networks/{}/utilization", network.id))
+            .body(Body::empty())
+            .unwrap();
+        let resp = app.router.clone().oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({"total": 5, "allocated": 0, "reserved": 1, "free": 4})
+        );
+
+        let req = Request::builder()
+            .uri("/api/dhcp/networks/9999/utilization")
+            .body(Body::empty())
+            .unwrap();
+        let resp = app.router.oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_put_quarantine_network() {
+        let app = build_test_app(test_connection_factory!()).await;
+        let mut ids = Vec::new();
+        for (name, subnet, gateway) in [
+            ("Production", "10.0.1.0/24", "10.0.1.1"),
+            ("Quarantine", "10.0.2.0/24", "10.0.2.1"),
+        ] {
+            let network = dhcp::store::create_network(
+                &app.conn,
+                name,
+                subnet,
+                gateway,
+                &[],
+                86400,
+                Some(gateway),
+                false,
+            )
+            .await
+            .unwrap();
+            ids.push(network.id);
+        }
+        let put = |id: i64, body: serde_json::Value| {
+            let router = app.router.clone();
+            async move {
+                let req = Request::builder()
+                    .method("PUT")
+                    .uri(format!("/api/dhcp/networks/{}/quarantine", id))
+                    .header("content-type", "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap();
+                router.oneshot(req).await.unwrap().status()
+            }
+        };
+
+        assert_eq!(
+            put(ids[0], serde_json::json!({"network_id": ids[1]})).await,
+            StatusCode::OK
+        );
+        let network = dhcp::store::get_network(&app.conn, ids[0]).await.unwrap();
+        assert_eq!(network.quarantine_network_id, Some(ids[1]));
+
+        assert_eq!(
+            put(ids[0], serde_json::json!({"network_id": ids[0]})).await,
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            put(ids[0], serde_json::json!({"network_id": 9999})).await,
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            put(9999, serde_json::json!({"network_id": ids[1]})).await,
+            StatusCode::NOT_FOUND
+        );
+
+        assert_eq!(
+            put(ids[0], serde_json::json!({"network_id": null})).await,
+            StatusCode::OK
+        );
+        let network = dhcp::store::get_network(&app.conn, ids[0]).await.unwrap();
+        assert_eq!(network.quarantine_network_id, None);
+
+        // Only the two successful changes are audited
+        assert_eq!(
+            audit_actions(&app.conn).await,
+            ["network.quarantine", "network.quarantine"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_put_server_identifier() {
+        let app = build_test_app(test_connection_factory!()).await;
+        let network = dhcp::store::create_network(
+            &app.conn,
+            "Rack 1",
+            "10.1.0.0/24",
+            "10.1.0.1",
+            &[],
+            86400,
+            Some("10.1.0.1"),
+            false,
+        )
+        .await
+        .unwrap();
+        let put = |id: i64, body: serde_json::Value| {
+            let router = app.router.clone();
+            async move {
+                let req = Request::builder()
+                    .method("PUT")
+                    .uri(format!("/api/dhcp/networks/{}/server-identifier", id))
+                    .header("content-type", "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap();
+                router.oneshot(req).await.unwrap().status()
+            }
+        };
+
+        assert_eq!(
+            put(
+                network.id,
+                serde_json::json!({"server_identifier": "10.1.0.2"})
+            )
+            .await,
+            StatusCode::OK
+        );
+        let updated = dhcp::store::get_network(&app.conn, network.id)
+            .await
+            .unwrap();
+        assert_eq!(updated.server_identifier, Some(Ipv4Addr::new(10, 1, 0, 2)));
+
+        assert_eq!(
+            put(
+                network.id,
+                serde_json::json!({"server_identifier": "0.0.0.0"})
+            )
+            .await,
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            put(9999, serde_json::json!({"server_identifier": "10.1.0.2"})).await,
+            StatusCode::NOT_FOUND
+        );
+
+        assert_eq!(
+            put(network.id, serde_json::json!({"server_identifier": null})).await,
+            StatusCode::OK
+        );
+        let updated = dhcp::store::get_network(&app.conn, network.id)
+            .await
+            .unwrap();
+        assert_eq!(updated.server_identifier, None);
+
+        assert_eq!(
+            audit_actions(&app.conn).await,
+            ["network.server_identifier", "network.server_identifier"]
+        );
+        let after: String = app
+            .conn
+            .query_one(
+                "SELECT after_summary FROM audit_log ORDER BY id LIMIT 1",
+                (),
+                |row| row.get(0),
+            )
+            .await
+            .unwrap();
+        let after: serde_json::Value = serde_json::from_str(&after).unwrap();
+        assert_eq!(after["server_identifier"], "10.1.0.2");
+    }
+
+    async fn audit_actions(conn: &crate::database::Connection) -> Vec<String> {
+        conn.query("SELECT action FROM audit_log ORDER BY id", (), |row| {
+            row.get(0)
+        })
+        .await
+        .unwrap()
+    }
+}
@@ -0,0 +1,259 @@
+//! `/api/image-sets` HTTP handlers for staged agent image rollouts.
+//!
+//! Operators register named kernel/ramdisk sets, mark one as the fleet default and
+//! pin individual devices to another, e.g. to trial a new agent image on one node.
+
+use std::sync::Arc;
+
+use axum::{
+    Json, Router,
+    extract::{Path, State},
+    http::StatusCode,
+    routing::{post, put},
+};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    http::{
+        AppState,
+        audit::{self, Actor},
+        error::Error as HttpError,
+    },
+    image_sets::{self, ImageSet},
+};
+
+// ---------------------------------------------------------------------------
+// Request / response types
+// ---------------------------------------------------------------------------
+
+/// Body for `POST /api/image-sets`.
+#[derive(Deserialize)]
+pub struct CreateImageSetRequest {
+    pub name: String,
+    /// Kernel path relative to `/cnc/`.
+    pub kernel: String,
+    /// Ramdisk path relative to `/cnc/`.
+    pub ramdisk: String,
+    #[serde(default)]
+    pub cmdline: String,
+    /// Make this the fleet-wide default, replacing any previous default.
+    #[serde(default)]
+    pub default: bool,
+}
+
+/// Body for `PUT /api/devices/{uuid}/image-set`.
+#[derive(Deserialize)]
+pub struct PutDeviceImageSetRequest {
+    /// Image set name to pin, or `null` to follow the fleet default.
+    pub image_set: Option<String>,
+}
+
+// ---------------------------------------------------------------------------
+// Route registration
+// ---------------------------------------------------------------------------
+
+pub fn routes(state: Arc<AppState>) -> Router {
+    Router::new()
+        .route("/api/image-sets", post(create_image_set))
+        .route("/api/devices/{uuid}/image-set", put(put_device_image_set))
+        .with_state(state)
+}
+
+// ---------------------------------------------------------------------------
+// Handlers
+// ---------------------------------------------------------------------------
+
+/// `POST /api/image-sets`
+///
+/// Returns `201 Created` with the image set, `400` if a field is empty or would
+/// break the generated iPXE script, and `409` if the name is taken.
+async fn create_image_set(
+    State(state): State<Arc<AppState>>,
+    actor: Actor,
+    Json(req): Json<CreateImageSetRequest>,
+) -> Result<(StatusCode, Json<ImageSet>), HttpError> {
+    if req.name.is_empty() || req.kernel.is_empty() || req.ramdisk.is_empty() {
+        return Err(HttpError::BadRequest(
+            "name, kernel and ramdisk are required".to_string(),
+        ));
+    }
+    image_sets::validate_boot_fields(&req.kernel, &req.ramdisk, &req.cmdline)
+        .map_err(|e| HttpError::BadRequest(e.to_string()))?;
+
+    let mut conn = state.connection_factory.open().await?;
+    if image_sets::get_image_set_by_name(&conn, &req.name)
+        .await?
+        .is_some()
+    {
+        return Err(HttpError::Conflict(format!(
+            "Image set {} already exists",
+            req.name
+        )));
+    }
+
+    let mut image_set =
+        image_sets::create_image_set(&conn, &req.name, &req.kernel, &req.ramdisk, &req.cmdline)
+            .await?;
+    if req.default {
+        image_sets::set_default_image_set(&mut conn, Some(image_set.id)).await?;
+        image_set.is_default = true;
+    }
+    audit::record(
+        &conn,
+        &actor,
+        "image_set.create",
+        &format!("image_set/{}", image_set.name),
+        None,
+        audit::summary(&image_set),
+    )
+    .await;
+    Ok((StatusCode::CREATED, Json(image_set)))
+}
+
+/// `PUT /api/devices/{uuid}/image-set`
+///
+/// Pin the device to the named image set, or unpin it with `null`. Takes effect
+/// on the device's next agent boot.
+///
+/// Returns `204 No Content`, `400` for an unknown image set and `404` for an
+/// unknown device.
+async fn put_device_image_set(
+    State(state): State<Arc<AppState>>,
+    actor: Actor,
+    Path(uuid): Path<Uuid>,
+    Json(req): Json<PutDeviceImageSetRequest>,
+) -> Result<StatusCode, HttpError> {
+    let conn = state.connection_factory.open().await?;
+
+    let image_set_id = match &req.image_set {
+        Some(name) => Some(
+            image_sets::get_image_set_by_name(&conn, name)
+                .await?
+                .ok_or_else(|| HttpError::BadRequest(format!("Image set {} not found", name)))?
+                .id,
+        ),
+        None => None,
+    };
+
+    if !image_sets::set_device_image_set(&conn, &uuid, image_set_id).await? {
+        return Err(HttpError::NotFound(format!("Device {} not found", uuid)));
+    }
+    audit::record(
+        &conn,
+        &actor,
+        "device.image_set",
+        &format!("device/{}", uuid),
+        None,
+        Some(serde_json::json!({ "image_set": req.image_set })),
+    )
+    .await;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        body::Body,
+        http::{Method, Request},
+    };
+    use serde_json::json;
+    use tower::ServiceExt;
+
+    use crate::{database, test_connection_factory};
+
+    async fn send(app: Router, method: Method, uri: &str, body: serde_json::Value) -> StatusCode {
+        let req = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        app.oneshot(req).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn test_create_and_pin_image_set() {
+        let factory = test_connection_factory!();
+        let conn = database::run_migrations(&factory).await.unwrap();
+        let uuid = Uuid::parse_str("e2000000-0000-0000-0000-000000000001").unwrap();
+        conn.execute(
+            "INSERT INTO devices (uuid, lifecycle, architecture) VALUES (?1, 'new', 'x86-64')",
+            (uuid,),
+        )
+        .await
+        .unwrap();
+        let conn_factory: Arc<dyn database::ConnectionFactory> = Arc::new(factory);
+        let app = routes(crate::http::test_helpers::build_test_state(conn_factory));
+
+        let v2 = json!({
+            "name": "v2",
+            "kernel": "agent-images/vmlinuz-v2",
+            "ramdisk": "agent-images/initramfs-v2.img",
+        });
+        assert_eq!(
+            send(app.clone(), Method::POST, "/api/image-sets", v2.clone()).await,
+            StatusCode::CREATED
+        );
+        assert_eq!(
+            send(app.clone(), Method::POST, "/api/image-sets", v2).await,
+            StatusCode::CONFLICT
+        );
+
+        let uri = format!("/api/devices/{}/image-set", uuid);
+        assert_eq!(
+            send(app.clone(), Method::PUT, &uri, json!({ "image_set": "v2" })).await,
+            StatusCode::NO_CONTENT
+        );
+        let resolved = image_sets::resolve_image_set(&conn, &uuid)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(resolved.name, "v2");
+
+        assert_eq!(
+            send(app, Method::PUT, &uri, json!({ "image_set": "missing" })).await,
+            StatusCode::BAD_REQUEST
+        );
+    }
+
+    #[tokio::test]
+    async fn test_create_image_set_rejects_script_injection() {
+        let factory = test_connection_factory!();
+        database::run_migrations(&factory).await.unwrap();
+        let conn_factory: Arc<dyn database::ConnectionFactory> = Arc::new(factory);
+        let app = routes(crate::http::test_helpers::build_test_state(conn_factory));
+
+        for (kernel, ramdisk, cmdline) in [
+            ("agent-images/vmlinuz\nshell", "agent-images/initrd", ""),
+            (
+                "agent-images/vmlinuz init=/bin/sh",
+                "agent-images/initrd",
+                "",
+            ),
+            ("agent-images/vmlinuz", "agent-images/initrd\r\nshell", ""),
+            (
+                "agent-images/vmlinuz",
+                "agent-images/initrd",
+                "quiet\nshell",
+            ),
+        ] {
+            let body = json!({
+                "name": "bad",
+                "kernel": kernel,
+                "ramdisk": ramdisk,
+                "cmdline": cmdline,
+            });
+            assert_eq!(
+                send(app.clone(), Method::POST, "/api/image-sets", body).await,
+                StatusCode::BAD_REQUEST,
+                "{kernel:?} {ramdisk:?} {cmdline:?}"
+            );
+        }
+    }
+}
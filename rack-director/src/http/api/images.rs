@@ -0,0 +1,118 @@
+//! `/api/images` HTTP handlers describing the agent images served under
+//! `/cnc/agent-images`.
+
+use std::sync::Arc;
+
+use axum::{
+    Json, Router,
+    extract::{Path, State},
+    routing::get,
+};
+use serde::Serialize;
+
+use crate::http::{AppState, cnc::resolve_agent_image, error::Error as HttpError};
+
+// ---------------------------------------------------------------------------
+// Response types
+// ---------------------------------------------------------------------------
+
+/// Response for `GET /api/images/{name}`.
+#[derive(Serialize)]
+pub struct ImageMetadata {
+    pub name: String,
+    pub size: u64,
+    /// Lowercase hex SHA-256 of the image, as served in `{name}.sha256`.
+    pub sha256: String,
+    /// A detached signature is published as `{name}.sig`.
+    pub signed: bool,
+}
+
+// ---------------------------------------------------------------------------
+// Route registration
+// ---------------------------------------------------------------------------
+
+pub fn routes(state: Arc<AppState>) -> Router {
+    Router::new()
+        .route("/api/images/{name}", get(get_image))
+        .with_state(state)
+}
+
+// ---------------------------------------------------------------------------
+// Handlers
+// ---------------------------------------------------------------------------
+
+/// `GET /api/images/{name}`
+///
+/// The hash is computed on first request and cached until the file changes.
+/// Returns `404` if there is no such image.
+async fn get_image(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+) -> Result<Json<ImageMetadata>, HttpError> {
+    let file = resolve_agent_image(&state.agent_images_path, &name).await?;
+    let digest = state.artifact_hashes.digest(&file).await?;
+
+    let mut signature = file.into_os_string();
+    signature.push(".sig");
+    let signed = tokio::fs::try_exists(&signature).await.unwrap_or(false);
+
+    Ok(Json(ImageMetadata {
+        name,
+        size: digest.size,
+        sha256: digest.sha256,
+        signed,
+    }))
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        body::Body,
+        http::{Request, StatusCode},
+    };
+    use tower::ServiceExt;
+
+    use crate::{database, test_connection_factory};
+
+    async fn fetch(app: Router, uri: &str) -> (StatusCode, serde_json::Value) {
+        let req = Request::builder().uri(uri).body(Body::empty()).unwrap();
+        let resp = app.oneshot(req).await.unwrap();
+        let status = resp.status();
+        let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap_or_default())
+    }
+
+    #[tokio::test]
+    async fn test_image_metadata_hash_matches_content() {
+        let factory = test_connection_factory!();
+        database::run_migrations(&factory).await.unwrap();
+        let conn_factory: Arc<dyn database::ConnectionFactory> = Arc::new(factory);
+        let state = crate::http::test_helpers::build_test_state(conn_factory);
+        std::fs::write(state.agent_images_path.join("vmlinuz"), b"abc").unwrap();
+        let app = routes(state.clone());
+
+        let (status, json) = fetch(app.clone(), "/api/images/vmlinuz").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(json["size"], 3);
+        // sha256("abc")
+        assert_eq!(
+            json["sha256"],
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(json["signed"], false);
+
+        std::fs::write(state.agent_images_path.join("vmlinuz.sig"), b"sig").unwrap();
+        let (_, json) = fetch(app.clone(), "/api/images/vmlinuz").await;
+        assert_eq!(json["signed"], true);
+
+        let (status, _) = fetch(app, "/api/images/missing").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+}
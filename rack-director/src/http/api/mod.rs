@@ -1,5 +1,14 @@
+mod admin;
+mod arp;
 mod devices;
+mod dhcp;
+mod image_sets;
+mod images;
 mod platforms;
+mod reservations;
+mod search;
+mod tftp;
+mod uuid_blocklist;
 
 use axum::Router;
 use std::sync::Arc;
@@ -8,6 +17,15 @@ use crate::http::AppState;
 
 pub fn routes(state: Arc<AppState>) -> Router {
     Router::new()
+        .merge(admin::routes(state.clone()))
+        .merge(arp::routes(state.clone()))
         .merge(devices::routes(state.clone()))
-        .merge(platforms::routes(state))
+        .merge(dhcp::routes(state.clone()))
+        .merge(image_sets::routes(state.clone()))
+        .merge(images::routes(state.clone()))
+        .merge(platforms::routes(state.clone()))
+        .merge(reservations::routes(state.clone()))
+        .merge(search::routes(state.clone()))
+        .merge(tftp::routes(state.clone()))
+        .merge(uuid_blocklist::routes(state))
 }
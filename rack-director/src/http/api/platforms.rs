@@ -15,7 +15,11 @@ use axum::{
 use serde::Deserialize;
 
 use crate::{
-    http::{AppState, error::Error as HttpError},
+    http::{
+        AppState,
+        audit::{self, Actor},
+        error::Error as HttpError,
+    },
     platforms::{Platform, store::UpdateDiskLabelError},
 };
 
@@ -62,6 +66,7 @@ pub fn routes(state: Arc<AppState>) -> Router {
 /// Returns 422 if the label already exists on a different disk in the same platform.
 async fn update_disk_label(
     State(state): State<Arc<AppState>>,
+    actor: Actor,
     Path((id, index)): Path<(i64, usize)>,
     Json(req): Json<UpdateDiskLabelRequest>,
 ) -> Result<(StatusCode, Json<Platform>), HttpError> {
@@ -72,6 +77,16 @@ async fn update_disk_label(
             .await
             .map_err(|e| map_update_disk_label_error(e, id, index))?;
 
+    audit::record(
+        &conn,
+        &actor,
+        "platform.disk_label",
+        &format!("platform/{id}"),
+        None,
+        Some(serde_json::json!({ "index": index, "label": req.label })),
+    )
+    .await;
+
     Ok((StatusCode::OK, Json(platform)))
 }
 
@@ -0,0 +1,340 @@
+//! `/api/reservations` HTTP handlers for pre-staging hardware.
+//!
+//! Lets operators register a MAC address with a reserved IP before the machine
+//! ever PXE boots, so its very first DHCP request is answered with the right
+//! address.
+
+use std::{net::Ipv4Addr, sync::Arc};
+
+use axum::{Json, Router, extract::State, http::StatusCode, routing::post};
+use common::Ipv4Subnet;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    database::Connection,
+    dhcp::{self, StaticReservation},
+    director::Director,
+    http::{
+        AppState,
+        audit::{self, Actor},
+        error::Error as HttpError,
+        ui::validation::{ValidationErrors, validate_mac_address, validate_required},
+    },
+};
+
+// ---------------------------------------------------------------------------
+// Request / response types
+// ---------------------------------------------------------------------------
+
+/// Body for `POST /api/reservations`.
+#[derive(Deserialize)]
+pub struct CreateReservationRequest {
+    pub mac_address: String,
+    /// DHCP network the reservation belongs to.
+    pub network_id: i64,
+    pub ip_address: String,
+    pub hostname: Option<String>,
+    /// Existing device the MAC belongs to, if already known.
+    pub device_uuid: Option<Uuid>,
+}
+
+// ---------------------------------------------------------------------------
+// Route registration
+// ---------------------------------------------------------------------------
+
+pub fn routes(state: Arc<AppState>) -> Router {
+    Router::new()
+        .route("/api/reservations", post(create_reservation))
+        .with_state(state)
+}
+
+// ---------------------------------------------------------------------------
+// Handlers
+// ---------------------------------------------------------------------------
+
+/// `POST /api/reservations`
+///
+/// Register the MAC as a pending device (linked to `device_uuid` when given)
+/// and reserve `ip_address` for it, atomically.
+///
+/// Returns `201 Created` with the reservation, `400` if the request is invalid
+/// or the IP is outside the network, and `409` if the network already has a
+/// reservation for the MAC or the IP.
+async fn create_reservation(
+    State(state): State<Arc<AppState>>,
+    actor: Actor,
+    Json(mut req): Json<CreateReservationRequest>,
+) -> Result<(StatusCode, Json<StaticReservation>), HttpError> {
+    // Normalize MAC address to lowercase for consistent storage and duplicate detection
+    req.mac_address = req.mac_address.to_lowercase();
+
+    let mut conn = state.connection_factory.open().await?;
+    validate_create_reservation(&conn, &req).await?;
+
+    let reservation = create_reservation_with_interface(&mut conn, &req).await?;
+    audit::record(
+        &conn,
+        &actor,
+        "reservation.create",
+        &format!("network/{}", req.network_id),
+        None,
+        audit::summary(&reservation),
+    )
+    .await;
+    Ok((StatusCode::CREATED, Json(reservation)))
+}
+
+/// Validate a create-reservation request.
+///
+/// Checks MAC and IP format, that the network exists and contains the IP, and
+/// that `device_uuid` (if given) refers to a known device.
+async fn validate_create_reservation(
+    conn: &Connection,
+    req: &CreateReservationRequest,
+) -> Result<(), HttpError> {
+    let mut errors = ValidationErrors::new();
+
+    errors.add_if_err(
+        "mac_address",
+        validate_required(&req.mac_address, "MAC address"),
+    );
+    if !req.mac_address.is_empty() {
+        errors.add_if_err("mac_address", validate_mac_address(&req.mac_address));
+    }
+
+    let ip = req.ip_address.parse::<Ipv4Addr>().ok();
+    if ip.is_none() {
+        errors.add_error("ip_address", "Invalid IPv4 address".to_string());
+    }
+
+    match dhcp::store::get_network(conn, req.network_id).await {
+        Ok(network) => {
+            let subnet: Ipv4Subnet = network.subnet.parse()?;
+            if let Some(ip) = ip
+                && !subnet.ip_in_range(ip)
+            {
+                errors.add_error(
+                    "ip_address",
+                    format!("IP address is not within {}", network.subnet),
+                );
+            }
+        }
+        Err(_) => errors.add_error("network_id", "Network not found".to_string()),
+    }
+
+    if let Some(uuid) = req.device_uuid
+        && Director::new(conn).get_device(&uuid).await.is_err()
+    {
+        errors.add_error("device_uuid", format!("Device {} not found", uuid));
+    }
+
+    errors.into_result().map_err(HttpError::ValidationError)
+}
+
+/// Create the pending-device entry and the static reservation in one transaction.
+///
+/// The conflict check runs inside the transaction so two concurrent requests for
+/// the same IP cannot both succeed.
+async fn create_reservation_with_interface(
+    conn: &mut Connection,
+    req: &CreateReservationRequest,
+) -> Result<StaticReservation, HttpError> {
+    let tx = conn.transaction().await.map_err(anyhow::Error::from)?;
+
+    if let Some(existing) = dhcp::store::find_conflicting_static_reservation(
+        &tx,
+        req.network_id,
+        &req.mac_address,
+        &req.ip_address,
+    )
+    .await?
+    {
+        return Err(HttpError::Conflict(format!(
+            "Network {} already reserves {} for {}",
+            req.network_id, existing.ip_address, existing.mac_address
+        )));
+    }
+
+    let director = Director::new(&tx);
+    director
+        .create_pending_device(&req.mac_address, req.network_id)
+        .await?;
+    if let Some(uuid) = req.device_uuid {
+        director
+            .complete_pending_device(&req.mac_address, &uuid)
+            .await?;
+    }
+
+    let reservation = dhcp::store::create_static_reservation(
+        &tx,
+        req.network_id,
+        &req.mac_address,
+        &req.ip_address,
+        req.hostname.as_deref(),
+    )
+    .await?;
+
+    tx.commit().await.map_err(anyhow::Error::from)?;
+    Ok(reservation)
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        body::Body,
+        http::{Method, Request},
+    };
+    use serde_json::json;
+    use tower::ServiceExt;
+
+    use crate::{database, test_connection_factory};
+
+    async fn setup_app(
+        factory: database::DatabaseConnectionFactory,
+    ) -> (Router, database::Connection, i64) {
+        let conn = database::run_migrations(&factory).await.unwrap();
+        let network = dhcp::store::create_network(
+            &conn,
+            "Staging",
+            "10.0.0.0/24",
+            "10.0.0.1",
+            &[],
+            86400,
+            None,
+            false,
+        )
+        .await
+        .unwrap();
+
+        let conn_factory: Arc<dyn database::ConnectionFactory> = Arc::new(factory);
+        let state = crate::http::test_helpers::build_test_state(conn_factory);
+        (routes(state), conn, network.id)
+    }
+
+    async fn post_reservation(app: Router, body: serde_json::Value) -> StatusCode {
+        let req = Request::builder()
+            .method(Method::POST)
+            .uri("/api/reservations")
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        app.oneshot(req).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn test_create_reservation_registers_mac_and_reserves_ip() {
+        let (app, conn, network_id) = setup_app(test_connection_factory!()).await;
+
+        let status = post_reservation(
+            app,
+            json!({
+                "mac_address": "AA:BB:CC:DD:EE:01",
+                "network_id": network_id,
+                "ip_address": "10.0.0.50",
+                "hostname": "rack1-node1",
+            }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+
+        let reservation =
+            dhcp::store::get_static_reservation(&conn, network_id, "aa:bb:cc:dd:ee:01")
+                .await
+                .unwrap()
+                .expect("reservation should exist");
+        assert_eq!(reservation.ip_address, "10.0.0.50");
+        assert_eq!(reservation.hostname.as_deref(), Some("rack1-node1"));
+
+        let pending = Director::new(&conn)
+            .find_pending_device_by_mac("aa:bb:cc:dd:ee:01")
+            .await
+            .unwrap();
+        assert!(pending.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_create_reservation_conflict_returns_409() {
+        let (app, conn, network_id) = setup_app(test_connection_factory!()).await;
+
+        let first = json!({
+            "mac_address": "aa:bb:cc:dd:ee:01",
+            "network_id": network_id,
+            "ip_address": "10.0.0.50",
+        });
+        assert_eq!(
+            post_reservation(app.clone(), first).await,
+            StatusCode::CREATED
+        );
+
+        // Same IP, different MAC
+        let second = json!({
+            "mac_address": "aa:bb:cc:dd:ee:02",
+            "network_id": network_id,
+            "ip_address": "10.0.0.50",
+        });
+        assert_eq!(post_reservation(app, second).await, StatusCode::CONFLICT);
+
+        // The rolled-back request must not leave a pending device behind
+        let pending = Director::new(&conn)
+            .find_pending_device_by_mac("aa:bb:cc:dd:ee:02")
+            .await
+            .unwrap();
+        assert!(pending.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_create_reservation_rejects_ip_outside_network() {
+        let (app, _conn, network_id) = setup_app(test_connection_factory!()).await;
+
+        let status = post_reservation(
+            app,
+            json!({
+                "mac_address": "aa:bb:cc:dd:ee:01",
+                "network_id": network_id,
+                "ip_address": "192.168.1.50",
+            }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_create_reservation_links_known_device() {
+        let (app, conn, network_id) = setup_app(test_connection_factory!()).await;
+        let uuid = Uuid::parse_str("d5000000-0000-0000-0000-000000000001").unwrap();
+        conn.execute(
+            "INSERT INTO devices (uuid, lifecycle, architecture) VALUES (?1, 'new', 'x86-64')",
+            (uuid,),
+        )
+        .await
+        .unwrap();
+
+        let status = post_reservation(
+            app,
+            json!({
+                "mac_address": "aa:bb:cc:dd:ee:03",
+                "network_id": network_id,
+                "ip_address": "10.0.0.60",
+                "device_uuid": uuid,
+            }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+
+        let linked: Option<Uuid> = conn
+            .query_one(
+                "SELECT device_uuid FROM pending_devices WHERE mac_address = ?1",
+                ("aa:bb:cc:dd:ee:03".to_string(),),
+                |row| row.get(0),
+            )
+            .await
+            .unwrap();
+        assert_eq!(linked, Some(uuid));
+    }
+}
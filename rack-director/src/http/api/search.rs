@@ -0,0 +1,119 @@
+//! `GET /api/search` HTTP handler: one search box for finding a device by UUID
+//! prefix, MAC, IP address, hostname or serial number.
+
+use std::sync::Arc;
+
+use axum::{
+    Json, Router,
+    extract::{Query, State},
+    routing::get,
+};
+use serde::Deserialize;
+
+use crate::{
+    device_search::{self, SearchHit},
+    http::{AppState, error::Error as HttpError},
+};
+
+/// Query string for `GET /api/search`.
+#[derive(Deserialize)]
+pub struct SearchQuery {
+    pub q: String,
+}
+
+// ---------------------------------------------------------------------------
+// Route registration
+// ---------------------------------------------------------------------------
+
+pub fn routes(state: Arc<AppState>) -> Router {
+    Router::new()
+        .route("/api/search", get(get_search))
+        .with_state(state)
+}
+
+// ---------------------------------------------------------------------------
+// Handlers
+// ---------------------------------------------------------------------------
+
+/// `GET /api/search?q=`
+///
+/// Devices matching `q`, best match first, each with the field it matched
+/// (`uuid`, `mac`, `ip`, `hostname` or `serial`) and the matched value. See
+/// [`device_search::search_devices`] for the matching rules. Returns `400` if `q`
+/// is blank.
+async fn get_search(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<SearchQuery>,
+) -> Result<Json<Vec<SearchHit>>, HttpError> {
+    if query.q.trim().is_empty() {
+        return Err(HttpError::BadRequest("q must not be blank".to_string()));
+    }
+    let conn = state.connection_factory.open().await?;
+    Ok(Json(device_search::search_devices(&conn, &query.q).await?))
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Body,
+        http::{Request, StatusCode},
+    };
+    use tower::ServiceExt;
+    use uuid::Uuid;
+
+    use crate::{
+        director::{Architecture, Director},
+        http::test_helpers::build_test_app,
+        test_connection_factory,
+    };
+
+    #[tokio::test]
+    async fn test_search_endpoint() {
+        let app = build_test_app(test_connection_factory!()).await;
+        let uuid = Uuid::parse_str("e8000000-0000-0000-0000-0000000000c3").unwrap();
+        let director = Director::new(&app.conn);
+        director
+            .register_device(&uuid, Architecture::X86_64)
+            .await
+            .unwrap();
+        director
+            .set_device_ip_address(&uuid, "10.0.0.5", "aa:bb:cc:00:00:c3")
+            .await
+            .unwrap();
+
+        let get = |uri: &str| {
+            let router = app.router.clone();
+            let req = Request::builder().uri(uri).body(Body::empty()).unwrap();
+            async move {
+                let resp = router.oneshot(req).await.unwrap();
+                let status = resp.status();
+                let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX)
+                    .await
+                    .unwrap();
+                (
+                    status,
+                    serde_json::from_slice::<serde_json::Value>(&bytes).unwrap_or_default(),
+                )
+            }
+        };
+
+        let (status, body) = get("/api/search?q=AA-BB-CC-00-00-C3").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body[0]["uuid"], uuid.to_string());
+        assert_eq!(body[0]["matched"], "mac");
+        assert_eq!(body[0]["hostname"], "node-0000000000c3");
+
+        let (_, body) = get("/api/search?q=10.0.0.5").await;
+        assert_eq!(body[0]["matched"], "ip");
+
+        let (_, body) = get("/api/search?q=nomatch").await;
+        assert_eq!(body, serde_json::json!([]));
+
+        let (status, _) = get("/api/search?q=%20").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+}
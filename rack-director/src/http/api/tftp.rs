@@ -0,0 +1,104 @@
+//! `/api/tftp` HTTP handlers exposing the state of the TFTP server.
+//!
+//! Reports the transfers currently in flight so operators can spot stuck clients
+//! and size the boot network.
+
+use std::sync::Arc;
+
+use axum::{Json, Router, extract::State, routing::get};
+use serde::Serialize;
+
+use crate::{http::AppState, tftp::TransferStatus};
+
+// ---------------------------------------------------------------------------
+// Response types
+// ---------------------------------------------------------------------------
+
+/// Response body for `GET /api/tftp/status`.
+#[derive(Serialize)]
+pub struct TftpStatusResponse {
+    /// Number of transfers currently in flight.
+    pub active: usize,
+    /// Payload bytes sent by all transfers since the server started.
+    pub bytes_sent_total: u64,
+    pub transfers: Vec<TransferStatus>,
+}
+
+// ---------------------------------------------------------------------------
+// Route registration
+// ---------------------------------------------------------------------------
+
+pub fn routes(state: Arc<AppState>) -> Router {
+    Router::new()
+        .route("/api/tftp/status", get(get_status))
+        .with_state(state)
+}
+
+// ---------------------------------------------------------------------------
+// Handlers
+// ---------------------------------------------------------------------------
+
+/// `GET /api/tftp/status`
+///
+/// List in-flight TFTP transfers (client address, filename, current block and
+/// bytes sent), ordered by start time.
+async fn get_status(State(state): State<Arc<AppState>>) -> Json<TftpStatusResponse> {
+    let transfers = state.tftp_transfers.snapshot();
+    Json(TftpStatusResponse {
+        active: transfers.len(),
+        bytes_sent_total: state.tftp_transfers.total_bytes_sent(),
+        transfers,
+    })
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        body::Body,
+        http::{Request, StatusCode},
+    };
+    use tower::ServiceExt;
+
+    use crate::{http::test_helpers::build_test_app, test_connection_factory};
+
+    async fn get_json(app: Router) -> serde_json::Value {
+        let req = Request::builder()
+            .uri("/api/tftp/status")
+            .body(Body::empty())
+            .unwrap();
+        let resp = app.oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_get_status_lists_active_transfers() {
+        let app = build_test_app(test_connection_factory!()).await;
+
+        let guard = app
+            .state
+            .tftp_transfers
+            .register("10.0.0.5:2000".parse().unwrap(), "undionly.kpxe");
+        guard.record_block(3, 1536);
+
+        let json = get_json(app.router.clone()).await;
+        assert_eq!(json["active"], 1);
+        assert_eq!(json["bytes_sent_total"], 1536);
+        assert_eq!(json["transfers"][0]["client"], "10.0.0.5:2000");
+        assert_eq!(json["transfers"][0]["filename"], "undionly.kpxe");
+        assert_eq!(json["transfers"][0]["block"], 3);
+
+        drop(guard);
+        let json = get_json(app.router).await;
+        assert_eq!(json["active"], 0);
+        assert!(json["transfers"].as_array().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,212 @@
+//! `/api/uuid-blocklist` HTTP handlers for hardware UUIDs the director ignores.
+//!
+//! Blocked UUIDs are never registered or updated by `/cnc/ipxe`; they get a local
+//! boot script instead. Unblocking lets the next boot register the device as usual.
+
+use std::sync::Arc;
+
+use axum::{
+    Json, Router,
+    extract::{Path, State},
+    http::StatusCode,
+    routing::{delete, get},
+};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    director::{RawUuid, normalize_uuid},
+    http::{
+        AppState,
+        audit::{self, Actor},
+        error::Error as HttpError,
+    },
+    uuid_blocklist::{self, BlockedUuid},
+};
+
+// ---------------------------------------------------------------------------
+// Request / response types
+// ---------------------------------------------------------------------------
+
+/// Body for `POST /api/uuid-blocklist`.
+#[derive(Deserialize)]
+pub struct BlockUuidRequest {
+    /// Hardware UUID, normalized the same way as the one iPXE reports.
+    pub uuid: String,
+    pub reason: Option<String>,
+}
+
+// ---------------------------------------------------------------------------
+// Route registration
+// ---------------------------------------------------------------------------
+
+pub fn routes(state: Arc<AppState>) -> Router {
+    Router::new()
+        .route(
+            "/api/uuid-blocklist",
+            get(list_blocked_uuids).post(block_uuid),
+        )
+        .route("/api/uuid-blocklist/{uuid}", delete(unblock_uuid))
+        .with_state(state)
+}
+
+// ---------------------------------------------------------------------------
+// Handlers
+// ---------------------------------------------------------------------------
+
+/// `GET /api/uuid-blocklist`
+async fn list_blocked_uuids(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Vec<BlockedUuid>>, HttpError> {
+    let conn = state.connection_factory.open().await?;
+    Ok(Json(uuid_blocklist::list_blocked_uuids(&conn).await?))
+}
+
+/// `POST /api/uuid-blocklist`
+///
+/// Returns `201 Created`, `400` for an invalid UUID and `409` if it is already
+/// blocked. A device already registered under the UUID is left as it is.
+async fn block_uuid(
+    State(state): State<Arc<AppState>>,
+    actor: Actor,
+    Json(req): Json<BlockUuidRequest>,
+) -> Result<StatusCode, HttpError> {
+    let uuid = normalize_uuid(RawUuid::Text(&req.uuid))
+        .ok_or_else(|| HttpError::BadRequest(format!("Invalid UUID '{}'", req.uuid)))?;
+
+    let conn = state.connection_factory.open().await?;
+    if !uuid_blocklist::block_uuid(&conn, &uuid, req.reason.as_deref()).await? {
+        return Err(HttpError::Conflict(format!(
+            "UUID {} is already blocked",
+            uuid
+        )));
+    }
+    audit::record(
+        &conn,
+        &actor,
+        "uuid_blocklist.add",
+        &format!("device/{}", uuid),
+        None,
+        Some(serde_json::json!({ "reason": req.reason })),
+    )
+    .await;
+    Ok(StatusCode::CREATED)
+}
+
+/// `DELETE /api/uuid-blocklist/{uuid}`
+///
+/// Returns `204 No Content`, or `404` if the UUID isn't blocked.
+async fn unblock_uuid(
+    State(state): State<Arc<AppState>>,
+    actor: Actor,
+    Path(uuid): Path<Uuid>,
+) -> Result<StatusCode, HttpError> {
+    let conn = state.connection_factory.open().await?;
+    if !uuid_blocklist::unblock_uuid(&conn, &uuid).await? {
+        return Err(HttpError::NotFound(format!("UUID {} is not blocked", uuid)));
+    }
+    audit::record(
+        &conn,
+        &actor,
+        "uuid_blocklist.remove",
+        &format!("device/{}", uuid),
+        None,
+        None,
+    )
+    .await;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        body::Body,
+        http::{Method, Request},
+    };
+    use serde_json::json;
+    use tower::ServiceExt;
+
+    use crate::{database, test_connection_factory};
+
+    async fn send(app: Router, method: Method, uri: &str, body: serde_json::Value) -> StatusCode {
+        let req = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        app.oneshot(req).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn test_block_list_and_unblock() {
+        let factory = test_connection_factory!();
+        let conn = database::run_migrations(&factory).await.unwrap();
+        let conn_factory: Arc<dyn database::ConnectionFactory> = Arc::new(factory);
+        let app = routes(crate::http::test_helpers::build_test_state(conn_factory));
+        let uuid = "b2000000-0000-0000-0000-000000000001";
+
+        let body = json!({ "uuid": uuid.to_uppercase(), "reason": "test VM" });
+        assert_eq!(
+            send(
+                app.clone(),
+                Method::POST,
+                "/api/uuid-blocklist",
+                body.clone()
+            )
+            .await,
+            StatusCode::CREATED
+        );
+        assert_eq!(
+            send(app.clone(), Method::POST, "/api/uuid-blocklist", body).await,
+            StatusCode::CONFLICT
+        );
+        assert_eq!(
+            send(
+                app.clone(),
+                Method::POST,
+                "/api/uuid-blocklist",
+                json!({ "uuid": "not-a-uuid" })
+            )
+            .await,
+            StatusCode::BAD_REQUEST
+        );
+
+        let resp = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/api/uuid-blocklist")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let entries: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(entries[0]["uuid"], uuid);
+        assert_eq!(entries[0]["reason"], "test VM");
+
+        let uri = format!("/api/uuid-blocklist/{uuid}");
+        assert_eq!(
+            send(app.clone(), Method::DELETE, &uri, json!(null)).await,
+            StatusCode::NO_CONTENT
+        );
+        assert_eq!(
+            send(app, Method::DELETE, &uri, json!(null)).await,
+            StatusCode::NOT_FOUND
+        );
+        assert!(
+            !uuid_blocklist::is_blocked(&conn, &Uuid::parse_str(uuid).unwrap())
+                .await
+                .unwrap()
+        );
+    }
+}
@@ -0,0 +1,71 @@
+//! Audit recording for state-changing HTTP handlers.
+//!
+//! Handlers take an [`Actor`] extractor and call [`record`] after a mutation
+//! succeeds. The server has no authentication layer yet, so the actor is the
+//! client's address; an authenticated identity should replace it once one exists.
+
+use std::{convert::Infallible, net::SocketAddr};
+
+use axum::{
+    extract::{ConnectInfo, FromRequestParts},
+    http::request::Parts,
+};
+use serde::Serialize;
+
+use crate::{
+    audit::{self, AuditEntry},
+    database::Connection,
+};
+
+/// Who is making the request, for the audit log.
+#[derive(Debug, Clone)]
+pub struct Actor(pub String);
+
+impl<S: Send + Sync> FromRequestParts<S> for Actor {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let actor = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip().to_string())
+            .unwrap_or_else(|| "unknown".to_string());
+        Ok(Actor(actor))
+    }
+}
+
+/// Record a completed mutation in the audit log.
+///
+/// `before` and `after` are JSON summaries of the target (see [`summary`]). The
+/// change has already been made, so a failure to write the entry is logged rather
+/// than returned.
+pub async fn record(
+    conn: &Connection,
+    actor: &Actor,
+    action: &str,
+    target: &str,
+    before: Option<serde_json::Value>,
+    after: Option<serde_json::Value>,
+) {
+    let entry = AuditEntry {
+        actor: actor.0.clone(),
+        action: action.to_string(),
+        target: target.to_string(),
+        before,
+        after,
+    };
+    if let Err(e) = audit::record(conn, &entry).await {
+        log::error!(
+            "Failed to record audit entry {} on {} by {}: {:#}",
+            action,
+            target,
+            actor.0,
+            e
+        );
+    }
+}
+
+/// Serialize `value` as an audit summary.
+pub fn summary<T: Serialize>(value: &T) -> Option<serde_json::Value> {
+    serde_json::to_value(value).ok()
+}
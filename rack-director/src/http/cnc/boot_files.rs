@@ -3,15 +3,28 @@ use std::sync::Arc;
 use axum::{
     body::Body,
     extract::{self, State},
-    http::Response,
     http::header,
+    http::{HeaderMap, Response},
     response::IntoResponse,
 };
-use log::warn;
+use log::{info, warn};
 use tokio::io::AsyncReadExt;
 
+use super::ipxe_scripts::generate_uuid_redirect;
 use crate::http::{AppState, error::Error};
 
+/// iPXE loaders handed out by DHCP. iPXE asking for one of these would only load
+/// itself a second time.
+const IPXE_LOADERS: &[&str] = &["snponly.efi", "undionly.kpxe", "ipxe.efi"];
+
+/// Whether the request comes from iPXE, which sends `User-Agent: iPXE/<version>`.
+fn is_ipxe_user_agent(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::USER_AGENT)
+        .and_then(|ua| ua.to_str().ok())
+        .is_some_and(|ua| ua.starts_with("iPXE/"))
+}
+
 /// HTTP endpoint to serve boot files (snponly.efi, undionly.kpxe) for UEFI HTTP Boot
 ///
 /// This endpoint serves firmware boot files over HTTP for modern UEFI clients that support
@@ -35,6 +48,9 @@ use crate::http::{AppState, error::Error};
 /// Returns HTTP 200 with the file contents and `application/octet-stream` content type,
 /// or HTTP 404 if the file doesn't exist or fails validation.
 ///
+/// When iPXE itself requests one of the iPXE loaders, it is answered with the
+/// `/cnc/ipxe` boot script instead, skipping a redundant chainload of iPXE.
+///
 /// # Errors
 ///
 /// Returns `Error::NotFound` if:
@@ -44,7 +60,17 @@ use crate::http::{AppState, error::Error};
 pub async fn boot_file_handler(
     State(state): State<Arc<AppState>>,
     extract::Path(filename): extract::Path<String>,
+    headers: HeaderMap,
 ) -> Result<impl IntoResponse, Error> {
+    if IPXE_LOADERS.contains(&filename.as_str())
+        && is_ipxe_user_agent(&headers)
+        && let Some(host) = headers.get(header::HOST).and_then(|h| h.to_str().ok())
+    {
+        info!("iPXE requested {filename}; serving the boot script instead");
+        let urls = state.boot_urls.resolve(&format!("http://{host}"));
+        return Ok(generate_uuid_redirect(urls.script).into_response());
+    }
+
     // Get a reader for the file (validates path)
     let mut reader = state
         .boot_file_provider
@@ -68,7 +94,7 @@ pub async fn boot_file_handler(
         header::CONTENT_TYPE,
         "application/octet-stream".parse().unwrap(),
     );
-    Ok(response)
+    Ok(response.into_response())
 }
 
 #[cfg(test)]
@@ -148,6 +174,10 @@ mod tests {
             unprovisioned_sleep_secs: 600,
             bundled_osm_path: None,
             power_config: crate::director::power::PowerConfig::default(),
+            default_lease_duration: crate::dhcp::store::DEFAULT_LEASE_DURATION,
+            tftp_transfers: crate::tftp::TransferRegistry::default(),
+            boot_urls: crate::plans::actions::BootUrlConfig::default(),
+            artifact_hashes: Default::default(),
         });
 
         (state, temp_dir)
@@ -179,6 +209,62 @@ mod tests {
         assert_eq!(body.as_ref(), b"IPXE_EFI_BINARY_DATA");
     }
 
+    #[tokio::test]
+    async fn test_boot_file_handler_serves_script_to_ipxe() {
+        let (state, _temp_dir) = create_test_state().await;
+
+        let app = Router::new()
+            .route(
+                "/cnc/boot/{filename}",
+                axum::routing::get(boot_file_handler),
+            )
+            .with_state(state);
+
+        let request = Request::builder()
+            .uri("/cnc/boot/snponly.efi")
+            .header("Host", "10.0.0.1:3000")
+            .header("User-Agent", "iPXE/1.21.1+ (g4bd0)")
+            .body(Body::empty())
+            .unwrap();
+
+        let response = app.oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body.starts_with("#!ipxe"), "got: {body}");
+        assert!(body.contains("chain http://10.0.0.1:3000/cnc/ipxe?uuid=${uuid}"));
+    }
+
+    #[tokio::test]
+    async fn test_boot_file_handler_serves_binary_to_plain_pxe() {
+        let (state, _temp_dir) = create_test_state().await;
+
+        let app = Router::new()
+            .route(
+                "/cnc/boot/{filename}",
+                axum::routing::get(boot_file_handler),
+            )
+            .with_state(state);
+
+        let request = Request::builder()
+            .uri("/cnc/boot/snponly.efi")
+            .header("Host", "10.0.0.1:3000")
+            .header("User-Agent", "UefiHttpBoot/1.0")
+            .body(Body::empty())
+            .unwrap();
+
+        let response = app.oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(body.as_ref(), b"IPXE_EFI_BINARY_DATA");
+    }
+
     #[tokio::test]
     async fn test_boot_file_handler_nonexistent_file() {
         let (state, _temp_dir) = create_test_state().await;
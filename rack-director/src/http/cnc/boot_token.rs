@@ -0,0 +1,34 @@
+//! Authenticating callbacks from a netbooted agent image.
+//!
+//! Machines on the provisioning network have no API credentials. Instead, each boot
+//! into the agent image is issued a token on its kernel cmdline
+//! (`rackdirector.token=`), which callbacks send back in [`BOOT_TOKEN_HEADER`].
+
+use axum::http::HeaderMap;
+use uuid::Uuid;
+
+use crate::{director::Director, http::error::Error};
+
+/// Header carrying the token from the device's kernel cmdline.
+pub const BOOT_TOKEN_HEADER: &str = "x-boot-token";
+
+/// Fail with `401 Unauthorized` unless `headers` carry `uuid`'s current boot token.
+pub async fn require_boot_token(
+    director: &Director<'_>,
+    uuid: &Uuid,
+    headers: &HeaderMap,
+) -> Result<(), Error> {
+    let Some(presented) = headers
+        .get(BOOT_TOKEN_HEADER)
+        .and_then(|value| value.to_str().ok())
+    else {
+        return Err(Error::Unauthorized(format!(
+            "Missing {BOOT_TOKEN_HEADER} header"
+        )));
+    };
+    if !director.check_boot_token(uuid, presented).await? {
+        log::warn!("Rejected callback for {uuid}: boot token does not match");
+        return Err(Error::Unauthorized("Invalid boot token".to_string()));
+    }
+    Ok(())
+}
@@ -338,6 +338,10 @@ mod tests {
             unprovisioned_sleep_secs: 600,
             bundled_osm_path: None,
             power_config: crate::director::power::PowerConfig::default(),
+            default_lease_duration: crate::dhcp::store::DEFAULT_LEASE_DURATION,
+            tftp_transfers: crate::tftp::TransferRegistry::default(),
+            boot_urls: crate::plans::actions::BootUrlConfig::default(),
+            artifact_hashes: Default::default(),
         });
 
         (state, temp_dir, migration_conn)
@@ -453,6 +457,11 @@ mod tests {
             }
             Err(Error::ValidationError(_)) => panic!("Expected NotFound but got ValidationError"),
             Err(Error::BadRequest(_)) => panic!("Expected NotFound but got BadRequest"),
+            Err(Error::Conflict(_)) => panic!("Expected NotFound but got Conflict"),
+            Err(Error::MethodNotAllowed(_)) => panic!("Expected NotFound but got MethodNotAllowed"),
+            Err(Error::ServiceUnavailable(e)) => {
+                panic!("Expected NotFound but got ServiceUnavailable: {}", e)
+            }
             Err(Error::UnprocessableEntity(_)) => {
                 panic!("Expected NotFound but got UnprocessableEntity")
             }
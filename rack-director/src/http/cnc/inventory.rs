@@ -0,0 +1,233 @@
+//! `/cnc/inventory`: hardware inventory submitted by the discovery image.
+
+use std::sync::Arc;
+
+use axum::{
+    Json,
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode},
+};
+use chrono::Utc;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use super::boot_token::require_boot_token;
+use crate::{
+    device_inventory::{self, Inventory},
+    device_warnings,
+    director::Director,
+    http::{AppState, error::Error},
+};
+
+/// Largest inventory document accepted, in bytes. Far more than a real inventory
+/// needs, far less than the server-wide limit an unauthenticated machine could
+/// otherwise send.
+pub const MAX_INVENTORY_BODY_BYTES: usize = 256 * 1024;
+
+/// Query parameters for the inventory endpoint.
+#[derive(Debug, Deserialize)]
+pub struct InventoryQuery {
+    pub uuid: Uuid,
+}
+
+/// Store the hardware inventory the discovery image collected for a device, and
+/// record warnings for NICs that don't match the interfaces DHCP has seen.
+///
+/// The request must carry the device's boot token (see [`super::boot_token`]).
+///
+/// Returns:
+/// - `204 No Content` once stored, replacing any earlier inventory.
+/// - `401 Unauthorized` if the boot token is missing or wrong.
+/// - `404 Not Found` if the device is unknown.
+/// - `413 Payload Too Large` over [`MAX_INVENTORY_BODY_BYTES`].
+/// - `4xx` if the body is not a JSON object.
+pub async fn inventory_handler(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<InventoryQuery>,
+    headers: HeaderMap,
+    Json(inventory): Json<Inventory>,
+) -> Result<StatusCode, Error> {
+    let conn = state.connection_factory.open().await?;
+    let device_id = device_warnings::get_device_id_by_uuid(&conn, &params.uuid)
+        .await?
+        .ok_or_else(|| Error::NotFound(format!("Device {} not found", params.uuid)))?;
+    require_boot_token(&Director::new(&conn), &params.uuid, &headers).await?;
+
+    device_inventory::save_inventory(&conn, device_id, &inventory, Utc::now()).await?;
+    let discrepancies =
+        device_inventory::reconcile_nics(&conn, device_id, &params.uuid, &inventory).await?;
+    log::info!(
+        "Stored inventory for {} (serial {:?}, model {:?}, {} NIC discrepancies)",
+        params.uuid,
+        inventory.serial,
+        inventory.model,
+        discrepancies.len()
+    );
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Body,
+        http::{Request, StatusCode},
+    };
+    use tower::ServiceExt;
+
+    use super::MAX_INVENTORY_BODY_BYTES;
+    use crate::{
+        director::Director,
+        http::{
+            cnc::boot_token::BOOT_TOKEN_HEADER,
+            test_helpers::{TestApp, build_test_app},
+        },
+        test_connection_factory,
+    };
+
+    const UUID: &str = "550e8400-e29b-41d4-a716-446655440020";
+
+    /// Register the device and return the boot token its agent image was given.
+    async fn register_device(app: &TestApp) -> String {
+        let uuid = uuid::Uuid::parse_str(UUID).unwrap();
+        app.conn
+            .execute(
+                "INSERT INTO devices (uuid, lifecycle, architecture) VALUES (?1, 'new', 'x86-64')",
+                (uuid,),
+            )
+            .await
+            .unwrap();
+        Director::new(&app.conn)
+            .issue_boot_token(&uuid)
+            .await
+            .unwrap()
+    }
+
+    async fn submit_raw(app: &TestApp, token: Option<&str>, body: String) -> StatusCode {
+        let mut req = Request::builder()
+            .method("POST")
+            .uri(format!("/cnc/inventory?uuid={UUID}"))
+            .header("content-type", "application/json");
+        if let Some(token) = token {
+            req = req.header(BOOT_TOKEN_HEADER, token);
+        }
+        let req = req.body(Body::from(body)).unwrap();
+        app.router.clone().oneshot(req).await.unwrap().status()
+    }
+
+    async fn submit(app: &TestApp, token: &str, body: serde_json::Value) -> StatusCode {
+        submit_raw(app, Some(token), body.to_string()).await
+    }
+
+    async fn fetch(app: &TestApp) -> (StatusCode, serde_json::Value) {
+        let req = Request::builder()
+            .uri(format!("/api/devices/{UUID}/inventory"))
+            .body(Body::empty())
+            .unwrap();
+        let resp = app.router.clone().oneshot(req).await.unwrap();
+        let status = resp.status();
+        let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap_or_default())
+    }
+
+    #[tokio::test]
+    async fn test_submit_then_fetch_inventory() {
+        let app = build_test_app(test_connection_factory!()).await;
+        let token = register_device(&app).await;
+
+        let (status, _) = fetch(&app).await;
+        assert_eq!(status, StatusCode::NOT_FOUND, "nothing submitted yet");
+
+        let document = serde_json::json!({
+            "serial": "CZ2049XYZ",
+            "model": "ProLiant DL360 Gen10",
+            "memory_bytes": 137438953472u64,
+            "disks": [{"path": "/dev/nvme0n1", "size_bytes": 960197124096u64}],
+            "nics": [{"mac": "aa:bb:cc:dd:ee:01", "driver": "ice"}],
+        });
+        assert_eq!(
+            submit(&app, &token, document.clone()).await,
+            StatusCode::NO_CONTENT
+        );
+
+        let (status, json) = fetch(&app).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(json["inventory"], document);
+        assert!(json["collected_at"].is_string());
+    }
+
+    #[tokio::test]
+    async fn test_submit_inventory_surfaces_nic_discrepancies_as_warnings() {
+        let app = build_test_app(test_connection_factory!()).await;
+        let token = register_device(&app).await;
+
+        let document = serde_json::json!({"nics": [{"mac": "aa:bb:cc:dd:ee:02"}]});
+        assert_eq!(submit(&app, &token, document).await, StatusCode::NO_CONTENT);
+
+        let req = Request::builder()
+            .uri(format!("/api/devices/{UUID}/warnings"))
+            .body(Body::empty())
+            .unwrap();
+        let resp = app.router.clone().oneshot(req).await.unwrap();
+        let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let warnings: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(warnings[0]["code"], "NIC_NOT_SEEN_BY_DHCP");
+        assert!(
+            warnings[0]["message"]
+                .as_str()
+                .unwrap()
+                .contains("aa:bb:cc:dd:ee:02")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_submit_inventory_for_unknown_device() {
+        let app = build_test_app(test_connection_factory!()).await;
+
+        let status = submit(&app, "token", serde_json::json!({"serial": "X"})).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_submit_inventory_rejects_non_object() {
+        let app = build_test_app(test_connection_factory!()).await;
+        let token = register_device(&app).await;
+
+        let status = submit(&app, &token, serde_json::json!(["not", "an", "object"])).await;
+        assert!(status.is_client_error());
+        let (status, _) = fetch(&app).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_submit_inventory_requires_boot_token() {
+        let app = build_test_app(test_connection_factory!()).await;
+        let token = register_device(&app).await;
+        let document = serde_json::json!({"serial": "X"});
+
+        let status = submit_raw(&app, None, document.to_string()).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let status = submit(&app, "not-the-token", document.clone()).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, _) = fetch(&app).await;
+        assert_eq!(status, StatusCode::NOT_FOUND, "nothing should be stored");
+
+        assert_eq!(submit(&app, &token, document).await, StatusCode::NO_CONTENT);
+    }
+
+    #[tokio::test]
+    async fn test_submit_inventory_rejects_oversized_body() {
+        let app = build_test_app(test_connection_factory!()).await;
+        let token = register_device(&app).await;
+
+        let padding = "x".repeat(MAX_INVENTORY_BODY_BYTES);
+        let body = serde_json::json!({ "serial": "X", "padding": padding }).to_string();
+        let status = submit_raw(&app, Some(&token), body).await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+        let (status, _) = fetch(&app).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+}
@@ -2,6 +2,27 @@ use axum::{
     http::{StatusCode, header},
     response::Response,
 };
+use uuid::Uuid;
+
+/// URL of the main iPXE endpoint for a device whose UUID is not yet known.
+///
+/// `${uuid}` and `${netX/mac}` are left for iPXE to expand on the client.
+///
+/// # Arguments
+/// * `root_url` - The base HTTP URL of the rack-director server
+pub fn uuid_chain_url(root_url: &str) -> String {
+    format!("{root_url}/cnc/ipxe?uuid=${{uuid}}&mac=${{netX/mac}}")
+}
+
+/// URL of the main iPXE endpoint for a known device.
+///
+/// # Arguments
+/// * `root_url` - The base HTTP URL of the rack-director server
+/// * `uuid` - The device's UUID
+/// * `mac` - The MAC address the device is booting from
+pub fn device_chain_url(root_url: &str, uuid: &Uuid, mac: &str) -> String {
+    format!("{root_url}/cnc/ipxe?uuid={uuid}&mac={mac}")
+}
 
 /// Generates an iPXE script that redirects to the main iPXE endpoint with UUID and MAC.
 ///
@@ -15,8 +36,9 @@ pub fn generate_uuid_script(root_url: &str) -> String {
     format!(
         r#"#!ipxe
 # Chain boot to send uuid and mac
-chain {root_url}/cnc/ipxe?uuid=${{uuid}}&mac=${{netX/mac}}
-"#
+chain {}
+"#,
+        uuid_chain_url(root_url)
     )
 }
 
@@ -57,6 +79,15 @@ mod tests {
         assert!(script.contains("chain http://example.com/cnc/ipxe?uuid=${uuid}&mac=${netX/mac}"));
     }
 
+    #[test]
+    fn test_device_chain_url() {
+        let uuid = Uuid::parse_str("d4000000-0000-0000-0000-000000000001").unwrap();
+        assert_eq!(
+            device_chain_url("http://example.com", &uuid, "aa:bb:cc:dd:ee:ff"),
+            "http://example.com/cnc/ipxe?uuid=d4000000-0000-0000-0000-000000000001&mac=aa:bb:cc:dd:ee:ff"
+        );
+    }
+
     #[test]
     fn test_generate_uuid_redirect() {
         let response = generate_uuid_redirect("http://example.com");
@@ -1,7 +1,9 @@
 mod boot_files;
+mod boot_token;
 mod device_registration;
 mod install_script;
-mod ipxe_scripts;
+mod inventory;
+pub(crate) mod ipxe_scripts;
 mod network_processing;
 mod osm_files;
 mod poll;
@@ -10,25 +12,27 @@ use std::sync::Arc;
 
 use axum::{
     Router,
-    extract::{self, ConnectInfo, Query, State},
+    body::Body,
+    extract::{self, ConnectInfo, DefaultBodyLimit, Query, State},
     http::{
-        StatusCode,
+        HeaderMap, StatusCode,
         header::{self},
     },
     response::{NoContent, Response},
     routing::{get, post},
 };
 use axum_extra::extract::Host;
-use log::{info, warn};
+use log::warn;
 use serde::{Deserialize, Serialize};
 use std::net::SocketAddr;
 use uuid::Uuid;
 
 use crate::http::error::Error;
 use crate::{
-    dhcp,
-    director::{Director, NetworkInterface},
+    artifacts,
+    director::{Director, NetworkInterface, RawUuid, normalize_uuid},
     http::AppState,
+    plans::actions::BootTarget,
 };
 use common::device_attributes::{BmcConfig, DeviceAttributes};
 
@@ -36,13 +40,21 @@ use ipxe_scripts::{build_response, generate_uuid_redirect};
 
 #[derive(Debug, Deserialize)]
 struct IpxeQuery {
-    uuid: Option<Uuid>,
+    /// Hardware UUID as iPXE's `${uuid}` renders it; see [`parse_device_uuid`].
+    uuid: Option<String>,
+    mac: Option<String>,
+}
+
+/// Query for `/cnc/ipxe/{uuid}`, where the UUID is part of the path.
+#[derive(Debug, Deserialize)]
+struct IpxePathQuery {
     mac: Option<String>,
 }
 
 pub fn routes(state: Arc<AppState>) -> Router {
     Router::new()
         .route("/cnc/ipxe", get(ipxe_handler))
+        .route("/cnc/ipxe/{uuid}", get(ipxe_path_handler))
         .route("/cnc/install_script", get(install_script_handler))
         .route("/cnc/agent-images/{filename}", get(agent_images_handler))
         .route("/cnc/boot/{filename}", get(boot_files::boot_file_handler))
@@ -56,6 +68,11 @@ pub fn routes(state: Arc<AppState>) -> Router {
         .route("/cnc/devices/{uuid}/bmc_config", get(get_bmc_config))
         .route("/cnc/devices/{uuid}/disk_layout", get(get_disk_layout))
         .route("/cnc/poll", get(poll::poll_handler))
+        .route(
+            "/cnc/inventory",
+            post(inventory::inventory_handler)
+                .layer(DefaultBodyLimit::max(inventory::MAX_INVENTORY_BODY_BYTES)),
+        )
         .with_state(state)
 }
 
@@ -68,113 +85,102 @@ async fn ipxe_handler(
     log::debug!("/cnc/ipxe, params: {:?}", params);
     let root_url = format!("http://{host}");
 
-    let uuid: Uuid = match params.uuid {
-        Some(uuid) => uuid,
-        None => return Ok(generate_uuid_redirect(&root_url)),
-    };
+    match params.uuid {
+        Some(uuid) => {
+            let uuid = parse_device_uuid(&uuid)?;
+            device_ipxe_script(&state, addr, &root_url, uuid, params.mac).await
+        }
+        None => Ok(generate_uuid_redirect(
+            state.boot_urls.resolve(&root_url).script,
+        )),
+    }
+}
+
+/// Per-device form of `/cnc/ipxe`, stable enough to put in DHCP option 67.
+async fn ipxe_path_handler(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    extract::Path(uuid): extract::Path<String>,
+    Query(params): Query<IpxePathQuery>,
+    Host(host): Host,
+) -> Result<Response<String>, Error> {
+    log::debug!("/cnc/ipxe/{uuid}, params: {:?}", params);
+    let root_url = format!("http://{host}");
+    let uuid = parse_device_uuid(&uuid)?;
+    device_ipxe_script(&state, addr, &root_url, uuid, params.mac).await
+}
+
+/// Normalize a UUID reported by iPXE the same way DHCP option 97 is, so a device
+/// is keyed identically whichever path saw it first.
+fn parse_device_uuid(uuid: &str) -> Result<Uuid, Error> {
+    normalize_uuid(RawUuid::Text(uuid))
+        .ok_or_else(|| Error::BadRequest(format!("Invalid device UUID '{}'", uuid)))
+}
+
+/// Handle a boot request from `uuid` and render the iPXE script it should run.
+///
+/// Shared by the query (`/cnc/ipxe?uuid=`) and path (`/cnc/ipxe/{uuid}`) routes so
+/// both behave identically for the same device.
+async fn device_ipxe_script(
+    state: &AppState,
+    addr: SocketAddr,
+    root_url: &str,
+    uuid: Uuid,
+    mac: Option<String>,
+) -> Result<Response<String>, Error> {
+    let urls = state.boot_urls.resolve(root_url);
 
     let conn = state
         .connection_factory
         .open()
         .await
         .map_err(Error::ServerInternalError)?;
+    // Blocked UUIDs are neither registered nor updated; just boot whatever is on disk
+    if crate::uuid_blocklist::is_blocked(&conn, &uuid).await? {
+        log::info!("Ignoring boot request from blocked UUID {uuid}");
+        let ipxe_script = BootTarget::LocalDisk
+            .to_ipxe_script(urls, Some(&uuid))
+            .await?;
+        return Ok(build_response(ipxe_script));
+    }
+
     // This handler can register devices and auto-start discovery transitions,
     // which issue the OOB power kick — it must carry the configured PowerConfig.
     let director = Director::with_power_config(&conn, state.power_config);
 
     // Resolve MAC address from parameter or DHCP lookup
-    let mac_address =
-        device_registration::resolve_mac_address(&conn, params.mac.as_ref(), addr).await;
-
-    // Discovery Support
-    // For devices that don't exist, determine if the device should be created.
-    // TODO: Please make this better.
-    if !director.device_exists(&uuid).await?
-        && let Some(mac) = &mac_address
-    {
-        if director.find_pending_device_by_mac(mac).await?.is_some() {
-            info!("Found pending device {}. Starting discovery.", uuid);
-            device_registration::register_and_start_discovery(
-                &conn,
-                &uuid,
-                Some(mac),
-                state.power_config,
-            )
-            .await;
-        } else {
-            // Device is not pending. Check to see if the network has autodiscovery enabled.
-            let dhcp_lease = dhcp::store::get_lease_by_mac(&conn, mac).await?;
-            if let Some(lease) = dhcp_lease {
-                if let Some(netid) = lease.network_id {
-                    let dhcp_network = dhcp::store::get_network(&conn, netid).await?;
-
-                    // Register device if it doesn't exist and automatically start discovery
-                    if dhcp_network.enable_autodiscovery {
-                        info!(
-                            "Found new device {} on network with autodiscovery enabled. Adopting and starting discovery.",
-                            uuid
-                        );
-                        device_registration::register_and_start_discovery(
-                            &conn,
-                            &uuid,
-                            Some(mac),
-                            state.power_config,
-                        )
-                        .await;
-                    }
-                } else {
-                    warn!("DHCP Lease does not have a network ID")
-                }
-            } else {
-                warn!("MAC {:?} does not have a DHCP lease", mac)
-            }
-        }
-    } else {
-        warn!("Missing MAC address")
-    }
-
-    // Handle boot event - advance plan if current action supports it
-    // This must happen before we check the boot target, because on_boot() might advance the plan
-    if let Err(e) = director.on_boot(&uuid).await {
-        log::warn!("Failed to handle boot event for {}: {:?}", uuid, e);
-    }
-
-    // Store network info from DHCP lease (reuse the MAC address lookup from above)
-    if let Some(mac) = &mac_address {
-        if let Ok(Some(lease)) = dhcp::store::get_lease_by_mac(&conn, mac).await {
-            log::info!(
-                "Found DHCP lease for device {}: MAC {} IP {}",
-                uuid,
-                lease.mac_address,
-                lease.ip_address
-            );
-
-            // Update IP address for the interface with this MAC
-            // This will also create the interface if it doesn't exist yet
-            if let Err(e) = director
-                .set_device_ip_address(&uuid, &lease.ip_address, &lease.mac_address)
-                .await
-            {
-                warn!("Couldn't store IP address for device {uuid}: {e}");
-            }
-        }
-    } else {
-        log::debug!("No MAC address available for device {}", uuid);
-    }
+    let mac_address = device_registration::resolve_mac_address(&conn, mac.as_ref(), addr).await;
 
     // Non-fatal. If the boot target can't be found, redirect loop back here to try again
     let boot_target = match director
-        .next_boot_target(&uuid, state.unprovisioned_sleep_secs)
+        .handle_boot_request(
+            &uuid,
+            mac_address.as_deref(),
+            state.unprovisioned_sleep_secs,
+        )
         .await
     {
         Ok(x) => x,
         Err(e) => {
             warn!("Couldn't get boot target from director for {uuid}: {e}");
-            return Ok(generate_uuid_redirect(&root_url));
+            return Ok(generate_uuid_redirect(urls.script));
         }
     };
+    // Non-fatal too: the device still gets its script if this can't be recorded
+    if let Err(e) = director.mark_device_seen(&uuid).await {
+        warn!("Couldn't record {uuid} as seen: {e}");
+    }
 
-    let ipxe_script = boot_target.to_ipxe_script(&root_url, Some(&uuid)).await?;
+    // Callbacks from the agent image must present the token it boots with
+    let mut boot_target = boot_target;
+    if let BootTarget::AgentImage { cmdline, .. } = &mut boot_target {
+        let token = director.issue_boot_token(&uuid).await?;
+        *cmdline = format!("{cmdline} rackdirector.token={token}")
+            .trim_start()
+            .to_string();
+    }
+
+    let ipxe_script = boot_target.to_ipxe_script(urls, Some(&uuid)).await?;
 
     log::debug!("cnc/ipxe: returning script for {}:\n{}", uuid, ipxe_script);
 
@@ -207,27 +213,92 @@ async fn install_script_handler(
     .await
 }
 
+/// Serve a file from the agent image directory.
+///
+/// When the client sends `Accept-Encoding: gzip` and a precompressed `{filename}.gz`
+/// sidecar exists next to the file, the sidecar is served as-is with
+/// `Content-Encoding: gzip`; nothing is compressed on the fly. Requests carrying a
+/// `Range` header always get the raw file so byte offsets refer to the real content.
+///
+/// Detached signatures (`{filename}.sig`) and checksums (`{filename}.sha256`) are
+/// served like any other file. A `.sha256` with no file on disk is generated from
+/// the image it names.
 async fn agent_images_handler(
     State(state): State<Arc<AppState>>,
     extract::Path(filename): extract::Path<String>,
-) -> Result<(StatusCode, [(header::HeaderName, &'static str); 1], Vec<u8>), Error> {
-    // Construct the full file path
-    let file_path = state.agent_images_path.join(&filename);
+    headers: HeaderMap,
+) -> Result<Response<Body>, Error> {
+    let canonical_file = match resolve_agent_image(&state.agent_images_path, &filename).await {
+        Ok(file) => file,
+        Err(e) => match filename.strip_suffix(".sha256") {
+            Some(artifact) => return generated_sha256_sidecar(&state, artifact).await,
+            None => return Err(e),
+        },
+    };
 
-    // Canonicalize both paths to prevent directory traversal attacks
-    // This resolves all symlinks, .., ., etc.
-    let canonical_file = tokio::fs::canonicalize(&file_path).await.map_err(|e| {
-        warn!("Failed to canonicalize path for {}: {}", filename, e);
+    let wants_gzip = accepts_gzip(&headers) && !headers.contains_key(header::RANGE);
+    if wants_gzip
+        && let Some(data) = read_gzip_sidecar(&state.agent_images_path, &canonical_file).await
+    {
+        return Ok(Response::builder()
+            .status(StatusCode::OK)
+            .header(header::CONTENT_TYPE, "application/octet-stream")
+            .header(header::CONTENT_ENCODING, "gzip")
+            .header(header::VARY, "accept-encoding")
+            .body(Body::from(data))
+            .expect("response build should not fail"));
+    }
+
+    // Read and serve the file
+    let data = tokio::fs::read(&canonical_file).await.map_err(|e| {
+        warn!("Failed to read agent image {}: {}", filename, e);
         Error::NotFound(format!("Agent image not found: {}", filename))
     })?;
 
-    let canonical_base = tokio::fs::canonicalize(&state.agent_images_path)
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "application/octet-stream")
+        .body(Body::from(data))
+        .expect("response build should not fail"))
+}
+
+/// A `sha256sum`-style checksum of the agent image `artifact`, for images
+/// published without a `.sha256` file.
+async fn generated_sha256_sidecar(
+    state: &AppState,
+    artifact: &str,
+) -> Result<Response<Body>, Error> {
+    let canonical_file = resolve_agent_image(&state.agent_images_path, artifact).await?;
+    let digest = state.artifact_hashes.digest(&canonical_file).await?;
+    let name = artifact.rsplit('/').next().unwrap_or(artifact);
+
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "text/plain; charset=utf-8")
+        .body(Body::from(artifacts::sha256sum_line(&digest, name)))
+        .expect("response build should not fail"))
+}
+
+/// Resolve `filename` inside the agent image directory, rejecting anything that
+/// escapes it.
+pub(crate) async fn resolve_agent_image(
+    base: &std::path::Path,
+    filename: &str,
+) -> Result<std::path::PathBuf, Error> {
+    // Canonicalize both paths to prevent directory traversal attacks
+    // This resolves all symlinks, .., ., etc.
+    let canonical_file = tokio::fs::canonicalize(base.join(filename))
         .await
         .map_err(|e| {
-            warn!("Failed to canonicalize base path: {}", e);
+            warn!("Failed to canonicalize path for {}: {}", filename, e);
             Error::NotFound(format!("Agent image not found: {}", filename))
         })?;
 
+    let canonical_base = tokio::fs::canonicalize(base).await.map_err(|e| {
+        warn!("Failed to canonicalize base path: {}", e);
+        Error::NotFound(format!("Agent image not found: {}", filename))
+    })?;
+
     // Verify the resolved file path is within the base directory
     // Return NotFound for security violations to avoid leaking information
     if !canonical_file.starts_with(&canonical_base) {
@@ -241,17 +312,48 @@ async fn agent_images_handler(
         )));
     }
 
-    // Read and serve the file
-    let data = tokio::fs::read(&canonical_file).await.map_err(|e| {
-        warn!("Failed to read agent image {}: {}", filename, e);
-        Error::NotFound(format!("Agent image not found: {}", filename))
-    })?;
+    Ok(canonical_file)
+}
+
+/// Read the precompressed `.gz` sidecar for an already-resolved agent image.
+///
+/// Returns `None` if there is no sidecar or it resolves outside the agent image
+/// directory, in which case the caller falls back to the raw file.
+async fn read_gzip_sidecar(
+    base: &std::path::Path,
+    canonical_file: &std::path::Path,
+) -> Option<Vec<u8>> {
+    let mut sidecar = canonical_file.as_os_str().to_owned();
+    sidecar.push(".gz");
+
+    let canonical_sidecar = tokio::fs::canonicalize(&sidecar).await.ok()?;
+    let canonical_base = tokio::fs::canonicalize(base).await.ok()?;
+    if !canonical_sidecar.starts_with(&canonical_base) {
+        return None;
+    }
 
-    Ok((
-        StatusCode::OK,
-        [(header::CONTENT_TYPE, "application/octet-stream")],
-        data,
-    ))
+    tokio::fs::read(&canonical_sidecar).await.ok()
+}
+
+/// Whether the `Accept-Encoding` header allows a gzip-encoded response.
+///
+/// A `gzip` (or `x-gzip`) coding with `q=0` is an explicit refusal.
+fn accepts_gzip(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(header::ACCEPT_ENCODING)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|coding| {
+            let mut parts = coding.split(';').map(str::trim);
+            let name = parts.next().unwrap_or_default();
+            if !name.eq_ignore_ascii_case("gzip") && !name.eq_ignore_ascii_case("x-gzip") {
+                return false;
+            }
+            parts
+                .filter_map(|param| param.strip_prefix("q="))
+                .all(|q| q.parse::<f32>().map(|q| q > 0.0).unwrap_or(false))
+        })
 }
 
 #[derive(Deserialize, Serialize)]
@@ -673,6 +775,10 @@ mod tests {
             unprovisioned_sleep_secs: 600,
             bundled_osm_path: None,
             power_config: crate::director::power::PowerConfig::default(),
+            default_lease_duration: crate::dhcp::store::DEFAULT_LEASE_DURATION,
+            tftp_transfers: crate::tftp::TransferRegistry::default(),
+            boot_urls: crate::plans::actions::BootUrlConfig::default(),
+            artifact_hashes: Default::default(),
         });
         (state, temp_dir)
     }
@@ -775,6 +881,105 @@ mod tests {
         );
     }
 
+    /// GET `uri` from the cnc routes and return the response body.
+    async fn get_ipxe_script(state: Arc<AppState>, uri: &str) -> String {
+        let app = routes(state).layer(axum::extract::connect_info::MockConnectInfo(
+            "127.0.0.1:1234".parse::<SocketAddr>().unwrap(),
+        ));
+        let request = Request::builder()
+            .header("Host", "localhost")
+            .uri(uri)
+            .body(Body::empty())
+            .unwrap();
+
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        String::from_utf8(body.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_ipxe_blocked_uuid_is_not_registered() {
+        let (state, _temp_dir) = setup_test_state().await;
+        let network_id = create_test_network(&state, true).await;
+        let uuid = test_uuid(0x30);
+        let mac = test_mac(0x30);
+        {
+            // A pending device would otherwise be adopted on its first boot
+            let conn = test_db(&state).await;
+            Director::new(&conn)
+                .create_pending_device(&mac, network_id)
+                .await
+                .unwrap();
+            crate::uuid_blocklist::block_uuid(&conn, &uuid, Some("test VM"))
+                .await
+                .unwrap();
+        }
+
+        let body =
+            get_ipxe_script(state.clone(), &format!("/cnc/ipxe?uuid={uuid}&mac={mac}")).await;
+        assert!(
+            body.contains("exit") && !body.contains("sleep"),
+            "Expected local boot, got: {body}"
+        );
+
+        let conn = test_db(&state).await;
+        assert!(!Director::new(&conn).device_exists(&uuid).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_ipxe_path_form_matches_query_form() {
+        let (state, _temp_dir) = setup_test_state().await;
+        let uuid = test_uuid(0x20);
+        let mac = test_mac(0x20);
+
+        {
+            let conn = test_db(&state).await;
+            let director = Director::new(&conn);
+            director
+                .register_device(&uuid, crate::director::Architecture::X86_64)
+                .await
+                .unwrap();
+            director
+                .start_lifecycle_transition(&uuid, crate::lifecycle::DeviceLifecycle::Unprovisioned)
+                .await
+                .unwrap();
+        }
+
+        let query_form =
+            get_ipxe_script(state.clone(), &format!("/cnc/ipxe?uuid={uuid}&mac={mac}")).await;
+        let path_form =
+            get_ipxe_script(state.clone(), &format!("/cnc/ipxe/{uuid}?mac={mac}")).await;
+        assert!(
+            query_form.contains("kernel"),
+            "device in discovery should boot the agent, got: {query_form}"
+        );
+        assert_eq!(path_form, query_form);
+
+        // Without the optional MAC the path form still resolves the same device
+        let bare_path = get_ipxe_script(state, &format!("/cnc/ipxe/{uuid}")).await;
+        assert_eq!(bare_path, query_form);
+    }
+
+    #[tokio::test]
+    async fn test_ipxe_path_form_rejects_invalid_uuid() {
+        let (state, _temp_dir) = setup_test_state().await;
+        let app = routes(state).layer(axum::extract::connect_info::MockConnectInfo(
+            "127.0.0.1:1234".parse::<SocketAddr>().unwrap(),
+        ));
+
+        let request = Request::builder()
+            .header("Host", "localhost")
+            .uri("/cnc/ipxe/not-a-uuid")
+            .body(Body::empty())
+            .unwrap();
+
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
     #[tokio::test]
     async fn test_ipxe_missing_uuid() {
         let (state, _temp_dir) = setup_test_state().await;
@@ -798,6 +1003,36 @@ mod tests {
         assert!(body_str.contains("chain http://localhost/cnc/ipxe?uuid=${uuid}&mac=${netX/mac}"));
     }
 
+    #[tokio::test]
+    async fn test_ipxe_missing_uuid_chains_to_configured_script_host() {
+        let (mut state, _temp_dir) = setup_test_state().await;
+        Arc::get_mut(&mut state).unwrap().boot_urls = crate::plans::actions::BootUrlConfig {
+            script_base_url: Some("http://scripts.example:8080".to_string()),
+            image_base_url: None,
+        };
+        let app = routes(state).layer(axum::extract::connect_info::MockConnectInfo(
+            "127.0.0.1:1234".parse::<SocketAddr>().unwrap(),
+        ));
+
+        let request = Request::builder()
+            .header("Host", "localhost")
+            .uri("/cnc/ipxe")
+            .body(Body::empty())
+            .unwrap();
+
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body_str = String::from_utf8(body.to_vec()).unwrap();
+        assert!(
+            body_str.contains(
+                "chain http://scripts.example:8080/cnc/ipxe?uuid=${uuid}&mac=${netX/mac}"
+            )
+        );
+    }
+
     #[tokio::test]
     async fn test_ipxe_empty_uuid() {
         let (state, _temp_dir) = setup_test_state().await;
@@ -875,6 +1110,91 @@ mod tests {
         assert!(pending_id.is_none(), "Pending device should be completed");
     }
 
+    /// The UUID iPXE reports and the one in DHCP option 97 name one device, whatever
+    /// text form iPXE uses and whichever byte order the firmware sends.
+    #[tokio::test]
+    async fn test_ipxe_and_dhcp_uuids_collapse_to_one_device() {
+        use crate::dhcp::device_resolution::{DeviceResolver, DirectorDeviceResolver};
+
+        let (state, _temp_dir) = setup_test_state().await;
+        let network_id = create_test_network(&state, false).await;
+        let uuid = test_uuid(0x30);
+        let mac = test_mac(0x30);
+        {
+            let conn = test_db(&state).await;
+            Director::new(&conn)
+                .create_pending_device(&mac, network_id)
+                .await
+                .unwrap();
+        }
+
+        let braced = format!("{{{}}}", uuid.to_string().to_uppercase());
+        get_ipxe_script(state.clone(), &format!("/cnc/ipxe?uuid={braced}&mac={mac}")).await;
+        get_ipxe_script(state.clone(), &format!("/cnc/ipxe/{uuid}?mac={mac}")).await;
+
+        let conn = test_db(&state).await;
+        let resolver = DirectorDeviceResolver::new();
+        let smbios = uuid.to_bytes_le();
+        let big_endian = *uuid.as_bytes();
+        for bytes in [smbios, big_endian] {
+            let guid = normalize_uuid(RawUuid::Smbios(&bytes)).unwrap();
+            let ctx = resolver
+                .resolve(&conn, "aa:bb:cc:00:03:30", Some(&guid))
+                .await
+                .unwrap();
+            assert_eq!(
+                ctx.device_uuid,
+                Some(uuid),
+                "option 97 bytes {:02x?}",
+                bytes
+            );
+        }
+
+        let devices = Director::new(&conn).get_all_devices().await.unwrap();
+        assert_eq!(devices.len(), 1);
+        assert_eq!(devices[0].uuid, uuid);
+    }
+
+    #[tokio::test]
+    async fn test_ipxe_chains_to_current_stage() {
+        let (state, _temp_dir) = setup_test_state().await;
+        let uuid = test_uuid(0x21);
+        let mac = test_mac(0x21);
+
+        {
+            let conn = test_db(&state).await;
+            let director = Director::new(&conn);
+            director
+                .register_device(&uuid, crate::director::Architecture::X86_64)
+                .await
+                .unwrap();
+            let plan = crate::plans::Plan::new(
+                uuid,
+                vec![
+                    crate::plans::Action::ChainScript {
+                        url: "/stages/discover.ipxe".to_string(),
+                    },
+                    crate::plans::Action::ChainScript {
+                        url: "/stages/install.ipxe".to_string(),
+                    },
+                ],
+            );
+            director.create_plan(&plan).await.unwrap();
+        }
+
+        let uri = format!("/cnc/ipxe?uuid={uuid}&mac={mac}");
+        let script = get_ipxe_script(state.clone(), &uri).await;
+        assert!(
+            script.contains("chain http://localhost/stages/discover.ipxe\n"),
+            "{script}"
+        );
+        let script = get_ipxe_script(state.clone(), &uri).await;
+        assert!(
+            script.contains("chain http://localhost/stages/install.ipxe\n"),
+            "{script}"
+        );
+    }
+
     #[tokio::test]
     async fn test_action_success() {
         let (state, _temp_dir) = setup_test_state().await;
@@ -1097,6 +1417,17 @@ mod tests {
             "iPXE script is missing rackdirector.url:\n{}",
             body_str
         );
+        let token = body_str
+            .split_whitespace()
+            .find_map(|arg| arg.strip_prefix("rackdirector.token="))
+            .expect("iPXE script is missing rackdirector.token");
+        let conn = test_db(&state).await;
+        assert!(
+            Director::new(&conn)
+                .check_boot_token(&test_uuid, token)
+                .await
+                .unwrap()
+        );
     }
 
     #[tokio::test]
@@ -1274,6 +1605,140 @@ mod tests {
         assert_eq!(response.status(), StatusCode::NOT_FOUND);
     }
 
+    #[tokio::test]
+    async fn test_agent_images_serves_gzip_sidecar_when_accepted() {
+        let (state, _temp_dir) = setup_test_state().await;
+        std::fs::write(
+            state.agent_images_path.join("initramfs.img.gz"),
+            b"mock gzipped initramfs",
+        )
+        .unwrap();
+        let app = routes(state.clone());
+
+        let request = Request::builder()
+            .uri("/cnc/agent-images/initramfs.img")
+            .header(header::ACCEPT_ENCODING, "deflate, gzip;q=0.8")
+            .body(Body::empty())
+            .unwrap();
+
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_ENCODING], "gzip");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(body.as_ref(), b"mock gzipped initramfs");
+
+        // Without Accept-Encoding the raw file is served
+        let request = Request::builder()
+            .uri("/cnc/agent-images/initramfs.img")
+            .body(Body::empty())
+            .unwrap();
+
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert!(!response.headers().contains_key(header::CONTENT_ENCODING));
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(body.as_ref(), b"mock initramfs data");
+
+        // Range requests stay on the raw path
+        let request = Request::builder()
+            .uri("/cnc/agent-images/initramfs.img")
+            .header(header::ACCEPT_ENCODING, "gzip")
+            .header(header::RANGE, "bytes=0-3")
+            .body(Body::empty())
+            .unwrap();
+
+        let response = app.oneshot(request).await.unwrap();
+        assert!(!response.headers().contains_key(header::CONTENT_ENCODING));
+    }
+
+    #[tokio::test]
+    async fn test_agent_images_falls_back_to_raw_without_sidecar() {
+        let (state, _temp_dir) = setup_test_state().await;
+        let app = routes(state.clone());
+
+        let request = Request::builder()
+            .uri("/cnc/agent-images/vmlinuz")
+            .header(header::ACCEPT_ENCODING, "gzip")
+            .body(Body::empty())
+            .unwrap();
+
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(!response.headers().contains_key(header::CONTENT_ENCODING));
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(body.as_ref(), b"mock kernel data");
+    }
+
+    #[tokio::test]
+    async fn test_agent_images_serves_checksum_and_signature_sidecars() {
+        let (state, _temp_dir) = setup_test_state().await;
+        std::fs::write(
+            state.agent_images_path.join("vmlinuz.sig"),
+            b"mock signature",
+        )
+        .unwrap();
+        let app = routes(state.clone());
+        let fetch = |uri: &'static str| {
+            let app = app.clone();
+            async move {
+                let request = Request::builder().uri(uri).body(Body::empty()).unwrap();
+                let response = app.oneshot(request).await.unwrap();
+                let status = response.status();
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                    .await
+                    .unwrap();
+                (status, body)
+            }
+        };
+
+        // Generated from the image: sha256("mock kernel data")
+        let (status, body) = fetch("/cnc/agent-images/vmlinuz.sha256").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            body.as_ref(),
+            b"c5254fecc7765dccbd4ec6d219b198031c6d6fe1578ab8c343895fba6a21ef8b  vmlinuz\n"
+        );
+
+        let (status, body) = fetch("/cnc/agent-images/vmlinuz.sig").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body.as_ref(), b"mock signature");
+
+        // A published checksum file wins over the generated one
+        std::fs::write(
+            state.agent_images_path.join("initramfs.img.sha256"),
+            b"published",
+        )
+        .unwrap();
+        let (_, body) = fetch("/cnc/agent-images/initramfs.img.sha256").await;
+        assert_eq!(body.as_ref(), b"published");
+
+        let (status, _) = fetch("/cnc/agent-images/nonexistent.sha256").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _) = fetch("/cnc/agent-images/initramfs.img.sig").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn test_accepts_gzip() {
+        let with = |value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::ACCEPT_ENCODING, value.parse().unwrap());
+            accepts_gzip(&headers)
+        };
+
+        assert!(with("gzip"));
+        assert!(with("br, GZIP"));
+        assert!(with("gzip;q=0.5"));
+        assert!(!with("gzip;q=0"));
+        assert!(!with("deflate, br"));
+        assert!(!accepts_gzip(&HeaderMap::new()));
+    }
+
     // ========== BMC Password Generation Tests ==========
 
     #[test]
@@ -1773,6 +2238,10 @@ pub(super) mod test_helpers {
             unprovisioned_sleep_secs: 600,
             bundled_osm_path: None,
             power_config: crate::director::power::PowerConfig::default(),
+            default_lease_duration: crate::dhcp::store::DEFAULT_LEASE_DURATION,
+            tftp_transfers: crate::tftp::TransferRegistry::default(),
+            boot_urls: crate::plans::actions::BootUrlConfig::default(),
+            artifact_hashes: Default::default(),
         });
 
         (state, temp_dir, migration_conn)
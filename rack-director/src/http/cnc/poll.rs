@@ -31,6 +31,7 @@ impl From<&Action> for PollAction {
             Action::RebootDevice => PollAction::RebootDevice,
             Action::InstallOs => PollAction::InstallOs,
             Action::Console => PollAction::Console,
+            Action::ChainScript { .. } => PollAction::ChainScript,
         }
     }
 }
@@ -331,5 +332,11 @@ mod tests {
         );
         assert_eq!(PollAction::from(&Action::InstallOs), PollAction::InstallOs);
         assert_eq!(PollAction::from(&Action::Console), PollAction::Console);
+        assert_eq!(
+            PollAction::from(&Action::ChainScript {
+                url: "/stages/install.ipxe".to_string()
+            }),
+            PollAction::ChainScript
+        );
     }
 }
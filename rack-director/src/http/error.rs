@@ -7,10 +7,16 @@ use serde::Serialize;
 
 pub enum Error {
     BadRequest(String),
+    /// The request lacks valid credentials.
+    Unauthorized(String),
     #[allow(clippy::enum_variant_names)]
     ValidationError(HashMap<String, String>),
     NotFound(String),
+    MethodNotAllowed(String),
+    Conflict(String),
     UnprocessableEntity(String),
+    /// The database is locked by another connection; the client should retry.
+    ServiceUnavailable(anyhow::Error),
     #[allow(clippy::enum_variant_names)] // ServerInternalError is the HTTP response code name
     ServerInternalError(anyhow::Error),
 }
@@ -31,14 +37,34 @@ impl IntoResponse for Error {
                 .status(400)
                 .body(Body::from(reason))
                 .expect("building body"),
+            Error::Unauthorized(reason) => axum::response::Response::builder()
+                .status(401)
+                .body(Body::from(reason))
+                .expect("building body"),
             Error::NotFound(reason) => axum::response::Response::builder()
                 .status(404)
                 .body(Body::from(reason))
                 .expect("building body"),
+            Error::MethodNotAllowed(reason) => axum::response::Response::builder()
+                .status(405)
+                .body(Body::from(reason))
+                .expect("building body"),
+            Error::Conflict(reason) => axum::response::Response::builder()
+                .status(409)
+                .body(Body::from(reason))
+                .expect("building body"),
             Error::UnprocessableEntity(reason) => axum::response::Response::builder()
                 .status(422)
                 .body(Body::from(reason))
                 .expect("building body"),
+            Error::ServiceUnavailable(error) => {
+                log::warn!("Database contended: {:#}", error);
+                axum::response::Response::builder()
+                    .status(503)
+                    .header("Retry-After", "1")
+                    .body(Body::from("database busy, retry shortly"))
+                    .expect("building body")
+            }
             Error::ServerInternalError(error) => {
                 log::error!("Error: {:#}", error);
                 axum::response::Response::builder()
@@ -52,7 +78,11 @@ impl IntoResponse for Error {
 
 impl From<anyhow::Error> for Error {
     fn from(value: anyhow::Error) -> Self {
-        Self::ServerInternalError(value)
+        if crate::database::is_lock_contention(&value) {
+            Self::ServiceUnavailable(value)
+        } else {
+            Self::ServerInternalError(value)
+        }
     }
 }
 
@@ -0,0 +1,118 @@
+//! Fallback handling for requests that match no route.
+//!
+//! Client-side UI routes (e.g. `/devices/{uuid}`) are only known to the React app,
+//! so unmatched `GET`s outside the server's own prefixes get the SPA index. Anything
+//! under `/ui/`, `/cnc/` or `/api/` is a server endpoint and gets a real 404 instead
+//! of an HTML page, so a mistyped agent or PXE path fails loudly.
+//!
+//! Unmatched requests and wrong methods on known paths are logged and answered with
+//! a short plain-text body naming the method and path.
+
+use axum::{
+    http::{Method, Uri},
+    response::{IntoResponse, Response},
+};
+
+use super::{error::Error as HttpError, ui};
+
+/// Path prefixes served by the backend rather than the React app.
+const SERVER_PREFIXES: &[&str] = &["/ui", "/cnc", "/api", "/assets"];
+
+/// Whether `path` belongs to the React app's client-side routing.
+fn is_spa_path(path: &str) -> bool {
+    !SERVER_PREFIXES.iter().any(|prefix| {
+        path.strip_prefix(prefix)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+    })
+}
+
+/// Router-wide fallback: SPA index for UI routes, 404 for everything else.
+pub(super) async fn fallback(method: Method, uri: Uri) -> Response {
+    if method == Method::GET && is_spa_path(uri.path()) {
+        return ui::http_index().await.into_response();
+    }
+    log::warn!("No route for {} {}", method, uri.path());
+    HttpError::NotFound(format!("No route for {} {}", method, uri.path())).into_response()
+}
+
+/// Fallback for a known path requested with a method it does not serve.
+///
+/// axum still adds the `Allow` header listing the supported methods.
+pub(super) async fn method_not_allowed(method: Method, uri: Uri) -> Response {
+    log::warn!("Method {} not allowed for {}", method, uri.path());
+    HttpError::MethodNotAllowed(format!("Method {} not allowed for {}", method, uri.path()))
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        body::Body,
+        http::{Request, StatusCode},
+    };
+    use tower::ServiceExt;
+
+    use crate::{http::test_helpers::build_test_app, test_connection_factory};
+
+    #[test]
+    fn test_is_spa_path() {
+        assert!(is_spa_path("/devices/550e8400-e29b-41d4-a716-446655440000"));
+        assert!(is_spa_path("/roles/new"));
+        assert!(is_spa_path("/cncx"));
+        assert!(!is_spa_path("/cnc/ipxe"));
+        assert!(!is_spa_path("/api"));
+        assert!(!is_spa_path("/ui/devices"));
+    }
+
+    #[tokio::test]
+    async fn test_unknown_server_path_returns_404() {
+        let app = build_test_app(test_connection_factory!()).await;
+
+        for path in ["/cnc/does-not-exist", "/api/nope"] {
+            let req = Request::builder().uri(path).body(Body::empty()).unwrap();
+            let resp = app.router.clone().oneshot(req).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::NOT_FOUND, "{}", path);
+
+            let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            assert_eq!(body, format!("No route for GET {}", path).as_bytes());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_unknown_post_returns_404() {
+        let app = build_test_app(test_connection_factory!()).await;
+
+        let req = Request::builder()
+            .method(Method::POST)
+            .uri("/devices")
+            .body(Body::empty())
+            .unwrap();
+        let resp = app.router.oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_wrong_method_on_known_path_returns_405() {
+        let app = build_test_app(test_connection_factory!()).await;
+
+        let req = Request::builder()
+            .method(Method::POST)
+            .uri("/api/tftp/status")
+            .body(Body::empty())
+            .unwrap();
+        let resp = app.router.oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert!(resp.headers().contains_key("allow"));
+
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(
+            body,
+            "Method POST not allowed for /api/tftp/status".as_bytes()
+        );
+    }
+}
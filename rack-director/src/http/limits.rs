@@ -0,0 +1,133 @@
+//! Server-wide request limits applied to every HTTP route.
+//!
+//! Bounds how much body a handler will buffer and how long a request may take to
+//! produce a response, so a slow or oversized client cannot tie up the server.
+//! Routes that legitimately need more (e.g. OSM uploads) override the body limit
+//! with their own `DefaultBodyLimit` layer.
+
+use std::time::Duration;
+
+use axum::{
+    Router,
+    extract::{DefaultBodyLimit, Request, State},
+    http::StatusCode,
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+};
+
+/// Default maximum request body accepted by extractors, in bytes (2 MiB).
+pub const DEFAULT_BODY_LIMIT_BYTES: usize = 2 * 1024 * 1024;
+
+/// Default time a handler has to produce a response, in seconds.
+pub const DEFAULT_REQUEST_TIMEOUT_SECS: u64 = 60;
+
+/// Limits applied to every route by [`apply`].
+#[derive(Debug, Clone, Copy)]
+pub struct HttpLimits {
+    /// Maximum request body size in bytes; larger bodies get `413 Payload Too Large`.
+    pub body_limit_bytes: usize,
+    /// Time allowed to produce a response; slower requests get `408 Request Timeout`.
+    pub request_timeout: Duration,
+}
+
+impl Default for HttpLimits {
+    fn default() -> Self {
+        Self {
+            body_limit_bytes: DEFAULT_BODY_LIMIT_BYTES,
+            request_timeout: Duration::from_secs(DEFAULT_REQUEST_TIMEOUT_SECS),
+        }
+    }
+}
+
+/// Wrap `router` with the body-size limit and request timeout.
+///
+/// The timeout covers producing the response head only; streamed response bodies
+/// (boot images, OSM files) are not cut off.
+pub fn apply(router: Router, limits: HttpLimits) -> Router {
+    router
+        .layer(middleware::from_fn_with_state(
+            limits.request_timeout,
+            request_timeout,
+        ))
+        .layer(DefaultBodyLimit::max(limits.body_limit_bytes))
+}
+
+async fn request_timeout(State(timeout): State<Duration>, req: Request, next: Next) -> Response {
+    let path = req.uri().path().to_string();
+    match tokio::time::timeout(timeout, next.run(req)).await {
+        Ok(response) => response,
+        Err(_) => {
+            log::warn!("HTTP request to {path} timed out after {timeout:?}");
+            StatusCode::REQUEST_TIMEOUT.into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        Json,
+        body::Body,
+        routing::{get, post},
+    };
+    use tower::ServiceExt;
+
+    fn test_router(limits: HttpLimits) -> Router {
+        let router = Router::new()
+            .route(
+                "/echo",
+                post(|Json(body): Json<serde_json::Value>| async move { Json(body) }),
+            )
+            .route(
+                "/hang",
+                get(|| async {
+                    tokio::time::sleep(Duration::from_secs(3600)).await;
+                    "never"
+                }),
+            );
+        apply(router, limits)
+    }
+
+    fn json_request(size: usize) -> Request<Body> {
+        let body = serde_json::json!({ "data": "x".repeat(size) }).to_string();
+        axum::http::Request::builder()
+            .method("POST")
+            .uri("/echo")
+            .header("content-type", "application/json")
+            .body(Body::from(body))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_oversized_body_rejected_with_413() {
+        let app = test_router(HttpLimits {
+            body_limit_bytes: 1024,
+            ..HttpLimits::default()
+        });
+
+        let resp = app.clone().oneshot(json_request(100)).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let resp = app.oneshot(json_request(4096)).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[tokio::test]
+    async fn test_hung_handler_times_out_with_408() {
+        let app = test_router(HttpLimits {
+            request_timeout: Duration::from_millis(50),
+            ..HttpLimits::default()
+        });
+
+        let req = axum::http::Request::builder()
+            .uri("/hang")
+            .body(Body::empty())
+            .unwrap();
+        let resp = tokio::time::timeout(Duration::from_secs(5), app.oneshot(req))
+            .await
+            .expect("timeout layer should answer before the test deadline")
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::REQUEST_TIMEOUT);
+    }
+}
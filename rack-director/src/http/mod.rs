@@ -1,6 +1,9 @@
 mod api;
-mod cnc;
+mod audit;
+pub(crate) mod cnc;
 mod error;
+mod fallback;
+pub mod limits;
 mod ui;
 
 #[cfg(test)]
@@ -14,11 +17,15 @@ use anyhow::Result;
 use axum::Router;
 use tokio::task::JoinHandle;
 
+use crate::artifacts::ArtifactHashes;
 use crate::boot_files::BootFileProvider;
 use crate::database::ConnectionFactory;
 use crate::dhcp::DhcpControl;
 use crate::director::power::PowerConfig;
+use crate::plans::actions::BootUrlConfig;
 use crate::storage::ImageStore;
+use crate::tftp::TransferRegistry;
+use limits::HttpLimits;
 
 /// Shared application state for all HTTP handlers.
 ///
@@ -46,6 +53,31 @@ pub struct AppState {
     /// Passed to `Director::with_power_config` in handlers that perform OOB
     /// power operations.
     pub power_config: PowerConfig,
+    /// Lease duration in seconds applied to networks created without an
+    /// explicit `lease_duration`.
+    pub default_lease_duration: u32,
+    /// Registry of in-flight TFTP transfers, shared with the TFTP server.
+    pub tftp_transfers: TransferRegistry,
+    /// Base URL overrides for generated iPXE scripts.
+    pub boot_urls: BootUrlConfig,
+    /// SHA-256 digests of agent images, computed on first request.
+    pub artifact_hashes: Arc<ArtifactHashes>,
+}
+
+/// Assemble the complete application router without binding a listener.
+///
+/// Merges the ui, cnc and api routes, installs the 404 and 405 fallbacks and
+/// applies the request limits. Shared middleware belongs here so it covers
+/// every route.
+pub fn build_router(state: Arc<AppState>, limits: HttpLimits) -> Router {
+    let app = Router::new()
+        .merge(ui::routes(state.clone()))
+        .merge(cnc::routes(state.clone()))
+        .merge(api::routes(state))
+        // Applies only to routes already registered, so it must follow the merges.
+        .method_not_allowed_fallback(fallback::method_not_allowed)
+        .fallback(fallback::fallback);
+    limits::apply(app, limits)
 }
 
 pub struct StartResult {
@@ -53,6 +85,7 @@ pub struct StartResult {
     pub port: u16,
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn start<T: Into<SocketAddr>>(
     connection_factory: Arc<dyn ConnectionFactory>,
     image_store: Arc<ImageStore>,
@@ -63,6 +96,10 @@ pub async fn start<T: Into<SocketAddr>>(
     unprovisioned_sleep_secs: u64,
     bundled_osm_path: Option<PathBuf>,
     power_config: PowerConfig,
+    default_lease_duration: u32,
+    tftp_transfers: TransferRegistry,
+    boot_urls: BootUrlConfig,
+    limits: HttpLimits,
 ) -> Result<StartResult> {
     let state = Arc::new(AppState {
         connection_factory,
@@ -73,12 +110,13 @@ pub async fn start<T: Into<SocketAddr>>(
         unprovisioned_sleep_secs,
         bundled_osm_path,
         power_config,
+        default_lease_duration,
+        tftp_transfers,
+        boot_urls,
+        artifact_hashes: Arc::default(),
     });
 
-    let app = Router::new()
-        .merge(ui::routes(state.clone()))
-        .merge(cnc::routes(state.clone()))
-        .merge(api::routes(state));
+    let app = build_router(state, limits);
 
     // run our app with hyper, listening globally on port 3000
     let listener = tokio::net::TcpListener::bind(bind.into()).await?;
@@ -3,9 +3,41 @@
 //! This module provides common setup utilities used across multiple HTTP API
 //! test modules.  It is only compiled in test builds.
 
+use std::net::SocketAddr;
 use std::sync::Arc;
 
-use crate::{database, http::AppState};
+use axum::{Router, extract::connect_info::MockConnectInfo};
+
+use crate::{
+    database,
+    http::{AppState, build_router, limits::HttpLimits},
+};
+
+/// The complete HTTP application over a migrated test database.
+pub struct TestApp {
+    /// Full router (ui + cnc + api, with request limits), ready for `oneshot`.
+    pub router: Router,
+    pub state: Arc<AppState>,
+    /// Connection for test setup and assertions.
+    pub conn: database::Connection,
+}
+
+/// Build the complete app the way `http::start` does, minus the listener.
+///
+/// Runs migrations on `factory`, builds state with [`build_test_state`] and
+/// supplies a fixed peer address for handlers that read `ConnectInfo`.
+pub async fn build_test_app(factory: database::DatabaseConnectionFactory) -> TestApp {
+    let conn = database::run_migrations(&factory).await.unwrap();
+    let state = build_test_state(Arc::new(factory));
+    let router = build_router(state.clone(), HttpLimits::default()).layer(MockConnectInfo(
+        "127.0.0.1:1234".parse::<SocketAddr>().unwrap(),
+    ));
+    TestApp {
+        router,
+        state,
+        conn,
+    }
+}
 
 /// Build a minimal `AppState` suitable for HTTP handler tests.
 ///
@@ -36,5 +68,9 @@ pub fn build_test_state(conn_factory: Arc<dyn database::ConnectionFactory>) -> A
         unprovisioned_sleep_secs: 0,
         bundled_osm_path: None,
         power_config: crate::director::power::PowerConfig::default(),
+        default_lease_duration: crate::dhcp::store::DEFAULT_LEASE_DURATION,
+        tftp_transfers: crate::tftp::TransferRegistry::default(),
+        boot_urls: crate::plans::actions::BootUrlConfig::default(),
+        artifact_hashes: Default::default(),
     })
 }
@@ -17,7 +17,11 @@ use super::validation::{
 use crate::{
     device_warnings,
     director::{Architecture, Director},
-    http::{AppState, error::Error as HttpError},
+    http::{
+        AppState,
+        audit::{self, Actor},
+        error::Error as HttpError,
+    },
     lifecycle::{DeviceLifecycle, LifecycleTransition},
     plans::{Action, Plan},
     platforms::{AssignPlatformRequest, Platform},
@@ -222,6 +226,7 @@ struct DeviceResponse {
     first_seen_at: Option<String>,
     last_seen_at: Option<String>,
     hostname: Option<String>,
+    notes: Option<String>,
 }
 
 #[derive(Serialize)]
@@ -327,6 +332,7 @@ async fn get_all_devices(
                 first_seen_at: device.first_seen_at,
                 last_seen_at: device.last_seen_at,
                 hostname,
+                notes: device.notes,
             }
         })
         .collect();
@@ -374,11 +380,13 @@ async fn get_device_by_uuid(
         first_seen_at: device.first_seen_at,
         last_seen_at: device.last_seen_at,
         hostname,
+        notes: device.notes,
     }))
 }
 
 async fn update_device_attributes(
     State(state): State<Arc<AppState>>,
+    actor: Actor,
     Path(uuid): Path<Uuid>,
     extract::Json(payload): extract::Json<UpdateAttributesRequest>,
 ) -> Result<StatusCode, HttpError> {
@@ -427,9 +435,22 @@ async fn update_device_attributes(
         .map_err(HttpError::ServerInternalError)?;
     let director = Director::new(&conn);
 
-    // Update device attributes
+    // Update device attributes. BMC credentials are rejected above, so the
+    // submitted attributes are safe to keep in the audit log.
+    let after = audit::summary(&payload.attributes);
     match director.update_attributes(&uuid, payload.attributes).await {
-        Ok(_) => Ok(StatusCode::NO_CONTENT),
+        Ok(_) => {
+            audit::record(
+                &conn,
+                &actor,
+                "device.update_attributes",
+                &format!("device/{}", uuid),
+                None,
+                after,
+            )
+            .await;
+            Ok(StatusCode::NO_CONTENT)
+        }
         Err(e) => {
             log::error!("Failed to update device attributes for {}: {}", uuid, e);
             Err(HttpError::ServerInternalError(e))
@@ -456,6 +477,7 @@ async fn get_device_lifecycle(
 
 async fn start_lifecycle_transition(
     State(state): State<Arc<AppState>>,
+    actor: Actor,
     Path(uuid): Path<Uuid>,
     extract::Json(payload): extract::Json<StartTransitionRequest>,
 ) -> Result<Json<StartTransitionResponse>, (StatusCode, Json<ErrorResponse>)> {
@@ -485,12 +507,27 @@ async fn start_lifecycle_transition(
         )
     })?;
     let director = Director::with_power_config(&conn, state.power_config);
+    let before = director.get_device_lifecycle(&uuid).await.ok().flatten();
 
-    match director.start_lifecycle_transition(&uuid, to_state).await {
-        Ok(transition_id) => Ok(Json(StartTransitionResponse {
-            transition_id,
-            message: format!("Started lifecycle transition for device {}", uuid),
-        })),
+    match director
+        .start_lifecycle_transition(&uuid, to_state.clone())
+        .await
+    {
+        Ok(transition_id) => {
+            audit::record(
+                &conn,
+                &actor,
+                "device.lifecycle_transition",
+                &format!("device/{}", uuid),
+                audit::summary(&before),
+                audit::summary(&to_state),
+            )
+            .await;
+            Ok(Json(StartTransitionResponse {
+                transition_id,
+                message: format!("Started lifecycle transition for device {}", uuid),
+            }))
+        }
         Err(e) => Err((
             StatusCode::BAD_REQUEST,
             Json(ErrorResponse {
@@ -502,6 +539,7 @@ async fn start_lifecycle_transition(
 
 async fn cancel_lifecycle_transition(
     State(state): State<Arc<AppState>>,
+    actor: Actor,
     Path(uuid): Path<Uuid>,
 ) -> Result<Json<serde_json::Value>, (StatusCode, Json<ErrorResponse>)> {
     let conn = state.connection_factory.open().await.map_err(|_| {
@@ -515,7 +553,18 @@ async fn cancel_lifecycle_transition(
     let director = Director::new(&conn);
 
     match director.cancel_active_transition(&uuid).await {
-        Ok(()) => Ok(Json(serde_json::json!({}))),
+        Ok(()) => {
+            audit::record(
+                &conn,
+                &actor,
+                "device.cancel_transition",
+                &format!("device/{}", uuid),
+                None,
+                None,
+            )
+            .await;
+            Ok(Json(serde_json::json!({})))
+        }
         Err(e) => Err((
             StatusCode::BAD_REQUEST,
             Json(ErrorResponse {
@@ -593,6 +642,7 @@ async fn get_device_status(
 
 async fn create_pending_device(
     State(state): State<Arc<AppState>>,
+    actor: Actor,
     extract::Json(mut payload): extract::Json<CreatePendingDeviceRequest>,
 ) -> Result<(StatusCode, Json<PendingDeviceResponse>), HttpError> {
     // Normalize MAC address to lowercase for consistent storage and duplicate detection
@@ -608,6 +658,15 @@ async fn create_pending_device(
     let id = director
         .create_pending_device(&payload.mac_address, payload.network_id)
         .await?;
+    audit::record(
+        &conn,
+        &actor,
+        "pending_device.create",
+        &format!("pending_device/{}", id),
+        None,
+        audit::summary(&payload),
+    )
+    .await;
 
     Ok((
         StatusCode::CREATED,
@@ -693,16 +752,27 @@ async fn get_pending_devices(
 
 async fn delete_pending_device(
     State(state): State<Arc<AppState>>,
+    actor: Actor,
     Path(id): Path<i64>,
 ) -> Result<StatusCode, HttpError> {
     let conn = state.connection_factory.open().await?;
     let director = Director::new(&conn);
     director.delete_pending_device(id).await?;
+    audit::record(
+        &conn,
+        &actor,
+        "pending_device.delete",
+        &format!("pending_device/{}", id),
+        None,
+        None,
+    )
+    .await;
     Ok(StatusCode::NO_CONTENT)
 }
 
 async fn delete_device_by_uuid(
     State(state): State<Arc<AppState>>,
+    actor: Actor,
     Path(uuid): Path<Uuid>,
 ) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
     let conn = state.connection_factory.open().await.map_err(|_| {
@@ -714,8 +784,21 @@ async fn delete_device_by_uuid(
         )
     })?;
     let director = Director::new(&conn);
+    // Only the lifecycle is kept: the attributes may hold BMC credentials
+    let before = director.get_device_lifecycle(&uuid).await.ok().flatten();
     match director.delete_device(&uuid).await {
-        Ok(_) => Ok(StatusCode::NO_CONTENT),
+        Ok(_) => {
+            audit::record(
+                &conn,
+                &actor,
+                "device.delete",
+                &format!("device/{}", uuid),
+                audit::summary(&before),
+                None,
+            )
+            .await;
+            Ok(StatusCode::NO_CONTENT)
+        }
         Err(e) => {
             log::error!("Failed to delete device {}: {}", uuid, e);
             Err((
@@ -730,6 +813,7 @@ async fn delete_device_by_uuid(
 
 async fn post_device_plan(
     State(state): State<Arc<AppState>>,
+    actor: Actor,
     Path(uuid): Path<Uuid>,
     extract::Json(payload): extract::Json<DevicesPlanRequest>,
 ) -> Result<StatusCode, HttpError> {
@@ -738,6 +822,15 @@ async fn post_device_plan(
 
     let plan = Plan::new(uuid, payload.plan);
     director.create_plan(&plan).await?;
+    audit::record(
+        &conn,
+        &actor,
+        "device.plan",
+        &format!("device/{}", uuid),
+        None,
+        audit::summary(&plan.actions),
+    )
+    .await;
     Ok(StatusCode::NO_CONTENT)
 }
 
@@ -807,6 +900,10 @@ mod tests {
             unprovisioned_sleep_secs: 600,
             bundled_osm_path: None,
             power_config: crate::director::power::PowerConfig::default(),
+            default_lease_duration: crate::dhcp::store::DEFAULT_LEASE_DURATION,
+            tftp_transfers: crate::tftp::TransferRegistry::default(),
+            boot_urls: crate::plans::actions::BootUrlConfig::default(),
+            artifact_hashes: Default::default(),
         });
         (state, temp_dir, migration_conn)
     }
@@ -1016,6 +1113,16 @@ mod tests {
             },
             "Device should be deleted"
         );
+
+        let (action, target): (String, String) = test_db(&state)
+            .await
+            .query_one("SELECT action, target FROM audit_log", (), |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .await
+            .unwrap();
+        assert_eq!(action, "device.delete");
+        assert_eq!(target, format!("device/{}", test_uuid));
     }
 
     #[tokio::test]
@@ -2190,6 +2297,7 @@ mod tests {
 
 async fn assign_platform(
     State(state): State<Arc<AppState>>,
+    actor: Actor,
     Path(uuid): Path<Uuid>,
     Json(req): Json<AssignPlatformRequest>,
 ) -> Result<StatusCode, HttpError> {
@@ -2202,9 +2310,19 @@ async fn assign_platform(
     // Verify device exists
     director.get_device(&uuid).await?;
 
+    let before = director.get_device_platform_id(&uuid).await?;
     director
         .assign_platform_to_device(&uuid, req.platform_id)
         .await?;
+    audit::record(
+        &conn,
+        &actor,
+        "device.platform",
+        &format!("device/{}", uuid),
+        Some(serde_json::json!({ "platform_id": before })),
+        Some(serde_json::json!({ "platform_id": req.platform_id })),
+    )
+    .await;
 
     Ok(StatusCode::OK)
 }
@@ -2232,6 +2350,7 @@ async fn get_device_platform(
 
 async fn assign_role(
     State(state): State<Arc<AppState>>,
+    actor: Actor,
     Path(uuid): Path<Uuid>,
     Json(req): Json<AssignRoleRequest>,
 ) -> Result<StatusCode, HttpError> {
@@ -2244,7 +2363,17 @@ async fn assign_role(
     // Verify device exists
     director.get_device(&uuid).await?;
 
+    let before = director.get_device_role_id(&uuid).await?;
     director.assign_role_to_device(&uuid, req.role_id).await?;
+    audit::record(
+        &conn,
+        &actor,
+        "device.role",
+        &format!("device/{}", uuid),
+        Some(serde_json::json!({ "role_id": before })),
+        Some(serde_json::json!({ "role_id": req.role_id })),
+    )
+    .await;
 
     Ok(StatusCode::OK)
 }
@@ -2292,6 +2421,7 @@ async fn get_warnings(
 /// Returns `204 No Content` on success, `404` if the device or warning is not found.
 async fn delete_warning(
     State(state): State<Arc<AppState>>,
+    actor: Actor,
     Path((uuid, warning_id)): Path<(Uuid, i64)>,
 ) -> Result<StatusCode, HttpError> {
     let conn = state.connection_factory.open().await?;
@@ -2302,6 +2432,15 @@ async fn delete_warning(
 
     let deleted = device_warnings::delete_warning(&conn, warning_id, device_id).await?;
     if deleted {
+        audit::record(
+            &conn,
+            &actor,
+            "device.dismiss_warning",
+            &format!("device/{}", uuid),
+            Some(serde_json::json!({ "warning_id": warning_id })),
+            None,
+        )
+        .await;
         Ok(StatusCode::NO_CONTENT)
     } else {
         Err(HttpError::NotFound(format!(
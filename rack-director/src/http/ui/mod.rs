@@ -5,7 +5,7 @@ mod osm;
 mod platforms;
 mod power;
 mod roles;
-mod validation;
+pub(crate) mod validation;
 
 use std::{path::PathBuf, sync::Arc};
 
@@ -26,7 +26,6 @@ pub fn routes(state: Arc<AppState>) -> Router {
         // Static asset serving
         .route("/assets/{asset}", get(http_assets))
         .route("/", get(http_index))
-        .route("/{*wildcard}", get(http_index))
         .with_state(state.clone())
         // Merge all UI API routes
         .merge(devices::routes(state.clone()))
@@ -38,7 +37,10 @@ pub fn routes(state: Arc<AppState>) -> Router {
         .merge(roles::routes(state))
 }
 
-async fn http_index() -> Result<Html<Vec<u8>>, StatusCode> {
+/// Serve the React app's `index.html`.
+///
+/// Also used by the router-wide fallback for client-side routes.
+pub(super) async fn http_index() -> Result<Html<Vec<u8>>, StatusCode> {
     let path = PathBuf::from(DEFAULT_UI_PATH).join("index.html");
     match tokio::fs::read(path).await {
         Ok(data) => Ok(Html(data)),
@@ -1,4 +1,8 @@
-use super::super::{AppState, error::Error as HttpError};
+use super::super::{
+    AppState,
+    audit::{self, Actor},
+    error::Error as HttpError,
+};
 use super::validation::{validate_create_network_request, validate_update_network_request};
 use crate::dhcp::{self, DhcpNetwork, DhcpPool, Lease, StaticReservation};
 use crate::director::Device;
@@ -53,7 +57,8 @@ pub struct CreateNetworkRequest {
     pub subnet: String,
     pub gateway: String,
     pub dns_servers: Vec<String>,
-    pub lease_duration: u32,
+    /// Lease duration in seconds; falls back to `AppState::default_lease_duration`.
+    pub lease_duration: Option<u32>,
     pub relay_agent_address: Option<String>,
     #[serde(default)]
     pub enable_autodiscovery: bool,
@@ -68,6 +73,7 @@ pub struct UpdateNetworkRequest {
     pub lease_duration: Option<u32>,
     pub relay_agent_address: Option<String>,
     pub enable_autodiscovery: Option<bool>,
+    pub enabled: Option<bool>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -121,6 +127,7 @@ async fn get_network(
 /// Create a new DHCP network
 async fn create_network(
     State(state): State<Arc<AppState>>,
+    actor: Actor,
     Json(req): Json<CreateNetworkRequest>,
 ) -> Result<(StatusCode, Json<DhcpNetwork>), HttpError> {
     log::debug!("create network request: {:?}", req);
@@ -139,7 +146,7 @@ async fn create_network(
         &req.subnet,
         &req.gateway,
         &req.dns_servers,
-        req.lease_duration,
+        req.lease_duration.unwrap_or(state.default_lease_duration),
         req.relay_agent_address.as_deref(),
         req.enable_autodiscovery,
     )
@@ -153,12 +160,23 @@ async fn create_network(
         .network_created(network.id, network.subnet.clone())
         .await;
 
+    audit::record(
+        &conn,
+        &actor,
+        "network.create",
+        &format!("network/{}", network.id),
+        None,
+        audit::summary(&network),
+    )
+    .await;
+
     Ok((StatusCode::CREATED, Json(network)))
 }
 
 /// Update an existing network
 async fn update_network(
     State(state): State<Arc<AppState>>,
+    actor: Actor,
     Path(id): Path<i64>,
     Json(req): Json<UpdateNetworkRequest>,
 ) -> Result<Json<DhcpNetwork>, HttpError> {
@@ -172,6 +190,7 @@ async fn update_network(
         return Err(HttpError::ValidationError(errors));
     }
 
+    let before = crate::dhcp::store::get_network(&conn, id).await?;
     let network = crate::dhcp::store::update_network(
         &mut conn,
         id,
@@ -184,21 +203,43 @@ async fn update_network(
             .as_deref()
             .map(|opt| if opt.is_empty() { None } else { Some(opt) }),
         req.enable_autodiscovery,
+        req.enabled,
     )
     .await?;
 
+    audit::record(
+        &conn,
+        &actor,
+        "network.update",
+        &format!("network/{}", id),
+        audit::summary(&before),
+        audit::summary(&network),
+    )
+    .await;
+
     Ok(Json(network))
 }
 
 /// Delete a network
 async fn delete_network(
     State(state): State<Arc<AppState>>,
+    actor: Actor,
     Path(id): Path<i64>,
 ) -> Result<StatusCode, HttpError> {
     let conn = state.connection_factory.open().await?;
+    let before = crate::dhcp::store::get_network(&conn, id).await?;
     crate::dhcp::store::delete_network(&conn, id).await?;
     // Notify the DHCP socket manager to close the socket for this network.
     state.dhcp.network_deleted(id).await;
+    audit::record(
+        &conn,
+        &actor,
+        "network.delete",
+        &format!("network/{}", id),
+        audit::summary(&before),
+        None,
+    )
+    .await;
     Ok(StatusCode::NO_CONTENT)
 }
 
@@ -217,6 +258,7 @@ async fn list_pools(
 /// Create a new pool in a network
 async fn create_pool(
     State(state): State<Arc<AppState>>,
+    actor: Actor,
     Path(network_id): Path<i64>,
     Json(req): Json<CreatePoolRequest>,
 ) -> Result<(StatusCode, Json<DhcpPool>), HttpError> {
@@ -230,16 +272,28 @@ async fn create_pool(
     )
     .await?;
 
+    audit::record(
+        &conn,
+        &actor,
+        "pool.create",
+        &format!("pool/{}", pool.id),
+        None,
+        audit::summary(&pool),
+    )
+    .await;
+
     Ok((StatusCode::CREATED, Json(pool)))
 }
 
 /// Update an existing pool
 async fn update_pool(
     State(state): State<Arc<AppState>>,
+    actor: Actor,
     Path(id): Path<i64>,
     Json(req): Json<UpdatePoolRequest>,
 ) -> Result<Json<DhcpPool>, HttpError> {
     let mut conn = state.connection_factory.open().await?;
+    let before = crate::dhcp::store::get_pool(&conn, id).await?;
     let pool = crate::dhcp::store::update_pool(
         &mut conn,
         id,
@@ -249,16 +303,37 @@ async fn update_pool(
     )
     .await?;
 
+    audit::record(
+        &conn,
+        &actor,
+        "pool.update",
+        &format!("pool/{}", id),
+        audit::summary(&before),
+        audit::summary(&pool),
+    )
+    .await;
+
     Ok(Json(pool))
 }
 
 /// Delete a pool
 async fn delete_pool(
     State(state): State<Arc<AppState>>,
+    actor: Actor,
     Path(id): Path<i64>,
 ) -> Result<StatusCode, HttpError> {
     let conn = state.connection_factory.open().await?;
+    let before = crate::dhcp::store::get_pool(&conn, id).await.ok();
     crate::dhcp::store::delete_pool(&conn, id).await?;
+    audit::record(
+        &conn,
+        &actor,
+        "pool.delete",
+        &format!("pool/{}", id),
+        before.as_ref().and_then(audit::summary),
+        None,
+    )
+    .await;
     Ok(StatusCode::NO_CONTENT)
 }
 
@@ -277,6 +352,7 @@ async fn list_static_reservations(
 /// Create a new static reservation
 async fn create_static_reservation(
     State(state): State<Arc<AppState>>,
+    actor: Actor,
     Path(network_id): Path<i64>,
     Json(req): Json<CreateStaticReservationRequest>,
 ) -> Result<(StatusCode, Json<StaticReservation>), HttpError> {
@@ -302,16 +378,36 @@ async fn create_static_reservation(
     )
     .await?;
 
+    audit::record(
+        &conn,
+        &actor,
+        "reservation.create",
+        &format!("network/{}", network_id),
+        None,
+        audit::summary(&reservation),
+    )
+    .await;
+
     Ok((StatusCode::CREATED, Json(reservation)))
 }
 
 /// Delete a static reservation
 async fn delete_static_reservation(
     State(state): State<Arc<AppState>>,
+    actor: Actor,
     Path(id): Path<i64>,
 ) -> Result<StatusCode, HttpError> {
     let conn = state.connection_factory.open().await?;
     crate::dhcp::store::delete_static_reservation(&conn, id).await?;
+    audit::record(
+        &conn,
+        &actor,
+        "reservation.delete",
+        &format!("reservation/{}", id),
+        None,
+        None,
+    )
+    .await;
     Ok(StatusCode::NO_CONTENT)
 }
 
@@ -349,6 +445,7 @@ async fn list_leases_by_network(
 /// Convert a dynamic lease to a static reservation
 async fn make_lease_static(
     State(state): State<Arc<AppState>>,
+    actor: Actor,
     Path(lease_id): Path<i64>,
     Json(req): Json<MakeStaticRequest>,
 ) -> Result<(StatusCode, Json<StaticReservation>), HttpError> {
@@ -391,6 +488,16 @@ async fn make_lease_static(
     )
     .await?;
 
+    audit::record(
+        &conn,
+        &actor,
+        "reservation.create",
+        &format!("network/{}", network_id),
+        None,
+        audit::summary(&reservation),
+    )
+    .await;
+
     Ok((StatusCode::CREATED, Json(reservation)))
 }
 
@@ -460,6 +567,7 @@ mod tests {
             first_seen_at: None,
             last_seen_at: None,
             last_polled_at: None,
+            notes: None,
         }
     }
 
@@ -648,4 +756,196 @@ mod tests {
         let result = find_device_uuid_by_mac(&devices, "aa:bb:cc:dd:ee:01");
         assert_eq!(result, None);
     }
+
+    /// Build a router backed by a migrated test database whose server-level
+    /// default lease duration is `default_lease_duration`.
+    async fn setup_app_with_default_lease(
+        factory: crate::database::DatabaseConnectionFactory,
+        default_lease_duration: u32,
+    ) -> (Router, crate::database::Connection) {
+        let migration_conn = crate::database::run_migrations(&factory).await.unwrap();
+        let conn_factory: Arc<dyn crate::database::ConnectionFactory> = Arc::new(factory);
+        let mut state =
+            Arc::into_inner(crate::http::test_helpers::build_test_state(conn_factory)).unwrap();
+        state.default_lease_duration = default_lease_duration;
+        (routes(Arc::new(state)), migration_conn)
+    }
+
+    async fn post_network(app: Router, body: serde_json::Value) -> (StatusCode, serde_json::Value) {
+        use tower::util::ServiceExt;
+
+        let req = axum::http::Request::builder()
+            .method(axum::http::Method::POST)
+            .uri("/ui/dhcp/networks")
+            .header("content-type", "application/json")
+            .body(axum::body::Body::from(body.to_string()))
+            .unwrap();
+        let resp = app.oneshot(req).await.unwrap();
+        let status = resp.status();
+        let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_create_network_uses_server_default_lease_duration() {
+        let (app, conn) =
+            setup_app_with_default_lease(crate::test_connection_factory!(), 7200).await;
+
+        let (status, json) = post_network(
+            app,
+            serde_json::json!({
+                "name": "Defaulted",
+                "subnet": "10.0.0.0/24",
+                "gateway": "10.0.0.1",
+                "dns_servers": ["8.8.8.8"],
+            }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(json["lease_duration"], 7200);
+
+        // The stored duration is what DHCP offers and what lease_end is derived from.
+        let network_id = json["id"].as_i64().unwrap();
+        let network = dhcp::store::get_network(&conn, network_id).await.unwrap();
+        assert_eq!(network.lease_duration, 7200);
+
+        let ip = "10.0.0.100".parse().unwrap();
+        dhcp::store::create_or_update_lease_with_network(
+            &conn,
+            "aa:bb:cc:dd:ee:01",
+            &ip,
+            None,
+            dhcp::LeaseState::Active,
+            network.lease_duration,
+            network_id,
+        )
+        .await
+        .unwrap();
+        let lease = dhcp::store::get_lease_by_mac(&conn, "aa:bb:cc:dd:ee:01")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!((lease.lease_end - lease.lease_start).num_seconds(), 7200);
+    }
+
+    #[tokio::test]
+    async fn test_create_network_explicit_lease_duration_overrides_default() {
+        let (app, _conn) =
+            setup_app_with_default_lease(crate::test_connection_factory!(), 7200).await;
+
+        let (status, json) = post_network(
+            app,
+            serde_json::json!({
+                "name": "Explicit",
+                "subnet": "10.0.0.0/24",
+                "gateway": "10.0.0.1",
+                "dns_servers": ["8.8.8.8"],
+                "lease_duration": 600,
+            }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(json["lease_duration"], 600);
+    }
+
+    #[tokio::test]
+    async fn test_update_network_records_audit_entry() {
+        let app =
+            crate::http::test_helpers::build_test_app(crate::test_connection_factory!()).await;
+        let network = dhcp::store::create_network(
+            &app.conn,
+            "Original",
+            "10.0.0.0/24",
+            "10.0.0.1",
+            &[],
+            86400,
+            None,
+            false,
+        )
+        .await
+        .unwrap();
+
+        let req = axum::http::Request::builder()
+            .method("PUT")
+            .uri(format!("/ui/dhcp/networks/{}", network.id))
+            .header("content-type", "application/json")
+            .body(axum::body::Body::from(r#"{"name":"Renamed"}"#))
+            .unwrap();
+        let resp = tower::ServiceExt::oneshot(app.router, req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let (actor, action, target, before, after): (String, String, String, String, String) = app
+            .conn
+            .query_one(
+                "SELECT actor, action, target, before_summary, after_summary FROM audit_log",
+                (),
+                |row| {
+                    Ok((
+                        row.get(0)?,
+                        row.get(1)?,
+                        row.get(2)?,
+                        row.get(3)?,
+                        row.get(4)?,
+                    ))
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(actor, "127.0.0.1");
+        assert_eq!(action, "network.update");
+        assert_eq!(target, format!("network/{}", network.id));
+        let before: serde_json::Value = serde_json::from_str(&before).unwrap();
+        let after: serde_json::Value = serde_json::from_str(&after).unwrap();
+        assert_eq!(before["name"], "Original");
+        assert_eq!(after["name"], "Renamed");
+    }
+
+    #[tokio::test]
+    async fn test_update_pool_records_audit_entry() {
+        let app =
+            crate::http::test_helpers::build_test_app(crate::test_connection_factory!()).await;
+        let network = dhcp::store::create_network(
+            &app.conn,
+            "Net",
+            "10.0.0.0/24",
+            "10.0.0.1",
+            &[],
+            86400,
+            None,
+            false,
+        )
+        .await
+        .unwrap();
+        let pool =
+            dhcp::store::create_pool(&app.conn, network.id, "Pool", "10.0.0.100", "10.0.0.150")
+                .await
+                .unwrap();
+
+        let req = axum::http::Request::builder()
+            .method("PUT")
+            .uri(format!("/ui/dhcp/pools/{}", pool.id))
+            .header("content-type", "application/json")
+            .body(axum::body::Body::from(r#"{"range_end":"10.0.0.200"}"#))
+            .unwrap();
+        let resp = tower::ServiceExt::oneshot(app.router, req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let (action, target, before, after): (String, String, String, String) = app
+            .conn
+            .query_one(
+                "SELECT action, target, before_summary, after_summary FROM audit_log",
+                (),
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+            )
+            .await
+            .unwrap();
+        assert_eq!(action, "pool.update");
+        assert_eq!(target, format!("pool/{}", pool.id));
+        let before: serde_json::Value = serde_json::from_str(&before).unwrap();
+        let after: serde_json::Value = serde_json::from_str(&after).unwrap();
+        assert_eq!(before["range_end"], "10.0.0.150");
+        assert_eq!(after["range_end"], "10.0.0.200");
+    }
 }
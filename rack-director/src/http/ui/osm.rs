@@ -13,6 +13,7 @@ use futures::StreamExt;
 use serde::Serialize;
 
 use crate::http::AppState;
+use crate::http::audit::{self, Actor};
 use crate::http::error::Error as HttpError;
 use crate::osm::store;
 
@@ -97,6 +98,7 @@ async fn get_module(
 /// and 404 if no module with the given ID exists. Returns 204 on success.
 async fn delete_module(
     State(state): State<Arc<AppState>>,
+    actor: Actor,
     Path(id): Path<i64>,
 ) -> Result<StatusCode, HttpError> {
     let conn = state.connection_factory.open().await?;
@@ -120,6 +122,15 @@ async fn delete_module(
     }
 
     store::delete_module(&conn, id).await?;
+    audit::record(
+        &conn,
+        &actor,
+        "osm_module.delete",
+        &format!("osm_module/{}", id),
+        audit::summary(&module),
+        None,
+    )
+    .await;
     Ok(StatusCode::NO_CONTENT)
 }
 
@@ -138,6 +149,7 @@ async fn delete_module(
 /// contains no filename parameter.
 async fn upload_osm(
     State(state): State<Arc<AppState>>,
+    actor: Actor,
     headers: axum::http::HeaderMap,
     body: axum::body::Body,
 ) -> Result<(StatusCode, Json<store::OsmUpload>), HttpError> {
@@ -145,6 +157,15 @@ async fn upload_osm(
         .unwrap_or_else(|| "module.tar.zst".to_string());
     let conn = state.connection_factory.open().await?;
     let upload = store::create_upload(&conn, &filename, None).await?;
+    audit::record(
+        &conn,
+        &actor,
+        "osm_upload.create",
+        &format!("osm_upload/{}", upload.id),
+        None,
+        audit::summary(&upload),
+    )
+    .await;
 
     let nonce: u64 = rand::random();
     let temp_path =
@@ -309,12 +330,22 @@ async fn list_module_os(
 /// Returns 204 on success.
 async fn disable_os(
     State(state): State<Arc<AppState>>,
+    actor: Actor,
     Path(id): Path<i64>,
 ) -> Result<StatusCode, HttpError> {
     let conn = state.connection_factory.open().await?;
     store::set_os_disabled(&conn, id, true)
         .await
         .map_err(|_| HttpError::NotFound(format!("OSM operating system {id} not found")))?;
+    audit::record(
+        &conn,
+        &actor,
+        "osm_os.disable",
+        &format!("osm_os/{}", id),
+        None,
+        None,
+    )
+    .await;
     Ok(StatusCode::NO_CONTENT)
 }
 
@@ -323,12 +354,22 @@ async fn disable_os(
 /// Returns 204 on success.
 async fn enable_os(
     State(state): State<Arc<AppState>>,
+    actor: Actor,
     Path(id): Path<i64>,
 ) -> Result<StatusCode, HttpError> {
     let conn = state.connection_factory.open().await?;
     store::set_os_disabled(&conn, id, false)
         .await
         .map_err(|_| HttpError::NotFound(format!("OSM operating system {id} not found")))?;
+    audit::record(
+        &conn,
+        &actor,
+        "osm_os.enable",
+        &format!("osm_os/{}", id),
+        None,
+        None,
+    )
+    .await;
     Ok(StatusCode::NO_CONTENT)
 }
 
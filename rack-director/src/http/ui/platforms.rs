@@ -1,4 +1,8 @@
-use super::super::{AppState, error::Error as HttpError};
+use super::super::{
+    AppState,
+    audit::{self, Actor},
+    error::Error as HttpError,
+};
 use super::validation::*;
 use crate::platforms::*;
 use axum::{
@@ -158,6 +162,7 @@ fn validate_platform_attributes(attrs: &PlatformAttributes, errors: &mut Validat
 // Create a new platform
 async fn create_platform(
     State(state): State<Arc<AppState>>,
+    actor: Actor,
     Json(req): Json<CreatePlatformRequest>,
 ) -> Result<(StatusCode, Json<Platform>), HttpError> {
     if let Err(errors) = validate_create_platform(&req) {
@@ -174,6 +179,16 @@ async fn create_platform(
     )
     .await?;
 
+    audit::record(
+        &conn,
+        &actor,
+        "platform.create",
+        &format!("platform/{}", platform.id),
+        None,
+        audit::summary(&platform),
+    )
+    .await;
+
     Ok((StatusCode::CREATED, Json(platform)))
 }
 
@@ -199,6 +214,7 @@ async fn get_platform(
 // Update a platform
 async fn update_platform(
     State(state): State<Arc<AppState>>,
+    actor: Actor,
     Path(id): Path<i64>,
     Json(req): Json<UpdatePlatformRequest>,
 ) -> Result<Json<Platform>, HttpError> {
@@ -207,6 +223,7 @@ async fn update_platform(
     }
 
     let conn = state.connection_factory.open().await?;
+    let before = crate::platforms::store::get(&conn, id).await?;
     let platform = crate::platforms::store::update(
         &conn,
         id,
@@ -217,16 +234,37 @@ async fn update_platform(
     )
     .await?;
 
+    audit::record(
+        &conn,
+        &actor,
+        "platform.update",
+        &format!("platform/{}", id),
+        audit::summary(&before),
+        audit::summary(&platform),
+    )
+    .await;
+
     Ok(Json(platform))
 }
 
 // Delete a platform
 async fn delete_platform(
     State(state): State<Arc<AppState>>,
+    actor: Actor,
     Path(id): Path<i64>,
 ) -> Result<StatusCode, HttpError> {
     let conn = state.connection_factory.open().await?;
+    let before = crate::platforms::store::get(&conn, id).await.ok();
     crate::platforms::store::delete(&conn, id).await?;
+    audit::record(
+        &conn,
+        &actor,
+        "platform.delete",
+        &format!("platform/{}", id),
+        before.as_ref().and_then(audit::summary),
+        None,
+    )
+    .await;
     Ok(StatusCode::NO_CONTENT)
 }
 
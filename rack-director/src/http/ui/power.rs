@@ -13,7 +13,10 @@ use uuid::Uuid;
 use crate::{
     director::power::PowerState,
     director::{Director, PowerAction},
-    http::AppState,
+    http::{
+        AppState,
+        audit::{self, Actor},
+    },
 };
 
 use super::devices::ErrorResponse;
@@ -113,6 +116,7 @@ async fn get_device_power(
 /// - `502 Bad Gateway` – BMC driver returned an error.
 async fn post_device_power(
     State(state): State<Arc<AppState>>,
+    actor: Actor,
     Path(uuid): Path<Uuid>,
     extract::Json(payload): extract::Json<PowerActionRequest>,
 ) -> Result<Json<serde_json::Value>, (StatusCode, Json<ErrorResponse>)> {
@@ -145,9 +149,20 @@ async fn post_device_power(
     }
 
     match director.power_action(&uuid, payload.action).await {
-        Ok(true) => Ok(Json(serde_json::json!({
-            "message": format!("Power action issued for device {}", uuid)
-        }))),
+        Ok(true) => {
+            audit::record(
+                &conn,
+                &actor,
+                "device.power",
+                &format!("device/{}", uuid),
+                None,
+                Some(serde_json::json!({ "action": payload.action })),
+            )
+            .await;
+            Ok(Json(serde_json::json!({
+                "message": format!("Power action issued for device {}", uuid)
+            })))
+        }
         Ok(false) => Err((
             StatusCode::CONFLICT,
             Json(ErrorResponse {
@@ -228,6 +243,10 @@ mod tests {
             unprovisioned_sleep_secs: 600,
             bundled_osm_path: None,
             power_config: crate::director::power::PowerConfig::default(),
+            default_lease_duration: crate::dhcp::store::DEFAULT_LEASE_DURATION,
+            tftp_transfers: crate::tftp::TransferRegistry::default(),
+            boot_urls: crate::plans::actions::BootUrlConfig::default(),
+            artifact_hashes: Default::default(),
         });
         (state, temp_dir, migration_conn)
     }
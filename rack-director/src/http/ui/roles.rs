@@ -1,4 +1,8 @@
-use super::super::{AppState, error::Error as HttpError};
+use super::super::{
+    AppState,
+    audit::{self, Actor},
+    error::Error as HttpError,
+};
 use crate::roles::*;
 use axum::{
     Json, Router,
@@ -22,6 +26,7 @@ pub fn routes(state: Arc<AppState>) -> Router {
 // Create a new role
 async fn create_role(
     State(state): State<Arc<AppState>>,
+    actor: Actor,
     Json(req): Json<CreateRoleRequest>,
 ) -> Result<(StatusCode, Json<Role>), HttpError> {
     // Validate the disk layout before persisting.
@@ -59,6 +64,16 @@ async fn create_role(
     )
     .await?;
 
+    audit::record(
+        &conn,
+        &actor,
+        "role.create",
+        &format!("role/{}", role.id),
+        None,
+        audit::summary(&role),
+    )
+    .await;
+
     Ok((StatusCode::CREATED, Json(role)))
 }
 
@@ -83,6 +98,7 @@ async fn get_role(
 #[axum::debug_handler]
 async fn update_role(
     State(state): State<Arc<AppState>>,
+    actor: Actor,
     Path(id): Path<i64>,
     Json(req): Json<UpdateRoleRequest>,
 ) -> Result<Json<Role>, HttpError> {
@@ -121,6 +137,7 @@ async fn update_role(
         check_platform_compatibility(&conn, id, new_layout).await?;
     }
 
+    let before = crate::roles::store::get(&conn, id).await?;
     let role = crate::roles::store::update(
         &conn,
         id,
@@ -140,6 +157,16 @@ async fn update_role(
     )
     .await?;
 
+    audit::record(
+        &conn,
+        &actor,
+        "role.update",
+        &format!("role/{}", id),
+        audit::summary(&before),
+        audit::summary(&role),
+    )
+    .await;
+
     Ok(Json(role))
 }
 
@@ -193,10 +220,21 @@ async fn check_platform_compatibility(
 // Delete a role
 async fn delete_role(
     State(state): State<Arc<AppState>>,
+    actor: Actor,
     Path(id): Path<i64>,
 ) -> Result<StatusCode, HttpError> {
     let conn = state.connection_factory.open().await?;
+    let before = crate::roles::store::get(&conn, id).await.ok();
     crate::roles::store::delete(&conn, id).await?;
+    audit::record(
+        &conn,
+        &actor,
+        "role.delete",
+        &format!("role/{}", id),
+        before.as_ref().and_then(audit::summary),
+        None,
+    )
+    .await;
     Ok(StatusCode::NO_CONTENT)
 }
 
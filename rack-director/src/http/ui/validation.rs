@@ -1,7 +1,4 @@
-use common::Ipv4Subnet;
 use std::collections::HashMap;
-use std::net::Ipv4Addr;
-use std::str::FromStr;
 
 // ============================================================================
 // GENERIC VALIDATORS - Reusable across all endpoints
@@ -28,48 +25,6 @@ pub fn validate_string_length(value: &str, max_len: usize, field_name: &str) ->
     }
 }
 
-/// Validate that a string is a valid IPv4 address
-pub fn validate_ipv4_address(ip: &str) -> Option<String> {
-    match Ipv4Addr::from_str(ip) {
-        Ok(_) => None,
-        Err(_) => Some("Must be a valid IPv4 address".to_string()),
-    }
-}
-
-/// Validate that a string is a valid CIDR subnet
-pub fn validate_cidr_subnet(subnet: &str) -> Result<Ipv4Subnet, String> {
-    Ipv4Subnet::from_str(subnet).map_err(|e| format!("Invalid CIDR notation: {}", e))
-}
-
-/// Validate that an IP address is within a subnet
-pub fn validate_ip_in_subnet(ip: &str, subnet: &Ipv4Subnet) -> Option<String> {
-    match Ipv4Addr::from_str(ip) {
-        Ok(ip_addr) => {
-            if subnet.ip_in_range(ip_addr) {
-                None
-            } else {
-                Some("IP address must be within the subnet range".to_string())
-            }
-        }
-        Err(_) => Some("Must be a valid IPv4 address".to_string()),
-    }
-}
-
-/// Validate a list of IPv4 addresses
-pub fn validate_ipv4_list(ips: &[String], field_name: &str, min_count: usize) -> Option<String> {
-    if ips.len() < min_count {
-        return Some(format!("At least {} {} required", min_count, field_name));
-    }
-
-    for ip in ips {
-        if Ipv4Addr::from_str(ip).is_err() {
-            return Some(format!("'{}' is not a valid IPv4 address", ip));
-        }
-    }
-
-    None
-}
-
 /// Validate MAC address format (`XX:XX:XX:XX:XX:XX` where X is a hex digit).
 ///
 /// Returns `Some(error_message)` when the format is invalid, `None` when valid.
@@ -88,17 +43,6 @@ pub fn validate_mac_address(mac: &str) -> Option<String> {
     }
 }
 
-/// Validate numeric range
-pub fn validate_u32_range(value: u32, min: u32, max: u32, field_name: &str) -> Option<String> {
-    if value < min {
-        Some(format!("{} must be at least {}", field_name, min))
-    } else if value > max {
-        Some(format!("{} must be less than {}", field_name, max))
-    } else {
-        None
-    }
-}
-
 /// Validate hostname according to RFC 1123
 /// - Required field (non-empty)
 /// - Max length 253 characters
@@ -183,10 +127,20 @@ impl ValidationErrors {
 // NETWORK-SPECIFIC VALIDATORS
 // ============================================================================
 
+use crate::dhcp::subnet_config::SubnetConfig;
 use crate::{database::Connection, dhcp};
 
 use super::networks::{CreateNetworkRequest, UpdateNetworkRequest};
 
+/// Record each problem with a network's addressing against its field.
+fn add_subnet_config_errors(errors: &mut ValidationErrors, config: &SubnetConfig) {
+    if let Err(e) = config.validate() {
+        for error in e.0 {
+            errors.add_error(error.field(), error.message());
+        }
+    }
+}
+
 /// Validate create network request using the generic validators
 pub async fn validate_create_network_request(
     conn: &Connection,
@@ -215,37 +169,21 @@ pub async fn validate_create_network_request(
         }
     }
 
-    // Validate subnet and gateway together
-    match validate_cidr_subnet(&req.subnet) {
-        Err(err) => {
-            errors.add_if_err("subnet", Some(err));
-        }
-        Ok(subnet) => {
-            // Only validate gateway in subnet if subnet is valid
-            errors.add_if_err("gateway", validate_ipv4_address(&req.gateway));
-            errors.add_if_err("gateway", validate_ip_in_subnet(&req.gateway, &subnet));
-        }
-    }
-
-    // Validate DNS servers using generic list validator
-    errors.add_if_err(
-        "dns_servers",
-        validate_ipv4_list(&req.dns_servers, "DNS server", 1),
+    // Validate addressing with the rules shared by every network create path
+    add_subnet_config_errors(
+        &mut errors,
+        &SubnetConfig {
+            subnet: &req.subnet,
+            gateway: &req.gateway,
+            dns_servers: &req.dns_servers,
+            lease_duration: req.lease_duration,
+            relay_agent_address: req.relay_agent_address.as_deref(),
+        },
     );
 
-    // Validate lease duration using generic range validator
-    const ONE_YEAR_SECONDS: u32 = 31536000;
-    errors.add_if_err(
-        "lease_duration",
-        validate_u32_range(req.lease_duration, 1, ONE_YEAR_SECONDS, "Lease duration"),
-    );
-
-    // Validate relay agent if present
-    if let Some(ref relay) = req.relay_agent_address {
-        // Validate format if not empty
-        if !relay.is_empty() {
-            errors.add_if_err("relay_agent_address", validate_ipv4_address(relay));
-        }
+    // Networks created from the UI must hand out at least one resolver
+    if req.dns_servers.is_empty() {
+        errors.add_error("dns_servers", "At least 1 DNS server required".to_string());
     }
 
     // Check for duplicate relay agent address
@@ -308,55 +246,39 @@ pub async fn validate_update_network_request(
         }
     }
 
-    // Validate subnet and gateway together if either is provided
-    if req.subnet.is_some() || req.gateway.is_some() {
-        // Get the current network to fill in missing values
-        let current_network = match dhcp::store::get_network(conn, network_id).await {
-            Ok(net) => net,
+    // Validate the addressing as it will be after the update
+    let addressing_changed = req.subnet.is_some()
+        || req.gateway.is_some()
+        || req.dns_servers.is_some()
+        || req.lease_duration.is_some()
+        || req.relay_agent_address.is_some();
+    if addressing_changed {
+        match dhcp::store::get_network(conn, network_id).await {
+            Ok(current) => add_subnet_config_errors(
+                &mut errors,
+                &SubnetConfig {
+                    subnet: req.subnet.as_deref().unwrap_or(&current.subnet),
+                    gateway: req.gateway.as_deref().unwrap_or(&current.gateway),
+                    dns_servers: req.dns_servers.as_deref().unwrap_or(&current.dns_servers),
+                    lease_duration: req.lease_duration,
+                    relay_agent_address: req
+                        .relay_agent_address
+                        .as_deref()
+                        .or(current.relay_agent_address.as_deref()),
+                },
+            ),
             Err(e) => {
                 log::warn!("Failed to fetch current network for validation: {}", e);
-                return errors.into_result();
-            }
-        };
-
-        let subnet_str = req.subnet.as_deref().unwrap_or(&current_network.subnet);
-        let gateway_str = req.gateway.as_deref().unwrap_or(&current_network.gateway);
-
-        match validate_cidr_subnet(subnet_str) {
-            Err(err) => {
-                errors.add_if_err("subnet", Some(err));
-            }
-            Ok(subnet) => {
-                errors.add_if_err("gateway", validate_ipv4_address(gateway_str));
-                errors.add_if_err("gateway", validate_ip_in_subnet(gateway_str, &subnet));
             }
         }
     }
 
-    // Validate DNS servers if provided
-    if let Some(ref dns_servers) = req.dns_servers {
-        errors.add_if_err(
-            "dns_servers",
-            validate_ipv4_list(dns_servers, "DNS server", 1),
-        );
-    }
-
-    // Validate lease duration if provided
-    if let Some(lease_duration) = req.lease_duration {
-        const ONE_YEAR_SECONDS: u32 = 31536000;
-        errors.add_if_err(
-            "lease_duration",
-            validate_u32_range(lease_duration, 1, ONE_YEAR_SECONDS, "Lease duration"),
-        );
+    if req.dns_servers.as_ref().is_some_and(|dns| dns.is_empty()) {
+        errors.add_error("dns_servers", "At least 1 DNS server required".to_string());
     }
 
-    // Validate relay agent if provided
+    // Check for a duplicate relay agent address if one is provided
     if let Some(relay) = &req.relay_agent_address {
-        // Validate format if not empty
-        if !relay.is_empty() {
-            errors.add_if_err("relay_agent_address", validate_ipv4_address(relay));
-        }
-
         // Check for duplicate relay agent address (excluding current network)
         let relay_for_check = if relay.is_empty() {
             None
@@ -423,33 +345,6 @@ mod tests {
         assert!(validate_string_length(&long_string, 10, "Field").is_some());
     }
 
-    #[test]
-    fn test_validate_ipv4_address() {
-        assert!(validate_ipv4_address("192.168.1.1").is_none());
-        assert!(validate_ipv4_address("invalid").is_some());
-    }
-
-    #[test]
-    fn test_validate_cidr_subnet() {
-        assert!(validate_cidr_subnet("192.168.1.0/24").is_ok());
-        assert!(validate_cidr_subnet("invalid").is_err());
-        assert!(validate_cidr_subnet("192.168.1.0/33").is_err());
-    }
-
-    #[test]
-    fn test_validate_ipv4_list() {
-        assert!(validate_ipv4_list(&["8.8.8.8".to_string()], "DNS", 1).is_none());
-        assert!(validate_ipv4_list(&[], "DNS", 1).is_some());
-        assert!(validate_ipv4_list(&["invalid".to_string()], "DNS", 1).is_some());
-    }
-
-    #[test]
-    fn test_validate_u32_range() {
-        assert!(validate_u32_range(50, 1, 100, "Value").is_none());
-        assert!(validate_u32_range(0, 1, 100, "Value").is_some());
-        assert!(validate_u32_range(101, 1, 100, "Value").is_some());
-    }
-
     #[test]
     fn test_validation_errors_builder() {
         let mut errors = ValidationErrors::new();
@@ -473,7 +368,7 @@ mod tests {
             subnet: "192.168.1.0/24".to_string(),
             gateway: "192.168.1.1".to_string(),
             dns_servers: vec!["8.8.8.8".to_string()],
-            lease_duration: 86400,
+            lease_duration: Some(86400),
             relay_agent_address: Some("10.0.0.2".to_string()),
             enable_autodiscovery: false,
         };
@@ -489,7 +384,7 @@ mod tests {
             subnet: "192.168.1.0/24".to_string(),
             gateway: "192.168.1.1".to_string(),
             dns_servers: vec!["8.8.8.8".to_string()],
-            lease_duration: 86400,
+            lease_duration: Some(86400),
             relay_agent_address: Some("10.0.0.2".to_string()),
             enable_autodiscovery: false,
         };
@@ -524,7 +419,7 @@ mod tests {
             subnet: "192.168.2.0/24".to_string(),
             gateway: "192.168.2.1".to_string(),
             dns_servers: vec!["8.8.8.8".to_string()],
-            lease_duration: 86400,
+            lease_duration: Some(86400),
             relay_agent_address: Some("10.0.0.3".to_string()),
             enable_autodiscovery: false,
         };
@@ -560,7 +455,7 @@ mod tests {
             subnet: "192.168.2.0/24".to_string(),
             gateway: "192.168.2.1".to_string(),
             dns_servers: vec!["8.8.8.8".to_string()],
-            lease_duration: 86400,
+            lease_duration: Some(86400),
             relay_agent_address: Some("10.0.0.2".to_string()),
             enable_autodiscovery: false,
         };
@@ -601,7 +496,7 @@ mod tests {
             subnet: "192.168.2.0/24".to_string(),
             gateway: "192.168.2.1".to_string(),
             dns_servers: vec!["8.8.8.8".to_string()],
-            lease_duration: 86400,
+            lease_duration: Some(86400),
             relay_agent_address: None,
             enable_autodiscovery: false,
         };
@@ -626,7 +521,7 @@ mod tests {
             subnet: "invalid".to_string(),
             gateway: "192.168.1.1".to_string(),
             dns_servers: vec!["8.8.8.8".to_string()],
-            lease_duration: 86400,
+            lease_duration: Some(86400),
             relay_agent_address: Some("10.0.0.2".to_string()),
             enable_autodiscovery: false,
         };
@@ -645,7 +540,7 @@ mod tests {
             subnet: "192.168.1.0/24".to_string(),
             gateway: "10.0.0.1".to_string(),
             dns_servers: vec!["8.8.8.8".to_string()],
-            lease_duration: 86400,
+            lease_duration: Some(86400),
             relay_agent_address: Some("10.0.0.2".to_string()),
             enable_autodiscovery: false,
         };
@@ -664,7 +559,7 @@ mod tests {
             subnet: "192.168.1.0/24".to_string(),
             gateway: "192.168.1.1".to_string(),
             dns_servers: vec![],
-            lease_duration: 86400,
+            lease_duration: Some(86400),
             relay_agent_address: Some("10.0.0.2".to_string()),
             enable_autodiscovery: false,
         };
@@ -683,7 +578,7 @@ mod tests {
             subnet: "192.168.1.0/24".to_string(),
             gateway: "192.168.1.1".to_string(),
             dns_servers: vec!["8.8.8.8".to_string()],
-            lease_duration: 0,
+            lease_duration: Some(0),
             relay_agent_address: Some("10.0.0.2".to_string()),
             enable_autodiscovery: false,
         };
@@ -694,6 +589,22 @@ mod tests {
         assert!(errors.contains_key("lease_duration"));
     }
 
+    #[tokio::test]
+    async fn test_validate_create_network_request_omitted_lease_duration() {
+        let conn = create_test_store(Arc::new(test_connection_factory!())).await;
+        let req = CreateNetworkRequest {
+            name: "Test".to_string(),
+            subnet: "192.168.1.0/24".to_string(),
+            gateway: "192.168.1.1".to_string(),
+            dns_servers: vec!["8.8.8.8".to_string()],
+            lease_duration: None,
+            relay_agent_address: Some("10.0.0.2".to_string()),
+            enable_autodiscovery: false,
+        };
+
+        assert!(validate_create_network_request(&conn, &req).await.is_ok());
+    }
+
     #[tokio::test]
     async fn test_validate_create_network_request_invalid_relay() {
         let conn = create_test_store(Arc::new(test_connection_factory!())).await;
@@ -702,7 +613,7 @@ mod tests {
             subnet: "192.168.1.0/24".to_string(),
             gateway: "192.168.1.1".to_string(),
             dns_servers: vec!["8.8.8.8".to_string()],
-            lease_duration: 86400,
+            lease_duration: Some(86400),
             relay_agent_address: Some("invalid".to_string()),
             enable_autodiscovery: false,
         };
@@ -721,7 +632,7 @@ mod tests {
             subnet: "invalid".to_string(),
             gateway: "10.0.0.1".to_string(),
             dns_servers: vec![],
-            lease_duration: 0,
+            lease_duration: Some(0),
             relay_agent_address: None,
             enable_autodiscovery: false,
         };
@@ -763,6 +674,7 @@ mod tests {
             lease_duration: Some(7200),
             relay_agent_address: None,
             enable_autodiscovery: None,
+            enabled: None,
         };
 
         assert!(
@@ -812,6 +724,7 @@ mod tests {
             lease_duration: None,
             relay_agent_address: None,
             enable_autodiscovery: None,
+            enabled: None,
         };
 
         let result = validate_update_network_request(&conn, network1.id, &req).await;
@@ -851,6 +764,7 @@ mod tests {
             lease_duration: None,
             relay_agent_address: None,
             enable_autodiscovery: None,
+            enabled: None,
         };
 
         assert!(
@@ -900,6 +814,7 @@ mod tests {
             lease_duration: None,
             relay_agent_address: Some("10.0.0.3".to_string()),
             enable_autodiscovery: None,
+            enabled: None,
         };
 
         let result = validate_update_network_request(&conn, network1.id, &req).await;
@@ -935,6 +850,7 @@ mod tests {
             lease_duration: None,
             relay_agent_address: Some("10.0.0.2".to_string()),
             enable_autodiscovery: None,
+            enabled: None,
         };
 
         assert!(
@@ -971,6 +887,7 @@ mod tests {
             lease_duration: None,
             relay_agent_address: None,
             enable_autodiscovery: None,
+            enabled: None,
         };
 
         let result = validate_update_network_request(&conn, network.id, &req).await;
@@ -1006,6 +923,7 @@ mod tests {
             lease_duration: None,
             relay_agent_address: None,
             enable_autodiscovery: None,
+            enabled: None,
         };
 
         let result = validate_update_network_request(&conn, network.id, &req).await;
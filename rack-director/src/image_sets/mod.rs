@@ -0,0 +1,56 @@
+//! Named agent image sets for staged rollouts.
+//!
+//! An image set is a kernel, ramdisk and extra cmdline that replaces the bundled
+//! agent images when a device netboots into the agent. A device may be pinned to a
+//! set; unpinned devices use the fleet default set, and with no default the bundled
+//! agent images are used unchanged.
+
+mod store;
+
+use anyhow::Result;
+
+pub use store::{
+    ImageSet, create_image_set, get_image_set_by_name, resolve_image_set, set_default_image_set,
+    set_device_image_set,
+};
+
+/// Check that an image set's files and cmdline are safe to put on an iPXE `kernel`
+/// or `initrd` line.
+///
+/// Paths may not contain whitespace or control characters, which would add
+/// arguments or lines to the script; the cmdline may have spaces but no control
+/// characters.
+pub fn validate_boot_fields(kernel: &str, ramdisk: &str, cmdline: &str) -> Result<()> {
+    for (field, path) in [("kernel", kernel), ("ramdisk", ramdisk)] {
+        if path.is_empty() || path.chars().any(|c| c.is_whitespace() || c.is_control()) {
+            anyhow::bail!("invalid {field} path {path:?}");
+        }
+    }
+    if cmdline.chars().any(char::is_control) {
+        anyhow::bail!("invalid cmdline {cmdline:?}");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_boot_fields() {
+        assert!(validate_boot_fields("agent-images/vmlinuz", "agent-images/initrd", "a b").is_ok());
+        assert!(validate_boot_fields("agent-images/vmlinuz", "agent-images/initrd", "").is_ok());
+        for (kernel, ramdisk, cmdline) in [
+            ("vmlinuz\nshell", "initrd", ""),
+            ("vmlinuz console=ttyS0", "initrd", ""),
+            ("vmlinuz", "initrd\tx", ""),
+            ("vmlinuz", "", ""),
+            ("vmlinuz", "initrd", "quiet\nshell"),
+        ] {
+            assert!(
+                validate_boot_fields(kernel, ramdisk, cmdline).is_err(),
+                "{kernel:?} {ramdisk:?} {cmdline:?}"
+            );
+        }
+    }
+}
@@ -0,0 +1,231 @@
+//! Database access for image sets.
+
+use anyhow::{Result, anyhow};
+use chrono::Utc;
+use rusqlite::OptionalExtension;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::database::{Connection, FromRow, to_db_time};
+
+/// A named kernel/ramdisk pair used in place of the bundled agent images.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ImageSet {
+    pub id: i64,
+    pub name: String,
+    /// Kernel path relative to `/cnc/` (e.g. `agent-images/vmlinuz-v2`).
+    pub kernel: String,
+    /// Ramdisk path relative to `/cnc/`.
+    pub ramdisk: String,
+    /// Extra kernel arguments appended to the agent cmdline.
+    pub cmdline: String,
+    /// Whether this set applies to devices without a pinned set.
+    pub is_default: bool,
+}
+
+impl FromRow for ImageSet {
+    fn from_row(row: &rusqlite::Row) -> rusqlite::Result<Self> {
+        Ok(ImageSet {
+            id: row.get("id")?,
+            name: row.get("name")?,
+            kernel: row.get("kernel")?,
+            ramdisk: row.get("ramdisk")?,
+            cmdline: row.get("cmdline")?,
+            is_default: row.get("is_default")?,
+        })
+    }
+}
+
+const IMAGE_SET_COLUMNS: &str = "id, name, kernel, ramdisk, cmdline, is_default";
+
+/// Create a new, non-default image set.
+pub async fn create_image_set(
+    conn: &Connection,
+    name: &str,
+    kernel: &str,
+    ramdisk: &str,
+    cmdline: &str,
+) -> Result<ImageSet> {
+    conn.execute(
+        "INSERT INTO image_sets (name, kernel, ramdisk, cmdline, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        (
+            name.to_string(),
+            kernel.to_string(),
+            ramdisk.to_string(),
+            cmdline.to_string(),
+            to_db_time(Utc::now()),
+        ),
+    )
+    .await?;
+
+    let image_set = conn
+        .query_one(
+            &format!("SELECT {IMAGE_SET_COLUMNS} FROM image_sets WHERE id = last_insert_rowid()"),
+            (),
+            ImageSet::from_row,
+        )
+        .await?;
+    Ok(image_set)
+}
+
+/// Look up an image set by name.
+pub async fn get_image_set_by_name(conn: &Connection, name: &str) -> Result<Option<ImageSet>> {
+    let image_set = conn
+        .query_row(
+            &format!("SELECT {IMAGE_SET_COLUMNS} FROM image_sets WHERE name = ?1"),
+            (name.to_string(),),
+            ImageSet::from_row,
+        )
+        .await
+        .optional()?;
+    Ok(image_set)
+}
+
+/// Make `id` the fleet-wide default image set, or clear the default with `None`.
+///
+/// Runs in one transaction, so an unknown `id` leaves the current default in place
+/// and no reader ever sees the fleet without one mid-switch.
+pub async fn set_default_image_set(conn: &mut Connection, id: Option<i64>) -> Result<()> {
+    let tx = conn.transaction().await?;
+
+    // Clear first so the partial unique index never sees two defaults.
+    tx.execute(
+        "UPDATE image_sets SET is_default = 0 WHERE is_default = 1",
+        (),
+    )
+    .await?;
+    if let Some(id) = id {
+        let updated = tx
+            .execute("UPDATE image_sets SET is_default = 1 WHERE id = ?1", (id,))
+            .await?;
+        if updated == 0 {
+            tx.rollback().await?;
+            return Err(anyhow!("Image set {} not found", id));
+        }
+    }
+
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Pin a device to an image set, or unpin it with `None`.
+///
+/// Returns `false` if the device does not exist.
+pub async fn set_device_image_set(
+    conn: &Connection,
+    uuid: &Uuid,
+    image_set_id: Option<i64>,
+) -> Result<bool> {
+    let updated = conn
+        .execute(
+            "UPDATE devices SET image_set_id = ?1 WHERE uuid = ?2",
+            (image_set_id, *uuid),
+        )
+        .await?;
+    Ok(updated > 0)
+}
+
+/// Resolve the image set a device should boot: its pinned set, else the default.
+///
+/// Returns `None` when neither exists, meaning the bundled agent images apply.
+pub async fn resolve_image_set(conn: &Connection, uuid: &Uuid) -> Result<Option<ImageSet>> {
+    let image_set = conn
+        .query_row(
+            "SELECT s.id, s.name, s.kernel, s.ramdisk, s.cmdline, s.is_default
+             FROM image_sets s
+             WHERE s.id = (SELECT image_set_id FROM devices WHERE uuid = ?1)
+                OR (s.is_default = 1
+                    AND (SELECT image_set_id FROM devices WHERE uuid = ?1) IS NULL)",
+            (*uuid,),
+            ImageSet::from_row,
+        )
+        .await
+        .optional()?;
+    Ok(image_set)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{database, test_connection_factory};
+
+    async fn insert_device(conn: &Connection, uuid: &Uuid) {
+        conn.execute(
+            "INSERT INTO devices (uuid, lifecycle, architecture) VALUES (?1, 'new', 'x86-64')",
+            (*uuid,),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_resolve_pinned_vs_default() {
+        let factory = test_connection_factory!();
+        let mut conn = database::run_migrations(&factory).await.unwrap();
+        let pinned = Uuid::parse_str("e1000000-0000-0000-0000-000000000001").unwrap();
+        let fleet = Uuid::parse_str("e1000000-0000-0000-0000-000000000002").unwrap();
+        insert_device(&conn, &pinned).await;
+        insert_device(&conn, &fleet).await;
+
+        // No sets at all: bundled images.
+        assert_eq!(resolve_image_set(&conn, &fleet).await.unwrap(), None);
+
+        let v1 = create_image_set(
+            &conn,
+            "v1",
+            "agent-images/vmlinuz-v1",
+            "agent-images/initrd-v1",
+            "",
+        )
+        .await
+        .unwrap();
+        let v2 = create_image_set(
+            &conn,
+            "v2",
+            "agent-images/vmlinuz-v2",
+            "agent-images/initrd-v2",
+            "debug",
+        )
+        .await
+        .unwrap();
+        set_default_image_set(&mut conn, Some(v1.id)).await.unwrap();
+        assert!(
+            set_device_image_set(&conn, &pinned, Some(v2.id))
+                .await
+                .unwrap()
+        );
+
+        let resolved = resolve_image_set(&conn, &pinned).await.unwrap().unwrap();
+        assert_eq!(resolved.name, "v2");
+        let resolved = resolve_image_set(&conn, &fleet).await.unwrap().unwrap();
+        assert_eq!(resolved.name, "v1");
+        assert!(resolved.is_default);
+
+        // Unpinning falls back to the default.
+        set_device_image_set(&conn, &pinned, None).await.unwrap();
+        let resolved = resolve_image_set(&conn, &pinned).await.unwrap().unwrap();
+        assert_eq!(resolved.name, "v1");
+    }
+
+    #[tokio::test]
+    async fn test_set_default_replaces_previous_default() {
+        let factory = test_connection_factory!();
+        let mut conn = database::run_migrations(&factory).await.unwrap();
+        let a = create_image_set(&conn, "a", "k", "r", "").await.unwrap();
+        let b = create_image_set(&conn, "b", "k", "r", "").await.unwrap();
+
+        set_default_image_set(&mut conn, Some(a.id)).await.unwrap();
+        set_default_image_set(&mut conn, Some(b.id)).await.unwrap();
+
+        let a = get_image_set_by_name(&conn, "a").await.unwrap().unwrap();
+        let b = get_image_set_by_name(&conn, "b").await.unwrap().unwrap();
+        assert!(!a.is_default);
+        assert!(b.is_default);
+
+        // An unknown id leaves the current default in place.
+        assert!(set_default_image_set(&mut conn, Some(9999)).await.is_err());
+        let b = get_image_set_by_name(&conn, "b").await.unwrap().unwrap();
+        assert!(b.is_default);
+    }
+}
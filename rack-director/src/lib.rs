@@ -1,18 +1,27 @@
+mod artifacts;
+mod audit;
 mod boot_files;
+mod consistency;
 mod database;
+mod device_inventory;
+mod device_search;
+mod device_tags;
 mod device_warnings;
 mod dhcp;
 mod director;
 mod disk_layout;
 mod http;
+mod image_sets;
 mod lifecycle;
 mod osm;
 mod plans;
 mod platforms;
 mod roles;
+mod snapshot;
 mod storage;
 mod templates;
 mod tftp;
+mod uuid_blocklist;
 
 use std::{
     io,
@@ -21,7 +30,7 @@ use std::{
 };
 
 use anyhow::anyhow;
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use tokio::task::JoinHandle;
 
 use crate::storage::ImageStore;
@@ -32,16 +41,66 @@ const DEFAULT_FIRMWARE_PATH: &str = env!("RACK_DIRECTOR_FIRMWARE_PATH");
 const DEFAULT_LOCAL_IMAGES_PATH: &str = env!("RACK_DIRECTOR_LOCAL_IMAGES_PATH");
 const DEFAULT_BUNDLED_OSM_PATH: &str = env!("RACK_DIRECTOR_BUNDLED_OSM_PATH");
 
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Decode a hex dump of a captured DHCP packet and print every field and option.
+    DecodeDhcp {
+        /// File containing the packet as hex (whitespace, `:` and `-` are ignored).
+        hexfile: std::path::PathBuf,
+    },
+    /// Report leases outside their pools, interface addresses no lease backs and
+    /// leases for deleted interfaces. Exits non-zero if any are left unfixed.
+    Check {
+        /// Release the offending leases and clear the stale interface addresses.
+        #[arg(long)]
+        fix: bool,
+    },
+}
+
 #[derive(Parser, Debug)]
 pub struct Args {
+    /// Run a one-off tool instead of starting the server.
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
     // Path to the database file.
     #[arg(long, default_value = DEFAULT_DATABASE_PATH)]
     db_path: String,
 
+    /// TOML file declaring DHCP subnets and reservations to seed into the database
+    /// at startup. See `dhcp::seed` for the format.
+    #[arg(long)]
+    config: Option<std::path::PathBuf>,
+
     // Path to the directory containing the TFTP files.
     #[arg(long, default_value = DEFAULT_FIRMWARE_PATH)]
     tftp_path: String,
 
+    /// Additional read-only boot file directories, searched in order after
+    /// `--tftp-path`. May be given multiple times.
+    #[arg(long = "extra-tftp-path")]
+    extra_tftp_paths: Vec<String>,
+
+    /// Milliseconds to wait for a TFTP client's reply before retransmitting a block.
+    #[arg(long, default_value_t = 2000)]
+    tftp_block_timeout_ms: u64,
+
+    /// Seconds a TFTP transfer may go without hearing from the client before it is
+    /// closed, regardless of remaining retransmits.
+    #[arg(long, default_value_t = 10)]
+    tftp_idle_timeout_secs: u64,
+
+    /// Only serve TFTP filenames matching this glob (`*` and `?`, neither matching
+    /// `/`), e.g. `*.efi` or `pxelinux.cfg/*`. May be given multiple times; when
+    /// absent, every filename is looked up.
+    #[arg(long = "tftp-allow")]
+    tftp_allow: Vec<boot_files::FilenamePattern>,
+
+    /// Bytes to read from disk at a time when serving a TFTP file, rounded down to a
+    /// whole number of the transfer's blocks (at least one) and capped at the file size.
+    #[arg(long, default_value_t = tftp::DEFAULT_READ_CHUNK)]
+    tftp_read_chunk: u64,
+
     // DHCP server address (optional, defaults to 67)
     #[arg(long)]
     dhcp_address: Option<SocketAddr>,
@@ -58,6 +117,14 @@ pub struct Args {
     #[arg(long)]
     tftp_public_address: Option<String>,
 
+    /// Maximum HTTP request body size in bytes. OSM uploads have their own limit.
+    #[arg(long, default_value_t = http::limits::DEFAULT_BODY_LIMIT_BYTES)]
+    http_body_limit_bytes: usize,
+
+    /// Seconds an HTTP handler has to respond before the request fails with 408.
+    #[arg(long, default_value_t = http::limits::DEFAULT_REQUEST_TIMEOUT_SECS)]
+    http_request_timeout_secs: u64,
+
     // HTTP server public url
     #[arg(long)]
     http_public_url: Option<String>,
@@ -109,6 +176,21 @@ pub struct Args {
     #[arg(long, help = "Base URL for serving images over HTTP")]
     storage_base_url: Option<String>,
 
+    /// Base URL iPXE chains to for boot and install scripts. Defaults to the URL
+    /// the device reached the director on.
+    #[arg(long)]
+    ipxe_script_base_url: Option<String>,
+
+    /// Base URL kernels, ramdisks and modules are fetched from in iPXE scripts, for
+    /// deployments with a dedicated image host. Defaults to the director's URL.
+    #[arg(long)]
+    image_base_url: Option<String>,
+
+    /// Base URL that boot configs served over TFTP chain to. Defaults to
+    /// `--ipxe-script-base-url`, then `--http-public-url`.
+    #[arg(long)]
+    tftp_base_url: Option<String>,
+
     // Agent images path (bundled with installation)
     #[arg(
         long,
@@ -129,10 +211,154 @@ pub struct Args {
     #[arg(long, default_value_t = false)]
     no_dhcp_broadcast: bool,
 
+    /// Include option 150 (Cisco TFTP server address) in boot replies even when
+    /// the client did not list it in its parameter request list.
+    #[arg(long, default_value_t = false)]
+    dhcp_always_send_option_150: bool,
+
+    /// Ignore DHCP REQUESTs for addresses this server never leased instead of NAKing
+    /// them. Use when another DHCP server shares the segment.
+    #[arg(long, default_value_t = false)]
+    dhcp_non_authoritative: bool,
+
+    /// Fraction of the lease time after which clients should renew (option 58, T1).
+    #[arg(long, default_value_t = 0.5)]
+    dhcp_renewal_ratio: f64,
+
+    /// Fraction of the lease time after which clients should rebind (option 59, T2).
+    /// Must be greater than `--dhcp-renewal-ratio` and less than 1.
+    #[arg(long, default_value_t = 0.875)]
+    dhcp_rebinding_ratio: f64,
+
+    /// Milliseconds to wait before sending each DHCP OFFER (ACKs are never delayed).
+    /// Set on the backup when another DHCP server shares the segment so that the
+    /// primary reliably answers first.
+    #[arg(long, default_value_t = 0)]
+    dhcp_offer_delay_ms: u64,
+
+    /// Seconds an address offered to a DHCP client stays reserved waiting for its
+    /// REQUEST. Offers are stored in the database, so they survive a restart.
+    #[arg(long, default_value_t = dhcp::store::DEFAULT_OFFER_TTL)]
+    dhcp_offer_ttl_secs: u32,
+
+    /// Most interfaces one device may have. DHCP clients that report a device's GUID
+    /// from a MAC it doesn't have yet are ignored once it has this many.
+    #[arg(long, default_value_t = dhcp::device_resolution::DEFAULT_MAX_INTERFACES_PER_DEVICE)]
+    dhcp_max_interfaces_per_device: usize,
+
+    /// Most DHCP clients never seen before that are offered addresses per
+    /// `--dhcp-lease-rate-window-secs`, across all networks. DISCOVERs from further new
+    /// MACs are dropped, guarding pools against MAC-spoofing exhaustion; renewals and
+    /// known clients are unaffected. Unlimited when unset.
+    #[arg(long)]
+    dhcp_max_lease_rate: Option<u32>,
+
+    /// Like `--dhcp-max-lease-rate`, but counted separately for each network.
+    #[arg(long)]
+    dhcp_max_lease_rate_per_network: Option<u32>,
+
+    /// Window in seconds over which `--dhcp-max-lease-rate` limits are counted.
+    #[arg(long, default_value_t = 60)]
+    dhcp_lease_rate_window_secs: u64,
+
+    /// Shortest lease in seconds granted to a DHCP client that requests a lease time
+    /// (option 51). Requests are honored between this and the network's lease duration.
+    #[arg(long, default_value_t = dhcp::message_builder::DEFAULT_MIN_LEASE_SECS)]
+    dhcp_min_lease_time: u32,
+
+    /// Only answer DHCP clients whose MAC starts with this vendor prefix (OUI),
+    /// e.g. `00:25:90`. May be given multiple times; when absent, all vendors
+    /// not denied by `--dhcp-oui-deny` are answered.
+    #[arg(long = "dhcp-oui-allow")]
+    dhcp_oui_allow: Vec<dhcp::oui::Oui>,
+
+    /// Never answer DHCP clients whose MAC starts with this vendor prefix (OUI).
+    /// May be given multiple times. Takes precedence over `--dhcp-oui-allow`.
+    #[arg(long = "dhcp-oui-deny")]
+    dhcp_oui_deny: Vec<dhcp::oui::Oui>,
+
+    /// Boot file to serve over TFTP to clients sending a DHCP user class (option 77),
+    /// as `CLASS=FILENAME`. May be given multiple times; the first entry whose class
+    /// the client sends wins. iPXE clients always get the boot script.
+    #[arg(long = "dhcp-user-class-bootfile")]
+    dhcp_user_class_bootfile: Vec<dhcp::UserClassBootFile>,
+
+    /// Domain to send in the DHCP domain search list (option 119) to clients that
+    /// request it. May be given multiple times; clients search them in order.
+    #[arg(long = "dhcp-domain-search")]
+    dhcp_domain_search: Vec<String>,
+
+    /// PXE_DISCOVERY_CONTROL bits (option 43, sub-option 6) to send to PXE ROMs,
+    /// e.g. 8 to download the offered boot file without boot server discovery.
+    #[arg(long = "dhcp-pxe-discovery-control")]
+    dhcp_pxe_discovery_control: Option<u8>,
+
+    /// Boot menu prompt (option 43, sub-option 10) for PXE ROMs to show before
+    /// booting.
+    #[arg(long = "dhcp-pxe-menu-prompt")]
+    dhcp_pxe_menu_prompt: Option<String>,
+
+    /// Seconds PXE ROMs show `--dhcp-pxe-menu-prompt` for. 0 boots at once and 255
+    /// waits for a key.
+    #[arg(long = "dhcp-pxe-menu-timeout", default_value_t = 10)]
+    dhcp_pxe_menu_timeout: u8,
+
+    /// Answer legacy BOOTP clients (BOOTREQUESTs without the DHCP magic cookie) that
+    /// have a static reservation with a plain BOOTREPLY carrying the reserved address
+    /// and the BIOS boot file. For very old PXE ROMs; off by default.
+    #[arg(long)]
+    dhcp_legacy_bootp: bool,
+
+    /// Largest DHCP reply to send, in bytes including IP and UDP headers, even to
+    /// clients that accept more. Low-priority options that don't fit are dropped with
+    /// a warning. Values below the RFC 2131 minimum of 576 act as 576.
+    #[arg(long, default_value_t = 1500)]
+    dhcp_max_message_size: u16,
+
+    /// Answer a DHCPDISCOVER carrying Rapid Commit (option 80) with an ACK that
+    /// commits the lease straight away, instead of an OFFER (RFC 4039).
+    #[arg(long)]
+    dhcp_rapid_commit: bool,
+
+    /// How new DHCP addresses are picked from a pool: `sequential` hands out the
+    /// lowest free address, `random` any free one.
+    #[arg(long, default_value = "sequential")]
+    dhcp_allocation_strategy: dhcp::AllocationStrategy,
+
+    /// Lease duration in seconds for DHCP networks created without one.
+    #[arg(long, default_value_t = dhcp::store::DEFAULT_LEASE_DURATION)]
+    default_lease_duration: u32,
+
+    /// Days to keep expired and released DHCP leases as history before purging them.
+    /// 0 purges them as soon as they end.
+    #[arg(long, default_value_t = 0)]
+    lease_retention_days: u32,
+
     /// Number of seconds unprovisioned devices sleep before rebooting to retry PXE boot.
     #[arg(long, default_value_t = 600)]
     unprovisioned_sleep_secs: u64,
 
+    /// Hardware UUID that is never registered or updated and is told to boot its
+    /// local disk. May be given multiple times. Added to the blocklist at startup;
+    /// removing the flag later does not unblock it (use the API for that).
+    #[arg(long = "blocked-uuid")]
+    blocked_uuid: Vec<uuid::Uuid>,
+
+    /// Seconds an open provisioning transition may go without progress (a state
+    /// change, agent check-in or poll) before it is failed and the device marked
+    /// broken. 0 disables the reaper.
+    #[arg(long, default_value_t = 7200)]
+    transition_timeout_secs: u64,
+
+    /// Write a JSON snapshot of devices, interfaces, active leases and subnets to this
+    /// path every `--snapshot-interval-secs`. Disabled when unset.
+    #[arg(long)]
+    snapshot_path: Option<std::path::PathBuf>,
+
+    /// Seconds between state snapshots (see `--snapshot-path`).
+    #[arg(long, default_value_t = snapshot::DEFAULT_SNAPSHOT_INTERVAL_SECS)]
+    snapshot_interval_secs: u64,
+
     /// Verify the BMC's TLS certificate for Redfish connections.
     ///
     /// Disabled by default because most BMC firmware ships with self-signed
@@ -157,6 +383,12 @@ pub struct RackDirectorHandle {
 
     // Background task for cleaning up expired DHCP leases
     lease_cleanup_handle: JoinHandle<()>,
+
+    // Background task for failing stuck lifecycle transitions
+    transition_reaper_handle: Option<JoinHandle<()>>,
+
+    // Background task writing state snapshots, when enabled
+    snapshot_handle: Option<JoinHandle<()>>,
 }
 
 impl RackDirectorHandle {
@@ -167,11 +399,64 @@ impl RackDirectorHandle {
         let _ = tokio::try_join!(self.http_handle, self.tftp_handle);
         self.dhcp_handle.abort();
         self.lease_cleanup_handle.abort();
+        if let Some(handle) = self.transition_reaper_handle {
+            handle.abort();
+        }
+        if let Some(handle) = self.snapshot_handle {
+            handle.abort();
+        }
     }
 }
 
+/// Parser entry points for the harnesses in `fuzz/`. Not a stable API.
+#[doc(hidden)]
+pub mod fuzzing {
+    use crate::tftp::packet::Packet;
+
+    /// Parse untrusted bytes as a received DHCP packet.
+    pub fn parse_dhcp(data: &[u8]) {
+        let _ = crate::dhcp::parse::parse_message(data);
+    }
+
+    /// Parse untrusted bytes as a TFTP packet; anything accepted must survive
+    /// re-encoding and parsing again unchanged.
+    pub fn parse_tftp(data: &[u8]) {
+        if let Ok(packet) = Packet::parse(data) {
+            let reparsed = Packet::parse(&packet.to_bytes()).expect("re-encoded packet parses");
+            assert_eq!(packet, reparsed);
+        }
+    }
+}
+
+/// Run the one-off [`Command`] in `args`, printing its output to stdout.
+pub async fn run_command(args: Args) -> Result<(), anyhow::Error> {
+    let Some(command) = args.command else {
+        return Ok(());
+    };
+    match command {
+        Command::DecodeDhcp { hexfile } => {
+            print!("{}", dhcp::decode::decode_file(&hexfile)?);
+        }
+        Command::Check { fix } => {
+            let factory = database::DatabaseConnectionFactory::new(database_file(&args.db_path));
+            let mut conn = database::run_migrations(&factory).await?;
+            let report = consistency::check(&mut conn, fix).await?;
+            print!("{}", report);
+            if !report.fixed && !report.anomalies.is_empty() {
+                anyhow::bail!("rerun with --fix to repair them");
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The SQLite file inside `--db-path`.
+fn database_file(db_path: &str) -> std::path::PathBuf {
+    std::path::PathBuf::from(format!("{}/db.sqlite", db_path))
+}
+
 pub async fn rack_director_start(args: crate::Args) -> Result<RackDirectorHandle, anyhow::Error> {
-    let db_file = std::path::PathBuf::from(format!("{}/db.sqlite", args.db_path));
+    let db_file = database_file(&args.db_path);
 
     // Create one shared factory. The factory holds only a PathBuf and opens a
     // fresh connection on each `.open()` call, so sharing it via Arc::clone
@@ -182,6 +467,19 @@ pub async fn rack_director_start(args: crate::Args) -> Result<RackDirectorHandle
     // dropped immediately after — the schema persists in the file.
     let _ = database::run_migrations(factory.as_ref()).await?;
 
+    if let Some(path) = &args.config {
+        let seed = dhcp::seed::SeedConfig::load(path)?;
+        let mut conn = factory.open().await?;
+        dhcp::seed::reconcile(&mut conn, &seed, args.default_lease_duration).await?;
+    }
+
+    if !args.blocked_uuid.is_empty() {
+        let conn = factory.open().await?;
+        for uuid in &args.blocked_uuid {
+            uuid_blocklist::block_uuid(&conn, uuid, Some("--blocked-uuid")).await?;
+        }
+    }
+
     // Load and sync bundled Default OSM
     let bundled_osm = osm::load_bundled_osm(std::path::Path::new(&args.bundled_osm_path))?;
     if let Some(ref bundled) = bundled_osm {
@@ -209,7 +507,24 @@ pub async fn rack_director_start(args: crate::Args) -> Result<RackDirectorHandle
     osm::cleanup_orphaned_storage(&conn, &image_store).await?;
 
     // Cleanup task uses the shared factory.
-    let lease_cleanup_handle = dhcp::spawn_lease_cleanup_task(factory.clone());
+    let lease_cleanup_handle = dhcp::spawn_lease_cleanup_task(
+        factory.clone(),
+        chrono::Duration::days(args.lease_retention_days.into()),
+    );
+    let transition_reaper_handle = director::spawn_transition_reaper_task(
+        factory.clone(),
+        std::time::Duration::from_secs(args.transition_timeout_secs),
+    );
+    if args.snapshot_path.is_some() && args.snapshot_interval_secs == 0 {
+        anyhow::bail!("--snapshot-interval-secs must be at least 1");
+    }
+    let snapshot_handle = args.snapshot_path.clone().map(|path| {
+        snapshot::spawn_snapshot_task(
+            factory.clone(),
+            path,
+            std::time::Duration::from_secs(args.snapshot_interval_secs),
+        )
+    });
 
     // Determine TFTP public address
     let tftp_public = args.tftp_public_address.unwrap_or_else(|| {
@@ -224,9 +539,28 @@ pub async fn rack_director_start(args: crate::Args) -> Result<RackDirectorHandle
     let http_server = public_url.clone();
 
     // Initialize boot file provider for DHCP (Option 13), HTTP Boot, and TFTP
-    let boot_file_provider = Arc::new(boot_files::FilesystemBootFileProvider::new(
-        std::path::PathBuf::from(&args.tftp_path),
-    )?);
+    let boot_file_roots = std::iter::once(&args.tftp_path)
+        .chain(&args.extra_tftp_paths)
+        .map(std::path::PathBuf::from)
+        .collect();
+    let boot_file_provider = Arc::new(
+        boot_files::FilesystemBootFileProvider::with_roots(boot_file_roots)?
+            .with_read_chunk(args.tftp_read_chunk),
+    );
+
+    let domain_search = if args.dhcp_domain_search.is_empty() {
+        None
+    } else {
+        Some(dhcp::DomainSearch::new(&args.dhcp_domain_search)?)
+    };
+
+    let mut pxe_vendor = dhcp::PxeVendorOptions::default();
+    if let Some(bits) = args.dhcp_pxe_discovery_control {
+        pxe_vendor = pxe_vendor.with_discovery_control(bits);
+    }
+    if let Some(prompt) = &args.dhcp_pxe_menu_prompt {
+        pxe_vendor = pxe_vendor.with_menu_prompt(args.dhcp_pxe_menu_timeout, prompt)?;
+    }
 
     let dhcp_server: dhcp::DhcpServer = dhcp::DhcpServer::new(
         factory.clone(),
@@ -235,13 +569,56 @@ pub async fn rack_director_start(args: crate::Args) -> Result<RackDirectorHandle
         boot_file_provider.clone(),
         server_identifier,
         args.dhcp_address,
+        args.dhcp_always_send_option_150,
     )
     .await
-    .unwrap();
+    .unwrap()
+    .with_lease_timers(dhcp::message_builder::LeaseTimers::new(
+        args.dhcp_renewal_ratio,
+        args.dhcp_rebinding_ratio,
+    )?)
+    .with_min_lease_time(args.dhcp_min_lease_time)
+    .with_authoritative(!args.dhcp_non_authoritative)
+    .with_oui_filter(dhcp::oui::OuiFilter::new(
+        args.dhcp_oui_allow.clone(),
+        args.dhcp_oui_deny.clone(),
+    ))
+    .with_user_class_boot_files(args.dhcp_user_class_bootfile.clone())
+    .with_domain_search(domain_search)
+    .with_pxe_vendor_options(Some(pxe_vendor))
+    .with_legacy_bootp(args.dhcp_legacy_bootp)
+    .with_max_message_size(args.dhcp_max_message_size)
+    .with_rapid_commit(args.dhcp_rapid_commit)
+    .with_allocation_strategy(args.dhcp_allocation_strategy)
+    .with_offer_delay(std::time::Duration::from_millis(args.dhcp_offer_delay_ms))
+    .with_offer_ttl(args.dhcp_offer_ttl_secs)
+    .with_max_interfaces_per_device(args.dhcp_max_interfaces_per_device)
+    .with_allocation_rate_limit(dhcp::rate_limit::AllocationRateLimiter::new(
+        args.dhcp_max_lease_rate,
+        args.dhcp_max_lease_rate_per_network,
+        std::time::Duration::from_secs(args.dhcp_lease_rate_window_secs),
+    ));
 
     // Initialize TFTP Server
-    let mut tftp_server = tftp::Server::new(boot_file_provider.clone());
+    let tftp_base_url = args
+        .tftp_base_url
+        .clone()
+        .or_else(|| args.ipxe_script_base_url.clone())
+        .map(|url| url.trim_end_matches('/').to_string())
+        .unwrap_or(public_url);
+    let mut tftp_server = tftp::Server::new(Arc::new(
+        boot_files::DirectorTftpHandler::new(
+            boot_file_provider.clone(),
+            factory.clone(),
+            tftp_base_url,
+        )
+        .with_allowlist(boot_files::FilenameAllowlist::new(args.tftp_allow.clone())),
+    ));
     tftp_server.address(args.tftp_address);
+    tftp_server.timeouts(tftp::Timeouts {
+        block: std::time::Duration::from_millis(args.tftp_block_timeout_ms),
+        idle: std::time::Duration::from_secs(args.tftp_idle_timeout_secs),
+    });
 
     // Start DHCP Service first so the DhcpControl handle is available for HTTP.
     let dhcp_start_result = dhcp_server.serve(args.no_dhcp_broadcast).await?;
@@ -277,6 +654,16 @@ pub async fn rack_director_start(args: crate::Args) -> Result<RackDirectorHandle
         args.unprovisioned_sleep_secs,
         bundled_osm_path,
         power_config,
+        args.default_lease_duration,
+        tftp_server.transfers(),
+        plans::actions::BootUrlConfig {
+            script_base_url: args.ipxe_script_base_url.clone(),
+            image_base_url: args.image_base_url.clone(),
+        },
+        http::limits::HttpLimits {
+            body_limit_bytes: args.http_body_limit_bytes,
+            request_timeout: std::time::Duration::from_secs(args.http_request_timeout_secs),
+        },
     )
     .await?;
 
@@ -294,6 +681,8 @@ pub async fn rack_director_start(args: crate::Args) -> Result<RackDirectorHandle
         dhcp_port: dhcp_start_result.port,
 
         lease_cleanup_handle,
+        transition_reaper_handle,
+        snapshot_handle,
     })
 }
 
@@ -1,9 +1,10 @@
 use crate::lifecycle::{DeviceLifecycle, LifecycleTransition};
 use anyhow::Result;
+use chrono::{DateTime, Utc};
 use rusqlite::OptionalExtension;
 use uuid::Uuid;
 
-use crate::database::{Connection, FromRow};
+use crate::database::{Connection, FromRow, from_db_time, to_db_time};
 
 pub async fn get_device_lifecycle(
     conn: &Connection,
@@ -24,6 +25,7 @@ pub async fn get_device_lifecycle(
     Ok(result)
 }
 
+/// Set a device's lifecycle, stamping `state_changed_at`.
 pub async fn update_device_lifecycle(
     conn: &Connection,
     device_uuid: &Uuid,
@@ -31,31 +33,42 @@ pub async fn update_device_lifecycle(
 ) -> Result<()> {
     let lifecycle_str: String = lifecycle.into();
     conn.execute(
-        "UPDATE devices SET lifecycle = ?1 WHERE uuid = ?2",
-        (lifecycle_str, *device_uuid),
+        "UPDATE devices SET lifecycle = ?1, state_changed_at = ?2 WHERE uuid = ?3",
+        (lifecycle_str, to_db_time(chrono::Utc::now()), *device_uuid),
     )
     .await?;
 
     Ok(())
 }
 
+/// Record a new open transition. Starting a transition counts as a state change,
+/// so the device's `state_changed_at` is stamped too.
 pub async fn create_transition(conn: &Connection, transition: &LifecycleTransition) -> Result<i64> {
     let from_state_str: String = transition.from_state.clone().into();
     let to_state_str: String = transition.to_state.clone().into();
+    let now = to_db_time(chrono::Utc::now());
 
     conn.execute(
         "INSERT INTO lifecycle_transitions (device_uuid, from_state, to_state, plan_id, created_at)
-         VALUES (?1, ?2, ?3, ?4, CURRENT_TIMESTAMP)",
+         VALUES (?1, ?2, ?3, ?4, ?5)",
         (
             transition.device_uuid,
             from_state_str,
             to_state_str,
             transition.plan_id,
+            now.clone(),
         ),
     )
     .await?;
+    let id = conn.last_insert_rowid().await;
 
-    Ok(conn.last_insert_rowid().await)
+    conn.execute(
+        "UPDATE devices SET state_changed_at = ?1 WHERE uuid = ?2",
+        (now, transition.device_uuid),
+    )
+    .await?;
+
+    Ok(id)
 }
 
 pub async fn get_active_transition_for_device(
@@ -89,9 +102,14 @@ pub async fn complete_transition(
 ) -> Result<usize> {
     let rows = conn
         .execute(
-            "UPDATE lifecycle_transitions SET success = ?1, error_message = ?2, completed_at = CURRENT_TIMESTAMP
-             WHERE id = ?3 AND success IS NULL",
-            (success, error_message.map(|s| s.to_string()), transition_id),
+            "UPDATE lifecycle_transitions SET success = ?1, error_message = ?2, completed_at = ?3
+             WHERE id = ?4 AND success IS NULL",
+            (
+                success,
+                error_message.map(|s| s.to_string()),
+                to_db_time(chrono::Utc::now()),
+                transition_id,
+            ),
         )
         .await?;
 
@@ -139,3 +157,47 @@ pub async fn get_transition_by_plan_id(
 
     Ok(transition)
 }
+
+/// Open provisioning transitions (to `provisioned`) whose device has shown no sign
+/// of progress since `cutoff`.
+///
+/// Progress is the latest of the device's `state_changed_at`, `last_seen_at` and
+/// `last_polled_at`, so an agent that is still checking in is never considered stuck.
+pub async fn get_stale_transitions(
+    conn: &Connection,
+    cutoff: DateTime<Utc>,
+) -> Result<Vec<LifecycleTransition>> {
+    let rows = conn
+        .query(
+            "SELECT t.id, t.device_uuid, t.from_state, t.to_state, t.plan_id, t.created_at, t.completed_at, t.success, t.error_message,
+                    d.state_changed_at, d.last_seen_at, d.last_polled_at
+             FROM lifecycle_transitions t
+             JOIN devices d ON d.uuid = t.device_uuid
+             WHERE t.success IS NULL AND t.to_state = 'provisioned'
+             ORDER BY t.created_at",
+            (),
+            |row| {
+                let activity: Vec<Option<String>> = vec![
+                    row.get("state_changed_at")?,
+                    row.get("last_seen_at")?,
+                    row.get("last_polled_at")?,
+                ];
+                Ok((LifecycleTransition::from_row(row)?, activity))
+            },
+        )
+        .await?;
+
+    Ok(rows
+        .into_iter()
+        .filter(|(transition, activity)| {
+            let last_progress = activity
+                .iter()
+                .flatten()
+                .filter_map(|s| from_db_time(s).ok())
+                .chain(transition.started_at)
+                .max();
+            last_progress.is_none_or(|at| at < cutoff)
+        })
+        .map(|(transition, _)| transition)
+        .collect())
+}
@@ -1,5 +1,5 @@
 use clap::Parser;
-use rack_director::rack_director_start;
+use rack_director::{rack_director_start, run_command};
 
 #[tokio::main]
 async fn main() {
@@ -8,6 +8,14 @@ async fn main() {
 
     let args = rack_director::Args::parse();
 
+    if args.command.is_some() {
+        let Err(e) = run_command(args).await else {
+            return;
+        };
+        eprintln!("Error: {e:#}");
+        std::process::exit(1);
+    }
+
     let start_result = rack_director_start(args)
         .await
         .expect("Error starting Rack Director");
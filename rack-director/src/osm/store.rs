@@ -1,6 +1,6 @@
 use anyhow::{Context, Result, anyhow};
 
-use crate::database::Connection;
+use crate::database::{Connection, to_db_time};
 use osm::os_config::OperatingSystemConfig;
 
 // ── Public types ─────────────────────────────────────────────────────────────
@@ -88,8 +88,8 @@ pub async fn create_module(
     archive_path: Option<&str>,
 ) -> Result<OsmModule> {
     conn.execute(
-        "INSERT INTO osm_modules (name, version, author, description, source, storage_prefix, is_default, archive_path)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        "INSERT INTO osm_modules (name, version, author, description, source, storage_prefix, is_default, archive_path, created_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?9)",
         (
             name.to_string(),
             version.to_string(),
@@ -99,6 +99,7 @@ pub async fn create_module(
             storage_prefix.to_string(),
             is_default as i32,
             archive_path.map(|s| s.to_string()),
+            to_db_time(chrono::Utc::now()),
         ),
     )
     .await
@@ -165,14 +166,15 @@ pub async fn update_module(
             "UPDATE osm_modules
              SET version = ?1, author = ?2, description = ?3,
                  storage_prefix = ?4, archive_path = ?5,
-                 updated_at = CURRENT_TIMESTAMP
-             WHERE id = ?6",
+                 updated_at = ?6
+             WHERE id = ?7",
             (
                 version.to_string(),
                 author.to_string(),
                 description.to_string(),
                 storage_prefix.to_string(),
                 archive_path.map(|s| s.to_string()),
+                to_db_time(chrono::Utc::now()),
                 id,
             ),
         )
@@ -192,8 +194,8 @@ pub async fn update_module(
 pub async fn update_module_source(conn: &Connection, id: i64, source: &str) -> Result<()> {
     let rows = conn
         .execute(
-            "UPDATE osm_modules SET source = ?1, updated_at = CURRENT_TIMESTAMP WHERE id = ?2",
-            (source.to_string(), id),
+            "UPDATE osm_modules SET source = ?1, updated_at = ?2 WHERE id = ?3",
+            (source.to_string(), to_db_time(chrono::Utc::now()), id),
         )
         .await
         .context("Failed to update OSM module source")?;
@@ -249,14 +251,15 @@ pub async fn create_operating_system(
     let config_json = serde_json::to_string(config).context("Failed to serialize OS config")?;
 
     conn.execute(
-        "INSERT INTO osm_operating_systems (module_id, dir_name, name, release, config)
-         VALUES (?1, ?2, ?3, ?4, ?5)",
+        "INSERT INTO osm_operating_systems (module_id, dir_name, name, release, config, created_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?6)",
         (
             module_id,
             dir_name.to_string(),
             name.to_string(),
             release.to_string(),
             config_json,
+            to_db_time(chrono::Utc::now()),
         ),
     )
     .await
@@ -335,9 +338,9 @@ pub async fn set_os_disabled(conn: &Connection, os_id: i64, disabled: bool) -> R
     let rows = conn
         .execute(
             "UPDATE osm_operating_systems
-             SET disabled = ?1, updated_at = CURRENT_TIMESTAMP
-             WHERE id = ?2",
-            (disabled as i32, os_id),
+             SET disabled = ?1, updated_at = ?2
+             WHERE id = ?3",
+            (disabled as i32, to_db_time(chrono::Utc::now()), os_id),
         )
         .await
         .context("Failed to update OS disabled flag")?;
@@ -374,9 +377,13 @@ pub async fn create_upload(
     total_bytes: Option<i64>,
 ) -> Result<OsmUpload> {
     conn.execute(
-        "INSERT INTO osm_uploads (filename, status, total_bytes)
-         VALUES (?1, 'uploading', ?2)",
-        (filename.to_string(), total_bytes),
+        "INSERT INTO osm_uploads (filename, status, total_bytes, created_at, updated_at)
+         VALUES (?1, 'uploading', ?2, ?3, ?3)",
+        (
+            filename.to_string(),
+            total_bytes,
+            to_db_time(chrono::Utc::now()),
+        ),
     )
     .await
     .context("Failed to create OSM upload")?;
@@ -398,12 +405,13 @@ pub async fn update_upload_status(
         .execute(
             "UPDATE osm_uploads
              SET status = ?1, error_message = ?2, module_id = ?3,
-                 updated_at = CURRENT_TIMESTAMP
-             WHERE id = ?4",
+                 updated_at = ?4
+             WHERE id = ?5",
             (
                 status.to_string(),
                 error_message.map(|s| s.to_string()),
                 module_id,
+                to_db_time(chrono::Utc::now()),
                 upload_id,
             ),
         )
@@ -425,9 +433,9 @@ pub async fn update_upload_progress(
     let rows = conn
         .execute(
             "UPDATE osm_uploads
-             SET received_bytes = ?1, updated_at = CURRENT_TIMESTAMP
-             WHERE id = ?2",
-            (received_bytes, upload_id),
+             SET received_bytes = ?1, updated_at = ?2
+             WHERE id = ?3",
+            (received_bytes, to_db_time(chrono::Utc::now()), upload_id),
         )
         .await
         .context("Failed to update OSM upload progress")?;
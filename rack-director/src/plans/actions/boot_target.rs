@@ -1,8 +1,61 @@
 use anyhow::Result;
+use serde::Serialize;
 
+use crate::image_sets::{self, ImageSet};
 use crate::templates;
 
-#[derive(Debug)]
+/// Base URLs a generated iPXE script points the device at.
+#[derive(Debug, Clone, Copy)]
+pub struct BootUrls<'a> {
+    /// The director itself, handed to agents as `rackdirector.url`.
+    pub director: &'a str,
+    /// Where iPXE fetches scripts from, including the OS install script.
+    pub script: &'a str,
+    /// Where kernels, ramdisks and modules are fetched from.
+    pub image: &'a str,
+}
+
+impl<'a> BootUrls<'a> {
+    /// Point everything at `root_url`.
+    pub fn single(root_url: &'a str) -> Self {
+        Self {
+            director: root_url,
+            script: root_url,
+            image: root_url,
+        }
+    }
+}
+
+/// Operator-configured base URLs for boot scripts.
+///
+/// Lets deployments serve images (and optionally scripts) from a host other than
+/// the director. Unset fields fall back to the URL the device reached the director on.
+#[derive(Debug, Clone, Default)]
+pub struct BootUrlConfig {
+    pub script_base_url: Option<String>,
+    pub image_base_url: Option<String>,
+}
+
+impl BootUrlConfig {
+    /// Resolve the URLs for a request that reached the director at `root_url`.
+    pub fn resolve<'a>(&'a self, root_url: &'a str) -> BootUrls<'a> {
+        let base = |configured: &'a Option<String>| {
+            configured
+                .as_deref()
+                .map_or(root_url, |url| url.trim_end_matches('/'))
+        };
+        BootUrls {
+            director: root_url,
+            script: base(&self.script_base_url),
+            image: base(&self.image_base_url),
+        }
+    }
+}
+
+/// What a device boots next. Serializes tagged by `type`, e.g.
+/// `{"type": "local_disk"}` or `{"type": "sleep_reboot", "seconds": 600}`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
 pub enum BootTarget {
     LocalDisk,
     /// Sleep for the given number of seconds and then reboot.
@@ -16,6 +69,9 @@ pub enum BootTarget {
     AgentImage {
         action: String,
         cmdline: String,
+        /// Kernel/ramdisk to use instead of the bundled agent images, resolved from
+        /// the device's pinned or the default image set. `None` boots the bundled images.
+        image_set: Option<ImageSet>,
     },
     NetBoot {
         ramdisk: String,
@@ -23,6 +79,19 @@ pub enum BootTarget {
         modules: Vec<String>,
         cmdline: String,
     },
+    /// Do not boot anything; show `reason` on the console and wait for an operator.
+    ///
+    /// Used when the director knows about the device but has nothing safe to boot it
+    /// into, e.g. a broken or removed device with no active plan.
+    Hold {
+        reason: String,
+    },
+    /// Hand iPXE over to another script, e.g. the next stage of a multi-stage install.
+    ///
+    /// A `url` starting with `/` is served relative to the script base URL.
+    Chain {
+        url: String,
+    },
 }
 
 impl BootTarget {
@@ -30,24 +99,51 @@ impl BootTarget {
     ///
     /// Storage paths stored in `NetBoot` variants use the format
     /// `osm/{module}/{version}/{os_dir}/{file}`.  The `/cnc/` prefix is
-    /// prepended here to produce the full URL that iPXE will fetch.
+    /// prepended here, under `urls.image`, to produce the full URL that iPXE will fetch.
     pub async fn to_ipxe_script(
         &self,
-        root_url: &str,
+        urls: BootUrls<'_>,
         device_uuid: Option<&uuid::Uuid>,
     ) -> Result<String> {
         match self {
             BootTarget::LocalDisk => Ok(generate_boot_local_script()),
             BootTarget::SleepReboot { seconds } => Ok(generate_sleep_reboot_script(*seconds)),
-            BootTarget::AgentImage { action, cmdline } => {
-                let full_cmdline = format!(
+            BootTarget::Hold { reason } => Ok(generate_hold_script(reason)),
+            BootTarget::Chain { url } => {
+                let url = if url.starts_with('/') {
+                    format!("{}{}", urls.script, url)
+                } else {
+                    url.clone()
+                };
+                generate_chain_script(&url)
+            }
+            BootTarget::AgentImage {
+                action,
+                cmdline,
+                image_set,
+            } => {
+                let mut full_cmdline = format!(
                     "{} rackdirector.action={} rackdirector.url={}",
-                    cmdline, action, root_url
+                    cmdline, action, urls.director
                 );
 
-                // Agent Images are shipped with rack-director and not stored in the ImageStore.
-                let kernel = format!("{}/cnc/agent-images/vmlinuz", root_url);
-                let initramfs = format!("{}/cnc/agent-images/initramfs.img", root_url);
+                let (kernel, initramfs) = match image_set {
+                    Some(set) => {
+                        image_sets::validate_boot_fields(&set.kernel, &set.ramdisk, &set.cmdline)?;
+                        if !set.cmdline.is_empty() {
+                            full_cmdline = format!("{} {}", full_cmdline, set.cmdline);
+                        }
+                        (
+                            format!("{}/cnc/{}", urls.image, set.kernel),
+                            format!("{}/cnc/{}", urls.image, set.ramdisk),
+                        )
+                    }
+                    // Agent Images are shipped with rack-director and not stored in the ImageStore.
+                    None => (
+                        format!("{}/cnc/agent-images/vmlinuz", urls.image),
+                        format!("{}/cnc/agent-images/initramfs.img", urls.image),
+                    ),
+                };
 
                 let script =
                     generate_netboot_script(&kernel, &initramfs, &full_cmdline, &Vec::new());
@@ -60,17 +156,17 @@ impl BootTarget {
                 cmdline,
             } => {
                 // Storage paths are like "osm/{module}/{version}/{os_dir}/{file}".
-                // Prepend "{root_url}/cnc/" to produce the full serving URL.
-                let kernel_url = format!("{}/cnc/{}", root_url, kernel);
-                let initrd_url = format!("{}/cnc/{}", root_url, ramdisk);
+                // Prepend "{image_url}/cnc/" to produce the full serving URL.
+                let kernel_url = format!("{}/cnc/{}", urls.image, kernel);
+                let initrd_url = format!("{}/cnc/{}", urls.image, ramdisk);
                 let module_urls: Vec<String> = modules
                     .iter()
-                    .map(|m| format!("{}/cnc/{}", root_url, m))
+                    .map(|m| format!("{}/cnc/{}", urls.image, m))
                     .collect();
 
                 // Run template.
                 let resolved_cmdline =
-                    templates::render_cmdline_args(cmdline, root_url, device_uuid)?;
+                    templates::render_cmdline_args(cmdline, urls.script, device_uuid)?;
 
                 Ok(generate_netboot_script(
                     &kernel_url,
@@ -110,6 +206,43 @@ reboot
     )
 }
 
+/// Generates an iPXE script that prints why the device is held and waits for a keypress
+/// before rebooting.
+///
+/// Control characters in `reason` are replaced with spaces and `$` is dropped so the
+/// text cannot break out of the `echo` line or trigger iPXE setting expansion.
+pub fn generate_hold_script(reason: &str) -> String {
+    let reason: String = reason
+        .chars()
+        .filter(|c| *c != '$')
+        .map(|c| if c.is_control() { ' ' } else { c })
+        .collect();
+    format!(
+        r#"#!ipxe
+# Device held - operator attention required
+echo Device held by rack-director: {reason}
+prompt Press any key to retry boot
+reboot
+"#
+    )
+}
+
+/// Generates an iPXE script that chains to the script at `url`.
+///
+/// Fails if `url` contains whitespace or control characters, which would let it
+/// add arguments or lines to the script.
+pub fn generate_chain_script(url: &str) -> Result<String> {
+    if url.is_empty() || url.chars().any(|c| c.is_whitespace() || c.is_control()) {
+        anyhow::bail!("invalid chain URL {url:?}");
+    }
+    Ok(format!(
+        r#"#!ipxe
+# Chain to the next boot stage
+chain {url}
+"#
+    ))
+}
+
 pub fn generate_netboot_script(
     kernel: &str,
     initrd: &str,
@@ -134,7 +267,9 @@ boot
 
 #[cfg(test)]
 mod tests {
+    use crate::image_sets::ImageSet;
     use crate::plans::actions::boot_target::{
+        BootTarget, BootUrlConfig, BootUrls, generate_chain_script, generate_hold_script,
         generate_netboot_script, generate_sleep_reboot_script,
     };
 
@@ -159,6 +294,194 @@ mod tests {
         assert_eq!(generate_sleep_reboot_script(300), expected);
     }
 
+    #[test]
+    fn hold_script_exact_output() {
+        let expected = "#!ipxe\n# Device held - operator attention required\necho Device held by rack-director: disk failed\nprompt Press any key to retry boot\nreboot\n";
+        assert_eq!(generate_hold_script("disk failed"), expected);
+    }
+
+    #[test]
+    fn hold_script_sanitizes_reason() {
+        let script = generate_hold_script("bad\nchain http://evil ${net0/ip}");
+        assert!(
+            script.contains("echo Device held by rack-director: bad chain http://evil {net0/ip}\n")
+        );
+        assert!(!script.contains("\nchain"));
+    }
+
+    #[tokio::test]
+    async fn hold_target_renders_hold_script() {
+        let target = BootTarget::Hold {
+            reason: "device is marked broken".to_string(),
+        };
+        let script = target
+            .to_ipxe_script(BootUrls::single("http://10.0.0.1:3000"), None)
+            .await
+            .unwrap();
+        assert!(script.contains("echo Device held by rack-director: device is marked broken\n"));
+        assert!(script.contains("prompt "));
+        assert!(!script.contains("kernel "));
+    }
+
+    #[test]
+    fn chain_script_exact_output() {
+        let expected =
+            "#!ipxe\n# Chain to the next boot stage\nchain http://stages.example/install.ipxe\n";
+        assert_eq!(
+            generate_chain_script("http://stages.example/install.ipxe").unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn chain_script_rejects_injected_lines() {
+        assert!(generate_chain_script("").is_err());
+        assert!(generate_chain_script("http://a/x.ipxe\nshell").is_err());
+        assert!(generate_chain_script("http://a/x.ipxe --autofree").is_err());
+    }
+
+    #[tokio::test]
+    async fn chain_target_resolves_paths_against_script_url() {
+        let config = BootUrlConfig {
+            script_base_url: Some("http://scripts.example:8080".to_string()),
+            image_base_url: None,
+        };
+        let target = BootTarget::Chain {
+            url: "/stages/install.ipxe".to_string(),
+        };
+        let script = target
+            .to_ipxe_script(config.resolve("http://10.0.0.1:3000"), None)
+            .await
+            .unwrap();
+        assert!(script.contains("chain http://scripts.example:8080/stages/install.ipxe\n"));
+
+        let target = BootTarget::Chain {
+            url: "https://other.example/stage2.ipxe".to_string(),
+        };
+        let script = target
+            .to_ipxe_script(config.resolve("http://10.0.0.1:3000"), None)
+            .await
+            .unwrap();
+        assert!(script.contains("chain https://other.example/stage2.ipxe\n"));
+    }
+
+    #[tokio::test]
+    async fn agent_image_target_uses_image_set_files() {
+        let target = BootTarget::AgentImage {
+            action: "daemon".to_string(),
+            cmdline: "ro".to_string(),
+            image_set: Some(ImageSet {
+                id: 1,
+                name: "v2".to_string(),
+                kernel: "agent-images/vmlinuz-v2".to_string(),
+                ramdisk: "agent-images/initramfs-v2.img".to_string(),
+                cmdline: "debug".to_string(),
+                is_default: false,
+            }),
+        };
+        let script = target
+            .to_ipxe_script(BootUrls::single("http://10.0.0.1:3000"), None)
+            .await
+            .unwrap();
+        assert!(script.contains(
+            "kernel http://10.0.0.1:3000/cnc/agent-images/vmlinuz-v2 ro rackdirector.action=daemon rackdirector.url=http://10.0.0.1:3000 debug\n"
+        ));
+        assert!(script.contains("initrd http://10.0.0.1:3000/cnc/agent-images/initramfs-v2.img\n"));
+    }
+
+    #[tokio::test]
+    async fn agent_image_target_rejects_unsafe_image_set() {
+        let image_set = ImageSet {
+            id: 1,
+            name: "v2".to_string(),
+            kernel: "agent-images/vmlinuz-v2".to_string(),
+            ramdisk: "agent-images/initramfs-v2.img".to_string(),
+            cmdline: String::new(),
+            is_default: false,
+        };
+        for image_set in [
+            ImageSet {
+                kernel: "agent-images/vmlinuz-v2\nshell".to_string(),
+                ..image_set.clone()
+            },
+            ImageSet {
+                ramdisk: "agent-images/initramfs-v2.img extra".to_string(),
+                ..image_set.clone()
+            },
+            ImageSet {
+                cmdline: "debug\nshell".to_string(),
+                ..image_set
+            },
+        ] {
+            let target = BootTarget::AgentImage {
+                action: "daemon".to_string(),
+                cmdline: "ro".to_string(),
+                image_set: Some(image_set),
+            };
+            assert!(
+                target
+                    .to_ipxe_script(BootUrls::single("http://10.0.0.1:3000"), None)
+                    .await
+                    .is_err()
+            );
+        }
+    }
+
+    #[test]
+    fn boot_url_config_defaults_to_root_url() {
+        let urls = BootUrlConfig::default().resolve("http://10.0.0.1:3000");
+        assert_eq!(urls.director, "http://10.0.0.1:3000");
+        assert_eq!(urls.script, "http://10.0.0.1:3000");
+        assert_eq!(urls.image, "http://10.0.0.1:3000");
+    }
+
+    #[tokio::test]
+    async fn netboot_target_fetches_images_from_configured_host() {
+        let config = BootUrlConfig {
+            script_base_url: Some("http://scripts.example:8080".to_string()),
+            image_base_url: Some("http://images.example/".to_string()),
+        };
+        let target = BootTarget::NetBoot {
+            kernel: "osm/ubuntu/24.04/os/vmlinuz".to_string(),
+            ramdisk: "osm/ubuntu/24.04/os/initrd".to_string(),
+            modules: vec!["osm/ubuntu/24.04/os/extra.cpio".to_string()],
+            cmdline: "ds=nocloud-net;s={{install_script_url}}".to_string(),
+        };
+        let script = target
+            .to_ipxe_script(config.resolve("http://10.0.0.1:3000"), None)
+            .await
+            .unwrap();
+        assert!(script.contains(
+            "kernel http://images.example/cnc/osm/ubuntu/24.04/os/vmlinuz ds=nocloud-net;s=http://scripts.example:8080/cnc/install_script\n"
+        ));
+        assert!(script.contains("initrd http://images.example/cnc/osm/ubuntu/24.04/os/initrd\n"));
+        assert!(
+            script.contains("module http://images.example/cnc/osm/ubuntu/24.04/os/extra.cpio\n")
+        );
+        assert!(!script.contains("10.0.0.1"));
+    }
+
+    #[tokio::test]
+    async fn agent_image_target_keeps_director_url_with_image_host() {
+        let config = BootUrlConfig {
+            script_base_url: None,
+            image_base_url: Some("http://images.example".to_string()),
+        };
+        let target = BootTarget::AgentImage {
+            action: "daemon".to_string(),
+            cmdline: "ro".to_string(),
+            image_set: None,
+        };
+        let script = target
+            .to_ipxe_script(config.resolve("http://10.0.0.1:3000"), None)
+            .await
+            .unwrap();
+        assert!(script.contains(
+            "kernel http://images.example/cnc/agent-images/vmlinuz ro rackdirector.action=daemon rackdirector.url=http://10.0.0.1:3000\n"
+        ));
+        assert!(script.contains("initrd http://images.example/cnc/agent-images/initramfs.img\n"));
+    }
+
     #[test]
     fn netboot_script_no_modules() {
         let expected = r#"#!ipxe
@@ -1,7 +1,7 @@
 mod boot_target;
 pub mod params;
 
-pub use boot_target::BootTarget;
+pub use boot_target::{BootTarget, BootUrlConfig, BootUrls};
 
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
@@ -21,6 +21,12 @@ pub enum Action {
     PartitionDisks,
     RebootDevice,
     Console,
+    /// Chain the device's iPXE to an operator-provided script, one boot stage.
+    ///
+    /// `url` is absolute, or a path (starting with `/`) on the script base URL.
+    ChainScript {
+        url: String,
+    },
 }
 
 /// Context required for converting Actions to BootTargets
@@ -39,10 +45,11 @@ impl Action {
     pub async fn to_boot_target(&self, ctx: &ActionContext<'_>) -> Result<BootTarget> {
         match self {
             Action::DiscoverHardware | Action::ConfigureBmc | Action::PartitionDisks => {
-                generate_agent_boot_target("daemon")
+                Ok(generate_agent_boot_target("daemon"))
             }
             Action::InstallOs => generate_os_install_boot_target(ctx).await,
-            Action::Console => generate_agent_boot_target("console"),
+            Action::Console => Ok(generate_agent_boot_target("console")),
+            Action::ChainScript { url } => Ok(BootTarget::Chain { url: url.clone() }),
             // All other actions default to local disk boot
             _ => Ok(BootTarget::LocalDisk),
         }
@@ -85,6 +92,15 @@ impl Action {
             _ => false,
         }
     }
+
+    /// Check if this action completes on the boot after its target was served
+    ///
+    /// A chained script runs entirely outside rack-director, so the only sign that a
+    /// stage is over is the device PXE booting again. The first boot serves the
+    /// action's target; the next one advances the plan, like `advance_on_boot()`.
+    pub fn completes_on_next_boot(&self) -> bool {
+        matches!(self, Action::ChainScript { .. })
+    }
 }
 
 /// Console and debugging kernel arguments shared by both the agent image boot and the OS
@@ -105,13 +121,22 @@ fn prepend_default_cmdline(os_cmdline: Option<String>) -> String {
 }
 
 /// Generate boot target for agent-based actions (discovery, BMC config, etc.)
-fn generate_agent_boot_target(action_name: &str) -> Result<BootTarget> {
+fn generate_agent_boot_target(action_name: &str) -> BootTarget {
     let cmdline = format!("ro no_timer_check {DEFAULT_LINUX_CMDLINE}");
 
-    Ok(BootTarget::AgentImage {
+    BootTarget::AgentImage {
         action: action_name.into(),
         cmdline,
-    })
+        image_set: None,
+    }
+}
+
+/// Boot target for a one-shot hardware rescan requested outside of any plan.
+///
+/// The agent runs `device-scan`, which reports fresh hardware attributes and exits,
+/// so no plan action is needed to receive the results.
+pub fn rediscovery_boot_target() -> BootTarget {
+    generate_agent_boot_target("device-scan")
 }
 
 /// Generate boot target for OS installation
@@ -226,10 +251,15 @@ mod tests {
 
     #[test]
     fn test_generate_agent_boot_target() {
-        let boot_target = generate_agent_boot_target("daemon").unwrap();
+        let boot_target = generate_agent_boot_target("daemon");
 
         match boot_target {
-            BootTarget::AgentImage { action, cmdline } => {
+            BootTarget::AgentImage {
+                action,
+                cmdline,
+                image_set,
+            } => {
+                assert!(image_set.is_none());
                 assert_eq!(action, "daemon");
                 // Agent-specific args precede the shared console/debugging defaults.
                 assert_eq!(
@@ -241,6 +271,14 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_rediscovery_boot_target_runs_device_scan() {
+        match rediscovery_boot_target() {
+            BootTarget::AgentImage { action, .. } => assert_eq!(action, "device-scan"),
+            other => panic!("Expected AgentImage, got {other:?}"),
+        }
+    }
+
     #[tokio::test]
     async fn test_install_os_action_to_boot_target_success() {
         let conn = setup_test_db(test_connection_factory!()).await;
@@ -322,6 +360,7 @@ mod tests {
             first_seen_at: None,
             last_seen_at: None,
             last_polled_at: None,
+            notes: None,
         };
 
         let ctx = ActionContext {
@@ -367,6 +406,7 @@ mod tests {
             first_seen_at: None,
             last_seen_at: None,
             last_polled_at: None,
+            notes: None,
         };
 
         let ctx = ActionContext {
@@ -467,6 +507,7 @@ mod tests {
             first_seen_at: None,
             last_seen_at: None,
             last_polled_at: None,
+            notes: None,
         };
 
         let ctx = ActionContext {
@@ -526,6 +567,30 @@ mod tests {
         assert!(!Action::PartitionDisks.advance_on_boot());
     }
 
+    #[test]
+    fn test_chain_script_serialization() {
+        let action = Action::ChainScript {
+            url: "/stages/install.ipxe".to_string(),
+        };
+        let json = serde_json::to_string(&action).unwrap();
+        assert_eq!(
+            json,
+            r#"{"type":"chain_script","url":"/stages/install.ipxe"}"#
+        );
+        assert_eq!(serde_json::from_str::<Action>(&json).unwrap(), action);
+    }
+
+    #[test]
+    fn test_completes_on_next_boot_only_chain_script() {
+        let chain = Action::ChainScript {
+            url: "/stages/install.ipxe".to_string(),
+        };
+        assert!(chain.completes_on_next_boot());
+        assert!(!chain.advance_on_boot());
+        assert!(!Action::RebootDevice.completes_on_next_boot());
+        assert!(!Action::InstallOs.completes_on_next_boot());
+    }
+
     #[tokio::test]
     async fn test_reboot_device_start() {
         let conn = setup_test_db(test_connection_factory!()).await;
@@ -542,6 +607,7 @@ mod tests {
             first_seen_at: None,
             last_seen_at: None,
             last_polled_at: None,
+            notes: None,
         };
 
         let ctx = ActionContext {
@@ -572,6 +638,7 @@ mod tests {
             first_seen_at: None,
             last_seen_at: None,
             last_polled_at: None,
+            notes: None,
         };
 
         let ctx = ActionContext {
@@ -608,6 +675,7 @@ mod tests {
             first_seen_at: None,
             last_seen_at: None,
             last_polled_at: None,
+            notes: None,
         };
 
         let ctx = ActionContext {
@@ -620,7 +688,12 @@ mod tests {
         let boot_target = action.to_boot_target(&ctx).await.unwrap();
 
         match boot_target {
-            BootTarget::AgentImage { action, cmdline } => {
+            BootTarget::AgentImage {
+                action,
+                cmdline,
+                image_set,
+            } => {
+                assert!(image_set.is_none());
                 assert_eq!(action, "daemon");
                 assert!(cmdline.contains("console=ttyS1"));
             }
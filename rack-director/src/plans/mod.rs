@@ -52,6 +52,9 @@ pub struct Plan {
     pub total_steps: i32,
     pub actions: Vec<Action>,
     pub error_message: Option<String>,
+    /// The step whose chain script the device has already been served, if any.
+    /// See [`Action::completes_on_next_boot`].
+    pub chained_step: Option<i32>,
     pub created_at: Option<DateTime<Utc>>,
     pub started_at: Option<DateTime<Utc>>,
     pub completed_at: Option<DateTime<Utc>>,
@@ -75,6 +78,7 @@ impl FromRow for Plan {
             total_steps: row.get("total_steps")?,
             actions,
             error_message: row.get("error_message")?,
+            chained_step: row.get("chained_step")?,
             created_at: row.get("created_at")?,
             started_at: row.get("started_at")?,
             completed_at: row.get("completed_at")?,
@@ -92,6 +96,7 @@ impl Plan {
             total_steps: actions.len() as i32,
             actions,
             error_message: None,
+            chained_step: None,
             created_at: None,
             started_at: None,
             completed_at: None,
@@ -1,4 +1,4 @@
-use crate::database::{Connection, FromRow};
+use crate::database::{Connection, FromRow, to_db_time};
 use crate::plans::{Plan, PlanStatus};
 use anyhow::Result;
 use rusqlite::OptionalExtension;
@@ -42,7 +42,7 @@ pub async fn create_plan(conn: &Connection, plan: &Plan) -> Result<i64> {
 
     conn.execute(
         "INSERT INTO plans (device_uuid, status, current_step, total_steps, actions, error_message, created_at)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6, CURRENT_TIMESTAMP)",
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
         (
             plan.device_uuid,
             status_str,
@@ -50,6 +50,7 @@ pub async fn create_plan(conn: &Connection, plan: &Plan) -> Result<i64> {
             plan.total_steps,
             actions_json,
             plan.error_message.clone(),
+            to_db_time(chrono::Utc::now()),
         ),
     )
     .await?;
@@ -64,7 +65,7 @@ pub async fn get_active_plan_for_device(
     let plan = conn
         .query_row(
             "SELECT id, device_uuid, status, current_step, total_steps, actions, error_message,
-                    created_at, started_at, completed_at
+                    chained_step, created_at, started_at, completed_at
              FROM plans
              WHERE device_uuid = ?1 AND status IN ('pending', 'running')
              ORDER BY created_at DESC
@@ -85,7 +86,7 @@ pub async fn get_active_plan_for_device(
 /// a plan was cancelled, `false` if none was found (already completed, or no
 /// active plan exists).
 pub async fn cancel_active_plan_for_device(conn: &Connection, device_uuid: &Uuid) -> Result<bool> {
-    let now = chrono::Utc::now();
+    let now = to_db_time(chrono::Utc::now());
     let rows = conn
         .execute(
             "UPDATE plans SET status = 'cancelled', error_message = 'Cancelled by user', completed_at = ?1
@@ -103,6 +104,26 @@ pub async fn cancel_active_plan_for_device(conn: &Connection, device_uuid: &Uuid
     Ok(rows > 0)
 }
 
+/// Fail the device's pending or running plan with `error_message`.
+///
+/// Conditional like [`cancel_active_plan_for_device`]: returns `false` if there was
+/// no active plan, e.g. because the agent completed it concurrently.
+pub async fn fail_active_plan_for_device(
+    conn: &Connection,
+    device_uuid: &Uuid,
+    error_message: &str,
+) -> Result<bool> {
+    let now = to_db_time(chrono::Utc::now());
+    let rows = conn
+        .execute(
+            "UPDATE plans SET status = 'failed', error_message = ?1, completed_at = ?2
+             WHERE device_uuid = ?3 AND status IN ('pending', 'running')",
+            (error_message.to_string(), now, *device_uuid),
+        )
+        .await?;
+    Ok(rows > 0)
+}
+
 pub async fn update_plan_status(
     conn: &Connection,
     plan_id: i64,
@@ -111,7 +132,7 @@ pub async fn update_plan_status(
     error_message: Option<&str>,
 ) -> Result<()> {
     let status_str: String = status.clone().into();
-    let now = chrono::Utc::now();
+    let now = to_db_time(chrono::Utc::now());
     let error_owned = error_message.map(|s| s.to_string());
 
     match status {
@@ -141,6 +162,16 @@ pub async fn update_plan_status(
     Ok(())
 }
 
+/// Record that the device has been served the chain script of `step`.
+pub async fn set_chained_step(conn: &Connection, plan_id: i64, step: i32) -> Result<()> {
+    conn.execute(
+        "UPDATE plans SET chained_step = ?1 WHERE id = ?2",
+        (step, plan_id),
+    )
+    .await?;
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -237,4 +268,25 @@ mod tests {
                 .contains("an active plan already exists")
         );
     }
+
+    #[tokio::test]
+    async fn test_set_chained_step() {
+        let conn = setup(test_connection_factory!()).await;
+        let uuid = Uuid::parse_str("550e8400-e29b-41d4-a716-446655441005").unwrap();
+        register_device(&conn, uuid).await;
+
+        let plan = Plan::new(
+            uuid,
+            vec![Action::ChainScript {
+                url: "http://stages.example/discover.ipxe".to_string(),
+            }],
+        );
+        let plan_id = create_plan(&conn, &plan).await.unwrap();
+        let plan = get_active_plan_for_device(&conn, &uuid).await.unwrap();
+        assert_eq!(plan.unwrap().chained_step, None);
+
+        set_chained_step(&conn, plan_id, 0).await.unwrap();
+        let plan = get_active_plan_for_device(&conn, &uuid).await.unwrap();
+        assert_eq!(plan.unwrap().chained_step, Some(0));
+    }
 }
@@ -2,7 +2,7 @@ use super::{Platform, PlatformAttributes};
 use anyhow::{Context, Result, anyhow};
 use chrono::Utc;
 
-use crate::database::{Connection, FromRow};
+use crate::database::{Connection, FromRow, to_db_time};
 
 /// Typed error returned by [`update_disk_label`].
 ///
@@ -68,8 +68,8 @@ pub async fn create(
             description.map(|s| s.to_string()),
             attributes_json,
             firmware_mode_val,
-            now,
-            now,
+            to_db_time(now),
+            to_db_time(now),
         ),
     )
     .await
@@ -154,7 +154,7 @@ pub async fn update(
     }
 
     updates.push("updated_at = ?");
-    values.push(rusqlite::types::Value::Text(now.to_rfc3339()));
+    values.push(rusqlite::types::Value::Text(to_db_time(now)));
     values.push(rusqlite::types::Value::Integer(id));
 
     let query = format!("UPDATE platforms SET {} WHERE id = ?", updates.join(", "));
@@ -205,7 +205,7 @@ pub async fn update_disk_label(
 
     conn.execute(
         "UPDATE platforms SET attributes = ?1, updated_at = ?2 WHERE id = ?3",
-        (attributes_json, now, id),
+        (attributes_json, to_db_time(now), id),
     )
     .await
     .context("Failed to update disk label")?;
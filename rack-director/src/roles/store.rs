@@ -2,7 +2,7 @@ use super::{DiskLayout, Role};
 use anyhow::{Context, Result, anyhow};
 use chrono::Utc;
 
-use crate::database::{Connection, FromRow};
+use crate::database::{Connection, FromRow, to_db_time};
 
 /// Parameters for updating a role. All fields are optional; only `Some` values are applied.
 pub struct UpdateRoleParams<'a> {
@@ -54,8 +54,8 @@ pub async fn create(
             cmdline_args.map(|s| s.to_string()),
             config_json,
             firmware_mode_val,
-            now,
-            now,
+            to_db_time(now),
+            to_db_time(now),
         ),
     )
     .await
@@ -167,7 +167,7 @@ pub async fn update(conn: &Connection, id: i64, params: UpdateRoleParams<'_>) ->
     }
 
     updates.push("updated_at = ?");
-    values.push(rusqlite::types::Value::Text(now.to_rfc3339()));
+    values.push(rusqlite::types::Value::Text(to_db_time(now)));
     values.push(rusqlite::types::Value::Integer(id));
 
     let query = format!("UPDATE roles SET {} WHERE id = ?", updates.join(", "));
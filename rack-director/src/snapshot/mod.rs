@@ -0,0 +1,305 @@
+//! Periodic JSON snapshots of device and DHCP state.
+//!
+//! With `--snapshot-path`, a background task writes the devices, their interfaces,
+//! the active leases and the DHCP subnets to one JSON file every
+//! `--snapshot-interval-secs`, for disaster recovery and for external CMDBs to pick
+//! up. The file is written to a temporary name next to it and renamed into place,
+//! so readers never see a partial snapshot. The file is created owner-only and BMC
+//! passwords are left out of it.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio::io::AsyncWriteExt;
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+
+use crate::database::{Connection, ConnectionFactory};
+use crate::dhcp::store::{self as dhcp_store, DhcpNetwork, Lease, LeaseState};
+use crate::director::{Architecture, store as director_store};
+use crate::lifecycle::DeviceLifecycle;
+use common::device_attributes::DeviceAttributes;
+
+/// Default time between snapshots, in seconds.
+pub const DEFAULT_SNAPSHOT_INTERVAL_SECS: u64 = 3600;
+
+/// Everything written to the snapshot file.
+#[derive(Debug, Serialize)]
+pub struct Snapshot {
+    pub generated_at: DateTime<Utc>,
+    pub devices: Vec<DeviceSnapshot>,
+    pub interfaces: Vec<InterfaceSnapshot>,
+    pub leases: Vec<Lease>,
+    pub subnets: Vec<DhcpNetwork>,
+}
+
+/// A device as recorded in a [`Snapshot`].
+#[derive(Debug, Serialize)]
+pub struct DeviceSnapshot {
+    pub uuid: Uuid,
+    pub architecture: Architecture,
+    pub lifecycle: Option<DeviceLifecycle>,
+    pub role_id: Option<i64>,
+    pub platform_id: Option<i64>,
+    pub attributes: DeviceAttributes,
+    pub created_at: Option<String>,
+    pub last_seen_at: Option<String>,
+    pub notes: Option<String>,
+}
+
+/// One of a device's NICs, whether reported by the agent or only seen via DHCP.
+#[derive(Debug, Serialize)]
+pub struct InterfaceSnapshot {
+    pub device_uuid: Uuid,
+    pub mac_address: String,
+    pub ip_address: Option<String>,
+    /// When DHCP last saw the interface, if it ever has.
+    pub last_seen_at: Option<DateTime<Utc>>,
+}
+
+/// Read the current state into a [`Snapshot`].
+pub async fn build_snapshot(conn: &Connection) -> Result<Snapshot> {
+    let mut devices = Vec::new();
+    let mut interfaces = Vec::new();
+    for device in director_store::get_all_devices(conn).await? {
+        interfaces.extend(device_interfaces(conn, &device.uuid, &device.attributes).await?);
+        let mut attributes = device.attributes;
+        if let Some(bmc_config) = attributes.bmc_config.as_mut() {
+            bmc_config.password = None;
+        }
+        devices.push(DeviceSnapshot {
+            uuid: device.uuid,
+            architecture: device.architecture,
+            lifecycle: device.lifecycle,
+            role_id: device.role_id,
+            platform_id: device.platform_id,
+            attributes,
+            created_at: device.created_at,
+            last_seen_at: device.last_seen_at,
+            notes: device.notes,
+        });
+    }
+
+    let leases = dhcp_store::get_all_leases(conn)
+        .await?
+        .into_iter()
+        .filter(|lease| lease.state == LeaseState::Active && !lease.is_expired())
+        .collect();
+
+    Ok(Snapshot {
+        generated_at: Utc::now(),
+        devices,
+        interfaces,
+        leases,
+        subnets: dhcp_store::list_networks(conn).await?,
+    })
+}
+
+/// The device's interfaces: those in its attributes first, then any only DHCP has seen.
+async fn device_interfaces(
+    conn: &Connection,
+    uuid: &Uuid,
+    attributes: &DeviceAttributes,
+) -> Result<Vec<InterfaceSnapshot>> {
+    let mut last_seen = director_store::get_interface_last_seen(conn, uuid).await?;
+    let mut interfaces: Vec<InterfaceSnapshot> = attributes
+        .network_interfaces
+        .iter()
+        .map(|iface| InterfaceSnapshot {
+            device_uuid: *uuid,
+            mac_address: iface.mac_address.clone(),
+            ip_address: iface.ip_address.clone(),
+            last_seen_at: last_seen.remove(&iface.mac_address),
+        })
+        .collect();
+    let mut dhcp_only: Vec<_> = last_seen.into_iter().collect();
+    dhcp_only.sort();
+    interfaces.extend(dhcp_only.into_iter().map(|(mac, seen)| InterfaceSnapshot {
+        device_uuid: *uuid,
+        mac_address: mac,
+        ip_address: None,
+        last_seen_at: Some(seen),
+    }));
+    Ok(interfaces)
+}
+
+/// Write a snapshot of the current state to `path`, replacing it atomically.
+pub async fn write_snapshot(conn: &Connection, path: &Path) -> Result<()> {
+    let snapshot = build_snapshot(conn).await?;
+    let json = serde_json::to_vec_pretty(&snapshot)?;
+    write_atomically(path, &json).await
+}
+
+/// Write `data` to a temporary file beside `path`, flush it to disk, then rename it
+/// over `path`. The temporary file is created with mode 0600, which the snapshot keeps.
+async fn write_atomically(path: &Path, data: &[u8]) -> Result<()> {
+    let file_name = path
+        .file_name()
+        .with_context(|| format!("Snapshot path {} has no file name", path.display()))?;
+    let mut tmp_name = std::ffi::OsString::from(".");
+    tmp_name.push(file_name);
+    tmp_name.push(".tmp");
+    let tmp_path = path.with_file_name(tmp_name);
+
+    // A leftover temp file would keep its old permissions, so start from scratch
+    match tokio::fs::remove_file(&tmp_path).await {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => {
+            return Err(e).with_context(|| format!("Failed to remove {}", tmp_path.display()));
+        }
+    }
+    let mut file = tokio::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(&tmp_path)
+        .await
+        .with_context(|| format!("Failed to create {}", tmp_path.display()))?;
+    file.write_all(data).await?;
+    file.sync_all().await?;
+    drop(file);
+    tokio::fs::rename(&tmp_path, path)
+        .await
+        .with_context(|| format!("Failed to move snapshot into place at {}", path.display()))?;
+    Ok(())
+}
+
+/// Spawn a background task that writes a snapshot to `path` every `interval`,
+/// starting immediately.
+pub fn spawn_snapshot_task(
+    connection_factory: Arc<dyn ConnectionFactory>,
+    path: PathBuf,
+    interval: Duration,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let conn = connection_factory
+            .open()
+            .await
+            .expect("Failed to open database");
+        let mut interval = tokio::time::interval(interval);
+        loop {
+            interval.tick().await;
+            match write_snapshot(&conn, &path).await {
+                Ok(()) => log::debug!("Wrote state snapshot to {}", path.display()),
+                Err(e) => log::error!("Failed to write state snapshot: {:#}", e),
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{database, test_connection_factory};
+
+    #[tokio::test]
+    async fn test_snapshot_file_has_expected_keys() {
+        let factory = test_connection_factory!();
+        let conn = database::run_migrations(&factory).await.unwrap();
+        let uuid = Uuid::parse_str("f7000000-0000-0000-0000-000000000001").unwrap();
+        conn.execute(
+            "INSERT INTO devices (uuid, lifecycle, architecture) VALUES (?1, 'new', 'x86-64')",
+            (uuid,),
+        )
+        .await
+        .unwrap();
+        director_store::touch_interface(&conn, &uuid, "aa:bb:cc:dd:ee:01")
+            .await
+            .unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.json");
+        write_snapshot(&conn, &path).await.unwrap();
+
+        let json: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        for key in ["generated_at", "devices", "interfaces", "leases", "subnets"] {
+            assert!(json.get(key).is_some(), "snapshot is missing {key}");
+        }
+        assert_eq!(json["devices"][0]["uuid"], uuid.to_string());
+        assert_eq!(json["interfaces"][0]["mac_address"], "aa:bb:cc:dd:ee:01");
+
+        // Only the snapshot itself is left behind
+        let names: Vec<_> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(names, ["state.json"]);
+
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_omits_bmc_password_and_expired_leases() {
+        let factory = test_connection_factory!();
+        let conn = database::run_migrations(&factory).await.unwrap();
+        let uuid = Uuid::parse_str("f7000000-0000-0000-0000-000000000002").unwrap();
+        let attributes = serde_json::json!({
+            "bmc_config": {
+                "ip_address_source": "dhcp",
+                "username": "admin",
+                "password": "hunter2",
+            }
+        });
+        conn.execute(
+            "INSERT INTO devices (uuid, lifecycle, architecture, attributes) VALUES (?1, 'new', 'x86-64', ?2)",
+            (uuid, attributes.to_string()),
+        )
+        .await
+        .unwrap();
+
+        let network = dhcp_store::create_network(
+            &conn,
+            "Net",
+            "10.0.0.0/24",
+            "10.0.0.1",
+            &[],
+            86400,
+            None,
+            false,
+        )
+        .await
+        .unwrap();
+        for (mac, ip) in [
+            ("aa:bb:cc:dd:ee:01", "10.0.0.10"),
+            ("aa:bb:cc:dd:ee:02", "10.0.0.11"),
+        ] {
+            dhcp_store::create_or_update_lease_with_network(
+                &conn,
+                mac,
+                &ip.parse().unwrap(),
+                None,
+                LeaseState::Active,
+                3600,
+                network.id,
+            )
+            .await
+            .unwrap();
+        }
+        conn.execute(
+            "UPDATE dhcp_leases SET lease_end = ?1 WHERE mac_address = 'aa:bb:cc:dd:ee:02'",
+            ((Utc::now() - chrono::Duration::hours(1)).to_rfc3339(),),
+        )
+        .await
+        .unwrap();
+
+        let snapshot = build_snapshot(&conn).await.unwrap();
+
+        let bmc_config = snapshot.devices[0].attributes.bmc_config.as_ref().unwrap();
+        assert_eq!(bmc_config.username.as_deref(), Some("admin"));
+        assert_eq!(bmc_config.password, None);
+        let macs: Vec<_> = snapshot
+            .leases
+            .iter()
+            .map(|lease| lease.mac_address.as_str())
+            .collect();
+        assert_eq!(macs, ["aa:bb:cc:dd:ee:01"]);
+    }
+}
@@ -1,14 +1,43 @@
+use futures::FutureExt;
 use log::{debug, trace};
-use std::{fmt::Display, net::SocketAddr, sync::Arc, time::Duration};
+use std::{
+    any::Any,
+    fmt::Display,
+    net::SocketAddr,
+    panic::AssertUnwindSafe,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 use tokio::{net::UdpSocket, time::timeout};
 
 use crate::tftp::{
     Handler,
-    packet::Packet,
+    packet::{self, Packet},
     state::{ControlFlow, State},
+    status::{TransferGuard, TransferRegistry},
 };
 
-const DEFAULT_TIMEOUT_MILLIS: u64 = 2000;
+const DEFAULT_BLOCK_TIMEOUT: Duration = Duration::from_millis(2000);
+const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Timers governing a single transfer.
+#[derive(Debug, Clone, Copy)]
+pub struct Timeouts {
+    /// How long to wait for the client's next packet before retransmitting.
+    pub block: Duration,
+    /// How long the transfer may go without hearing from the client before it is
+    /// closed, whether or not retransmits remain.
+    pub idle: Duration,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Self {
+            block: DEFAULT_BLOCK_TIMEOUT,
+            idle: DEFAULT_IDLE_TIMEOUT,
+        }
+    }
+}
 
 #[derive(Debug)]
 pub enum Error {
@@ -41,99 +70,115 @@ pub struct Connection<H: Handler> {
     addr: SocketAddr,
     socket: UdpSocket,
     state: State<H>,
+    transfer: Option<TransferGuard>,
 }
 
 impl<H: Handler + 'static> Connection<H> {
     // TFTP handles each connection with a separate port. Accept will bind a new UDP port
     // and create a new State for the connection.
-    pub async fn accept(handler: Arc<H>, addr: SocketAddr, packet: Packet) -> Result<(), Error> {
+    pub async fn accept(
+        handler: Arc<H>,
+        addr: SocketAddr,
+        packet: Packet,
+        timeouts: Timeouts,
+        transfers: TransferRegistry,
+    ) -> Result<(), Error> {
         let socket = UdpSocket::bind("0.0.0.0:0").await?;
         socket.connect(addr).await?;
         debug!("Accepted connection from {addr}");
 
+        let transfer = match &packet {
+            Packet::Rrq { filename, .. } => Some(transfers.register(addr, filename)),
+            _ => None,
+        };
         let mut connection = Self {
             addr,
             socket,
             state: State::new(addr, handler),
+            transfer,
         };
 
         // Handle the initial packet
-        match connection.handle(packet).await {
-            Ok(_) => {
-                // Continue processing packets
-            }
-            Err(Error::ConnectionClosed) => {
-                debug!("Connection closed for {}", connection.addr);
-                return Ok(());
-            }
-            Err(e) => {
-                debug!("Error handling packet: {e}");
-                return Err(e);
-            }
+        let result = connection.handle(packet).await;
+        if let Some(done) = connection.finished(result) {
+            return done;
         }
 
+        connection.run(timeouts).await
+    }
+
+    // Main receive loop. Retransmits every `timeouts.block` without a reply and closes
+    // once `timeouts.idle` has passed since the client was last heard from.
+    async fn run(&mut self, timeouts: Timeouts) -> Result<(), Error> {
         let mut buf = [0; 512]; // TFTP packets can be up to 512 bytes
-        // Start the main loop to handle incoming packets
+        let mut last_heard = Instant::now();
         loop {
-            match timeout(
-                Duration::from_millis(DEFAULT_TIMEOUT_MILLIS),
-                connection.socket.recv(&mut buf),
-            )
-            .await
-            {
-                // Received a packet
-                Ok(Ok(size)) => {
-                    let packet =
-                        Packet::parse(&buf[..size]).map_err(|e| Error::Parse(e.to_string()))?;
-
-                    match connection.handle(packet).await {
-                        Ok(_) => {
-                            // Continue processing packets
-                        }
-                        Err(Error::ConnectionClosed) => {
-                            debug!("Connection closed for {}", connection.addr);
-                            return Ok(());
-                        }
-                        Err(e) => {
-                            debug!("Error handling packet: {e}");
-                            return Err(e);
-                        }
+            let remaining = timeouts.idle.saturating_sub(last_heard.elapsed());
+            if remaining.is_zero() {
+                debug!("Closing idle connection for {}", self.addr);
+                return Ok(());
+            }
+
+            let result =
+                match timeout(timeouts.block.min(remaining), self.socket.recv(&mut buf)).await {
+                    // Received a packet
+                    Ok(Ok(size)) => {
+                        last_heard = Instant::now();
+                        let packet =
+                            Packet::parse(&buf[..size]).map_err(|e| Error::Parse(e.to_string()))?;
+                        self.handle(packet).await
                     }
-                }
-                // Socket closed or had an error
-                Ok(Err(e)) => {
-                    debug!("Error receiving packet: {e}");
-                    return Err(Error::Send(e));
-                }
-                // Timeout occurred
-                Err(_) => {
-                    match connection.timeout().await {
-                        Ok(_) => {
-                            // Continue processing packets
-                        }
-                        Err(Error::ConnectionClosed) => {
-                            debug!("Connection closed for {}", connection.addr);
-                            return Ok(());
-                        }
-                        Err(e) => {
-                            debug!("Error handling timeout: {e}");
-                            return Err(e);
-                        }
+                    // Socket closed or had an error
+                    Ok(Err(e)) => {
+                        debug!("Error receiving packet: {e}");
+                        return Err(Error::Send(e));
                     }
-                }
+                    // Idle deadline reached; the top of the loop closes the connection
+                    Err(_) if last_heard.elapsed() >= timeouts.idle => continue,
+                    // Block timeout occurred
+                    Err(_) => self.timeout().await,
+                };
+
+            if let Some(done) = self.finished(result) {
+                return done;
+            }
+        }
+    }
+
+    // Map the outcome of handling a packet or timeout to the connection's final result,
+    // or None if the transfer should continue.
+    fn finished(&self, result: Result<(), Error>) -> Option<Result<(), Error>> {
+        match result {
+            Ok(()) => None,
+            Err(Error::ConnectionClosed) => {
+                debug!("Connection closed for {}", self.addr);
+                Some(Ok(()))
+            }
+            Err(e) => {
+                debug!("Error handling packet: {e}");
+                Some(Err(e))
             }
         }
     }
 
     async fn handle(&mut self, packet: Packet) -> std::result::Result<(), Error> {
         trace!("TFTP: Handling packet {:?}", packet);
-        let control_flow = self.state.handle(packet).await;
+        // Handlers and readers are arbitrary code; a panic in one must still answer
+        // the client instead of silently killing the transfer task.
+        let control_flow = match AssertUnwindSafe(self.state.handle(packet))
+            .catch_unwind()
+            .await
+        {
+            Ok(control_flow) => control_flow,
+            Err(panic) => self.panicked(panic),
+        };
         match control_flow {
             ControlFlow::Continue(packet) => {
                 trace!("TFTP: Sending packet to {}: {:?}", self.addr, packet);
                 // Send the response packet back to the client
-                self.socket.send(&packet.to_bytes()).await?;
+                self.send(&packet).await?;
             }
+            ControlFlow::Ignore => trace!("TFTP: Nothing to send to {}", self.addr),
             ControlFlow::Closed(packet_opt) => {
                 if let Some(packet) = packet_opt {
                     trace!(
@@ -141,7 +186,7 @@ impl<H: Handler + 'static> Connection<H> {
                         self.addr, packet
                     );
                     // Send the final packet before closing
-                    self.socket.send(&packet.to_bytes()).await?;
+                    self.send(&packet).await?;
                 } else {
                     trace!("TFTP: Closed");
                 }
@@ -156,13 +201,21 @@ impl<H: Handler + 'static> Connection<H> {
     async fn timeout(&mut self) -> std::result::Result<(), Error> {
         // Handle timeout logic, e.g., retransmitting packets or closing the connection
         debug!("Handling timeout for connection {}", self.addr);
-        match self.state.handle_timeout().await {
+        let control_flow = match AssertUnwindSafe(self.state.handle_timeout())
+            .catch_unwind()
+            .await
+        {
+            Ok(control_flow) => control_flow,
+            Err(panic) => self.panicked(panic),
+        };
+        match control_flow {
             ControlFlow::Continue(packet) => {
-                self.socket.send(&packet.to_bytes()).await?;
+                self.send(&packet).await?;
             }
+            ControlFlow::Ignore => {}
             ControlFlow::Closed(packet_opt) => {
                 if let Some(packet) = packet_opt {
-                    self.socket.send(&packet.to_bytes()).await?;
+                    self.send(&packet).await?;
                 }
                 // Close the connection
                 return Err(Error::ConnectionClosed);
@@ -170,4 +223,36 @@ impl<H: Handler + 'static> Connection<H> {
         }
         Ok(())
     }
+
+    // Log a panic caught while handling a packet or timeout and close the transfer
+    // with an error packet. The state may be half-updated, so it is not reused.
+    fn panicked(&self, panic: Box<dyn Any + Send>) -> ControlFlow {
+        let message = panic
+            .downcast_ref::<&str>()
+            .copied()
+            .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+            .unwrap_or("non-string panic payload");
+        log::error!(
+            "TFTP: Handler panicked serving {}{}: {}",
+            self.addr,
+            self.transfer
+                .as_ref()
+                .map(|t| format!(" ({})", t.filename()))
+                .unwrap_or_default(),
+            message
+        );
+        ControlFlow::Closed(Some(Packet::Error {
+            code: packet::Error::Undefined,
+            message: String::from("internal error occured"),
+        }))
+    }
+
+    // Send a packet to the client, recording DATA progress for the transfer registry.
+    async fn send(&self, packet: &Packet) -> std::result::Result<(), Error> {
+        self.socket.send(&packet.to_bytes()).await?;
+        if let (Some(transfer), Packet::Data { block, data }) = (&self.transfer, packet) {
+            transfer.record_block(*block, data.len());
+        }
+        Ok(())
+    }
 }
@@ -13,10 +13,13 @@ use crate::tftp::{connection::Connection, packet::Packet};
 
 mod connection;
 mod options;
-mod packet;
+pub(crate) mod packet;
 mod state;
+mod status;
+pub use connection::Timeouts;
 pub use state::Handler;
 pub use state::Reader;
+pub use status::{TransferRegistry, TransferStatus};
 
 pub struct StartResult {
     pub join_handle: JoinHandle<Result<()>>,
@@ -26,6 +29,8 @@ pub struct StartResult {
 pub struct Server<H: Handler> {
     address: SocketAddr,
     handler: Arc<H>,
+    timeouts: Timeouts,
+    transfers: TransferRegistry,
 }
 
 impl<H: Handler + Send + Sync + 'static> Server<H> {
@@ -33,6 +38,8 @@ impl<H: Handler + Send + Sync + 'static> Server<H> {
         Self {
             address: SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 69).into(),
             handler,
+            timeouts: Timeouts::default(),
+            transfers: TransferRegistry::default(),
         }
     }
 
@@ -42,10 +49,21 @@ impl<H: Handler + Send + Sync + 'static> Server<H> {
         self
     }
 
+    /// Set the per-block retransmit interval and the idle deadline for transfers.
+    pub fn timeouts(&mut self, timeouts: Timeouts) -> &mut Self {
+        self.timeouts = timeouts;
+        self
+    }
+
+    /// Handle to the registry of in-flight transfers served by this server.
+    pub fn transfers(&self) -> TransferRegistry {
+        self.transfers.clone()
+    }
+
     pub async fn serve(self) -> Result<StartResult> {
         let socket = tokio::net::UdpSocket::bind(self.address).await?;
         let port = socket.local_addr()?.port();
-        let join_handle = tokio::spawn(serve(socket, self.handler));
+        let join_handle = tokio::spawn(serve(socket, self.handler, self.timeouts, self.transfers));
         Ok(StartResult { join_handle, port })
     }
 }
@@ -53,6 +71,8 @@ impl<H: Handler + Send + Sync + 'static> Server<H> {
 async fn serve<H: Handler + Send + Sync + 'static>(
     socket: UdpSocket,
     handler: Arc<H>,
+    timeouts: Timeouts,
+    transfers: TransferRegistry,
 ) -> Result<()> {
     let arc_socket = Arc::new(socket);
     let mut buf: [u8; 512] = [0; 512];
@@ -62,23 +82,41 @@ async fn serve<H: Handler + Send + Sync + 'static>(
     );
     loop {
         let (size, addr) = arc_socket.recv_from(&mut buf).await?;
-        let packet = Packet::parse(&buf[0..size])?;
+        // One bad datagram must not take the listener down with it
+        let packet = match Packet::parse(&buf[0..size]) {
+            Ok(packet) => packet,
+            Err(e) => {
+                log::warn!("Ignoring malformed TFTP packet from {}: {}", addr, e);
+                continue;
+            }
+        };
         log::info!("TFTP {:?}", packet);
-        tokio::spawn(Connection::accept(handler.clone(), addr, packet));
+        tokio::spawn(Connection::accept(
+            handler.clone(),
+            addr,
+            packet,
+            timeouts,
+            transfers.clone(),
+        ));
     }
 }
 
+/// Default size of the disk reads behind a [`TftpReader`], in bytes.
+pub const DEFAULT_READ_CHUNK: u64 = 64 * 1024;
+
 /// TFTP-specific file reader that reads files in chunks.
 ///
-/// This reader wraps a tokio BufReader and provides chunk-based reading
-/// suitable for TFTP block transfers.
+/// Each [`Reader::read`] returns exactly one block of the negotiated size (less at
+/// end of file). Blocks are served from a buffer refilled by disk reads of a whole
+/// number of blocks, so a block never straddles two disk reads. Only that buffer is
+/// held in memory, so file size does not affect memory use.
 pub struct TftpReader {
     file: BufReader<tokio::fs::File>,
     block_size: u64,
 }
 
 impl TftpReader {
-    /// Open a file for TFTP reading.
+    /// Open a file for TFTP reading with [`DEFAULT_READ_CHUNK`] disk reads.
     ///
     /// # Arguments
     ///
@@ -93,14 +131,34 @@ impl TftpReader {
     ///
     /// Returns an error if the file cannot be opened.
     pub async fn open(path: &Path, block_size: u64) -> Result<Self> {
+        Self::open_with_read_chunk(path, block_size, DEFAULT_READ_CHUNK).await
+    }
+
+    /// Open a file for TFTP reading, reading about `read_chunk` bytes from disk at a
+    /// time. See [`read_buffer_size`] for how that is aligned to `block_size`.
+    pub async fn open_with_read_chunk(
+        path: &Path,
+        block_size: u64,
+        read_chunk: u64,
+    ) -> Result<Self> {
         let file = tokio::fs::File::open(path).await?;
+        let file_len = file.metadata().await?.len();
+        let capacity = read_buffer_size(block_size, read_chunk, file_len);
         Ok(TftpReader {
-            file: BufReader::new(file),
+            file: BufReader::with_capacity(capacity, file),
             block_size,
         })
     }
 }
 
+/// Size of the disk read buffer for a transfer: `read_chunk` rounded down to a
+/// whole number of blocks (at least one), and no more than the file needs.
+pub fn read_buffer_size(block_size: u64, read_chunk: u64, file_len: u64) -> usize {
+    let block_size = block_size.max(1);
+    let aligned = (read_chunk / block_size).max(1) * block_size;
+    aligned.min(file_len) as usize
+}
+
 impl Reader for TftpReader {
     async fn read(&mut self) -> Result<Vec<u8>> {
         let mut buffered: usize = 0;
@@ -129,6 +187,7 @@ mod tests {
 
     use super::*;
     use anyhow::Result;
+    use std::time::Duration;
 
     // Helper function to start a test TFTP server.
     //
@@ -136,9 +195,17 @@ mod tests {
     // Returns the server's listening port and join handle for cleanup.
     async fn start_test_server<H: Handler + Send + Sync + 'static>(
         handler: H,
+    ) -> Result<(u16, JoinHandle<Result<()>>)> {
+        start_test_server_with_timeouts(handler, Timeouts::default()).await
+    }
+
+    async fn start_test_server_with_timeouts<H: Handler + Send + Sync + 'static>(
+        handler: H,
+        timeouts: Timeouts,
     ) -> Result<(u16, JoinHandle<Result<()>>)> {
         let mut server = Server::new(Arc::new(handler));
         server.address(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0).into());
+        server.timeouts(timeouts);
         let result = server.serve().await?;
         Ok((result.port, result.join_handle))
     }
@@ -184,7 +251,12 @@ mod tests {
     impl Handler for TestHandler {
         type Reader = TestReader;
 
-        async fn create_reader(&self, _filename: &str, block_size: u64) -> Result<Self::Reader> {
+        async fn create_reader(
+            &self,
+            _client: SocketAddr,
+            _filename: &str,
+            block_size: u64,
+        ) -> Result<Self::Reader> {
             Ok(TestReader {
                 data: self
                     .data
@@ -195,8 +267,8 @@ mod tests {
             })
         }
 
-        async fn filesize(&self, _filename: &str) -> Result<u64> {
-            Ok(self.data.len() as u64)
+        async fn filesize(&self, _client: SocketAddr, _filename: &str) -> Result<Option<u64>> {
+            Ok(Some(self.data.len() as u64))
         }
     }
 
@@ -223,15 +295,48 @@ mod tests {
     impl Handler for ErrorHandler {
         type Reader = TestReader;
 
-        async fn create_reader(&self, _filename: &str, _block_size: u64) -> Result<Self::Reader> {
+        async fn create_reader(
+            &self,
+            _client: SocketAddr,
+            _filename: &str,
+            _block_size: u64,
+        ) -> Result<Self::Reader> {
             Err(anyhow::anyhow!("File not found"))
         }
 
-        async fn filesize(&self, _filename: &str) -> Result<u64> {
+        async fn filesize(&self, _client: SocketAddr, _filename: &str) -> Result<Option<u64>> {
             Err(anyhow::anyhow!("File not found"))
         }
     }
 
+    // Test handler whose readers panic on the first read.
+    struct PanicHandler;
+
+    struct PanicReader;
+
+    impl Reader for PanicReader {
+        async fn read(&mut self) -> Result<Vec<u8>> {
+            panic!("reader bug");
+        }
+    }
+
+    impl Handler for PanicHandler {
+        type Reader = PanicReader;
+
+        async fn create_reader(
+            &self,
+            _client: SocketAddr,
+            _filename: &str,
+            _block_size: u64,
+        ) -> Result<Self::Reader> {
+            Ok(PanicReader)
+        }
+
+        async fn filesize(&self, _client: SocketAddr, _filename: &str) -> Result<Option<u64>> {
+            Ok(Some(512))
+        }
+    }
+
     // Helper function to get the transfer port used by the server.
     //
     // Sends an RRQ to the server and extracts the source port from the first DATA packet.
@@ -304,6 +409,28 @@ mod tests {
         Ok(())
     }
 
+    /// Test that a malformed initial packet is skipped rather than ending the serve loop.
+    #[tokio::test]
+    async fn test_malformed_packet_does_not_stop_server() -> Result<()> {
+        let handler = TestHandler::new(vec![0u8; 100]);
+        let (listening_port, join_handle) = start_test_server(handler).await?;
+
+        let client_socket = tokio::net::UdpSocket::bind("127.0.0.1:0").await?;
+        client_socket
+            .send_to(
+                &[0xde, 0xad, 0xbe, 0xef, 0x00],
+                format!("127.0.0.1:{}", listening_port),
+            )
+            .await?;
+
+        // The next valid request is still served
+        get_transfer_port(listening_port, "test.txt").await?;
+        assert!(!join_handle.is_finished());
+
+        join_handle.abort();
+        Ok(())
+    }
+
     /// Test that multiple concurrent transfers each get unique ports.
     ///
     /// RFC 1350 requires each concurrent transfer to have its own TID.
@@ -440,4 +567,235 @@ mod tests {
 
         Ok(())
     }
+
+    // Send an RRQ for `filename` without options and return the client socket.
+    async fn send_rrq(server_port: u16, filename: &str) -> Result<tokio::net::UdpSocket> {
+        let client_socket = tokio::net::UdpSocket::bind("127.0.0.1:0").await?;
+        let rrq = Packet::Rrq {
+            filename: filename.to_string(),
+            mode: "octet".to_string(),
+            options: Vec::new(),
+        };
+        client_socket
+            .send_to(&rrq.to_bytes(), format!("127.0.0.1:{}", server_port))
+            .await?;
+        Ok(client_socket)
+    }
+
+    /// A client that answers slower than the block timeout, but well within the idle
+    /// deadline, still completes its transfer (retransmits are tolerated).
+    #[tokio::test]
+    async fn test_slow_client_within_idle_deadline_completes() -> Result<()> {
+        let data: Vec<u8> = (0..1100).map(|i| i as u8).collect();
+        let timeouts = Timeouts {
+            block: Duration::from_millis(200),
+            idle: Duration::from_secs(2),
+        };
+        let (listening_port, join_handle) =
+            start_test_server_with_timeouts(TestHandler::new(data.clone()), timeouts).await?;
+        let client_socket = send_rrq(listening_port, "slow.bin").await?;
+
+        let mut received = Vec::new();
+        let mut expected_block = 1;
+        loop {
+            let mut buf = [0u8; 516];
+            let (size, addr) =
+                tokio::time::timeout(Duration::from_secs(2), client_socket.recv_from(&mut buf))
+                    .await??;
+            let Packet::Data { block, data: chunk } = Packet::parse(&buf[..size])? else {
+                panic!("Expected DATA packet");
+            };
+            if block < expected_block {
+                // Retransmit caused by our slow ACK
+                continue;
+            }
+            assert_eq!(block, expected_block);
+            received.extend_from_slice(&chunk);
+
+            // Answer later than the block timeout
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            client_socket
+                .send_to(&Packet::Ack { block }.to_bytes(), addr)
+                .await?;
+            if chunk.len() < 512 {
+                break;
+            }
+            expected_block += 1;
+        }
+
+        join_handle.abort();
+        assert_eq!(received, data);
+        Ok(())
+    }
+
+    /// A client that stops responding is dropped at the idle deadline, even though
+    /// the block timeout has not yet fired a single retransmit.
+    #[tokio::test]
+    async fn test_dead_client_closed_at_idle_deadline() -> Result<()> {
+        let timeouts = Timeouts {
+            block: Duration::from_millis(1000),
+            idle: Duration::from_millis(200),
+        };
+        let (listening_port, join_handle) =
+            start_test_server_with_timeouts(TestHandler::new(vec![0u8; 1024]), timeouts).await?;
+        let client_socket = send_rrq(listening_port, "dead.bin").await?;
+
+        let mut buf = [0u8; 516];
+        let (size, _) =
+            tokio::time::timeout(Duration::from_secs(1), client_socket.recv_from(&mut buf))
+                .await??;
+        assert!(matches!(
+            Packet::parse(&buf[..size])?,
+            Packet::Data { block: 1, .. }
+        ));
+
+        // Never ACK. A live connection would retransmit block 1 after one second.
+        let retransmit = tokio::time::timeout(
+            Duration::from_millis(1500),
+            client_socket.recv_from(&mut buf),
+        )
+        .await;
+        assert!(
+            retransmit.is_err(),
+            "Connection should have closed at the idle deadline without retransmitting"
+        );
+
+        join_handle.abort();
+        Ok(())
+    }
+
+    /// A transfer shows up in the registry while it is in flight and is removed
+    /// once the client has acknowledged the final block.
+    #[tokio::test]
+    async fn test_active_transfer_appears_and_disappears() -> Result<()> {
+        let mut server = Server::new(Arc::new(TestHandler::new(vec![7u8; 600])));
+        server.address(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0).into());
+        let transfers = server.transfers();
+        let result = server.serve().await?;
+        let client_socket = send_rrq(result.port, "status.bin").await?;
+
+        let mut buf = [0u8; 516];
+        let (_, addr) =
+            tokio::time::timeout(Duration::from_secs(1), client_socket.recv_from(&mut buf))
+                .await??;
+
+        let active = transfers.snapshot();
+        assert_eq!(active.len(), 1);
+        assert_eq!(active[0].filename, "status.bin");
+        assert_eq!(active[0].client, client_socket.local_addr()?);
+        assert_eq!(active[0].block, 1);
+        assert_eq!(active[0].bytes_sent, 512);
+
+        // ACK block 1, receive the short final block 2 and ACK it to finish
+        client_socket
+            .send_to(&Packet::Ack { block: 1 }.to_bytes(), addr)
+            .await?;
+        tokio::time::timeout(Duration::from_secs(1), client_socket.recv_from(&mut buf)).await??;
+        client_socket
+            .send_to(&Packet::Ack { block: 2 }.to_bytes(), addr)
+            .await?;
+
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(1);
+        while !transfers.snapshot().is_empty() && tokio::time::Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        result.join_handle.abort();
+        assert!(transfers.snapshot().is_empty());
+        assert_eq!(transfers.total_bytes_sent(), 600);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_reader_streams_large_file_in_blocks() -> Result<()> {
+        // Sparse file: large on paper, cheap on disk
+        const FILE_SIZE: u64 = 64 * 1024 * 1024 + 100;
+        const BLOCK_SIZE: u64 = 8192;
+        let file = tempfile::NamedTempFile::new()?;
+        file.as_file().set_len(FILE_SIZE)?;
+
+        let mut reader = TftpReader::open(file.path(), BLOCK_SIZE).await?;
+        let mut total = 0u64;
+        loop {
+            let chunk = reader.read().await?;
+            assert!(chunk.len() as u64 <= BLOCK_SIZE);
+            total += chunk.len() as u64;
+            if (chunk.len() as u64) < BLOCK_SIZE {
+                break;
+            }
+        }
+
+        assert_eq!(total, FILE_SIZE);
+        assert!(reader.read().await?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_buffer_size_aligns_to_blocks() {
+        assert_eq!(read_buffer_size(512, DEFAULT_READ_CHUNK, u64::MAX), 65536);
+        // 45 blocks of 1428 bytes, not a partial 46th
+        assert_eq!(read_buffer_size(1428, DEFAULT_READ_CHUNK, u64::MAX), 64260);
+        // Never less than one block
+        assert_eq!(read_buffer_size(8192, 100, u64::MAX), 8192);
+        // Small files don't get a full-size buffer
+        assert_eq!(read_buffer_size(512, DEFAULT_READ_CHUNK, 3000), 3000);
+    }
+
+    #[tokio::test]
+    async fn test_reader_reads_match_non_default_block_size() -> Result<()> {
+        const BLOCK_SIZE: u64 = 1428;
+        let file = tempfile::NamedTempFile::new()?;
+        let contents: Vec<u8> = (0..5000u32).map(|i| i as u8).collect();
+        std::fs::write(file.path(), &contents)?;
+
+        let mut reader = TftpReader::open_with_read_chunk(file.path(), BLOCK_SIZE, 4096).await?;
+        let mut sizes = Vec::new();
+        let mut read_back = Vec::new();
+        loop {
+            let chunk = reader.read().await?;
+            sizes.push(chunk.len());
+            read_back.extend_from_slice(&chunk);
+            if (chunk.len() as u64) < BLOCK_SIZE {
+                break;
+            }
+        }
+
+        assert_eq!(sizes, vec![1428, 1428, 1428, 716]);
+        assert_eq!(read_back, contents);
+        Ok(())
+    }
+
+    /// A panicking reader answers the client with an ERROR packet instead of
+    /// leaving it to time out, and the transfer is deregistered.
+    #[tokio::test]
+    async fn test_reader_panic_sends_error_packet() -> Result<()> {
+        let mut server = Server::new(Arc::new(PanicHandler));
+        server.address(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0).into());
+        let transfers = server.transfers();
+        let result = server.serve().await?;
+
+        let client = send_rrq(result.port, "panic.bin").await?;
+        let mut buf = [0u8; 516];
+        let size = tokio::time::timeout(
+            tokio::time::Duration::from_millis(1000),
+            client.recv(&mut buf),
+        )
+        .await??;
+
+        assert!(
+            matches!(
+                Packet::parse(&buf[..size])?,
+                Packet::Error {
+                    code: packet::Error::Undefined,
+                    ..
+                }
+            ),
+            "Expected ERROR Undefined packet"
+        );
+        tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+        assert!(transfers.snapshot().is_empty());
+
+        result.join_handle.abort();
+        Ok(())
+    }
 }
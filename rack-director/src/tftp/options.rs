@@ -6,6 +6,9 @@ pub enum Error {
 pub enum TftpOption {
     TSize(u64),
     BlkSize(u64),
+    /// RFC 2090 multicast (MTFTP) request. Only recognized so it can be declined;
+    /// the value is kept verbatim (empty in requests).
+    Multicast(String),
     Unrecognized(String, String),
 }
 
@@ -20,6 +23,7 @@ impl TftpOption {
                 let v: u64 = value.as_ref().parse().map_err(|_| Error::InvalidValue)?;
                 Ok(TftpOption::BlkSize(v))
             }
+            "multicast" => Ok(TftpOption::Multicast(value.as_ref().to_owned())),
             _ => Ok(TftpOption::Unrecognized(
                 key.as_ref().to_owned(),
                 value.as_ref().to_owned(),
@@ -31,6 +35,7 @@ impl TftpOption {
         match self {
             TftpOption::TSize(v) => ("tsize", v.to_string()),
             TftpOption::BlkSize(v) => ("blksize", v.to_string()),
+            TftpOption::Multicast(v) => ("multicast", v.to_owned()),
             TftpOption::Unrecognized(key, value) => (key, value.to_owned()),
         }
     }
@@ -260,14 +260,15 @@ fn parse_options(mut data: &[u8]) -> Result<Vec<TftpOption>> {
     while !data.is_empty() {
         let (key, remainder) = read_string(data)?;
         let (value, remainder) = read_string(remainder)?;
+        data = remainder;
 
         // Normalize key to lowercase for case-insensitive comparison
         let key_lower = key.to_lowercase();
 
-        let option = match TftpOption::from_pair(key_lower, value) {
+        let option = match TftpOption::from_pair(key_lower.as_str(), value.as_str()) {
             Ok(option) => option,
             Err(TftpError::InvalidValue) => {
-                log::warn!("TFTP: Invalid value parsing options");
+                log::warn!("TFTP: Invalid value {:?} for option {}", value, key);
                 continue;
             }
         };
@@ -278,7 +279,6 @@ fn parse_options(mut data: &[u8]) -> Result<Vec<TftpOption>> {
         }
 
         options.push(option);
-        data = remainder;
     }
 
     Ok(options)
@@ -644,4 +644,29 @@ mod tests {
 
         assert_eq!(packet, parsed);
     }
+
+    #[test]
+    fn test_parse_skips_invalid_option_value() {
+        // Previously looped forever: the skipped option was never consumed
+        let bytes = b"\x00\x01pxelinux.0\x00octet\x00blksize\x00abc\x00tsize\x000\x00";
+        let parsed = Packet::parse(bytes).unwrap();
+
+        assert_eq!(
+            parsed,
+            Packet::Rrq {
+                filename: "pxelinux.0".to_string(),
+                mode: "octet".to_string(),
+                options: vec![TftpOption::TSize(0)],
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_rrq_multicast_option() {
+        let bytes = b"\x00\x01pxelinux.0\x00octet\x00MULTICAST\x00\x00";
+        let Packet::Rrq { options, .. } = Packet::parse(bytes).unwrap() else {
+            panic!("expected RRQ");
+        };
+        assert_eq!(options, vec![TftpOption::Multicast(String::new())]);
+    }
 }
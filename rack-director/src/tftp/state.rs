@@ -22,15 +22,23 @@
 //!
 //! ## Current Implementation Status
 //!
-//! The framework for option negotiation is fully implemented:
 //! - Option parsing from RRQ/WRQ packets (case-insensitive, RFC 2347 compliant)
-//! - OACK packet generation and transmission
-//! - OptionNegotiation state for waiting on ACK block 0
-//! - Timeout handling during option negotiation
-//! - Error handling for option negotiation failures
+//! - `blksize` and `tsize` are negotiated; anything else is left out of the OACK
+//! - OptionNegotiation state for waiting on ACK block 0, retransmitting the OACK on
+//!   timeout
 //!
-//! However, no specific options (blksize, tsize, etc.) are currently recognized.
-//! The `negotiate_options` function returns an empty map for all inputs.
+//! Once data flows, an ACK for block 0 is told apart by how the transfer started.
+//! After an OACK it is a duplicate of the client's acceptance and is treated like
+//! any late ACK. Without one nothing was ever sent as block 0, so it is ignored
+//! rather than answered with data. After block numbers wrap, block 0 is ordinary
+//! data.
+//!
+//! ## Multicast (RFC 2090)
+//!
+//! Some PXE ROMs ask for MTFTP first. The `multicast` option is recognized and
+//! logged but never acknowledged; leaving it out of the OACK (or answering with
+//! DATA when nothing else was negotiated) is how RFC 2090 says a server declines,
+//! and the client falls back to unicast.
 //!
 //! To add support for a specific option (e.g., blksize):
 //! 1. Update `negotiate_options` to recognize and validate the option
@@ -39,7 +47,7 @@
 
 use std::{net::SocketAddr, sync::Arc};
 
-use log::{debug, warn};
+use log::{debug, info, warn};
 
 use crate::tftp::{
     options::TftpOption,
@@ -49,16 +57,36 @@ use anyhow::Result;
 
 const DEFAULT_MAX_RETRIES: u8 = 4;
 
+/// Source of the files a TFTP server serves.
+///
+/// Both methods receive the requesting client's address so a handler can serve
+/// per-client content (e.g. `pxelinux.cfg/<hex-ip>`) or log who asked.
 pub trait Handler {
     type Reader: Reader + Send + Sync;
     fn create_reader(
         &self,
+        client: SocketAddr,
         filename: &str,
         block_size: u64,
     ) -> impl Future<Output = Result<Self::Reader>> + Send;
-    fn filesize(&self, filename: &str) -> impl Future<Output = Result<u64>> + Send;
+    /// Size of `filename` in bytes, answered in the `tsize` option.
+    ///
+    /// Returns `None` for content whose length isn't known up front (generated or
+    /// streamed files); the OACK then leaves `tsize` out rather than guessing.
+    fn filesize(
+        &self,
+        client: SocketAddr,
+        filename: &str,
+    ) -> impl Future<Output = Result<Option<u64>>> + Send;
 }
 
+/// Source of file data for one transfer.
+///
+/// Readers must stream: each `read` returns the next block of at most
+/// `block_size` bytes (as given to `Handler::create_reader`), and a short or empty
+/// block marks end of file. Implementations must not load the whole file into
+/// memory, since boot images can be hundreds of megabytes and many clients may
+/// transfer at once.
 pub trait Reader {
     fn read(&mut self) -> impl Future<Output = Result<Vec<u8>>> + Send;
 }
@@ -67,9 +95,22 @@ pub trait Reader {
 #[derive(Debug)]
 pub enum ControlFlow {
     Continue(Packet),
+    /// Keep the transfer open without sending anything (e.g. for a stale ACK).
+    Ignore,
     Closed(Option<Packet>),
 }
 
+// What an ACK for block 0 refers to while reading.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BlockZero {
+    // The OACK the client accepted before DATA block 1.
+    Oack,
+    // Nothing: no OACK was sent.
+    Unused,
+    // A DATA block, once block numbers have wrapped around.
+    Data,
+}
+
 // The TransferState enum represents the different states of a TFTP transfer.
 enum TransferState<H: Handler> {
     Uninitialized,
@@ -87,6 +128,7 @@ enum TransferState<H: Handler> {
         data: Vec<u8>,
         timeouts: u8,
         block_size: u64,
+        block_zero: BlockZero,
     },
     Complete,
 }
@@ -118,7 +160,10 @@ impl<H: Handler + 'static> State<H> {
                     filename,
                     mode,
                     options,
-                } => handle_read_request(self.handler.as_ref(), filename, mode, options).await,
+                } => {
+                    handle_read_request(self.handler.as_ref(), self.addr, filename, mode, options)
+                        .await
+                }
                 _ => self.error(None),
             },
             TransferState::OptionNegotiation {
@@ -130,6 +175,7 @@ impl<H: Handler + 'static> State<H> {
                 Packet::Ack { block: 0 } => {
                     handle_option_ack_with_state(
                         self.handler.as_ref(),
+                        self.addr,
                         filename,
                         mode,
                         negotiated_options,
@@ -155,9 +201,20 @@ impl<H: Handler + 'static> State<H> {
                 data,
                 timeouts,
                 block_size,
+                block_zero,
             } => match packet {
+                Packet::Ack { block: 0 } if *block_zero == BlockZero::Unused => {
+                    debug!("TFTP: Ignoring ACK 0 from {}; no OACK was sent", self.addr);
+                    Ok(HandleResponse {
+                        next_state: None,
+                        response: ControlFlow::Ignore,
+                    })
+                }
                 Packet::Ack { block: acked_block } => {
-                    handle_ack(
+                    if acked_block == 0 && *block_zero == BlockZero::Oack {
+                        debug!("TFTP: Duplicate ACK of OACK from {}", self.addr);
+                    }
+                    let response = handle_ack(
                         reader,
                         mode,
                         block,
@@ -166,7 +223,11 @@ impl<H: Handler + 'static> State<H> {
                         *block_size,
                         acked_block,
                     )
-                    .await
+                    .await;
+                    if *block == 0 {
+                        *block_zero = BlockZero::Data;
+                    }
+                    response
                 }
                 Packet::Error { code, message } => {
                     log::debug!(
@@ -290,6 +351,7 @@ struct HandleResponse<H: Handler> {
 // Negotiate which options are acceptable
 async fn negotiate_options<H: Handler>(
     handler: &H,
+    client: SocketAddr,
     filename: &str,
     options: Vec<TftpOption>,
 ) -> Result<Vec<TftpOption>> {
@@ -297,10 +359,10 @@ async fn negotiate_options<H: Handler>(
 
     for opt in options {
         match opt {
-            TftpOption::TSize(_) => {
-                let filelen = handler.filesize(filename).await?;
-                negotiated_options.push(TftpOption::TSize(filelen));
-            }
+            TftpOption::TSize(_) => match handler.filesize(client, filename).await? {
+                Some(filelen) => negotiated_options.push(TftpOption::TSize(filelen)),
+                None => debug!("TFTP: Omitting tsize for {}; size is not known", filename),
+            },
             TftpOption::BlkSize(size) => {
                 if size >= 10000 {
                     warn!("TFTP: Rejecting blksize over 10,000 ({})", size);
@@ -308,6 +370,12 @@ async fn negotiate_options<H: Handler>(
                     negotiated_options.push(TftpOption::BlkSize(size));
                 }
             }
+            TftpOption::Multicast(_) => {
+                info!(
+                    "TFTP: Declining multicast (MTFTP) for {}; serving unicast only",
+                    filename
+                )
+            }
             TftpOption::Unrecognized(key, _) => warn!("TFTP: Ignoring unrecognized option {}", key),
         }
     }
@@ -320,12 +388,13 @@ async fn negotiate_options<H: Handler>(
 // Otherwise, starts reading immediately.
 async fn handle_read_request<H: Handler>(
     handler: &H,
+    client: SocketAddr,
     filename: String,
     mode: String,
     options: Vec<TftpOption>,
 ) -> Result<HandleResponse<H>> {
     // Negotiate options
-    let negotiated_options = match negotiate_options(handler, &filename, options).await {
+    let negotiated_options = match negotiate_options(handler, client, &filename, options).await {
         Ok(options) => options,
         Err(_) => {
             return Ok(HandleResponse {
@@ -356,7 +425,7 @@ async fn handle_read_request<H: Handler>(
     } else {
         // No options or no options negotiated - start transfer immediately
         // Per RFC 1350, block numbers begin with one
-        let mut reader = handler.create_reader(&filename, 512).await?;
+        let mut reader = handler.create_reader(client, &filename, 512).await?;
         let data = reader.read().await?;
         let next_state = TransferState::Reading {
             filename,
@@ -366,6 +435,7 @@ async fn handle_read_request<H: Handler>(
             data: data.clone(),
             timeouts: 0,
             block_size: 512,
+            block_zero: BlockZero::Unused,
         };
         let reply = Packet::Data { block: 1, data };
         Ok(HandleResponse {
@@ -380,6 +450,7 @@ async fn handle_read_request<H: Handler>(
 // Per RFC 1350, block numbers begin with one, so we send DATA block 1.
 async fn handle_option_ack_with_state<H: Handler>(
     handler: &H,
+    client: SocketAddr,
     filename: &str,
     mode: &str,
     negotiated_options: &Vec<TftpOption>,
@@ -393,7 +464,7 @@ async fn handle_option_ack_with_state<H: Handler>(
     }
 
     // Client acknowledged the options - start sending data at block 1
-    let mut reader = handler.create_reader(filename, block_size).await?;
+    let mut reader = handler.create_reader(client, filename, block_size).await?;
     let data = reader.read().await?;
 
     let next_state = TransferState::Reading {
@@ -404,6 +475,7 @@ async fn handle_option_ack_with_state<H: Handler>(
         data: data.clone(),
         timeouts: 0,
         block_size,
+        block_zero: BlockZero::Oack,
     };
 
     let reply = Packet::Data { block: 1, data };
@@ -413,12 +485,22 @@ async fn handle_option_ack_with_state<H: Handler>(
     })
 }
 
+// How far behind the current block an ACK may be and still count as a stale
+// duplicate rather than a block the transfer never reached. Half the block number
+// space, so the classification survives wraparound.
+const STALE_ACK_WINDOW: u16 = u16::MAX / 2;
+
 // Handles an ACK (Acknowledgment) packet by updating the block number and reading the next data chunk.
 //
 // Per RFC 1350, block numbers begin with one:
 // - Client sends ACK 1 to acknowledge DATA block 1
 // - Server responds with DATA block 2
 // - Client sends ACK 2 to acknowledge DATA block 2, etc.
+//
+// An ACK for the previous block resends the current one. Older ACKs are delayed
+// duplicates and are ignored; answering each of them would multiply traffic (the
+// Sorcerer's Apprentice problem). An ACK for a block not yet sent is an illegal
+// operation and ends the transfer.
 async fn handle_ack<H: Handler>(
     reader: &mut H::Reader,
     _mode: &str,
@@ -428,8 +510,8 @@ async fn handle_ack<H: Handler>(
     block_size: u64,
     acked_block: u16,
 ) -> Result<HandleResponse<H>> {
-    let next_block = block.wrapping_add(1);
-    let data = if acked_block == *block {
+    let behind = block.wrapping_sub(acked_block);
+    let data = if behind == 0 {
         // If the ACK is for the current block, and the current block is less than the negotiated
         // block size, the transfer is complete.
         if data.len() < block_size as usize {
@@ -441,22 +523,31 @@ async fn handle_ack<H: Handler>(
         }
         // Otherwise, read the next block of data.
         *timeouts = 0;
-        *block = next_block;
+        *block = block.wrapping_add(1);
         *data = reader.read().await?;
         data.clone()
-    } else if acked_block == block.wrapping_sub(1) {
+    } else if behind == 1 {
         // If the ACK is for the previous block, resend the current block.
         data.clone()
+    } else if behind <= STALE_ACK_WINDOW {
+        debug!("TFTP: Ignoring stale ACK {acked_block} while sending block {block}");
+        return Ok(HandleResponse {
+            next_state: None,
+            response: ControlFlow::Ignore,
+        });
     } else {
-        // If the ACK is for a block that is not expected, return an error.
-        return Err(anyhow::anyhow!(
-            "Unexpected ACK block number: {}",
-            acked_block
-        ));
+        warn!("TFTP: ACK {acked_block} is for a block not yet sent (current block {block})");
+        return Ok(HandleResponse {
+            next_state: Some(TransferState::Complete),
+            response: ControlFlow::Closed(Some(Packet::Error {
+                code: Error::IllegalOperation,
+                message: format!("ACK for unsent block {acked_block}"),
+            })),
+        });
     };
 
     let reply = Packet::Data {
-        block: acked_block + 1,
+        block: *block,
         data,
     };
     Ok(HandleResponse {
@@ -474,18 +565,39 @@ mod tests {
 
     struct MockHandler {
         data: Vec<u8>,
+        /// Whether `filesize` reports the length, as opposed to a streamed file's `None`.
+        size_known: bool,
+        /// Client address passed to each `create_reader` / `filesize` call, in order.
+        clients: std::sync::Mutex<Vec<SocketAddr>>,
     }
 
     impl MockHandler {
         fn with_data(data: Vec<u8>) -> Self {
-            MockHandler { data }
+            MockHandler {
+                data,
+                size_known: true,
+                clients: Default::default(),
+            }
+        }
+
+        fn streaming(data: Vec<u8>) -> Self {
+            MockHandler {
+                size_known: false,
+                ..Self::with_data(data)
+            }
         }
     }
 
     impl Handler for MockHandler {
         type Reader = MockReader;
 
-        async fn create_reader(&self, _filename: &str, block_size: u64) -> Result<Self::Reader> {
+        async fn create_reader(
+            &self,
+            client: SocketAddr,
+            _filename: &str,
+            block_size: u64,
+        ) -> Result<Self::Reader> {
+            self.clients.lock().unwrap().push(client);
             Ok(MockReader {
                 data: self
                     .data
@@ -496,8 +608,9 @@ mod tests {
             })
         }
 
-        async fn filesize(&self, _filename: &str) -> Result<u64> {
-            Ok(self.data.len() as u64)
+        async fn filesize(&self, client: SocketAddr, _filename: &str) -> Result<Option<u64>> {
+            self.clients.lock().unwrap().push(client);
+            Ok(self.size_known.then_some(self.data.len() as u64))
         }
     }
 
@@ -629,20 +742,142 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_multicast_request_is_declined() {
+        let handler = Arc::new(MockHandler::with_data(vec![0; 100]));
+
+        // Alongside another option: OACK acknowledges only the other option
+        let mut state = State::new(
+            SocketAddr::from_str("127.0.0.1:55").unwrap(),
+            handler.clone(),
+        );
+        let result = state
+            .handle(Packet::Rrq {
+                filename: String::from("test.txt"),
+                mode: String::from("octet"),
+                options: vec![
+                    TftpOption::Multicast(String::new()),
+                    TftpOption::BlkSize(1024),
+                ],
+            })
+            .await;
+        assert!(
+            matches!(result, ControlFlow::Continue(Packet::Oack { ref options }) if options == &vec![TftpOption::BlkSize(1024)]),
+            "Expected OACK without multicast, got {result:?}"
+        );
+
+        // On its own: nothing to acknowledge, so the transfer starts as plain unicast
+        let mut state = State::new(SocketAddr::from_str("127.0.0.1:55").unwrap(), handler);
+        let result = state
+            .handle(Packet::Rrq {
+                filename: String::from("test.txt"),
+                mode: String::from("octet"),
+                options: vec![TftpOption::Multicast(String::new())],
+            })
+            .await;
+        assert!(
+            matches!(result, ControlFlow::Continue(Packet::Data { block: 1, .. })),
+            "Expected DATA block 1, got {result:?}"
+        );
+    }
+
+    /// A transfer that has sent an OACK for `blksize=512` and awaits ACK 0.
+    async fn awaiting_oack_ack(data: Vec<u8>) -> State<MockHandler> {
+        let mut state = State::new(
+            SocketAddr::from_str("127.0.0.1:55").unwrap(),
+            Arc::new(MockHandler::with_data(data)),
+        );
+        let result = state
+            .handle(Packet::Rrq {
+                filename: String::from("test.txt"),
+                mode: String::from("octet"),
+                options: vec![TftpOption::BlkSize(512)],
+            })
+            .await;
+        assert!(
+            matches!(result, ControlFlow::Continue(Packet::Oack { .. })),
+            "Expected OACK, got {result:?}"
+        );
+        state
+    }
+
     #[tokio::test]
     async fn test_option_negotiation_retransmits_oack_on_timeout() {
-        // When in OptionNegotiation state, timeout should retransmit OACK
-        // This test will be relevant once we implement specific options
-        // For now, we can't test this since no options are recognized
-        // TODO: Enable this test when we implement blksize or another option
+        let mut state = awaiting_oack_ack(vec![0; 100]).await;
+        let result = state.handle_timeout().await;
+        assert!(
+            matches!(result, ControlFlow::Continue(Packet::Oack { ref options }) if options == &vec![TftpOption::BlkSize(512)]),
+            "Timeout before ACK 0 should resend the OACK, got {result:?}"
+        );
     }
 
     #[tokio::test]
     async fn test_option_negotiation_closes_after_max_timeouts() {
-        // When in OptionNegotiation state, should close after max timeouts
-        // This test will be relevant once we implement specific options
-        // For now, we can't test this since no options are recognized
-        // TODO: Enable this test when we implement blksize or another option
+        let mut state = awaiting_oack_ack(vec![0; 100]).await;
+        for _ in 1..DEFAULT_MAX_RETRIES {
+            assert!(matches!(
+                state.handle_timeout().await,
+                ControlFlow::Continue(Packet::Oack { .. })
+            ));
+        }
+        assert!(matches!(
+            state.handle_timeout().await,
+            ControlFlow::Closed(None)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_ack_zero_after_oack_is_a_duplicate_acceptance() {
+        let mut state = awaiting_oack_ack(vec![0; 1024]).await;
+
+        let result = state.handle(Packet::Ack { block: 0 }).await;
+        assert!(
+            matches!(result, ControlFlow::Continue(Packet::Data { block: 1, .. })),
+            "ACK 0 of the OACK should start DATA block 1, got {result:?}"
+        );
+
+        // The client repeated its acceptance before DATA 1 arrived: resend it
+        let result = state.handle(Packet::Ack { block: 0 }).await;
+        assert!(
+            matches!(result, ControlFlow::Continue(Packet::Data { block: 1, .. })),
+            "Repeated ACK 0 should resend DATA block 1, got {result:?}"
+        );
+
+        // Later on it is just a stale duplicate
+        state.handle(Packet::Ack { block: 1 }).await;
+        let result = state.handle(Packet::Ack { block: 0 }).await;
+        assert!(
+            matches!(result, ControlFlow::Ignore),
+            "Late ACK 0 should be ignored, got {result:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_ack_zero_without_oack_is_ignored() {
+        let mut state = State::new(
+            SocketAddr::from_str("127.0.0.1:55").unwrap(),
+            Arc::new(MockHandler::with_data(vec![0; 1024])),
+        );
+        state
+            .handle(Packet::Rrq {
+                filename: String::from("test.txt"),
+                mode: String::from("octet"),
+                options: Vec::new(),
+            })
+            .await;
+
+        // No OACK was sent, so ACK 0 acknowledges nothing
+        let result = state.handle(Packet::Ack { block: 0 }).await;
+        assert!(
+            matches!(result, ControlFlow::Ignore),
+            "ACK 0 without an OACK should be ignored, got {result:?}"
+        );
+
+        let result = state.handle(Packet::Ack { block: 1 }).await;
+        assert!(
+            matches!(result, ControlFlow::Continue(Packet::Data { block: 2, .. })),
+            "ACK 1 should be answered with DATA block 2, got {result:?}"
+        );
     }
 
     // Test helper: MockHandlerWithOptions that simulates option support
@@ -785,7 +1020,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_unexpected_ack_returns_error() {
-        // Test that receiving an out-of-sequence ACK causes an error
+        // Test that an ACK for a block not yet sent ends the transfer
         let mut state = State::new(
             SocketAddr::from_str("127.0.0.1:55").unwrap(),
             Arc::new(MockHandler::with_data(vec![0; 1024])),
@@ -803,11 +1038,48 @@ mod tests {
         // Send ACK 5 (out of sequence - we're expecting ACK 1)
         let result = state.handle(Packet::Ack { block: 5 }).await;
         assert!(
-            matches!(result, ControlFlow::Closed(Some(Packet::Error { .. }))),
+            matches!(
+                result,
+                ControlFlow::Closed(Some(Packet::Error {
+                    code: Error::IllegalOperation,
+                    ..
+                }))
+            ),
             "Unexpected ACK should return error, got {result:?}"
         );
     }
 
+    #[tokio::test]
+    async fn test_stale_duplicate_ack_is_ignored() {
+        let mut state = State::new(
+            SocketAddr::from_str("127.0.0.1:55").unwrap(),
+            Arc::new(MockHandler::with_data(vec![0; 2048])),
+        );
+        state
+            .handle(Packet::Rrq {
+                filename: String::from("test.txt"),
+                mode: String::from("octet"),
+                options: Vec::new(),
+            })
+            .await;
+        state.handle(Packet::Ack { block: 1 }).await;
+        state.handle(Packet::Ack { block: 2 }).await;
+
+        // Sending block 3: a late duplicate of ACK 1 gets no answer
+        let result = state.handle(Packet::Ack { block: 1 }).await;
+        assert!(
+            matches!(result, ControlFlow::Ignore),
+            "Stale ACK 1 should be ignored, got {result:?}"
+        );
+
+        // and the transfer carries on
+        let result = state.handle(Packet::Ack { block: 3 }).await;
+        assert!(
+            matches!(result, ControlFlow::Continue(Packet::Data { block: 4, .. })),
+            "ACK 3 should be answered with DATA block 4, got {result:?}"
+        );
+    }
+
     #[tokio::test]
     async fn test_future_option_negotiation_flow() {
         // This test documents the expected flow when we implement option support
@@ -900,4 +1172,69 @@ mod tests {
             "After ACK 2 for final block (576 < 1024), transfer should complete, got {result:?}"
         );
     }
+
+    #[tokio::test]
+    async fn test_handler_receives_client_address() {
+        let client = SocketAddr::from_str("10.0.0.100:2000").unwrap();
+        let handler = Arc::new(MockHandler::with_data(vec![0; 100]));
+        let mut state = State::new(client, handler.clone());
+
+        // tsize makes the handler see the client twice: filesize, then create_reader
+        let result = state
+            .handle(Packet::Rrq {
+                filename: String::from("pxelinux.cfg/0A000064"),
+                mode: String::from("octet"),
+                options: vec![TftpOption::TSize(0)],
+            })
+            .await;
+        assert!(matches!(result, ControlFlow::Continue(Packet::Oack { .. })));
+        state.handle(Packet::Ack { block: 0 }).await;
+
+        assert_eq!(*handler.clients.lock().unwrap(), vec![client, client]);
+    }
+
+    #[tokio::test]
+    async fn test_unknown_size_omits_tsize_from_oack() {
+        let rrq = || Packet::Rrq {
+            filename: String::from("test.txt"),
+            mode: String::from("octet"),
+            options: vec![TftpOption::BlkSize(1024), TftpOption::TSize(0)],
+        };
+        let client = SocketAddr::from_str("127.0.0.1:55").unwrap();
+
+        let mut state = State::new(client, Arc::new(MockHandler::streaming(vec![0; 100])));
+        let result = state.handle(rrq()).await;
+        assert!(
+            matches!(&result, ControlFlow::Continue(Packet::Oack { options }) if *options == vec![TftpOption::BlkSize(1024)]),
+            "Streamed file's OACK should leave out tsize, got {result:?}"
+        );
+
+        let mut state = State::new(client, Arc::new(MockHandler::with_data(vec![0; 100])));
+        let result = state.handle(rrq()).await;
+        assert!(
+            matches!(&result, ControlFlow::Continue(Packet::Oack { options }) if options.contains(&TftpOption::TSize(100))),
+            "Sized file's OACK should carry tsize, got {result:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unknown_size_with_only_tsize_sends_data() {
+        let mut state = State::new(
+            SocketAddr::from_str("127.0.0.1:55").unwrap(),
+            Arc::new(MockHandler::streaming(vec![0; 100])),
+        );
+
+        // Nothing left to acknowledge, so the transfer starts without an OACK.
+        let result = state
+            .handle(Packet::Rrq {
+                filename: String::from("test.txt"),
+                mode: String::from("octet"),
+                options: vec![TftpOption::TSize(0)],
+            })
+            .await;
+        assert!(
+            matches!(result, ControlFlow::Continue(Packet::Data { block: 1, .. })),
+            "Expected DATA block 1, got {result:?}"
+        );
+    }
 }
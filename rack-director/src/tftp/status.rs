@@ -0,0 +1,157 @@
+//! Live registry of in-flight TFTP transfers.
+//!
+//! Each `Connection` registers itself on accept and is removed when it closes, so
+//! the registry always reflects the transfers currently being served. The HTTP API
+//! reads snapshots of it for capacity planning and spotting stuck transfers.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+/// Progress of a single in-flight transfer.
+#[derive(Debug, Clone, Serialize)]
+pub struct TransferStatus {
+    pub client: SocketAddr,
+    pub filename: String,
+    /// Last DATA block sent (retransmits included).
+    pub block: u16,
+    /// Payload bytes sent so far, counting retransmits.
+    pub bytes_sent: u64,
+    pub started_at: DateTime<Utc>,
+}
+
+#[derive(Default)]
+struct Inner {
+    next_id: AtomicU64,
+    total_bytes_sent: AtomicU64,
+    transfers: Mutex<HashMap<u64, TransferStatus>>,
+}
+
+/// Shared, cloneable handle to the set of in-flight transfers.
+#[derive(Clone, Default)]
+pub struct TransferRegistry {
+    inner: Arc<Inner>,
+}
+
+impl TransferRegistry {
+    /// All in-flight transfers, ordered by start time.
+    pub fn snapshot(&self) -> Vec<TransferStatus> {
+        let mut transfers: Vec<TransferStatus> = self
+            .inner
+            .transfers
+            .lock()
+            .unwrap()
+            .values()
+            .cloned()
+            .collect();
+        transfers.sort_by_key(|t| t.started_at);
+        transfers
+    }
+
+    /// Payload bytes sent by every transfer since the server started.
+    pub fn total_bytes_sent(&self) -> u64 {
+        self.inner.total_bytes_sent.load(Ordering::Relaxed)
+    }
+
+    /// Track a new transfer until the returned guard is dropped.
+    pub(crate) fn register(&self, client: SocketAddr, filename: &str) -> TransferGuard {
+        let id = self.inner.next_id.fetch_add(1, Ordering::Relaxed);
+        self.inner.transfers.lock().unwrap().insert(
+            id,
+            TransferStatus {
+                client,
+                filename: filename.to_string(),
+                block: 0,
+                bytes_sent: 0,
+                started_at: Utc::now(),
+            },
+        );
+        TransferGuard {
+            registry: self.clone(),
+            id,
+            filename: filename.to_string(),
+        }
+    }
+}
+
+/// Registration of one transfer; removes it from the registry on drop.
+pub(crate) struct TransferGuard {
+    registry: TransferRegistry,
+    id: u64,
+    filename: String,
+}
+
+impl TransferGuard {
+    /// The file being transferred.
+    pub(crate) fn filename(&self) -> &str {
+        &self.filename
+    }
+
+    /// Record that DATA `block` carrying `bytes` of payload was sent.
+    pub(crate) fn record_block(&self, block: u16, bytes: usize) {
+        let inner = &self.registry.inner;
+        inner
+            .total_bytes_sent
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+        if let Some(status) = inner.transfers.lock().unwrap().get_mut(&self.id) {
+            status.block = block;
+            status.bytes_sent += bytes as u64;
+        }
+    }
+}
+
+impl Drop for TransferGuard {
+    fn drop(&mut self) {
+        self.registry
+            .inner
+            .transfers
+            .lock()
+            .unwrap()
+            .remove(&self.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transfer_registered_until_guard_dropped() {
+        let registry = TransferRegistry::default();
+        let client: SocketAddr = "10.0.0.5:2000".parse().unwrap();
+
+        let guard = registry.register(client, "snponly.efi");
+        guard.record_block(1, 512);
+        guard.record_block(2, 100);
+
+        let transfers = registry.snapshot();
+        assert_eq!(transfers.len(), 1);
+        assert_eq!(transfers[0].client, client);
+        assert_eq!(transfers[0].filename, "snponly.efi");
+        assert_eq!(transfers[0].block, 2);
+        assert_eq!(transfers[0].bytes_sent, 612);
+
+        drop(guard);
+        assert!(registry.snapshot().is_empty());
+        assert_eq!(registry.total_bytes_sent(), 612);
+    }
+
+    #[test]
+    fn test_duplicate_client_transfers_tracked_separately() {
+        let registry = TransferRegistry::default();
+        let client: SocketAddr = "10.0.0.5:2000".parse().unwrap();
+
+        let first = registry.register(client, "a.efi");
+        let second = registry.register(client, "a.efi");
+        assert_eq!(registry.snapshot().len(), 2);
+
+        drop(first);
+        assert_eq!(registry.snapshot().len(), 1);
+        drop(second);
+        assert!(registry.snapshot().is_empty());
+    }
+}
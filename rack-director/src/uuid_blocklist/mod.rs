@@ -0,0 +1,104 @@
+//! Hardware UUIDs the director refuses to manage.
+//!
+//! Test VMs and decommissioned hardware that keeps PXE booting would otherwise be
+//! registered again on every boot and clutter the fleet. A blocked UUID is never
+//! registered or updated by the iPXE handler; it is told to boot its local disk.
+//! Entries come from `POST /api/uuid-blocklist` or `--blocked-uuid` at startup.
+
+use anyhow::Result;
+use chrono::Utc;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::database::{Connection, FromRow, to_db_time};
+
+/// One blocklist entry.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BlockedUuid {
+    pub uuid: Uuid,
+    /// Free-text note on why the UUID is blocked.
+    pub reason: Option<String>,
+    pub created_at: String,
+}
+
+impl FromRow for BlockedUuid {
+    fn from_row(row: &rusqlite::Row) -> rusqlite::Result<Self> {
+        Ok(BlockedUuid {
+            uuid: row.get("uuid")?,
+            reason: row.get("reason")?,
+            created_at: row.get("created_at")?,
+        })
+    }
+}
+
+/// Block `uuid`. Returns `false`, leaving the existing entry untouched, if it was
+/// already blocked.
+pub async fn block_uuid(conn: &Connection, uuid: &Uuid, reason: Option<&str>) -> Result<bool> {
+    let rows_affected = conn
+        .execute(
+            "INSERT OR IGNORE INTO uuid_blocklist (uuid, reason, created_at) VALUES (?1, ?2, ?3)",
+            (*uuid, reason.map(str::to_string), to_db_time(Utc::now())),
+        )
+        .await?;
+    Ok(rows_affected > 0)
+}
+
+/// Unblock `uuid`. Returns `false` if it wasn't blocked.
+pub async fn unblock_uuid(conn: &Connection, uuid: &Uuid) -> Result<bool> {
+    let rows_affected = conn
+        .execute("DELETE FROM uuid_blocklist WHERE uuid = ?1", (*uuid,))
+        .await?;
+    Ok(rows_affected > 0)
+}
+
+/// Whether `uuid` is blocked.
+pub async fn is_blocked(conn: &Connection, uuid: &Uuid) -> Result<bool> {
+    let blocked = conn
+        .query_one(
+            "SELECT EXISTS (SELECT 1 FROM uuid_blocklist WHERE uuid = ?1)",
+            (*uuid,),
+            |row| row.get(0),
+        )
+        .await?;
+    Ok(blocked)
+}
+
+/// Every blocked UUID, oldest entry first.
+pub async fn list_blocked_uuids(conn: &Connection) -> Result<Vec<BlockedUuid>> {
+    let entries = conn
+        .query(
+            "SELECT uuid, reason, created_at FROM uuid_blocklist ORDER BY created_at, uuid",
+            (),
+            BlockedUuid::from_row,
+        )
+        .await?;
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{database, test_connection_factory};
+
+    #[tokio::test]
+    async fn test_block_and_unblock() {
+        let factory = test_connection_factory!();
+        let conn = database::run_migrations(&factory).await.unwrap();
+        let uuid = Uuid::parse_str("b1000000-0000-0000-0000-000000000001").unwrap();
+
+        assert!(!is_blocked(&conn, &uuid).await.unwrap());
+        assert!(block_uuid(&conn, &uuid, Some("test VM")).await.unwrap());
+        assert!(is_blocked(&conn, &uuid).await.unwrap());
+
+        // Blocking again keeps the original reason
+        assert!(!block_uuid(&conn, &uuid, None).await.unwrap());
+        let entries = list_blocked_uuids(&conn).await.unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].uuid, uuid);
+        assert_eq!(entries[0].reason.as_deref(), Some("test VM"));
+
+        assert!(unblock_uuid(&conn, &uuid).await.unwrap());
+        assert!(!unblock_uuid(&conn, &uuid).await.unwrap());
+        assert!(!is_blocked(&conn, &uuid).await.unwrap());
+    }
+}
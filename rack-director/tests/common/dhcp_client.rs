@@ -76,9 +76,10 @@ impl DhcpClient {
     fn add_guid_option(&self, msg: &mut Message) {
         if let Some(guid) = self.guid {
             // DHCP Option 97 - Client Machine Identifier
-            // Format: 1 byte type (0 = GUID), followed by 16 bytes UUID
+            // Format: 1 byte type (0 = GUID), followed by 16 bytes UUID in SMBIOS
+            // (mixed-endian) byte order, as firmware sends it
             let mut option_data = vec![0u8]; // Type byte = 0 (GUID)
-            option_data.extend_from_slice(guid.as_bytes());
+            option_data.extend_from_slice(&guid.to_bytes_le());
 
             msg.opts_mut()
                 .insert(DhcpOption::Unknown(UnknownOption::new(
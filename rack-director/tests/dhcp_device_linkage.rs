@@ -0,0 +1,96 @@
+mod common;
+
+use anyhow::Result;
+use common::dhcp_client::{Architecture, DhcpClient};
+use uuid::Uuid;
+
+/// A second NIC of a known device identifies itself by option 97 alone.
+/// Expected flow: DISCOVER/REQUEST with option 97 → device resolved by GUID →
+/// ACK → interface recorded on that device and the lease linked to it
+#[tokio::test]
+async fn test_option_97_links_interface_to_device() -> Result<()> {
+    let handle = common::start_rack_director().await?;
+    let http_port = handle.handle.http_port;
+    let dhcp_port = handle.handle.dhcp_port;
+
+    let network_id = common::create_test_network(http_port).await?;
+    common::create_test_pool(http_port, network_id).await?;
+    handle
+        .set_network_autodiscover(network_id as u16, true)
+        .await?;
+
+    // Register the device through its first NIC
+    let uuid = Uuid::parse_str("4c4c4544-0042-3510-8052-b4c04f4d3232")?;
+    let first_mac = [0x52, 0x54, 0x00, 0xCC, 0x00, 0x01];
+    common::register_test_device(http_port, dhcp_port, first_mac, uuid).await?;
+
+    // A NIC the director has never seen, sending only the device's GUID
+    let second_mac = [0x52, 0x54, 0x00, 0xCC, 0x00, 0x02];
+    let (offered_ip, leased_ip) = tokio::task::spawn_blocking(move || -> Result<_> {
+        let mut dhcp_client = DhcpClient::new(second_mac, Architecture::X86UefiHttp, dhcp_port)?;
+        dhcp_client.set_guid(uuid);
+        let (offered_ip, server_id) = dhcp_client.discover()?;
+        let (leased_ip, _boot_options) = dhcp_client.request(offered_ip, server_id)?;
+        Ok((offered_ip, leased_ip))
+    })
+    .await??;
+    assert_eq!(offered_ip, leased_ip, "Leased IP should match offered IP");
+
+    let client = reqwest::Client::new();
+
+    // VALIDATION: The device is known under the GUID it sent
+    let device: serde_json::Value = client
+        .get(format!(
+            "http://127.0.0.1:{}/ui/devices/{}",
+            http_port, uuid
+        ))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    assert_eq!(device["uuid"], uuid.to_string());
+
+    // VALIDATION: The second NIC is recorded on the device with its leased address
+    let interfaces = device["attributes"]["network_interfaces"]
+        .as_array()
+        .expect("device should have network interfaces");
+    let second_mac_str = "52:54:00:cc:00:02";
+    let interface = interfaces
+        .iter()
+        .find(|i| i["mac_address"] == second_mac_str)
+        .unwrap_or_else(|| panic!("no interface for {second_mac_str} in {interfaces:?}"));
+    assert_eq!(interface["ip_address"], leased_ip.to_string());
+    assert!(
+        interfaces
+            .iter()
+            .any(|i| i["mac_address"] == "52:54:00:cc:00:01"),
+        "the first NIC should still be recorded"
+    );
+
+    // VALIDATION: The lease is linked to the same device
+    let lease: serde_json::Value = client
+        .get(format!(
+            "http://127.0.0.1:{}/ui/dhcp/leases/{}",
+            http_port, second_mac_str
+        ))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    assert_eq!(lease["device_uuid"], uuid.to_string());
+    assert_eq!(lease["ip_address"], leased_ip.to_string());
+
+    // VALIDATION: No separate device was created for the second NIC
+    let devices: serde_json::Value = client
+        .get(format!("http://127.0.0.1:{}/ui/devices", http_port))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    assert_eq!(devices["devices"].as_array().map(Vec::len), Some(1));
+
+    Ok(())
+}
@@ -0,0 +1,80 @@
+mod common;
+
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use anyhow::Result;
+use common::dhcp_client::{Architecture, DhcpClient};
+
+/// Log target of the per-option dump of received DHCP packets.
+const OPTION_DUMP_TARGET: &str = "rack_director::dhcp::options";
+
+/// Captures every record handed to it, whether or not it reported the record as
+/// enabled, so a test can tell whether a disabled dump was still emitted.
+struct CaptureLogger {
+    dump_enabled: AtomicBool,
+    dumps: Mutex<Vec<String>>,
+}
+
+impl log::Log for CaptureLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.target() == OPTION_DUMP_TARGET && self.dump_enabled.load(Ordering::SeqCst)
+    }
+
+    fn log(&self, record: &log::Record) {
+        if record.target() == OPTION_DUMP_TARGET {
+            self.dumps.lock().unwrap().push(record.args().to_string());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: CaptureLogger = CaptureLogger {
+    dump_enabled: AtomicBool::new(false),
+    dumps: Mutex::new(Vec::new()),
+};
+
+async fn discover(dhcp_port: u16, mac: [u8; 6]) -> Result<()> {
+    tokio::task::spawn_blocking(move || -> Result<()> {
+        let mut dhcp_client = DhcpClient::new(mac, Architecture::X86Bios, dhcp_port)?;
+        dhcp_client.discover()?;
+        Ok(())
+    })
+    .await?
+}
+
+/// Received packets' options are dumped at trace level on their own target, and
+/// only while that target is enabled
+#[tokio::test]
+async fn test_option_dump_follows_trace_level() -> Result<()> {
+    // Installed before the director so its env_logger setup is a no-op
+    log::set_logger(&LOGGER).unwrap();
+    log::set_max_level(log::LevelFilter::Trace);
+
+    let handle = common::start_rack_director().await?;
+    let network_id = common::create_test_network(handle.handle.http_port).await?;
+    common::create_test_pool(handle.handle.http_port, network_id).await?;
+    handle
+        .set_network_autodiscover(network_id as u16, true)
+        .await?;
+    let dhcp_port = handle.handle.dhcp_port;
+
+    discover(dhcp_port, [0x52, 0x54, 0x00, 0xDD, 0x00, 0x01]).await?;
+    assert!(
+        LOGGER.dumps.lock().unwrap().is_empty(),
+        "no dump is emitted while the target is disabled"
+    );
+
+    LOGGER.dump_enabled.store(true, Ordering::SeqCst);
+    discover(dhcp_port, [0x52, 0x54, 0x00, 0xDD, 0x00, 0x02]).await?;
+
+    let dumps = LOGGER.dumps.lock().unwrap();
+    assert_eq!(dumps.len(), 1, "one dump per received packet: {:?}", dumps);
+    let dump = &dumps[0];
+    assert!(dump.contains("52:54:00:dd:00:02"), "{}", dump);
+    assert!(dump.contains(" 53 MessageType: Discover"), "{}", dump);
+    assert!(dump.contains(" 93 ClientSystemArchitecture: "), "{}", dump);
+
+    Ok(())
+}